@@ -0,0 +1,11 @@
+pub mod client;
+pub mod dns;
+pub mod error;
+pub mod ip_check;
+pub mod policy;
+pub mod rate_limit;
+pub mod url_check;
+
+pub use client::{FetchRequest, FetchResponse, Meter, SafeClient};
+pub use error::FetchError;
+pub use policy::{DomainPattern, FetchPolicy};
@@ -1,6 +1,7 @@
 import test from 'ava';
 import * as fs from 'node:fs';
 import * as path from 'node:path';
+import { Sandbox } from '../index.js';
 import { createTempDir, createSandbox, cleanup } from './helpers.js';
 
 test('exec runs cat command', async (t) => {
@@ -271,3 +272,23 @@ test('exec runs env command', async (t) => {
 
   cleanup(tmpDir);
 });
+
+test('exec returns a trace of shell commands when enabled', async (t) => {
+  const tmpDir = createTempDir();
+  const sandbox = new Sandbox({ workDir: tmpDir, traceCommands: true });
+  const result = await sandbox.exec('sh', ['-c', 'cd /work; seq 3 > n.txt; cat n.txt | wc -l']);
+  t.is(result.stdout.toString().trim(), '3');
+  t.deepEqual(
+    result.trace.map((c) => [c.argv, c.cwd, c.exitCode, c.redirections]),
+    [
+      [['seq', '3'], '/work', 0, ['>n.txt']],
+      [['cat', 'n.txt'], '/work', 0, []],
+      [['wc', '-l'], '/work', 0, []],
+    ],
+  );
+  t.true(result.trace.every((c) => c.fuel > 0 && c.durationMs >= 0));
+
+  const untraced = await createSandbox(tmpDir).sandbox.exec('sh', ['-c', 'echo hi | cat']);
+  t.deepEqual(untraced.trace, []);
+  cleanup(tmpDir);
+});
@@ -129,7 +129,7 @@ test('curl: basic GET via exec', async (t) => {
   const result = await sandbox.exec('curl', ['https://example.com']);
   t.is(result.exitCode, 0);
   t.true(result.stdout.toString().includes('Example Domain'));
-  t.true(result.stderr.toString().includes('HTTP 200'));
+  t.is(result.stderr.toString(), '');
 
   cleanup(tmpDir);
 });
@@ -217,7 +217,7 @@ test('execJs fetch: basic GET returns status 200', async (t) => {
   const { tmpDir, sandbox } = createFetchSandbox();
 
   const result = await sandbox.execJs(
-    "var r = fetch('https://example.com'); console.log(r.status)",
+    "var r = await fetch('https://example.com'); console.log(r.status)",
   );
   t.is(result.exitCode, 0);
   t.true(result.stdout.toString().includes('200'));
@@ -229,7 +229,7 @@ test('execJs fetch: response body contains content', async (t) => {
   const { tmpDir, sandbox } = createFetchSandbox();
 
   const result = await sandbox.execJs(
-    "var r = fetch('https://example.com'); console.log(r.body.indexOf('Example Domain') >= 0)",
+    "var r = await fetch('https://example.com'); var body = await r.text(); console.log(body.indexOf('Example Domain') >= 0)",
   );
   t.is(result.exitCode, 0);
   t.true(result.stdout.toString().includes('true'));
@@ -241,7 +241,7 @@ test('execJs fetch: ok property is true for 200', async (t) => {
   const { tmpDir, sandbox } = createFetchSandbox();
 
   const result = await sandbox.execJs(
-    "var r = fetch('https://example.com'); console.log(r.ok)",
+    "var r = await fetch('https://example.com'); console.log(r.ok)",
   );
   t.is(result.exitCode, 0);
   t.true(result.stdout.toString().includes('true'));
@@ -249,11 +249,11 @@ test('execJs fetch: ok property is true for 200', async (t) => {
   cleanup(tmpDir);
 });
 
-test('execJs fetch: text() method returns body string', async (t) => {
+test('execJs fetch: text() method resolves to body string', async (t) => {
   const { tmpDir, sandbox } = createFetchSandbox();
 
   const result = await sandbox.execJs(
-    "var r = fetch('https://example.com'); console.log(r.text().indexOf('Example') >= 0)",
+    "fetch('https://example.com').then(r => r.text()).then(text => console.log(text.indexOf('Example') >= 0))",
   );
   t.is(result.exitCode, 0);
   t.true(result.stdout.toString().includes('true'));
@@ -265,7 +265,7 @@ test('execJs fetch: POST with options', async (t) => {
   const { tmpDir, sandbox } = createFetchSandbox();
 
   const result = await sandbox.execJs(
-    `var r = fetch('https://httpbin.org/post', {
+    `var r = await fetch('https://httpbin.org/post', {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       body: '{"key":"value"}'
@@ -282,7 +282,7 @@ test('execJs fetch: throws error when networking is disabled', async (t) => {
   const { tmpDir, sandbox } = createSandbox();
 
   const result = await sandbox.execJs(
-    "try { fetch('https://example.com'); } catch(e) { console.log('caught: ' + e.message); }",
+    "try { await fetch('https://example.com'); } catch(e) { console.log('caught: ' + e.message); }",
   );
   const output = result.stdout.toString() + result.stderr.toString();
   t.true(
@@ -297,10 +297,10 @@ test('execJs fetch: headers object is accessible', async (t) => {
   const { tmpDir, sandbox } = createFetchSandbox();
 
   const result = await sandbox.execJs(
-    "var r = fetch('https://example.com'); console.log(typeof r.headers)",
+    "var r = await fetch('https://example.com'); console.log(typeof r.headers.get)",
   );
   t.is(result.exitCode, 0);
-  t.true(result.stdout.toString().includes('object'));
+  t.true(result.stdout.toString().includes('function'));
 
   cleanup(tmpDir);
 });
@@ -309,10 +309,10 @@ test('execJs fetch: full workflow - fetch, check status, read body', async (t) =
   const { tmpDir, sandbox } = createFetchSandbox();
 
   const result = await sandbox.execJs(`
-    var response = fetch('https://example.com');
+    var response = await fetch('https://example.com');
     var status = response.status;
     var ok = response.ok;
-    var body = response.text();
+    var body = await response.text();
     var hasContent = body.indexOf('Example Domain') >= 0;
     console.log(status + '|' + ok + '|' + hasContent);
   `);
@@ -321,3 +321,40 @@ test('execJs fetch: full workflow - fetch, check status, read body', async (t) =
 
   cleanup(tmpDir);
 });
+
+test('execJs fetch: returns a Promise usable with Promise.all', async (t) => {
+  const { tmpDir, sandbox } = createFetchSandbox();
+
+  const result = await sandbox.execJs(`
+    const p = fetch('https://example.com');
+    console.log(p instanceof Promise);
+    const [a, b] = await Promise.all([p, fetch('https://example.com')]);
+    console.log(a.status, b.status);
+  `);
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString(), 'true\n200 200\n');
+
+  cleanup(tmpDir);
+});
+
+test('networkQuota: request budget is shared by fetch and curl', async (t) => {
+  const tmpDir = createTempDir();
+  const sandbox = new Sandbox({
+    workDir: tmpDir,
+    fetchPolicy: { denyPrivateIps: true },
+    networkQuota: { maxRequests: 1 },
+  });
+
+  const res = await sandbox.fetch({ url: 'https://example.com' });
+  t.is(res.status, 200);
+
+  const result = await sandbox.exec('curl', ['https://example.com']);
+  t.not(result.exitCode, 0);
+  t.true(result.stderr.toString().includes('network quota exceeded'));
+
+  const usage = sandbox.networkUsage();
+  t.is(usage.requests, 1);
+  t.true(usage.bytesDownloaded > 0);
+
+  cleanup(tmpDir);
+});
@@ -0,0 +1,78 @@
+import test from 'ava';
+import * as fs from 'node:fs';
+import * as path from 'node:path';
+import { createSandbox, cleanup } from './helpers.js';
+
+const ORDERS = '[{"name":"ada","qty":2,"price":1.5},{"name":"bob","qty":1,"price":4}]';
+
+test('jq builds objects with arithmetic', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  fs.writeFileSync(path.join(tmpDir, 'orders.json'), ORDERS);
+  const result = await sandbox.exec('jq', ['-c', 'map({name, total: (.qty * .price)})', '/work/orders.json']);
+  t.is(result.exitCode, 0);
+  t.deepEqual(JSON.parse(result.stdout.toString()), [
+    { name: 'ada', total: 3 },
+    { name: 'bob', total: 4 },
+  ]);
+  cleanup(tmpDir);
+});
+
+test('jq supports reduce and string interpolation', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  fs.writeFileSync(path.join(tmpDir, 'orders.json'), ORDERS);
+  const result = await sandbox.exec('jq', [
+    '-r',
+    'reduce .[] as $o (0; . + $o.qty) | "\\(.) items"',
+    '/work/orders.json',
+  ]);
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString(), '3 items\n');
+  cleanup(tmpDir);
+});
+
+test('jq takes --arg and --argjson with -n', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  const result = await sandbox.exec('jq', [
+    '-n',
+    '-c',
+    '--arg',
+    'who',
+    'ada',
+    '--argjson',
+    'n',
+    '3',
+    '{who: $who, next: ($n + 1)}',
+  ]);
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString(), '{"who":"ada","next":4}\n');
+  cleanup(tmpDir);
+});
+
+test('jq formats rows as CSV and TSV', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  fs.writeFileSync(path.join(tmpDir, 'orders.json'), ORDERS);
+  const result = await sandbox.exec('jq', ['-r', '.[] | [.name, .qty] | @csv, @tsv', '/work/orders.json']);
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString(), '"ada",2\nada\t2\n"bob",1\nbob\t1\n');
+  cleanup(tmpDir);
+});
+
+test('jq slurps piped input and catches errors', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  const result = await sandbox.exec('sh', [
+    '-c',
+    `printf '1\\n2\\n3\\n' | jq -s -c '[add / length, (try error("boom") catch .)]'`,
+  ]);
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString(), '[2,"boom"]\n');
+  cleanup(tmpDir);
+});
+
+test('jq -e exits 1 for a false result', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  fs.writeFileSync(path.join(tmpDir, 'flag.json'), '{"a": false}');
+  const result = await sandbox.exec('jq', ['-e', '.a', '/work/flag.json']);
+  t.is(result.exitCode, 1);
+  t.is(result.stdout.toString(), 'false\n');
+  cleanup(tmpDir);
+});
@@ -125,3 +125,92 @@ test('execJs returns error for invalid code', async (t) => {
   t.true(result.stderr.toString().includes('test error'));
   cleanup(tmpDir);
 });
+
+test('execJs runs timers and top-level await in order', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  const result = await sandbox.execJs(`
+    setTimeout(() => console.log('timer'), 10);
+    Promise.resolve().then(() => console.log('microtask'));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    console.log('done');
+  `);
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString(), 'microtask\ntimer\ndone\n');
+  cleanup(tmpDir);
+});
+
+test('execJs can use fs, path and process', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  fs.writeFileSync(path.join(tmpDir, 'in.json'), JSON.stringify({ items: [1, 2, 3] }));
+  const result = await sandbox.execJs(`
+    const fs = require('fs');
+    const path = require('path');
+    const data = JSON.parse(fs.readFileSync(path.join('/work', 'in.json'), 'utf8'));
+    fs.writeFileSync('/work/out.txt', String(data.items.length));
+    process.exitCode = 2;
+  `);
+  t.is(result.exitCode, 2);
+  t.is(fs.readFileSync(path.join(tmpDir, 'out.txt'), 'utf-8'), '3');
+  cleanup(tmpDir);
+});
+
+test('node runs multi-file ES module projects', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  fs.mkdirSync(path.join(tmpDir, 'lib'));
+  fs.writeFileSync(path.join(tmpDir, 'lib', 'greet.mjs'), 'export const greet = (n) => `hi ${n}`;\n');
+  fs.writeFileSync(path.join(tmpDir, 'lib', 'count.cjs'), 'module.exports = (xs) => xs.length;\n');
+  fs.writeFileSync(
+    path.join(tmpDir, 'main.mjs'),
+    "import { greet } from './lib/greet.mjs';\nimport count from './lib/count.cjs';\nconsole.log(greet('there'), count([1, 2]));\n",
+  );
+  const result = await sandbox.exec('node', ['/work/main.mjs']);
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString().trim(), 'hi there 2');
+  cleanup(tmpDir);
+});
+
+test('execTs strips types before running', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  const result = await sandbox.execTs(`
+    enum Level { Low = 1, High }
+    const levels: Level[] = [Level.Low, Level.High];
+    console.log(levels.map((l): string => Level[l]).join(' '));
+  `);
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString().trim(), 'Low High');
+  cleanup(tmpDir);
+});
+
+test('evalJs returns values and rejects with thrown errors', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  const value = await sandbox.evalJs('const total = await Promise.resolve(input.a + input.b);\n({ total })', { a: 2, b: 3 });
+  t.deepEqual(value, { total: 5 });
+  const err = await t.throwsAsync(sandbox.evalJs("throw new TypeError('nope')"));
+  t.true(err!.message.startsWith('TypeError: nope'));
+  cleanup(tmpDir);
+});
+
+test('parseShell returns the syntax tree and rejects syntax errors', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  const ast = await sandbox.parseShell('echo $(whoami) | wc -c');
+  const pipeline = ast.commands[0].first.commands;
+  t.is(pipeline.length, 2);
+  t.deepEqual(pipeline[0].simple.words[0], { parts: [{ literal: 'echo' }] });
+  t.is(pipeline[0].simple.words[1].parts[0].command_sub.script, 'whoami');
+  const err = await t.throwsAsync(sandbox.parseShell('if true; then echo'));
+  t.true(err!.message.startsWith('shell syntax error:'));
+  cleanup(tmpDir);
+});
+
+test('registered host functions are callable from JS and the shell', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  sandbox.registerHostFunction('double', async (args) => args.n * 2);
+  sandbox.registerHostFunction('fail', () => {
+    throw new Error('not allowed');
+  });
+  t.is(await sandbox.evalJs("await host.call('double', { n: 21 })"), 42);
+  t.is(await sandbox.evalJs("host.call('fail').catch((e) => e.message)"), 'not allowed');
+  const result = await sandbox.exec('sh', ['-c', `hostcall double '{"n": 5}'`]);
+  t.is(result.stdout.toString(), '10\n');
+  cleanup(tmpDir);
+});
@@ -0,0 +1,60 @@
+import test from 'ava';
+import * as fs from 'node:fs';
+import * as path from 'node:path';
+import { createSandbox, cleanup } from './helpers.js';
+
+test('python3 --version returns version string', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  const result = await sandbox.exec('python3', ['--version']);
+  t.is(result.exitCode, 0);
+  t.true(result.stdout.toString().startsWith('Python 3.'));
+  cleanup(tmpDir);
+});
+
+test('execPython convenience method works', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  const result = await sandbox.execPython("print('execPython works', 6 * 7)");
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString(), 'execPython works 42\n');
+  cleanup(tmpDir);
+});
+
+test('python runs a script that imports a sibling module', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  fs.writeFileSync(path.join(tmpDir, 'helpers.py'), "def shout(text):\n    return text.upper() + '!'\n");
+  fs.writeFileSync(
+    path.join(tmpDir, 'main.py'),
+    'import sys\nfrom helpers import shout\nfor arg in sys.argv[1:]:\n    print(shout(arg))\n',
+  );
+  const result = await sandbox.exec('python', ['/work/main.py', 'hello', 'world']);
+  t.is(result.exitCode, 0);
+  t.is(result.stdout.toString(), 'HELLO!\nWORLD!\n');
+  cleanup(tmpDir);
+});
+
+test('execPython reads JSON and CSV from the work directory', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  fs.writeFileSync(path.join(tmpDir, 'orders.csv'), 'customer,amount\nada,3.5\nbob,2\nada,1.5\n');
+  const result = await sandbox.execPython(`
+import csv, json
+from collections import defaultdict
+totals = defaultdict(float)
+with open('/work/orders.csv', newline='') as f:
+    for row in csv.DictReader(f):
+        totals[row['customer']] += float(row['amount'])
+print(json.dumps(totals, sort_keys=True))
+`);
+  t.is(result.exitCode, 0);
+  t.deepEqual(JSON.parse(result.stdout.toString()), { ada: 5, bob: 2 });
+  cleanup(tmpDir);
+});
+
+test('execPython reports uncaught exceptions with a traceback', async (t) => {
+  const { tmpDir, sandbox } = createSandbox();
+  const result = await sandbox.execPython("raise ValueError('test error')");
+  t.is(result.exitCode, 1);
+  const stderr = result.stderr.toString();
+  t.true(stderr.startsWith('Traceback (most recent call last):'));
+  t.true(stderr.includes('ValueError: test error'));
+  cleanup(tmpDir);
+});
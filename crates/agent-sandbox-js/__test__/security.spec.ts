@@ -177,3 +177,25 @@ test('security: node cannot access host filesystem', async (t) => {
   t.not(result.exitCode, 0);
   cleanup(tmpDir);
 });
+
+test('security: command policy applies inside shell scripts', async (t) => {
+  const tmpDir = createTempDir();
+  const sandbox = new Sandbox({
+    workDir: tmpDir,
+    commandPolicy: { argumentRules: [{ command: 'rm', blockedOptions: ['-r'] }] },
+  });
+  fs.mkdirSync(path.join(tmpDir, 'src'));
+  fs.writeFileSync(path.join(tmpDir, 'src', 'main.rs'), 'fn main() {}');
+
+  await t.throwsAsync(sandbox.exec('rm', ['-rf', '/work/src']), { message: /command denied: rm: option -r is blocked/ });
+  const result = await sandbox.exec('sh', ['-c', 'rm -fr /work/src']);
+  t.is(result.exitCode, 126);
+  t.true(result.stderr.toString().includes('rm: command denied: option -r is blocked'));
+  t.true(fs.existsSync(path.join(tmpDir, 'src', 'main.rs')));
+  t.deepEqual(
+    sandbox.deniedCommands().map((d) => d.args),
+    [['-rf', '/work/src'], ['-fr', '/work/src']],
+  );
+
+  cleanup(tmpDir);
+});
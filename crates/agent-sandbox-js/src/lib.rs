@@ -1,12 +1,42 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
-use agent_sandbox::config::{MountPoint as RustMountPoint, SandboxConfig as RustSandboxConfig};
+use agent_sandbox::config::{
+    ArgumentRule as RustArgumentRule, CommandPolicy as RustCommandPolicy,
+    MountPoint as RustMountPoint, NetworkQuota as RustNetworkQuota,
+    SandboxConfig as RustSandboxConfig,
+};
 use agent_sandbox::fs::overlay::FsChangeKind;
 use napi::bindgen_prelude::*;
+use napi::check_status;
+use napi::threadsafe_function::ThreadsafeFunction;
 use napi_derive::napi;
 
+/// JS host function: called with the JSON arguments, returning a value or a
+/// Promise. Weak, so a registered function does not keep Node.js running.
+type HostCallback =
+    ThreadsafeFunction<serde_json::Value, HostReturn, serde_json::Value, Status, false, true>;
+
+/// What a JS host function returned.
+pub enum HostReturn {
+    Pending(Promise<serde_json::Value>),
+    Ready(serde_json::Value),
+}
+
+impl FromNapiValue for HostReturn {
+    unsafe fn from_napi_value(env: sys::napi_env, value: sys::napi_value) -> Result<Self> {
+        let mut is_promise = false;
+        check_status!(unsafe { sys::napi_is_promise(env, value, &mut is_promise) })?;
+        if is_promise {
+            unsafe { Promise::from_napi_value(env, value) }.map(HostReturn::Pending)
+        } else {
+            unsafe { serde_json::Value::from_napi_value(env, value) }.map(HostReturn::Ready)
+        }
+    }
+}
+
 #[napi(object)]
 pub struct SandboxOptions {
     pub work_dir: String,
@@ -16,6 +46,9 @@ pub struct SandboxOptions {
     pub memory_limit_bytes: Option<f64>,
     pub fuel_limit: Option<f64>,
     pub fetch_policy: Option<FetchPolicyOption>,
+    pub network_quota: Option<NetworkQuotaOption>,
+    pub command_policy: Option<CommandPolicyOption>,
+    pub trace_commands: Option<bool>,
 }
 
 #[napi(object)]
@@ -37,11 +70,81 @@ pub struct FetchPolicyOption {
     pub max_response_body_bytes: Option<f64>,
 }
 
+#[napi(object)]
+pub struct NetworkQuotaOption {
+    pub max_requests: Option<f64>,
+    pub max_bytes_downloaded: Option<f64>,
+    pub max_bytes_uploaded: Option<f64>,
+    pub max_requests_per_domain: Option<f64>,
+}
+
+#[napi(object)]
+pub struct CommandPolicyOption {
+    pub allowed_commands: Option<Vec<String>>,
+    pub blocked_commands: Option<Vec<String>>,
+    pub argument_rules: Option<Vec<ArgumentRuleOption>>,
+}
+
+#[napi(object)]
+pub struct ArgumentRuleOption {
+    pub command: String,
+    pub blocked_options: Option<Vec<String>>,
+    pub blocked_args: Option<Vec<String>>,
+}
+
+#[napi(object)]
+pub struct DeniedCommand {
+    pub command: String,
+    pub args: Vec<String>,
+    pub reason: String,
+}
+
+#[napi(object)]
+pub struct NetworkUsage {
+    pub requests: f64,
+    pub bytes_downloaded: f64,
+    pub bytes_uploaded: f64,
+    pub requests_per_domain: HashMap<String, f64>,
+}
+
 #[napi(object)]
 pub struct ExecResult {
     pub exit_code: i32,
     pub stdout: Buffer,
     pub stderr: Buffer,
+    pub trace: Vec<TracedCommand>,
+}
+
+impl From<agent_sandbox::runtime::ExecResult> for ExecResult {
+    fn from(result: agent_sandbox::runtime::ExecResult) -> Self {
+        ExecResult {
+            exit_code: result.exit_code,
+            stdout: Buffer::from(result.stdout),
+            stderr: Buffer::from(result.stderr),
+            trace: result
+                .trace
+                .into_iter()
+                .map(|command| TracedCommand {
+                    argv: command.argv,
+                    cwd: command.cwd,
+                    exit_code: command.exit_code,
+                    duration_ms: command.duration.as_secs_f64() * 1000.0,
+                    fuel: command.fuel as f64,
+                    redirections: command.redirections,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[napi(object)]
+pub struct TracedCommand {
+    pub argv: Vec<String>,
+    pub cwd: String,
+    pub exit_code: i32,
+    pub duration_ms: f64,
+    pub fuel: f64,
+    pub redirections: Vec<String>,
 }
 
 #[napi(object)]
@@ -147,6 +250,33 @@ impl Sandbox {
                 .unwrap_or(512.0 * 1024.0 * 1024.0) as u64,
             fuel_limit: options.fuel_limit.unwrap_or(1_000_000_000.0) as u64,
             fetch_policy,
+            network_quota: options
+                .network_quota
+                .map(|q| RustNetworkQuota {
+                    max_requests: q.max_requests.map(|n| n as u64),
+                    max_bytes_downloaded: q.max_bytes_downloaded.map(|n| n as u64),
+                    max_bytes_uploaded: q.max_bytes_uploaded.map(|n| n as u64),
+                    max_requests_per_domain: q.max_requests_per_domain.map(|n| n as u64),
+                })
+                .unwrap_or_default(),
+            command_policy: options
+                .command_policy
+                .map(|p| RustCommandPolicy {
+                    allowed_commands: p.allowed_commands,
+                    blocked_commands: p.blocked_commands.unwrap_or_default(),
+                    argument_rules: p
+                        .argument_rules
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|rule| RustArgumentRule {
+                            command: rule.command,
+                            blocked_options: rule.blocked_options.unwrap_or_default(),
+                            blocked_args: rule.blocked_args.unwrap_or_default(),
+                        })
+                        .collect(),
+                })
+                .unwrap_or_default(),
+            trace_commands: options.trace_commands.unwrap_or(false),
         };
 
         let inner =
@@ -163,11 +293,7 @@ impl Sandbox {
             .await
             .map_err(|e| Error::from_reason(e.to_string()))?;
 
-        Ok(ExecResult {
-            exit_code: result.exit_code,
-            stdout: Buffer::from(result.stdout),
-            stderr: Buffer::from(result.stderr),
-        })
+        Ok(result.into())
     }
 
     /// Execute JavaScript code inside the sandbox using the built-in JS engine.
@@ -179,13 +305,93 @@ impl Sandbox {
             .await
             .map_err(|e| Error::from_reason(e.to_string()))?;
 
-        Ok(ExecResult {
-            exit_code: result.exit_code,
-            stdout: Buffer::from(result.stdout),
-            stderr: Buffer::from(result.stderr),
+        Ok(result.into())
+    }
+
+    /// Execute TypeScript code inside the sandbox (types are stripped before it runs).
+    #[napi]
+    pub async fn exec_ts(&self, code: String) -> Result<ExecResult> {
+        let result = self
+            .inner
+            .exec_ts(&code)
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+
+        Ok(result.into())
+    }
+
+    /// Execute Python code inside the sandbox using the built-in interpreter.
+    #[napi]
+    pub async fn exec_python(&self, code: String) -> Result<ExecResult> {
+        let result = self
+            .inner
+            .exec_python(&code)
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+
+        Ok(result.into())
+    }
+
+    /// Evaluate JavaScript inside the sandbox and return its value as JSON.
+    ///
+    /// `input` is available to the code as the global `input`. A thrown
+    /// exception rejects with `"<name>: <message>"`, followed by its stack
+    /// when one is known.
+    #[napi]
+    pub async fn eval_js(
+        &self,
+        code: String,
+        input: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let input = input.unwrap_or(serde_json::Value::Null);
+        self.inner.eval_js(&code, input).await.map_err(|e| match e {
+            agent_sandbox::error::SandboxError::Js(err) => match &err.stack {
+                Some(stack) => Error::from_reason(format!("{err}\n{stack}")),
+                None => Error::from_reason(err.to_string()),
+            },
+            e => Error::from_reason(e.to_string()),
         })
     }
 
+    /// Parse a shell script without running it and return its syntax tree.
+    ///
+    /// Command and process substitutions include the program they run. A
+    /// script that does not parse rejects with `"shell syntax error: ..."`.
+    #[napi]
+    pub async fn parse_shell(&self, script: String) -> Result<serde_json::Value> {
+        self.inner
+            .parse_shell(&script)
+            .await
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Register a host function that sandboxed code can call by name: as
+    /// `await host.call(name, args)` in JavaScript, or `hostcall NAME [json]`
+    /// in the shell.
+    ///
+    /// `callback` receives the JSON arguments and returns a JSON value or a
+    /// Promise of one; a thrown error or rejection fails the guest's call.
+    #[napi(ts_args_type = "name: string, callback: (args: any) => any")]
+    pub fn register_host_function(&self, name: String, callback: HostCallback) {
+        let callback = Arc::new(callback);
+        self.inner.register_host_function(name, move |args| {
+            let callback = callback.clone();
+            async move {
+                match callback.call_async(args).await {
+                    Ok(HostReturn::Pending(promise)) => promise.await.map_err(|e| e.reason),
+                    Ok(HostReturn::Ready(value)) => Ok(value),
+                    Err(e) => Err(e.reason),
+                }
+            }
+        });
+    }
+
+    /// Remove a host function; returns whether it was registered.
+    #[napi]
+    pub fn unregister_host_function(&self, name: String) -> bool {
+        self.inner.unregister_host_function(&name)
+    }
+
     /// Perform an HTTP fetch using the sandbox's safe client.
     #[napi]
     pub async fn fetch(&self, options: FetchOptions) -> Result<FetchResult> {
@@ -209,6 +415,36 @@ impl Sandbox {
         })
     }
 
+    /// Network usage so far, counted against the sandbox's network quota.
+    #[napi]
+    pub fn network_usage(&self) -> NetworkUsage {
+        let usage = self.inner.network_usage();
+        NetworkUsage {
+            requests: usage.requests as f64,
+            bytes_downloaded: usage.bytes_downloaded as f64,
+            bytes_uploaded: usage.bytes_uploaded as f64,
+            requests_per_domain: usage
+                .requests_per_domain
+                .into_iter()
+                .map(|(host, count)| (host, count as f64))
+                .collect(),
+        }
+    }
+
+    /// Commands refused by the sandbox's command policy so far, oldest first.
+    #[napi]
+    pub fn denied_commands(&self) -> Vec<DeniedCommand> {
+        self.inner
+            .denied_commands()
+            .into_iter()
+            .map(|denied| DeniedCommand {
+                command: denied.command,
+                args: denied.args,
+                reason: denied.reason,
+            })
+            .collect()
+    }
+
     #[napi]
     pub async fn read_file(&self, path: String) -> Result<Buffer> {
         let content = self
@@ -26,6 +26,7 @@ fn main() {
         // Engine config here MUST match runtime config in runtime/mod.rs.
         let mut engine_config = wasmtime::Config::new();
         engine_config.consume_fuel(true);
+        engine_config.epoch_interruption(true);
 
         let engine =
             wasmtime::Engine::new(&engine_config).expect("Failed to create wasmtime engine");
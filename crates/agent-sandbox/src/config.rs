@@ -23,17 +23,100 @@ pub struct SandboxConfig {
     #[serde(default = "default_timeout")]
     pub timeout: Duration,
 
-    /// Maximum memory in bytes the WASM instance can use (default: 512MB).
+    /// Maximum memory in bytes a command can use, shared by every instance
+    /// it starts for pipeline stages and background jobs (default: 512MB).
     #[serde(default = "default_memory_limit")]
     pub memory_limit_bytes: u64,
 
-    /// Fuel limit for execution (higher = more compute allowed, default: 1 billion).
+    /// Fuel limit for execution, shared like the memory limit (higher = more
+    /// compute allowed, default: 1 billion).
     #[serde(default = "default_fuel_limit")]
     pub fuel_limit: u64,
 
     /// Fetch policy for HTTP networking. `None` disables all networking (default).
     #[serde(default)]
     pub fetch_policy: Option<FetchPolicy>,
+
+    /// Cumulative network budget over the sandbox's lifetime (default: unlimited).
+    #[serde(default)]
+    pub network_quota: NetworkQuota,
+
+    /// Which commands sandboxed code may run (default: all of them).
+    #[serde(default)]
+    pub command_policy: CommandPolicy,
+
+    /// Record every command the shell runs in `ExecResult::trace`
+    /// (default: false).
+    #[serde(default)]
+    pub trace_commands: bool,
+}
+
+/// Lifetime limits on network use, shared by every fetch path in a sandbox.
+///
+/// `None` means unlimited. Every attempted request counts, including ones the
+/// fetch policy rejects and each redirect followed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkQuota {
+    /// Maximum number of requests.
+    #[serde(default)]
+    pub max_requests: Option<u64>,
+
+    /// Maximum total response body bytes, enforced while a body streams in.
+    #[serde(default)]
+    pub max_bytes_downloaded: Option<u64>,
+
+    /// Maximum total request body bytes.
+    #[serde(default)]
+    pub max_bytes_uploaded: Option<u64>,
+
+    /// Maximum number of requests to any single host.
+    #[serde(default)]
+    pub max_requests_per_domain: Option<u64>,
+}
+
+/// Which commands sandboxed code may run.
+///
+/// Checked for every tool invocation, whether it comes straight from
+/// `Sandbox::exec` or from a shell script, pipeline stage, `xargs` or `eval`
+/// inside the sandbox. Shell builtins such as `cd` and `echo` are part of
+/// the shell and are not checked. `bash` and `sh` are the same command, as
+/// are `[` and `test`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandPolicy {
+    /// Commands that may run. `None` allows every command not blocked.
+    #[serde(default)]
+    pub allowed_commands: Option<Vec<String>>,
+
+    /// Commands that may never run, even if allowed.
+    #[serde(default)]
+    pub blocked_commands: Vec<String>,
+
+    /// Arguments refused to commands that may otherwise run.
+    #[serde(default)]
+    pub argument_rules: Vec<ArgumentRule>,
+}
+
+/// Arguments one command may not be given, as in "`rm` without `-r`".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArgumentRule {
+    /// The command the rule applies to.
+    pub command: String,
+
+    /// Options the command may not be given, such as `-r` or `--recursive`.
+    /// A short option also matches when bundled with others, as in `-rf`,
+    /// and a long one when given a value, as in `--output=file`. Arguments
+    /// after `--` are not options.
+    ///
+    /// Options are matched literally: a rule on `-r` does not catch `-R`,
+    /// even for a command that treats the two alike, as `rm` does, so list
+    /// every spelling that should be blocked.
+    #[serde(default)]
+    pub blocked_options: Vec<String>,
+
+    /// Arguments the command may not be given anywhere, such as `push` for
+    /// `git`.
+    #[serde(default)]
+    pub blocked_args: Vec<String>,
 }
 
 /// A directory mount point mapping host path to guest path.
@@ -72,6 +155,9 @@ impl Default for SandboxConfig {
             memory_limit_bytes: default_memory_limit(),
             fuel_limit: default_fuel_limit(),
             fetch_policy: None,
+            network_quota: NetworkQuota::default(),
+            command_policy: CommandPolicy::default(),
+            trace_commands: false,
         }
     }
 }
@@ -14,6 +14,9 @@ pub enum SandboxError {
     #[error("command not found: {0}")]
     CommandNotFound(String),
 
+    #[error("command denied: {command}: {reason}")]
+    CommandDenied { command: String, reason: String },
+
     #[error("execution timed out after {0:?}")]
     Timeout(std::time::Duration),
 
@@ -31,8 +34,34 @@ pub enum SandboxError {
     #[error("fetch error: {0}")]
     Fetch(String),
 
+    #[error("network quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("{0}")]
+    Js(JsError),
+
+    #[error("shell syntax error: {0}")]
+    ShellSyntax(String),
+
     #[error("{0}")]
     Other(String),
 }
 
+/// An exception thrown by code run with `Sandbox::eval_js`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct JsError {
+    /// The error's `name`, e.g. `TypeError`.
+    pub name: String,
+    pub message: String,
+    /// The error's stack as the engine traces it, preceded for syntax errors
+    /// by the failing source line.
+    pub stack: Option<String>,
+}
+
+impl std::fmt::Display for JsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, SandboxError>;
@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+
+/// Future returned by a [`HostFunction`]: the JSON result, or an error message
+/// for the guest.
+pub type HostFuture = Pin<Box<dyn Future<Output = Result<Value, String>> + Send>>;
+
+/// A function the embedder exposes to guest code: JSON arguments in, JSON out.
+pub type HostFunction = Arc<dyn Fn(Value) -> HostFuture + Send + Sync>;
+
+/// The host functions registered on a `Sandbox`.
+///
+/// Shared with its runtime, so functions registered after the sandbox was
+/// created are visible to the next command.
+#[derive(Clone, Default)]
+pub struct HostFunctions {
+    functions: Arc<RwLock<HashMap<String, HostFunction>>>,
+}
+
+impl HostFunctions {
+    pub fn insert(&self, name: String, function: HostFunction) {
+        self.functions.write().unwrap().insert(name, function);
+    }
+
+    pub fn remove(&self, name: &str) -> bool {
+        self.functions.write().unwrap().remove(name).is_some()
+    }
+
+    /// Call `name`, or fail if no such function is registered.
+    pub async fn call(&self, name: &str, args: Value) -> Result<Value, String> {
+        let function = self.functions.read().unwrap().get(name).cloned();
+        match function {
+            Some(function) => function(args).await,
+            None => Err(format!("unknown host function: {name}")),
+        }
+    }
+}
@@ -2,19 +2,24 @@ pub mod config;
 pub mod error;
 pub mod exec;
 pub mod fs;
+pub mod host;
+pub mod policy;
+pub mod quota;
 pub mod runtime;
 pub mod toolbox;
 
-use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 
-use agent_fetch::SafeClient;
 pub use agent_fetch::{DomainPattern, FetchPolicy, FetchRequest, FetchResponse};
 use tokio::sync::Mutex;
 
 use crate::config::SandboxConfig;
-use crate::error::{Result, SandboxError};
+use crate::error::{JsError, Result, SandboxError};
 use crate::fs::overlay::{FsChange, FsOverlay};
+use crate::host::HostFunctions;
+use crate::policy::{CommandGuard, DeniedCommand};
+use crate::quota::{MeteredClient, NetworkError, NetworkUsage};
 use crate::runtime::{ExecResult, WasiRuntime};
 
 /// A sandboxed execution environment backed by WASM (Wasmtime + WASI).
@@ -23,7 +28,9 @@ pub struct Sandbox {
     overlay: Arc<Mutex<Option<FsOverlay>>>,
     config: SandboxConfig,
     destroyed: Arc<std::sync::atomic::AtomicBool>,
-    fetch_client: Option<Arc<SafeClient>>,
+    fetch_client: Option<Arc<MeteredClient>>,
+    host_functions: HostFunctions,
+    command_guard: Arc<CommandGuard>,
 }
 
 impl Sandbox {
@@ -31,12 +38,21 @@ impl Sandbox {
     pub fn new(config: SandboxConfig) -> Result<Self> {
         let overlay = FsOverlay::new(&config.work_dir)?;
 
-        let fetch_client = config
-            .fetch_policy
-            .as_ref()
-            .map(|policy| Arc::new(SafeClient::new(policy.clone())));
-
-        let runtime = WasiRuntime::new(config.clone(), fetch_client.clone())?;
+        let fetch_client = config.fetch_policy.as_ref().map(|policy| {
+            Arc::new(MeteredClient::new(
+                policy.clone(),
+                config.network_quota.clone(),
+            ))
+        });
+
+        let host_functions = HostFunctions::default();
+        let command_guard = Arc::new(CommandGuard::new(config.command_policy.clone()));
+        let runtime = WasiRuntime::new(
+            config.clone(),
+            fetch_client.clone(),
+            host_functions.clone(),
+            command_guard.clone(),
+        )?;
 
         Ok(Self {
             runtime,
@@ -44,6 +60,8 @@ impl Sandbox {
             config,
             destroyed: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             fetch_client,
+            host_functions,
+            command_guard,
         })
     }
 
@@ -51,9 +69,11 @@ impl Sandbox {
     pub async fn exec(&self, command: &str, args: &[String]) -> Result<ExecResult> {
         self.check_destroyed()?;
 
-        // Intercept curl commands and route through fetch
-        if command == "curl" {
-            return self.exec_curl(args).await;
+        self.check_command(command, args)?;
+
+        // The toolbox's curl fetches through the host; without a client it could only fail
+        if command == "curl" && self.fetch_client.is_none() {
+            return Err(SandboxError::NetworkingDisabled);
         }
 
         if !toolbox::is_available(command) {
@@ -69,6 +89,104 @@ impl Sandbox {
             .await
     }
 
+    /// Execute TypeScript code inside the sandbox.
+    ///
+    /// Types are stripped inside the sandbox before the code runs as an ES
+    /// module; line numbers in reported errors refer to `code` as given.
+    pub async fn exec_ts(&self, code: &str) -> Result<ExecResult> {
+        let args = [
+            "--input-type=module-typescript".to_string(),
+            "-e".to_string(),
+            code.to_string(),
+        ];
+        self.exec("node", &args).await
+    }
+
+    /// Execute Python code inside the sandbox using the built-in interpreter.
+    pub async fn exec_python(&self, code: &str) -> Result<ExecResult> {
+        self.exec("python3", &["-c".to_string(), code.to_string()])
+            .await
+    }
+
+    /// Evaluate JavaScript inside the sandbox and return its value as JSON.
+    ///
+    /// `input` is available to the code as the global `input`. The result is
+    /// the value of the last statement, awaited if it is a promise (for ES
+    /// module code, the default export); `undefined` becomes `null`. An
+    /// uncaught exception is returned as [`SandboxError::Js`].
+    pub async fn eval_js(&self, code: &str, input: serde_json::Value) -> Result<serde_json::Value> {
+        self.check_destroyed()?;
+
+        let args = [
+            format!("--sandbox-eval={input}"),
+            "-e".to_string(),
+            code.to_string(),
+        ];
+        // Judged as the `node -e` it runs as; the input is not an argument
+        self.check_command("node", &args[1..])?;
+        let outcome = self.runtime.run("node", &args).await?;
+        let Some(report) = outcome.report else {
+            return Err(SandboxError::Other(format!(
+                "node exited with code {} without a result: {}",
+                outcome.result.exit_code,
+                String::from_utf8_lossy(&outcome.result.stderr).trim_end()
+            )));
+        };
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Outcome {
+            Ok(serde_json::Value),
+            Error(JsError),
+        }
+        match serde_json::from_slice(&report) {
+            Ok(Outcome::Ok(value)) => Ok(value),
+            Ok(Outcome::Error(err)) => Err(SandboxError::Js(err)),
+            Err(e) => Err(SandboxError::Other(format!("invalid eval_js result: {e}"))),
+        }
+    }
+
+    /// Parse a shell script without running it and return its syntax tree
+    /// as JSON.
+    ///
+    /// The tree mirrors the shell's AST: a program is a list of commands,
+    /// each a pipeline of simple commands (assignments, words and
+    /// redirections) or compound ones such as `if` and `for`. Command and
+    /// process substitutions carry the program they run, and arithmetic
+    /// expressions the word they expand as, so every command a script could
+    /// invoke can be reviewed before it is run with `exec`. A
+    /// script that does not parse is returned as [`SandboxError::ShellSyntax`].
+    pub async fn parse_shell(&self, script: &str) -> Result<serde_json::Value> {
+        self.check_destroyed()?;
+
+        // Nothing in the script runs, but a policy refusing the shell
+        // itself refuses parsing with it too
+        self.check_command("sh", &[])?;
+        let args = ["--sandbox-ast".to_string(), script.to_string()];
+        let outcome = self.runtime.run("sh", &args).await?;
+        let Some(report) = outcome.report else {
+            return Err(SandboxError::Other(format!(
+                "sh exited with code {} without a syntax tree: {}",
+                outcome.result.exit_code,
+                String::from_utf8_lossy(&outcome.result.stderr).trim_end()
+            )));
+        };
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Outcome {
+            Ok(serde_json::Value),
+            Error(String),
+        }
+        match serde_json::from_slice(&report) {
+            Ok(Outcome::Ok(program)) => Ok(program),
+            Ok(Outcome::Error(message)) => Err(SandboxError::ShellSyntax(message)),
+            Err(e) => Err(SandboxError::Other(format!(
+                "invalid parse_shell result: {e}"
+            ))),
+        }
+    }
+
     /// Perform an HTTP fetch using the sandbox's safe client.
     pub async fn fetch(&self, request: FetchRequest) -> Result<FetchResponse> {
         self.check_destroyed()?;
@@ -78,10 +196,47 @@ impl Sandbox {
             .as_ref()
             .ok_or(SandboxError::NetworkingDisabled)?;
 
-        client
-            .fetch(request)
-            .await
-            .map_err(|e| SandboxError::Fetch(e.to_string()))
+        client.fetch(request).await.map_err(|e| match e {
+            NetworkError::QuotaExceeded(msg) => SandboxError::QuotaExceeded(msg),
+            NetworkError::Fetch(e) => SandboxError::Fetch(e.to_string()),
+        })
+    }
+
+    /// Network usage so far, counted against `SandboxConfig::network_quota`.
+    ///
+    /// Returns zeroed usage when networking is disabled.
+    pub fn network_usage(&self) -> NetworkUsage {
+        self.fetch_client
+            .as_ref()
+            .map(|client| client.usage())
+            .unwrap_or_default()
+    }
+
+    /// Commands refused by `SandboxConfig::command_policy` so far, oldest
+    /// first, whether passed to `exec` or run from inside the sandbox.
+    pub fn denied_commands(&self) -> Vec<DeniedCommand> {
+        self.command_guard.denied()
+    }
+
+    /// Register a host function that guest code can call by name: as
+    /// `await host.call(name, args)` in JavaScript, or `hostcall NAME [json]`
+    /// in the shell.
+    ///
+    /// `function` receives the JSON arguments and returns a JSON result or an
+    /// error message, which the guest sees as a rejected promise or a failed
+    /// command. Registering a name again replaces the previous function.
+    pub fn register_host_function<F, Fut>(&self, name: impl Into<String>, function: F)
+    where
+        F: Fn(serde_json::Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = std::result::Result<serde_json::Value, String>> + Send + 'static,
+    {
+        self.host_functions
+            .insert(name.into(), Arc::new(move |args| Box::pin(function(args))));
+    }
+
+    /// Remove a host function; returns whether it was registered.
+    pub fn unregister_host_function(&self, name: &str) -> bool {
+        self.host_functions.remove(name)
     }
 
     /// Read a file from the sandbox's work directory.
@@ -150,6 +305,17 @@ impl Sandbox {
         Ok(())
     }
 
+    /// Refuse `command` up front if the command policy does not allow it,
+    /// rather than leaving the guest to refuse it with exit status 126.
+    fn check_command(&self, command: &str, args: &[String]) -> Result<()> {
+        self.command_guard
+            .check(command, args)
+            .map_err(|reason| SandboxError::CommandDenied {
+                command: command.to_string(),
+                reason,
+            })
+    }
+
     fn check_destroyed(&self) -> Result<()> {
         if self.destroyed.load(std::sync::atomic::Ordering::SeqCst) {
             Err(SandboxError::Destroyed)
@@ -157,123 +323,6 @@ impl Sandbox {
             Ok(())
         }
     }
-
-    /// Intercept `curl` commands and route through the fetch client.
-    async fn exec_curl(&self, args: &[String]) -> Result<ExecResult> {
-        let client = self
-            .fetch_client
-            .as_ref()
-            .ok_or(SandboxError::NetworkingDisabled)?;
-
-        let (request, output_file) = parse_curl_args(args)?;
-
-        match client.fetch(request).await {
-            Ok(resp) => {
-                let body = resp.body.clone();
-
-                // If -o was specified, write to file
-                if let Some(out_path) = output_file {
-                    let full_path = fs::validate_path(&self.config.work_dir, &out_path)?;
-                    if let Some(parent) = full_path.parent() {
-                        tokio::fs::create_dir_all(parent).await?;
-                    }
-                    tokio::fs::write(&full_path, &body).await?;
-                }
-
-                let status_line = format!("HTTP {}\n", resp.status);
-                Ok(ExecResult {
-                    exit_code: 0,
-                    stdout: body,
-                    stderr: status_line.into_bytes(),
-                })
-            }
-            Err(e) => {
-                let err_msg = format!("curl: {}\n", e);
-                Ok(ExecResult {
-                    exit_code: 1,
-                    stdout: Vec::new(),
-                    stderr: err_msg.into_bytes(),
-                })
-            }
-        }
-    }
-}
-
-/// Parse curl-like arguments into a FetchRequest.
-fn parse_curl_args(args: &[String]) -> Result<(FetchRequest, Option<String>)> {
-    let mut url = None;
-    let mut method = "GET".to_string();
-    let mut headers = HashMap::new();
-    let mut body: Option<Vec<u8>> = None;
-    let mut output_file = None;
-
-    let mut i = 0;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-X" | "--request" => {
-                i += 1;
-                if i < args.len() {
-                    method = args[i].clone();
-                }
-            }
-            "-H" | "--header" => {
-                i += 1;
-                if i < args.len()
-                    && let Some((k, v)) = args[i].split_once(':')
-                {
-                    headers.insert(k.trim().to_string(), v.trim().to_string());
-                }
-            }
-            "-d" | "--data" => {
-                i += 1;
-                if i < args.len() {
-                    body = Some(args[i].as_bytes().to_vec());
-                    if method == "GET" {
-                        method = "POST".to_string();
-                    }
-                }
-            }
-            "-o" | "--output" => {
-                i += 1;
-                if i < args.len() {
-                    output_file = Some(args[i].clone());
-                }
-            }
-            "-s" | "--silent" | "-S" | "--show-error" | "-L" | "--location" | "-f" | "--fail"
-            | "-v" | "--verbose" | "-k" | "--insecure" | "-I" | "--head" | "-N" | "--no-buffer"
-            | "-g" | "--globoff" => {
-                // Silently ignore common boolean flags
-            }
-            "--max-time" | "--connect-timeout" | "--retry" | "--retry-delay" | "--max-redirs"
-            | "-u" | "--user" | "-A" | "--user-agent" | "-e" | "--referer" | "-w"
-            | "--write-out" | "--max-filesize" => {
-                // Unknown flags that take a value — skip the next argument
-                i += 1;
-            }
-            arg if !arg.starts_with('-') && url.is_none() => {
-                url = Some(arg.to_string());
-            }
-            arg if arg.starts_with('-') => {
-                // Unknown flag — ignore (may cause issues with value-taking flags)
-            }
-            _ => {
-                // Non-flag argument after URL — ignore
-            }
-        }
-        i += 1;
-    }
-
-    let url = url.ok_or_else(|| SandboxError::Other("curl: no URL specified".into()))?;
-
-    Ok((
-        FetchRequest {
-            url,
-            method,
-            headers,
-            body,
-        },
-        output_file,
-    ))
 }
 
 /// A directory entry returned by `list_dir`.
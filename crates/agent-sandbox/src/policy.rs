@@ -0,0 +1,121 @@
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::config::{ArgumentRule, CommandPolicy};
+
+/// A command invocation the command policy refused.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeniedCommand {
+    pub command: String,
+    pub args: Vec<String>,
+    /// Why it was refused, e.g. `option -r is blocked`.
+    pub reason: String,
+}
+
+/// Enforces the sandbox's [`CommandPolicy`] and keeps every refusal.
+///
+/// One guard is shared by `Sandbox::exec` and the guest's command check, so
+/// refusals inside shell scripts are recorded alongside top-level ones.
+pub struct CommandGuard {
+    policy: CommandPolicy,
+    denied: Mutex<Vec<DeniedCommand>>,
+}
+
+impl CommandGuard {
+    pub fn new(policy: CommandPolicy) -> Self {
+        Self {
+            policy,
+            denied: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Snapshot of the invocations refused so far, oldest first.
+    pub fn denied(&self) -> Vec<DeniedCommand> {
+        self.denied.lock().unwrap().clone()
+    }
+
+    /// Check one invocation, recording it if the policy refuses it.
+    pub fn check(&self, command: &str, args: &[String]) -> std::result::Result<(), String> {
+        let reason = match refusal(&self.policy, command, args) {
+            Some(reason) => reason,
+            None => return Ok(()),
+        };
+        self.denied.lock().unwrap().push(DeniedCommand {
+            command: command.to_string(),
+            args: args.to_vec(),
+            reason: reason.clone(),
+        });
+        Err(reason)
+    }
+}
+
+/// Why `policy` refuses `command` with `args`, if it does.
+fn refusal(policy: &CommandPolicy, command: &str, args: &[String]) -> Option<String> {
+    let name = canonical(command);
+    let listed = |commands: &[String]| commands.iter().any(|c| canonical(c) == name);
+
+    if listed(&policy.blocked_commands) {
+        return Some("command is blocked".to_string());
+    }
+    if let Some(allowed) = &policy.allowed_commands
+        && !listed(allowed)
+    {
+        return Some("command is not allowed".to_string());
+    }
+    policy
+        .argument_rules
+        .iter()
+        .filter(|rule| canonical(&rule.command) == name)
+        .find_map(|rule| argument_refusal(rule, args))
+}
+
+fn argument_refusal(rule: &ArgumentRule, args: &[String]) -> Option<String> {
+    let mut options = true;
+    for arg in args {
+        if options && arg == "--" {
+            options = false;
+            continue;
+        }
+        if options && let Some(option) = rule.blocked_options.iter().find(|o| gives_option(arg, o))
+        {
+            return Some(format!("option {option} is blocked"));
+        }
+        if rule.blocked_args.contains(arg) {
+            return Some(format!("argument {arg} is blocked"));
+        }
+    }
+    None
+}
+
+/// Whether `arg` gives `option`: exactly, bundled with other short options
+/// (`-rf` gives `-r`), or with a value (`--output=x` gives `--output`).
+fn gives_option(arg: &str, option: &str) -> bool {
+    if let Some(long) = option.strip_prefix("--") {
+        return arg.strip_prefix("--").is_some_and(|given| {
+            given == long || given.strip_prefix(long).is_some_and(|v| v.starts_with('='))
+        });
+    }
+    let mut short = option.strip_prefix('-').unwrap_or_default().chars();
+    match (short.next(), short.next()) {
+        (Some(flag), None) => {
+            arg.len() > 1
+                && arg.starts_with('-')
+                && !arg.starts_with("--")
+                && arg[1..].contains(flag)
+        }
+        _ => arg == option,
+    }
+}
+
+/// The name a command is listed under: `bash` runs the same shell as `sh`,
+/// `python3` the same interpreter as `python`, and `[` is `test`, so a
+/// policy naming either form covers both.
+fn canonical(command: &str) -> &str {
+    match command {
+        "bash" => "sh",
+        "python3" => "python",
+        "[" => "test",
+        other => other,
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use agent_fetch::{FetchError, FetchPolicy, FetchRequest, FetchResponse, Meter, SafeClient};
+use serde::Serialize;
+
+use crate::config::NetworkQuota;
+
+/// Cumulative network usage over a sandbox's lifetime.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NetworkUsage {
+    /// Requests attempted, including ones rejected by the fetch policy and
+    /// every redirect followed.
+    pub requests: u64,
+    /// Response body bytes received.
+    pub bytes_downloaded: u64,
+    /// Request body bytes sent.
+    pub bytes_uploaded: u64,
+    /// Requests attempted per host (lowercased).
+    pub requests_per_domain: HashMap<String, u64>,
+}
+
+/// Error from a metered fetch: either the quota refused it or the client failed.
+#[derive(Debug)]
+pub enum NetworkError {
+    QuotaExceeded(String),
+    Fetch(FetchError),
+}
+
+impl NetworkError {
+    /// A stable name for the kind of failure, handed to the guest alongside
+    /// the message so tools such as `curl` can pick an exit status from it.
+    pub fn kind(&self) -> &'static str {
+        let e = match self {
+            NetworkError::QuotaExceeded(_) => return "blocked",
+            NetworkError::Fetch(e) => e,
+        };
+        match e {
+            FetchError::InvalidUrl(_) => "invalid_url",
+            FetchError::DnsResolutionFailed(_) => "dns",
+            FetchError::PrivateIpBlocked { .. }
+            | FetchError::RedirectToPrivateIp { .. }
+            | FetchError::DomainBlocked(_)
+            | FetchError::DomainNotAllowed(_) => "blocked",
+            FetchError::ConnectionTimeout | FetchError::RequestTimeout => "timeout",
+            FetchError::TooManyRedirects { .. } => "too_many_redirects",
+            FetchError::RequestBodyTooLarge { .. } => "request_too_large",
+            FetchError::ResponseBodyTooLarge { .. } => "response_too_large",
+            FetchError::HttpError(_) => "http",
+            FetchError::SchemeNotAllowed(_)
+            | FetchError::MethodNotAllowed(_)
+            | FetchError::RateLimitExceeded => "other",
+        }
+    }
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::QuotaExceeded(msg) => write!(f, "network quota exceeded: {msg}"),
+            NetworkError::Fetch(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// A fetch client that charges every request against the sandbox's [`NetworkQuota`].
+///
+/// It wraps `agent_fetch::SafeClient` and meters its traffic as it goes:
+/// each request, redirects included, is charged before it is sent, and a
+/// download stops as soon as it would exceed the remaining budget rather
+/// than after it has fully arrived.
+///
+/// One meter is shared by `Sandbox::fetch` and the guest fetch bridge behind
+/// `curl`, `wget` and JS `fetch()`, so they all draw from the same budget.
+pub struct MeteredClient {
+    client: SafeClient,
+    account: Account,
+}
+
+impl MeteredClient {
+    pub fn new(policy: FetchPolicy, quota: NetworkQuota) -> Self {
+        Self {
+            client: SafeClient::new(policy),
+            account: Account {
+                quota,
+                usage: Mutex::new(NetworkUsage::default()),
+            },
+        }
+    }
+
+    /// Snapshot of the usage so far.
+    pub fn usage(&self) -> NetworkUsage {
+        self.account.usage.lock().unwrap().clone()
+    }
+
+    /// Perform a fetch if the remaining budget allows it.
+    pub async fn fetch(
+        &self,
+        request: FetchRequest,
+    ) -> std::result::Result<FetchResponse, NetworkError> {
+        self.client.fetch_metered(request, &self.account).await
+    }
+}
+
+/// The budget a [`MeteredClient`] charges its traffic to.
+struct Account {
+    quota: NetworkQuota,
+    usage: Mutex<NetworkUsage>,
+}
+
+impl Account {
+    /// Check a request to `url` uploading `upload` bytes against every limit
+    /// and charge it up front.
+    fn reserve(&self, url: &str, upload: u64) -> std::result::Result<(), NetworkError> {
+        let host = request_host(url);
+        let mut usage = self.usage.lock().unwrap();
+
+        if let Some(limit) = self.quota.max_requests
+            && usage.requests >= limit
+        {
+            return Err(NetworkError::QuotaExceeded(format!(
+                "request limit of {limit} reached"
+            )));
+        }
+        if let (Some(limit), Some(host)) = (self.quota.max_requests_per_domain, host.as_ref())
+            && usage.requests_per_domain.get(host).copied().unwrap_or(0) >= limit
+        {
+            return Err(NetworkError::QuotaExceeded(format!(
+                "request limit of {limit} for {host} reached"
+            )));
+        }
+        if let Some(limit) = self.quota.max_bytes_uploaded
+            && usage.bytes_uploaded + upload > limit
+        {
+            return Err(NetworkError::QuotaExceeded(format!(
+                "upload limit of {limit} bytes exceeded"
+            )));
+        }
+        if let Some(limit) = self.quota.max_bytes_downloaded
+            && usage.bytes_downloaded >= limit
+        {
+            return Err(NetworkError::QuotaExceeded(format!(
+                "download limit of {limit} bytes reached"
+            )));
+        }
+
+        usage.requests += 1;
+        usage.bytes_uploaded += upload;
+        if let Some(host) = host {
+            *usage.requests_per_domain.entry(host).or_insert(0) += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Meter for Account {
+    type Error = NetworkError;
+
+    fn request(&self, url: &str, body_bytes: usize) -> std::result::Result<(), NetworkError> {
+        self.reserve(url, body_bytes as u64)
+    }
+
+    fn response(&self, content_length: Option<u64>) -> std::result::Result<(), NetworkError> {
+        // A body declared larger than the budget would only be cut off
+        if let (Some(limit), Some(length)) = (self.quota.max_bytes_downloaded, content_length)
+            && self.usage.lock().unwrap().bytes_downloaded + length > limit
+        {
+            return Err(download_exceeded(limit));
+        }
+        Ok(())
+    }
+
+    fn chunk(&self, bytes: usize) -> std::result::Result<(), NetworkError> {
+        let mut usage = self.usage.lock().unwrap();
+        usage.bytes_downloaded += bytes as u64;
+        if let Some(limit) = self.quota.max_bytes_downloaded
+            && usage.bytes_downloaded > limit
+        {
+            return Err(download_exceeded(limit));
+        }
+        Ok(())
+    }
+}
+
+impl From<FetchError> for NetworkError {
+    fn from(e: FetchError) -> Self {
+        NetworkError::Fetch(e)
+    }
+}
+
+fn download_exceeded(limit: u64) -> NetworkError {
+    NetworkError::QuotaExceeded(format!("download limit of {limit} bytes exceeded"))
+}
+
+fn request_host(url: &str) -> Option<String> {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_request(url: &str) -> FetchRequest {
+        FetchRequest {
+            url: url.to_string(),
+            method: "GET".to_string(),
+            headers: HashMap::new(),
+            body: None,
+        }
+    }
+
+    /// A meter that may fetch from the local test servers.
+    fn local_meter(quota: NetworkQuota) -> MeteredClient {
+        let policy = FetchPolicy {
+            deny_private_ips: false,
+            ..Default::default()
+        };
+        MeteredClient::new(policy, quota)
+    }
+
+    fn meter(quota: NetworkQuota) -> MeteredClient {
+        MeteredClient::new(FetchPolicy::default(), quota)
+    }
+
+    #[test]
+    fn test_request_limit() {
+        let meter = meter(NetworkQuota {
+            max_requests: Some(2),
+            ..Default::default()
+        });
+        assert!(meter.account.reserve("https://a.com/", 0).is_ok());
+        assert!(meter.account.reserve("https://b.com/", 0).is_ok());
+        let err = meter.account.reserve("https://c.com/", 0).unwrap_err();
+        assert!(err.to_string().contains("request limit of 2"));
+        assert_eq!(meter.usage().requests, 2);
+    }
+
+    #[test]
+    fn test_per_domain_limit() {
+        let meter = meter(NetworkQuota {
+            max_requests_per_domain: Some(1),
+            ..Default::default()
+        });
+        assert!(meter.account.reserve("https://A.com/x", 0).is_ok());
+        assert!(meter.account.reserve("https://a.com/y", 0).is_err());
+        assert!(meter.account.reserve("https://b.com/", 0).is_ok());
+        assert_eq!(meter.usage().requests_per_domain["a.com"], 1);
+    }
+
+    #[test]
+    fn test_upload_limit() {
+        let meter = meter(NetworkQuota {
+            max_bytes_uploaded: Some(10),
+            ..Default::default()
+        });
+        assert!(meter.account.reserve("https://a.com/", 8).is_ok());
+        let err = meter.account.reserve("https://a.com/", 3).unwrap_err();
+        assert!(err.to_string().contains("upload limit of 10 bytes"));
+        assert_eq!(meter.usage().bytes_uploaded, 8);
+    }
+
+    #[tokio::test]
+    async fn test_download_limit_stops_stream() {
+        use std::io::{Read, Write};
+
+        // A server that never ends its chunked body
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0u8; 4096]);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n");
+            let chunk = format!("400\r\n{}\r\n", "x".repeat(1024));
+            while stream.write_all(chunk.as_bytes()).is_ok() {}
+        });
+
+        let meter = local_meter(NetworkQuota {
+            max_bytes_downloaded: Some(10_000),
+            ..Default::default()
+        });
+        let err = meter
+            .fetch(get_request(&format!("http://{addr}/")))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("download limit of 10000 bytes"));
+        assert!(meter.usage().bytes_downloaded < 20_000);
+    }
+
+    #[tokio::test]
+    async fn test_redirects_charged_per_domain() {
+        use std::io::{Read, Write};
+
+        // A server that redirects every request back to itself
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.read(&mut [0u8; 4096]);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 302 Found\r\nLocation: /again\r\n\
+                      Content-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+
+        let meter = local_meter(NetworkQuota {
+            max_requests_per_domain: Some(3),
+            ..Default::default()
+        });
+        let err = meter
+            .fetch(get_request(&format!("http://{addr}/")))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("request limit of 3 for 127.0.0.1"),
+            "{err}"
+        );
+        let usage = meter.usage();
+        assert_eq!(usage.requests, 3);
+        assert_eq!(usage.requests_per_domain["127.0.0.1"], 3);
+    }
+}
@@ -0,0 +1,217 @@
+//! Resources shared by every instance one command starts.
+//!
+//! A command's own instance and all the child instances it starts for
+//! pipeline stages and background jobs draw on one budget of fuel and
+//! memory, and only so many children may run at once, so fanning out cannot
+//! multiply the limits in `SandboxConfig`.
+//!
+//! Fuel cannot be taken from a pool as it is burnt, so every instance is
+//! handed all the fuel the pool has left and settles up with it on every
+//! [`FUEL_TICK`]: what it burnt comes out of the pool, and it goes on with
+//! what remains. Instances running side by side can overdraw the pool by
+//! what they burn in one tick at most.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use wasmtime::{Engine, ResourceLimiter};
+
+/// Most child instances one command may have running at a time.
+pub(super) const MAX_CHILDREN: usize = 64;
+
+/// How often running instances settle their fuel with the pool.
+const FUEL_TICK: Duration = Duration::from_millis(10);
+
+/// Fuel, memory and instance slots left to one command and its children.
+pub(super) struct Budget {
+    /// Fuel not yet burnt by any instance.
+    fuel: Mutex<u64>,
+    /// Linear memory, in bytes, not yet claimed by any instance.
+    memory: Mutex<usize>,
+    /// Child instances running now.
+    children: Mutex<usize>,
+}
+
+impl Budget {
+    pub(super) fn new(fuel: u64, memory: usize) -> Arc<Budget> {
+        Arc::new(Budget {
+            fuel: Mutex::new(fuel),
+            memory: Mutex::new(memory),
+            children: Mutex::new(0),
+        })
+    }
+
+    /// Claim a slot for each of `count` children, or none of them if that
+    /// would go over [`MAX_CHILDREN`]. Each slot is freed when dropped.
+    pub(super) fn claim_children(self: &Arc<Self>, count: usize) -> Result<Vec<ChildSlot>, String> {
+        let mut children = self.children.lock().unwrap();
+        if *children + count > MAX_CHILDREN {
+            return Err(format!(
+                "too many processes: at most {MAX_CHILDREN} may run at once"
+            ));
+        }
+        *children += count;
+        Ok((0..count).map(|_| ChildSlot(self.clone())).collect())
+    }
+}
+
+/// A running child's claim on [`Budget`].
+pub(super) struct ChildSlot(Arc<Budget>);
+
+impl Drop for ChildSlot {
+    fn drop(&mut self) {
+        *self.0.children.lock().unwrap() -= 1;
+    }
+}
+
+/// Charges one store's fuel to the shared [`Budget`].
+pub(super) struct FuelMeter {
+    budget: Arc<Budget>,
+    /// Fuel the store was given when it last settled up.
+    granted: u64,
+    /// Fuel burnt before then.
+    settled: u64,
+}
+
+impl FuelMeter {
+    pub(super) fn new(budget: &Arc<Budget>) -> Self {
+        FuelMeter {
+            budget: budget.clone(),
+            granted: 0,
+            settled: 0,
+        }
+    }
+
+    /// Charge the fuel burnt since the last settlement, given the store has
+    /// `left` of what it was granted, and return the fuel it may go on with.
+    pub(super) fn settle(&mut self, left: u64) -> u64 {
+        let burnt = self.granted.saturating_sub(left);
+        self.settled += burnt;
+        let mut fuel = self.budget.fuel.lock().unwrap();
+        *fuel = fuel.saturating_sub(burnt);
+        self.granted = *fuel;
+        self.granted
+    }
+
+    /// Fuel the store has burnt in all, given it has `left` of what it was
+    /// last granted.
+    pub(super) fn used(&self, left: u64) -> u64 {
+        self.settled + self.granted.saturating_sub(left)
+    }
+}
+
+/// Charges one store's memory growth to the shared [`Budget`], and hands
+/// it back once the store is dropped.
+pub(super) struct MemoryLimiter {
+    budget: Arc<Budget>,
+    /// Bytes this store has claimed so far.
+    claimed: usize,
+}
+
+impl MemoryLimiter {
+    pub(super) fn new(budget: &Arc<Budget>) -> Self {
+        MemoryLimiter {
+            budget: budget.clone(),
+            claimed: 0,
+        }
+    }
+}
+
+impl ResourceLimiter for MemoryLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        if maximum.is_some_and(|max| desired > max) {
+            return Ok(false);
+        }
+        let growth = desired.saturating_sub(current);
+        let mut memory = self.budget.memory.lock().unwrap();
+        if growth > *memory {
+            return Ok(false);
+        }
+        *memory -= growth;
+        self.claimed += growth;
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        Ok(maximum.is_none_or(|max| desired <= max))
+    }
+}
+
+impl Drop for MemoryLimiter {
+    fn drop(&mut self) {
+        *self.budget.memory.lock().unwrap() += self.claimed;
+    }
+}
+
+/// Bumps the engine's epoch every [`FUEL_TICK`] until dropped, so the
+/// instances of a running command settle their fuel.
+pub(super) struct FuelTicker(tokio::task::JoinHandle<()>);
+
+impl FuelTicker {
+    pub(super) fn start(engine: &'static Engine) -> Self {
+        FuelTicker(tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(FUEL_TICK);
+            loop {
+                ticks.tick().await;
+                engine.increment_epoch();
+            }
+        }))
+    }
+}
+
+impl Drop for FuelTicker {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuel_is_shared() {
+        let budget = Budget::new(100, 0);
+        let mut first = FuelMeter::new(&budget);
+        let mut second = FuelMeter::new(&budget);
+        assert_eq!(first.settle(0), 100);
+        assert_eq!(second.settle(0), 100);
+        // Each burns 30 of the 100 it was given
+        assert_eq!(first.settle(70), 70);
+        assert_eq!(second.settle(70), 40);
+        assert_eq!(first.used(70), 30);
+        assert_eq!(second.used(10), 60);
+        assert_eq!(first.settle(0), 0);
+    }
+
+    #[test]
+    fn test_memory_is_shared_and_returned() {
+        let budget = Budget::new(0, 100);
+        let mut first = MemoryLimiter::new(&budget);
+        let mut second = MemoryLimiter::new(&budget);
+        assert!(first.memory_growing(0, 60, None).unwrap());
+        assert!(!second.memory_growing(0, 60, None).unwrap());
+        assert!(second.memory_growing(0, 40, None).unwrap());
+        drop(first);
+        assert!(second.memory_growing(40, 100, None).unwrap());
+    }
+
+    #[test]
+    fn test_children_are_capped() {
+        let budget = Budget::new(0, 0);
+        let slots = budget.claim_children(MAX_CHILDREN).unwrap();
+        assert!(budget.claim_children(1).is_err());
+        drop(slots);
+        assert_eq!(budget.claim_children(2).unwrap().len(), 2);
+    }
+}
@@ -1,15 +1,26 @@
 use std::collections::HashMap;
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 
-use agent_fetch::SafeClient;
-use wasmtime::{
-    Caller, Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap,
-};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use wasmtime::{Caller, Config, Engine, Linker, Module, Store, Trap, UpdateDeadline};
 use wasmtime_wasi::WasiCtx;
+use wasmtime_wasi::cli::{StdinStream, StdoutStream};
 use wasmtime_wasi::p2::pipe::{MemoryInputPipe, MemoryOutputPipe};
 
 use crate::config::SandboxConfig;
 use crate::error::{Result, SandboxError};
+use crate::host::HostFunctions;
+use crate::policy::CommandGuard;
+use crate::quota::MeteredClient;
+
+mod limits;
+mod signal;
+mod spawn;
+
+use limits::{Budget, FuelMeter, FuelTicker, MemoryLimiter};
+use signal::{InterruptOnDrop, Signals};
 
 /// Result of executing a command in the sandbox.
 #[derive(Debug, Clone)]
@@ -17,6 +28,33 @@ pub struct ExecResult {
     pub exit_code: i32,
     pub stdout: Vec<u8>,
     pub stderr: Vec<u8>,
+    /// Every command the shell ran, in the order they finished. Empty unless
+    /// `SandboxConfig::trace_commands` is set.
+    pub trace: Vec<TracedCommand>,
+}
+
+/// One command run by the sandboxed shell, as recorded in
+/// [`ExecResult::trace`].
+#[derive(Debug, Clone)]
+pub struct TracedCommand {
+    /// The command and its arguments, after expansion.
+    pub argv: Vec<String>,
+    /// Working directory it ran in.
+    pub cwd: String,
+    pub exit_code: i32,
+    /// Wall-clock time it ran for.
+    pub duration: Duration,
+    /// Fuel it consumed.
+    pub fuel: u64,
+    /// Redirections in effect, outermost first, as in `>out.txt` or `2>&1`.
+    pub redirections: Vec<String>,
+}
+
+/// A finished run: its `ExecResult` plus any JSON result the guest handed
+/// over through `__sandbox_report`.
+pub(crate) struct ExecOutcome {
+    pub result: ExecResult,
+    pub report: Option<Vec<u8>>,
 }
 
 /// JSON request sent from WASM guest to host for fetch.
@@ -29,29 +67,116 @@ struct GuestFetchRequest {
     headers: HashMap<String, String>,
     #[serde(default)]
     body: Option<String>,
+    /// Base64-encoded request body, used instead of `body` for binary payloads.
+    #[serde(default)]
+    body_base64: Option<String>,
+    /// Return the response body base64-encoded in `body_base64`.
+    #[serde(default)]
+    binary: bool,
 }
 
 fn default_method() -> String {
     "GET".to_string()
 }
 
+/// JSON request sent from WASM guest to host to call a host function.
+#[derive(serde::Deserialize)]
+struct GuestHostCall {
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+/// JSON trace entry sent from WASM guest to host once a command has run.
+#[derive(serde::Deserialize)]
+struct GuestTracedCommand {
+    argv: Vec<String>,
+    #[serde(default)]
+    cwd: String,
+    exit_code: i32,
+    #[serde(default)]
+    duration_us: u64,
+    #[serde(default)]
+    fuel: u64,
+    #[serde(default)]
+    redirections: Vec<String>,
+}
+
+/// JSON request sent from WASM guest to host before running a command.
+#[derive(serde::Deserialize)]
+struct GuestCommandCheck {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
 /// JSON response sent from host back to WASM guest.
 #[derive(serde::Serialize)]
 struct GuestFetchResponse {
     status: u16,
     headers: HashMap<String, String>,
     body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_base64: Option<String>,
     ok: bool,
     error: Option<String>,
+    /// What kind of failure `error` is, from `NetworkError::kind`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<&'static str>,
 }
 
-/// Store data combining WASI context with resource limits and fetch state.
+/// Store data combining WASI context with resource limits and host bridge state.
 struct SandboxState {
     wasi: wasmtime_wasi::p1::WasiP1Ctx,
-    limits: StoreLimits,
-    fetch_client: Option<Arc<SafeClient>>,
+    limits: MemoryLimiter,
+    launcher: Arc<Launcher>,
+    fuel: FuelMeter,
+    /// The instance's own standard streams, shared with children that inherit them.
+    stdio: InstanceStdio,
     fetch_response: Option<Vec<u8>>,
-    tokio_handle: Option<tokio::runtime::Handle>,
+    host_response: Option<Vec<u8>>,
+    policy_response: Option<Vec<u8>>,
+    spawn_response: Option<Vec<u8>>,
+    /// Signals sent to this instance from outside.
+    signals: Arc<Signals>,
+    /// Child pipelines started with `__sandbox_spawn` that have not been waited for.
+    jobs: HashMap<i32, spawn::PipelineJob>,
+    next_job: i32,
+    report: Option<Vec<u8>>,
+}
+
+/// Host services the guest reaches through the `sandbox` imports.
+#[derive(Clone)]
+struct HostBridge {
+    fetch_client: Option<Arc<MeteredClient>>,
+    host_functions: HostFunctions,
+    command_guard: Arc<CommandGuard>,
+    tokio_handle: tokio::runtime::Handle,
+}
+
+/// Everything needed to start an instance of the toolbox module. Kept in the
+/// store so the guest shell can start child instances for pipeline stages.
+struct Launcher {
+    engine: &'static Engine,
+    module: &'static Module,
+    config: Arc<SandboxConfig>,
+    bridge: HostBridge,
+    /// Mounted at `/dev/fd` in every instance, where the guest shell keeps
+    /// the files standing for process substitutions. Removed once the
+    /// command and all its child instances are done.
+    pipe_dir: tempfile::TempDir,
+    /// Commands the shell reported running, when tracing is on.
+    trace: Option<Mutex<Vec<TracedCommand>>>,
+    /// Fuel, memory and child slots shared by every instance of the command.
+    budget: Arc<Budget>,
+}
+
+/// Standard streams handed to one instance of the toolbox module.
+#[derive(Clone)]
+struct InstanceStdio {
+    stdin: Arc<dyn StdinStream + Sync>,
+    stdout: Arc<dyn StdoutStream + Sync>,
+    stderr: Arc<dyn StdoutStream + Sync>,
 }
 
 /// Cached WASM engine and compiled module shared across all Sandbox instances.
@@ -75,6 +200,9 @@ fn get_or_compile_module() -> Result<(&'static Engine, &'static Module)> {
         // Engine config MUST match build.rs exactly
         let mut engine_config = Config::new();
         engine_config.consume_fuel(true);
+        // Epochs let `kill` interrupt a running child instance, and let
+        // instances settle the fuel they share
+        engine_config.epoch_interruption(true);
 
         let engine =
             Engine::new(&engine_config).map_err(|e| format!("engine creation failed: {e}"))?;
@@ -98,13 +226,20 @@ pub struct WasiRuntime {
     engine: &'static Engine,
     module: &'static Module,
     config: Arc<SandboxConfig>,
-    fetch_client: Option<Arc<SafeClient>>,
+    fetch_client: Option<Arc<MeteredClient>>,
+    host_functions: HostFunctions,
+    command_guard: Arc<CommandGuard>,
 }
 
 impl WasiRuntime {
     /// Create a new WASI runtime with the given sandbox config.
     /// The toolbox WASM binary is compiled once and cached globally.
-    pub fn new(config: SandboxConfig, fetch_client: Option<Arc<SafeClient>>) -> Result<Self> {
+    pub fn new(
+        config: SandboxConfig,
+        fetch_client: Option<Arc<MeteredClient>>,
+        host_functions: HostFunctions,
+        command_guard: Arc<CommandGuard>,
+    ) -> Result<Self> {
         let (engine, module) = get_or_compile_module()?;
 
         Ok(Self {
@@ -112,38 +247,69 @@ impl WasiRuntime {
             module,
             config: Arc::new(config),
             fetch_client,
+            host_functions,
+            command_guard,
         })
     }
 
     /// Execute a command inside the WASM sandbox.
     pub async fn exec(&self, command: &str, args: &[String]) -> Result<ExecResult> {
-        let config = self.config.clone();
-        let engine = self.engine;
-        let module = self.module;
+        self.run(command, args).await.map(|outcome| outcome.result)
+    }
+
+    /// Execute a command, also returning the result it reported to the host.
+    pub(crate) async fn run(&self, command: &str, args: &[String]) -> Result<ExecOutcome> {
         let command = command.to_string();
         let args = args.to_vec();
-        let timeout = config.timeout;
-        let fetch_client = self.fetch_client.clone();
-        let tokio_handle = tokio::runtime::Handle::current();
+        let timeout = self.config.timeout;
+        let launcher = Arc::new(Launcher {
+            engine: self.engine,
+            module: self.module,
+            config: self.config.clone(),
+            bridge: HostBridge {
+                fetch_client: self.fetch_client.clone(),
+                host_functions: self.host_functions.clone(),
+                command_guard: self.command_guard.clone(),
+                tokio_handle: tokio::runtime::Handle::current(),
+            },
+            pipe_dir: tempfile::tempdir()?,
+            trace: self.config.trace_commands.then(Mutex::default),
+            budget: Budget::new(
+                self.config.fuel_limit,
+                self.config.memory_limit_bytes as usize,
+            ),
+        });
+
+        let signals = Arc::new(Signals::default());
+        let engine = self.engine;
 
         // Run in blocking thread since Wasmtime is synchronous, with a wall-clock timeout
-        let task = tokio::task::spawn_blocking(move || {
-            exec_sync(
-                engine,
-                module,
-                &config,
-                &command,
-                &args,
-                fetch_client,
-                tokio_handle,
-            )
+        let task_signals = signals.clone();
+        let _ticker = FuelTicker::start(engine);
+        let mut task = tokio::task::spawn_blocking(move || {
+            exec_sync(&launcher, &command, &args, &task_signals)
         });
 
-        match tokio::time::timeout(timeout, task).await {
+        // If the caller gives up on this future, interrupt the command
+        let mut interrupt = InterruptOnDrop {
+            engine,
+            signals: signals.clone(),
+            armed: true,
+        };
+
+        let outcome = match tokio::time::timeout(timeout, &mut task).await {
             Ok(Ok(result)) => result,
             Ok(Err(e)) => Err(SandboxError::Other(format!("task join error: {}", e))),
-            Err(_) => Err(SandboxError::Timeout(timeout)),
-        }
+            Err(_) => {
+                // Let the shell run its traps before stopping it for good
+                signals.send(engine, signal::SIGTERM);
+                let _ = tokio::time::timeout(signal::GRACE_PERIOD, &mut task).await;
+                signals.kill(engine, signal::SIGTERM);
+                Err(SandboxError::Timeout(timeout))
+            }
+        };
+        interrupt.armed = false;
+        outcome
     }
 }
 
@@ -188,37 +354,100 @@ fn write_guest_memory(caller: &mut Caller<'_, SandboxState>, ptr: i32, buf: &[u8
 }
 
 fn exec_sync(
-    engine: &Engine,
-    module: &Module,
-    config: &SandboxConfig,
+    launcher: &Arc<Launcher>,
     command: &str,
     args: &[String],
-    fetch_client: Option<Arc<SafeClient>>,
-    tokio_handle: tokio::runtime::Handle,
-) -> Result<ExecResult> {
+    signals: &Arc<Signals>,
+) -> Result<ExecOutcome> {
     // Build argv: [command, ...args]
     let mut argv: Vec<String> = vec![command.to_string()];
     argv.extend(args.iter().cloned());
 
-    let argv_refs: Vec<&str> = argv.iter().map(|s| s.as_str()).collect();
-
     // Set up stdout/stderr capture via MemoryOutputPipe
     let stdout_pipe = MemoryOutputPipe::new(1024 * 1024); // 1MB capacity
     let stderr_pipe = MemoryOutputPipe::new(1024 * 1024);
+    let stdio = InstanceStdio {
+        // Empty stdin — prevents blocking on host stdin
+        stdin: Arc::new(MemoryInputPipe::new(b"" as &[u8])),
+        stdout: Arc::new(stdout_pipe.clone()),
+        stderr: Arc::new(stderr_pipe.clone()),
+    };
+
+    // Set user-configured env vars
+    let env: Vec<(String, String)> = launcher
+        .config
+        .env_vars
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    let exit = run_instance(launcher, &argv, &env, None, stdio, signals)?;
+
+    let stdout_bytes = stdout_pipe.contents().to_vec();
+    let stderr_bytes = stderr_pipe.contents().to_vec();
+    let trace = launcher
+        .trace
+        .as_ref()
+        .map(|trace| std::mem::take(&mut *trace.lock().unwrap()))
+        .unwrap_or_default();
+
+    Ok(ExecOutcome {
+        result: ExecResult {
+            exit_code: exit.code,
+            stdout: stdout_bytes,
+            stderr: stderr_bytes,
+            trace,
+        },
+        report: exit.report,
+    })
+}
+
+/// How an instance of the toolbox module exited.
+struct InstanceExit {
+    code: i32,
+    /// Anything it reported through `__sandbox_report`.
+    report: Option<Vec<u8>>,
+    /// Fuel it consumed.
+    fuel_used: u64,
+}
+
+/// Run one instance of the toolbox module to completion with `argv` and the
+/// given environment. An instance started as a subshell of the guest shell
+/// is handed the state it runs in `subshell`.
+///
+/// Killing the instance through `signals` interrupts it, and it then fails
+/// with `Trap::Interrupt`.
+fn run_instance(
+    launcher: &Arc<Launcher>,
+    argv: &[String],
+    env: &[(String, String)],
+    subshell: Option<&str>,
+    stdio: InstanceStdio,
+    signals: &Arc<Signals>,
+) -> Result<InstanceExit> {
+    let config = &launcher.config;
+    let engine = launcher.engine;
+    let argv_refs: Vec<&str> = argv.iter().map(|s| s.as_str()).collect();
 
     // Build WASI context using WasiCtx::builder()
     let mut builder = WasiCtx::builder();
     builder.args(&argv_refs);
-    builder.stdin(MemoryInputPipe::new(b"" as &[u8])); // Empty stdin — prevents blocking on host stdin
-    builder.stdout(stdout_pipe.clone());
-    builder.stderr(stderr_pipe.clone());
+    builder.stdin(stdio.stdin.clone());
+    builder.stdout(stdio.stdout.clone());
+    builder.stderr(stdio.stderr.clone());
 
     // Set TOOLBOX_CMD env var for BusyBox-style dispatch
-    builder.env("TOOLBOX_CMD", command);
+    builder.env("TOOLBOX_CMD", &argv[0]);
 
-    // Set user-configured env vars
-    for (key, value) in &config.env_vars {
-        builder.env(key, value);
+    // Only the host starts subshells, so a state in the given environment
+    // is never passed on
+    for (key, value) in env {
+        if key != "TOOLBOX_CMD" && key != "TOOLBOX_SUBSHELL" {
+            builder.env(key, value);
+        }
+    }
+    if let Some(state) = subshell {
+        builder.env("TOOLBOX_SUBSHELL", state);
     }
 
     // Mount work directory
@@ -232,6 +461,7 @@ fn exec_sync(
     let dir = wasmtime_wasi::DirPerms::all();
     let file = wasmtime_wasi::FilePerms::all();
     builder.preopened_dir(&work_dir, "/work", dir, file)?;
+    builder.preopened_dir(launcher.pipe_dir.path(), "/dev/fd", dir, file)?;
 
     // Mount additional directories
     for mount in &config.mounts {
@@ -260,30 +490,56 @@ fn exec_sync(
     // Build the WASIp1 context
     let wasi_p1 = builder.build_p1();
 
-    // Build memory limiter
-    let limits = StoreLimitsBuilder::new()
-        .memory_size(config.memory_limit_bytes as usize)
-        .build();
-
     let mut store = Store::new(
         engine,
         SandboxState {
             wasi: wasi_p1,
-            limits,
-            fetch_client,
+            limits: MemoryLimiter::new(&launcher.budget),
+            launcher: launcher.clone(),
+            fuel: FuelMeter::new(&launcher.budget),
+            stdio,
             fetch_response: None,
-            tokio_handle: Some(tokio_handle),
+            host_response: None,
+            policy_response: None,
+            spawn_response: None,
+            signals: signals.clone(),
+            jobs: HashMap::new(),
+            next_job: 1,
+            report: None,
         },
     );
     store.limiter(|state| &mut state.limits);
 
-    // Set fuel limit
-    store.set_fuel(config.fuel_limit)?;
+    let fuel = store.data_mut().fuel.settle(0);
+    store.set_fuel(fuel)?;
+
+    // The epoch moves on every fuel tick and whenever a signal is sent;
+    // settle up, and stop if a signal was aimed here
+    let signals = signals.clone();
+    store.epoch_deadline_callback(move |mut store| {
+        if signals.stopped_by() != 0 {
+            return Ok(UpdateDeadline::Interrupt);
+        }
+        let left = store.get_fuel()?;
+        let fuel = store.data_mut().fuel.settle(left);
+        store.set_fuel(fuel)?;
+        Ok(UpdateDeadline::Continue(1))
+    });
+    store.set_epoch_deadline(1);
 
     // Link WASI p1 and instantiate
     let mut linker = Linker::new(engine);
     wasmtime_wasi::p1::add_to_linker_sync(&mut linker, |state: &mut SandboxState| &mut state.wasi)?;
 
+    // WASI only allows exit statuses below 126, but shell statuses such as
+    // 127 (command not found) and 141 (broken pipe) must reach the caller
+    linker.allow_shadowing(true);
+    linker.func_wrap(
+        "wasi_snapshot_preview1",
+        "proc_exit",
+        |code: i32| -> wasmtime::Result<()> { Err(wasmtime_wasi::I32Exit(code).into()) },
+    )?;
+
     // Link sandbox host functions for fetch bridge
     linker.func_wrap(
         "sandbox",
@@ -300,7 +556,7 @@ fn exec_sync(
                 Err(_) => return -1,
             };
 
-            let client = match caller.data().fetch_client.as_ref() {
+            let client = match caller.data().launcher.bridge.fetch_client.as_ref() {
                 Some(c) => c.clone(),
                 None => {
                     // Networking disabled — store error response
@@ -308,43 +564,64 @@ fn exec_sync(
                         status: 0,
                         headers: HashMap::new(),
                         body: String::new(),
+                        body_base64: None,
                         ok: false,
                         error: Some("networking disabled: configure fetch_policy to enable".into()),
+                        kind: None,
                     };
                     caller.data_mut().fetch_response = Some(serde_json::to_vec(&resp).unwrap());
                     return -2;
                 }
             };
 
-            let handle = match caller.data().tokio_handle.as_ref() {
-                Some(h) => h.clone(),
-                None => return -1,
+            let handle = caller.data().launcher.bridge.tokio_handle.clone();
+
+            let body = match guest_req.body_base64 {
+                Some(encoded) => match BASE64.decode(encoded) {
+                    Ok(bytes) => Some(bytes),
+                    Err(_) => return -1,
+                },
+                None => guest_req.body.map(|s| s.into_bytes()),
             };
+            let binary = guest_req.binary;
 
             let fetch_req = agent_fetch::FetchRequest {
                 url: guest_req.url,
                 method: guest_req.method,
                 headers: guest_req.headers,
-                body: guest_req.body.map(|s| s.into_bytes()),
+                body,
             };
 
             // Bridge async fetch to sync context via the tokio handle
             let result = std::thread::scope(|_| handle.block_on(client.fetch(fetch_req)));
 
             let resp = match result {
+                Ok(r) if binary => GuestFetchResponse {
+                    status: r.status,
+                    headers: r.headers,
+                    body: String::new(),
+                    body_base64: Some(BASE64.encode(&r.body)),
+                    ok: (200..300).contains(&(r.status as u32)),
+                    error: None,
+                    kind: None,
+                },
                 Ok(r) => GuestFetchResponse {
                     status: r.status,
                     headers: r.headers,
                     body: String::from_utf8_lossy(&r.body).to_string(),
+                    body_base64: None,
                     ok: (200..300).contains(&(r.status as u32)),
                     error: None,
+                    kind: None,
                 },
                 Err(e) => GuestFetchResponse {
                     status: 0,
                     headers: HashMap::new(),
                     body: String::new(),
+                    body_base64: None,
                     ok: false,
                     error: Some(e.to_string()),
+                    kind: Some(e.kind()),
                 },
             };
 
@@ -386,14 +663,314 @@ fn exec_sync(
         },
     )?;
 
-    linker.module(&mut store, "", module)?;
+    // Link the host function bridge
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_host_call",
+        |mut caller: Caller<'_, SandboxState>, req_ptr: i32, req_len: i32| -> i32 {
+            let req_bytes = match read_guest_memory(&mut caller, req_ptr, req_len) {
+                Some(b) => b,
+                None => return -1,
+            };
+            let call: GuestHostCall = match serde_json::from_slice(&req_bytes) {
+                Ok(c) => c,
+                Err(_) => return -1,
+            };
+            let handle = caller.data().launcher.bridge.tokio_handle.clone();
+            let functions = caller.data().launcher.bridge.host_functions.clone();
+
+            // Bridge the async host function to sync context via the tokio handle
+            let result =
+                std::thread::scope(|_| handle.block_on(functions.call(&call.name, call.args)));
+            let resp = match result {
+                Ok(value) => serde_json::json!({ "ok": value }),
+                Err(message) => serde_json::json!({ "error": message }),
+            };
+            caller.data_mut().host_response = Some(serde_json::to_vec(&resp).unwrap());
+            0
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_host_response_len",
+        |caller: Caller<'_, SandboxState>| -> i32 {
+            caller
+                .data()
+                .host_response
+                .as_ref()
+                .map(|r| r.len() as i32)
+                .unwrap_or(0)
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_host_response_read",
+        |mut caller: Caller<'_, SandboxState>, buf_ptr: i32, buf_len: i32| -> i32 {
+            if buf_ptr < 0 || buf_len < 0 {
+                return -1;
+            }
+            let resp = match caller.data().host_response.as_ref() {
+                Some(r) => r.clone(),
+                None => return -1,
+            };
+            let copy_len = std::cmp::min(resp.len(), buf_len as usize);
+            if write_guest_memory(&mut caller, buf_ptr, &resp[..copy_len]) {
+                copy_len as i32
+            } else {
+                -1
+            }
+        },
+    )?;
+
+    // Link the command policy check the guest makes before running any tool
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_check_command",
+        |mut caller: Caller<'_, SandboxState>, req_ptr: i32, req_len: i32| -> i32 {
+            let req_bytes = match read_guest_memory(&mut caller, req_ptr, req_len) {
+                Some(b) => b,
+                None => return -1,
+            };
+            let check: GuestCommandCheck = match serde_json::from_slice(&req_bytes) {
+                Ok(c) => c,
+                Err(_) => return -1,
+            };
+            let guard = caller.data().launcher.bridge.command_guard.clone();
+            match guard.check(&check.command, &check.args) {
+                Ok(()) => 0,
+                Err(reason) => {
+                    let resp = serde_json::json!({ "error": reason });
+                    caller.data_mut().policy_response = Some(serde_json::to_vec(&resp).unwrap());
+                    -2
+                }
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_policy_response_len",
+        |caller: Caller<'_, SandboxState>| -> i32 {
+            caller
+                .data()
+                .policy_response
+                .as_ref()
+                .map(|r| r.len() as i32)
+                .unwrap_or(0)
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_policy_response_read",
+        |mut caller: Caller<'_, SandboxState>, buf_ptr: i32, buf_len: i32| -> i32 {
+            if buf_ptr < 0 || buf_len < 0 {
+                return -1;
+            }
+            let resp = match caller.data().policy_response.as_ref() {
+                Some(r) => r.clone(),
+                None => return -1,
+            };
+            let copy_len = std::cmp::min(resp.len(), buf_len as usize);
+            if write_guest_memory(&mut caller, buf_ptr, &resp[..copy_len]) {
+                copy_len as i32
+            } else {
+                -1
+            }
+        },
+    )?;
+
+    // Link the child-instance bridge the shell uses to run pipelines
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_spawn",
+        |mut caller: Caller<'_, SandboxState>, req_ptr: i32, req_len: i32| -> i32 {
+            let req_bytes = match read_guest_memory(&mut caller, req_ptr, req_len) {
+                Some(b) => b,
+                None => return -1,
+            };
+            let req: spawn::SpawnRequest = match serde_json::from_slice(&req_bytes) {
+                Ok(r) => r,
+                Err(_) => return -1,
+            };
+            let state = caller.data_mut();
+            match spawn::spawn_pipeline(&state.launcher, &state.stdio, &state.signals, req) {
+                Ok(pipeline) => {
+                    let job = state.next_job;
+                    state.next_job += 1;
+                    state.jobs.insert(job, pipeline);
+                    job
+                }
+                Err(message) => {
+                    let resp = serde_json::json!({ "error": message });
+                    state.spawn_response = Some(serde_json::to_vec(&resp).unwrap());
+                    -2
+                }
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_wait",
+        |mut caller: Caller<'_, SandboxState>, job: i32| -> i32 {
+            let state = caller.data_mut();
+            let pipeline = match state.jobs.remove(&job) {
+                Some(p) => p,
+                None => return -1,
+            };
+            let resp = match pipeline.wait() {
+                Some(resp) => serde_json::to_vec(&resp).unwrap(),
+                None => serde_json::to_vec(&serde_json::json!({
+                    "error": "pipeline thread panicked"
+                }))
+                .unwrap(),
+            };
+            state.spawn_response = Some(resp);
+            0
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_poll",
+        |caller: Caller<'_, SandboxState>, job: i32| -> i32 {
+            match caller.data().jobs.get(&job) {
+                Some(pipeline) => pipeline.is_finished() as i32,
+                None => -1,
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_kill",
+        |caller: Caller<'_, SandboxState>, job: i32, signal: i32| -> i32 {
+            if signal <= 0 {
+                return -1;
+            }
+            match caller.data().jobs.get(&job) {
+                Some(pipeline) => {
+                    pipeline.kill(caller.engine(), signal);
+                    0
+                }
+                None => -1,
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_take_signal",
+        |caller: Caller<'_, SandboxState>| -> i32 { caller.data().signals.take_pending() },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_spawn_response_len",
+        |caller: Caller<'_, SandboxState>| -> i32 {
+            caller
+                .data()
+                .spawn_response
+                .as_ref()
+                .map(|r| r.len() as i32)
+                .unwrap_or(0)
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_spawn_response_read",
+        |mut caller: Caller<'_, SandboxState>, buf_ptr: i32, buf_len: i32| -> i32 {
+            if buf_ptr < 0 || buf_len < 0 {
+                return -1;
+            }
+            let resp = match caller.data().spawn_response.as_ref() {
+                Some(r) => r.clone(),
+                None => return -1,
+            };
+            let copy_len = std::cmp::min(resp.len(), buf_len as usize);
+            if write_guest_memory(&mut caller, buf_ptr, &resp[..copy_len]) {
+                copy_len as i32
+            } else {
+                -1
+            }
+        },
+    )?;
+
+    // Link the execution trace bridge the shell reports commands through
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_trace_enabled",
+        |caller: Caller<'_, SandboxState>| -> i32 { caller.data().launcher.trace.is_some() as i32 },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_trace",
+        |mut caller: Caller<'_, SandboxState>, ptr: i32, len: i32| {
+            let Some(bytes) = read_guest_memory(&mut caller, ptr, len) else {
+                return;
+            };
+            let (Some(trace), Ok(entry)) = (
+                caller.data().launcher.trace.as_ref(),
+                serde_json::from_slice::<GuestTracedCommand>(&bytes),
+            ) else {
+                return;
+            };
+            trace.lock().unwrap().push(TracedCommand {
+                argv: entry.argv,
+                cwd: entry.cwd,
+                exit_code: entry.exit_code,
+                duration: Duration::from_micros(entry.duration_us),
+                fuel: entry.fuel,
+                redirections: entry.redirections,
+            });
+        },
+    )?;
+
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_fuel_used",
+        |caller: Caller<'_, SandboxState>| -> i64 {
+            let left = caller.get_fuel().unwrap_or(0);
+            caller.data().fuel.used(left) as i64
+        },
+    )?;
+
+    // Link the structured-result bridge used by `Sandbox::eval_js`
+    linker.func_wrap(
+        "sandbox",
+        "__sandbox_report",
+        |mut caller: Caller<'_, SandboxState>, ptr: i32, len: i32| {
+            if let Some(bytes) = read_guest_memory(&mut caller, ptr, len) {
+                caller.data_mut().report = Some(bytes);
+            }
+        },
+    )?;
+
+    linker.module(&mut store, "", launcher.module)?;
 
     // Get the default function (_start) and call it
     let func = linker
         .get_default(&mut store, "")?
         .typed::<(), ()>(&store)?;
 
-    let exit_code = match func.call(&mut store, ()) {
+    let outcome = func.call(&mut store, ());
+
+    // Charge what the instance burnt since it last settled up, however it ended
+    let left = store.get_fuel().unwrap_or(0);
+    let fuel_used = store.data().fuel.used(left);
+    store.data_mut().fuel.settle(left);
+
+    // Children must not outlive the instance that started them
+    for pipeline in store.data().jobs.values() {
+        pipeline.kill(engine, signal::SIGKILL);
+    }
+
+    let exit_code = match outcome {
         Ok(()) => 0,
         Err(e) => {
             // Check if it's a normal process exit
@@ -407,12 +984,9 @@ fn exec_sync(
         }
     };
 
-    let stdout_bytes = stdout_pipe.contents().to_vec();
-    let stderr_bytes = stderr_pipe.contents().to_vec();
-
-    Ok(ExecResult {
-        exit_code,
-        stdout: stdout_bytes,
-        stderr: stderr_bytes,
+    Ok(InstanceExit {
+        code: exit_code,
+        report: store.into_data().report,
+        fuel_used,
     })
 }
@@ -0,0 +1,111 @@
+//! Signals delivered to running instances.
+//!
+//! WASM code cannot be preempted by a real signal, so the host simulates
+//! them. A *pending* signal waits for the guest shell to take it with
+//! `__sandbox_take_signal` and run its traps; a *kill* interrupts the
+//! instance through the engine's epoch. Children stop as soon as any
+//! ancestor is sent either, so a shell blocked waiting for a pipeline gets
+//! control back.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+
+use wasmtime::Engine;
+
+/// Signal sent to a command whose caller stopped waiting for it.
+pub(super) const SIGINT: i32 = 2;
+
+/// Signal sent to pipelines still running when their parent exits.
+pub(super) const SIGKILL: i32 = 9;
+
+/// Signal sent to a command that ran out of time.
+pub(super) const SIGTERM: i32 = 15;
+
+/// How long a signalled command gets to run its traps before it is killed.
+pub(super) const GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Signals sent to one instance, or to all stages of one pipeline.
+#[derive(Default)]
+pub(super) struct Signals {
+    /// Signal the instance was killed with, or 0.
+    kill: AtomicI32,
+    /// Signal waiting for the guest to take, or 0.
+    pending: AtomicI32,
+    /// Signals of the instance that started this one.
+    parent: Option<Arc<Signals>>,
+}
+
+impl Signals {
+    /// Signals for instances started by the owner of `parent`.
+    pub(super) fn child_of(parent: &Arc<Signals>) -> Arc<Signals> {
+        Arc::new(Signals {
+            parent: Some(parent.clone()),
+            ..Signals::default()
+        })
+    }
+
+    /// Leave `signal` for the guest to handle, stopping its children.
+    pub(super) fn send(&self, engine: &Engine, signal: i32) {
+        self.pending.store(signal, Ordering::Relaxed);
+        engine.increment_epoch();
+    }
+
+    /// Take the signal left for the guest, if any.
+    pub(super) fn take_pending(&self) -> i32 {
+        self.pending.swap(0, Ordering::Relaxed)
+    }
+
+    /// Interrupt the instance. The first signal wins, as the instance may
+    /// already be stopping.
+    pub(super) fn kill(&self, engine: &Engine, signal: i32) {
+        let _ = self
+            .kill
+            .compare_exchange(0, signal, Ordering::Relaxed, Ordering::Relaxed);
+        engine.increment_epoch();
+    }
+
+    /// The signal the instance has to stop for, or 0: its own kill, or any
+    /// signal sent to an ancestor.
+    pub(super) fn stopped_by(&self) -> i32 {
+        match self.kill.load(Ordering::Relaxed) {
+            0 => self.parent.as_ref().map_or(0, |parent| parent.received()),
+            signal => signal,
+        }
+    }
+
+    /// Any signal sent to this instance or an ancestor, or 0.
+    fn received(&self) -> i32 {
+        match self.pending.load(Ordering::Relaxed) {
+            0 => self.stopped_by(),
+            signal => signal,
+        }
+    }
+}
+
+/// Sends SIGINT to a command, and kills it after the grace period, if it is
+/// dropped while still armed: the caller stopped waiting for the command.
+pub(super) struct InterruptOnDrop {
+    pub(super) engine: &'static Engine,
+    pub(super) signals: Arc<Signals>,
+    pub(super) armed: bool,
+}
+
+impl Drop for InterruptOnDrop {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        self.signals.send(self.engine, SIGINT);
+        let engine = self.engine;
+        let signals = self.signals.clone();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                tokio::time::sleep(GRACE_PERIOD).await;
+                signals.kill(engine, SIGINT);
+            });
+        } else {
+            signals.kill(engine, SIGINT);
+        }
+    }
+}
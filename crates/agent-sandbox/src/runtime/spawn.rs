@@ -0,0 +1,298 @@
+//! Child instances of the toolbox module.
+//!
+//! The guest shell runs the external stages of a pipeline through
+//! `__sandbox_spawn`: every stage becomes its own instance of the toolbox
+//! module on a host thread, and neighbouring stages are connected by bounded
+//! in-memory pipes. Stages run concurrently, so data streams through the
+//! pipeline, and when a reader exits its writer gets a broken pipe instead
+//! of blocking forever (`yes | head -1`).
+//!
+//! A pipeline can also be left running in the background and signalled with
+//! `__sandbox_kill`, which interrupts its stages through the engine's epoch.
+//! Stages also stop when their parent instance is sent a signal.
+//!
+//! Stages do not get limits of their own: their fuel and memory, and how
+//! many of them run at once, come out of the command's shared
+//! [`Budget`](super::limits::Budget).
+
+use std::io::{Seek, Write};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use wasmtime::Engine;
+use wasmtime_wasi::cli::{
+    AsyncStdinStream, AsyncStdoutStream, InputFile, StdinStream, StdoutStream,
+};
+use wasmtime_wasi::p2::pipe::{MemoryOutputPipe, SinkOutputStream};
+
+use super::signal::Signals;
+use super::{InstanceStdio, Launcher, run_instance};
+use crate::error::SandboxError;
+
+/// Capacity of the in-memory pipe between two pipeline stages.
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Most output a pipeline can hand back to the guest through a capture.
+const CAPTURE_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// Exit code of a stage that ran out of fuel, as if it had been killed.
+const EXIT_KILLED: i32 = 137;
+
+/// Exit code of a stage that trapped, as if it had aborted.
+const EXIT_ABORTED: i32 = 134;
+
+/// JSON request sent from the WASM guest to run a pipeline.
+#[derive(serde::Deserialize)]
+pub(super) struct SpawnRequest {
+    /// The argv of every stage, first to last.
+    stages: Vec<Vec<String>>,
+    /// The state handed to every stage that runs a subshell of the guest
+    /// shell, by position.
+    #[serde(default)]
+    subshells: Vec<Option<String>>,
+    /// Environment of every stage.
+    #[serde(default)]
+    env: Vec<(String, String)>,
+    /// Working directory of every stage.
+    #[serde(default)]
+    cwd: Option<String>,
+    /// Base64-encoded stdin of the first stage; absent to share the
+    /// caller's stdin.
+    #[serde(default)]
+    stdin: Option<String>,
+    /// Capture the last stage's stdout instead of sharing the caller's.
+    #[serde(default)]
+    capture_stdout: bool,
+    /// Capture every stage's stderr instead of sharing the caller's.
+    #[serde(default)]
+    capture_stderr: bool,
+    /// Throw away the last stage's stdout.
+    #[serde(default)]
+    discard_stdout: bool,
+    /// Throw away every stage's stderr.
+    #[serde(default)]
+    discard_stderr: bool,
+    /// Send every stage's stderr to the caller's stdout.
+    #[serde(default)]
+    stderr_to_stdout: bool,
+}
+
+/// JSON response sent back to the guest once a pipeline has finished.
+#[derive(serde::Serialize)]
+pub(super) struct SpawnResponse {
+    /// Exit code of every stage, first to last.
+    codes: Vec<i32>,
+    /// Base64-encoded captured stdout.
+    stdout: Option<String>,
+    /// Base64-encoded captured stderr.
+    stderr: Option<String>,
+    /// Stages that failed to run to completion, as `command: error`.
+    errors: Vec<String>,
+    /// How many bytes of the given stdin the first stage read, so the guest
+    /// can hand the rest to whatever runs next.
+    stdin_read: Option<u64>,
+    /// Fuel every stage consumed, for the guest's execution trace.
+    fuel: Vec<u64>,
+    /// How long every stage ran for, in microseconds.
+    durations_us: Vec<u64>,
+}
+
+/// A started pipeline that has not been waited for.
+pub(super) struct PipelineJob {
+    handle: JoinHandle<SpawnResponse>,
+    /// Signals shared by every stage.
+    signals: Arc<Signals>,
+}
+
+impl PipelineJob {
+    /// Whether every stage has exited.
+    pub(super) fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Interrupt every stage still running, which then exits with
+    /// `128 + signal`.
+    pub(super) fn kill(&self, engine: &Engine, signal: i32) {
+        if !self.is_finished() {
+            self.signals.kill(engine, signal);
+        }
+    }
+
+    /// Block until every stage has exited. Returns `None` if the pipeline
+    /// thread panicked.
+    pub(super) fn wait(self) -> Option<SpawnResponse> {
+        self.handle.join().ok()
+    }
+}
+
+/// Start the pipeline described by `req` on a background thread.
+///
+/// Stages inherit whichever of `parent`'s streams the request does not
+/// replace, and stop when the parent is signalled. The returned job yields
+/// the response once every stage exits.
+pub(super) fn spawn_pipeline(
+    launcher: &Arc<Launcher>,
+    parent: &InstanceStdio,
+    parent_signals: &Arc<Signals>,
+    req: SpawnRequest,
+) -> Result<PipelineJob, String> {
+    if req.stages.is_empty() || req.stages.iter().any(|argv| argv.is_empty()) {
+        return Err("empty pipeline stage".into());
+    }
+    let slots = launcher.budget.claim_children(req.stages.len())?;
+
+    // Pipe streams start tasks on the runtime, so make sure one is current
+    let _guard = launcher.bridge.tokio_handle.enter();
+
+    // Given stdin is served from a host temp file, whose offset records how
+    // much of it the first stage consumed
+    let mut stdin_file = None;
+    let mut stdin: Arc<dyn StdinStream + Sync> = match &req.stdin {
+        Some(encoded) => {
+            let bytes = BASE64
+                .decode(encoded)
+                .map_err(|e| format!("invalid stdin: {e}"))?;
+            let file =
+                stdin_temp_file(&bytes).map_err(|e| format!("failed to buffer stdin: {e}"))?;
+            stdin_file = Some(
+                file.try_clone()
+                    .map_err(|e| format!("failed to buffer stdin: {e}"))?,
+            );
+            Arc::new(InputFile::new(file))
+        }
+        None => parent.stdin.clone(),
+    };
+    let stdout_capture = req
+        .capture_stdout
+        .then(|| MemoryOutputPipe::new(CAPTURE_CAPACITY));
+    let stderr_capture = req
+        .capture_stderr
+        .then(|| MemoryOutputPipe::new(CAPTURE_CAPACITY));
+    let stdout: Arc<dyn StdoutStream + Sync> = match &stdout_capture {
+        Some(pipe) => Arc::new(pipe.clone()),
+        None if req.discard_stdout => Arc::new(SinkOutputStream),
+        None => parent.stdout.clone(),
+    };
+    let stderr: Arc<dyn StdoutStream + Sync> = match &stderr_capture {
+        Some(pipe) => Arc::new(pipe.clone()),
+        None if req.discard_stderr => Arc::new(SinkOutputStream),
+        None if req.stderr_to_stdout => parent.stdout.clone(),
+        None => parent.stderr.clone(),
+    };
+
+    // Connect each stage's stdout to the next stage's stdin
+    let last = req.stages.len() - 1;
+    let mut stdios = Vec::with_capacity(req.stages.len());
+    for i in 0..=last {
+        if i == last {
+            stdios.push(InstanceStdio {
+                stdin,
+                stdout,
+                stderr,
+            });
+            break;
+        }
+        let (writer, reader) = tokio::io::duplex(PIPE_CAPACITY);
+        stdios.push(InstanceStdio {
+            stdin,
+            stdout: Arc::new(AsyncStdoutStream::new(PIPE_CAPACITY, writer)),
+            stderr: stderr.clone(),
+        });
+        stdin = Arc::new(AsyncStdinStream::new(reader));
+    }
+
+    let mut env = req.env;
+    if let Some(cwd) = req.cwd {
+        env.push(("TOOLBOX_CWD".to_string(), cwd));
+    }
+    let stages = req.stages;
+    let subshells = req.subshells;
+    let launcher = launcher.clone();
+    let signals = Signals::child_of(parent_signals);
+    let stage_signals = signals.clone();
+
+    let handle = std::thread::Builder::new()
+        .name("sandbox-pipeline".into())
+        .spawn(move || {
+            let outcomes: Vec<_> = std::thread::scope(|scope| {
+                let running: Vec<_> = stages
+                    .iter()
+                    .zip(stdios)
+                    .zip(slots)
+                    .enumerate()
+                    .map(|(i, ((argv, stdio), slot))| {
+                        let subshell = subshells.get(i).and_then(Option::as_deref);
+                        let launcher = &launcher;
+                        let env = &env;
+                        let signals = &stage_signals;
+                        scope.spawn(move || {
+                            let _guard = launcher.bridge.tokio_handle.enter();
+                            let started = Instant::now();
+                            let exit = run_instance(launcher, argv, env, subshell, stdio, signals);
+                            drop(slot);
+                            (exit, started.elapsed())
+                        })
+                    })
+                    .collect();
+                running.into_iter().map(|stage| stage.join()).collect()
+            });
+
+            let killed = stage_signals.stopped_by();
+            let mut codes = Vec::with_capacity(outcomes.len());
+            let mut errors = Vec::new();
+            let mut fuel = Vec::with_capacity(outcomes.len());
+            let mut durations_us = Vec::with_capacity(outcomes.len());
+            for (argv, outcome) in stages.iter().zip(outcomes) {
+                let (outcome, elapsed) = match outcome {
+                    Ok((exit, elapsed)) => (Ok(exit), elapsed),
+                    Err(panic) => (Err(panic), Default::default()),
+                };
+                durations_us.push(elapsed.as_micros() as u64);
+                fuel.push(match &outcome {
+                    Ok(Ok(exit)) => exit.fuel_used,
+                    _ => 0,
+                });
+                let code = match outcome {
+                    Ok(Ok(exit)) => exit.code,
+                    Ok(Err(_)) if killed != 0 => 128 + killed,
+                    Ok(Err(SandboxError::Timeout(_))) => {
+                        errors.push(format!("{}: out of fuel", argv[0]));
+                        EXIT_KILLED
+                    }
+                    Ok(Err(e)) => {
+                        errors.push(format!("{}: {}", argv[0], e));
+                        EXIT_ABORTED
+                    }
+                    Err(_) => {
+                        errors.push(format!("{}: stage thread panicked", argv[0]));
+                        EXIT_ABORTED
+                    }
+                };
+                codes.push(code);
+            }
+
+            SpawnResponse {
+                codes,
+                stdout: stdout_capture.map(|pipe| BASE64.encode(pipe.contents())),
+                stderr: stderr_capture.map(|pipe| BASE64.encode(pipe.contents())),
+                errors,
+                stdin_read: stdin_file.and_then(|mut file| file.stream_position().ok()),
+                fuel,
+                durations_us,
+            }
+        })
+        .map_err(|e| format!("failed to start pipeline: {e}"))?;
+
+    Ok(PipelineJob { handle, signals })
+}
+
+/// An anonymous host file holding `bytes`, positioned at the start.
+fn stdin_temp_file(bytes: &[u8]) -> std::io::Result<std::fs::File> {
+    let mut file = tempfile::tempfile()?;
+    file.write_all(bytes)?;
+    file.rewind()?;
+    Ok(file)
+}
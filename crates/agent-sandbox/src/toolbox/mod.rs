@@ -61,8 +61,14 @@ pub const AVAILABLE_TOOLS: &[&str] = &[
     "git",
     // JavaScript runtime
     "node",
+    // Python runtime
+    "python",
+    "python3",
     // Networking
     "curl",
+    "wget",
+    // Host functions
+    "hostcall",
     // Shell utils
     "echo",
     "printf",
@@ -78,6 +84,7 @@ pub const AVAILABLE_TOOLS: &[&str] = &[
     "printenv",
     "date",
     "expr",
+    "yes",
     "true",
     "false",
     "test",
@@ -0,0 +1,7 @@
+fn main() {
+    // The default 1 MiB shadow stack is unguarded in wasm, so deep recursion in
+    // the Python and JavaScript interpreters would silently corrupt memory
+    if std::env::var("CARGO_CFG_TARGET_ARCH").as_deref() == Ok("wasm32") {
+        println!("cargo:rustc-link-arg-bins=-zstack-size=8388608");
+    }
+}
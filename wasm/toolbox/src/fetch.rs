@@ -1,5 +1,8 @@
 use std::collections::HashMap;
+use std::fmt;
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use serde_json::{Value, json};
 
 // Host-provided functions for the fetch bridge.
@@ -11,6 +14,59 @@ unsafe extern "C" {
     fn __sandbox_fetch_response_read(buf_ptr: i32, buf_len: i32) -> i32;
 }
 
+/// Why a fetch failed, as classified by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    InvalidUrl,
+    Dns,
+    /// Refused by the fetch policy or the network quota.
+    Blocked,
+    Timeout,
+    TooManyRedirects,
+    RequestTooLarge,
+    ResponseTooLarge,
+    Http,
+    Other,
+}
+
+impl ErrorKind {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "invalid_url" => ErrorKind::InvalidUrl,
+            "dns" => ErrorKind::Dns,
+            "blocked" => ErrorKind::Blocked,
+            "timeout" => ErrorKind::Timeout,
+            "too_many_redirects" => ErrorKind::TooManyRedirects,
+            "request_too_large" => ErrorKind::RequestTooLarge,
+            "response_too_large" => ErrorKind::ResponseTooLarge,
+            "http" => ErrorKind::Http,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// A failed fetch: the host's message plus its kind.
+#[derive(Debug, Clone)]
+pub struct FetchError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl FetchError {
+    fn other(message: impl Into<String>) -> Self {
+        FetchError {
+            kind: ErrorKind::Other,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
 /// Response from a fetch call.
 pub struct FetchResponse {
     pub status: u16,
@@ -19,13 +75,20 @@ pub struct FetchResponse {
     pub ok: bool,
 }
 
+/// Response from a binary-safe fetch call.
+pub struct RawFetchResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
 /// Perform an HTTP fetch via the host sandbox bridge.
 pub fn fetch(
     url: &str,
     method: &str,
     headers: &HashMap<String, String>,
     body: Option<&str>,
-) -> Result<FetchResponse, String> {
+) -> Result<FetchResponse, FetchError> {
     let req = json!({
         "url": url,
         "method": method,
@@ -33,61 +96,120 @@ pub fn fetch(
         "body": body,
     });
 
-    let req_bytes = serde_json::to_vec(&req).map_err(|e| format!("serialize error: {e}"))?;
+    let resp = call_host(&req)?;
+
+    let status = resp.get("status").and_then(|v| v.as_u64()).unwrap_or(0) as u16;
+
+    let headers: HashMap<String, String> = resp
+        .get("headers")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+
+    let body = resp
+        .get("body")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+
+    let ok = resp.get("ok").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    Ok(FetchResponse {
+        status,
+        headers,
+        body,
+        ok,
+    })
+}
+
+/// Perform an HTTP fetch with raw byte bodies in both directions.
+///
+/// Bodies cross the host bridge base64-encoded so binary payloads
+/// (archives, images) survive intact.
+pub fn fetch_bytes(
+    url: &str,
+    method: &str,
+    headers: &HashMap<String, String>,
+    body: Option<&[u8]>,
+) -> Result<RawFetchResponse, FetchError> {
+    let req = json!({
+        "url": url,
+        "method": method,
+        "headers": headers,
+        "body_base64": body.map(|b| BASE64.encode(b)),
+        "binary": true,
+    });
+
+    let resp = call_host(&req)?;
+
+    let status = resp.get("status").and_then(|v| v.as_u64()).unwrap_or(0) as u16;
+
+    let headers: HashMap<String, String> = resp
+        .get("headers")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+
+    let body = match resp.get("body_base64").and_then(|v| v.as_str()) {
+        Some(encoded) => BASE64
+            .decode(encoded)
+            .map_err(|e| FetchError::other(format!("deserialize error: {e}")))?,
+        None => resp
+            .get("body")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .as_bytes()
+            .to_vec(),
+    };
+
+    Ok(RawFetchResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Send a JSON request over the host bridge and return the parsed JSON response.
+fn call_host(req: &Value) -> Result<Value, FetchError> {
+    let req_bytes = serde_json::to_vec(req)
+        .map_err(|e| FetchError::other(format!("serialize error: {e}")))?;
 
     let result = unsafe { __sandbox_fetch(req_bytes.as_ptr() as i32, req_bytes.len() as i32) };
 
     if result == -1 {
-        return Err("fetch bridge error: failed to communicate with host".into());
+        return Err(FetchError::other(
+            "fetch bridge error: failed to communicate with host",
+        ));
     }
 
     // Read response length
     let resp_len = unsafe { __sandbox_fetch_response_len() };
     if resp_len <= 0 {
-        return Err("fetch bridge error: empty response".into());
+        return Err(FetchError::other("fetch bridge error: empty response"));
     }
 
     // Read response bytes
     let mut resp_buf = vec![0u8; resp_len as usize];
     let read = unsafe { __sandbox_fetch_response_read(resp_buf.as_mut_ptr() as i32, resp_len) };
     if read < 0 {
-        return Err("fetch bridge error: failed to read response".into());
+        return Err(FetchError::other(
+            "fetch bridge error: failed to read response",
+        ));
     }
     resp_buf.truncate(read as usize);
 
     // Parse response JSON
-    let resp: Value =
-        serde_json::from_slice(&resp_buf).map_err(|e| format!("deserialize error: {e}"))?;
+    let resp: Value = serde_json::from_slice(&resp_buf)
+        .map_err(|e| FetchError::other(format!("deserialize error: {e}")))?;
 
     // Check for error field
-    if let Some(err) = resp.get("error").and_then(|v| v.as_str()) {
-        if !err.is_empty() {
-            return Err(err.to_string());
-        }
+    if let Some(err) = resp.get("error").and_then(|v| v.as_str())
+        && !err.is_empty()
+    {
+        let kind = resp.get("kind").and_then(|v| v.as_str()).unwrap_or("");
+        return Err(FetchError {
+            kind: ErrorKind::from_name(kind),
+            message: err.to_string(),
+        });
     }
 
-    let status = resp
-        .get("status")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0) as u16;
-
-    let headers: HashMap<String, String> = resp
-        .get("headers")
-        .and_then(|v| serde_json::from_value(v.clone()).ok())
-        .unwrap_or_default();
-
-    let body = resp
-        .get("body")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
-
-    let ok = resp.get("ok").and_then(|v| v.as_bool()).unwrap_or(false);
-
-    Ok(FetchResponse {
-        status,
-        headers,
-        body,
-        ok,
-    })
+    Ok(resp)
 }
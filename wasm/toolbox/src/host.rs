@@ -0,0 +1,74 @@
+use std::sync::OnceLock;
+
+use serde_json::{Value, json};
+
+// Host-provided functions for host calls and returning structured results.
+// These are linked from the "sandbox" module by the Wasmtime host.
+#[link(wasm_import_module = "sandbox")]
+unsafe extern "C" {
+    fn __sandbox_host_call(req_ptr: i32, req_len: i32) -> i32;
+    fn __sandbox_host_response_len() -> i32;
+    fn __sandbox_host_response_read(buf_ptr: i32, buf_len: i32) -> i32;
+    fn __sandbox_report(ptr: i32, len: i32);
+    fn __sandbox_trace_enabled() -> i32;
+    fn __sandbox_trace(ptr: i32, len: i32);
+    fn __sandbox_fuel_used() -> i64;
+}
+
+/// Call a function the embedder registered with `Sandbox::register_host_function`.
+///
+/// Returns its JSON result, or the error message it failed with.
+pub fn call(name: &str, args: &Value) -> Result<Value, String> {
+    let req = json!({ "name": name, "args": args });
+    let req_bytes = serde_json::to_vec(&req).map_err(|e| format!("serialize error: {e}"))?;
+
+    let result = unsafe { __sandbox_host_call(req_bytes.as_ptr() as i32, req_bytes.len() as i32) };
+    if result < 0 {
+        return Err("host bridge error: failed to communicate with host".into());
+    }
+
+    let resp_len = unsafe { __sandbox_host_response_len() };
+    if resp_len <= 0 {
+        return Err("host bridge error: empty response".into());
+    }
+    let mut resp_buf = vec![0u8; resp_len as usize];
+    let read = unsafe { __sandbox_host_response_read(resp_buf.as_mut_ptr() as i32, resp_len) };
+    if read < 0 {
+        return Err("host bridge error: failed to read response".into());
+    }
+    resp_buf.truncate(read as usize);
+
+    let mut resp: Value =
+        serde_json::from_slice(&resp_buf).map_err(|e| format!("deserialize error: {e}"))?;
+    match resp.get("error").and_then(|v| v.as_str()) {
+        Some(err) => Err(err.to_string()),
+        None => Ok(resp["ok"].take()),
+    }
+}
+
+/// Hand a JSON result to the host, replacing any earlier one.
+///
+/// Used by `node` for `Sandbox::eval_js` and by `sh` for
+/// `Sandbox::parse_shell`; the host reads it back after the run instead of
+/// parsing stdout.
+pub fn report(json: &str) {
+    unsafe { __sandbox_report(json.as_ptr() as i32, json.len() as i32) }
+}
+
+/// Whether the host wants the shell's execution trace
+/// (`SandboxConfig::trace_commands`).
+pub fn trace_enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED.get_or_init(|| unsafe { __sandbox_trace_enabled() } == 1)
+}
+
+/// Add a command to the execution trace the host returns with the result.
+pub fn trace(entry: &Value) {
+    let json = entry.to_string();
+    unsafe { __sandbox_trace(json.as_ptr() as i32, json.len() as i32) }
+}
+
+/// Fuel this instance has consumed so far.
+pub fn fuel_used() -> u64 {
+    unsafe { __sandbox_fuel_used() }.max(0) as u64
+}
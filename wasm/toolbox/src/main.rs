@@ -1,5 +1,9 @@
 pub mod fetch;
+pub mod host;
+mod policy;
 mod shell;
+pub mod signal;
+pub mod spawn;
 mod tools;
 
 use std::env;
@@ -7,6 +11,39 @@ use std::env;
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    // A failed write to stdout means the reading end of a pipeline stage has
+    // exited; stop quietly with the broken-pipe status instead of panicking
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info
+            .payload()
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .unwrap_or_default();
+        if message.starts_with("failed printing to stdout") {
+            std::process::exit(141);
+        }
+        default_hook(info);
+    }));
+
+    // Child instances started by the shell run in its working directory
+    if let Ok(dir) = env::var("TOOLBOX_CWD") {
+        let _ = env::set_current_dir(dir);
+    }
+
+    // The host hands a subshell its state itself, never on the command line,
+    // so a script cannot start one with a state of its own. Like any other
+    // shell, it runs only if the command policy allows a bare `sh`
+    if let Ok(state) = env::var(shell::subshell::ENV) {
+        // SAFETY: WASM is single-threaded, so remove_var is safe
+        unsafe { env::remove_var(shell::subshell::ENV) };
+        if let Err(reason) = policy::check("sh", &[]) {
+            eprintln!("sh: command denied: {reason}");
+            std::process::exit(126);
+        }
+        std::process::exit(shell::subshell::run(&state, dispatch));
+    }
+
     // BusyBox-style dispatch: check argv[0] or TOOLBOX_CMD env var
     let cmd = env::var("TOOLBOX_CMD")
         .ok()
@@ -44,6 +81,14 @@ fn main() {
 }
 
 pub fn dispatch(cmd: &str, args: &[String]) -> i32 {
+    // Every tool, however it was started, runs only if the command policy
+    // allows it; parsing a script for `Sandbox::parse_shell` runs nothing
+    let parse_only = cmd == "sh" && args.first().is_some_and(|arg| arg == "--sandbox-ast");
+    if !parse_only && let Err(reason) = policy::check(cmd, args) {
+        eprintln!("{cmd}: command denied: {reason}");
+        return 126;
+    }
+
     match cmd {
         // File viewing
         "cat" => tools::cat::run(args),
@@ -114,6 +159,16 @@ pub fn dispatch(cmd: &str, args: &[String]) -> i32 {
         // JavaScript runtime
         "node" => tools::node::run(args),
 
+        // Python runtime
+        "python" | "python3" => tools::python::run(args),
+
+        // Networking
+        "curl" => tools::curl::run(args),
+        "wget" => tools::wget::run(args),
+
+        // Host functions
+        "hostcall" => tools::hostcall::run(args),
+
         // Shell utils
         "echo" => tools::echo::run(args),
         "printf" => tools::printf::run(args),
@@ -129,6 +184,7 @@ pub fn dispatch(cmd: &str, args: &[String]) -> i32 {
         "printenv" => tools::printenv::run(args),
         "date" => tools::date::run(args),
         "expr" => tools::expr::run(args),
+        "yes" => tools::yes::run(args),
         "true" => 0,
         "false" => 1,
         "test" | "[" => {
@@ -139,7 +195,7 @@ pub fn dispatch(cmd: &str, args: &[String]) -> i32 {
             } else {
                 &args_ref
             };
-            if eval_test_args(args_ref) { 0 } else { 1 }
+            if shell::builtins::eval_test(args_ref) { 0 } else { 1 }
         }
 
         // Shell interpreter
@@ -154,41 +210,6 @@ pub fn dispatch(cmd: &str, args: &[String]) -> i32 {
     }
 }
 
-fn eval_test_args(args: &[&str]) -> bool {
-    match args.len() {
-        0 => false,
-        1 => !args[0].is_empty(),
-        2 => match args[0] {
-            "-z" => args[1].is_empty(),
-            "-n" => !args[1].is_empty(),
-            "!" => !eval_test_args(&args[1..]),
-            "-e" | "-f" => std::path::Path::new(args[1]).exists(),
-            "-d" => std::path::Path::new(args[1]).is_dir(),
-            _ => !args[0].is_empty(),
-        },
-        _ => {
-            if args.len() >= 3 {
-                let left = args[0];
-                let op = args[1];
-                let right = args[2];
-                match op {
-                    "=" | "==" => left == right,
-                    "!=" => left != right,
-                    "-eq" => left.parse::<i64>().unwrap_or(0) == right.parse::<i64>().unwrap_or(0),
-                    "-ne" => left.parse::<i64>().unwrap_or(0) != right.parse::<i64>().unwrap_or(0),
-                    "-lt" => left.parse::<i64>().unwrap_or(0) < right.parse::<i64>().unwrap_or(0),
-                    "-le" => left.parse::<i64>().unwrap_or(0) <= right.parse::<i64>().unwrap_or(0),
-                    "-gt" => left.parse::<i64>().unwrap_or(0) > right.parse::<i64>().unwrap_or(0),
-                    "-ge" => left.parse::<i64>().unwrap_or(0) >= right.parse::<i64>().unwrap_or(0),
-                    _ => false,
-                }
-            } else {
-                false
-            }
-        }
-    }
-}
-
 fn print_available_commands() {
     let commands = [
         "cat", "head", "tail", "touch", "tee",
@@ -200,9 +221,11 @@ fn print_available_commands() {
         "ls", "mkdir", "cp", "mv", "rm", "du", "ln", "stat",
         "readlink", "rmdir", "split", "file",
         "tar", "gzip", "zip",
-        "git", "node",
+        "git", "node", "python", "python3",
+        "curl", "wget",
+        "hostcall",
         "echo", "printf", "env", "xargs", "basename", "dirname",
-        "seq", "sleep", "which", "whoami", "hostname", "printenv", "date", "expr",
+        "seq", "sleep", "which", "whoami", "hostname", "printenv", "date", "expr", "yes",
         "true", "false", "test", "[",
         "sh", "bash",
     ];
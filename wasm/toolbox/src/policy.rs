@@ -0,0 +1,41 @@
+use serde_json::{Value, json};
+
+// Host-provided functions for checking commands against the sandbox's
+// command policy. These are linked from the "sandbox" module by the Wasmtime host.
+#[link(wasm_import_module = "sandbox")]
+unsafe extern "C" {
+    fn __sandbox_check_command(req_ptr: i32, req_len: i32) -> i32;
+    fn __sandbox_policy_response_len() -> i32;
+    fn __sandbox_policy_response_read(buf_ptr: i32, buf_len: i32) -> i32;
+}
+
+/// Ask the host whether `cmd` may run with `args` under the sandbox's
+/// command policy.
+///
+/// Returns why not if it may not. A check that cannot be made refuses the
+/// command rather than letting it through.
+pub fn check(cmd: &str, args: &[String]) -> Result<(), String> {
+    let req = json!({ "command": cmd, "args": args });
+    let req_bytes = serde_json::to_vec(&req).map_err(|e| format!("serialize error: {e}"))?;
+
+    match unsafe { __sandbox_check_command(req_bytes.as_ptr() as i32, req_bytes.len() as i32) } {
+        0 => Ok(()),
+        -2 => Err(read_reason().unwrap_or_else(|| "command policy refused it".into())),
+        _ => Err("policy bridge error: failed to communicate with host".into()),
+    }
+}
+
+fn read_reason() -> Option<String> {
+    let len = unsafe { __sandbox_policy_response_len() };
+    if len <= 0 {
+        return None;
+    }
+    let mut buf = vec![0u8; len as usize];
+    let read = unsafe { __sandbox_policy_response_read(buf.as_mut_ptr() as i32, len) };
+    if read < 0 {
+        return None;
+    }
+    buf.truncate(read as usize);
+    let resp: Value = serde_json::from_slice(&buf).ok()?;
+    resp["error"].as_str().map(String::from)
+}
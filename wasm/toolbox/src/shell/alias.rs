@@ -0,0 +1,109 @@
+//! Aliases: `alias`, `unalias`, and running a command whose name is an
+//! alias.
+//!
+//! The script is parsed as a whole before anything runs, so an alias is
+//! looked up when its command runs rather than when it is read. That lets
+//! an alias defined earlier in the same `sh -c` script take effect.
+
+use super::ast::{SimpleCommand, WordPart};
+use super::builtins::BuiltinResult;
+use super::env::ShellEnv;
+use super::exec::{self, DispatchFn, ExecResult};
+
+/// `alias [name[=value]...]`: define aliases, or print them as the
+/// commands that would define them.
+pub fn builtin_alias(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let args = match args.first() {
+        Some(flag) if flag == "-p" => &args[1..],
+        _ => args,
+    };
+    if args.is_empty() {
+        for (name, value) in &env.aliases {
+            env.print(&format!("alias {}={}\n", name, quote(value)));
+        }
+        return BuiltinResult::code(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, value)) if is_alias_name(name) => {
+                env.aliases.insert(name.to_string(), value.to_string());
+            }
+            Some(_) => {
+                env.eprint(&format!("sh: alias: `{}': invalid alias name\n", arg));
+                status = 1;
+            }
+            None => match env.aliases.get(arg) {
+                Some(value) => env.print(&format!("alias {}={}\n", arg, quote(value))),
+                None => {
+                    env.eprint(&format!("sh: alias: {}: not found\n", arg));
+                    status = 1;
+                }
+            },
+        }
+    }
+    BuiltinResult::code(status)
+}
+
+/// `unalias [-a] name...`: remove aliases, or with `-a` all of them.
+pub fn builtin_unalias(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    if args.first().is_some_and(|arg| arg == "-a") {
+        env.aliases.clear();
+        return BuiltinResult::code(0);
+    }
+    let mut status = 0;
+    for name in args {
+        if env.aliases.remove(name).is_none() {
+            env.eprint(&format!("sh: unalias: {}: not found\n", name));
+            status = 1;
+        }
+    }
+    BuiltinResult::code(status)
+}
+
+/// Whether `name` can name an alias: it cannot be empty or contain
+/// whitespace, quotes or characters that expand.
+fn is_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '/' | '$' | '`' | '=' | '\'' | '"' | '\\'))
+}
+
+/// Quote a value in single quotes, as `alias` prints it.
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// The alias a command runs and the script it stands for: the alias's
+/// value followed by the command's other words, quoted. Only a command name
+/// written as a plain word is looked up, so `\ls` or `'ls'` runs the
+/// command itself.
+pub fn expansion(
+    cmd: &SimpleCommand,
+    words: &[String],
+    env: &ShellEnv,
+) -> Option<(String, String)> {
+    let [WordPart::Literal(name)] = cmd.words.first()?.parts.as_slice() else {
+        return None;
+    };
+    let mut script = env.aliases.get(name)?.clone();
+    for word in &words[1..] {
+        script.push(' ');
+        script.push_str(&quote(word));
+    }
+    Some((name.clone(), script))
+}
+
+/// Run the script an alias stands for. The alias is hidden meanwhile, so
+/// one that names the command it wraps, as `alias ls='ls -F'` does, runs
+/// that command rather than itself.
+pub fn run(name: &str, script: &str, env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
+    let value = env.aliases.remove(name);
+    let result = exec::exec_script(script, env, dispatch);
+    if let Some(value) = value {
+        env.aliases.entry(name.to_string()).or_insert(value);
+    }
+    result
+}
@@ -0,0 +1,481 @@
+//! Arithmetic evaluation for `$(( ))`, `(( ))`, `let` and array subscripts.
+//!
+//! Expressions use bash's operators and precedence on 64-bit integers,
+//! which wrap on overflow. Parameter expansion and command substitution
+//! have already happened by the time an expression gets here; variables
+//! named without `$` are looked up while evaluating, and a variable whose
+//! value is itself an expression is evaluated in turn.
+
+use super::env::{ShellEnv, Value};
+
+/// How deeply variables may refer to expressions in other variables.
+const MAX_DEPTH: usize = 64;
+
+/// Operators, longest first so that `<<=` is not read as `<` and `<=`.
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "+=", "-=", "*=", "/=", "%=", "&=", "^=", "|=", "<<", ">>",
+    "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~",
+    "?", ":", "=", ",", "(", ")",
+];
+
+/// Binary operators from the loosest binding to the tightest, above the
+/// ternary operator and below `**`.
+const BINARY_LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    /// A variable, with the subscript of an array element as written
+    Name(String, Option<String>),
+    Op(&'static str),
+}
+
+/// Evaluate an arithmetic expression. Errors are messages to print after
+/// `sh: `.
+pub fn eval(expr: &str, env: &mut ShellEnv) -> Result<i64, String> {
+    eval_nested(expr, env, 0)
+}
+
+fn eval_nested(expr: &str, env: &mut ShellEnv, depth: usize) -> Result<i64, String> {
+    if depth > MAX_DEPTH {
+        return Err(format!(
+            "{}: expression recursion level exceeded",
+            expr.trim()
+        ));
+    }
+    let tokens = tokenize(expr)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut parser = Parser {
+        expr,
+        tokens,
+        pos: 0,
+        env,
+        depth,
+    };
+    let value = parser.comma(true)?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.error("syntax error in expression"));
+    }
+    Ok(value)
+}
+
+/// Split an expression into tokens, each with the offset it starts at.
+fn tokenize(expr: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expr;
+    loop {
+        rest = rest.trim_start();
+        let offset = expr.len() - rest.len();
+        let Some(c) = rest.chars().next() else {
+            return Ok(tokens);
+        };
+
+        let (token, len) = if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '#' || c == '@' || c == '_'))
+                .unwrap_or(rest.len());
+            (Token::Number(parse_number(&rest[..len])?), len)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let name = rest[..len].to_string();
+            let mut subscript = None;
+            if rest[len..].starts_with('[') {
+                let close = matching_bracket(&rest[len..])
+                    .ok_or_else(|| format!("{}: bad array subscript", expr.trim()))?;
+                subscript = Some(rest[len + 1..len + close].to_string());
+                len += close + 1;
+            }
+            (Token::Name(name, subscript), len)
+        } else {
+            match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                Some(op) => (Token::Op(op), op.len()),
+                None => {
+                    return Err(format!(
+                        "{}: syntax error: invalid arithmetic operator (error token is \"{}\")",
+                        expr.trim(),
+                        rest.trim_end()
+                    ));
+                }
+            }
+        };
+        tokens.push((token, offset));
+        rest = &rest[len..];
+    }
+}
+
+/// The offset of the `]` closing the `[` that `s` starts with.
+fn matching_bracket(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse an integer constant: decimal, octal with a leading `0`, hex with
+/// `0x`, or `base#digits` for bases 2 to 64.
+fn parse_number(text: &str) -> Result<i64, String> {
+    let invalid = || {
+        format!(
+            "{}: value too great for base (error token is \"{}\")",
+            text, text
+        )
+    };
+    let (base, digits) = if let Some((base, digits)) = text.split_once('#') {
+        match base.parse::<u32>() {
+            Ok(base) if (2..=64).contains(&base) => (base, digits),
+            _ => return Err(format!("{}: invalid arithmetic base", text)),
+        }
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        (16, hex)
+    } else if text.len() > 1 && text.starts_with('0') {
+        (8, &text[1..])
+    } else {
+        (10, text)
+    };
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            // Up to base 36, letters of either case are the same digit
+            'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err(invalid()),
+        };
+        if digit >= base {
+            return Err(invalid());
+        }
+        value = value.wrapping_mul(base as i64).wrapping_add(digit as i64);
+    }
+    Ok(value)
+}
+
+/// Apply a binary operator, which `=` compound assignments share.
+fn apply(op: &str, l: i64, r: i64) -> Result<i64, &'static str> {
+    Ok(match op {
+        "+" => l.wrapping_add(r),
+        "-" => l.wrapping_sub(r),
+        "*" => l.wrapping_mul(r),
+        "/" | "%" if r == 0 => return Err("division by 0"),
+        "/" => l.wrapping_div(r),
+        "%" => l.wrapping_rem(r),
+        "**" if r < 0 => return Err("exponent less than 0"),
+        "**" => l.wrapping_pow(r.min(u32::MAX as i64) as u32),
+        "<<" => l.wrapping_shl(r as u32),
+        ">>" => l.wrapping_shr(r as u32),
+        "&" => l & r,
+        "^" => l ^ r,
+        "|" => l | r,
+        "==" => (l == r) as i64,
+        "!=" => (l != r) as i64,
+        "<" => (l < r) as i64,
+        ">" => (l > r) as i64,
+        "<=" => (l <= r) as i64,
+        ">=" => (l >= r) as i64,
+        _ => unreachable!("not a binary operator: {}", op),
+    })
+}
+
+/// A recursive descent parser that evaluates as it goes. Each method takes
+/// whether to evaluate for real: the untaken side of `&&`, `||` and `?:` is
+/// parsed without assigning anything or failing on division by zero.
+struct Parser<'a> {
+    expr: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    env: &'a mut ShellEnv,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = self.peek_op() == Some(op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// An error message naming the rest of the expression from the current
+    /// token, or from `pos` if given.
+    fn error_at(&self, message: &str, pos: usize) -> String {
+        let offset = self
+            .tokens
+            .get(pos)
+            .map_or(self.expr.len(), |(_, offset)| *offset);
+        let rest = self.expr[offset..].trim();
+        format!(
+            "{}: {} (error token is \"{}\")",
+            self.expr.trim(),
+            message,
+            rest
+        )
+    }
+
+    fn error(&self, message: &str) -> String {
+        self.error_at(message, self.pos)
+    }
+
+    /// `a, b`: evaluates both, giving `b`.
+    fn comma(&mut self, run: bool) -> Result<i64, String> {
+        let mut value = self.assignment(run)?;
+        while self.eat(",") {
+            value = self.assignment(run)?;
+        }
+        Ok(value)
+    }
+
+    /// `x = a`, `x += a` and the other compound assignments.
+    fn assignment(&mut self, run: bool) -> Result<i64, String> {
+        let op = match self.tokens.get(self.pos + 1) {
+            Some((Token::Op(op), _))
+                if op.ends_with('=') && !matches!(*op, "==" | "!=" | "<=" | ">=") =>
+            {
+                *op
+            }
+            _ => return self.ternary(run),
+        };
+        let Some(Token::Name(name, subscript)) = self.peek().cloned() else {
+            return Err(self.error_at("attempted assignment to non-variable", self.pos + 1));
+        };
+        self.pos += 2;
+        let start = self.pos;
+        let r = self.assignment(run)?;
+        if !run {
+            return Ok(0);
+        }
+        let value = match op.strip_suffix('=').unwrap_or_default() {
+            "" => r,
+            binary => {
+                let l = self.value(&name, subscript.as_deref())?;
+                apply(binary, l, r).map_err(|e| self.error_at(e, start))?
+            }
+        };
+        self.assign(&name, subscript.as_deref(), value)?;
+        Ok(value)
+    }
+
+    /// `cond ? a : b`
+    fn ternary(&mut self, run: bool) -> Result<i64, String> {
+        let cond = self.binary(0, run)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then = self.assignment(run && cond != 0)?;
+        if !self.eat(":") {
+            return Err(self.error("`:' expected for conditional expression"));
+        }
+        let otherwise = self.assignment(run && cond == 0)?;
+        Ok(if cond != 0 { then } else { otherwise })
+    }
+
+    /// The binary operators of `BINARY_LEVELS[level]` and tighter ones.
+    fn binary(&mut self, level: usize, run: bool) -> Result<i64, String> {
+        let Some(ops) = BINARY_LEVELS.get(level) else {
+            return self.power(run);
+        };
+        let mut l = self.binary(level + 1, run)?;
+        while let Some(op) = self.peek_op().filter(|op| ops.contains(op)) {
+            self.pos += 1;
+            let start = self.pos;
+            l = match op {
+                "&&" => {
+                    let r = self.binary(level + 1, run && l != 0)?;
+                    (l != 0 && r != 0) as i64
+                }
+                "||" => {
+                    let r = self.binary(level + 1, run && l == 0)?;
+                    (l != 0 || r != 0) as i64
+                }
+                _ => {
+                    let r = self.binary(level + 1, run)?;
+                    match apply(op, l, r) {
+                        Ok(value) => value,
+                        Err(_) if !run => 0,
+                        Err(e) => return Err(self.error_at(e, start)),
+                    }
+                }
+            };
+        }
+        Ok(l)
+    }
+
+    /// `a ** b`, which groups to the right.
+    fn power(&mut self, run: bool) -> Result<i64, String> {
+        let base = self.unary(run)?;
+        if !self.eat("**") {
+            return Ok(base);
+        }
+        let start = self.pos;
+        let exponent = self.power(run)?;
+        match apply("**", base, exponent) {
+            Ok(value) => Ok(value),
+            Err(_) if !run => Ok(0),
+            Err(e) => Err(self.error_at(e, start)),
+        }
+    }
+
+    /// `-a`, `+a`, `!a`, `~a`, `++x` and `--x`.
+    fn unary(&mut self, run: bool) -> Result<i64, String> {
+        match self.peek_op() {
+            Some("-") => {
+                self.pos += 1;
+                Ok(self.unary(run)?.wrapping_neg())
+            }
+            Some("+") => {
+                self.pos += 1;
+                self.unary(run)
+            }
+            Some("!") => {
+                self.pos += 1;
+                Ok((self.unary(run)? == 0) as i64)
+            }
+            Some("~") => {
+                self.pos += 1;
+                Ok(!self.unary(run)?)
+            }
+            Some(op @ ("++" | "--")) => {
+                self.pos += 1;
+                let Some(Token::Name(name, subscript)) = self.peek().cloned() else {
+                    return Err(self.error("syntax error: operand expected"));
+                };
+                self.pos += 1;
+                if !run {
+                    return Ok(0);
+                }
+                let step = if op == "++" { 1 } else { -1 };
+                let value = self.value(&name, subscript.as_deref())?.wrapping_add(step);
+                self.assign(&name, subscript.as_deref(), value)?;
+                Ok(value)
+            }
+            _ => self.postfix(run),
+        }
+    }
+
+    /// Numbers, variables, `x++`, `x--` and parentheses.
+    fn postfix(&mut self, run: bool) -> Result<i64, String> {
+        match self.peek().cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            Some(Token::Name(name, subscript)) => {
+                self.pos += 1;
+                if !run {
+                    if !self.eat("++") {
+                        self.eat("--");
+                    }
+                    return Ok(0);
+                }
+                let value = self.value(&name, subscript.as_deref())?;
+                let step = match self.peek_op() {
+                    Some("++") => 1,
+                    Some("--") => -1,
+                    _ => return Ok(value),
+                };
+                self.pos += 1;
+                self.assign(&name, subscript.as_deref(), value.wrapping_add(step))?;
+                Ok(value)
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let value = self.comma(run)?;
+                if !self.eat(")") {
+                    return Err(self.error("missing `)'"));
+                }
+                Ok(value)
+            }
+            _ => Err(self.error("syntax error: operand expected")),
+        }
+    }
+
+    /// The value of a variable or array element: 0 if unset or empty,
+    /// otherwise its value evaluated as an expression.
+    fn value(&mut self, name: &str, subscript: Option<&str>) -> Result<i64, String> {
+        let text = match subscript {
+            None => self.env.get(name).map(str::to_string),
+            Some(subscript) => {
+                let key = self.key(name, subscript)?;
+                self.env
+                    .value(name)
+                    .and_then(|value| value.get(&key))
+                    .map(str::to_string)
+            }
+        };
+        match text {
+            None if self.env.options.nounset => Err(format!("{}: unbound variable", name)),
+            None => Ok(0),
+            Some(text) => match text.trim().parse::<i64>() {
+                Ok(n) => Ok(n),
+                Err(_) => eval_nested(&text, self.env, self.depth + 1),
+            },
+        }
+    }
+
+    fn assign(&mut self, name: &str, subscript: Option<&str>, value: i64) -> Result<(), String> {
+        match subscript {
+            None => self.env.assign(name, &value.to_string()),
+            Some(subscript) => {
+                let key = self.key(name, subscript)?;
+                self.env.assign_element(name, &key, &value.to_string())
+            }
+        }
+    }
+
+    /// The key of an array element: the subscript itself for associative
+    /// arrays, or its value counting negative indexes from the end.
+    fn key(&mut self, name: &str, subscript: &str) -> Result<String, String> {
+        let end = match self.env.value(name) {
+            Some(Value::Assoc(_)) => return Ok(subscript.to_string()),
+            Some(value) => value.next_index() as i64,
+            None => 0,
+        };
+        let index = eval_nested(subscript, self.env, self.depth + 1)?;
+        match if index < 0 { end + index } else { index } {
+            index if index >= 0 => Ok(index.to_string()),
+            _ => Err(format!("{}: bad array subscript", name)),
+        }
+    }
+}
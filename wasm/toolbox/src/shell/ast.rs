@@ -1,33 +1,38 @@
-/// AST nodes for the shell interpreter.
+use serde::{Deserialize, Serialize};
+
+use super::syntax;
 
-#[derive(Debug, Clone)]
+/// AST nodes for the shell interpreter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
     pub commands: Vec<CompleteCommand>,
 }
 
 /// A complete command is a list of pipelines connected by && or ||.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompleteCommand {
     pub first: Pipeline,
     pub rest: Vec<(ListOp, Pipeline)>,
     pub background: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ListOp {
     And, // &&
     Or,  // ||
 }
 
 /// A pipeline is a sequence of simple commands connected by |.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pipeline {
     pub commands: Vec<Command>,
     pub negated: bool,
 }
 
 /// A single command — could be simple, compound, or a function definition.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Command {
     Simple(SimpleCommand),
     If(IfClause),
@@ -35,27 +40,48 @@ pub enum Command {
     While(WhileClause),
     Until(UntilClause),
     Case(CaseClause),
+    #[serde(
+        serialize_with = "syntax::arithmetic",
+        deserialize_with = "syntax::arithmetic_expression"
+    )]
+    Arithmetic(String), // (( expr ))
+    ArithFor(ArithForClause),
+    Cond(CondExpr), // [[ expr ]]
     Subshell(Program),
     BraceGroup(Program),
     FuncDef(FuncDef),
+    /// A compound command with redirections, as in `while ...; done < file`
+    Redirected(Box<Command>, Vec<Redirect>),
 }
 
 /// A simple command: optional assignments, words, and redirections.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimpleCommand {
     pub assignments: Vec<Assignment>,
     pub words: Vec<Word>,
     pub redirections: Vec<Redirect>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Assignment {
     pub name: String,
-    pub value: Word,
+    /// The subscript of an array element being assigned, as in `a[1]=x`
+    pub index: Option<Word>,
+    pub value: AssignValue,
+    /// Whether this is `+=`, which appends to the current value
+    pub append: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssignValue {
+    Scalar(Word),
+    /// `(x y z)`, where elements may give their subscript as `[key]=value`
+    Array(Vec<(Option<Word>, Word)>),
 }
 
 /// A word is a sequence of parts that get concatenated after expansion.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Word {
     pub parts: Vec<WordPart>,
 }
@@ -72,50 +98,121 @@ impl Word {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum WordPart {
     Literal(String),
     SingleQuoted(String),
     DoubleQuoted(Vec<WordPart>),
-    Variable(String),                    // $VAR or ${VAR}
-    VarDefault(String, Word),            // ${VAR:-default}
-    VarAssignDefault(String, Word),      // ${VAR:=default}
-    VarLength(String),                   // ${#VAR}
-    CommandSub(String),                  // $(cmd) or `cmd`
-    ArithmeticSub(String),              // $((expr))
-    Glob(String),                        // *, ?, [...]
+    Variable(String),            // $VAR or ${VAR}
+    Param(Box<ParamExpansion>),  // ${VAR:-default}, ${VAR#prefix}, ...
+    VarLength(String),           // ${#VAR}
+    Indirect(String),            // ${!VAR}
+    VarNames(String, bool),      // ${!prefix@} (true) or ${!prefix*}
+    ArrayElement(String, Word),  // ${a[i]}
+    ArrayValues(String, bool),   // ${a[@]} (true) or ${a[*]}
+    ArrayKeys(String, bool),     // ${!a[@]} (true) or ${!a[*]}
+    ArrayLength(String),         // ${#a[@]}
+    ElementLength(String, Word), // ${#a[i]}
+    Assignment(Box<Assignment>), // a=(x y) passed to declare or local
+    #[serde(
+        serialize_with = "syntax::command_sub",
+        deserialize_with = "syntax::command_sub_script"
+    )]
+    CommandSub(String),          // $(cmd) or `cmd`
+    #[serde(
+        serialize_with = "syntax::process_sub",
+        deserialize_with = "syntax::process_sub_script"
+    )]
+    ProcessSub(String, bool),    // <(cmd), or >(cmd) (true)
+    #[serde(
+        serialize_with = "syntax::arithmetic",
+        deserialize_with = "syntax::arithmetic_expression"
+    )]
+    ArithmeticSub(String),       // $((expr))
+    Glob(String),                // *, ?, [...]
     SpecialVar(SpecialVar),
 }
 
-#[derive(Debug, Clone)]
+/// A `${...}` expansion with an operator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParamExpansion {
+    /// A variable name, a positional parameter number, `@` or `*`
+    pub name: String,
+    /// The subscript after an array name
+    pub index: Option<Subscript>,
+    pub op: ParamOp,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subscript {
+    Element(Word), // [i]
+    All,           // [@]
+    Joined,        // [*]
+}
+
+/// The operators of `${...}`. Where there is a bool after a word, it says
+/// whether the operator had a colon, making an empty value count as unset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamOp {
+    Default(Word, bool),              // ${VAR:-word}
+    AssignDefault(Word, bool),        // ${VAR:=word}
+    Alternative(Word, bool),          // ${VAR:+word}
+    Error(Word, bool),                // ${VAR:?message}
+    RemovePrefix(Word, bool),         // ${VAR#pattern}, or ${VAR##pattern} (true)
+    RemoveSuffix(Word, bool),         // ${VAR%pattern}, or ${VAR%%pattern} (true)
+    Replace(Word, Word, ReplaceMode), // ${VAR/pattern/replacement}
+    Substring(Word, Option<Word>),    // ${VAR:offset} or ${VAR:offset:length}
+    Upper(bool),                      // ${VAR^}, or ${VAR^^} (true)
+    Lower(bool),                      // ${VAR,}, or ${VAR,,} (true)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaceMode {
+    First,  // ${VAR/pattern/replacement}
+    All,    // ${VAR//pattern/replacement}
+    Prefix, // ${VAR/#pattern/replacement}
+    Suffix, // ${VAR/%pattern/replacement}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SpecialVar {
     ExitStatus,     // $?
     NumArgs,        // $#
     AllArgs,        // $@
     AllArgsStar,    // $*
     ProcessId,      // $$
+    OptionFlags,    // $-
+    LastBackgroundPid, // $!
     Positional(u32), // $0, $1, ...
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Redirect {
     pub fd: Option<i32>,
     pub kind: RedirectKind,
     pub target: Word,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RedirectKind {
-    Output,         // >
-    Append,         // >>
-    Input,          // <
-    HereDoc(String), // <<EOF ... EOF (stores the content)
-    HereString,     // <<<
-    DupOutput,      // >&
-    DupInput,       // <&
+    Output,     // >
+    Append,     // >>
+    OutputAll,  // &> (stdout and stderr)
+    AppendAll,  // &>>
+    Input,      // <
+    HereDoc,    // <<EOF ... EOF (the target is the body)
+    HereString, // <<<
+    DupOutput,  // >&
+    DupInput,   // <&
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IfClause {
     pub condition: Program,
     pub then_body: Program,
@@ -123,38 +220,71 @@ pub struct IfClause {
     pub else_body: Option<Program>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ForClause {
     pub var: String,
     pub words: Option<Vec<Word>>,
     pub body: Program,
 }
 
-#[derive(Debug, Clone)]
+/// `for (( init; condition; step ))`, where any expression may be empty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArithForClause {
+    #[serde(
+        serialize_with = "syntax::arithmetic",
+        deserialize_with = "syntax::arithmetic_expression"
+    )]
+    pub init: String,
+    #[serde(
+        serialize_with = "syntax::arithmetic",
+        deserialize_with = "syntax::arithmetic_expression"
+    )]
+    pub condition: String,
+    #[serde(
+        serialize_with = "syntax::arithmetic",
+        deserialize_with = "syntax::arithmetic_expression"
+    )]
+    pub step: String,
+    pub body: Program,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WhileClause {
     pub condition: Program,
     pub body: Program,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UntilClause {
     pub condition: Program,
     pub body: Program,
 }
 
-#[derive(Debug, Clone)]
+/// An expression inside `[[ ]]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CondExpr {
+    Word(Word),                 // a lone word: true if not empty
+    Unary(String, Word),        // -f file, -z string, ...
+    Binary(Word, String, Word), // a == pattern, a =~ regex, a -lt b, ...
+    Not(Box<CondExpr>),         // ! expr
+    And(Box<CondExpr>, Box<CondExpr>),
+    Or(Box<CondExpr>, Box<CondExpr>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CaseClause {
     pub word: Word,
     pub arms: Vec<CaseArm>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CaseArm {
     pub patterns: Vec<Word>,
     pub body: Program,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FuncDef {
     pub name: String,
     pub body: Box<Command>,
@@ -0,0 +1,86 @@
+//! The execution trace `SandboxConfig::trace_commands` asks for: every tool
+//! the shell runs, with where, how long, how much fuel and which
+//! redirections it ran with. Builtins and functions are part of the shell
+//! and are not traced; the tools they run are.
+
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+use super::ast::{Redirect, RedirectKind};
+use super::env::ShellEnv;
+use crate::host;
+
+/// A tool the shell has started and will report once it exits.
+#[derive(Debug)]
+pub struct Started {
+    argv: Vec<String>,
+    cwd: String,
+    redirections: Vec<String>,
+    at: Instant,
+    fuel: u64,
+}
+
+/// Whether commands are being traced.
+pub fn enabled() -> bool {
+    host::trace_enabled()
+}
+
+/// Note that the shell is about to run `argv`, if commands are traced.
+pub fn start(argv: &[String], env: &ShellEnv) -> Option<Started> {
+    if !enabled() {
+        return None;
+    }
+    Some(Started {
+        argv: argv.to_vec(),
+        cwd: std::env::current_dir()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        redirections: env.redirections.clone(),
+        at: Instant::now(),
+        fuel: host::fuel_used(),
+    })
+}
+
+impl Started {
+    /// Report a tool that ran inside this instance.
+    pub fn finish(self, exit_code: i32) {
+        let fuel = host::fuel_used().saturating_sub(self.fuel);
+        let duration = self.at.elapsed();
+        self.report(exit_code, duration, fuel);
+    }
+
+    /// Report a tool that ran as a child instance, which the host measured.
+    pub fn finish_child(self, exit_code: i32, duration: Option<Duration>, fuel: Option<u64>) {
+        let duration = duration.unwrap_or_else(|| self.at.elapsed());
+        self.report(exit_code, duration, fuel.unwrap_or(0));
+    }
+
+    fn report(self, exit_code: i32, duration: Duration, fuel: u64) {
+        host::trace(&json!({
+            "argv": self.argv,
+            "cwd": self.cwd,
+            "exit_code": exit_code,
+            "duration_us": duration.as_micros() as u64,
+            "fuel": fuel,
+            "redirections": self.redirections,
+        }));
+    }
+}
+
+/// A redirection as the trace shows it, e.g. `>out.txt` or `2>&1`, with
+/// its target expanded. A here-document's body is left out.
+pub fn describe(redirect: &Redirect, target: &str) -> String {
+    let fd = redirect.fd.map(|fd| fd.to_string()).unwrap_or_default();
+    match redirect.kind {
+        RedirectKind::Output => format!("{fd}>{target}"),
+        RedirectKind::Append => format!("{fd}>>{target}"),
+        RedirectKind::OutputAll => format!("&>{target}"),
+        RedirectKind::AppendAll => format!("&>>{target}"),
+        RedirectKind::Input => format!("{fd}<{target}"),
+        RedirectKind::HereDoc => format!("{fd}<<here-document"),
+        RedirectKind::HereString => format!("{fd}<<<{target}"),
+        RedirectKind::DupOutput => format!("{fd}>&{target}"),
+        RedirectKind::DupInput => format!("{fd}<&{target}"),
+    }
+}
@@ -0,0 +1,198 @@
+//! Brace expansion: `pre{a,b}post`, `{1..10}`, `{01..10..2}` and `{a..e}`.
+//!
+//! Braces expand before anything else, so only braces and commas written
+//! unquoted in the word count; those inside quotes, escaped with `\` or
+//! produced by other expansions are left as they are.
+
+use super::ast::{Word, WordPart};
+
+/// A piece of a word: a character written unquoted, which may be brace
+/// syntax, or any other part, which never is.
+#[derive(Clone)]
+enum Piece<'a> {
+    Char(char),
+    Part(&'a WordPart),
+}
+
+/// The words a word expands to through brace expansion, in order. A word
+/// without a brace expression is returned unchanged.
+pub fn expand(word: &Word) -> Vec<Word> {
+    let has_brace = word
+        .parts
+        .iter()
+        .any(|part| matches!(part, WordPart::Literal(s) if s.contains('{')));
+    if !has_brace {
+        return vec![word.clone()];
+    }
+
+    let pieces: Vec<Piece> = word
+        .parts
+        .iter()
+        .flat_map(|part| match part {
+            WordPart::Literal(s) => s.chars().map(Piece::Char).collect(),
+            _ => vec![Piece::Part(part)],
+        })
+        .collect();
+    expand_pieces(&pieces)
+        .iter()
+        .map(|pieces| to_word(pieces))
+        .collect()
+}
+
+fn expand_pieces<'a>(pieces: &[Piece<'a>]) -> Vec<Vec<Piece<'a>>> {
+    let Some((open, close, alternatives)) = find_expression(pieces) else {
+        return vec![pieces.to_vec()];
+    };
+    let prefix = &pieces[..open];
+    let suffixes = expand_pieces(&pieces[close + 1..]);
+
+    let mut words = Vec::new();
+    for alternative in &alternatives {
+        for middle in expand_pieces(alternative) {
+            for suffix in &suffixes {
+                let mut word = prefix.to_vec();
+                word.extend(middle.iter().cloned());
+                word.extend(suffix.iter().cloned());
+                words.push(word);
+            }
+        }
+    }
+    words
+}
+
+/// The first brace expression in `pieces`: where it opens and closes, and
+/// the alternatives it stands for. Braces without a comma or a sequence
+/// between them, as in `{}` or `{a}`, are not an expression.
+fn find_expression<'a>(pieces: &[Piece<'a>]) -> Option<(usize, usize, Vec<Vec<Piece<'a>>>)> {
+    let is = |i: usize, c: char| matches!(pieces[i], Piece::Char(p) if p == c);
+
+    for open in (0..pieces.len()).filter(|&i| is(i, '{')) {
+        let mut depth = 0;
+        let mut commas = Vec::new();
+        let mut close = None;
+        for i in open + 1..pieces.len() {
+            if is(i, '{') {
+                depth += 1;
+            } else if is(i, '}') {
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+                depth -= 1;
+            } else if is(i, ',') && depth == 0 {
+                commas.push(i);
+            }
+        }
+        let Some(close) = close else {
+            continue;
+        };
+
+        if !commas.is_empty() {
+            let mut alternatives = Vec::new();
+            let mut start = open + 1;
+            for end in commas.into_iter().chain([close]) {
+                alternatives.push(pieces[start..end].to_vec());
+                start = end + 1;
+            }
+            return Some((open, close, alternatives));
+        }
+
+        let text: Option<String> = pieces[open + 1..close]
+            .iter()
+            .map(|piece| match piece {
+                Piece::Char(c) => Some(*c),
+                Piece::Part(_) => None,
+            })
+            .collect();
+        if let Some(items) = text.as_deref().and_then(sequence) {
+            let alternatives = items
+                .into_iter()
+                .map(|item| item.chars().map(Piece::Char).collect())
+                .collect();
+            return Some((open, close, alternatives));
+        }
+    }
+    None
+}
+
+/// The items of a sequence expression such as `1..10`, `10..1..3`,
+/// `01..10` or `a..e`. Numbers written with leading zeros are padded to the
+/// same width.
+fn sequence(text: &str) -> Option<Vec<String>> {
+    let mut bounds = text.split("..");
+    let (start, end) = (bounds.next()?, bounds.next()?);
+    let step = match bounds.next() {
+        Some(step) => step.parse::<i64>().ok()?.unsigned_abs().max(1),
+        None => 1,
+    };
+    if bounds.next().is_some() {
+        return None;
+    }
+
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let padded = |s: &str| s.trim_start_matches('-').starts_with('0') && s.len() > 1;
+        let width = if padded(start) || padded(end) {
+            start.len().max(end.len())
+        } else {
+            0
+        };
+        return Some(
+            range(first, last, step)
+                .map(|n| {
+                    if n < 0 {
+                        format!("-{:0>1$}", -n, width.saturating_sub(1))
+                    } else {
+                        format!("{:0>1$}", n, width)
+                    }
+                })
+                .collect(),
+        );
+    }
+
+    let (mut first, mut last) = (start.chars(), end.chars());
+    match (first.next(), first.next(), last.next(), last.next()) {
+        (Some(first), None, Some(last), None)
+            if first.is_ascii_alphabetic() && last.is_ascii_alphabetic() =>
+        {
+            Some(
+                range(first as i64, last as i64, step)
+                    .filter_map(|c| char::from_u32(c as u32))
+                    .map(String::from)
+                    .collect(),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// The numbers from `first` to `last`, counting up or down by `step`.
+fn range(first: i64, last: i64, step: u64) -> impl Iterator<Item = i64> {
+    let count = first.abs_diff(last) / step + 1;
+    let step = if first <= last {
+        step as i64
+    } else {
+        -(step as i64)
+    };
+    (0..count as i64).map(move |i| first + i * step)
+}
+
+/// Rebuild a word from pieces, joining runs of characters into literals.
+fn to_word(pieces: &[Piece]) -> Word {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Char(c) => literal.push(*c),
+            Piece::Part(part) => {
+                if !literal.is_empty() {
+                    parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push((*part).clone());
+            }
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(WordPart::Literal(literal));
+    }
+    Word { parts }
+}
@@ -1,6 +1,15 @@
-use std::io::{self, BufRead, Write};
+use std::collections::BTreeMap;
 
-use super::env::ShellEnv;
+use super::alias;
+use super::arith;
+use super::cond;
+use super::dirs;
+use super::env::{Attributes, ShellEnv, ShellOptions, Value};
+use super::expand::is_name;
+use super::jobs;
+use super::token;
+use super::traps;
+use crate::tools::which::is_known_command;
 
 /// Check if a command name is a shell builtin.
 pub fn is_builtin(name: &str) -> bool {
@@ -18,6 +27,8 @@ pub fn is_builtin(name: &str) -> bool {
             | ":"
             | "shift"
             | "local"
+            | "declare"
+            | "typeset"
             | "source"
             | "."
             | "return"
@@ -25,6 +36,27 @@ pub fn is_builtin(name: &str) -> bool {
             | "continue"
             | "eval"
             | "type"
+            | "echo"
+            | "printf"
+            | "wait"
+            | "jobs"
+            | "kill"
+            | "trap"
+            | "let"
+            | "readonly"
+            | "getopts"
+            | "alias"
+            | "unalias"
+            | "pushd"
+            | "popd"
+            | "dirs"
+            | "pwd"
+            | "mapfile"
+            | "readarray"
+            | "command"
+            | "hash"
+            | "umask"
+            | "shopt"
     )
 }
 
@@ -42,13 +74,13 @@ pub fn run_builtin(
             BuiltinResult::exit(code)
         }
         "cd" => builtin_cd(args, env),
-        "export" => builtin_export(args, env),
+        "export" | "readonly" | "local" => builtin_declare(name, args, env),
         "unset" => builtin_unset(args, env),
         "set" => builtin_set(args, env),
         "read" => builtin_read(args, env),
         "test" | "[" => builtin_test(args),
         "shift" => builtin_shift(args, env),
-        "local" => builtin_local(args, env),
+        "declare" | "typeset" => builtin_declare(name, args, env),
         "return" => {
             let code = args.first().and_then(|a| a.parse().ok()).unwrap_or(0);
             BuiltinResult::control(ControlFlow::Return(code))
@@ -61,7 +93,29 @@ pub fn run_builtin(
             let n = args.first().and_then(|a| a.parse().ok()).unwrap_or(1);
             BuiltinResult::control(ControlFlow::Continue(n))
         }
-        "type" => builtin_type(args),
+        "type" => builtin_type(args, env),
+        "echo" => {
+            env.print(&crate::tools::echo::render(args));
+            BuiltinResult::code(0)
+        }
+        "printf" => builtin_printf(args, env),
+        "wait" => jobs::builtin_wait(args, env),
+        "jobs" => jobs::builtin_jobs(args, env),
+        "kill" => jobs::builtin_kill(args, env),
+        "trap" => traps::builtin_trap(args, env),
+        "let" => builtin_let(args, env),
+        "getopts" => builtin_getopts(args, env),
+        "alias" => alias::builtin_alias(args, env),
+        "unalias" => alias::builtin_unalias(args, env),
+        "pushd" => dirs::builtin_pushd(args, env),
+        "popd" => dirs::builtin_popd(args, env),
+        "dirs" => dirs::builtin_dirs(args, env),
+        "pwd" => builtin_pwd(args, env),
+        "mapfile" | "readarray" => builtin_mapfile(args, env),
+        "command" => builtin_command(args, env),
+        "hash" => builtin_hash(args, env),
+        "umask" => builtin_umask(args, env),
+        "shopt" => builtin_shopt(args, env),
         _ => BuiltinResult::code(127),
     }
 }
@@ -107,55 +161,135 @@ impl BuiltinResult {
 }
 
 fn builtin_cd(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
-    let dir = if args.is_empty() {
-        env.get("HOME").unwrap_or("/").to_string()
-    } else {
-        args[0].clone()
+    let dir = match args.first().map(String::as_str) {
+        None => env.get("HOME").unwrap_or("/").to_string(),
+        // `cd -` goes back to the previous directory and prints it
+        Some("-") => match env.get("OLDPWD") {
+            Some(dir) => {
+                env.print(&format!("{}\n", dir));
+                dir.to_string()
+            }
+            None => {
+                env.eprint("sh: cd: OLDPWD not set\n");
+                return BuiltinResult::code(1);
+            }
+        },
+        Some(dir) => dir.to_string(),
     };
 
-    match std::env::set_current_dir(&dir) {
-        Ok(()) => {
-            if let Ok(cwd) = std::env::current_dir() {
-                env.set("PWD", &cwd.to_string_lossy());
-            }
-            BuiltinResult::code(0)
-        }
+    match change_dir(&dir, env) {
+        Ok(()) => BuiltinResult::code(0),
         Err(e) => {
-            eprintln!("cd: {}: {}", dir, e);
+            env.eprint(&format!("cd: {}\n", e));
             BuiltinResult::code(1)
         }
     }
 }
 
-fn builtin_export(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
-    if args.is_empty() {
-        // Print all exports
-        for (k, v) in env.exported_vars() {
-            println!("declare -x {}=\"{}\"", k, v);
+/// `pwd`: the working directory as `$PWD` names it, or with `-P` with every
+/// symbolic link resolved. A `$PWD` naming some other directory is ignored.
+fn builtin_pwd(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let mut physical = false;
+    for arg in args {
+        let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            continue;
+        };
+        for flag in flags.chars() {
+            match flag {
+                'L' => physical = false,
+                'P' => physical = true,
+                _ => {
+                    env.eprint(&format!("sh: pwd: -{}: invalid option\n", flag));
+                    return BuiltinResult::code(2);
+                }
+            }
         }
-        return BuiltinResult::code(0);
     }
 
-    for arg in args {
-        if let Some(eq_pos) = arg.find('=') {
-            let name = &arg[..eq_pos];
-            let value = &arg[eq_pos + 1..];
-            env.export(name, Some(value));
-        } else {
-            env.export(arg, None);
+    let cwd = match std::env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(e) => {
+            env.eprint(&format!("sh: pwd: {}\n", e));
+            return BuiltinResult::code(1);
         }
-    }
+    };
+    let resolved = std::fs::canonicalize(&cwd).unwrap_or_else(|_| cwd.clone());
+    let dir = if physical {
+        resolved.to_string_lossy().into_owned()
+    } else {
+        match env.get("PWD") {
+            Some(pwd)
+                if pwd.starts_with('/')
+                    && std::fs::canonicalize(pwd).is_ok_and(|dir| dir == resolved) =>
+            {
+                pwd.to_string()
+            }
+            _ => cwd.to_string_lossy().into_owned(),
+        }
+    };
+    env.print(&format!("{}\n", dir));
     BuiltinResult::code(0)
 }
 
+/// Change the working directory, updating `PWD` and `OLDPWD`. Fails with
+/// `dir: error`.
+pub fn change_dir(dir: &str, env: &mut ShellEnv) -> Result<(), String> {
+    let old = std::env::current_dir().ok();
+    std::env::set_current_dir(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    if let Some(old) = old {
+        env.set("OLDPWD", &old.to_string_lossy());
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        env.set("PWD", &cwd.to_string_lossy());
+    }
+    Ok(())
+}
+
 fn builtin_unset(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let functions = args.first().is_some_and(|arg| arg == "-f");
+    let mut status = 0;
     for name in args {
         if name == "-v" || name == "-f" {
             continue;
         }
-        env.unset(name);
+        if functions {
+            env.functions.remove(name);
+            continue;
+        }
+        // `unset 'a[1]'` removes one element
+        let element = name
+            .split_once('[')
+            .and_then(|(name, rest)| Some((name, rest.strip_suffix(']')?)));
+        let variable = element.map_or(name.as_str(), |(name, _)| name);
+        if env.attributes(variable).readonly {
+            env.eprint(&format!(
+                "sh: unset: {}: cannot unset: readonly variable\n",
+                variable
+            ));
+            status = 1;
+            continue;
+        }
+        match element {
+            Some((name, key)) => {
+                let key = match (env.value(name), key.parse::<i64>()) {
+                    (Some(Value::Assoc(_)), _) | (_, Err(_)) => key.to_string(),
+                    (value, Ok(n)) if n < 0 => {
+                        (value.map_or(0, Value::next_index) as i64 + n).to_string()
+                    }
+                    (_, Ok(n)) => n.to_string(),
+                };
+                env.unset_element(name, &key);
+            }
+            None => {
+                // Without a variable of that name, a function is unset
+                if env.value(name).is_none() {
+                    env.functions.remove(name);
+                }
+                env.unset(name);
+            }
+        }
     }
-    BuiltinResult::code(0)
+    BuiltinResult::code(status)
 }
 
 fn builtin_set(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
@@ -164,19 +298,73 @@ fn builtin_set(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
         return BuiltinResult::code(0);
     }
 
-    // set -- args... sets positional parameters
-    if !args.is_empty() && args[0] == "--" {
-        env.positional = args[1..].to_vec();
+    // `set -o` and `set +o` on their own list the options
+    if let [flag] = args
+        && (flag == "-o" || flag == "+o")
+    {
+        for (name, on) in env.options.list() {
+            let line = match (flag.as_str(), on) {
+                ("-o", on) => format!("{:<15}\t{}\n", name, if on { "on" } else { "off" }),
+                (_, true) => format!("set -o {}\n", name),
+                (_, false) => format!("set +o {}\n", name),
+            };
+            env.print(&line);
+        }
         return BuiltinResult::code(0);
     }
 
-    // set -e, set -x, etc. (flags we don't fully implement but accept)
+    let used = match apply_options(args, &mut env.options) {
+        Ok(used) => used,
+        Err(e) => {
+            env.eprint(&format!("set: {}\n", e));
+            return BuiltinResult::code(2);
+        }
+    };
+
+    // set -- args... (or any other remaining words) sets positional parameters
+    let rest = &args[used..];
+    match rest.first().map(String::as_str) {
+        Some("--") => env.positional = rest[1..].to_vec(),
+        Some(_) => env.positional = rest.to_vec(),
+        None => {}
+    }
     BuiltinResult::code(0)
 }
 
+/// Apply leading option arguments such as `-eu`, `+x` or `-o pipefail`,
+/// returning how many arguments were used. Stops at `--` or the first
+/// argument that is not an option.
+pub fn apply_options(args: &[String], options: &mut ShellOptions) -> Result<usize, String> {
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        let on = match arg.chars().next() {
+            Some('-') => true,
+            Some('+') => false,
+            _ => break,
+        };
+        if arg.len() < 2 || arg == "--" {
+            break;
+        }
+        i += 1;
+        for flag in arg[1..].chars() {
+            if flag == 'o' {
+                let name = args.get(i).ok_or("-o: option requires an argument")?;
+                if !options.set(name, on) {
+                    return Err(format!("{}: invalid option name", name));
+                }
+                i += 1;
+            } else if !options.set_flag(flag, on) {
+                return Err(format!("{}{}: invalid option", &arg[..1], flag));
+            }
+        }
+    }
+    Ok(i)
+}
+
 fn builtin_read(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
     let mut prompt = String::new();
     let mut var_names = Vec::new();
+    let mut array_name = None;
 
     let mut i = 0;
     while i < args.len() {
@@ -187,6 +375,10 @@ fn builtin_read(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
                     prompt = args[i].clone();
                 }
             }
+            "-a" => {
+                i += 1;
+                array_name = args.get(i).cloned();
+            }
             "-r" => {} // raw mode (default for us)
             _ => var_names.push(args[i].clone()),
         }
@@ -194,31 +386,54 @@ fn builtin_read(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
     }
 
     if !prompt.is_empty() {
-        eprint!("{}", prompt);
-        io::stderr().flush().ok();
+        env.eprint(&prompt);
     }
 
-    let mut line = String::new();
-    match io::stdin().lock().read_line(&mut line) {
-        Ok(0) => return BuiltinResult::code(1), // EOF
-        Ok(_) => {}
-        Err(_) => return BuiltinResult::code(1),
-    }
+    let line = match env.io.stdin.read_line() {
+        Some(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        None => return BuiltinResult::code(1), // EOF
+    };
 
     let line = line.trim_end_matches('\n').trim_end_matches('\r');
 
+    // `read -a name` splits the line into an indexed array
+    if let Some(name) = array_name {
+        let items = line
+            .split_whitespace()
+            .map(String::from)
+            .enumerate()
+            .collect();
+        return assigned(env.assign_value(&name, Value::Indexed(items)), env);
+    }
+
     if var_names.is_empty() {
         var_names.push("REPLY".to_string());
     }
 
     let parts: Vec<&str> = line.splitn(var_names.len(), char::is_whitespace).collect();
 
+    let mut status = 0;
     for (i, name) in var_names.iter().enumerate() {
         let value = parts.get(i).unwrap_or(&"");
-        env.set(name, value);
+        if let Err(e) = env.assign(name, value) {
+            env.eprint(&format!("sh: read: {}\n", e));
+            status = 1;
+        }
     }
 
-    BuiltinResult::code(0)
+    BuiltinResult::code(status)
+}
+
+/// The result of a builtin whose only failure is an assignment being
+/// refused.
+fn assigned(result: Result<(), String>, env: &ShellEnv) -> BuiltinResult {
+    match result {
+        Ok(()) => BuiltinResult::code(0),
+        Err(e) => {
+            env.eprint(&format!("sh: {}\n", e));
+            BuiltinResult::code(1)
+        }
+    }
 }
 
 fn builtin_test(args: &[String]) -> BuiltinResult {
@@ -235,7 +450,8 @@ fn builtin_test(args: &[String]) -> BuiltinResult {
     BuiltinResult::code(if result { 0 } else { 1 })
 }
 
-fn eval_test(args: &[&str]) -> bool {
+/// Evaluate the arguments of `test`, without the closing `]` of `[`.
+pub fn eval_test(args: &[&str]) -> bool {
     match args.len() {
         0 => false,
         1 => !args[0].is_empty(),
@@ -246,11 +462,7 @@ fn eval_test(args: &[&str]) -> bool {
                 "-z" => val.is_empty(),
                 "-n" => !val.is_empty(),
                 "!" => !eval_test(&args[1..]),
-                "-e" | "-f" => std::path::Path::new(val).exists(),
-                "-d" => std::path::Path::new(val).is_dir(),
-                "-s" => std::fs::metadata(val).map(|m| m.len() > 0).unwrap_or(false),
-                "-r" | "-w" | "-x" => std::path::Path::new(val).exists(),
-                _ => !args[0].is_empty(),
+                _ => cond::unary_test(op, val).unwrap_or(!args[0].is_empty()),
             }
         }
         _ => {
@@ -260,17 +472,7 @@ fn eval_test(args: &[&str]) -> bool {
                 let op = args[1];
                 let right = args[2];
 
-                let result = match op {
-                    "=" | "==" => left == right,
-                    "!=" => left != right,
-                    "-eq" => left.parse::<i64>().unwrap_or(0) == right.parse::<i64>().unwrap_or(0),
-                    "-ne" => left.parse::<i64>().unwrap_or(0) != right.parse::<i64>().unwrap_or(0),
-                    "-lt" => left.parse::<i64>().unwrap_or(0) < right.parse::<i64>().unwrap_or(0),
-                    "-le" => left.parse::<i64>().unwrap_or(0) <= right.parse::<i64>().unwrap_or(0),
-                    "-gt" => left.parse::<i64>().unwrap_or(0) > right.parse::<i64>().unwrap_or(0),
-                    "-ge" => left.parse::<i64>().unwrap_or(0) >= right.parse::<i64>().unwrap_or(0),
-                    _ => false,
-                };
+                let result = cond::binary_test(left, op, right).unwrap_or(false);
 
                 // Handle remaining args with -a/-o
                 if args.len() > 3 {
@@ -297,33 +499,843 @@ fn eval_test(args: &[&str]) -> bool {
     }
 }
 
+/// `let expr...`: evaluate each argument as arithmetic, succeeding if the
+/// last is non-zero.
+fn builtin_let(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    if args.is_empty() {
+        env.eprint("sh: let: expression expected\n");
+        return BuiltinResult::code(1);
+    }
+    let mut last = 0;
+    for arg in args {
+        match arith::eval(arg, env) {
+            Ok(value) => last = value,
+            Err(e) => {
+                env.eprint(&format!("sh: let: {}\n", e));
+                return BuiltinResult::code(1);
+            }
+        }
+    }
+    BuiltinResult::code(if last != 0 { 0 } else { 1 })
+}
+
 fn builtin_shift(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
     let n = args.first().and_then(|a| a.parse().ok()).unwrap_or(1);
     env.shift(n);
     BuiltinResult::code(0)
 }
 
-fn builtin_local(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+/// The option letters each declaration command accepts.
+fn declaration_options(command: &str) -> &'static str {
+    match command {
+        "local" => "aAilrux",
+        "readonly" => "aAfp",
+        "export" => "fnp",
+        _ => "aAfFgilprux",
+    }
+}
+
+/// `declare`, `typeset`, `local`, `readonly` and `export`: give variables
+/// values and attributes, or print them. Options starting with `+` take
+/// attributes away.
+fn builtin_declare(command: &str, args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let mut flags = Vec::new();
+    let mut used = 0;
     for arg in args {
-        if let Some(eq_pos) = arg.find('=') {
-            let name = &arg[..eq_pos];
-            let value = &arg[eq_pos + 1..];
-            env.declare_local(name);
-            env.set(name, value);
-        } else {
-            env.declare_local(arg);
+        if arg == "--" {
+            used += 1;
+            break;
         }
+        let on = match arg.chars().next() {
+            Some('-') => true,
+            Some('+') => false,
+            _ => break,
+        };
+        if arg.len() < 2 {
+            break;
+        }
+        for flag in arg[1..].chars() {
+            if !declaration_options(command).contains(flag) {
+                env.eprint(&format!(
+                    "sh: {}: {}{}: invalid option\n",
+                    command,
+                    &arg[..1],
+                    flag
+                ));
+                return BuiltinResult::code(2);
+            }
+            flags.push((flag, on));
+        }
+        used += 1;
     }
-    BuiltinResult::code(0)
+    let names = &args[used..];
+
+    match command {
+        "readonly" => flags.push(('r', true)),
+        "export" => {
+            let on = !flags.contains(&('n', true));
+            flags.push(('x', on));
+        }
+        _ => {}
+    }
+    let has = |flag: char| flags.contains(&(flag, true));
+
+    if has('f') || has('F') {
+        // Functions cannot be exported or made read-only
+        return match command {
+            "declare" | "typeset" => print_functions(names, env),
+            _ => BuiltinResult::code(0),
+        };
+    }
+    if names.is_empty() && command == "local" {
+        return BuiltinResult::code(0);
+    }
+    if names.is_empty() || has('p') {
+        return print_declarations(names, &flags, env);
+    }
+
+    // Inside a function, `declare` makes variables local unless given -g
+    let local = command == "local" || (matches!(command, "declare" | "typeset") && !has('g'));
+    let mut status = 0;
+    for arg in names {
+        if let Err(e) = declare(arg, &flags, local, env) {
+            env.eprint(&format!("sh: {}: {}\n", command, e));
+            status = 1;
+        }
+    }
+    BuiltinResult::code(status)
 }
 
-fn builtin_type(args: &[String]) -> BuiltinResult {
-    for name in args {
-        if is_builtin(name) {
-            println!("{} is a shell builtin", name);
+/// Declare one variable, given as `name` or `name=value`, applying the
+/// option letters in `flags`.
+fn declare(
+    arg: &str,
+    flags: &[(char, bool)],
+    local: bool,
+    env: &mut ShellEnv,
+) -> Result<(), String> {
+    let (name, value) = match arg.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (arg, None),
+    };
+    if !is_name(name) {
+        return Err(format!("`{}': not a valid identifier", arg));
+    }
+    if local {
+        env.declare_local(name);
+    }
+
+    let mut attributes = env.attributes(name);
+    let readonly_error = || format!("{}: readonly variable", name);
+    // Read-only applies last, so `readonly x=1` can still assign
+    let mut readonly = attributes.readonly;
+    for &(flag, on) in flags {
+        match flag {
+            'i' => attributes.integer = on,
+            'l' => {
+                attributes.lowercase = on;
+                attributes.uppercase &= !on;
+            }
+            'u' => {
+                attributes.uppercase = on;
+                attributes.lowercase &= !on;
+            }
+            'r' if on => readonly = true,
+            'r' if attributes.readonly => return Err(readonly_error()),
+            'x' if on => env.export(name, None),
+            'x' => env.unexport(name),
+            'a' | 'A' if on && attributes.readonly => return Err(readonly_error()),
+            'a' if on => declare_array(name, &Value::Indexed(BTreeMap::new()), env),
+            'A' if on => declare_array(name, &Value::Assoc(BTreeMap::new()), env),
+            _ => {}
+        }
+    }
+    env.set_attributes(name, attributes);
+    if let Some(value) = value {
+        env.assign(name, value)?;
+    }
+    if readonly {
+        env.set_attributes(
+            name,
+            Attributes {
+                readonly,
+                ..attributes
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Make `name` an array of the same kind as `empty`, keeping the elements it
+/// already has if it is one. A scalar becomes element 0.
+fn declare_array(name: &str, empty: &Value, env: &mut ShellEnv) {
+    let value = match (env.value(name), empty) {
+        (Some(Value::Indexed(items)), Value::Indexed(_)) => Value::Indexed(items.clone()),
+        (Some(Value::Assoc(items)), Value::Assoc(_)) => Value::Assoc(items.clone()),
+        (Some(Value::Scalar(scalar)), Value::Indexed(_)) => {
+            Value::Indexed(BTreeMap::from([(0, scalar.clone())]))
+        }
+        _ => empty.clone(),
+    };
+    env.set_value(name, value);
+}
+
+/// `declare -p`: print variables as the commands that would recreate them.
+/// Without names, prints every variable that has the attributes turned on
+/// in `flags`.
+fn print_declarations(names: &[String], flags: &[(char, bool)], env: &ShellEnv) -> BuiltinResult {
+    if names.is_empty() {
+        for name in env.names_with_prefix("") {
+            let shown = attribute_flags(&name, env);
+            let selected = flags
+                .iter()
+                .all(|&(flag, on)| !on || !"aAilrux".contains(flag) || shown.contains(flag));
+            if let Some(line) = declaration(&name, env).filter(|_| selected) {
+                env.print(&format!("{}\n", line));
+            }
+        }
+        return BuiltinResult::code(0);
+    }
+
+    let mut status = 0;
+    for name in names {
+        match declaration(name, env) {
+            Some(line) => env.print(&format!("{}\n", line)),
+            None => {
+                env.eprint(&format!("declare: {}: not found\n", name));
+                status = 1;
+            }
+        }
+    }
+    BuiltinResult::code(status)
+}
+
+/// The `declare` command that recreates a variable, if it is set or has
+/// attributes.
+fn declaration(name: &str, env: &ShellEnv) -> Option<String> {
+    let flags = match attribute_flags(name, env) {
+        flags if flags.is_empty() => "--".to_string(),
+        flags => format!("-{}", flags),
+    };
+    let line = match env.value(name) {
+        Some(Value::Scalar(value)) => format!("declare {} {}={}", flags, name, double_quote(value)),
+        Some(Value::Indexed(items)) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|(i, v)| format!("[{}]={}", i, double_quote(v)))
+                .collect();
+            format!("declare {} {}=({})", flags, name, items.join(" "))
+        }
+        Some(Value::Assoc(items)) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|(k, v)| format!("[{}]={} ", k, double_quote(v)))
+                .collect();
+            format!("declare {} {}=({})", flags, name, items.concat())
+        }
+        None if flags != "--" => format!("declare {} {}", flags, name),
+        None => return None,
+    };
+    Some(line)
+}
+
+/// The option letters that give a variable its type and attributes, in the
+/// order bash prints them.
+fn attribute_flags(name: &str, env: &ShellEnv) -> String {
+    let attributes = env.attributes(name);
+    let value = env.value(name);
+    [
+        ('a', matches!(value, Some(Value::Indexed(_)))),
+        ('A', matches!(value, Some(Value::Assoc(_)))),
+        ('i', attributes.integer),
+        ('l', attributes.lowercase),
+        ('r', attributes.readonly),
+        ('u', attributes.uppercase),
+        ('x', env.is_exported(name)),
+    ]
+    .iter()
+    .filter(|(_, on)| *on)
+    .map(|(flag, _)| *flag)
+    .collect()
+}
+
+/// `declare -F` and `declare -f`: list functions by name, since their
+/// source text is not kept.
+fn print_functions(names: &[String], env: &ShellEnv) -> BuiltinResult {
+    if names.is_empty() {
+        let mut all: Vec<&String> = env.functions.keys().collect();
+        all.sort();
+        for name in all {
+            env.print(&format!("declare -f {}\n", name));
+        }
+        return BuiltinResult::code(0);
+    }
+
+    let mut status = 0;
+    for name in names {
+        if env.functions.contains_key(name) {
+            env.print(&format!("{}\n", name));
         } else {
-            println!("{} is /usr/bin/{}", name, name);
+            status = 1;
+        }
+    }
+    BuiltinResult::code(status)
+}
+
+/// Quote a value in double quotes, escaping what would expand inside them.
+fn double_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
         }
+        quoted.push(c);
     }
+    quoted.push('"');
+    quoted
+}
+
+/// What running a command name would run, in the order the shell looks.
+enum CommandKind {
+    Alias(String),
+    Keyword,
+    Function,
+    Builtin,
+    Tool,
+}
+
+fn command_kind(name: &str, env: &ShellEnv) -> Option<CommandKind> {
+    if let Some(value) = env.aliases.get(name) {
+        Some(CommandKind::Alias(value.clone()))
+    } else if token::keyword_token(name).is_some() {
+        Some(CommandKind::Keyword)
+    } else if env.functions.contains_key(name) {
+        Some(CommandKind::Function)
+    } else if is_builtin(name) {
+        Some(CommandKind::Builtin)
+    } else if is_known_command(name) {
+        Some(CommandKind::Tool)
+    } else {
+        None
+    }
+}
+
+/// How `type` describes a command.
+fn describe_kind(name: &str, kind: &CommandKind) -> String {
+    match kind {
+        CommandKind::Alias(value) => format!("{} is aliased to `{}'", name, value),
+        CommandKind::Keyword => format!("{} is a shell keyword", name),
+        CommandKind::Function => format!("{} is a function", name),
+        CommandKind::Builtin => format!("{} is a shell builtin", name),
+        CommandKind::Tool => format!("{} is /usr/bin/{}", name, name),
+    }
+}
+
+/// `type [-t] name...`: say what each name would run; with `-t`, as one
+/// word.
+fn builtin_type(args: &[String], env: &ShellEnv) -> BuiltinResult {
+    let (short, names) = match args.first() {
+        Some(flag) if flag == "-t" => (true, &args[1..]),
+        _ => (false, args),
+    };
+    let mut status = 0;
+    for name in names {
+        let line = match command_kind(name, env) {
+            Some(kind) if !short => describe_kind(name, &kind),
+            Some(kind) => match kind {
+                CommandKind::Alias(_) => "alias",
+                CommandKind::Keyword => "keyword",
+                CommandKind::Function => "function",
+                CommandKind::Builtin => "builtin",
+                CommandKind::Tool => "file",
+            }
+            .to_string(),
+            None => {
+                if !short {
+                    env.eprint(&format!("sh: type: {}: not found\n", name));
+                }
+                status = 1;
+                continue;
+            }
+        };
+        env.print(&format!("{}\n", line));
+    }
+    BuiltinResult::code(status)
+}
+
+/// `command -v` and `command -V`: say how each name would run, briefly or
+/// as `type` does. Running `command name args`, which skips functions and
+/// aliases, happens in `exec`.
+fn builtin_command(args: &[String], env: &ShellEnv) -> BuiltinResult {
+    let mut verbose = None;
+    let mut used = 0;
+    for arg in args {
+        match arg.as_str() {
+            "-p" => {}
+            "-v" => verbose = Some(false),
+            "-V" => verbose = Some(true),
+            "--" => {
+                used += 1;
+                break;
+            }
+            _ if arg.starts_with('-') => {
+                env.eprint(&format!("sh: command: {}: invalid option\n", arg));
+                return BuiltinResult::code(2);
+            }
+            _ => break,
+        }
+        used += 1;
+    }
+    let Some(verbose) = verbose else {
+        return BuiltinResult::code(0);
+    };
+
+    let mut status = 0;
+    for name in &args[used..] {
+        let line = match command_kind(name, env) {
+            Some(kind) if verbose => describe_kind(name, &kind),
+            Some(CommandKind::Alias(value)) => format!("alias {}={}", name, alias::quote(&value)),
+            Some(CommandKind::Tool) => format!("/usr/bin/{}", name),
+            Some(_) => name.clone(),
+            None => {
+                if verbose {
+                    env.eprint(&format!("sh: command: {}: not found\n", name));
+                }
+                status = 1;
+                continue;
+            }
+        };
+        env.print(&format!("{}\n", line));
+    }
+    BuiltinResult::code(status)
+}
+
+/// `hash`: tools run in-process, so there are no paths to remember. Names
+/// are checked, and `-t` prints where each would be found.
+fn builtin_hash(args: &[String], env: &ShellEnv) -> BuiltinResult {
+    if args.is_empty() {
+        env.print("hash: hash table empty\n");
+        return BuiltinResult::code(0);
+    }
+    let mut print = false;
+    let mut status = 0;
+    for arg in args {
+        match arg.as_str() {
+            "-r" => {}
+            "-t" => print = true,
+            _ if is_known_command(arg) => {
+                if print {
+                    env.print(&format!("/usr/bin/{}\n", arg));
+                }
+            }
+            _ => {
+                env.eprint(&format!("sh: hash: {}: not found\n", arg));
+                status = 1;
+            }
+        }
+    }
+    BuiltinResult::code(status)
+}
+
+/// `getopts optstring name [args...]`: parse the next option from the
+/// positional parameters, or `args`, setting `name`, `OPTARG` and `OPTIND`.
+/// A leading `:` in `optstring` reports errors through `name` and `OPTARG`
+/// instead of printing them. Fails once there are no options left.
+fn builtin_getopts(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let [optstring, name, rest @ ..] = args else {
+        env.eprint("sh: getopts: usage: getopts optstring name [arg ...]\n");
+        return BuiltinResult::code(2);
+    };
+    let params = if rest.is_empty() {
+        env.positional.clone()
+    } else {
+        rest.to_vec()
+    };
+    let (silent, spec) = match optstring.strip_prefix(':') {
+        Some(spec) => (true, spec),
+        None => (false, optstring.as_str()),
+    };
+
+    let mut index = env
+        .get("OPTIND")
+        .and_then(|index| index.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    // Carry on inside a group such as `-ab` only if OPTIND was left alone
+    let mut offset = match env.getopts_position {
+        (at, offset) if at == index => offset,
+        _ => 1,
+    };
+
+    let chars: Vec<char> = params
+        .get(index - 1)
+        .map(|arg| arg.chars().collect())
+        .unwrap_or_default();
+    if offset >= chars.len() {
+        offset = 1;
+    }
+    if offset == 1 && (chars.len() < 2 || chars[0] != '-' || chars == ['-', '-']) {
+        // Options end at `--`, which is skipped, or the first operand
+        if chars == ['-', '-'] {
+            index += 1;
+        }
+        env.set("OPTIND", &index.to_string());
+        env.getopts_position = (index, 1);
+        env.unset("OPTARG");
+        let _ = env.assign(name, "?");
+        return BuiltinResult::code(1);
+    }
+
+    let option = chars[offset];
+    offset += 1;
+    // Whether more letters of the same argument follow
+    let grouped = offset < chars.len();
+    if !grouped {
+        index += 1;
+        offset = 1;
+    }
+
+    let takes_argument = match spec.find(option) {
+        Some(pos) if option != ':' => Some(spec[pos + option.len_utf8()..].starts_with(':')),
+        _ => None,
+    };
+    let (value, optarg) = match takes_argument {
+        None => {
+            if !silent {
+                env.eprint(&format!("sh: illegal option -- {}\n", option));
+            }
+            ("?".to_string(), silent.then(|| option.to_string()))
+        }
+        Some(false) => (option.to_string(), None),
+        Some(true) if grouped => {
+            // The rest of the argument is the option's value, as in `-ofile`
+            let optarg = chars[offset..].iter().collect();
+            index += 1;
+            offset = 1;
+            (option.to_string(), Some(optarg))
+        }
+        Some(true) => match params.get(index - 1) {
+            Some(optarg) => {
+                index += 1;
+                (option.to_string(), Some(optarg.clone()))
+            }
+            None if silent => (":".to_string(), Some(option.to_string())),
+            None => {
+                env.eprint(&format!("sh: option requires an argument -- {}\n", option));
+                ("?".to_string(), None)
+            }
+        },
+    };
+
+    env.set("OPTIND", &index.to_string());
+    env.getopts_position = (index, offset);
+    match optarg {
+        Some(optarg) => env.set("OPTARG", &optarg),
+        None => env.unset("OPTARG"),
+    }
+    assigned(env.assign(name, &value), env)
+}
+
+/// `mapfile` and `readarray`: read lines of input into an indexed array,
+/// `MAPFILE` unless named.
+fn builtin_mapfile(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let mut name = "MAPFILE";
+    let mut strip = false;
+    let mut count = 0;
+    let mut skip = 0;
+    let mut origin = None;
+    let mut delimiter = b'\n';
+
+    let mut i = 0;
+    while i < args.len() {
+        let option = args[i].as_str();
+        match option {
+            "-t" => strip = true,
+            "-n" | "-s" | "-O" | "-d" | "-u" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    env.eprint(&format!(
+                        "sh: mapfile: {}: option requires an argument\n",
+                        option
+                    ));
+                    return BuiltinResult::code(2);
+                };
+                if option == "-d" {
+                    // An empty delimiter splits at NUL bytes
+                    delimiter = value.bytes().next().unwrap_or(0);
+                } else {
+                    let Ok(n) = value.parse::<usize>() else {
+                        env.eprint(&format!("sh: mapfile: {}: invalid number\n", value));
+                        return BuiltinResult::code(1);
+                    };
+                    match option {
+                        "-n" => count = n,
+                        "-s" => skip = n,
+                        "-O" => origin = Some(n),
+                        // Only standard input can be read
+                        _ if n != 0 => {
+                            env.eprint(&format!(
+                                "sh: mapfile: {}: invalid file descriptor specification\n",
+                                value
+                            ));
+                            return BuiltinResult::code(1);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ if option.starts_with('-') && option.len() > 1 => {
+                env.eprint(&format!("sh: mapfile: {}: invalid option\n", option));
+                return BuiltinResult::code(2);
+            }
+            _ => name = option,
+        }
+        i += 1;
+    }
+
+    // With -O the array keeps its elements; otherwise it is replaced
+    let mut items = match (origin, env.value(name)) {
+        (Some(_), Some(Value::Indexed(items))) => items.clone(),
+        _ => BTreeMap::new(),
+    };
+    let mut index = origin.unwrap_or(0);
+    let mut read = 0;
+    while count == 0 || read < count {
+        let Some(mut record) = env.io.stdin.read_until(delimiter) else {
+            break;
+        };
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+        if strip && record.last() == Some(&delimiter) {
+            record.pop();
+        }
+        items.insert(index, String::from_utf8_lossy(&record).into_owned());
+        index += 1;
+        read += 1;
+    }
+    assigned(env.assign_value(name, Value::Indexed(items)), env)
+}
+
+/// `printf [-v name] format [args...]`: print the formatted text, or assign
+/// it to `name`, which may be an array element.
+fn builtin_printf(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let args = match args.first() {
+        Some(first) if first == "--" => &args[1..],
+        _ => args,
+    };
+    let [flag, rest @ ..] = args else {
+        env.eprint("sh: printf: usage: printf [-v var] format [arguments]\n");
+        return BuiltinResult::code(2);
+    };
+    if flag != "-v" {
+        env.print(&crate::tools::printf::render(args));
+        return BuiltinResult::code(0);
+    }
+
+    let [name, format_args @ ..] = rest else {
+        env.eprint("sh: printf: -v: option requires an argument\n");
+        return BuiltinResult::code(2);
+    };
+    let text = crate::tools::printf::render(format_args);
+    let element = name
+        .split_once('[')
+        .and_then(|(name, rest)| Some((name, rest.strip_suffix(']')?)));
+    let result = match element {
+        Some((name, key)) if is_name(name) => env.assign_element(name, key, &text),
+        None if is_name(name) => env.assign(name, &text),
+        _ => Err(format!("printf: `{}': not a valid identifier", name)),
+    };
+    assigned(result, env)
+}
+
+/// `umask [-p] [-S] [mode]`: show or set the file mode mask, in octal or,
+/// with `-S`, as the permissions it leaves. WASI has no permission bits, so
+/// the mask is only kept for scripts that read it back.
+fn builtin_umask(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let mut symbolic = false;
+    let mut reusable = false;
+    let mut mode = None;
+    for arg in args {
+        match arg.as_str() {
+            "-S" => symbolic = true,
+            "-p" => reusable = true,
+            _ => mode = Some(arg),
+        }
+    }
+
+    if let Some(mode) = mode {
+        return match parse_umask(mode, env.umask) {
+            Some(mask) => {
+                env.umask = mask;
+                BuiltinResult::code(0)
+            }
+            None => {
+                env.eprint(&format!("sh: umask: {}: invalid mode\n", mode));
+                BuiltinResult::code(1)
+            }
+        };
+    }
+
+    let mask = if symbolic {
+        symbolic_umask(env.umask)
+    } else {
+        format!("{:04o}", env.umask)
+    };
+    let prefix = match (reusable, symbolic) {
+        (true, true) => "umask -S ",
+        (true, false) => "umask ",
+        _ => "",
+    };
+    env.print(&format!("{}{}\n", prefix, mask));
     BuiltinResult::code(0)
 }
+
+/// Parse a mask given in octal, or as the permissions it leaves in the form
+/// `u=rwx,g+r,o-w`.
+fn parse_umask(mode: &str, current: u32) -> Option<u32> {
+    if mode.chars().all(|c| c.is_digit(8)) {
+        return u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|mask| *mask <= 0o777);
+    }
+
+    let mut allowed = !current & 0o777;
+    for clause in mode.split(',') {
+        let (who, perms) = clause.split_at(clause.find(['=', '+', '-'])?);
+        let mut who_bits = 0;
+        for c in who.chars() {
+            who_bits |= match c {
+                'u' => 0o700,
+                'g' => 0o070,
+                'o' => 0o007,
+                'a' => 0o777,
+                _ => return None,
+            };
+        }
+        if who.is_empty() {
+            who_bits = 0o777;
+        }
+        let mut bits = 0;
+        for c in perms[1..].chars() {
+            bits |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                _ => return None,
+            };
+        }
+        bits &= who_bits;
+        match &perms[..1] {
+            "=" => allowed = (allowed & !who_bits) | bits,
+            "+" => allowed |= bits,
+            _ => allowed &= !bits,
+        }
+    }
+    Some(!allowed & 0o777)
+}
+
+/// The permissions a mask leaves, as `u=rwx,g=rx,o=rx`.
+fn symbolic_umask(mask: u32) -> String {
+    let allowed = !mask & 0o777;
+    ["u", "g", "o"]
+        .iter()
+        .enumerate()
+        .map(|(i, who)| {
+            let bits = (allowed >> (6 - 3 * i)) & 0o7;
+            let perms: String = [(0o4, 'r'), (0o2, 'w'), (0o1, 'x')]
+                .iter()
+                .filter(|(bit, _)| bits & bit != 0)
+                .map(|(_, c)| *c)
+                .collect();
+            format!("{}={}", who, perms)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// `shopt [-s|-u] [-pq] [-o] [name...]`: show or change the options that
+/// are not set with `set`, or with `-o` those that are.
+fn builtin_shopt(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let mut change = None;
+    let mut quiet = false;
+    let mut reusable = false;
+    let mut set_options = false;
+    let mut names = Vec::new();
+    for arg in args {
+        let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            names.push(arg.as_str());
+            continue;
+        };
+        for flag in flags.chars() {
+            match flag {
+                's' => change = Some(true),
+                'u' => change = Some(false),
+                'q' => quiet = true,
+                'p' => reusable = true,
+                'o' => set_options = true,
+                _ => {
+                    env.eprint(&format!("sh: shopt: -{}: invalid option\n", flag));
+                    return BuiltinResult::code(2);
+                }
+            }
+        }
+    }
+
+    let options = if set_options {
+        env.options.list()
+    } else {
+        env.options.shopt_list()
+    };
+    let mut status = 0;
+    let mut selected = Vec::new();
+    for name in &names {
+        match options.iter().find(|(option, _)| option == name) {
+            Some(option) => selected.push(*option),
+            None => {
+                env.eprint(&format!("sh: shopt: {}: invalid shell option name\n", name));
+                status = 1;
+            }
+        }
+    }
+
+    if let Some(on) = change {
+        for (name, _) in selected {
+            if set_options {
+                env.options.set(name, on);
+            } else {
+                env.options.set_shopt(name, on);
+            }
+        }
+        // `shopt -s` or `-u` alone lists the options that are on or off
+        if names.is_empty() {
+            for (name, _) in options.into_iter().filter(|(_, value)| *value == on) {
+                env.print(&shopt_line(name, on, reusable, set_options));
+            }
+        }
+        return BuiltinResult::code(status);
+    }
+
+    // Asking about named options fails if any is off
+    let listed = if names.is_empty() { options } else { selected };
+    for (name, on) in listed {
+        if !on && !names.is_empty() {
+            status = 1;
+        }
+        if !quiet {
+            env.print(&shopt_line(name, on, reusable, set_options));
+        }
+    }
+    BuiltinResult::code(status)
+}
+
+/// One line of `shopt` output: a table row, or with `-p` the command that
+/// sets the option.
+fn shopt_line(name: &str, on: bool, reusable: bool, set_options: bool) -> String {
+    match (reusable, set_options) {
+        (true, true) => format!("set {}o {}\n", if on { '-' } else { '+' }, name),
+        (true, false) => format!("shopt {} {}\n", if on { "-s" } else { "-u" }, name),
+        _ => format!("{:<15}\t{}\n", name, if on { "on" } else { "off" }),
+    }
+}
@@ -0,0 +1,310 @@
+//! `[[ ]]` conditional expressions, and the string and file tests they
+//! share with `test` and `[`.
+//!
+//! Inside `[[ ]]` words are not split or globbed, the right side of `==`
+//! and `!=` is a pattern, `=~` matches an extended regular expression and
+//! fills `BASH_REMATCH`, and integer comparisons evaluate arithmetic.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use regex::Regex;
+
+use super::arith;
+use super::ast::CondExpr;
+use super::env::{ShellEnv, Value};
+use super::exec::{DispatchFn, ExecResult};
+use super::expand;
+
+/// How many symlinks resolving one path may go through.
+const MAX_SYMLINKS: usize = 40;
+
+/// Operators taking one operand.
+pub const UNARY_OPERATORS: &[&str] = &[
+    "-a", "-b", "-c", "-d", "-e", "-f", "-g", "-h", "-k", "-n", "-p", "-r", "-s", "-t", "-u", "-v",
+    "-w", "-x", "-z", "-G", "-L", "-N", "-O", "-S",
+];
+
+/// Operators between two operands, besides `<` and `>`, which the lexer
+/// reads as redirections.
+pub const BINARY_OPERATORS: &[&str] = &[
+    "=", "==", "!=", "=~", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+/// Run `[[ expr ]]`: 0 if true, 1 if false, or 2 if it cannot be evaluated.
+pub fn exec_cond(expr: &CondExpr, env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
+    let result = eval(expr, env, dispatch);
+    if std::mem::take(&mut env.expansion_error) {
+        return ExecResult::exit(1);
+    }
+    match result {
+        Ok(true) => ExecResult::code(0),
+        Ok(false) => ExecResult::code(1),
+        Err(e) => {
+            env.eprint(&format!("sh: {}\n", e));
+            ExecResult::code(2)
+        }
+    }
+}
+
+fn eval(expr: &CondExpr, env: &mut ShellEnv, dispatch: DispatchFn) -> Result<bool, String> {
+    Ok(match expr {
+        CondExpr::Not(expr) => !eval(expr, env, dispatch)?,
+        CondExpr::And(left, right) => eval(left, env, dispatch)? && eval(right, env, dispatch)?,
+        CondExpr::Or(left, right) => eval(left, env, dispatch)? || eval(right, env, dispatch)?,
+        CondExpr::Word(word) => !expand::expand_word_to_string(word, env, dispatch).is_empty(),
+        CondExpr::Unary(op, word) => {
+            let operand = expand::expand_word_to_string(word, env, dispatch);
+            match op.as_str() {
+                "-v" => is_set(&operand, env)?,
+                _ => unary_test(op, &operand)
+                    .ok_or_else(|| format!("{}: unary operator expected", op))?,
+            }
+        }
+        CondExpr::Binary(left, op, right) => {
+            let left = expand::expand_word_to_string(left, env, dispatch);
+            match op.as_str() {
+                "=" | "==" => {
+                    expand::glob_match(&expand::expand_pattern(right, env, dispatch), &left)
+                }
+                "!=" => !expand::glob_match(&expand::expand_pattern(right, env, dispatch), &left),
+                "=~" => {
+                    let pattern = expand::expand_regex(right, env, dispatch);
+                    regex_match(&left, &pattern, env)?
+                }
+                "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" => {
+                    let right = expand::expand_word_to_string(right, env, dispatch);
+                    let left = arith::eval(&left, env)?;
+                    let right = arith::eval(&right, env)?;
+                    compare_integers(op, left, right)
+                }
+                _ => {
+                    let right = expand::expand_word_to_string(right, env, dispatch);
+                    binary_test(&left, op, &right)
+                        .ok_or_else(|| format!("{}: binary operator expected", op))?
+                }
+            }
+        }
+    })
+}
+
+/// `-v name`: whether a variable, or an array element as in `a[1]`, is set.
+fn is_set(operand: &str, env: &mut ShellEnv) -> Result<bool, String> {
+    let Some((name, subscript)) = operand
+        .strip_suffix(']')
+        .and_then(|operand| operand.split_once('['))
+    else {
+        return Ok(env.get(operand).is_some());
+    };
+    let (assoc, end) = match env.value(name) {
+        Some(value) => (matches!(value, Value::Assoc(_)), value.next_index() as i64),
+        None => return Ok(false),
+    };
+    let key = if assoc {
+        subscript.to_string()
+    } else {
+        match arith::eval(subscript, env)? {
+            index if index < 0 => (end + index).to_string(),
+            index => index.to_string(),
+        }
+    };
+    Ok(env
+        .value(name)
+        .is_some_and(|value| value.get(&key).is_some()))
+}
+
+/// Match `value` against `pattern`, setting `BASH_REMATCH` to the match and
+/// its groups, or to an empty array if it does not match.
+fn regex_match(value: &str, pattern: &str, env: &mut ShellEnv) -> Result<bool, String> {
+    let regex =
+        Regex::new(pattern).map_err(|_| format!("{}: invalid regular expression", pattern))?;
+    let mut groups = BTreeMap::new();
+    let captures = regex.captures(value);
+    for (i, group) in captures
+        .iter()
+        .flat_map(|captures| captures.iter())
+        .enumerate()
+    {
+        groups.insert(i, group.map_or("", |m| m.as_str()).to_string());
+    }
+    env.set_value("BASH_REMATCH", Value::Indexed(groups));
+    Ok(captures.is_some())
+}
+
+fn compare_integers(op: &str, left: i64, right: i64) -> bool {
+    match op {
+        "-eq" => left == right,
+        "-ne" => left != right,
+        "-lt" => left < right,
+        "-le" => left <= right,
+        "-gt" => left > right,
+        "-ge" => left >= right,
+        _ => false,
+    }
+}
+
+/// Evaluate a test on a string or file, as `test` and `[[ ]]` do. Returns
+/// None for an unknown operator.
+pub fn unary_test(op: &str, operand: &str) -> Option<bool> {
+    let path = Path::new(operand);
+    Some(match op {
+        "-z" => operand.is_empty(),
+        "-n" => !operand.is_empty(),
+        "-e" | "-a" => path.exists(),
+        "-f" => path.is_file(),
+        "-d" => path.is_dir(),
+        "-L" | "-h" => path.is_symlink(),
+        "-s" => fs::metadata(path).is_ok_and(|m| m.len() > 0),
+        "-r" => is_readable(path),
+        "-w" => is_writable(path),
+        "-x" => is_executable(path),
+        // Everything in the sandbox belongs to its one user
+        "-O" | "-G" => path.exists(),
+        "-N" => fs::metadata(path)
+            .is_ok_and(|m| matches!((m.modified(), m.accessed()), (Ok(m), Ok(a)) if m > a)),
+        // Commands never run on a terminal
+        "-t" => false,
+        "-b" | "-c" | "-p" | "-S" | "-u" | "-g" | "-k" => special_file(op, path),
+        _ => return None,
+    })
+}
+
+/// Evaluate a test between two strings, integers or files, as `test`
+/// does. Returns None for an unknown operator.
+pub fn binary_test(left: &str, op: &str, right: &str) -> Option<bool> {
+    let integer = |s: &str| s.trim().parse::<i64>().unwrap_or(0);
+    Some(match op {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "<" => left < right,
+        ">" => left > right,
+        "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" => {
+            compare_integers(op, integer(left), integer(right))
+        }
+        "-nt" => newer(left, right),
+        "-ot" => newer(right, left),
+        "-ef" => match (real_path(Path::new(left)), real_path(Path::new(right))) {
+            (Some(left), Some(right)) => left == right,
+            _ => false,
+        },
+        _ => return None,
+    })
+}
+
+/// Whether file `a` was modified after file `b`, or exists when `b` does
+/// not.
+fn newer(a: &str, b: &str) -> bool {
+    let modified = |path: &str| -> Option<SystemTime> { fs::metadata(path).ok()?.modified().ok() };
+    match (modified(a), modified(b)) {
+        (Some(a), Some(b)) => a > b,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Resolve an existing file's path to one without `.`, `..` or symlinks,
+/// as `fs::canonicalize` would if WASI supported it.
+fn real_path(path: &Path) -> Option<PathBuf> {
+    let absolute = std::env::current_dir().ok()?.join(path);
+    let mut pending: Vec<OsString> = absolute
+        .components()
+        .rev()
+        .map(|c| c.as_os_str().to_owned())
+        .collect();
+    let mut resolved = PathBuf::new();
+    let mut links = 0;
+    while let Some(part) = pending.pop() {
+        match part.to_str() {
+            Some(".") => {}
+            Some("..") => {
+                resolved.pop();
+            }
+            _ => {
+                let next = resolved.join(&part);
+                match fs::read_link(&next) {
+                    Ok(target) => {
+                        links += 1;
+                        if links > MAX_SYMLINKS {
+                            return None;
+                        }
+                        // An absolute target starts again from the root
+                        pending.extend(target.components().rev().map(|c| c.as_os_str().to_owned()));
+                    }
+                    Err(_) => resolved = next,
+                }
+            }
+        }
+    }
+    resolved.exists().then_some(resolved)
+}
+
+fn is_readable(path: &Path) -> bool {
+    if path.is_dir() {
+        fs::read_dir(path).is_ok()
+    } else {
+        fs::File::open(path).is_ok()
+    }
+}
+
+/// Whether a file can be opened for writing, which fails on read-only
+/// mounts, without changing it.
+fn is_writable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(meta) if meta.is_dir() => !meta.permissions().readonly(),
+        Ok(_) => fs::OpenOptions::new().append(true).open(path).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+/// WASI has no permission bits: directories can always be entered, and
+/// files are executable if they start with `#!`.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    use std::io::Read;
+    if path.is_dir() {
+        return true;
+    }
+    let mut start = [0u8; 2];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok()
+        && &start == b"#!"
+}
+
+/// Tests for devices, pipes, sockets and the setuid, setgid and sticky
+/// bits.
+#[cfg(unix)]
+fn special_file(op: &str, path: &Path) -> bool {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    let Ok(meta) = fs::metadata(path) else {
+        return false;
+    };
+    let file_type = meta.file_type();
+    let mode = meta.permissions().mode();
+    match op {
+        "-b" => file_type.is_block_device(),
+        "-c" => file_type.is_char_device(),
+        "-p" => file_type.is_fifo(),
+        "-S" => file_type.is_socket(),
+        "-u" => mode & 0o4000 != 0,
+        "-g" => mode & 0o2000 != 0,
+        "-k" => mode & 0o1000 != 0,
+        _ => false,
+    }
+}
+
+/// WASI exposes no devices, pipes or sockets in the filesystem, nor
+/// permission bits.
+#[cfg(not(unix))]
+fn special_file(_op: &str, _path: &Path) -> bool {
+    false
+}
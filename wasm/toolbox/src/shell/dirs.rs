@@ -0,0 +1,156 @@
+//! The directory stack: `pushd`, `popd` and `dirs`.
+//!
+//! The stack shows the current directory as its first entry, followed by
+//! the directories saved in `ShellEnv::dir_stack`, most recent first.
+
+use super::builtins::{self, BuiltinResult};
+use super::env::ShellEnv;
+
+/// `pushd [dir | +N | -N]`: save the current directory and change to
+/// `dir`. Without an argument the top two entries swap, and `+N` or `-N`
+/// rotates the stack to bring that entry to the top.
+pub fn builtin_pushd(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let mut stack = vec![current_dir()];
+    stack.extend(env.dir_stack.iter().cloned());
+
+    let new_stack = match args.first() {
+        None => {
+            if stack.len() < 2 {
+                env.eprint("sh: pushd: no other directory\n");
+                return BuiltinResult::code(1);
+            }
+            stack.swap(0, 1);
+            stack
+        }
+        Some(spec) => match entry(spec, stack.len()) {
+            Some(Ok(n)) => {
+                stack.rotate_left(n);
+                stack
+            }
+            Some(Err(e)) => {
+                env.eprint(&format!("sh: pushd: {}\n", e));
+                return BuiltinResult::code(1);
+            }
+            None => {
+                stack.insert(0, spec.clone());
+                stack
+            }
+        },
+    };
+
+    if let Err(e) = builtins::change_dir(&new_stack[0], env) {
+        env.eprint(&format!("sh: pushd: {}\n", e));
+        return BuiltinResult::code(1);
+    }
+    env.dir_stack = new_stack[1..].to_vec();
+    print_stack(env, false, false, false);
+    BuiltinResult::code(0)
+}
+
+/// `popd [+N | -N]`: remove the top entry of the stack and change to the
+/// next, or remove entry `N` without changing directory.
+pub fn builtin_popd(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    if env.dir_stack.is_empty() {
+        env.eprint("sh: popd: directory stack empty\n");
+        return BuiltinResult::code(1);
+    }
+
+    let n = match args.first() {
+        None => 0,
+        Some(spec) => match entry(spec, env.dir_stack.len() + 1) {
+            Some(Ok(n)) => n,
+            Some(Err(e)) => {
+                env.eprint(&format!("sh: popd: {}\n", e));
+                return BuiltinResult::code(1);
+            }
+            None => {
+                env.eprint(&format!("sh: popd: {}: invalid argument\n", spec));
+                return BuiltinResult::code(2);
+            }
+        },
+    };
+
+    if n == 0 {
+        if let Err(e) = builtins::change_dir(&env.dir_stack[0].clone(), env) {
+            env.eprint(&format!("sh: popd: {}\n", e));
+            return BuiltinResult::code(1);
+        }
+        env.dir_stack.remove(0);
+    } else {
+        env.dir_stack.remove(n - 1);
+    }
+    print_stack(env, false, false, false);
+    BuiltinResult::code(0)
+}
+
+/// `dirs [-c] [-l] [-p] [-v]`: print the stack on one line, or with `-p`
+/// one entry per line and with `-v` numbered. `-l` prints the home
+/// directory in full rather than as `~`, and `-c` clears the stack.
+pub fn builtin_dirs(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let mut long = false;
+    let mut per_line = false;
+    let mut numbered = false;
+    for arg in args {
+        match arg.as_str() {
+            "-c" => {
+                env.dir_stack.clear();
+                return BuiltinResult::code(0);
+            }
+            "-l" => long = true,
+            "-p" => per_line = true,
+            "-v" => numbered = true,
+            _ => {
+                env.eprint(&format!("sh: dirs: {}: invalid option\n", arg));
+                return BuiltinResult::code(2);
+            }
+        }
+    }
+    print_stack(env, long, per_line, numbered);
+    BuiltinResult::code(0)
+}
+
+/// The entry of the stack `+N` or `-N` refers to, counting from the top or
+/// the bottom, where the current directory is the top. Returns `None` if
+/// `spec` is not of that form, and an error if it is out of range.
+fn entry(spec: &str, len: usize) -> Option<Result<usize, String>> {
+    let (from_bottom, digits) = match spec.split_at_checked(1)? {
+        ("+", digits) => (false, digits),
+        ("-", digits) => (true, digits),
+        _ => return None,
+    };
+    let n: usize = digits.parse().ok()?;
+    if n >= len {
+        return Some(Err(format!("{}: directory stack index out of range", spec)));
+    }
+    Some(Ok(if from_bottom { len - 1 - n } else { n }))
+}
+
+fn print_stack(env: &ShellEnv, long: bool, per_line: bool, numbered: bool) {
+    let home = env.get("HOME").filter(|home| !home.is_empty() && !long);
+    let entries: Vec<String> = std::iter::once(current_dir())
+        .chain(env.dir_stack.iter().cloned())
+        .map(|dir| match home.and_then(|home| dir.strip_prefix(home)) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+            _ => dir,
+        })
+        .collect();
+
+    let text = if numbered {
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, dir)| format!("{:2}  {}\n", i, dir))
+            .collect()
+    } else if per_line {
+        entries.iter().map(|dir| format!("{}\n", dir)).collect()
+    } else {
+        format!("{}\n", entries.join(" "))
+    };
+    env.print(&text);
+}
+
+fn current_dir() -> String {
+    std::env::current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -1,14 +1,27 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+use serde::{Deserialize, Serialize};
+
+use super::arith;
 use super::ast::Command;
+use super::io::Stdio;
+use super::jobs::JobTable;
+use super::procsub::ProcessSubstitution;
 
-/// Shell environment: variables, functions, positional parameters.
-#[derive(Debug, Clone)]
+/// Shell environment: variables, functions, positional parameters, and the
+/// streams commands currently read from and write to.
+///
+/// A subshell run as a child instance is handed the environment as JSON;
+/// what belongs to the running process, such as its streams and jobs, is
+/// left behind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShellEnv {
     /// Local shell variables
-    vars: HashMap<String, String>,
+    vars: HashMap<String, Value>,
     /// Exported variables (also in vars)
     exports: HashMap<String, bool>,
+    /// Attributes given to variables with `declare`
+    attributes: HashMap<String, Attributes>,
     /// Positional parameters ($1, $2, ...)
     pub positional: Vec<String>,
     /// Last exit status ($?)
@@ -16,7 +29,281 @@ pub struct ShellEnv {
     /// Shell functions
     pub functions: HashMap<String, Command>,
     /// Local variable scopes (for function-local vars)
-    local_stack: Vec<HashMap<String, Option<String>>>,
+    local_stack: Vec<HashMap<String, Hidden>>,
+    /// Current stdin/stdout/stderr, replaced while redirections are in effect
+    #[serde(skip, default = "Stdio::inherit")]
+    pub io: Stdio,
+    /// Background jobs started with `&`
+    #[serde(skip)]
+    pub jobs: JobTable,
+    /// Process ID of the most recent background job ($!)
+    pub last_background_pid: Option<u32>,
+    /// Options changed with `set`
+    pub options: ShellOptions,
+    /// How many conditions (`if`, `while`, `&&`, `!`, ...) we are inside,
+    /// where a failing command does not trigger `set -e`
+    pub condition_depth: usize,
+    /// Set when expanding a word failed, for the command being expanded to
+    /// report
+    pub expansion_error: bool,
+    /// Trap actions by signal name (`EXIT`, `ERR`, `INT`, ...); an empty
+    /// action ignores the signal
+    pub traps: HashMap<String, String>,
+    /// How many of the signals received from the host this shell has handled
+    #[serde(skip)]
+    pub signals_handled: usize,
+    /// Process substitutions whose files are in use by the pipelines
+    /// running now
+    #[serde(skip)]
+    pub process_substitutions: Vec<ProcessSubstitution>,
+    /// Aliases defined with `alias`, by name
+    pub aliases: BTreeMap<String, String>,
+    /// Directories saved by `pushd`, most recent first; the current
+    /// directory is not included
+    pub dir_stack: Vec<String>,
+    /// The file mode mask shown and set by `umask`
+    pub umask: u32,
+    /// The `OPTIND` that `getopts` left and how far into that argument it
+    /// has read, for options grouped as in `-ab`
+    pub getopts_position: (usize, usize),
+    /// Redirections in effect, outermost first, as the execution trace
+    /// shows them; only kept while commands are traced
+    pub redirections: Vec<String>,
+}
+
+/// The value and attributes a local variable hides, restored when its
+/// function returns.
+type Hidden = (Option<Value>, Option<Attributes>);
+
+/// Attributes a variable can be given with `declare`, besides being an
+/// array or exported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attributes {
+    /// Values assigned are evaluated as arithmetic (`-i`)
+    pub integer: bool,
+    /// The variable can no longer be assigned or unset (`-r`)
+    pub readonly: bool,
+    /// Values assigned are converted to lower case (`-l`)
+    pub lowercase: bool,
+    /// Values assigned are converted to upper case (`-u`)
+    pub uppercase: bool,
+}
+
+/// The value of a variable: a string, or an array of strings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Value {
+    Scalar(String),
+    /// An indexed array, which may have gaps
+    Indexed(BTreeMap<usize, String>),
+    /// An associative array, declared with `declare -A`
+    Assoc(BTreeMap<String, String>),
+}
+
+impl Value {
+    /// The string `$name` expands to: a scalar's value, or element 0 of an
+    /// array.
+    fn first(&self) -> Option<&str> {
+        self.get("0")
+    }
+
+    /// The element with the given key; indexed arrays take a number.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        match self {
+            Value::Scalar(value) => (key == "0").then_some(value.as_str()),
+            Value::Indexed(items) => key
+                .parse()
+                .ok()
+                .and_then(|index: usize| items.get(&index))
+                .map(String::as_str),
+            Value::Assoc(items) => items.get(key).map(String::as_str),
+        }
+    }
+
+    /// Every element, in order of their keys.
+    pub fn values(&self) -> Vec<String> {
+        match self {
+            Value::Scalar(value) => vec![value.clone()],
+            Value::Indexed(items) => items.values().cloned().collect(),
+            Value::Assoc(items) => items.values().cloned().collect(),
+        }
+    }
+
+    /// Every key, in order.
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            Value::Scalar(_) => vec!["0".to_string()],
+            Value::Indexed(items) => items.keys().map(|index| index.to_string()).collect(),
+            Value::Assoc(items) => items.keys().cloned().collect(),
+        }
+    }
+
+    /// The index after the last element of an indexed array, where `+=`
+    /// appends.
+    pub fn next_index(&self) -> usize {
+        match self {
+            Value::Indexed(items) => items.keys().next_back().map_or(0, |last| last + 1),
+            _ => 1,
+        }
+    }
+
+    /// Set the element with the given key, turning a scalar into an indexed
+    /// array.
+    pub fn set(&mut self, key: &str, value: String) {
+        if let Value::Scalar(scalar) = self {
+            if key == "0" {
+                *scalar = value;
+                return;
+            }
+            *self = Value::Indexed(BTreeMap::from([(0, std::mem::take(scalar))]));
+        }
+        match self {
+            Value::Indexed(items) => {
+                if let Ok(index) = key.parse() {
+                    items.insert(index, value);
+                }
+            }
+            Value::Assoc(items) => {
+                items.insert(key.to_string(), value);
+            }
+            Value::Scalar(_) => unreachable!(),
+        }
+    }
+}
+
+/// Shell options, as set with `set -e` or `set -o pipefail`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShellOptions {
+    /// Exit as soon as a command fails (`-e`)
+    pub errexit: bool,
+    /// Treat expanding an unset variable as an error (`-u`)
+    pub nounset: bool,
+    /// Print every command to stderr before running it (`-x`)
+    pub xtrace: bool,
+    /// Give a pipeline the status of its last failing stage
+    pub pipefail: bool,
+    /// Let functions inherit the ERR trap (`-E`)
+    pub errtrace: bool,
+    /// Let functions inherit the RETURN trap (`-T`)
+    pub functrace: bool,
+    /// Read commands without running them, to check their syntax (`-n`)
+    pub noexec: bool,
+    /// Let glob patterns that match nothing expand to no words (`shopt`)
+    pub nullglob: bool,
+    /// Make glob patterns that match nothing an expansion error (`shopt`)
+    pub failglob: bool,
+    /// Let `**` match any number of directories (`shopt`)
+    pub globstar: bool,
+    /// Accept the extended patterns `?(...)`, `*(...)`, `+(...)`, `@(...)`
+    /// and `!(...)` (`shopt`). They are always recognised; the option is
+    /// kept for scripts that set or test it
+    pub extglob: bool,
+}
+
+/// Long name and single-letter flag of every option.
+const OPTION_NAMES: &[(&str, Option<char>)] = &[
+    ("errexit", Some('e')),
+    ("errtrace", Some('E')),
+    ("functrace", Some('T')),
+    ("noexec", Some('n')),
+    ("nounset", Some('u')),
+    ("pipefail", None),
+    ("xtrace", Some('x')),
+];
+
+/// Names of the options changed with `shopt` rather than `set`.
+const SHOPT_NAMES: &[&str] = &["extglob", "failglob", "globstar", "nullglob"];
+
+impl ShellOptions {
+    fn option_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "errexit" => Some(&mut self.errexit),
+            "errtrace" => Some(&mut self.errtrace),
+            "functrace" => Some(&mut self.functrace),
+            "noexec" => Some(&mut self.noexec),
+            "nounset" => Some(&mut self.nounset),
+            "pipefail" => Some(&mut self.pipefail),
+            "xtrace" => Some(&mut self.xtrace),
+            "extglob" => Some(&mut self.extglob),
+            "globstar" => Some(&mut self.globstar),
+            "nullglob" => Some(&mut self.nullglob),
+            "failglob" => Some(&mut self.failglob),
+            _ => None,
+        }
+    }
+
+    /// Turn an option of `set` on or off by its long name. Returns false if
+    /// there is no such option.
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        OPTION_NAMES.iter().any(|(option, _)| *option == name) && self.update(name, on)
+    }
+
+    /// Turn an option of `shopt` on or off. Returns false if there is no
+    /// such option.
+    pub fn set_shopt(&mut self, name: &str, on: bool) -> bool {
+        SHOPT_NAMES.contains(&name) && self.update(name, on)
+    }
+
+    fn update(&mut self, name: &str, on: bool) -> bool {
+        match self.option_mut(name) {
+            Some(option) => {
+                *option = on;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Turn an option on or off by its flag letter. Returns false if there
+    /// is no such option.
+    pub fn set_flag(&mut self, flag: char, on: bool) -> bool {
+        OPTION_NAMES
+            .iter()
+            .find(|(_, letter)| *letter == Some(flag))
+            .is_some_and(|(name, _)| self.set(name, on))
+    }
+
+    /// Whether the option with the given long name is on.
+    pub fn is_set(&self, name: &str) -> bool {
+        match name {
+            "errexit" => self.errexit,
+            "errtrace" => self.errtrace,
+            "functrace" => self.functrace,
+            "noexec" => self.noexec,
+            "nounset" => self.nounset,
+            "pipefail" => self.pipefail,
+            "xtrace" => self.xtrace,
+            "extglob" => self.extglob,
+            "globstar" => self.globstar,
+            "nullglob" => self.nullglob,
+            "failglob" => self.failglob,
+            _ => false,
+        }
+    }
+
+    /// Every option's long name and whether it is on.
+    pub fn list(&self) -> Vec<(&'static str, bool)> {
+        OPTION_NAMES
+            .iter()
+            .map(|(name, _)| (*name, self.is_set(name)))
+            .collect()
+    }
+
+    /// Every `shopt` option's name and whether it is on.
+    pub fn shopt_list(&self) -> Vec<(&'static str, bool)> {
+        SHOPT_NAMES
+            .iter()
+            .map(|name| (*name, self.is_set(name)))
+            .collect()
+    }
+
+    /// The letters of the options that are on ($-).
+    pub fn flags(&self) -> String {
+        OPTION_NAMES
+            .iter()
+            .filter(|(name, _)| self.is_set(name))
+            .filter_map(|(_, letter)| *letter)
+            .collect()
+    }
 }
 
 impl ShellEnv {
@@ -24,21 +311,47 @@ impl ShellEnv {
         let mut env = ShellEnv {
             vars: HashMap::new(),
             exports: HashMap::new(),
+            attributes: HashMap::new(),
             positional: Vec::new(),
             last_status: 0,
             functions: HashMap::new(),
             local_stack: Vec::new(),
+            io: Stdio::inherit(),
+            jobs: JobTable::default(),
+            last_background_pid: None,
+            options: ShellOptions::default(),
+            condition_depth: 0,
+            expansion_error: false,
+            traps: HashMap::new(),
+            signals_handled: 0,
+            process_substitutions: Vec::new(),
+            aliases: BTreeMap::new(),
+            dir_stack: Vec::new(),
+            umask: 0o022,
+            getopts_position: (1, 1),
+            redirections: Vec::new(),
         };
 
         // Import environment variables
         for (key, value) in std::env::vars() {
-            env.vars.insert(key.clone(), value);
+            env.vars.insert(key.clone(), Value::Scalar(value));
             env.exports.insert(key, true);
         }
 
         env
     }
 
+    /// Write to the current stdout. A reader that has gone away shows up
+    /// through `Output::is_broken`.
+    pub fn print(&self, s: &str) {
+        let _ = self.io.stdout.write(s.as_bytes());
+    }
+
+    /// Write to the current stderr.
+    pub fn eprint(&self, s: &str) {
+        let _ = self.io.stderr.write(s.as_bytes());
+    }
+
     /// Get a variable value.
     pub fn get(&self, name: &str) -> Option<&str> {
         // Special variables
@@ -58,30 +371,153 @@ impl ShellEnv {
             return None;
         }
 
-        self.vars.get(name).map(|s| s.as_str())
+        self.vars.get(name).and_then(Value::first)
     }
 
-    /// Set a variable value.
+    /// Set a variable value. Setting an array sets its element 0.
     pub fn set(&mut self, name: &str, value: &str) {
-        // If we're in a local scope, track the old value
-        if let Some(scope) = self.local_stack.last_mut() {
-            if !scope.contains_key(name) {
-                scope.insert(name.to_string(), self.vars.get(name).cloned());
+        match self.vars.get_mut(name) {
+            Some(current) => current.set("0", value.to_string()),
+            None => {
+                self.vars
+                    .insert(name.to_string(), Value::Scalar(value.to_string()));
+            }
+        }
+    }
+
+    /// Get a variable's value, which may be an array.
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        self.vars.get(name)
+    }
+
+    /// Names of the variables that start with `prefix`, sorted.
+    pub fn names_with_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .vars
+            .keys()
+            .filter(|name| name.starts_with(prefix))
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Replace a variable's value.
+    pub fn set_value(&mut self, name: &str, value: Value) {
+        self.vars.insert(name.to_string(), value);
+    }
+
+    /// Set one element of an array, making the variable an indexed array
+    /// if it is not one yet.
+    pub fn set_element(&mut self, name: &str, key: &str, value: &str) {
+        self.vars
+            .entry(name.to_string())
+            .or_insert_with(|| Value::Indexed(BTreeMap::new()))
+            .set(key, value.to_string());
+    }
+
+    /// Assign a variable as a script does, applying its attributes. Fails
+    /// if it is read-only or an integer assigned a bad expression.
+    pub fn assign(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = self.converted(name, value)?;
+        self.set(name, &value);
+        Ok(())
+    }
+
+    /// Assign one element of an array as a script does, like `assign`.
+    pub fn assign_element(&mut self, name: &str, key: &str, value: &str) -> Result<(), String> {
+        let value = self.converted(name, value)?;
+        self.set_element(name, key, &value);
+        Ok(())
+    }
+
+    /// Replace a variable's value as a script does, applying its attributes
+    /// to every element.
+    pub fn assign_value(&mut self, name: &str, mut value: Value) -> Result<(), String> {
+        match &mut value {
+            Value::Scalar(scalar) => *scalar = self.converted(name, scalar)?,
+            Value::Indexed(items) => {
+                for item in items.values_mut() {
+                    *item = self.converted(name, item)?;
+                }
+            }
+            Value::Assoc(items) => {
+                for item in items.values_mut() {
+                    *item = self.converted(name, item)?;
+                }
             }
         }
-        self.vars.insert(name.to_string(), value.to_string());
+        self.set_value(name, value);
+        Ok(())
+    }
+
+    /// The value a variable holds when assigned `value`, after its
+    /// attributes apply.
+    fn converted(&mut self, name: &str, value: &str) -> Result<String, String> {
+        let attributes = self.attributes(name);
+        if attributes.readonly {
+            return Err(format!("{}: readonly variable", name));
+        }
+        let value = if attributes.integer {
+            arith::eval(value, self)?.to_string()
+        } else {
+            value.to_string()
+        };
+        Ok(if attributes.lowercase {
+            value.to_lowercase()
+        } else if attributes.uppercase {
+            value.to_uppercase()
+        } else {
+            value
+        })
+    }
+
+    /// The attributes a variable has been given.
+    pub fn attributes(&self, name: &str) -> Attributes {
+        self.attributes.get(name).copied().unwrap_or_default()
     }
 
-    /// Unset a variable.
+    /// Replace the attributes of a variable.
+    pub fn set_attributes(&mut self, name: &str, attributes: Attributes) {
+        if attributes == Attributes::default() {
+            self.attributes.remove(name);
+        } else {
+            self.attributes.insert(name.to_string(), attributes);
+        }
+    }
+
+    /// Unset a variable, along with its attributes.
     pub fn unset(&mut self, name: &str) {
         self.vars.remove(name);
         self.exports.remove(name);
+        self.attributes.remove(name);
+    }
+
+    /// Stop exporting a variable (`export -n`).
+    pub fn unexport(&mut self, name: &str) {
+        self.exports.remove(name);
+    }
+
+    /// Unset one element of an array.
+    pub fn unset_element(&mut self, name: &str, key: &str) {
+        match self.vars.get_mut(name) {
+            Some(Value::Indexed(items)) => {
+                if let Ok(index) = key.parse() {
+                    items.remove(&index);
+                }
+            }
+            Some(Value::Assoc(items)) => {
+                items.remove(key);
+            }
+            Some(Value::Scalar(_)) if key == "0" => self.unset(name),
+            _ => {}
+        }
     }
 
     /// Mark a variable as exported.
     pub fn export(&mut self, name: &str, value: Option<&str>) {
         if let Some(v) = value {
-            self.vars.insert(name.to_string(), v.to_string());
+            self.set(name, v);
         }
         self.exports.insert(name.to_string(), true);
     }
@@ -91,11 +527,15 @@ impl ShellEnv {
         self.exports.contains_key(name)
     }
 
-    /// Get all exported variables for child processes.
+    /// Get all exported variables for child processes. Arrays are not
+    /// exported.
     pub fn exported_vars(&self) -> Vec<(String, String)> {
         self.exports
             .keys()
-            .filter_map(|k| self.vars.get(k).map(|v| (k.clone(), v.clone())))
+            .filter_map(|k| match self.vars.get(k) {
+                Some(Value::Scalar(v)) => Some((k.clone(), v.clone())),
+                _ => None,
+            })
             .collect()
     }
 
@@ -121,27 +561,44 @@ impl ShellEnv {
         self.local_stack.push(HashMap::new());
     }
 
-    /// Pop a local variable scope, restoring old values.
+    /// Pop a local variable scope, restoring old values and attributes.
     pub fn pop_local_scope(&mut self) {
         if let Some(scope) = self.local_stack.pop() {
-            for (name, old_value) in scope {
+            for (name, (old_value, old_attributes)) in scope {
                 match old_value {
                     Some(v) => {
-                        self.vars.insert(name, v);
+                        self.vars.insert(name.clone(), v);
                     }
                     None => {
                         self.vars.remove(&name);
                     }
                 }
+                match old_attributes {
+                    Some(attributes) => {
+                        self.attributes.insert(name, attributes);
+                    }
+                    None => {
+                        self.attributes.remove(&name);
+                    }
+                }
             }
         }
     }
 
     /// Declare a variable as local (only meaningful inside a function).
     pub fn declare_local(&mut self, name: &str) {
-        if let Some(scope) = self.local_stack.last_mut() {
-            if !scope.contains_key(name) {
-                scope.insert(name.to_string(), self.vars.get(name).cloned());
+        if let Some(scope) = self.local_stack.last_mut()
+            && !scope.contains_key(name)
+        {
+            let saved = (
+                self.vars.get(name).cloned(),
+                self.attributes.get(name).copied(),
+            );
+            scope.insert(name.to_string(), saved);
+            // A local variable starts without the attributes of the one
+            // it hides, though a read-only variable cannot be hidden
+            if !self.attributes(name).readonly {
+                self.attributes.remove(name);
             }
         }
     }
@@ -1,15 +1,24 @@
 use std::fs;
-use std::io::Write;
 
+use super::alias;
 use super::ast::*;
+use super::audit;
 use super::builtins::{self, ControlFlow};
-use super::env::ShellEnv;
+use super::cond;
+use super::env::{Attributes, ShellEnv, Value};
 use super::expand;
-use super::pipeline::PipelineState;
+use super::io::{Output, Stdio};
+use super::jobs;
+use super::pipeline;
+use super::procsub;
 use super::redirect;
+use super::traps;
 
 /// The dispatch function from main.rs, used to execute external commands.
-type DispatchFn = fn(&str, &[String]) -> i32;
+pub type DispatchFn = fn(&str, &[String]) -> i32;
+
+/// Exit code of a command stopped because its output is no longer read.
+const EXIT_BROKEN_PIPE: i32 = 141;
 
 /// Result of executing a command — may include control flow signals.
 pub struct ExecResult {
@@ -19,7 +28,7 @@ pub struct ExecResult {
 }
 
 impl ExecResult {
-    fn code(c: i32) -> Self {
+    pub fn code(c: i32) -> Self {
         ExecResult {
             exit_code: c,
             should_exit: false,
@@ -27,7 +36,7 @@ impl ExecResult {
         }
     }
 
-    fn exit(c: i32) -> Self {
+    pub fn exit(c: i32) -> Self {
         ExecResult {
             exit_code: c,
             should_exit: true,
@@ -45,749 +54,633 @@ impl ExecResult {
 }
 
 /// Execute a program (list of commands).
-pub fn exec_program(
-    program: &Program,
-    env: &mut ShellEnv,
-    dispatch: DispatchFn,
-) -> ExecResult {
+pub fn exec_program(program: &Program, env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
     let mut last_code = 0;
 
     for cmd in &program.commands {
-        let result = exec_complete_command(cmd, env, dispatch);
+        let result = if cmd.background {
+            jobs::run_background(cmd, env, dispatch)
+        } else {
+            exec_complete_command(cmd, env, dispatch)
+        };
         last_code = result.exit_code;
         env.last_status = last_code;
 
         if result.should_exit || result.control_flow.is_some() {
             return result;
         }
+        if let Some(result) = traps::check_signals(env, dispatch) {
+            return result;
+        }
+
+        // Like SIGPIPE, stop once nothing reads our output any more
+        if env.io.stdout.is_broken() {
+            return ExecResult::code(EXIT_BROKEN_PIPE);
+        }
+        // After `set -n`, the rest of the script is only read
+        if env.options.noexec {
+            break;
+        }
     }
 
     ExecResult::code(last_code)
 }
 
-/// Execute a complete command (pipeline && pipeline || pipeline ...).
-fn exec_complete_command(
+/// Execute a complete command (pipeline && pipeline || pipeline ...) in the
+/// foreground.
+pub fn exec_complete_command(
     cmd: &CompleteCommand,
     env: &mut ShellEnv,
     dispatch: DispatchFn,
 ) -> ExecResult {
-    let mut result = exec_pipeline(&cmd.first, env, dispatch);
-    env.last_status = result.exit_code;
+    let pipelines = std::iter::once((None, &cmd.first))
+        .chain(cmd.rest.iter().map(|(op, pipeline)| (Some(op), pipeline)));
+    let last = cmd.rest.len();
+    let mut result = ExecResult::code(0);
 
-    if result.should_exit || result.control_flow.is_some() {
-        return result;
-    }
-
-    for (op, pipeline) in &cmd.rest {
+    for (i, (op, pipeline)) in pipelines.enumerate() {
         let should_run = match op {
-            ListOp::And => result.exit_code == 0,
-            ListOp::Or => result.exit_code != 0,
+            None => true,
+            Some(ListOp::And) => result.exit_code == 0,
+            Some(ListOp::Or) => result.exit_code != 0,
         };
+        if !should_run {
+            continue;
+        }
 
-        if should_run {
-            result = exec_pipeline(pipeline, env, dispatch);
-            env.last_status = result.exit_code;
+        // Only the pipeline after the final && or || can trigger `set -e`
+        result = if i < last {
+            in_condition(env, |env| exec_pipeline(pipeline, env, dispatch))
+        } else {
+            exec_pipeline(pipeline, env, dispatch)
+        };
+        env.last_status = result.exit_code;
 
-            if result.should_exit || result.control_flow.is_some() {
+        if result.should_exit || result.control_flow.is_some() {
+            return result;
+        }
+        if i == last && !pipeline.negated && counts_as_failure(result.exit_code, env) {
+            if let Some(result) = traps::run_trap("ERR", env, dispatch) {
                 return result;
             }
+            if env.options.errexit {
+                return ExecResult::exit(result.exit_code);
+            }
         }
     }
 
     result
 }
 
+/// Run `f` as part of a condition, where a failing command does not
+/// trigger `set -e`.
+fn in_condition<T>(env: &mut ShellEnv, f: impl FnOnce(&mut ShellEnv) -> T) -> T {
+    env.condition_depth += 1;
+    let result = f(env);
+    env.condition_depth -= 1;
+    result
+}
+
+/// Whether a command that exited with `code` failed outside any condition,
+/// which runs the ERR trap and makes the shell exit under `set -e`.
+fn counts_as_failure(code: i32, env: &ShellEnv) -> bool {
+    code != 0 && env.condition_depth == 0
+}
+
 /// Execute a pipeline.
-fn exec_pipeline(
-    pipeline: &Pipeline,
-    env: &mut ShellEnv,
-    dispatch: DispatchFn,
-) -> ExecResult {
-    if pipeline.commands.len() == 1 {
-        let result = exec_command(&pipeline.commands[0], env, dispatch, None, None);
-        let exit_code = if pipeline.negated {
-            if result.exit_code == 0 { 1 } else { 0 }
-        } else {
-            result.exit_code
-        };
-        return ExecResult {
+fn exec_pipeline(pipeline: &Pipeline, env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
+    // Process substitutions made for the pipeline last until it ends
+    let substitutions = env.process_substitutions.len();
+    let result = if pipeline.negated {
+        // Nothing inside a negated pipeline triggers `set -e`
+        let result = in_condition(env, |env| exec_stages(&pipeline.commands, env, dispatch));
+        let exit_code = if result.exit_code == 0 { 1 } else { 0 };
+        ExecResult {
             exit_code,
             ..result
-        };
-    }
-
-    // Multi-command pipeline: use temp files
-    let pipe_state = PipelineState::new(pipeline.commands.len());
-    let mut last_code = 0;
-
-    for (i, cmd) in pipeline.commands.iter().enumerate() {
-        let stdin_file = pipe_state.input_for(i);
-        let stdout_file = pipe_state.output_for(i);
-
-        let result = exec_command(cmd, env, dispatch, stdin_file, stdout_file);
-        last_code = result.exit_code;
-
-        if result.should_exit || result.control_flow.is_some() {
-            pipe_state.cleanup();
-            return result;
         }
+    } else {
+        exec_stages(&pipeline.commands, env, dispatch)
+    };
+    procsub::finish(substitutions, env, dispatch);
+    result
+}
+
+/// Execute the commands of a pipeline, ignoring any negation.
+fn exec_stages(commands: &[Command], env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
+    if commands.len() == 1 {
+        return exec_command(&commands[0], env, dispatch);
     }
 
-    pipe_state.cleanup();
+    let codes = pipeline::exec_pipeline(commands, env, dispatch);
 
-    let exit_code = if pipeline.negated {
-        if last_code == 0 { 1 } else { 0 }
+    // With pipefail, the last stage to fail decides the status
+    let exit_code = if env.options.pipefail {
+        codes.iter().rev().find(|&&code| code != 0)
     } else {
-        last_code
+        codes.last()
     };
-
-    ExecResult::code(exit_code)
+    ExecResult::code(exit_code.copied().unwrap_or(0))
 }
 
 /// Execute a single command.
-fn exec_command(
-    cmd: &Command,
-    env: &mut ShellEnv,
-    dispatch: DispatchFn,
-    pipe_stdin: Option<&str>,
-    pipe_stdout: Option<&str>,
-) -> ExecResult {
+pub fn exec_command(cmd: &Command, env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
     match cmd {
-        Command::Simple(simple) => exec_simple(simple, env, dispatch, pipe_stdin, pipe_stdout),
+        Command::Simple(simple) => exec_simple(simple, env, dispatch),
         Command::If(if_clause) => exec_if(if_clause, env, dispatch),
         Command::For(for_clause) => exec_for(for_clause, env, dispatch),
         Command::While(while_clause) => exec_while(while_clause, env, dispatch),
         Command::Until(until_clause) => exec_until(until_clause, env, dispatch),
         Command::Case(case_clause) => exec_case(case_clause, env, dispatch),
+        Command::Arithmetic(expr) => {
+            if env.options.xtrace {
+                trace_line(&format!("(( {} ))", expr.trim()), env);
+            }
+            match eval_arithmetic(expr, env, dispatch) {
+                Ok(value) => ExecResult::code(if value != 0 { 0 } else { 1 }),
+                Err(result) => result,
+            }
+        }
+        Command::ArithFor(clause) => exec_arith_for(clause, env, dispatch),
+        Command::Cond(expr) => cond::exec_cond(expr, env, dispatch),
         Command::Subshell(program) => {
             let mut sub_env = env.clone();
+            traps::reset_for_subshell(&mut sub_env);
             let result = exec_program(program, &mut sub_env, dispatch);
-            ExecResult::code(result.exit_code)
+            let code = traps::run_exit_trap(&mut sub_env, dispatch, result.exit_code);
+            sub_env.jobs.wait_all();
+            ExecResult::code(code)
         }
         Command::BraceGroup(program) => exec_program(program, env, dispatch),
         Command::FuncDef(func_def) => {
-            env.functions.insert(func_def.name.clone(), *func_def.body.clone());
+            env.functions
+                .insert(func_def.name.clone(), *func_def.body.clone());
             ExecResult::code(0)
         }
+        Command::Redirected(cmd, redirections) => {
+            with_redirections(redirections, env, dispatch, |env| {
+                exec_command(cmd, env, dispatch)
+            })
+        }
     }
 }
 
 /// Execute a simple command.
-fn exec_simple(
+fn exec_simple(cmd: &SimpleCommand, env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
+    match prepare_simple(cmd, env, dispatch) {
+        Ok(words) => exec_prepared(cmd, &words, env, dispatch),
+        Err(result) => result,
+    }
+}
+
+/// Expand a command's words into its argv.
+pub fn expand_words(words: &[Word], env: &mut ShellEnv, dispatch: DispatchFn) -> Vec<String> {
+    let mut expanded_words: Vec<String> = Vec::new();
+    for word in words {
+        expanded_words.extend(expand::expand_word(word, env, dispatch));
+    }
+    expanded_words
+}
+
+/// Get a simple command ready to run: expand its words, make its
+/// assignments and trace it. Returns its argv, or its result if there is
+/// nothing left to run.
+pub fn prepare_simple(
     cmd: &SimpleCommand,
     env: &mut ShellEnv,
     dispatch: DispatchFn,
-    pipe_stdin: Option<&str>,
-    pipe_stdout: Option<&str>,
-) -> ExecResult {
-    // Expand words
-    let mut expanded_words: Vec<String> = Vec::new();
-    for word in &cmd.words {
-        let words = expand::expand_word(word, env, dispatch);
-        expanded_words.extend(words);
-    }
+) -> Result<Vec<String>, ExecResult> {
+    let expanded_words = expand_words(&cmd.words, env, dispatch);
 
-    // Process assignments
-    for assign in &cmd.assignments {
-        let value = expand::expand_word_to_string(&assign.value, env, dispatch);
-        env.set(&assign.name, &value);
+    // Process assignments; one that fails stops the command
+    let mut assigned = true;
+    for assignment in &cmd.assignments {
+        if let Err(e) = assign(assignment, env, dispatch) {
+            env.eprint(&format!("sh: {}\n", e));
+            assigned = false;
+        }
+    }
+    if std::mem::take(&mut env.expansion_error) {
+        return Err(ExecResult::exit(1));
+    }
+    if !assigned {
+        return Err(ExecResult::code(1));
     }
 
-    // If no command words, just apply assignments
+    // If no command words, just apply assignments; the status is that of
+    // the last command substitution, if any
     if expanded_words.is_empty() {
-        return ExecResult::code(0);
+        let substituted = cmd
+            .assignments
+            .iter()
+            .flat_map(assigned_words)
+            .chain(&cmd.words)
+            .any(|word| has_command_sub(&word.parts));
+        return Err(ExecResult::code(if substituted { env.last_status } else { 0 }));
     }
 
-    let cmd_name = &expanded_words[0];
-    let cmd_args = &expanded_words[1..];
+    trace(&expanded_words, env);
+    Ok(expanded_words)
+}
 
-    // Expand redirection targets
-    let expanded_targets: Vec<String> = cmd.redirections
+/// Run a simple command `prepare_simple` got ready, given its argv.
+pub fn exec_prepared(
+    cmd: &SimpleCommand,
+    expanded_words: &[String],
+    env: &mut ShellEnv,
+    dispatch: DispatchFn,
+) -> ExecResult {
+    // Declaration commands assign the arrays given as arguments once the
+    // names are declared, as in `local -a list=(a b)`. Only variables that
+    // were read-only beforehand refuse them, so `readonly list=(a b)` works
+    let declared: Vec<(&Assignment, bool)> = cmd
+        .words
         .iter()
-        .map(|r| expand::expand_word_to_string(&r.target, env, dispatch))
+        .filter_map(|word| match word.parts.as_slice() {
+            [WordPart::Assignment(assignment)] => Some(assignment.as_ref()),
+            _ => None,
+        })
+        .map(|assignment| (assignment, env.attributes(&assignment.name).readonly))
         .collect();
 
-    // Apply redirections
-    let redir_state = redirect::apply_redirections(&cmd.redirections, &expanded_targets)
-        .unwrap_or_else(|e| {
-            eprintln!("sh: {}", e);
-            redirect::RedirectState::new()
-        });
-
-    // Determine actual stdin content
-    let stdin_content = redir_state.stdin_content.or_else(|| {
-        pipe_stdin.and_then(|f| fs::read_to_string(f).ok())
+    let alias = alias::expansion(cmd, expanded_words, env);
+    let result = with_redirections(&cmd.redirections, env, dispatch, |env| match &alias {
+        Some((name, script)) => alias::run(name, script, env, dispatch),
+        None => exec_words(expanded_words, env, dispatch, true),
     });
 
-    // Determine stdout target
-    let stdout_target = redir_state.stdout_file.as_deref().or(pipe_stdout);
-    let stderr_target = redir_state.stderr_file.as_deref();
-
-    // Check for builtins
-    if builtins::is_builtin(cmd_name) {
-        let args: Vec<String> = cmd_args.to_vec();
-        let builtin_result = builtins::run_builtin(cmd_name, &args, env);
-
-        if builtin_result.should_exit {
-            return ExecResult::exit(builtin_result.exit_code);
+    for (assignment, readonly) in declared {
+        let attributes = env.attributes(&assignment.name);
+        if !readonly {
+            let writable = Attributes {
+                readonly: false,
+                ..attributes
+            };
+            env.set_attributes(&assignment.name, writable);
         }
-        if let Some(cf) = builtin_result.control_flow {
-            return ExecResult::control(cf);
+        let assigned = assign(assignment, env, dispatch);
+        env.set_attributes(&assignment.name, attributes);
+        if let Err(e) = assigned {
+            env.eprint(&format!("sh: {}\n", e));
+            return ExecResult::code(1);
         }
-        return ExecResult::code(builtin_result.exit_code);
     }
+    result
+}
 
-    // Check for shell functions
-    if let Some(func_body) = env.functions.get(cmd_name).cloned() {
-        // Save positional params
-        let saved_positional = env.positional.clone();
-        env.positional = cmd_args.to_vec();
-        env.push_local_scope();
-
-        let result = exec_command(&func_body, env, dispatch, pipe_stdin, pipe_stdout);
-
-        env.pop_local_scope();
-        env.positional = saved_positional;
+/// Perform an assignment: `name=value`, `name[i]=value`, `name=(x y)`, or
+/// any of them with `+=`. Fails if the variable's attributes refuse the
+/// value.
+fn assign(assignment: &Assignment, env: &mut ShellEnv, dispatch: DispatchFn) -> Result<(), String> {
+    let name = assignment.name.as_str();
+    let (assoc, end) = match env.value(name) {
+        Some(value) => (matches!(value, Value::Assoc(_)), value.next_index()),
+        None => (false, 0),
+    };
 
-        let exit_code = if let Some(ControlFlow::Return(code)) = result.control_flow {
-            code
-        } else {
-            if result.control_flow.is_some() {
-                return result;
+    match (&assignment.value, &assignment.index) {
+        (AssignValue::Scalar(word), None) => {
+            let mut value = expand::expand_assigned(word, env, dispatch);
+            if env.options.xtrace {
+                trace_line(&format!("{}={}", name, trace_quote(&value)), env);
             }
-            result.exit_code
-        };
-
-        return ExecResult::code(exit_code);
-    }
-
-    // Check for eval
-    if cmd_name == "eval" {
-        let script = cmd_args.join(" ");
-        let (code, output) = exec_capture(&script, env, dispatch);
-        if !output.is_empty() {
-            if let Some(target) = stdout_target {
-                let _ = write_to_file(target, &output, redir_state.stdout_file.is_some()
-                    && cmd.redirections.iter().any(|r| matches!(r.kind, RedirectKind::Append)));
-            } else {
-                print!("{}", output);
+            if assignment.append {
+                value = append_value(name, env.get(name), &value, env);
             }
+            env.assign(name, &value)
         }
-        return ExecResult::code(code);
-    }
-
-    // Check for source / .
-    if cmd_name == "source" || cmd_name == "." {
-        if let Some(file) = cmd_args.first() {
-            match fs::read_to_string(file) {
-                Ok(content) => {
-                    let (code, _) = exec_capture(&content, env, dispatch);
-                    return ExecResult::code(code);
-                }
-                Err(e) => {
-                    eprintln!("sh: {}: {}", file, e);
-                    return ExecResult::code(1);
+        (AssignValue::Scalar(word), Some(index)) => {
+            let Some(key) = expand::subscript(index, assoc, end, env, dispatch) else {
+                return Err(format!("{}: bad array subscript", name));
+            };
+            let mut value = expand::expand_assigned(word, env, dispatch);
+            if env.options.xtrace {
+                trace_line(&format!("{}[{}]={}", name, key, trace_quote(&value)), env);
+            }
+            if assignment.append {
+                let current = env.value(name).and_then(|array| array.get(&key));
+                value = append_value(name, current.map(str::to_string).as_deref(), &value, env);
+            }
+            env.assign_element(name, &key, &value)
+        }
+        (AssignValue::Array(elements), _) => {
+            let mut array = match env.value(name) {
+                Some(current) if assignment.append => current.clone(),
+                _ if assoc => Value::Assoc(Default::default()),
+                _ => Value::Indexed(Default::default()),
+            };
+            // Associative arrays also take `(key value key value ...)`
+            let mut pending_key: Option<String> = None;
+            for (index, word) in elements {
+                let next = array.next_index();
+                match index {
+                    Some(index) => {
+                        let Some(key) = expand::subscript(index, assoc, next, env, dispatch) else {
+                            env.eprint(&format!("sh: {}: bad array subscript\n", name));
+                            continue;
+                        };
+                        let value = expand::expand_word_to_string(word, env, dispatch);
+                        array.set(&key, value);
+                    }
+                    None if assoc => {
+                        for value in expand::expand_word(word, env, dispatch) {
+                            match pending_key.take() {
+                                Some(key) => array.set(&key, value),
+                                None => pending_key = Some(value),
+                            }
+                        }
+                    }
+                    None => {
+                        for (offset, value) in expand::expand_word(word, env, dispatch)
+                            .into_iter()
+                            .enumerate()
+                        {
+                            array.set(&(next + offset).to_string(), value);
+                        }
+                    }
                 }
             }
+            if let Some(key) = pending_key {
+                array.set(&key, String::new());
+            }
+            if env.options.xtrace {
+                let values: Vec<String> = array.values().iter().map(|v| trace_quote(v)).collect();
+                trace_line(&format!("{}=({})", name, values.join(" ")), env);
+            }
+            env.assign_value(name, array)
         }
-        return ExecResult::code(0);
     }
+}
 
-    // External command via dispatch
-    // We need to handle stdin/stdout redirection via temp files since
-    // the dispatch function uses actual stdout/stderr
-
-    // If we have stdin content, write it to a temp file and set it up
-    // For simple cases, we can use the dispatch directly
-    if stdin_content.is_none() && stdout_target.is_none() && stderr_target.is_none() {
-        let args: Vec<String> = cmd_args.to_vec();
-        let code = dispatch(cmd_name, &args);
-        return ExecResult::code(code);
+/// The value `+=` assigns: the sum for an integer variable, otherwise the
+/// current value with `value` appended.
+fn append_value(name: &str, current: Option<&str>, value: &str, env: &ShellEnv) -> String {
+    let current = current.unwrap_or("");
+    if env.attributes(name).integer {
+        format!(
+            "{}+({})",
+            if current.is_empty() { "0" } else { current },
+            value
+        )
+    } else {
+        format!("{}{}", current, value)
     }
+}
 
-    // For redirected commands, capture output
-    let args: Vec<String> = cmd_args.to_vec();
-
-    // Handle stdin redirection via fd_renumber on fd 0 and file args
-    if let Some(ref content) = stdin_content {
-        let stdin_file = "/work/.sh_stdin_tmp";
-        let _ = fs::write(stdin_file, content);
+/// The words an assignment expands.
+fn assigned_words(assignment: &Assignment) -> Vec<&Word> {
+    let values: Vec<&Word> = match &assignment.value {
+        AssignValue::Scalar(word) => vec![word],
+        AssignValue::Array(elements) => elements.iter().map(|(_, word)| word).collect(),
+    };
+    assignment.index.iter().chain(values).collect()
+}
 
-        // Try to redirect WASI stdin (fd 0) to the file
-        let stdin_redirected = redirect_stdin_to_file(stdin_file);
+/// Whether a word contains a command substitution.
+fn has_command_sub(parts: &[WordPart]) -> bool {
+    parts.iter().any(|part| match part {
+        WordPart::CommandSub(_) => true,
+        WordPart::DoubleQuoted(inner) => has_command_sub(inner),
+        _ => false,
+    })
+}
 
-        let actual_args = if stdin_redirected {
-            // stdin is redirected — the command can read from stdin normally
-            args.clone()
-        } else {
-            // Fallback: add the file as an argument for commands that read files
-            let mut new_args = args.clone();
-            if !matches!(cmd_name.as_str(), "echo" | "printf" | "mkdir" | "rm" | "cp" | "mv" | "ln" | "touch" | "chmod") {
-                if new_args.is_empty() || new_args.iter().all(|a| a.starts_with('-')) {
-                    new_args.push(stdin_file.to_string());
-                }
-            }
-            new_args
-        };
+/// Under `set -x`, print a command about to run to stderr, prefixed with
+/// `$PS4`.
+pub fn trace(words: &[String], env: &ShellEnv) {
+    if env.options.xtrace {
+        let quoted: Vec<String> = words.iter().map(|word| trace_quote(word)).collect();
+        trace_line(&quoted.join(" "), env);
+    }
+}
 
-        let result = if let Some(target) = stdout_target {
-            let (code, output) = capture_dispatch(cmd_name, &actual_args, dispatch);
-            let append = cmd.redirections.iter().any(|r| matches!(r.kind, RedirectKind::Append) && r.fd.unwrap_or(1) == 1);
-            let _ = write_to_file(target, &output, append);
-            ExecResult::code(code)
-        } else {
-            let code = dispatch(cmd_name, &actual_args);
-            ExecResult::code(code)
-        };
+fn trace_line(line: &str, env: &ShellEnv) {
+    let prefix = env.get("PS4").unwrap_or("+ ");
+    env.eprint(&format!("{}{}\n", prefix, line));
+}
 
-        if stdin_redirected {
-            restore_stdin();
-        }
-        let _ = fs::remove_file(stdin_file);
-        return result;
-    } else if let Some(target) = stdout_target {
-        // Capture stdout to file
-        let (code, output) = capture_dispatch(cmd_name, &args, dispatch);
-        let append = cmd.redirections.iter().any(|r| matches!(r.kind, RedirectKind::Append) && r.fd.unwrap_or(1) == 1);
-        let _ = write_to_file(target, &output, append);
-        return ExecResult::code(code);
+/// Quote a word for `set -x` output the way bash does, only when needed.
+fn trace_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-+=./:,@%^".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_string();
     }
-
-    let code = dispatch(cmd_name, &args);
-    ExecResult::code(code)
+    format!("'{}'", word.replace('\'', "'\\''"))
 }
 
-/// Execute a script and capture its stdout output.
-pub fn exec_capture(
-    script: &str,
+/// Run `f` with redirections applied to the shell's streams for the
+/// duration of the command.
+fn with_redirections(
+    redirections: &[Redirect],
     env: &mut ShellEnv,
     dispatch: DispatchFn,
-) -> (i32, String) {
-    use super::parser::Parser;
-
-    let mut parser = Parser::new(script);
-    let program = match parser.parse_program() {
-        Ok(p) => p,
+    f: impl FnOnce(&mut ShellEnv) -> ExecResult,
+) -> ExecResult {
+    let base = env.io.clone();
+    let targets = expand_targets(redirections, env, dispatch);
+    let io = match redirect::apply_redirections(redirections, &targets, &base) {
+        Ok(io) => io,
         Err(e) => {
-            return (2, format!("sh: parse error: {}\n", e));
+            env.eprint(&format!("sh: {}\n", e));
+            return ExecResult::code(1);
         }
     };
-
-    // For command substitution, we need to capture stdout.
-    // In WASM, we redirect to a temp file.
-    let capture_file = "/work/.sh_capture_tmp";
-
-    // Execute each command, redirecting stdout to capture file
-    let mut total_output = String::new();
-    let mut last_code = 0;
-
-    for cmd in &program.commands {
-        // We need to wrap each pipeline's commands to redirect to capture file
-        let (code, output) = exec_and_capture_complete_cmd(cmd, env, dispatch);
-        last_code = code;
-        total_output.push_str(&output);
+    if std::mem::take(&mut env.expansion_error) {
+        return ExecResult::exit(1);
     }
 
-    let _ = fs::remove_file(capture_file);
-    (last_code, total_output)
+    let traced = env.redirections.len();
+    if audit::enabled() {
+        let described = redirections.iter().zip(&targets);
+        env.redirections
+            .extend(described.map(|(redirect, target)| audit::describe(redirect, target)));
+    }
+    let outer = std::mem::replace(&mut env.io, io);
+    let result = f(env);
+    env.io = outer;
+    env.redirections.truncate(traced);
+    result
 }
 
-fn exec_and_capture_complete_cmd(
-    cmd: &CompleteCommand,
+/// Expand redirection targets and apply the redirections on top of `base`.
+pub fn redirected_io(
+    redirections: &[Redirect],
+    base: &Stdio,
     env: &mut ShellEnv,
     dispatch: DispatchFn,
-) -> (i32, String) {
-    // For captures, we run commands and collect their output
-    let mut output = String::new();
-
-    let mut result = exec_and_capture_pipeline(&cmd.first, env, dispatch);
-    output.push_str(&result.1);
-    env.last_status = result.0;
-
-    for (op, pipeline) in &cmd.rest {
-        let should_run = match op {
-            ListOp::And => result.0 == 0,
-            ListOp::Or => result.0 != 0,
-        };
-
-        if should_run {
-            result = exec_and_capture_pipeline(pipeline, env, dispatch);
-            output.push_str(&result.1);
-            env.last_status = result.0;
-        }
-    }
-
-    (result.0, output)
+) -> Result<Stdio, String> {
+    let targets = expand_targets(redirections, env, dispatch);
+    redirect::apply_redirections(redirections, &targets, base)
 }
 
-fn exec_and_capture_pipeline(
-    pipeline: &Pipeline,
+fn expand_targets(
+    redirections: &[Redirect],
     env: &mut ShellEnv,
     dispatch: DispatchFn,
-) -> (i32, String) {
-    if pipeline.commands.len() == 1 {
-        let result = exec_and_capture_single(&pipeline.commands[0], env, dispatch, None);
-        let code = if pipeline.negated {
-            if result.0 == 0 { 1 } else { 0 }
-        } else {
-            result.0
-        };
-        return (code, result.1);
-    }
-
-    // Multi-stage pipeline with capture
-    let pipe_state = PipelineState::new(pipeline.commands.len());
-    let mut last_code = 0;
-    let mut last_output = String::new();
-
-    for (i, cmd) in pipeline.commands.iter().enumerate() {
-        let stdin_file = pipe_state.input_for(i);
-
-        if i < pipeline.commands.len() - 1 {
-            // Not last stage: write output to pipe file
-            let stdout_file = pipe_state.output_for(i).unwrap();
-            let (code, output) = exec_and_capture_single(cmd, env, dispatch, stdin_file);
-            let _ = fs::write(stdout_file, &output);
-            last_code = code;
-        } else {
-            // Last stage: capture output
-            let (code, output) = exec_and_capture_single(cmd, env, dispatch, stdin_file);
-            last_code = code;
-            last_output = output;
-        }
-    }
-
-    pipe_state.cleanup();
-
-    let code = if pipeline.negated {
-        if last_code == 0 { 1 } else { 0 }
-    } else {
-        last_code
-    };
-
-    (code, last_output)
+) -> Vec<String> {
+    redirections
+        .iter()
+        .map(|r| expand::expand_word_to_string(&r.target, env, dispatch))
+        .collect()
 }
 
-fn exec_and_capture_single(
-    cmd: &Command,
+/// Run an expanded command: eval, source, a builtin, a function, or a tool.
+/// Functions are skipped unless `functions`, as for `command name`.
+fn exec_words(
+    words: &[String],
     env: &mut ShellEnv,
     dispatch: DispatchFn,
-    pipe_stdin: Option<&str>,
-) -> (i32, String) {
-    match cmd {
-        Command::Simple(simple) => {
-            let mut expanded_words: Vec<String> = Vec::new();
-            for word in &simple.words {
-                let words = expand::expand_word(word, env, dispatch);
-                expanded_words.extend(words);
-            }
-
-            for assign in &simple.assignments {
-                let value = expand::expand_word_to_string(&assign.value, env, dispatch);
-                env.set(&assign.name, &value);
-            }
-
-            if expanded_words.is_empty() {
-                return (0, String::new());
-            }
-
-            let cmd_name = &expanded_words[0];
-            let cmd_args = &expanded_words[1..];
-
-            if builtins::is_builtin(cmd_name) {
-                // For builtins in capture mode, we can't easily capture their output
-                // Use a simulated approach
-                let args: Vec<String> = cmd_args.to_vec();
-                let result = builtins::run_builtin(cmd_name, &args, env);
-                return (result.exit_code, String::new());
-            }
+    functions: bool,
+) -> ExecResult {
+    let cmd_name = &words[0];
+    let cmd_args = &words[1..];
+
+    // `command name args` runs a builtin or tool even if a function has the
+    // same name; with -v or -V it is the builtin that describes commands
+    if cmd_name == "command" {
+        let args = match cmd_args.first().map(String::as_str) {
+            Some("-p" | "--") => &cmd_args[1..],
+            _ => cmd_args,
+        };
+        if args.first().is_some_and(|arg| !arg.starts_with('-')) {
+            return exec_words(args, env, dispatch, false);
+        }
+    }
 
-            // Check for shell functions
-            if let Some(func_body) = env.functions.get(cmd_name).cloned() {
-                let saved_positional = env.positional.clone();
-                env.positional = cmd_args.to_vec();
-                env.push_local_scope();
-                let (code, output) = exec_and_capture_single(&func_body, env, dispatch, pipe_stdin);
-                env.pop_local_scope();
-                env.positional = saved_positional;
-                return (code, output);
-            }
+    // Check for eval
+    if cmd_name == "eval" {
+        return exec_script(&cmd_args.join(" "), env, dispatch);
+    }
 
-            // For stdin piping
-            let mut args: Vec<String> = cmd_args.to_vec();
-            if let Some(stdin_file) = pipe_stdin {
-                if args.is_empty() || args.iter().all(|a| a.starts_with('-')) {
-                    if !matches!(cmd_name.as_str(), "echo" | "printf" | "mkdir" | "rm" | "cp" | "mv" | "ln" | "touch") {
-                        args.push(stdin_file.to_string());
+    // Check for source / .
+    if cmd_name == "source" || cmd_name == "." {
+        if let Some(file) = cmd_args.first() {
+            match fs::read_to_string(file) {
+                Ok(content) => {
+                    let mut result = exec_script(&content, env, dispatch);
+                    if let Some(ControlFlow::Return(code)) = result.control_flow {
+                        result = ExecResult::code(code);
                     }
-                }
-            }
-
-            capture_dispatch(cmd_name, &args, dispatch)
-        }
-        Command::If(ic) => {
-            let cond_result = exec_program(&ic.condition, env, dispatch);
-            if cond_result.exit_code == 0 {
-                let mut out = String::new();
-                let mut code = 0;
-                for c in &ic.then_body.commands {
-                    let (c2, o) = exec_and_capture_complete_cmd(c, env, dispatch);
-                    code = c2;
-                    out.push_str(&o);
-                }
-                (code, out)
-            } else {
-                for (elif_cond, elif_body) in &ic.elifs {
-                    let cr = exec_program(elif_cond, env, dispatch);
-                    if cr.exit_code == 0 {
-                        let mut out = String::new();
-                        let mut code = 0;
-                        for c in &elif_body.commands {
-                            let (c2, o) = exec_and_capture_complete_cmd(c, env, dispatch);
-                            code = c2;
-                            out.push_str(&o);
+                    if !result.should_exit {
+                        env.last_status = result.exit_code;
+                        if let Some(exit) = traps::run_trap("RETURN", env, dispatch) {
+                            return exit;
                         }
-                        return (code, out);
                     }
+                    return result;
                 }
-                if let Some(ref else_body) = ic.else_body {
-                    let mut out = String::new();
-                    let mut code = 0;
-                    for c in &else_body.commands {
-                        let (c2, o) = exec_and_capture_complete_cmd(c, env, dispatch);
-                        code = c2;
-                        out.push_str(&o);
-                    }
-                    (code, out)
-                } else {
-                    (0, String::new())
+                Err(e) => {
+                    env.eprint(&format!("sh: {}: {}\n", file, e));
+                    return ExecResult::code(1);
                 }
             }
         }
-        Command::BraceGroup(program) | Command::Subshell(program) => {
-            let mut out = String::new();
-            let mut code = 0;
-            for c in &program.commands {
-                let (c2, o) = exec_and_capture_complete_cmd(c, env, dispatch);
-                code = c2;
-                out.push_str(&o);
-            }
-            (code, out)
-        }
-        Command::For(fc) => {
-            let words = if let Some(ref word_list) = fc.words {
-                let mut expanded = Vec::new();
-                for w in word_list {
-                    expanded.extend(expand::expand_word(w, env, dispatch));
-                }
-                expanded
-            } else {
-                env.all_positional().to_vec()
-            };
-            let mut out = String::new();
-            let mut code = 0;
-            for val in &words {
-                env.set(&fc.var, val);
-                for c in &fc.body.commands {
-                    let (c2, o) = exec_and_capture_complete_cmd(c, env, dispatch);
-                    code = c2;
-                    out.push_str(&o);
-                }
-            }
-            (code, out)
+        return ExecResult::code(0);
+    }
+
+    // Check for builtins
+    if builtins::is_builtin(cmd_name) {
+        let args: Vec<String> = cmd_args.to_vec();
+        let builtin_result = builtins::run_builtin(cmd_name, &args, env);
+
+        if builtin_result.should_exit {
+            return ExecResult::exit(builtin_result.exit_code);
         }
-        _ => {
-            let result = exec_command(cmd, env, dispatch, pipe_stdin, None);
-            (result.exit_code, String::new())
+        if let Some(cf) = builtin_result.control_flow {
+            return ExecResult::control(cf);
         }
+        return ExecResult::code(builtin_result.exit_code);
     }
-}
-
-/// Atomic counter for unique capture file names.
-static CAPTURE_COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
 
-/// Capture the output of an external command by redirecting fd 1 to a file.
-///
-/// Uses WASI fd_renumber to swap stdout to a capture file, run the command,
-/// then restore stdout and read the captured output.
-fn capture_dispatch(cmd: &str, args: &[String], dispatch: DispatchFn) -> (i32, String) {
-    let id = CAPTURE_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-    let capture_file = format!("/work/.sh_cap_{}", id);
+    // Check for shell functions
+    if let Some(func_body) = env.functions.get(cmd_name).filter(|_| functions).cloned() {
+        // Save positional params
+        let saved_positional = env.positional.clone();
+        env.positional = cmd_args.to_vec();
+        env.push_local_scope();
 
-    #[cfg(target_os = "wasi")]
-    {
-        use std::os::wasi::io::AsRawFd;
+        // Functions only inherit the ERR and RETURN traps under `set -E`
+        // and `set -T`
+        let saved_traps = [
+            ("ERR", env.options.errtrace),
+            ("RETURN", env.options.functrace),
+        ]
+        .map(|(name, inherit)| (name, (!inherit).then(|| env.traps.remove(name)).flatten()));
 
-        #[link(wasm_import_module = "wasi_snapshot_preview1")]
-        unsafe extern "C" {
-            fn fd_renumber(from: u32, to: u32) -> u16;
-        }
+        let result = exec_command(&func_body, env, dispatch);
 
-        // Flush stdout before swapping
-        let _ = std::io::stdout().flush();
+        env.pop_local_scope();
+        env.positional = saved_positional;
 
-        // Open capture file for writing — this gives us a file fd (call it C)
-        let cap_file = match std::fs::File::create(&capture_file) {
-            Ok(f) => f,
-            Err(_) => {
-                let code = dispatch(cmd, args);
-                return (code, String::new());
-            }
+        let result = match result.control_flow {
+            _ if result.should_exit => result,
+            Some(ControlFlow::Return(code)) => return_from_function(code, env, dispatch),
+            None => return_from_function(result.exit_code, env, dispatch),
+            Some(_) => result,
         };
-        let cap_fd = cap_file.as_raw_fd() as u32;
-        std::mem::forget(cap_file); // we manage this fd manually
-
-        // Strategy: fd_renumber(from, to) requires `to` to be open.
-        // Swap stdout (1) with capture file (cap_fd):
-        //   1. fd_renumber(1, cap_fd) — moves stdout to cap_fd's slot, cap_fd is replaced
-        //      After: cap_fd = original stdout, fd 1 = freed
-        //   2. Now fd 1 is free. Opening a new file should allocate fd 1.
-        //   3. Open capture file again for writing → gets fd 1 (hopefully)
-        //   4. Run command → output goes to fd 1 (our capture file)
-        //   5. fd_renumber(cap_fd, 1) — restore: cap_fd (original stdout) → fd 1
-        //      This requires fd 1 to be in used (it is, from step 3)
-
-        unsafe {
-            // Step 1: Save stdout by moving fd 1 → cap_fd
-            let ret = fd_renumber(1, cap_fd);
-            if ret != 0 {
-                let code = dispatch(cmd, args);
-                let _ = fs::remove_file(&capture_file);
-                return (code, String::new());
+        for (name, saved) in saved_traps {
+            if let Some(action) = saved {
+                env.traps.entry(name.to_string()).or_insert(action);
             }
-            // cap_fd now holds original stdout, fd 1 is freed
-        }
-
-        // Step 2: Open capture file for writing — should get fd 1 since it's free
-        let cap_write = match std::fs::File::create(&capture_file) {
-            Ok(f) => f,
-            Err(_) => {
-                // Restore stdout
-                unsafe { let _ = fd_renumber(cap_fd, cap_fd); } // no-op but keeps it alive
-                let code = dispatch(cmd, args);
-                let _ = fs::remove_file(&capture_file);
-                return (code, String::new());
-            }
-        };
-        let new_fd = cap_write.as_raw_fd() as u32;
-        std::mem::forget(cap_write);
-
-        if new_fd != 1 {
-            // We didn't get fd 1. We need to move new_fd to fd 1.
-            // But fd 1 might still not be in used. Try opening one more file
-            // to "fill" up to fd 1, or just use renumber if fd 1 is now used.
-            // Actually, if new_fd != 1, fd 1 is still not allocated.
-            // We can't easily force fd 1. Fall back to no-capture.
-            unsafe {
-                // Try: move cap_fd (saved stdout) to new_fd (closing the capture file there)
-                let _ = fd_renumber(cap_fd, new_fd);
-                // Now new_fd = original stdout. We need it at fd 1.
-                // fd 1 is still free. We can't renumber to it.
-                // Last resort: just accept new_fd as our "stdout"
-                // This breaks things, so let's try a different approach
-            }
-            // Fallback: just dispatch without capture
-            let code = dispatch(cmd, args);
-            let _ = fs::remove_file(&capture_file);
-            return (code, String::new());
         }
+        return result;
+    }
 
-        // fd 1 now points to capture file. Run the command.
-        let code = dispatch(cmd, args);
-
-        // Flush to ensure all output is written
-        let _ = std::io::stdout().flush();
-
-        // Step 3: Restore stdout. cap_fd still holds original stdout.
-        // fd 1 is in used (capture file). fd_renumber(cap_fd, 1) works.
-        unsafe {
-            let _ = fd_renumber(cap_fd, 1);
+    // External command: tools run in-process when nothing is redirected,
+    // otherwise as a child instance wired to the current streams
+    if env.io.is_inherited() {
+        let started = audit::start(words, env);
+        let code = dispatch(cmd_name, cmd_args);
+        if let Some(started) = started {
+            started.finish(code);
         }
-
-        // Read captured output
-        let output = fs::read_to_string(&capture_file).unwrap_or_default();
-        let _ = fs::remove_file(&capture_file);
-
-        (code, output)
+        return ExecResult::code(code);
     }
 
-    #[cfg(not(target_os = "wasi"))]
-    {
-        let code = dispatch(cmd, args);
-        let _ = fs::remove_file(&capture_file);
-        (code, String::new())
-    }
+    let codes = pipeline::run_external(vec![words.to_vec()], &env.io, env);
+    ExecResult::code(codes[0])
 }
 
-/// Saved original stdin fd for restore_stdin.
-static SAVED_STDIN_FD: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(u32::MAX);
-
-/// Redirect WASI stdin (fd 0) to read from a file.
-/// Returns true if successful.
-fn redirect_stdin_to_file(path: &str) -> bool {
-    #[cfg(target_os = "wasi")]
-    {
-        use std::os::wasi::io::AsRawFd;
+/// Finish a function call that returned `code`: a RETURN trap the
+/// function set or inherited runs as it returns.
+fn return_from_function(code: i32, env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
+    env.last_status = code;
+    traps::run_trap("RETURN", env, dispatch).unwrap_or(ExecResult::code(code))
+}
 
-        #[link(wasm_import_module = "wasi_snapshot_preview1")]
-        unsafe extern "C" {
-            fn fd_renumber(from: u32, to: u32) -> u16;
-        }
+/// Parse and execute a script in the current shell (for eval and source).
+pub fn exec_script(script: &str, env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
+    use super::parser::Parser;
 
-        // Open the file for reading
-        let file = match std::fs::File::open(path) {
-            Ok(f) => f,
-            Err(_) => return false,
-        };
-        let file_fd = file.as_raw_fd() as u32;
-        std::mem::forget(file);
-
-        unsafe {
-            // fd_renumber(file_fd, 0): moves file_fd → fd 0
-            // fd 0 must be in used set (it is — it's WASI stdin)
-            // This replaces stdin with our file
-            let ret = fd_renumber(file_fd, 0);
-            if ret != 0 {
-                return false;
-            }
-            // Note: original stdin is gone. We can't restore it.
-            // Store file_fd as a marker that we redirected.
-            SAVED_STDIN_FD.store(0, std::sync::atomic::Ordering::Relaxed);
+    let mut parser = Parser::new(script);
+    match parser.parse_program() {
+        Ok(program) => exec_program(&program, env, dispatch),
+        Err(e) => {
+            env.eprint(&format!("sh: parse error: {}\n", e));
+            ExecResult::code(2)
         }
-        true
-    }
-    #[cfg(not(target_os = "wasi"))]
-    {
-        let _ = path;
-        false
-    }
-}
-
-/// Restore stdin after redirect. Since we can't save the original stdin fd
-/// (fd_renumber requires target to be open), we open /dev/null as stdin.
-fn restore_stdin() {
-    #[cfg(target_os = "wasi")]
-    {
-        // We can't truly restore the original stdin since fd_renumber replaced it.
-        // In the sandbox, stdin is empty anyway (MemoryInputPipe with no data),
-        // so losing it is acceptable. Just leave stdin pointing at EOF.
-        SAVED_STDIN_FD.store(u32::MAX, std::sync::atomic::Ordering::Relaxed);
     }
-    #[cfg(not(target_os = "wasi"))]
-    {}
 }
 
-fn write_to_file(path: &str, content: &str, append: bool) -> std::io::Result<()> {
-    if append {
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(path)?;
-        file.write_all(content.as_bytes())?;
-    } else {
-        fs::write(path, content)?;
-    }
-    Ok(())
+/// Execute a script in a subshell and capture its stdout (for command
+/// substitution).
+pub fn exec_capture(script: &str, env: &mut ShellEnv, dispatch: DispatchFn) -> (i32, String) {
+    let output = Output::buffer();
+    let mut sub_env = env.clone();
+    sub_env.io.stdout = output.clone();
+    // Like bash, command substitutions don't inherit `set -e`
+    sub_env.options.errexit = false;
+    traps::reset_for_subshell(&mut sub_env);
+
+    let result = exec_script(script, &mut sub_env, dispatch);
+    let code = traps::run_exit_trap(&mut sub_env, dispatch, result.exit_code);
+    // Background jobs write into the capture too, so wait for them
+    sub_env.jobs.wait_all();
+    env.last_status = code;
+    (
+        code,
+        String::from_utf8_lossy(&output.take()).into_owned(),
+    )
 }
 
 fn exec_if(
@@ -795,7 +688,7 @@ fn exec_if(
     env: &mut ShellEnv,
     dispatch: DispatchFn,
 ) -> ExecResult {
-    let cond_result = exec_program(&if_clause.condition, env, dispatch);
+    let cond_result = in_condition(env, |env| exec_program(&if_clause.condition, env, dispatch));
     if cond_result.should_exit {
         return cond_result;
     }
@@ -805,7 +698,7 @@ fn exec_if(
     }
 
     for (elif_cond, elif_body) in &if_clause.elifs {
-        let cr = exec_program(elif_cond, env, dispatch);
+        let cr = in_condition(env, |env| exec_program(elif_cond, env, dispatch));
         if cr.should_exit {
             return cr;
         }
@@ -835,17 +728,26 @@ fn exec_for(
     } else {
         env.all_positional().to_vec()
     };
+    if std::mem::take(&mut env.expansion_error) {
+        return ExecResult::exit(1);
+    }
 
     let mut last_code = 0;
 
     for val in &words {
-        env.set(&for_clause.var, val);
+        if let Err(e) = env.assign(&for_clause.var, val) {
+            env.eprint(&format!("sh: {}\n", e));
+            return ExecResult::code(1);
+        }
         let result = exec_program(&for_clause.body, env, dispatch);
         last_code = result.exit_code;
 
         if result.should_exit {
             return result;
         }
+        if env.io.stdout.is_broken() {
+            return ExecResult::code(EXIT_BROKEN_PIPE);
+        }
 
         match &result.control_flow {
             Some(ControlFlow::Break(n)) => {
@@ -870,6 +772,75 @@ fn exec_for(
     ExecResult::code(last_code)
 }
 
+/// Evaluate the expression of `(( ))` or `for (( ))`. On an error, gives
+/// the result to stop with: failure, or exiting after an expansion error.
+fn eval_arithmetic(
+    expr: &str,
+    env: &mut ShellEnv,
+    dispatch: DispatchFn,
+) -> Result<i64, ExecResult> {
+    let value = expand::arithmetic(expr, env, dispatch);
+    if std::mem::take(&mut env.expansion_error) {
+        return Err(ExecResult::exit(1));
+    }
+    value.map_err(|e| {
+        env.eprint(&format!("sh: ((: {}\n", e));
+        ExecResult::code(1)
+    })
+}
+
+fn exec_arith_for(clause: &ArithForClause, env: &mut ShellEnv, dispatch: DispatchFn) -> ExecResult {
+    if let Err(result) = eval_arithmetic(&clause.init, env, dispatch) {
+        return result;
+    }
+
+    let mut last_code = 0;
+
+    loop {
+        // An empty condition is always true
+        if !clause.condition.trim().is_empty() {
+            match eval_arithmetic(&clause.condition, env, dispatch) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(result) => return result,
+            }
+        }
+
+        let result = exec_program(&clause.body, env, dispatch);
+        last_code = result.exit_code;
+
+        if result.should_exit {
+            return result;
+        }
+        if env.io.stdout.is_broken() {
+            return ExecResult::code(EXIT_BROKEN_PIPE);
+        }
+
+        match &result.control_flow {
+            Some(ControlFlow::Break(n)) => {
+                if *n > 1 {
+                    return ExecResult::control(ControlFlow::Break(n - 1));
+                }
+                break;
+            }
+            Some(ControlFlow::Continue(n)) if *n > 1 => {
+                return ExecResult::control(ControlFlow::Continue(n - 1));
+            }
+            Some(ControlFlow::Return(code)) => {
+                return ExecResult::control(ControlFlow::Return(*code));
+            }
+            // The step still runs before the next iteration
+            Some(ControlFlow::Continue(_)) | None => {}
+        }
+
+        if let Err(result) = eval_arithmetic(&clause.step, env, dispatch) {
+            return result;
+        }
+    }
+
+    ExecResult::code(last_code)
+}
+
 fn exec_while(
     while_clause: &WhileClause,
     env: &mut ShellEnv,
@@ -878,7 +849,9 @@ fn exec_while(
     let mut last_code = 0;
 
     loop {
-        let cond = exec_program(&while_clause.condition, env, dispatch);
+        let cond = in_condition(env, |env| {
+            exec_program(&while_clause.condition, env, dispatch)
+        });
         if cond.should_exit {
             return cond;
         }
@@ -892,6 +865,9 @@ fn exec_while(
         if result.should_exit {
             return result;
         }
+        if env.io.stdout.is_broken() {
+            return ExecResult::code(EXIT_BROKEN_PIPE);
+        }
 
         match &result.control_flow {
             Some(ControlFlow::Break(n)) => {
@@ -924,7 +900,9 @@ fn exec_until(
     let mut last_code = 0;
 
     loop {
-        let cond = exec_program(&until_clause.condition, env, dispatch);
+        let cond = in_condition(env, |env| {
+            exec_program(&until_clause.condition, env, dispatch)
+        });
         if cond.should_exit {
             return cond;
         }
@@ -938,6 +916,9 @@ fn exec_until(
         if result.should_exit {
             return result;
         }
+        if env.io.stdout.is_broken() {
+            return ExecResult::code(EXIT_BROKEN_PIPE);
+        }
 
         match &result.control_flow {
             Some(ControlFlow::Break(n)) => {
@@ -972,6 +953,9 @@ fn exec_case(
     for arm in &case_clause.arms {
         for pattern in &arm.patterns {
             let pat_val = expand::expand_word_to_string(pattern, env, dispatch);
+            if std::mem::take(&mut env.expansion_error) {
+                return ExecResult::exit(1);
+            }
             if case_matches(&word_val, &pat_val) {
                 return exec_program(&arm.body, env, dispatch);
             }
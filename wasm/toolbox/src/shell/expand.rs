@@ -1,23 +1,257 @@
+use std::fs;
+
+use super::arith;
 use super::ast::*;
-use super::env::ShellEnv;
+use super::brace;
+use super::env::{ShellEnv, Value};
+use super::parser;
+use super::procsub;
 
 /// The dispatch function type.
 pub type DispatchFn = fn(&str, &[String]) -> i32;
 
-/// Expand a Word into one or more strings, performing variable expansion,
-/// command substitution, and glob expansion.
+/// Expand a Word into one or more strings. Expansions happen in the order
+/// bash applies them: braces, then tildes, then parameters, arithmetic and
+/// command substitutions, and finally globs.
 pub fn expand_word(word: &Word, env: &mut ShellEnv, dispatch: DispatchFn) -> Vec<String> {
-    let result = expand_word_to_string(word, env, dispatch);
+    let mut words = Vec::new();
+    for word in brace::expand(word) {
+        let word = expand_tilde(&word, false, env);
+        words.extend(expand_fields(&word, env, dispatch));
+    }
+    words
+}
+
+/// Expand a word after brace and tilde expansion: its parameters and
+/// substitutions, then the globs in each resulting word.
+fn expand_fields(word: &Word, env: &mut ShellEnv, dispatch: DispatchFn) -> Vec<String> {
+    let mut fields = Fields::new();
+    for part in &word.parts {
+        match part {
+            WordPart::DoubleQuoted(inner) => {
+                for p in inner {
+                    fields.push_part(p, true, env, dispatch);
+                }
+                // A quoted empty string still makes a word, unlike "$@"
+                // with no arguments
+                if !inner.iter().any(|p| is_list(p, true)) {
+                    fields.push_str("");
+                }
+            }
+            _ => fields.push_part(part, false, env, dispatch),
+        }
+    }
 
     // Glob expansion on the result
-    if has_glob_parts(word) {
-        let expanded = glob_expand(&result);
-        if !expanded.is_empty() {
-            return expanded;
+    let mut words = Vec::new();
+    for result in fields.finish() {
+        if has_glob_parts(word) {
+            let expanded = glob_expand(&result, env.options.globstar);
+            if !expanded.is_empty() {
+                words.extend(expanded);
+                continue;
+            }
+            // A pattern that matches nothing is an error with failglob and
+            // dropped with nullglob; otherwise it stays as it is
+            if env.options.failglob {
+                env.eprint(&format!("sh: no match: {}\n", result));
+                env.expansion_error = true;
+                continue;
+            }
+            if env.options.nullglob {
+                continue;
+            }
+        }
+        words.push(result);
+    }
+    words
+}
+
+/// The words a Word expands to. Lists such as `"$@"` and `"${a[@]}"` start
+/// a new word for each element after the first.
+struct Fields {
+    words: Vec<String>,
+    /// Whether anything but an empty list went into the words; a word made
+    /// only of empty lists expands to no words at all
+    filled: bool,
+}
+
+impl Fields {
+    fn new() -> Self {
+        Fields {
+            words: vec![String::new()],
+            filled: false,
         }
     }
 
-    vec![result]
+    fn push_str(&mut self, s: &str) {
+        if let Some(word) = self.words.last_mut() {
+            word.push_str(s);
+        }
+        self.filled = true;
+    }
+
+    fn push_part(
+        &mut self,
+        part: &WordPart,
+        quoted: bool,
+        env: &mut ShellEnv,
+        dispatch: DispatchFn,
+    ) {
+        match is_list(part, quoted).then(|| list_values(part, env, dispatch)) {
+            Some(items) => {
+                let mut items = items.into_iter();
+                if let Some(first) = items.next() {
+                    self.push_str(&first);
+                }
+                self.words.extend(items);
+            }
+            None => self.push_str(&expand_part(part, env, dispatch)),
+        }
+    }
+
+    fn finish(self) -> Vec<String> {
+        if self.filled { self.words } else { Vec::new() }
+    }
+}
+
+/// Whether a part expands to a list of words: `$@` and `${a[@]}` always,
+/// `$*` and `${a[*]}` only outside double quotes.
+fn is_list(part: &WordPart, quoted: bool) -> bool {
+    match part {
+        WordPart::SpecialVar(SpecialVar::AllArgs) => true,
+        WordPart::SpecialVar(SpecialVar::AllArgsStar) => !quoted,
+        WordPart::ArrayValues(_, all)
+        | WordPart::ArrayKeys(_, all)
+        | WordPart::VarNames(_, all) => *all || !quoted,
+        WordPart::Param(param) => match (param.name.as_str(), &param.index) {
+            (_, Some(Subscript::All)) | ("@", None) => true,
+            (_, Some(Subscript::Joined)) | ("*", None) => !quoted,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The elements of a part for which `is_list` holds.
+fn list_values(part: &WordPart, env: &mut ShellEnv, dispatch: DispatchFn) -> Vec<String> {
+    match part {
+        WordPart::ArrayValues(name, _) => env.value(name).map(Value::values).unwrap_or_default(),
+        WordPart::ArrayKeys(name, _) => env.value(name).map(Value::keys).unwrap_or_default(),
+        WordPart::VarNames(prefix, _) => env.names_with_prefix(prefix),
+        WordPart::Param(param) => expand_param(param, env, dispatch),
+        _ => env.all_positional().to_vec(),
+    }
+}
+
+/// Join a list into one word with the first character of `$IFS`, as `"$*"`
+/// does.
+fn join_list(items: &[String], env: &ShellEnv) -> String {
+    let separator = match env.get("IFS") {
+        Some(ifs) => ifs.chars().next().map(String::from).unwrap_or_default(),
+        None => " ".to_string(),
+    };
+    items.join(&separator)
+}
+
+/// Resolve an array subscript to the key of an element. Associative arrays
+/// use the expanded word as it is; indexed arrays evaluate it as
+/// arithmetic, counting negative indexes back from `end`, the index after
+/// the last element. Returns None for a negative index before the start.
+pub fn subscript(
+    index: &Word,
+    assoc: bool,
+    end: usize,
+    env: &mut ShellEnv,
+    dispatch: DispatchFn,
+) -> Option<String> {
+    let text = expand_word_to_string(index, env, dispatch);
+    if assoc {
+        return Some(text);
+    }
+    let n = eval_arith_expr(&text, env);
+    let n = if n < 0 { end as i64 + n } else { n };
+    (n >= 0).then(|| n.to_string())
+}
+
+/// The element of an array a subscript refers to, if set.
+fn element(name: &str, index: &Word, env: &mut ShellEnv, dispatch: DispatchFn) -> Option<String> {
+    let value = env.value(name)?;
+    let (assoc, end) = (matches!(value, Value::Assoc(_)), value.next_index());
+    let key = subscript(index, assoc, end, env, dispatch)?;
+    env.value(name)?.get(&key).map(str::to_string)
+}
+
+/// Expand the value of a scalar assignment into a single string, with
+/// tildes expanded at its start and after each colon, as in
+/// `PATH=~/bin:$PATH`.
+pub fn expand_assigned(word: &Word, env: &mut ShellEnv, dispatch: DispatchFn) -> String {
+    let word = expand_tilde(word, true, env);
+    expand_word_to_string(&word, env, dispatch)
+}
+
+/// Replace the tilde prefixes of a word: `~` with `$HOME`, `~+` with `$PWD`
+/// and `~-` with `$OLDPWD`. A prefix runs from an unquoted `~` at the start
+/// of the word to the first `/`, and in an assignment one also follows each
+/// unquoted `:`. Prefixes naming other users, or variables that are unset,
+/// are left as they are.
+fn expand_tilde(word: &Word, assignment: bool, env: &ShellEnv) -> Word {
+    let mut parts = Vec::new();
+    // Whether the text so far leaves the next character where a prefix
+    // can start
+    let mut at_start = true;
+    for (i, part) in word.parts.iter().enumerate() {
+        let WordPart::Literal(text) = part else {
+            parts.push(part.clone());
+            at_start = false;
+            continue;
+        };
+        let last_part = i + 1 == word.parts.len();
+        let segments: Vec<&str> = if assignment {
+            text.split(':').collect()
+        } else {
+            vec![text.as_str()]
+        };
+
+        let mut literal = String::new();
+        for (j, segment) in segments.iter().enumerate() {
+            if j > 0 {
+                literal.push(':');
+            }
+            let starts_prefix = (j > 0 || at_start) && segment.starts_with('~');
+            let name_end = segment.find('/').unwrap_or(segment.len());
+            // A prefix ending at the end of this part must end the word or
+            // the segment, or it would run on into the quoted text after it
+            let complete = name_end < segment.len() || j + 1 < segments.len() || last_part;
+            let user = if starts_prefix && complete {
+                segment.get(1..name_end)
+            } else {
+                None
+            };
+            let home = match user {
+                Some("") => env.get("HOME"),
+                Some("+") => env.get("PWD"),
+                Some("-") => env.get("OLDPWD"),
+                _ => None,
+            };
+            match home {
+                Some(home) => {
+                    if !literal.is_empty() {
+                        parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    // Quoted, so a home directory is neither split nor globbed
+                    parts.push(WordPart::SingleQuoted(home.to_string()));
+                    literal.push_str(&segment[name_end..]);
+                }
+                None => literal.push_str(segment),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(WordPart::Literal(literal));
+        }
+        at_start = assignment && text.ends_with(':');
+    }
+    Word { parts }
 }
 
 /// Expand a Word into a single string (no glob expansion).
@@ -43,40 +277,92 @@ fn expand_part(part: &WordPart, env: &mut ShellEnv, dispatch: DispatchFn) -> Str
             result
         }
 
-        WordPart::Variable(name) => env.get(name).unwrap_or("").to_string(),
+        WordPart::Variable(name) => match env.get(name) {
+            Some(value) => value.to_string(),
+            None => unset_value(name, env),
+        },
 
-        WordPart::VarDefault(name, default) => {
-            let val = env.get(name).unwrap_or("").to_string();
-            if val.is_empty() {
-                expand_word_to_string(default, env, dispatch)
-            } else {
-                val
-            }
+        WordPart::Param(param) => {
+            let values = expand_param(param, env, dispatch);
+            join_list(&values, env)
         }
 
-        WordPart::VarAssignDefault(name, default) => {
-            let val = env.get(name).unwrap_or("").to_string();
-            if val.is_empty() {
-                let expanded = expand_word_to_string(default, env, dispatch);
-                env.set(name, &expanded);
-                expanded
-            } else {
-                val
+        WordPart::Indirect(name) => {
+            let target = match env.get(name) {
+                Some(target) => target.to_string(),
+                None => return unset_value(name, env),
+            };
+            let reference = match target.split_once('[') {
+                Some((name, index)) => is_name(name) && index.ends_with(']'),
+                None => is_name(&target) || target.parse::<u32>().is_ok(),
+            };
+            if !reference {
+                env.eprint(&format!("sh: {}: invalid indirect expansion\n", target));
+                env.expansion_error = true;
+                return String::new();
             }
+            let word = parser::parse_word_from_str(&format!("${{{}}}", target));
+            expand_word_to_string(&word, env, dispatch)
+        }
+
+        WordPart::VarNames(prefix, _) => {
+            let names = env.names_with_prefix(prefix);
+            join_list(&names, env)
+        }
+
+        // `${#@}` and `${#*}` count the positional parameters, like `$#`
+        WordPart::VarLength(name) if matches!(name.as_str(), "@" | "*") => {
+            env.num_positional().to_string()
+        }
+
+        WordPart::VarLength(name) => match scalar_value(name, env) {
+            Some(value) => value.len().to_string(),
+            None => unset_value(name, env).len().to_string(),
+        },
+
+        WordPart::ArrayElement(name, index) => match element(name, index, env, dispatch) {
+            Some(value) => value,
+            None => unset_value(name, env),
+        },
+
+        WordPart::ArrayValues(name, _) => {
+            let values = env.value(name).map(Value::values).unwrap_or_default();
+            join_list(&values, env)
         }
 
-        WordPart::VarLength(name) => {
-            let val = env.get(name).unwrap_or("").to_string();
-            val.len().to_string()
+        WordPart::ArrayKeys(name, _) => {
+            let keys = env.value(name).map(Value::keys).unwrap_or_default();
+            join_list(&keys, env)
         }
 
+        WordPart::ArrayLength(name) => env
+            .value(name)
+            .map_or(0, |value| value.values().len())
+            .to_string(),
+
+        WordPart::ElementLength(name, index) => match element(name, index, env, dispatch) {
+            Some(value) => value.chars().count().to_string(),
+            None => unset_value(name, env).len().to_string(),
+        },
+
+        WordPart::Assignment(assignment) => assignment.name.clone(),
+
         WordPart::CommandSub(cmd) => {
             let (_, output) = exec_capture_for_expand(cmd, env, dispatch);
             // Trim trailing newlines (shell behavior)
             output.trim_end_matches('\n').to_string()
         }
 
-        WordPart::ArithmeticSub(expr) => eval_arithmetic(expr, env),
+        WordPart::ProcessSub(cmd, output) => procsub::substitute(cmd, *output, env, dispatch),
+
+        WordPart::ArithmeticSub(expr) => match arithmetic(expr, env, dispatch) {
+            Ok(n) => n.to_string(),
+            Err(e) => {
+                env.eprint(&format!("sh: {}\n", e));
+                env.expansion_error = true;
+                String::new()
+            }
+        },
 
         WordPart::Glob(pattern) => {
             // Return the pattern as-is; glob expansion happens at the word level
@@ -86,24 +372,373 @@ fn expand_part(part: &WordPart, env: &mut ShellEnv, dispatch: DispatchFn) -> Str
         WordPart::SpecialVar(var) => match var {
             SpecialVar::ExitStatus => env.last_status.to_string(),
             SpecialVar::NumArgs => env.num_positional().to_string(),
-            SpecialVar::AllArgs | SpecialVar::AllArgsStar => {
-                env.all_positional().join(" ")
-            }
+            SpecialVar::AllArgs | SpecialVar::AllArgsStar => join_list(env.all_positional(), env),
             SpecialVar::ProcessId => "1".to_string(), // Fake PID in WASM
+            SpecialVar::OptionFlags => env.options.flags(),
+            SpecialVar::LastBackgroundPid => env
+                .last_background_pid
+                .map(|pid| pid.to_string())
+                .unwrap_or_default(),
             SpecialVar::Positional(n) => {
                 if *n == 0 {
                     "sh".to_string()
                 } else {
-                    env.all_positional()
-                        .get(*n as usize - 1)
-                        .cloned()
-                        .unwrap_or_default()
+                    match env.all_positional().get(*n as usize - 1) {
+                        Some(value) => value.clone(),
+                        None => unset_value(&format!("${}", n), env),
+                    }
                 }
             }
         },
     }
 }
 
+/// Expand `${...}` with an operator into the values it yields: one for a
+/// scalar, or one for each element of `$@` or an array.
+fn expand_param(param: &ParamExpansion, env: &mut ShellEnv, dispatch: DispatchFn) -> Vec<String> {
+    let values = param_values(param, env, dispatch);
+    let unset = values.is_none();
+    let empty = values
+        .as_ref()
+        .is_some_and(|values| values.iter().all(String::is_empty));
+    let null = |colon: bool| unset || (colon && empty);
+
+    match &param.op {
+        ParamOp::Default(word, colon) if null(*colon) => {
+            return vec![expand_word_to_string(word, env, dispatch)];
+        }
+        ParamOp::AssignDefault(word, colon) if null(*colon) => {
+            let value = expand_word_to_string(word, env, dispatch);
+            assign_param(param, &value, env, dispatch);
+            return vec![value];
+        }
+        ParamOp::Alternative(word, colon) => {
+            return match null(*colon) {
+                true => Vec::new(),
+                false => vec![expand_word_to_string(word, env, dispatch)],
+            };
+        }
+        ParamOp::Error(word, colon) if null(*colon) => {
+            let message = match expand_word_to_string(word, env, dispatch) {
+                message if message.is_empty() => "parameter null or not set".to_string(),
+                message => message,
+            };
+            env.eprint(&format!("sh: {}: {}\n", param.name, message));
+            env.expansion_error = true;
+            return Vec::new();
+        }
+        _ => {}
+    }
+
+    let list = matches!(param.index, Some(Subscript::All | Subscript::Joined))
+        || (param.index.is_none() && matches!(param.name.as_str(), "@" | "*"));
+    let mut values = match values {
+        Some(values) => values,
+        None => {
+            unset_value(&param.name, env);
+            if list {
+                Vec::new()
+            } else {
+                vec![String::new()]
+            }
+        }
+    };
+
+    match &param.op {
+        ParamOp::RemovePrefix(pattern, longest) => {
+            let pattern = expand_pattern(pattern, env, dispatch);
+            values
+                .iter()
+                .map(|value| remove_prefix(value, &pattern, *longest))
+                .collect()
+        }
+        ParamOp::RemoveSuffix(pattern, longest) => {
+            let pattern = expand_pattern(pattern, env, dispatch);
+            values
+                .iter()
+                .map(|value| remove_suffix(value, &pattern, *longest))
+                .collect()
+        }
+        ParamOp::Replace(pattern, replacement, mode) => {
+            let pattern = expand_pattern(pattern, env, dispatch);
+            let replacement = expand_word_to_string(replacement, env, dispatch);
+            values
+                .iter()
+                .map(|value| replace(value, &pattern, &replacement, *mode))
+                .collect()
+        }
+        ParamOp::Substring(offset, length) => {
+            let offset = eval_arith_expr(&expand_word_to_string(offset, env, dispatch), env);
+            let length = length
+                .as_ref()
+                .map(|length| eval_arith_expr(&expand_word_to_string(length, env, dispatch), env));
+            if list {
+                // Offsets into $@ count $0 as 0
+                if param.index.is_none() {
+                    values.insert(0, "sh".to_string());
+                }
+                slice(&values, offset, length)
+            } else {
+                values
+                    .iter()
+                    .map(|value| {
+                        let chars: Vec<char> = value.chars().collect();
+                        slice(&chars, offset, length).into_iter().collect()
+                    })
+                    .collect()
+            }
+        }
+        ParamOp::Upper(all) => values
+            .iter()
+            .map(|value| change_case(value, *all, true))
+            .collect(),
+        ParamOp::Lower(all) => values
+            .iter()
+            .map(|value| change_case(value, *all, false))
+            .collect(),
+        _ => values,
+    }
+}
+
+/// The values a `${...}` expansion starts from, or None if the parameter
+/// is unset. `$@` with no arguments and empty arrays count as unset.
+fn param_values(
+    param: &ParamExpansion,
+    env: &mut ShellEnv,
+    dispatch: DispatchFn,
+) -> Option<Vec<String>> {
+    let name = param.name.as_str();
+    let values = match &param.index {
+        Some(Subscript::All | Subscript::Joined) => env.value(name)?.values(),
+        Some(Subscript::Element(index)) => vec![element(name, index, env, dispatch)?],
+        None => match name {
+            "@" | "*" => env.all_positional().to_vec(),
+            _ => vec![scalar_value(name, env)?],
+        },
+    };
+    (!values.is_empty()).then_some(values)
+}
+
+/// The value of a parameter other than `$@` and `$*`: a special parameter,
+/// a positional parameter or a variable. None if it is unset.
+fn scalar_value(name: &str, env: &ShellEnv) -> Option<String> {
+    Some(match name {
+        "?" => env.last_status.to_string(),
+        "#" => env.num_positional().to_string(),
+        "$" => "1".to_string(),
+        "!" => env.last_background_pid?.to_string(),
+        "-" => env.options.flags(),
+        _ => env.get(name)?.to_string(),
+    })
+}
+
+/// Assign the value of `${VAR:=word}`, which only variables and array
+/// elements can take.
+fn assign_param(param: &ParamExpansion, value: &str, env: &mut ShellEnv, dispatch: DispatchFn) {
+    let assigned = match &param.index {
+        None if is_name(&param.name) => env.assign(&param.name, value),
+        Some(Subscript::Element(index)) => {
+            let (assoc, end) = match env.value(&param.name) {
+                Some(current) => (matches!(current, Value::Assoc(_)), current.next_index()),
+                None => (false, 0),
+            };
+            match subscript(index, assoc, end, env, dispatch) {
+                Some(key) => env.assign_element(&param.name, &key, value),
+                None => Ok(()),
+            }
+        }
+        _ => Err(format!("${}: cannot assign in this way", param.name)),
+    };
+    if let Err(e) = assigned {
+        env.eprint(&format!("sh: {}\n", e));
+        env.expansion_error = true;
+    }
+}
+
+/// Whether `s` is a valid variable name.
+pub fn is_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Expand a word used as a pattern. Quoted parts match literally, while
+/// glob characters and unquoted expansions keep their meaning.
+pub fn expand_pattern(word: &Word, env: &mut ShellEnv, dispatch: DispatchFn) -> String {
+    expand_escaping_quoted(word, env, dispatch, escape_glob)
+}
+
+/// Expand the regular expression on the right of `=~` in `[[ ]]`, where
+/// quoted parts also match literally.
+pub fn expand_regex(word: &Word, env: &mut ShellEnv, dispatch: DispatchFn) -> String {
+    expand_escaping_quoted(word, env, dispatch, regex::escape)
+}
+
+fn expand_escaping_quoted(
+    word: &Word,
+    env: &mut ShellEnv,
+    dispatch: DispatchFn,
+    escape: fn(&str) -> String,
+) -> String {
+    let mut pattern = String::new();
+    for part in &word.parts {
+        match part {
+            WordPart::Glob(glob) => pattern.push_str(glob),
+            WordPart::Literal(s) | WordPart::SingleQuoted(s) => pattern.push_str(&escape(s)),
+            WordPart::DoubleQuoted(_) => {
+                pattern.push_str(&escape(&expand_part(part, env, dispatch)))
+            }
+            _ => pattern.push_str(&expand_part(part, env, dispatch)),
+        }
+    }
+    pattern
+}
+
+/// Escape the characters a glob pattern gives a meaning to.
+fn escape_glob(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The byte offsets of every character boundary in `s`, including its end.
+fn boundaries(s: &str) -> Vec<usize> {
+    s.char_indices().map(|(i, _)| i).chain([s.len()]).collect()
+}
+
+/// Remove the shortest or longest prefix of `value` matching `pattern`.
+fn remove_prefix(value: &str, pattern: &str, longest: bool) -> String {
+    let ends = boundaries(value);
+    let matches = |end: &&usize| glob_match(pattern, &value[..**end]);
+    let found = if longest {
+        ends.iter().rev().find(matches)
+    } else {
+        ends.iter().find(matches)
+    };
+    match found {
+        Some(&end) => value[end..].to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Remove the shortest or longest suffix of `value` matching `pattern`.
+fn remove_suffix(value: &str, pattern: &str, longest: bool) -> String {
+    let starts = boundaries(value);
+    let matches = |start: &&usize| glob_match(pattern, &value[**start..]);
+    let found = if longest {
+        starts.iter().find(matches)
+    } else {
+        starts.iter().rev().find(matches)
+    };
+    match found {
+        Some(&start) => value[..start].to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Replace the longest matches of `pattern` in `value`.
+fn replace(value: &str, pattern: &str, replacement: &str, mode: ReplaceMode) -> String {
+    if pattern.is_empty() {
+        return value.to_string();
+    }
+    let bounds = boundaries(value);
+    match mode {
+        ReplaceMode::Prefix => match bounds
+            .iter()
+            .rev()
+            .find(|&&end| glob_match(pattern, &value[..end]))
+        {
+            Some(&end) => format!("{}{}", replacement, &value[end..]),
+            None => value.to_string(),
+        },
+        ReplaceMode::Suffix => match bounds
+            .iter()
+            .find(|&&start| glob_match(pattern, &value[start..]))
+        {
+            Some(&start) => format!("{}{}", &value[..start], replacement),
+            None => value.to_string(),
+        },
+        ReplaceMode::First | ReplaceMode::All => {
+            let mut result = String::new();
+            let mut i = 0;
+            while i + 1 < bounds.len() {
+                let start = bounds[i];
+                let longest = bounds[i + 1..]
+                    .iter()
+                    .rev()
+                    .find(|&&end| glob_match(pattern, &value[start..end]));
+                match longest {
+                    Some(&end) => {
+                        result.push_str(replacement);
+                        if mode == ReplaceMode::First {
+                            result.push_str(&value[end..]);
+                            return result;
+                        }
+                        i = bounds.partition_point(|&b| b < end);
+                    }
+                    None => {
+                        result.push_str(&value[start..bounds[i + 1]]);
+                        i += 1;
+                    }
+                }
+            }
+            result
+        }
+    }
+}
+
+/// Take `length` items from `offset`, as `${VAR:offset:length}` does. A
+/// negative offset counts back from the end, and a negative length gives
+/// where to stop counting back from the end.
+fn slice<T: Clone>(items: &[T], offset: i64, length: Option<i64>) -> Vec<T> {
+    let len = items.len() as i64;
+    let start = if offset < 0 { len + offset } else { offset };
+    if start < 0 || start > len {
+        return Vec::new();
+    }
+    let end = match length {
+        Some(length) if length < 0 => len + length,
+        Some(length) => (start + length).min(len),
+        None => len,
+    };
+    if end < start {
+        return Vec::new();
+    }
+    items[start as usize..end as usize].to_vec()
+}
+
+/// Upper- or lowercase the first character of `value`, or all of them.
+fn change_case(value: &str, all: bool, upper: bool) -> String {
+    let convert = |s: &str| {
+        if upper {
+            s.to_uppercase()
+        } else {
+            s.to_lowercase()
+        }
+    };
+    if all {
+        return convert(value);
+    }
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => convert(&first.to_string()) + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// The value of an unset variable: empty, or an error under `set -u`.
+fn unset_value(name: &str, env: &mut ShellEnv) -> String {
+    if env.options.nounset {
+        env.eprint(&format!("sh: {}: unbound variable\n", name));
+        env.expansion_error = true;
+    }
+    String::new()
+}
+
 /// Execute a script and capture its output (for command substitution).
 /// This calls into the exec module.
 fn exec_capture_for_expand(script: &str, env: &mut ShellEnv, dispatch: DispatchFn) -> (i32, String) {
@@ -114,45 +749,94 @@ fn has_glob_parts(word: &Word) -> bool {
     word.parts.iter().any(|p| matches!(p, WordPart::Glob(_)))
 }
 
-/// Expand glob patterns against the filesystem.
-fn glob_expand(pattern: &str) -> Vec<String> {
-    use std::fs;
-
-    if !pattern.contains('*') && !pattern.contains('?') && !pattern.contains('[') {
+/// Expand a glob pattern against the filesystem, one path component at a
+/// time. With `globstar`, a `**` component matches any number of
+/// directories, or as the last component every file and directory below.
+/// Returns the matching paths sorted, or none.
+fn glob_expand(pattern: &str, globstar: bool) -> Vec<String> {
+    if !pattern.contains(['*', '?', '[', '(']) {
         return Vec::new();
     }
 
-    let (dir, file_pattern) = if let Some(slash_pos) = pattern.rfind('/') {
-        (&pattern[..slash_pos], &pattern[slash_pos + 1..])
-    } else {
-        (".", pattern)
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
     };
+    let components: Vec<&str> = rest.split('/').collect();
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+        let mut next = Vec::new();
+        for path in &paths {
+            if globstar && *component == "**" {
+                if !last {
+                    next.push(path.clone());
+                }
+                descendants(path, last, &mut next);
+            } else if component.contains(['*', '?', '[', '(']) {
+                next.extend(matching_entries(path, component, last));
+            } else {
+                next.push(join_path(path, component));
+            }
+        }
+        paths = next;
+    }
 
-    let entries = match fs::read_dir(dir) {
-        Ok(entries) => entries,
-        Err(_) => return Vec::new(),
-    };
+    // Components without glob characters were taken on trust
+    paths.retain(|path| fs::symlink_metadata(path).is_ok());
+    paths.sort();
+    paths.dedup();
+    paths
+}
 
-    let mut matches: Vec<String> = entries
-        .filter_map(|e| e.ok())
-        .map(|e| e.file_name().to_string_lossy().to_string())
+/// The entries of directory `dir` whose names match `pattern`, keeping only
+/// directories unless this is the last component. Names starting with `.`
+/// only match a pattern that starts with one.
+fn matching_entries(dir: &str, pattern: &str, last: bool) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| last || fs::metadata(entry.path()).is_ok_and(|meta| meta.is_dir()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
         .filter(|name| {
-            if name.starts_with('.') && !file_pattern.starts_with('.') {
-                return false;
-            }
-            glob_match(file_pattern, name)
+            (!name.starts_with('.') || pattern.starts_with('.')) && glob_match(pattern, name)
         })
-        .map(|name| {
-            if dir == "." {
-                name
-            } else {
-                format!("{}/{}", dir, name)
-            }
-        })
-        .collect();
+        .map(|name| join_path(dir, &name))
+        .collect()
+}
 
-    matches.sort();
-    matches
+/// Collect what `**` matches below `dir`: every directory, or with `files`
+/// every entry. Hidden entries and symbolic links to directories are not
+/// followed.
+fn descendants(dir: &str, files: bool, found: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = join_path(dir, &name);
+        let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+        if is_dir || files {
+            found.push(path.clone());
+        }
+        if is_dir {
+            descendants(&path, files, found);
+        }
+    }
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else if dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
 }
 
 /// Match a string against a glob pattern.
@@ -164,6 +848,9 @@ pub fn glob_match(pattern: &str, s: &str) -> bool {
 
 fn glob_match_impl(pattern: &[char], string: &[char], mut pi: usize, mut si: usize) -> bool {
     while pi < pattern.len() {
+        if let Some((kind, alternatives, end)) = extglob_group(pattern, pi) {
+            return extglob_match(kind, &alternatives, pattern, end, string, si);
+        }
         if si >= string.len() {
             while pi < pattern.len() && pattern[pi] == '*' {
                 pi += 1;
@@ -185,34 +872,28 @@ fn glob_match_impl(pattern: &[char], string: &[char], mut pi: usize, mut si: usi
                 pi += 1;
                 si += 1;
             }
-            '[' => {
-                pi += 1;
-                let negate = pi < pattern.len() && pattern[pi] == '!';
-                if negate {
-                    pi += 1;
-                }
-                let mut matched = false;
-                while pi < pattern.len() && pattern[pi] != ']' {
-                    if pi + 2 < pattern.len() && pattern[pi + 1] == '-' {
-                        let lo = pattern[pi];
-                        let hi = pattern[pi + 2];
-                        if string[si] >= lo && string[si] <= hi {
-                            matched = true;
-                        }
-                        pi += 3;
-                    } else {
-                        if pattern[pi] == string[si] {
-                            matched = true;
-                        }
-                        pi += 1;
+            '[' => match bracket_match(pattern, pi + 1, string[si]) {
+                Some((matched, end)) => {
+                    if !matched {
+                        return false;
                     }
+                    pi = end;
+                    si += 1;
                 }
-                if pi < pattern.len() {
+                // Without a closing bracket, `[` matches itself
+                None => {
+                    if string[si] != '[' {
+                        return false;
+                    }
                     pi += 1;
+                    si += 1;
                 }
-                if matched == negate {
+            },
+            '\\' if pi + 1 < pattern.len() => {
+                if pattern[pi + 1] != string[si] {
                     return false;
                 }
+                pi += 2;
                 si += 1;
             }
             c => {
@@ -228,156 +909,162 @@ fn glob_match_impl(pattern: &[char], string: &[char], mut pi: usize, mut si: usi
     si >= string.len()
 }
 
-/// Simple arithmetic evaluation for $(( expr )).
-fn eval_arithmetic(expr: &str, env: &ShellEnv) -> String {
-    eval_arith_expr(expr.trim(), env).to_string()
-}
-
-fn eval_arith_expr(expr: &str, env: &ShellEnv) -> i64 {
-    let expr = expr.trim();
-    if expr.is_empty() {
-        return 0;
+/// Match `c` against the bracket expression whose list starts at `start`,
+/// just after the `[`. Returns whether it matches and where the pattern
+/// carries on, or `None` if the expression is never closed. The list may
+/// be negated with `!` or `^` and hold ranges such as `a-z` and classes such
+/// as `[:alpha:]`; a `]` first in the list stands for itself.
+fn bracket_match(pattern: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = start;
+    let negate = matches!(pattern.get(i), Some('!' | '^'));
+    if negate {
+        i += 1;
     }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let p = *pattern.get(i)?;
+        if p == ']' && !first {
+            return Some((matched != negate, i + 1));
+        }
+        first = false;
 
-    if expr.starts_with('(') {
-        if let Some(closing) = find_matching_paren(expr) {
-            if closing == expr.len() - 1 {
-                return eval_arith_expr(&expr[1..closing], env);
+        if p == '[' && pattern.get(i + 1) == Some(&':') {
+            let name_end = (i + 2..pattern.len().saturating_sub(1))
+                .find(|&j| pattern[j] == ':' && pattern[j + 1] == ']');
+            if let Some(name_end) = name_end {
+                let name: String = pattern[i + 2..name_end].iter().collect();
+                matched |= class_match(&name, c);
+                i = name_end + 2;
+                continue;
             }
         }
-    }
 
-    if let Some(pos) = find_op(expr, "||") {
-        let l = eval_arith_expr(&expr[..pos], env);
-        let r = eval_arith_expr(&expr[pos + 2..], env);
-        return if l != 0 || r != 0 { 1 } else { 0 };
-    }
-    if let Some(pos) = find_op(expr, "&&") {
-        let l = eval_arith_expr(&expr[..pos], env);
-        let r = eval_arith_expr(&expr[pos + 2..], env);
-        return if l != 0 && r != 0 { 1 } else { 0 };
-    }
-    if let Some(pos) = find_op(expr, "==") {
-        let l = eval_arith_expr(&expr[..pos], env);
-        let r = eval_arith_expr(&expr[pos + 2..], env);
-        return if l == r { 1 } else { 0 };
-    }
-    if let Some(pos) = find_op(expr, "!=") {
-        let l = eval_arith_expr(&expr[..pos], env);
-        let r = eval_arith_expr(&expr[pos + 2..], env);
-        return if l != r { 1 } else { 0 };
-    }
-    if let Some(pos) = find_op(expr, "<=") {
-        let l = eval_arith_expr(&expr[..pos], env);
-        let r = eval_arith_expr(&expr[pos + 2..], env);
-        return if l <= r { 1 } else { 0 };
-    }
-    if let Some(pos) = find_op(expr, ">=") {
-        let l = eval_arith_expr(&expr[..pos], env);
-        let r = eval_arith_expr(&expr[pos + 2..], env);
-        return if l >= r { 1 } else { 0 };
-    }
-    if let Some(pos) = rfind_op_additive(expr) {
-        let l = eval_arith_expr(&expr[..pos], env);
-        let r = eval_arith_expr(&expr[pos + 1..], env);
-        return if expr.as_bytes()[pos] == b'+' { l + r } else { l - r };
-    }
-    if let Some(pos) = rfind_op_multiplicative(expr) {
-        let l = eval_arith_expr(&expr[..pos], env);
-        let r = eval_arith_expr(&expr[pos + 1..], env);
-        return match expr.as_bytes()[pos] {
-            b'*' => l * r,
-            b'/' => if r == 0 { 0 } else { l / r },
-            b'%' => if r == 0 { 0 } else { l % r },
-            _ => 0,
+        let lo = match p {
+            '\\' if i + 1 < pattern.len() => {
+                i += 1;
+                pattern[i]
+            }
+            _ => p,
         };
-    }
-
-    if expr.starts_with('!') {
-        let val = eval_arith_expr(&expr[1..], env);
-        return if val == 0 { 1 } else { 0 };
-    }
-    if expr.starts_with('-') && expr.len() > 1 && expr.as_bytes()[1].is_ascii_digit() {
-        if let Ok(n) = expr.parse::<i64>() {
-            return n;
+        match (pattern.get(i + 1), pattern.get(i + 2)) {
+            (Some('-'), Some(&hi)) if hi != ']' => {
+                matched |= lo <= c && c <= hi;
+                i += 3;
+            }
+            _ => {
+                matched |= lo == c;
+                i += 1;
+            }
         }
     }
+}
 
-    if let Ok(n) = expr.parse::<i64>() {
-        return n;
+/// Whether `c` belongs to the character class `name`, as in `[:alpha:]`.
+fn class_match(name: &str, c: char) -> bool {
+    match name {
+        "alnum" => c.is_alphanumeric(),
+        "alpha" => c.is_alphabetic(),
+        "blank" => c == ' ' || c == '\t',
+        "cntrl" => c.is_control(),
+        "digit" => c.is_ascii_digit(),
+        "graph" => c.is_ascii_graphic(),
+        "lower" => c.is_lowercase(),
+        "print" => c.is_ascii_graphic() || c == ' ',
+        "punct" => c.is_ascii_punctuation(),
+        "space" => c.is_whitespace(),
+        "upper" => c.is_uppercase(),
+        "word" => c.is_alphanumeric() || c == '_',
+        "xdigit" => c.is_ascii_hexdigit(),
+        _ => false,
     }
-
-    let var_name = expr.trim_start_matches('$');
-    if let Some(val) = env.get(var_name) {
-        if let Ok(n) = val.parse::<i64>() {
-            return n;
-        }
-    }
-
-    0
 }
 
-fn find_matching_paren(expr: &str) -> Option<usize> {
+/// The extended glob group starting at `pi`, as in `@(a|b)`: its kind, its
+/// alternatives, and where the pattern carries on after it.
+fn extglob_group(pattern: &[char], pi: usize) -> Option<(char, Vec<&[char]>, usize)> {
+    let kind = pattern[pi];
+    if !matches!(kind, '?' | '*' | '+' | '@' | '!') || pattern.get(pi + 1) != Some(&'(') {
+        return None;
+    }
+    let mut alternatives = Vec::new();
     let mut depth = 0;
-    for (i, ch) in expr.chars().enumerate() {
-        match ch {
+    let mut start = pi + 2;
+    let mut i = pi + 1;
+    while i < pattern.len() {
+        match pattern[i] {
             '(' => depth += 1,
-            ')' => {
-                depth -= 1;
-                if depth == 0 {
-                    return Some(i);
-                }
+            ')' if depth == 1 => {
+                alternatives.push(&pattern[start..i]);
+                return Some((kind, alternatives, i + 1));
+            }
+            ')' => depth -= 1,
+            '|' if depth == 1 => {
+                alternatives.push(&pattern[start..i]);
+                start = i + 1;
             }
+            '\\' => i += 1,
             _ => {}
         }
+        i += 1;
     }
+    // Without a closing parenthesis the characters match literally
     None
 }
 
-fn find_op(expr: &str, op: &str) -> Option<usize> {
-    let bytes = expr.as_bytes();
-    let op_bytes = op.as_bytes();
-    let mut depth = 0i32;
-    let mut i = 0;
-    while i < bytes.len() {
-        if bytes[i] == b'(' { depth += 1; }
-        if bytes[i] == b')' { depth -= 1; }
-        if depth == 0 && i + op_bytes.len() <= bytes.len() && &bytes[i..i + op_bytes.len()] == op_bytes {
-            return Some(i);
+/// Match `string[si..]` against an extended glob group followed by the
+/// rest of the pattern from `rest`.
+fn extglob_match(
+    kind: char,
+    alternatives: &[&[char]],
+    pattern: &[char],
+    rest: usize,
+    string: &[char],
+    si: usize,
+) -> bool {
+    let any_matches = |end: usize| {
+        alternatives
+            .iter()
+            .any(|alternative| glob_match_impl(alternative, &string[si..end], 0, 0))
+    };
+    let ends = si..=string.len();
+    match kind {
+        // Anything that none of the alternatives match
+        '!' => ends
+            .into_iter()
+            .any(|end| !any_matches(end) && glob_match_impl(pattern, string, rest, end)),
+        // One of the alternatives, or for ? none
+        '@' | '?' => {
+            (kind == '?' && glob_match_impl(pattern, string, rest, si))
+                || ends
+                    .into_iter()
+                    .any(|end| any_matches(end) && glob_match_impl(pattern, string, rest, end))
+        }
+        // Any number of alternatives one after another, at least one for +
+        _ => {
+            (kind == '*' && glob_match_impl(pattern, string, rest, si))
+                || (si + 1..=string.len()).any(|end| {
+                    any_matches(end)
+                        && (glob_match_impl(pattern, string, rest, end)
+                            || extglob_match('*', alternatives, pattern, rest, string, end))
+                })
         }
-        i += 1;
     }
-    None
 }
 
-fn rfind_op_additive(expr: &str) -> Option<usize> {
-    let bytes = expr.as_bytes();
-    let mut depth = 0i32;
-    let mut result = None;
-    for (i, &b) in bytes.iter().enumerate() {
-        if b == b'(' { depth += 1; }
-        if b == b')' { depth -= 1; }
-        if depth == 0 && (b == b'+' || b == b'-') && i > 0 {
-            let prev = bytes[i - 1];
-            if prev == b'*' || prev == b'/' || prev == b'%' || prev == b'(' {
-                continue;
-            }
-            result = Some(i);
-        }
-    }
-    result
+/// Expand the parameters and command substitutions in an arithmetic
+/// expression, then evaluate it.
+pub fn arithmetic(expr: &str, env: &mut ShellEnv, dispatch: DispatchFn) -> Result<i64, String> {
+    let text = expand_word_to_string(&parser::parse_word_from_str(expr), env, dispatch);
+    arith::eval(&text, env)
 }
 
-fn rfind_op_multiplicative(expr: &str) -> Option<usize> {
-    let bytes = expr.as_bytes();
-    let mut depth = 0i32;
-    let mut result = None;
-    for (i, &b) in bytes.iter().enumerate() {
-        if b == b'(' { depth += 1; }
-        if b == b')' { depth -= 1; }
-        if depth == 0 && (b == b'*' || b == b'/' || b == b'%') && i > 0 {
-            result = Some(i);
-        }
-    }
-    result
+/// Evaluate an expanded arithmetic expression, reporting an error as an
+/// expansion error.
+fn eval_arith_expr(expr: &str, env: &mut ShellEnv) -> i64 {
+    arith::eval(expr, env).unwrap_or_else(|e| {
+        env.eprint(&format!("sh: {}\n", e));
+        env.expansion_error = true;
+        0
+    })
 }
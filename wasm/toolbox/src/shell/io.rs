@@ -0,0 +1,191 @@
+//! The shell's standard streams.
+//!
+//! Commands read and write through a `Stdio` instead of file descriptors, so
+//! redirections, pipes between in-shell stages and command substitution all
+//! stay in memory and never touch `/work`.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufRead, Cursor, Read, Write};
+use std::mem::ManuallyDrop;
+use std::os::fd::FromRawFd;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Most data an in-memory pipe or command substitution holds. A writer that
+/// goes past it gets a broken pipe, as if its reader had exited.
+pub const BUFFER_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// Set once a write to the process's own stdout fails.
+static STDOUT_BROKEN: AtomicBool = AtomicBool::new(false);
+
+/// Where a command reads its input from.
+#[derive(Debug, Clone)]
+pub enum Input {
+    /// The process's own stdin.
+    Inherit,
+    /// In-memory data. Readers share the position, so each one carries on
+    /// where the previous one stopped.
+    Data(Rc<RefCell<Cursor<Vec<u8>>>>),
+}
+
+impl Input {
+    pub fn data(bytes: Vec<u8>) -> Self {
+        Input::Data(Rc::new(RefCell::new(Cursor::new(bytes))))
+    }
+
+    /// Read one line, including its newline. Returns `None` at end of input.
+    pub fn read_line(&self) -> Option<Vec<u8>> {
+        self.read_until(b'\n')
+    }
+
+    /// Read up to and including the next `delimiter`. Returns `None` at end
+    /// of input.
+    pub fn read_until(&self, delimiter: u8) -> Option<Vec<u8>> {
+        let mut line = Vec::new();
+        match self {
+            Input::Inherit => {
+                // Read a byte at a time straight from fd 0 so nothing past
+                // the delimiter is consumed; later commands share this stdin.
+                let mut stdin = ManuallyDrop::new(unsafe { File::from_raw_fd(0) });
+                let mut byte = [0u8; 1];
+                while let Ok(1) = stdin.read(&mut byte) {
+                    line.push(byte[0]);
+                    if byte[0] == delimiter {
+                        break;
+                    }
+                }
+            }
+            Input::Data(cursor) => {
+                let _ = cursor.borrow_mut().read_until(delimiter, &mut line);
+            }
+        }
+        if line.is_empty() { None } else { Some(line) }
+    }
+
+    /// The in-memory data not yet read, for handing to a child instance.
+    /// Returns `None` for the process's own stdin, which children share.
+    pub fn remaining(&self) -> Option<Vec<u8>> {
+        match self {
+            Input::Inherit => None,
+            Input::Data(cursor) => {
+                let cursor = cursor.borrow();
+                let start = (cursor.position() as usize).min(cursor.get_ref().len());
+                Some(cursor.get_ref()[start..].to_vec())
+            }
+        }
+    }
+
+    /// Mark `n` bytes of in-memory data as read by a child instance.
+    pub fn consume(&self, n: usize) {
+        if let Input::Data(cursor) = self {
+            let mut cursor = cursor.borrow_mut();
+            let position = cursor.position() + n as u64;
+            cursor.set_position(position);
+        }
+    }
+}
+
+/// Where a command writes its output to.
+#[derive(Debug, Clone)]
+pub enum Output {
+    /// The process's own stdout.
+    Stdout,
+    /// The process's own stderr.
+    Stderr,
+    /// Discarded (`/dev/null`).
+    Null,
+    /// An in-memory pipe or capture.
+    Buffer(Rc<RefCell<Buffer>>),
+    /// A redirection target.
+    File(Rc<RefCell<File>>),
+}
+
+/// In-memory output, bounded by [`BUFFER_CAPACITY`].
+#[derive(Debug, Default)]
+pub struct Buffer {
+    data: Vec<u8>,
+    broken: bool,
+}
+
+impl Output {
+    pub fn buffer() -> Self {
+        Output::Buffer(Rc::new(RefCell::new(Buffer::default())))
+    }
+
+    pub fn file(file: File) -> Self {
+        Output::File(Rc::new(RefCell::new(file)))
+    }
+
+    pub fn write(&self, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            Output::Stdout => io::stdout().write_all(bytes).inspect_err(|_| {
+                STDOUT_BROKEN.store(true, Ordering::Relaxed);
+            }),
+            Output::Stderr => io::stderr().write_all(bytes),
+            Output::Null => Ok(()),
+            Output::Buffer(buffer) => {
+                let mut buffer = buffer.borrow_mut();
+                let room = if buffer.broken {
+                    0
+                } else {
+                    BUFFER_CAPACITY.saturating_sub(buffer.data.len())
+                };
+                if bytes.len() > room {
+                    buffer.data.extend_from_slice(&bytes[..room]);
+                    buffer.broken = true;
+                    return Err(io::ErrorKind::BrokenPipe.into());
+                }
+                buffer.data.extend_from_slice(bytes);
+                Ok(())
+            }
+            Output::File(file) => file.borrow_mut().write_all(bytes),
+        }
+    }
+
+    /// Whether a write has failed because nobody can read any more output.
+    /// Commands writing here should stop.
+    pub fn is_broken(&self) -> bool {
+        match self {
+            Output::Stdout => STDOUT_BROKEN.load(Ordering::Relaxed),
+            Output::Buffer(buffer) => buffer.borrow().broken,
+            _ => false,
+        }
+    }
+
+    /// Take everything written to an in-memory buffer so far.
+    pub fn take(&self) -> Vec<u8> {
+        match self {
+            Output::Buffer(buffer) => std::mem::take(&mut buffer.borrow_mut().data),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The standard streams a command runs with.
+#[derive(Debug, Clone)]
+pub struct Stdio {
+    pub stdin: Input,
+    pub stdout: Output,
+    pub stderr: Output,
+}
+
+impl Stdio {
+    /// The process's own streams.
+    pub fn inherit() -> Self {
+        Stdio {
+            stdin: Input::Inherit,
+            stdout: Output::Stdout,
+            stderr: Output::Stderr,
+        }
+    }
+
+    /// Whether all three streams are the process's own, so a tool can run
+    /// in-process without redirecting anything.
+    pub fn is_inherited(&self) -> bool {
+        matches!(
+            (&self.stdin, &self.stdout, &self.stderr),
+            (Input::Inherit, Output::Stdout, Output::Stderr)
+        )
+    }
+}
@@ -0,0 +1,586 @@
+//! Background jobs started with `&`.
+//!
+//! A job runs as child instances alongside the shell until `wait` collects
+//! it: a pipeline of tools as one instance per tool, and anything that needs
+//! the shell itself, such as a function, builtin or loop, as a subshell in
+//! an instance of its own.
+
+use std::time::Duration;
+
+use super::ast::{
+    Command, CompleteCommand, ListOp, Pipeline, Program, Redirect, RedirectKind, SimpleCommand,
+    WordPart,
+};
+use super::builtins::BuiltinResult;
+use super::env::ShellEnv;
+use super::exec::{self, DispatchFn, ExecResult};
+use super::io::{Input, Stdio};
+use super::pipeline::{self, Running, Stage};
+use super::subshell;
+
+/// Process ID of the first background job; the shell itself is `$$` = 1.
+const FIRST_PID: u32 = 1000;
+
+/// Signal `kill` sends when none is given.
+const SIGTERM: i32 = 15;
+
+/// Signals known by name: (name, number, description).
+pub const SIGNALS: &[(&str, i32, &str)] = &[
+    ("HUP", 1, "Hangup"),
+    ("INT", 2, "Interrupt"),
+    ("QUIT", 3, "Quit"),
+    ("ABRT", 6, "Aborted"),
+    ("KILL", 9, "Killed"),
+    ("USR1", 10, "User defined signal 1"),
+    ("USR2", 12, "User defined signal 2"),
+    ("PIPE", 13, "Broken pipe"),
+    ("ALRM", 14, "Alarm clock"),
+    ("TERM", 15, "Terminated"),
+];
+
+/// The shell's background jobs.
+///
+/// A subshell has no jobs of its own, so a clone starts out empty.
+#[derive(Debug)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+    next_pid: u32,
+}
+
+/// A background job.
+#[derive(Debug)]
+struct Job {
+    /// Job number, for `%n`.
+    id: usize,
+    pid: u32,
+    /// The command as `jobs` shows it.
+    command: String,
+    /// Whether the pipeline's status is inverted with `!`.
+    negated: bool,
+    state: State,
+}
+
+#[derive(Debug)]
+enum State {
+    /// Tools running as child instances.
+    Running(Running),
+    /// Finished, with its exit status.
+    Done(i32),
+}
+
+impl Default for JobTable {
+    fn default() -> Self {
+        JobTable {
+            jobs: Vec::new(),
+            next_pid: FIRST_PID,
+        }
+    }
+}
+
+impl Clone for JobTable {
+    fn clone(&self) -> Self {
+        JobTable {
+            jobs: Vec::new(),
+            next_pid: self.next_pid,
+        }
+    }
+}
+
+impl JobTable {
+    /// Record a new job, returning its process ID.
+    fn add(&mut self, command: String, negated: bool, state: State) -> u32 {
+        let id = self.jobs.last().map_or(1, |job| job.id + 1);
+        let pid = self.next_pid;
+        self.next_pid += 1;
+        self.jobs.push(Job {
+            id,
+            pid,
+            command,
+            negated,
+            state,
+        });
+        pid
+    }
+
+    /// Find a job by job spec (`%n`, `%%`, `%+`, `%-`) or process ID.
+    fn find(&self, spec: &str) -> Option<usize> {
+        match spec {
+            "%" | "%%" | "%+" => self.jobs.len().checked_sub(1),
+            "%-" => self
+                .jobs
+                .len()
+                .checked_sub(2)
+                .or(self.jobs.len().checked_sub(1)),
+            _ => match spec.strip_prefix('%') {
+                Some(n) => {
+                    let id: usize = n.parse().ok()?;
+                    self.jobs.iter().position(|job| job.id == id)
+                }
+                None => {
+                    let pid: u32 = spec.parse().ok()?;
+                    self.jobs.iter().position(|job| job.pid == pid)
+                }
+            },
+        }
+    }
+
+    /// Wait for a job and forget it, returning its exit status.
+    fn wait(&mut self, index: usize) -> i32 {
+        self.jobs.remove(index).finish()
+    }
+
+    /// Wait for every job, so none outlives the shell.
+    pub fn wait_all(&mut self) {
+        for job in std::mem::take(&mut self.jobs) {
+            job.finish();
+        }
+    }
+
+    /// Wait for whichever job finishes first, returning its exit status, or
+    /// `None` if there are no jobs.
+    fn wait_any(&mut self) -> Option<i32> {
+        if self.jobs.is_empty() {
+            return None;
+        }
+        loop {
+            if let Some(index) = self.jobs.iter().position(Job::is_finished) {
+                return Some(self.wait(index));
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Record the status of jobs whose tools have all exited.
+    fn refresh(&mut self) {
+        for job in &mut self.jobs {
+            if matches!(&job.state, State::Running(running) if running.is_finished()) {
+                let state = std::mem::replace(&mut job.state, State::Done(0));
+                if let State::Running(running) = state {
+                    job.state = State::Done(job_status(job.negated, &running.finish()));
+                }
+            }
+        }
+    }
+}
+
+impl Job {
+    fn is_finished(&self) -> bool {
+        match &self.state {
+            State::Running(running) => running.is_finished(),
+            State::Done(_) => true,
+        }
+    }
+
+    /// Wait for the job's tools to exit, returning its exit status.
+    fn finish(self) -> i32 {
+        match self.state {
+            State::Running(running) => job_status(self.negated, &running.finish()),
+            State::Done(code) => code,
+        }
+    }
+
+    /// The state column of `jobs`.
+    fn state_text(&self) -> String {
+        match self.state {
+            State::Running(_) => "Running".to_string(),
+            State::Done(0) => "Done".to_string(),
+            State::Done(code) => match SIGNALS.iter().find(|(_, n, _)| code == 128 + n) {
+                Some((_, _, description)) => description.to_string(),
+                None => format!("Exit {}", code),
+            },
+        }
+    }
+}
+
+/// A job's exit status, given the exit code of every stage.
+fn job_status(negated: bool, codes: &[i32]) -> i32 {
+    let code = codes.last().copied().unwrap_or(0);
+    match (negated, code) {
+        (false, code) => code,
+        (true, 0) => 1,
+        (true, _) => 0,
+    }
+}
+
+/// Run `cmd` in the background, as for `cmd &`, and record it as a job.
+pub fn run_background(
+    cmd: &CompleteCommand,
+    env: &mut ShellEnv,
+    dispatch: DispatchFn,
+) -> ExecResult {
+    // Without job control, background commands read from an empty stdin
+    let io = Stdio {
+        stdin: Input::data(Vec::new()),
+        ..env.io.clone()
+    };
+
+    let (negated, state) = match tool_stages(cmd, env) {
+        Some((stages, negated)) => (negated, start_tools(&stages, io, env, dispatch)),
+        None => (false, start_subshell(cmd, io, env)),
+    };
+
+    let pid = env.jobs.add(describe(cmd), negated, state);
+    env.last_background_pid = Some(pid);
+    ExecResult::code(0)
+}
+
+/// The stages of `cmd` if it is a pipeline made only of tools, which can
+/// run as child instances alongside the shell, and whether it is negated.
+///
+/// Only a command name written out literally counts, so nothing is
+/// expanded twice when `cmd` has to run in a subshell instead.
+fn tool_stages<'a>(
+    cmd: &'a CompleteCommand,
+    env: &ShellEnv,
+) -> Option<(Vec<&'a SimpleCommand>, bool)> {
+    if !cmd.rest.is_empty() {
+        return None;
+    }
+    let mut stages = Vec::new();
+    for command in &cmd.first.commands {
+        let Command::Simple(simple) = command else {
+            return None;
+        };
+        let name = pipeline::literal_word(simple.words.first()?)?;
+        if !simple.assignments.is_empty() || !pipeline::is_external(&[name], env) {
+            return None;
+        }
+        stages.push(simple);
+    }
+    // Redirections are only supported on a lone command
+    if stages.len() > 1 && stages.iter().any(|simple| !simple.redirections.is_empty()) {
+        return None;
+    }
+    Some((stages, cmd.first.negated))
+}
+
+/// Start tools as child instances, returning the job's state.
+fn start_tools(
+    stages: &[&SimpleCommand],
+    io: Stdio,
+    env: &mut ShellEnv,
+    dispatch: DispatchFn,
+) -> State {
+    let argvs: Vec<Vec<String>> = stages
+        .iter()
+        .map(|simple| exec::expand_words(&simple.words, env, dispatch))
+        .collect();
+    for argv in &argvs {
+        exec::trace(argv, env);
+    }
+
+    let io = match stages {
+        [simple] => match exec::redirected_io(&simple.redirections, &io, env, dispatch) {
+            Ok(io) => io,
+            Err(e) => {
+                env.eprint(&format!("sh: {}\n", e));
+                return State::Done(1);
+            }
+        },
+        _ => io,
+    };
+    if std::mem::take(&mut env.expansion_error) {
+        return State::Done(1);
+    }
+
+    let stages = argvs.into_iter().map(Stage::Tool).collect();
+    State::Running(pipeline::start_external(stages, &io, env))
+}
+
+/// Start `cmd` in a subshell run as a child instance, returning the job's
+/// state.
+fn start_subshell(cmd: &CompleteCommand, io: Stdio, env: &mut ShellEnv) -> State {
+    let body = Command::BraceGroup(Program {
+        commands: vec![CompleteCommand {
+            background: false,
+            ..cmd.clone()
+        }],
+    });
+    let state = match subshell::state(&body, env) {
+        Ok(state) => state,
+        Err(e) => {
+            env.eprint(&format!("sh: {}\n", e));
+            return State::Done(1);
+        }
+    };
+    State::Running(pipeline::start_external(vec![Stage::Shell(state)], &io, env))
+}
+
+/// A rough rendering of a command for `jobs`.
+fn describe(cmd: &CompleteCommand) -> String {
+    let mut text = describe_pipeline(&cmd.first);
+    for (op, pipeline) in &cmd.rest {
+        text.push_str(match op {
+            ListOp::And => " && ",
+            ListOp::Or => " || ",
+        });
+        text.push_str(&describe_pipeline(pipeline));
+    }
+    text
+}
+
+fn describe_pipeline(pipeline: &Pipeline) -> String {
+    let stages: Vec<String> = pipeline.commands.iter().map(describe_command).collect();
+    let text = stages.join(" | ");
+    if pipeline.negated {
+        format!("! {}", text)
+    } else {
+        text
+    }
+}
+
+fn describe_command(cmd: &Command) -> String {
+    match cmd {
+        Command::Simple(simple) => {
+            let words = simple.words.iter().map(|word| describe_parts(&word.parts));
+            let redirections = simple.redirections.iter().map(describe_redirect);
+            words.chain(redirections).collect::<Vec<_>>().join(" ")
+        }
+        Command::If(_) => "if ...".to_string(),
+        Command::For(_) => "for ...".to_string(),
+        Command::While(_) => "while ...".to_string(),
+        Command::Until(_) => "until ...".to_string(),
+        Command::Case(_) => "case ...".to_string(),
+        Command::Arithmetic(expr) => format!("(({}))", expr),
+        Command::ArithFor(_) => "for ((...))".to_string(),
+        Command::Cond(_) => "[[ ... ]]".to_string(),
+        Command::Subshell(_) => "( ... )".to_string(),
+        Command::BraceGroup(_) => "{ ... }".to_string(),
+        Command::FuncDef(func_def) => format!("{} () {{ ... }}", func_def.name),
+        Command::Redirected(cmd, redirections) => {
+            let redirections = redirections.iter().map(describe_redirect);
+            std::iter::once(describe_command(cmd))
+                .chain(redirections)
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+    }
+}
+
+fn describe_redirect(redirect: &Redirect) -> String {
+    let op = match &redirect.kind {
+        RedirectKind::Output => "> ",
+        RedirectKind::Append => ">> ",
+        RedirectKind::OutputAll => "&> ",
+        RedirectKind::AppendAll => "&>> ",
+        RedirectKind::Input => "< ",
+        RedirectKind::HereDoc => return "<< ...".to_string(),
+        RedirectKind::HereString => "<<< ",
+        RedirectKind::DupOutput => ">&",
+        RedirectKind::DupInput => "<&",
+    };
+    let fd = redirect.fd.map(|fd| fd.to_string()).unwrap_or_default();
+    format!("{}{}{}", fd, op, describe_parts(&redirect.target.parts))
+}
+
+fn describe_parts(parts: &[WordPart]) -> String {
+    parts
+        .iter()
+        .map(|part| match part {
+            WordPart::Literal(s) => s.clone(),
+            WordPart::SingleQuoted(s) => format!("'{}'", s),
+            WordPart::DoubleQuoted(inner) => format!("\"{}\"", describe_parts(inner)),
+            WordPart::Variable(name) => format!("${}", name),
+            WordPart::CommandSub(script) => format!("$({})", script),
+            WordPart::ProcessSub(script, output) => {
+                format!("{}({})", if *output { '>' } else { '<' }, script)
+            }
+            _ => "...".to_string(),
+        })
+        .collect()
+}
+
+/// Look up a signal by number or name, with or without the `SIG` prefix.
+pub fn parse_signal(spec: &str) -> Option<i32> {
+    if let Ok(n) = spec.parse::<i32>() {
+        return (n >= 0).then_some(n);
+    }
+    let upper = spec.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(known, _, _)| *known == name)
+        .map(|(_, n, _)| *n)
+}
+
+pub fn builtin_wait(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    match args.first().map(String::as_str) {
+        None => {
+            env.jobs.wait_all();
+            BuiltinResult::code(0)
+        }
+        Some("-n") => BuiltinResult::code(env.jobs.wait_any().unwrap_or(127)),
+        Some(_) => {
+            let mut status = 0;
+            for spec in args {
+                status = match env.jobs.find(spec) {
+                    Some(index) => env.jobs.wait(index),
+                    None => {
+                        if spec.starts_with('%') {
+                            env.eprint(&format!("wait: {}: no such job\n", spec));
+                        } else {
+                            env.eprint(&format!(
+                                "wait: pid {} is not a child of this shell\n",
+                                spec
+                            ));
+                        }
+                        127
+                    }
+                };
+            }
+            BuiltinResult::code(status)
+        }
+    }
+}
+
+pub fn builtin_jobs(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let mut long = false;
+    let mut pids_only = false;
+    let mut running_only = false;
+    for arg in args {
+        match arg.as_str() {
+            "-l" => long = true,
+            "-p" => pids_only = true,
+            "-r" => running_only = true,
+            "-s" => return BuiltinResult::code(0), // Nothing is ever stopped
+            _ => {
+                env.eprint(&format!("jobs: {}: invalid option\n", arg));
+                return BuiltinResult::code(2);
+            }
+        }
+    }
+
+    env.jobs.refresh();
+    let count = env.jobs.jobs.len();
+    let mut out = String::new();
+    for (i, job) in env.jobs.jobs.iter().enumerate() {
+        let running = matches!(job.state, State::Running(_));
+        if running_only && !running {
+            continue;
+        }
+        if pids_only {
+            out.push_str(&format!("{}\n", job.pid));
+            continue;
+        }
+        let mark = if i + 1 == count {
+            '+'
+        } else if i + 2 == count {
+            '-'
+        } else {
+            ' '
+        };
+        let pid = if long {
+            format!(" {}", job.pid)
+        } else {
+            " ".to_string()
+        };
+        let suffix = if running { " &" } else { "" };
+        out.push_str(&format!(
+            "[{}]{}{} {:<24}{}{}\n",
+            job.id,
+            mark,
+            pid,
+            job.state_text(),
+            job.command,
+            suffix
+        ));
+    }
+    env.print(&out);
+    BuiltinResult::code(0)
+}
+
+pub fn builtin_kill(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let usage = "kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]\n";
+    let mut signal = SIGTERM;
+    let mut targets = args;
+
+    match args.first().map(String::as_str) {
+        Some("-l") | Some("-L") => return list_signals(&args[1..], env),
+        Some("-s") | Some("-n") => {
+            let Some(spec) = args.get(1) else {
+                env.eprint(usage);
+                return BuiltinResult::code(2);
+            };
+            match parse_signal(spec) {
+                Some(n) => signal = n,
+                None => {
+                    env.eprint(&format!("kill: {}: invalid signal specification\n", spec));
+                    return BuiltinResult::code(1);
+                }
+            }
+            targets = &args[2..];
+        }
+        Some("--") => targets = &args[1..],
+        Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+            match parse_signal(&flag[1..]) {
+                Some(n) => signal = n,
+                None => {
+                    env.eprint(&format!(
+                        "kill: {}: invalid signal specification\n",
+                        &flag[1..]
+                    ));
+                    return BuiltinResult::code(1);
+                }
+            }
+            targets = &args[1..];
+        }
+        _ => {}
+    }
+
+    if targets.is_empty() {
+        env.eprint(usage);
+        return BuiltinResult::code(2);
+    }
+
+    let mut status = 0;
+    for target in targets {
+        match env.jobs.find(target) {
+            Some(index) => {
+                // Signal 0 only checks that the job exists
+                if signal != 0
+                    && let State::Running(running) = &env.jobs.jobs[index].state
+                {
+                    running.kill(signal);
+                }
+            }
+            None => {
+                if target.starts_with('%') {
+                    env.eprint(&format!("kill: {}: no such job\n", target));
+                } else {
+                    env.eprint(&format!("kill: ({}) - No such process\n", target));
+                }
+                status = 1;
+            }
+        }
+    }
+    BuiltinResult::code(status)
+}
+
+/// `kill -l`: list signal names, or name the signals given by number (or
+/// by the exit status of a command they stopped).
+pub fn list_signals(args: &[String], env: &ShellEnv) -> BuiltinResult {
+    if args.is_empty() {
+        let names: Vec<&str> = SIGNALS.iter().map(|(name, _, _)| *name).collect();
+        env.print(&format!("{}\n", names.join(" ")));
+        return BuiltinResult::code(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        let number = arg
+            .parse::<i32>()
+            .ok()
+            .map(|n| if n > 128 { n - 128 } else { n });
+        match number.and_then(|n| SIGNALS.iter().find(|(_, known, _)| *known == n)) {
+            Some((name, _, _)) => env.print(&format!("{}\n", name)),
+            None => match parse_signal(arg) {
+                Some(n) => env.print(&format!("{}\n", n)),
+                None => {
+                    env.eprint(&format!("kill: {}: invalid signal specification\n", arg));
+                    status = 1;
+                }
+            },
+        }
+    }
+    BuiltinResult::code(status)
+}
@@ -1,15 +1,20 @@
+use std::collections::VecDeque;
+
 use super::token::{keyword_token, Token};
 
 /// Lexer for shell scripts.
+#[derive(Clone)]
 pub struct Lexer {
     chars: Vec<char>,
     pos: usize,
     /// Whether to recognize keywords in the next word position
     keyword_ok: bool,
-    /// Pending here-doc delimiters to read after newline
-    heredoc_pending: Vec<(String, bool)>, // (delimiter, strip_tabs)
-    /// Stored here-doc bodies read after newline
-    pub heredoc_bodies: Vec<String>,
+    /// Where the bodies of the here-docs on the current line end, which
+    /// the next newline skips to
+    heredoc_end: Option<usize>,
+    /// Here-doc bodies read for `<<` operators the parser has yet to take,
+    /// and whether their delimiter was quoted
+    pub heredoc_bodies: VecDeque<(String, bool)>,
 }
 
 impl Lexer {
@@ -18,15 +23,11 @@ impl Lexer {
             chars: input.chars().collect(),
             pos: 0,
             keyword_ok: true,
-            heredoc_pending: Vec::new(),
-            heredoc_bodies: Vec::new(),
+            heredoc_end: None,
+            heredoc_bodies: VecDeque::new(),
         }
     }
 
-    pub fn pos(&self) -> usize {
-        self.pos
-    }
-
     fn peek(&self) -> Option<char> {
         self.chars.get(self.pos).copied()
     }
@@ -63,37 +64,44 @@ impl Lexer {
         }
     }
 
-    /// Read here-doc bodies after a newline when here-docs are pending.
-    fn read_pending_heredocs(&mut self) {
-        let pending = std::mem::take(&mut self.heredoc_pending);
-        for (delim, strip_tabs) in pending {
-            let mut body = String::new();
-            loop {
-                // Read a line
-                let mut line = String::new();
-                loop {
-                    match self.advance() {
-                        Some('\n') => break,
-                        Some(ch) => line.push(ch),
-                        None => break,
-                    }
-                }
-
-                let trimmed = if strip_tabs {
-                    line.trim_start_matches('\t').to_string()
-                } else {
-                    line.clone()
-                };
-
-                if trimmed == delim {
-                    break;
-                }
+    /// Read the body of a here-doc for the `<<` operator just read. It
+    /// starts on the next line, or after the body of an earlier here-doc on
+    /// the same line, and the newline ending this line skips past it.
+    fn read_heredoc(&mut self, strip_tabs: bool) {
+        self.skip_whitespace_no_newline();
+        let (delim, quoted) = self.read_heredoc_delimiter();
+        let line_end = |from: usize| {
+            self.chars[from..]
+                .iter()
+                .position(|&ch| ch == '\n')
+                .map_or(self.chars.len(), |i| from + i)
+        };
 
-                body.push_str(&line);
-                body.push('\n');
+        let mut pos = match self.heredoc_end {
+            Some(end) => end,
+            None => (line_end(self.pos) + 1).min(self.chars.len()),
+        };
+        let mut body = String::new();
+        while pos < self.chars.len() {
+            let end = line_end(pos);
+            let line: String = self.chars[pos..end].iter().collect();
+            pos = (end + 1).min(self.chars.len());
+
+            // `<<-` strips leading tabs from the body and the delimiter
+            let line = if strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                &line
+            };
+            if line == delim {
+                break;
             }
-            self.heredoc_bodies.push(body);
+            body.push_str(line);
+            body.push('\n');
         }
+
+        self.heredoc_end = Some(pos);
+        self.heredoc_bodies.push_back((body, quoted));
     }
 
     /// Get the next token.
@@ -109,8 +117,8 @@ impl Lexer {
         match ch {
             '\n' => {
                 self.advance();
-                if !self.heredoc_pending.is_empty() {
-                    self.read_pending_heredocs();
+                if let Some(end) = self.heredoc_end.take() {
+                    self.pos = self.pos.max(end);
                 }
                 self.keyword_ok = true;
                 Token::Newline
@@ -130,23 +138,45 @@ impl Lexer {
 
             '&' => {
                 self.advance();
-                if self.peek() == Some('&') {
-                    self.advance();
-                    self.keyword_ok = true;
-                    Token::And
-                } else {
-                    self.keyword_ok = true;
-                    Token::Amp
+                match self.peek() {
+                    Some('&') => {
+                        self.advance();
+                        self.keyword_ok = true;
+                        Token::And
+                    }
+                    Some('>') => {
+                        self.advance();
+                        self.keyword_ok = false;
+                        if self.peek() == Some('>') {
+                            self.advance();
+                            Token::AndDGreat
+                        } else {
+                            Token::AndGreat
+                        }
+                    }
+                    _ => {
+                        self.keyword_ok = true;
+                        Token::Amp
+                    }
                 }
             }
 
             ';' => {
                 self.advance();
                 self.keyword_ok = true;
-                Token::Semi
+                if self.peek() == Some(';') {
+                    self.advance();
+                    Token::DSemi
+                } else {
+                    Token::Semi
+                }
             }
 
             '(' => {
+                if let Some(expr) = self.read_arithmetic() {
+                    self.keyword_ok = true;
+                    return Token::DParen(expr);
+                }
                 self.advance();
                 self.keyword_ok = true;
                 Token::LParen
@@ -169,16 +199,11 @@ impl Lexer {
                             Token::TLess
                         } else if self.peek() == Some('-') {
                             self.advance();
-                            // Read here-doc delimiter
-                            self.skip_whitespace_no_newline();
-                            let delim = self.read_heredoc_delimiter();
-                            self.heredoc_pending.push((delim, true));
+                            self.read_heredoc(true);
                             self.keyword_ok = false;
                             Token::DLessDash
                         } else {
-                            self.skip_whitespace_no_newline();
-                            let delim = self.read_heredoc_delimiter();
-                            self.heredoc_pending.push((delim, false));
+                            self.read_heredoc(false);
                             self.keyword_ok = false;
                             Token::DLess
                         }
@@ -188,6 +213,10 @@ impl Lexer {
                         self.keyword_ok = false;
                         Token::LessAnd
                     }
+                    Some('(') => {
+                        self.keyword_ok = false;
+                        Token::Word(self.read_process_substitution('<'))
+                    }
                     _ => {
                         self.keyword_ok = false;
                         Token::Less
@@ -208,6 +237,10 @@ impl Lexer {
                         self.keyword_ok = false;
                         Token::GreatAnd
                     }
+                    Some('(') => {
+                        self.keyword_ok = false;
+                        Token::Word(self.read_process_substitution('>'))
+                    }
                     _ => {
                         self.keyword_ok = false;
                         Token::Great
@@ -245,6 +278,55 @@ impl Lexer {
         }
     }
 
+    /// Read the regular expression after `=~` in `[[ ]]` as one word, up to
+    /// whitespace outside quotes and parentheses, so that `(`, `|` and the
+    /// like keep their meaning in the regex.
+    pub fn read_regex(&mut self) -> String {
+        self.skip_whitespace_no_newline();
+        let mut regex = String::new();
+        let mut depth = 0;
+        let mut quote = None;
+        while let Some(ch) = self.peek() {
+            if ch.is_whitespace() && quote.is_none() && depth == 0 {
+                break;
+            }
+            self.advance();
+            regex.push(ch);
+            match (ch, quote) {
+                ('\\', q) if q != Some('\'') => regex.extend(self.advance()),
+                ('\'' | '"', None) => quote = Some(ch),
+                (_, Some(q)) if ch == q => quote = None,
+                ('(', None) => depth += 1,
+                (')', None) if depth > 0 => depth -= 1,
+                _ => {}
+            }
+        }
+        regex
+    }
+
+    /// Read `(( expr ))` if that is what comes next, rather than nested
+    /// subshells such as `((cd dir) && ls)`.
+    fn read_arithmetic(&mut self) -> Option<String> {
+        if self.chars.get(self.pos + 1) != Some(&'(') {
+            return None;
+        }
+        let start = self.pos + 2;
+        let mut depth = 0;
+        for p in start..self.chars.len() {
+            match self.chars[p] {
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                ')' if self.chars.get(p + 1) == Some(&')') => {
+                    self.pos = p + 2;
+                    return Some(self.chars[start..p].iter().collect());
+                }
+                ')' => return None,
+                _ => {}
+            }
+        }
+        None
+    }
+
     fn is_io_number(&self) -> bool {
         let start = self.pos;
         let mut p = start;
@@ -272,40 +354,45 @@ impl Lexer {
         num
     }
 
-    fn read_heredoc_delimiter(&mut self) -> String {
+    /// Read a here-doc delimiter, returning it without quotes and whether
+    /// any of it was quoted, which keeps the body from being expanded.
+    fn read_heredoc_delimiter(&mut self) -> (String, bool) {
         let mut delim = String::new();
         let mut quoted = false;
 
-        if self.peek() == Some('\'') {
-            quoted = true;
-            self.advance();
-            while let Some(ch) = self.advance() {
-                if ch == '\'' {
-                    break;
+        while let Some(ch) = self.peek() {
+            match ch {
+                '\'' | '"' => {
+                    quoted = true;
+                    self.advance();
+                    while let Some(c) = self.advance() {
+                        if c == ch {
+                            break;
+                        }
+                        delim.push(c);
+                    }
                 }
-                delim.push(ch);
-            }
-        } else if self.peek() == Some('"') {
-            quoted = true;
-            self.advance();
-            while let Some(ch) = self.advance() {
-                if ch == '"' {
-                    break;
+                '\\' => {
+                    quoted = true;
+                    self.advance();
+                    delim.extend(self.advance());
                 }
-                delim.push(ch);
-            }
-        } else {
-            while let Some(ch) = self.peek() {
-                if ch.is_whitespace() || ch == '\n' {
-                    break;
+                ' ' | '\t' | '\n' | '|' | '&' | ';' | '<' | '>' | '(' | ')' => break,
+                _ => {
+                    delim.push(ch);
+                    self.advance();
                 }
-                delim.push(ch);
-                self.advance();
             }
         }
 
-        let _ = quoted; // quoted here-docs suppress expansion (handled in expand phase)
-        delim
+        (delim, quoted)
+    }
+
+    /// Read `<(cmd)` or `>(cmd)`, the `(` being next, as one word.
+    fn read_process_substitution(&mut self, direction: char) -> String {
+        let mut word = direction.to_string();
+        self.read_dollar_into(&mut word);
+        word
     }
 
     fn read_word(&mut self) -> String {
@@ -315,6 +402,26 @@ impl Lexer {
             match self.peek() {
                 None => break,
                 Some(ch) => match ch {
+                    // Array literal: a=(x y z) or a+=(w)
+                    '(' if starts_array_literal(&word) => {
+                        self.advance();
+                        word.push('(');
+                        while let Some(element) = self.read_array_element() {
+                            word.push_str(&element);
+                            word.push(' ');
+                        }
+                        if word.ends_with(' ') {
+                            word.pop();
+                        }
+                        word.push(')');
+                    }
+
+                    // Extended glob patterns: ?(...), *(...), +(...), @(...)
+                    // and !(...)
+                    '(' if word.ends_with(['?', '*', '+', '@', '!']) => {
+                        self.read_extglob_into(&mut word);
+                    }
+
                     // Word terminators
                     ' ' | '\t' | '\n' | '|' | '&' | ';' | '(' | ')' => break,
                     '<' | '>' if word.is_empty() || !word.ends_with('$') => break,
@@ -403,6 +510,64 @@ impl Lexer {
         word
     }
 
+    /// Read the parenthesised list of an extended glob pattern, from the
+    /// opening parenthesis to the one that closes it.
+    fn read_extglob_into(&mut self, word: &mut String) {
+        let mut depth = 0;
+        while let Some(c) = self.advance() {
+            word.push(c);
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                '\\' => {
+                    if let Some(c) = self.advance() {
+                        word.push(c);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Read the next element of an array literal, skipping blank lines and
+    /// comments. Returns None at the closing parenthesis.
+    fn read_array_element(&mut self) -> Option<String> {
+        loop {
+            self.skip_whitespace_no_newline();
+            match self.peek()? {
+                '\n' => {
+                    self.advance();
+                }
+                '#' => self.skip_comment(),
+                ')' => {
+                    self.advance();
+                    return None;
+                }
+                ch => {
+                    let element = self.read_word();
+                    if element.is_empty() {
+                        // A stray operator character; keep it as a word
+                        self.advance();
+                        return Some(ch.to_string());
+                    }
+                    return Some(element);
+                }
+            }
+        }
+    }
+
+    /// Split the inside of an array literal, as kept in the word by the
+    /// lexer, into its elements.
+    pub fn array_elements(input: &str) -> Vec<String> {
+        let mut lexer = Lexer::new(input);
+        std::iter::from_fn(|| lexer.read_array_element()).collect()
+    }
+
     fn read_dollar_into(&mut self, word: &mut String) {
         match self.peek() {
             Some('(') => {
@@ -547,11 +712,6 @@ impl Lexer {
         }
     }
 
-    /// Get a slice of the internal chars buffer.
-    pub fn chars_slice(&self) -> &[char] {
-        &self.chars
-    }
-
     /// Peek at the next token without consuming it.
     pub fn peek_token(&mut self) -> Token {
         let saved_pos = self.pos;
@@ -562,3 +722,14 @@ impl Lexer {
         tok
     }
 }
+
+/// Whether a word read so far is `name=` or `name+=`, which an array literal
+/// can follow.
+fn starts_array_literal(word: &str) -> bool {
+    let Some(name) = word.strip_suffix('=') else {
+        return false;
+    };
+    let name = name.strip_suffix('+').unwrap_or(name);
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
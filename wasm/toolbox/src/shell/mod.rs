@@ -1,65 +1,103 @@
+pub mod alias;
+pub mod arith;
+pub mod audit;
 pub mod ast;
+pub mod brace;
 pub mod builtins;
+pub mod cond;
+pub mod dirs;
 pub mod env;
 pub mod exec;
 pub mod expand;
+pub mod io;
+pub mod jobs;
 pub mod lexer;
 pub mod parser;
 pub mod pipeline;
+pub mod procsub;
 pub mod redirect;
+pub mod subshell;
+pub mod syntax;
 pub mod token;
+pub mod traps;
 
 use std::fs;
 
-use self::env::ShellEnv;
+use self::env::{ShellEnv, ShellOptions};
 use self::exec::exec_program;
 use self::parser::Parser;
 
 /// Entry point for `sh` command.
 /// Supports `sh -c "script"`, `sh script.sh [args...]`, and `sh` (interactive-ish, reads stdin).
 pub fn run(args: &[String], dispatch: fn(&str, &[String]) -> i32) -> i32 {
+    // `Sandbox::parse_shell` asks for the syntax tree instead of a run
+    if args.first().is_some_and(|arg| arg == "--sandbox-ast") {
+        return syntax::report_ast(args.get(1).map_or("", String::as_str));
+    }
+
     let mut script = None;
     let mut script_args = Vec::new();
+    let mut options = ShellOptions::default();
 
     let mut i = 0;
     while i < args.len() {
-        match args[i].as_str() {
-            "-c" => {
-                i += 1;
-                if i < args.len() {
-                    script = Some(args[i].clone());
-                    // Remaining args become $0, $1, ...
-                    i += 1;
-                    while i < args.len() {
-                        script_args.push(args[i].clone());
-                        i += 1;
-                    }
-                } else {
-                    eprintln!("sh: -c: option requires an argument");
-                    return 2;
+        let arg = args[i].as_str();
+
+        // Options such as `-e` or `-o pipefail`, possibly bundled with `-c`
+        if arg.len() > 1 && (arg.starts_with('-') || arg.starts_with('+')) && arg != "--" {
+            let (sign, letters) = arg.split_at(1);
+            let mut command = false;
+            let mut flags = sign.to_string();
+            for letter in letters.chars() {
+                match letter {
+                    'c' if sign == "-" => command = true,
+                    _ => flags.push(letter),
                 }
             }
-            _ => {
-                // First non-flag arg is script file
-                let file = &args[i];
-                match fs::read_to_string(file) {
-                    Ok(content) => {
-                        script = Some(content);
-                        // Remaining args become positional params
-                        i += 1;
-                        while i < args.len() {
-                            script_args.push(args[i].clone());
-                            i += 1;
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("sh: {}: {}", file, e);
-                        return 1;
-                    }
-                }
+            // Every `o` takes the option name that follows
+            let names = flags.matches('o').count();
+            let option_args: Vec<String> = std::iter::once(flags)
+                .chain(args[i + 1..].iter().take(names).cloned())
+                .collect();
+            if option_args[0].len() > 1
+                && let Err(e) = builtins::apply_options(&option_args, &mut options)
+            {
+                eprintln!("sh: {}", e);
+                return 2;
+            }
+            i += option_args.len();
+            if !command {
+                continue;
+            }
+
+            if i < args.len() {
+                script = Some(args[i].clone());
+                // Remaining args become $0, $1, ...
+                script_args = args[i + 1..].to_vec();
+                break;
+            }
+            eprintln!("sh: -c: option requires an argument");
+            return 2;
+        }
+
+        if arg == "--" {
+            i += 1;
+            continue;
+        }
+
+        // First non-flag arg is script file
+        match fs::read_to_string(arg) {
+            Ok(content) => {
+                script = Some(content);
+                // Remaining args become positional params
+                script_args = args[i + 1..].to_vec();
+            }
+            Err(e) => {
+                eprintln!("sh: {}: {}", arg, e);
+                return 1;
             }
         }
-        i += 1;
+        break;
     }
 
     let script = match script {
@@ -78,6 +116,7 @@ pub fn run(args: &[String], dispatch: fn(&str, &[String]) -> i32) -> i32 {
 
     let mut env = ShellEnv::new();
     env.positional = script_args;
+    env.options = options;
 
     let mut parser = Parser::new(&script);
     let program = match parser.parse_program() {
@@ -87,12 +126,16 @@ pub fn run(args: &[String], dispatch: fn(&str, &[String]) -> i32) -> i32 {
             return 2;
         }
     };
+    // `sh -n` only checks the syntax
+    if env.options.noexec {
+        return 0;
+    }
 
     let result = exec_program(&program, &mut env, dispatch);
+    let code = traps::run_exit_trap(&mut env, dispatch, result.exit_code);
 
-    if result.should_exit {
-        return result.exit_code;
-    }
+    // Like a caller reading our output to the end, collect background jobs
+    env.jobs.wait_all();
 
-    result.exit_code
+    code
 }
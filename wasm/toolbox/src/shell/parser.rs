@@ -1,4 +1,5 @@
 use super::ast::*;
+use super::cond::{BINARY_OPERATORS, UNARY_OPERATORS};
 use super::lexer::Lexer;
 use super::token::Token;
 
@@ -167,21 +168,45 @@ impl Parser {
 
     /// Parse a single command.
     fn parse_command(&mut self) -> Result<Command, String> {
-        match &self.current {
+        let command = match &self.current {
             Token::If => self.parse_if(),
             Token::For => self.parse_for(),
             Token::While => self.parse_while(),
             Token::Until => self.parse_until(),
             Token::Case => self.parse_case(),
             Token::LParen => self.parse_subshell(),
+            Token::DParen(expr) => {
+                let expr = expr.clone();
+                self.advance();
+                Ok(Command::Arithmetic(expr))
+            }
             Token::LBrace => self.parse_brace_group(),
             Token::Function => self.parse_function_keyword(),
+            Token::Word(w) if w == "[[" => self.parse_cond(),
             Token::Word(_) => {
                 // Could be a function definition: name() { ... }
                 // Or a simple command
-                self.parse_simple_or_func()
+                return self.parse_simple_or_func();
             }
-            other => Err(format!("unexpected token: {:?}", other)),
+            other => return Err(format!("unexpected token: {:?}", other)),
+        }?;
+
+        // Redirections after a compound command apply to all of it
+        let mut redirections = Vec::new();
+        while self.current.is_redirect() || matches!(self.current, Token::IoNumber(_)) {
+            let fd = match self.current {
+                Token::IoNumber(fd) => {
+                    self.advance();
+                    Some(fd)
+                }
+                _ => None,
+            };
+            redirections.push(self.parse_redirect(fd)?);
+        }
+        if redirections.is_empty() {
+            Ok(command)
+        } else {
+            Ok(Command::Redirected(Box::new(command), redirections))
         }
     }
 
@@ -193,7 +218,7 @@ impl Parser {
         };
 
         // Check for function definition: name() { body }
-        let saved_pos = self.lexer.pos();
+        let saved_lexer = self.lexer.clone();
         let saved_current = self.current.clone();
 
         self.advance();
@@ -211,19 +236,12 @@ impl Parser {
         }
 
         // Not a function def — restore and parse as simple command
-        // We can't easily restore the lexer, so we'll just re-parse
-        // Restore lexer state and parse as simple command with first word
+        self.lexer = saved_lexer;
         self.current = saved_current;
-        self.lexer = Lexer::new(&self.lexer_remaining_from_chars(saved_pos));
-        self.current = Token::Word(first_word.clone());
 
         self.parse_simple_command_starting_with(first_word)
     }
 
-    fn lexer_remaining_from_chars(&self, pos: usize) -> String {
-        self.lexer.chars_slice()[pos..].iter().collect()
-    }
-
     fn parse_simple_command(&mut self) -> Result<Command, String> {
         let mut cmd = SimpleCommand {
             assignments: Vec::new(),
@@ -238,26 +256,14 @@ impl Parser {
 
                     // Check if it's an assignment (name=value)
                     if cmd.words.is_empty() {
-                        if let Some(eq_pos) = w.find('=') {
-                            let name = &w[..eq_pos];
-                            if !name.is_empty()
-                                && name
-                                    .chars()
-                                    .all(|c| c.is_alphanumeric() || c == '_')
-                                && name.chars().next().map(|c| !c.is_ascii_digit()).unwrap_or(false)
-                            {
-                                let value = &w[eq_pos + 1..];
-                                cmd.assignments.push(Assignment {
-                                    name: name.to_string(),
-                                    value: parse_word_from_str(value),
-                                });
-                                self.advance();
-                                continue;
-                            }
+                        if let Some(assignment) = parse_assignment(&w) {
+                            cmd.assignments.push(assignment);
+                            self.advance();
+                            continue;
                         }
                     }
 
-                    cmd.words.push(parse_word_from_str(&w));
+                    cmd.words.push(parse_argument(&cmd.words, &w));
                     self.advance();
                 }
 
@@ -268,7 +274,7 @@ impl Parser {
                     cmd.redirections.push(redir);
                 }
 
-                Token::Less | Token::Great | Token::DGreat | Token::GreatAnd | Token::LessAnd | Token::DLess | Token::DLessDash | Token::TLess => {
+                token if token.is_redirect() => {
                     let redir = self.parse_redirect(None)?;
                     cmd.redirections.push(redir);
                 }
@@ -288,22 +294,9 @@ impl Parser {
         };
 
         // Check if first word is an assignment (name=value)
-        if let Some(eq_pos) = first_word.find('=') {
-            let name = &first_word[..eq_pos];
-            if !name.is_empty()
-                && name.chars().all(|c| c.is_alphanumeric() || c == '_')
-                && name.chars().next().map(|c| !c.is_ascii_digit()).unwrap_or(false)
-            {
-                let value = &first_word[eq_pos + 1..];
-                cmd.assignments.push(Assignment {
-                    name: name.to_string(),
-                    value: parse_word_from_str(value),
-                });
-            } else {
-                cmd.words.push(parse_word_from_str(&first_word));
-            }
-        } else {
-            cmd.words.push(parse_word_from_str(&first_word));
+        match parse_assignment(&first_word) {
+            Some(assignment) => cmd.assignments.push(assignment),
+            None => cmd.words.push(parse_word_from_str(&first_word)),
         }
 
         self.advance(); // skip the first word we already consumed
@@ -312,7 +305,13 @@ impl Parser {
             match &self.current {
                 Token::Word(w) => {
                     let w = w.clone();
-                    cmd.words.push(parse_word_from_str(&w));
+                    // Assignments continue until the first word of the command
+                    match parse_assignment(&w) {
+                        Some(assignment) if cmd.words.is_empty() => {
+                            cmd.assignments.push(assignment)
+                        }
+                        _ => cmd.words.push(parse_argument(&cmd.words, &w)),
+                    }
                     self.advance();
                 }
 
@@ -323,7 +322,7 @@ impl Parser {
                     cmd.redirections.push(redir);
                 }
 
-                Token::Less | Token::Great | Token::DGreat | Token::GreatAnd | Token::LessAnd | Token::DLess | Token::DLessDash | Token::TLess => {
+                token if token.is_redirect() => {
                     let redir = self.parse_redirect(None)?;
                     cmd.redirections.push(redir);
                 }
@@ -343,14 +342,21 @@ impl Parser {
             Token::GreatAnd => RedirectKind::DupOutput,
             Token::LessAnd => RedirectKind::DupInput,
             Token::TLess => RedirectKind::HereString,
+            Token::AndGreat => RedirectKind::OutputAll,
+            Token::AndDGreat => RedirectKind::AppendAll,
             Token::DLess | Token::DLessDash => {
-                // Here-doc: body was already read by the lexer
-                let body = self.lexer.heredoc_bodies.pop().unwrap_or_default();
+                // Here-doc: the lexer read the body along with the operator
+                let (body, quoted) = self.lexer.heredoc_bodies.pop_front().unwrap_or_default();
                 self.advance();
+                let target = if quoted {
+                    Word::literal(&body)
+                } else {
+                    parse_heredoc_body(&body)
+                };
                 return Ok(Redirect {
                     fd,
-                    kind: RedirectKind::HereDoc(body),
-                    target: Word::literal(""),
+                    kind: RedirectKind::HereDoc,
+                    target,
                 });
             }
             other => return Err(format!("expected redirection operator, got {:?}", other)),
@@ -429,6 +435,11 @@ impl Parser {
 
     fn parse_for(&mut self) -> Result<Command, String> {
         self.advance(); // eat 'for'
+        if let Token::DParen(expr) = &self.current {
+            let expr = expr.clone();
+            self.advance();
+            return self.parse_arith_for(&expr);
+        }
         let var = self.expect_word()?;
 
         self.skip_newlines();
@@ -468,6 +479,134 @@ impl Parser {
         Ok(Command::For(ForClause { var, words, body }))
     }
 
+    /// Parse the rest of `for (( init; condition; step ))` after the
+    /// expressions.
+    fn parse_arith_for(&mut self, expr: &str) -> Result<Command, String> {
+        let exprs: Vec<&str> = expr.split(';').collect();
+        let [init, condition, step] = exprs.as_slice() else {
+            return Err(format!(
+                "expected 3 expressions in for (( )), got '{}'",
+                expr
+            ));
+        };
+
+        while matches!(self.current, Token::Semi | Token::Newline) {
+            self.advance();
+        }
+        if !matches!(self.current, Token::Do) {
+            return Err(format!("expected 'do', got {:?}", self.current));
+        }
+        self.advance();
+        self.skip_newlines();
+
+        let body = self.parse_compound_list()?;
+
+        if !matches!(self.current, Token::Done) {
+            return Err(format!("expected 'done', got {:?}", self.current));
+        }
+        self.advance();
+
+        Ok(Command::ArithFor(ArithForClause {
+            init: init.to_string(),
+            condition: condition.to_string(),
+            step: step.to_string(),
+            body,
+        }))
+    }
+
+    /// Parse `[[ expression ]]`.
+    fn parse_cond(&mut self) -> Result<Command, String> {
+        self.advance(); // eat '[['
+        let expr = self.parse_cond_or()?;
+        self.skip_newlines();
+        match &self.current {
+            Token::Word(w) if w == "]]" => {
+                self.advance();
+                Ok(Command::Cond(expr))
+            }
+            other => Err(format!("expected ']]', got {:?}", other)),
+        }
+    }
+
+    fn parse_cond_or(&mut self) -> Result<CondExpr, String> {
+        let mut expr = self.parse_cond_and()?;
+        while matches!(self.current, Token::Or) {
+            self.advance();
+            let right = self.parse_cond_and()?;
+            expr = CondExpr::Or(Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_cond_and(&mut self) -> Result<CondExpr, String> {
+        let mut expr = self.parse_cond_not()?;
+        while matches!(self.current, Token::And) {
+            self.advance();
+            let right = self.parse_cond_not()?;
+            expr = CondExpr::And(Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_cond_not(&mut self) -> Result<CondExpr, String> {
+        self.skip_newlines();
+        match &self.current {
+            Token::Bang => {
+                self.advance();
+                Ok(CondExpr::Not(Box::new(self.parse_cond_not()?)))
+            }
+            Token::Word(w) if w == "!" => {
+                self.advance();
+                Ok(CondExpr::Not(Box::new(self.parse_cond_not()?)))
+            }
+            Token::LParen => {
+                self.advance();
+                let expr = self.parse_cond_or()?;
+                self.skip_newlines();
+                if !matches!(self.current, Token::RParen) {
+                    return Err(format!("expected ')' in [[ ]], got {:?}", self.current));
+                }
+                self.advance();
+                Ok(expr)
+            }
+            _ => self.parse_cond_primary(),
+        }
+    }
+
+    /// Parse a test: `word`, `-op word` or `word op word`.
+    fn parse_cond_primary(&mut self) -> Result<CondExpr, String> {
+        let first = self.expect_cond_word()?;
+        let has_operand = matches!(&self.current, Token::Word(w) if w != "]]");
+        if UNARY_OPERATORS.contains(&first.as_str()) && has_operand {
+            let operand = self.expect_cond_word()?;
+            return Ok(CondExpr::Unary(first, parse_word_from_str(&operand)));
+        }
+
+        // `<` and `>` compare strings here rather than redirect
+        let op = match &self.current {
+            Token::Less => "<".to_string(),
+            Token::Great => ">".to_string(),
+            Token::Word(w) if BINARY_OPERATORS.contains(&w.as_str()) => w.clone(),
+            _ => return Ok(CondExpr::Word(parse_word_from_str(&first))),
+        };
+        let right = if op == "=~" {
+            let regex = self.lexer.read_regex();
+            self.advance();
+            parse_regex_word(&regex)
+        } else {
+            self.advance();
+            parse_word_from_str(&self.expect_cond_word()?)
+        };
+        Ok(CondExpr::Binary(parse_word_from_str(&first), op, right))
+    }
+
+    fn expect_cond_word(&mut self) -> Result<String, String> {
+        match &self.current {
+            Token::Word(w) if w == "]]" => Err("unexpected ']]' in [[ ]]".to_string()),
+            _ => self.expect_word(),
+        }
+    }
+
     fn parse_while(&mut self) -> Result<Command, String> {
         self.advance(); // eat 'while'
         self.skip_newlines();
@@ -518,7 +657,9 @@ impl Parser {
         let word = parse_word_from_str(&word_str);
 
         self.skip_newlines();
-        if !matches!(self.current, Token::In) {
+        if !matches!(self.current, Token::In)
+            && !matches!(&self.current, Token::Word(w) if w == "in")
+        {
             return Err(format!("expected 'in', got {:?}", self.current));
         }
         self.advance();
@@ -551,7 +692,7 @@ impl Parser {
             let body = self.parse_compound_list()?;
 
             // Expect ;; or newline before esac
-            while matches!(self.current, Token::Semi | Token::Newline) {
+            while matches!(self.current, Token::DSemi | Token::Semi | Token::Newline) {
                 self.advance();
             }
 
@@ -607,7 +748,133 @@ impl Parser {
     }
 }
 
+/// Commands whose `name=(...)` arguments assign arrays, as in
+/// `declare -A map=([key]=value)`.
+const DECLARATION_COMMANDS: &[&str] = &["declare", "typeset", "local", "export", "readonly"];
+
+/// Parse a word that follows `words` in a simple command.
+fn parse_argument(words: &[Word], w: &str) -> Word {
+    let declares = matches!(
+        words.first().map(|word| word.parts.as_slice()),
+        Some([WordPart::Literal(name)]) if DECLARATION_COMMANDS.contains(&name.as_str())
+    );
+    match parse_assignment(w) {
+        Some(assignment) if declares && matches!(assignment.value, AssignValue::Array(_)) => Word {
+            parts: vec![WordPart::Assignment(Box::new(assignment))],
+        },
+        _ => parse_word_from_str(w),
+    }
+}
+
+/// Parse an assignment word: `name=value`, `name+=value`, `name[i]=value`
+/// or `name=(x y z)`.
+fn parse_assignment(w: &str) -> Option<Assignment> {
+    let eq_pos = w.find('=')?;
+    let (target, append) = match w[..eq_pos].strip_suffix('+') {
+        Some(target) => (target, true),
+        None => (&w[..eq_pos], false),
+    };
+    let (name, index) = match target.find('[') {
+        Some(open) if target.ends_with(']') => (
+            &target[..open],
+            Some(parse_word_from_str(&target[open + 1..target.len() - 1])),
+        ),
+        _ => (target, None),
+    };
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+
+    let raw = &w[eq_pos + 1..];
+    let value = match raw
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        Some(inner) if index.is_none() => AssignValue::Array(
+            Lexer::array_elements(inner)
+                .iter()
+                .map(|element| parse_array_element(element))
+                .collect(),
+        ),
+        _ => AssignValue::Scalar(parse_word_from_str(raw)),
+    };
+    Some(Assignment {
+        name: name.to_string(),
+        index,
+        value,
+        append,
+    })
+}
+
+/// Parse an element of an array literal, which may start with its
+/// subscript as in `[key]=value`.
+fn parse_array_element(element: &str) -> (Option<Word>, Word) {
+    if let Some(rest) = element.strip_prefix('[')
+        && let Some(close) = rest.find("]=")
+    {
+        return (
+            Some(parse_word_from_str(&rest[..close])),
+            parse_word_from_str(&rest[close + 2..]),
+        );
+    }
+    (None, parse_word_from_str(element))
+}
+
 /// Parse a raw word string from the lexer into a Word AST node.
+/// Parse the regular expression after `=~`. Unquoted text becomes Glob
+/// parts, keeping its meaning in the regex, while quoted and escaped text
+/// matches literally.
+pub fn parse_regex_word(s: &str) -> Word {
+    let mut parts = Vec::new();
+    let chars: Vec<char> = s.chars().collect();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        match chars[pos] {
+            '\\' => {
+                pos += 1;
+                if pos < chars.len() {
+                    parts.push(WordPart::Literal(chars[pos].to_string()));
+                    pos += 1;
+                }
+            }
+            quote @ ('\'' | '"') => {
+                let start = pos;
+                pos += 1;
+                while pos < chars.len() && chars[pos] != quote {
+                    if quote == '"' && chars[pos] == '\\' {
+                        pos += 1;
+                    }
+                    pos += 1;
+                }
+                pos = (pos + 1).min(chars.len());
+                let quoted: String = chars[start..pos].iter().collect();
+                parts.extend(parse_word_from_str(&quoted).parts);
+            }
+            // A `$` not starting an expansion anchors the end
+            '$' if chars
+                .get(pos + 1)
+                .is_some_and(|&c| c.is_alphanumeric() || matches!(c, '_' | '{' | '(')) =>
+            {
+                pos += 1;
+                parts.push(parse_dollar(&chars, &mut pos));
+            }
+            _ => {
+                let start = pos;
+                pos += 1;
+                while pos < chars.len() && !matches!(chars[pos], '\\' | '\'' | '"' | '$') {
+                    pos += 1;
+                }
+                parts.push(WordPart::Glob(chars[start..pos].iter().collect()));
+            }
+        }
+    }
+
+    Word { parts }
+}
+
 pub fn parse_word_from_str(s: &str) -> Word {
     let mut parts = Vec::new();
     let chars: Vec<char> = s.chars().collect();
@@ -630,53 +897,28 @@ pub fn parse_word_from_str(s: &str) -> Word {
 
             '"' => {
                 pos += 1;
-                let mut inner_parts = Vec::new();
-                while pos < chars.len() && chars[pos] != '"' {
-                    if chars[pos] == '\\' && pos + 1 < chars.len() {
-                        let next = chars[pos + 1];
-                        if matches!(next, '$' | '`' | '"' | '\\' | '\n') {
-                            if next != '\n' {
-                                inner_parts.push(WordPart::Literal(next.to_string()));
-                            }
-                            pos += 2;
-                        } else {
-                            inner_parts.push(WordPart::Literal("\\".to_string()));
-                            pos += 1;
-                        }
-                    } else if chars[pos] == '$' {
-                        pos += 1;
-                        let part = parse_dollar(&chars, &mut pos);
-                        inner_parts.push(part);
-                    } else if chars[pos] == '`' {
-                        pos += 1;
-                        let start = pos;
-                        while pos < chars.len() && chars[pos] != '`' {
-                            pos += 1;
-                        }
-                        let cmd: String = chars[start..pos].iter().collect();
-                        inner_parts.push(WordPart::CommandSub(cmd));
-                        if pos < chars.len() {
-                            pos += 1;
-                        }
-                    } else {
-                        let start = pos;
-                        while pos < chars.len() && !matches!(chars[pos], '"' | '$' | '`' | '\\') {
-                            pos += 1;
-                        }
-                        let lit: String = chars[start..pos].iter().collect();
-                        inner_parts.push(WordPart::Literal(lit));
-                    }
-                }
+                let inner_parts = parse_double_quoted(&chars, &mut pos, Some('"'));
                 if pos < chars.len() {
                     pos += 1; // skip closing "
                 }
                 parts.push(WordPart::DoubleQuoted(inner_parts));
             }
 
+            // Process substitution, which only starts a word
+            '<' | '>' if pos == 0 && chars.get(1) == Some(&'(') => {
+                let output = chars[0] == '>';
+                pos += 1;
+                if let WordPart::CommandSub(cmd) = parse_dollar(&chars, &mut pos) {
+                    parts.push(WordPart::ProcessSub(cmd, output));
+                }
+            }
+
+            // An escaped character is quoted, so it is not brace or tilde
+            // syntax either
             '\\' => {
                 pos += 1;
                 if pos < chars.len() {
-                    parts.push(WordPart::Literal(chars[pos].to_string()));
+                    parts.push(WordPart::SingleQuoted(chars[pos].to_string()));
                     pos += 1;
                 }
             }
@@ -700,15 +942,53 @@ pub fn parse_word_from_str(s: &str) -> Word {
                 }
             }
 
+            // Extended glob patterns such as @(a|b), kept whole
+            '?' | '*' | '+' | '@' | '!' if chars.get(pos + 1) == Some(&'(') => {
+                let start = pos;
+                pos += 1;
+                let mut depth = 0;
+                while pos < chars.len() {
+                    match chars[pos] {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        '\\' => pos += 1,
+                        _ => {}
+                    }
+                    pos += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                let end = pos.min(chars.len());
+                parts.push(WordPart::Glob(chars[start..end].iter().collect()));
+                pos = end;
+            }
+
             '*' | '?' => {
                 parts.push(WordPart::Glob(chars[pos].to_string()));
                 pos += 1;
             }
 
+            // A bracket expression, where a `]` first in the list and
+            // character classes such as [:alpha:] do not close it
             '[' => {
                 let start = pos;
                 pos += 1;
+                if matches!(chars.get(pos), Some('!' | '^')) {
+                    pos += 1;
+                }
+                if chars.get(pos) == Some(&']') {
+                    pos += 1;
+                }
                 while pos < chars.len() && chars[pos] != ']' {
+                    if chars[pos] == '[' && chars.get(pos + 1) == Some(&':') {
+                        let class_end = (pos + 2..chars.len().saturating_sub(1))
+                            .find(|&i| chars[i] == ':' && chars[i + 1] == ']');
+                        if let Some(class_end) = class_end {
+                            pos = class_end + 2;
+                            continue;
+                        }
+                    }
                     pos += 1;
                 }
                 if pos < chars.len() {
@@ -722,6 +1002,7 @@ pub fn parse_word_from_str(s: &str) -> Word {
                 let start = pos;
                 while pos < chars.len()
                     && !matches!(chars[pos], '\'' | '"' | '\\' | '$' | '`' | '*' | '?' | '[')
+                    && !(matches!(chars[pos], '+' | '@' | '!') && chars.get(pos + 1) == Some(&'('))
                 {
                     pos += 1;
                 }
@@ -734,6 +1015,64 @@ pub fn parse_word_from_str(s: &str) -> Word {
     Word { parts }
 }
 
+/// Parse a here-doc body whose delimiter was not quoted, which expands
+/// like a double-quoted string in which `"` has no special meaning.
+pub fn parse_heredoc_body(body: &str) -> Word {
+    let chars: Vec<char> = body.chars().collect();
+    let mut pos = 0;
+    Word {
+        parts: vec![WordPart::DoubleQuoted(parse_double_quoted(
+            &chars, &mut pos, None,
+        ))],
+    }
+}
+
+/// Parse the inside of double quotes up to the closing `end`, or to the end
+/// of `chars` if there is none.
+fn parse_double_quoted(chars: &[char], pos: &mut usize, end: Option<char>) -> Vec<WordPart> {
+    let mut parts = Vec::new();
+    let is_end = |ch: char| Some(ch) == end;
+    while *pos < chars.len() && !is_end(chars[*pos]) {
+        if chars[*pos] == '\\' && *pos + 1 < chars.len() {
+            let next = chars[*pos + 1];
+            if matches!(next, '$' | '`' | '\\' | '\n') || is_end(next) {
+                if next != '\n' {
+                    parts.push(WordPart::Literal(next.to_string()));
+                }
+                *pos += 2;
+            } else {
+                parts.push(WordPart::Literal("\\".to_string()));
+                *pos += 1;
+            }
+        } else if chars[*pos] == '$' {
+            *pos += 1;
+            parts.push(parse_dollar(chars, pos));
+        } else if chars[*pos] == '`' {
+            *pos += 1;
+            let start = *pos;
+            while *pos < chars.len() && chars[*pos] != '`' {
+                *pos += 1;
+            }
+            let cmd: String = chars[start..*pos].iter().collect();
+            parts.push(WordPart::CommandSub(cmd));
+            if *pos < chars.len() {
+                *pos += 1;
+            }
+        } else {
+            let start = *pos;
+            while *pos < chars.len()
+                && !matches!(chars[*pos], '$' | '`' | '\\')
+                && !is_end(chars[*pos])
+            {
+                *pos += 1;
+            }
+            let lit: String = chars[start..*pos].iter().collect();
+            parts.push(WordPart::Literal(lit));
+        }
+    }
+    parts
+}
+
 fn parse_dollar(chars: &[char], pos: &mut usize) -> WordPart {
     if *pos >= chars.len() {
         return WordPart::Literal("$".to_string());
@@ -743,26 +1082,21 @@ fn parse_dollar(chars: &[char], pos: &mut usize) -> WordPart {
         '(' => {
             *pos += 1;
             if *pos < chars.len() && chars[*pos] == '(' {
-                // $(( arithmetic ))
+                // $(( arithmetic )), up to the `)` closing the inner `(`
                 *pos += 1;
                 let start = *pos;
-                let mut depth = 2;
-                while *pos < chars.len() && depth > 0 {
-                    if chars[*pos] == '(' {
-                        depth += 1;
-                    }
-                    if chars[*pos] == ')' {
-                        depth -= 1;
+                let mut depth = 0;
+                while *pos < chars.len() {
+                    match chars[*pos] {
+                        '(' => depth += 1,
+                        ')' if depth == 0 => break,
+                        ')' => depth -= 1,
+                        _ => {}
                     }
-                    if depth > 0 {
-                        *pos += 1;
-                    }
-                }
-                let expr: String = chars[start..*pos].iter().collect();
-                *pos += 1; // skip last )
-                if *pos < chars.len() && chars[*pos] == ')' {
                     *pos += 1;
                 }
+                let expr: String = chars[start..*pos].iter().collect();
+                *pos = (*pos + 2).min(chars.len()); // skip ))
                 WordPart::ArithmeticSub(expr)
             } else {
                 // $( command substitution )
@@ -789,88 +1123,7 @@ fn parse_dollar(chars: &[char], pos: &mut usize) -> WordPart {
 
         '{' => {
             *pos += 1;
-
-            // ${#VAR} — length
-            if *pos < chars.len() && chars[*pos] == '#' {
-                *pos += 1;
-                let start = *pos;
-                while *pos < chars.len() && chars[*pos] != '}' {
-                    *pos += 1;
-                }
-                let name: String = chars[start..*pos].iter().collect();
-                if *pos < chars.len() {
-                    *pos += 1; // skip }
-                }
-                return WordPart::VarLength(name);
-            }
-
-            let start = *pos;
-            // Read variable name
-            while *pos < chars.len()
-                && (chars[*pos].is_alphanumeric() || chars[*pos] == '_' || chars[*pos] == '?' || chars[*pos] == '#')
-            {
-                *pos += 1;
-            }
-            let name: String = chars[start..*pos].iter().collect();
-
-            if *pos < chars.len() && chars[*pos] == '}' {
-                *pos += 1;
-                return WordPart::Variable(name);
-            }
-
-            // Check for modifiers
-            if *pos + 1 < chars.len() && chars[*pos] == ':' && chars[*pos + 1] == '-' {
-                *pos += 2; // skip :-
-                let def_start = *pos;
-                let mut depth = 1;
-                while *pos < chars.len() && depth > 0 {
-                    if chars[*pos] == '{' {
-                        depth += 1;
-                    }
-                    if chars[*pos] == '}' {
-                        depth -= 1;
-                    }
-                    if depth > 0 {
-                        *pos += 1;
-                    }
-                }
-                let default: String = chars[def_start..*pos].iter().collect();
-                if *pos < chars.len() {
-                    *pos += 1; // skip }
-                }
-                return WordPart::VarDefault(name, parse_word_from_str(&default));
-            }
-
-            if *pos + 1 < chars.len() && chars[*pos] == ':' && chars[*pos + 1] == '=' {
-                *pos += 2;
-                let def_start = *pos;
-                let mut depth = 1;
-                while *pos < chars.len() && depth > 0 {
-                    if chars[*pos] == '{' {
-                        depth += 1;
-                    }
-                    if chars[*pos] == '}' {
-                        depth -= 1;
-                    }
-                    if depth > 0 {
-                        *pos += 1;
-                    }
-                }
-                let default: String = chars[def_start..*pos].iter().collect();
-                if *pos < chars.len() {
-                    *pos += 1;
-                }
-                return WordPart::VarAssignDefault(name, parse_word_from_str(&default));
-            }
-
-            // Skip to closing }
-            while *pos < chars.len() && chars[*pos] != '}' {
-                *pos += 1;
-            }
-            if *pos < chars.len() {
-                *pos += 1;
-            }
-            WordPart::Variable(name)
+            parse_braced(&read_braced(chars, pos))
         }
 
         '?' => {
@@ -893,9 +1146,13 @@ fn parse_dollar(chars: &[char], pos: &mut usize) -> WordPart {
             *pos += 1;
             WordPart::SpecialVar(SpecialVar::ProcessId)
         }
+        '-' => {
+            *pos += 1;
+            WordPart::SpecialVar(SpecialVar::OptionFlags)
+        }
         '!' => {
             *pos += 1;
-            WordPart::SpecialVar(SpecialVar::ProcessId) // $! — last bg pid (approximate)
+            WordPart::SpecialVar(SpecialVar::LastBackgroundPid)
         }
 
         '0'..='9' => {
@@ -917,3 +1174,181 @@ fn parse_dollar(chars: &[char], pos: &mut usize) -> WordPart {
     }
 }
 
+/// Read the inside of `${...}` up to its closing brace, leaving `pos` after
+/// the brace.
+fn read_braced(chars: &[char], pos: &mut usize) -> String {
+    let start = *pos;
+    let mut depth = 1;
+    let mut in_quotes = false;
+    while *pos < chars.len() {
+        match chars[*pos] {
+            '\\' => *pos += 1,
+            '"' => in_quotes = !in_quotes,
+            '{' if !in_quotes => depth += 1,
+            '}' if !in_quotes => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+        *pos += 1;
+    }
+    let body = chars[start..(*pos).min(chars.len())].iter().collect();
+    if *pos < chars.len() {
+        *pos += 1; // skip }
+    }
+    body
+}
+
+/// Parse the inside of `${...}`.
+fn parse_braced(body: &str) -> WordPart {
+    // ${#VAR}, ${#a[@]} and ${#a[i]} — lengths; ${#} alone is $#
+    if let Some(name) = body.strip_prefix('#').filter(|name| !name.is_empty()) {
+        return match split_subscript(name) {
+            Some((name, "@" | "*")) => WordPart::ArrayLength(name.to_string()),
+            Some((name, index)) => {
+                WordPart::ElementLength(name.to_string(), parse_word_from_str(index))
+            }
+            None => WordPart::VarLength(name.to_string()),
+        };
+    }
+
+    // ${!a[@]} — keys of an array; ${!prefix*} — names of variables;
+    // ${!VAR} — the variable VAR names
+    if let Some(rest) = body.strip_prefix('!').filter(|rest| !rest.is_empty()) {
+        if let Some((name, all @ ("@" | "*"))) = split_subscript(rest) {
+            return WordPart::ArrayKeys(name.to_string(), all == "@");
+        }
+        if let Some(prefix) = rest.strip_suffix('@') {
+            return WordPart::VarNames(prefix.to_string(), true);
+        }
+        if let Some(prefix) = rest.strip_suffix('*') {
+            return WordPart::VarNames(prefix.to_string(), false);
+        }
+        return WordPart::Indirect(rest.to_string());
+    }
+
+    // The parameter: a special parameter, a positional number or a name
+    let name_len = match body.chars().next() {
+        Some('@' | '*' | '?' | '$' | '!' | '-' | '#') => 1,
+        Some(c) if c.is_ascii_digit() => body.chars().take_while(char::is_ascii_digit).count(),
+        _ => body
+            .chars()
+            .take_while(|&c| c.is_alphanumeric() || c == '_')
+            .map(char::len_utf8)
+            .sum(),
+    };
+    let (name, mut rest) = body.split_at(name_len);
+
+    let mut index = None;
+    if rest.starts_with('[')
+        && let Some(close) = rest.find(']')
+    {
+        index = Some(match &rest[1..close] {
+            "@" => Subscript::All,
+            "*" => Subscript::Joined,
+            text => Subscript::Element(parse_word_from_str(text)),
+        });
+        rest = &rest[close + 1..];
+    }
+
+    if rest.is_empty() {
+        return match (name, index) {
+            (_, Some(Subscript::All)) => WordPart::ArrayValues(name.to_string(), true),
+            (_, Some(Subscript::Joined)) => WordPart::ArrayValues(name.to_string(), false),
+            (_, Some(Subscript::Element(index))) => WordPart::ArrayElement(name.to_string(), index),
+            ("@", None) => WordPart::SpecialVar(SpecialVar::AllArgs),
+            ("*", None) => WordPart::SpecialVar(SpecialVar::AllArgsStar),
+            ("?", None) => WordPart::SpecialVar(SpecialVar::ExitStatus),
+            ("#", None) => WordPart::SpecialVar(SpecialVar::NumArgs),
+            ("$", None) => WordPart::SpecialVar(SpecialVar::ProcessId),
+            ("!", None) => WordPart::SpecialVar(SpecialVar::LastBackgroundPid),
+            ("-", None) => WordPart::SpecialVar(SpecialVar::OptionFlags),
+            (_, None) => match name.parse() {
+                Ok(n) => WordPart::SpecialVar(SpecialVar::Positional(n)),
+                Err(_) => WordPart::Variable(name.to_string()),
+            },
+        };
+    }
+
+    let word = |text: &str| parse_word_from_str(text);
+    let op = if let Some(rest) = rest.strip_prefix(':') {
+        match rest.chars().next() {
+            Some('-') => ParamOp::Default(word(&rest[1..]), true),
+            Some('=') => ParamOp::AssignDefault(word(&rest[1..]), true),
+            Some('+') => ParamOp::Alternative(word(&rest[1..]), true),
+            Some('?') => ParamOp::Error(word(&rest[1..]), true),
+            _ => {
+                let (offset, length) = split_top_level(rest, ':');
+                ParamOp::Substring(
+                    word(offset.trim()),
+                    length.map(|length| word(length.trim())),
+                )
+            }
+        }
+    } else if let Some(pattern) = rest.strip_prefix("##") {
+        ParamOp::RemovePrefix(word(pattern), true)
+    } else if let Some(pattern) = rest.strip_prefix('#') {
+        ParamOp::RemovePrefix(word(pattern), false)
+    } else if let Some(pattern) = rest.strip_prefix("%%") {
+        ParamOp::RemoveSuffix(word(pattern), true)
+    } else if let Some(pattern) = rest.strip_prefix('%') {
+        ParamOp::RemoveSuffix(word(pattern), false)
+    } else if let Some(rest) = rest.strip_prefix('/') {
+        let (mode, rest) = match rest.chars().next() {
+            Some('/') => (ReplaceMode::All, &rest[1..]),
+            Some('#') => (ReplaceMode::Prefix, &rest[1..]),
+            Some('%') => (ReplaceMode::Suffix, &rest[1..]),
+            _ => (ReplaceMode::First, rest),
+        };
+        let (pattern, replacement) = split_top_level(rest, '/');
+        ParamOp::Replace(word(pattern), word(replacement.unwrap_or("")), mode)
+    } else if let Some(rest) = rest.strip_prefix('^') {
+        ParamOp::Upper(rest.starts_with('^'))
+    } else if let Some(rest) = rest.strip_prefix(',') {
+        ParamOp::Lower(rest.starts_with(','))
+    } else {
+        match rest.chars().next() {
+            Some('-') => ParamOp::Default(word(&rest[1..]), false),
+            Some('=') => ParamOp::AssignDefault(word(&rest[1..]), false),
+            Some('+') => ParamOp::Alternative(word(&rest[1..]), false),
+            Some('?') => ParamOp::Error(word(&rest[1..]), false),
+            // Not an operator we know; expand the parameter alone
+            _ => return WordPart::Variable(name.to_string()),
+        }
+    };
+
+    WordPart::Param(Box::new(ParamExpansion {
+        name: name.to_string(),
+        index,
+        op,
+    }))
+}
+
+/// Split `s` at the first `sep` that is not escaped, quoted or inside a
+/// nested expansion.
+fn split_top_level(s: &str, sep: char) -> (&str, Option<&str>) {
+    let mut depth = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '{' | '(' if !in_quotes => depth += 1,
+            '}' | ')' if !in_quotes => depth -= 1,
+            _ if c == sep && depth == 0 && !in_quotes => return (&s[..i], Some(&s[i + 1..])),
+            _ => {}
+        }
+    }
+    (s, None)
+}
+
+/// Split `name[index]` into the name and the index.
+fn split_subscript(s: &str) -> Option<(&str, &str)> {
+    let (name, rest) = s.split_once('[')?;
+    Some((name, rest.strip_suffix(']')?))
+}
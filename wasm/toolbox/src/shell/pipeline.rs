@@ -1,57 +1,244 @@
-use std::fs;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::io::Write;
 
-static PIPE_COUNTER: AtomicU32 = AtomicU32::new(0);
+use super::ast::{Command, Word, WordPart};
+use super::audit;
+use super::builtins;
+use super::env::ShellEnv;
+use super::exec::{self, DispatchFn};
+use super::io::{Output, Stdio};
+use super::subshell;
+use crate::spawn;
 
-/// Get a unique temp file path for pipe buffers.
-pub fn pipe_temp_file() -> String {
-    let n = PIPE_COUNTER.fetch_add(1, Ordering::SeqCst);
-    format!("/work/.sh_pipe_{}", n)
+/// A pipeline stage, run as a child instance.
+pub enum Stage {
+    /// A tool, with its words expanded.
+    Tool(Vec<String>),
+    /// A command that needs the shell itself, such as a builtin, function or
+    /// loop, given as the state of the subshell that runs it.
+    Shell(String),
 }
 
-/// Clean up a temp pipe file.
-pub fn cleanup_pipe(path: &str) {
-    let _ = fs::remove_file(path);
+/// Execute a multi-stage pipeline, returning the exit code of every stage.
+///
+/// Every stage runs as a child instance, all at once and connected by
+/// bounded pipes, so data streams between them and a stage that stops
+/// reading stops its writers (`yes | head -1`). Stages that need the shell
+/// itself run in subshells, so as in bash, none of them can change this
+/// shell's variables or directory.
+pub fn exec_pipeline(commands: &[Command], env: &mut ShellEnv, dispatch: DispatchFn) -> Vec<i32> {
+    let stages = commands
+        .iter()
+        .map(|cmd| stage(cmd, env, dispatch))
+        .collect();
+    let io = env.io.clone();
+    start_external(stages, &io, env).finish()
 }
 
-/// Execute a pipeline: run each stage sequentially, using temp files as pipe buffers.
-/// Returns the exit code of the last command in the pipeline.
+/// How `cmd` runs as a stage of a pipeline.
 ///
-/// The actual execution is done by the caller — this module just manages the temp files.
-pub struct PipelineState {
-    /// Temp files used as pipe buffers between stages.
-    pub pipe_files: Vec<String>,
-}
-
-impl PipelineState {
-    /// Create state for a pipeline with `n` commands.
-    pub fn new(num_commands: usize) -> Self {
-        let mut pipe_files = Vec::new();
-        // We need n-1 pipe files for n commands
-        for _ in 0..num_commands.saturating_sub(1) {
-            pipe_files.push(pipe_temp_file());
+/// A tool named literally, without assignments or redirections, runs
+/// directly; anything else runs in a subshell. A simple command is
+/// expanded and traced here either way, so stages trace in order under
+/// `set -x` and nothing is expanded twice.
+fn stage(cmd: &Command, env: &mut ShellEnv, dispatch: DispatchFn) -> Stage {
+    let Command::Simple(simple) = cmd else {
+        return subshell_stage(subshell::state(cmd, env), env);
+    };
+    if simple.assignments.is_empty()
+        && simple.redirections.is_empty()
+        && let Some(name) = simple.words.first().and_then(literal_word)
+        && is_external(&[name], env)
+    {
+        let words = exec::expand_words(&simple.words, env, dispatch);
+        // The error is reported already; the stage only has to fail
+        if std::mem::take(&mut env.expansion_error) {
+            return Stage::Shell(subshell::exit(1));
+        }
+        exec::trace(&words, env);
+        return Stage::Tool(words);
+    }
+    // Assignments go to the subshell's environment, not this shell's
+    let mut sub_env = env.clone();
+    match exec::prepare_simple(simple, &mut sub_env, dispatch) {
+        Ok(words) => subshell_stage(subshell::prepared(simple, words, &sub_env), env),
+        Err(result) => Stage::Shell(subshell::exit(result.exit_code)),
+    }
+}
+
+/// The stage running a subshell, or failing if it could not be handed over.
+fn subshell_stage(state: Result<String, String>, env: &ShellEnv) -> Stage {
+    match state {
+        Ok(state) => Stage::Shell(state),
+        Err(e) => {
+            env.eprint(&format!("sh: {}\n", e));
+            Stage::Shell(subshell::exit(1))
+        }
+    }
+}
+
+/// The text of a word made only of literal parts.
+pub fn literal_word(word: &Word) -> Option<String> {
+    let mut text = String::new();
+    for part in &word.parts {
+        match part {
+            WordPart::Literal(s) | WordPart::SingleQuoted(s) => text.push_str(s),
+            _ => return None,
         }
-        PipelineState { pipe_files }
     }
+    Some(text)
+}
+
+/// Run external commands as a pipeline of child instances using the given
+/// streams, returning the exit code of every stage.
+pub fn run_external(stages: Vec<Vec<String>>, stdio: &Stdio, env: &ShellEnv) -> Vec<i32> {
+    let stages = stages.into_iter().map(Stage::Tool).collect();
+    start_external(stages, stdio, env).finish()
+}
+
+/// External commands started as child instances and not yet waited for.
+#[derive(Debug)]
+pub struct Running {
+    /// `None` if the pipeline failed to start.
+    job: Option<spawn::Job>,
+    stdio: Stdio,
+    capture_stdout: bool,
+    capture_stderr: bool,
+    num_stages: usize,
+    /// Every tool stage by its position, for the execution trace.
+    traced: Vec<(usize, audit::Started)>,
+}
+
+/// Start a pipeline of child instances using the given streams, without
+/// waiting for them.
+pub fn start_external(stages: Vec<Stage>, stdio: &Stdio, env: &ShellEnv) -> Running {
+    let discard_stdout = matches!(stdio.stdout, Output::Null);
+    let discard_stderr = matches!(stdio.stderr, Output::Null);
+    let stderr_to_stdout = matches!(stdio.stderr, Output::Stdout);
+    let capture_stdout = !discard_stdout && !matches!(stdio.stdout, Output::Stdout);
+    let capture_stderr =
+        !discard_stderr && !stderr_to_stdout && !matches!(stdio.stderr, Output::Stderr);
 
-    /// Get the input file for stage `i` (None for the first stage).
-    pub fn input_for(&self, stage: usize) -> Option<&str> {
-        if stage == 0 {
+    // Children write to our stdout and stderr directly, after anything we
+    // have buffered
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+
+    let num_stages = stages.len();
+    let traced = stages
+        .iter()
+        .enumerate()
+        .filter_map(|(i, stage)| match stage {
+            Stage::Tool(argv) => audit::start(argv, env).map(|started| (i, started)),
+            Stage::Shell(_) => None,
+        })
+        .collect();
+    let (stages, subshells) = stages
+        .into_iter()
+        .map(|stage| match stage {
+            Stage::Tool(argv) => (argv, None),
+            Stage::Shell(state) => (vec!["sh".to_string()], Some(state)),
+        })
+        .unzip();
+    let pipeline = spawn::Pipeline {
+        stages,
+        subshells,
+        env: env.exported_vars(),
+        cwd: std::env::current_dir()
+            .ok()
+            .map(|dir| dir.to_string_lossy().into_owned()),
+        stdin: stdio.stdin.remaining(),
+        capture_stdout,
+        capture_stderr,
+        discard_stdout,
+        discard_stderr,
+        stderr_to_stdout,
+    };
+
+    let job = match pipeline.spawn() {
+        Ok(job) => Some(job),
+        Err(e) => {
+            let _ = stdio.stderr.write(format!("sh: {}\n", e).as_bytes());
             None
-        } else {
-            self.pipe_files.get(stage - 1).map(|s| s.as_str())
         }
+    };
+
+    Running {
+        job,
+        stdio: stdio.clone(),
+        capture_stdout,
+        capture_stderr,
+        num_stages,
+        traced,
+    }
+}
+
+impl Running {
+    /// Whether every stage has exited, without waiting.
+    pub fn is_finished(&self) -> bool {
+        self.job.as_ref().is_none_or(|job| job.is_finished())
     }
 
-    /// Get the output file for stage `i` (None for the last stage).
-    pub fn output_for(&self, stage: usize) -> Option<&str> {
-        self.pipe_files.get(stage).map(|s| s.as_str())
+    /// Send `signal` to every stage still running.
+    pub fn kill(&self, signal: i32) {
+        if let Some(job) = &self.job {
+            let _ = job.kill(signal);
+        }
     }
 
-    /// Clean up all pipe temp files.
-    pub fn cleanup(&self) {
-        for f in &self.pipe_files {
-            cleanup_pipe(f);
+    /// Wait for every stage to exit and hand over anything captured,
+    /// returning the exit code of every stage.
+    pub fn finish(self) -> Vec<i32> {
+        let stdio = &self.stdio;
+        let result = match self.job {
+            Some(job) => job.wait(),
+            None => return trace_failed(self.traced, self.num_stages),
+        };
+
+        match result {
+            Ok(finished) => {
+                stdio.stdin.consume(finished.stdin_read);
+                if self.capture_stdout {
+                    let _ = stdio.stdout.write(&finished.stdout);
+                }
+                if self.capture_stderr {
+                    let _ = stdio.stderr.write(&finished.stderr);
+                }
+                for error in &finished.errors {
+                    let _ = stdio.stderr.write(format!("sh: {}\n", error).as_bytes());
+                }
+                for (i, started) in self.traced {
+                    let code = finished.codes.get(i).copied().unwrap_or(126);
+                    let duration = finished.durations.get(i).copied();
+                    started.finish_child(code, duration, finished.fuel.get(i).copied());
+                }
+                finished.codes
+            }
+            Err(e) => {
+                let _ = stdio.stderr.write(format!("sh: {}\n", e).as_bytes());
+                trace_failed(self.traced, self.num_stages)
+            }
+        }
+    }
+}
+
+/// The exit codes of stages that could not be run, reporting them to the
+/// execution trace.
+fn trace_failed(traced: Vec<(usize, audit::Started)>, num_stages: usize) -> Vec<i32> {
+    for (_, started) in traced {
+        started.finish_child(126, None, None);
+    }
+    vec![126; num_stages]
+}
+
+/// Whether an expanded command is a tool rather than something the shell
+/// runs itself.
+pub fn is_external(words: &[String], env: &ShellEnv) -> bool {
+    match words.first() {
+        Some(name) => {
+            !builtins::is_builtin(name)
+                && !env.functions.contains_key(name)
+                && !env.aliases.contains_key(name)
         }
+        None => false,
     }
 }
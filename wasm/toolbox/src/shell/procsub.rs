@@ -0,0 +1,106 @@
+//! Process substitution: `<(cmd)` and `>(cmd)`.
+//!
+//! The command runs in a subshell connected to an in-memory pipe, and the
+//! other end of the pipe is exposed as a file under `/dev/fd`, a directory
+//! the host sets aside for each run. `<(cmd)` runs the command first and
+//! fills the file with its output; `>(cmd)` creates an empty file and, once
+//! the pipeline using it ends, runs the command on what was written to it.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+
+use super::env::ShellEnv;
+use super::exec::{self, DispatchFn};
+use super::io::{Input, Output, Stdio};
+use super::traps;
+
+/// The directory holding the files that stand for process substitutions.
+const PIPE_DIR: &str = "/dev/fd";
+
+/// The descriptor number the first process substitution's file is named
+/// after; later ones count down from it, as in bash.
+const FIRST_FD: u32 = 63;
+
+/// A process substitution whose file is still in use.
+#[derive(Debug, Clone)]
+pub struct ProcessSubstitution {
+    path: String,
+    /// For `>(cmd)`, the command to run on what was written to the file,
+    /// and the streams it runs with
+    reader: Option<(String, Stdio)>,
+}
+
+/// Expand `<(command)`, or `>(command)` if `output`, to the path of the
+/// file standing for it.
+pub fn substitute(command: &str, output: bool, env: &mut ShellEnv, dispatch: DispatchFn) -> String {
+    let Some((path, mut file)) = create_file() else {
+        env.eprint("sh: cannot make pipe for process substitution\n");
+        env.expansion_error = true;
+        return String::new();
+    };
+
+    let reader = if output {
+        Some((command.to_string(), env.io.clone()))
+    } else {
+        let pipe = Output::buffer();
+        let io = Stdio {
+            stdout: pipe.clone(),
+            ..env.io.clone()
+        };
+        run(command, io, env, dispatch);
+        if let Err(e) = file.write_all(&pipe.take()) {
+            env.eprint(&format!("sh: {}: {}\n", path, e));
+        }
+        None
+    };
+
+    env.process_substitutions.push(ProcessSubstitution {
+        path: path.clone(),
+        reader,
+    });
+    path
+}
+
+/// Finish the process substitutions made since there were `from` of them:
+/// run the commands of `>(cmd)` on what was written for them, and remove
+/// every file.
+pub fn finish(from: usize, env: &mut ShellEnv, dispatch: DispatchFn) {
+    if env.process_substitutions.len() <= from {
+        return;
+    }
+    for substitution in env.process_substitutions.split_off(from) {
+        if let Some((command, io)) = substitution.reader {
+            let data = fs::read(&substitution.path).unwrap_or_default();
+            let io = Stdio {
+                stdin: Input::data(data),
+                ..io
+            };
+            run(&command, io, env, dispatch);
+        }
+        let _ = fs::remove_file(&substitution.path);
+    }
+}
+
+/// Create the file for a new process substitution, named after the highest
+/// descriptor number not in use.
+fn create_file() -> Option<(String, File)> {
+    (3..=FIRST_FD).rev().find_map(|fd| {
+        let path = format!("{}/{}", PIPE_DIR, fd);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .ok()?;
+        Some((path, file))
+    })
+}
+
+/// Run `command` in a subshell with the given streams.
+fn run(command: &str, io: Stdio, env: &ShellEnv, dispatch: DispatchFn) {
+    let mut sub_env = env.clone();
+    sub_env.io = io;
+    traps::reset_for_subshell(&mut sub_env);
+    let result = exec::exec_script(command, &mut sub_env, dispatch);
+    traps::run_exit_trap(&mut sub_env, dispatch, result.exit_code);
+    sub_env.jobs.wait_all();
+}
@@ -1,92 +1,82 @@
-use std::fs::{File, OpenOptions};
-use std::io;
+use std::fs::{self, File, OpenOptions};
 
 use super::ast::{Redirect, RedirectKind};
+use super::io::{Input, Output, Stdio};
 
-/// Saved file descriptors for restoring after a command.
-pub struct SavedFds {
-    saves: Vec<SavedFd>,
-}
-
-struct SavedFd {
-    original_fd: i32,
-    saved_file: Option<File>,
-    was_stdout: bool,
-    was_stderr: bool,
-}
-
-/// State of stdout/stderr capture.
-pub struct RedirectState {
-    pub original_stdout: Option<File>,
-    pub original_stderr: Option<File>,
-    pub stdout_file: Option<String>,
-    pub stderr_file: Option<String>,
-    pub stdin_content: Option<String>,
-}
-
-impl RedirectState {
-    pub fn new() -> Self {
-        RedirectState {
-            original_stdout: None,
-            original_stderr: None,
-            stdout_file: None,
-            stderr_file: None,
-            stdin_content: None,
-        }
-    }
-}
-
-/// Apply redirections for a command. Returns paths to temp files for pipe-like behavior.
-/// In WASM, we can't actually redirect file descriptors, so we use temp files.
+/// Apply a command's redirections on top of the streams it would otherwise
+/// use, returning the streams it runs with.
+///
+/// Redirections apply left to right, so `>out 2>&1` sends both streams to
+/// `out` while `2>&1 >out` sends stderr to the original stdout. Fails with
+/// `target: error` when a file cannot be opened.
 pub fn apply_redirections(
     redirections: &[Redirect],
     expanded_targets: &[String],
-) -> io::Result<RedirectState> {
-    let mut state = RedirectState::new();
+    base: &Stdio,
+) -> Result<Stdio, String> {
+    let mut stdio = base.clone();
 
     for (i, redir) in redirections.iter().enumerate() {
         let target = expanded_targets.get(i).map(|s| s.as_str()).unwrap_or("");
         let fd = redir.fd.unwrap_or(match &redir.kind {
-            RedirectKind::Input | RedirectKind::HereDoc(_) | RedirectKind::HereString | RedirectKind::DupInput => 0,
+            RedirectKind::Input
+            | RedirectKind::HereDoc
+            | RedirectKind::HereString
+            | RedirectKind::DupInput => 0,
             _ => 1,
         });
 
+        let failed = |e: std::io::Error| format!("{}: {}", target, e);
+
         match &redir.kind {
-            RedirectKind::Output => {
-                if fd == 1 {
-                    state.stdout_file = Some(target.to_string());
-                } else if fd == 2 {
-                    state.stderr_file = Some(target.to_string());
-                }
-                // Create/truncate the file
-                File::create(target)?;
+            RedirectKind::Output | RedirectKind::Append => {
+                let append = matches!(redir.kind, RedirectKind::Append);
+                let output = open_output(target, append).map_err(failed)?;
+                set_output(&mut stdio, fd, output);
             }
-            RedirectKind::Append => {
-                if fd == 1 {
-                    state.stdout_file = Some(target.to_string());
-                } else if fd == 2 {
-                    state.stderr_file = Some(target.to_string());
-                }
-                // Touch the file (append mode will be handled by the writer)
-                OpenOptions::new().create(true).append(true).open(target)?;
+            RedirectKind::OutputAll | RedirectKind::AppendAll => {
+                // &>file: both streams share one open file, as with >file 2>&1
+                let append = matches!(redir.kind, RedirectKind::AppendAll);
+                let output = open_output(target, append).map_err(failed)?;
+                stdio.stdout = output.clone();
+                stdio.stderr = output;
             }
             RedirectKind::Input => {
-                let content = std::fs::read_to_string(target)?;
-                state.stdin_content = Some(content);
+                let content = if target == "/dev/null" {
+                    Vec::new()
+                } else {
+                    fs::read(target).map_err(failed)?
+                };
+                set_input(&mut stdio, fd, Input::data(content));
             }
-            RedirectKind::HereDoc(content) => {
-                state.stdin_content = Some(content.clone());
+            RedirectKind::HereDoc => {
+                // The target is the body, already expanded unless quoted
+                set_input(&mut stdio, fd, Input::data(target.as_bytes().to_vec()));
             }
             RedirectKind::HereString => {
-                state.stdin_content = Some(format!("{}\n", target));
+                set_input(
+                    &mut stdio,
+                    fd,
+                    Input::data(format!("{}\n", target).into_bytes()),
+                );
             }
             RedirectKind::DupOutput => {
-                // 2>&1 — redirect stderr to stdout's target
-                if fd == 2 && target == "1" {
-                    state.stderr_file = state.stdout_file.clone();
-                } else if fd == 1 && target == "2" {
-                    state.stdout_file = state.stderr_file.clone();
-                }
+                // 2>&1 and 1>&2 — point one stream at the other's current target
+                let output = match target {
+                    "1" => stdio.stdout.clone(),
+                    "2" => stdio.stderr.clone(),
+                    // >&- closes the stream; nothing written to it is kept
+                    "-" => Output::Null,
+                    // >&file is the same as &>file
+                    _ if redir.fd.is_none() && target.parse::<i32>().is_err() => {
+                        let output = open_output(target, false).map_err(failed)?;
+                        stdio.stdout = output.clone();
+                        stdio.stderr = output;
+                        continue;
+                    }
+                    _ => continue,
+                };
+                set_output(&mut stdio, fd, output);
             }
             RedirectKind::DupInput => {
                 // Not commonly used, ignore
@@ -94,5 +84,32 @@ pub fn apply_redirections(
         }
     }
 
-    Ok(state)
+    Ok(stdio)
+}
+
+/// Open a redirection's target for writing, truncating it unless `append`.
+fn open_output(target: &str, append: bool) -> std::io::Result<Output> {
+    if target == "/dev/null" {
+        return Ok(Output::Null);
+    }
+    let file = if append {
+        OpenOptions::new().create(true).append(true).open(target)?
+    } else {
+        File::create(target)?
+    };
+    Ok(Output::file(file))
+}
+
+fn set_output(stdio: &mut Stdio, fd: i32, output: Output) {
+    match fd {
+        1 => stdio.stdout = output,
+        2 => stdio.stderr = output,
+        _ => {}
+    }
+}
+
+fn set_input(stdio: &mut Stdio, fd: i32, input: Input) {
+    if fd == 0 {
+        stdio.stdin = input;
+    }
 }
@@ -0,0 +1,74 @@
+//! Subshells run as child instances.
+//!
+//! A pipeline stage or background job that needs the shell itself, such as
+//! a loop, function or builtin, runs in a fresh instance of the shell so it
+//! goes on alongside the rest of the pipeline or the shell that started it.
+//! The child is started as a bare `sh`, and the host hands it the state it
+//! runs, the environment and what to run in it serialized as JSON, in
+//! [`ENV`]. Only the host sets that variable, so a script cannot start a
+//! subshell with a state of its own.
+
+use serde::{Deserialize, Serialize};
+
+use super::ast::{Command, SimpleCommand};
+use super::env::ShellEnv;
+use super::exec::{self, DispatchFn};
+use super::traps;
+
+/// The variable the host hands a subshell its state in.
+pub const ENV: &str = "TOOLBOX_SUBSHELL";
+
+/// What a subshell runs.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Body {
+    Command(Command),
+    /// A simple command the parent already got ready with
+    /// `exec::prepare_simple`, and its argv. Its assignments are made and it
+    /// has been traced, so the child only runs it.
+    Prepared(SimpleCommand, Vec<String>),
+    /// Nothing to run; only exit with this status.
+    Exit(i32),
+}
+
+/// The state of a subshell of `env` running `command`.
+pub fn state(command: &Command, env: &ShellEnv) -> Result<String, String> {
+    start(&Body::Command(command.clone()), env)
+}
+
+/// The state of a subshell running a simple command that
+/// `exec::prepare_simple` got ready in `env`, with the argv it returned.
+pub fn prepared(cmd: &SimpleCommand, words: Vec<String>, env: &ShellEnv) -> Result<String, String> {
+    start(&Body::Prepared(cmd.clone(), words), env)
+}
+
+/// The state of a subshell that only exits with `code`, for a stage that
+/// finished, or failed for an error already reported, before it could
+/// start.
+pub fn exit(code: i32) -> String {
+    start(&Body::Exit(code), &ShellEnv::new()).expect("a new environment always serializes")
+}
+
+fn start(body: &Body, env: &ShellEnv) -> Result<String, String> {
+    serde_json::to_string(&(env, body)).map_err(|e| e.to_string())
+}
+
+/// Run the subshell described by `state`, returning its exit status.
+pub fn run(state: &str, dispatch: DispatchFn) -> i32 {
+    let (mut env, body): (ShellEnv, Body) = match serde_json::from_str(state) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("sh: invalid subshell state: {}", e);
+            return 2;
+        }
+    };
+    traps::reset_for_subshell(&mut env);
+    let result = match &body {
+        Body::Command(command) => exec::exec_command(command, &mut env, dispatch),
+        Body::Prepared(cmd, words) => exec::exec_prepared(cmd, words, &mut env, dispatch),
+        Body::Exit(code) => return *code,
+    };
+    let code = traps::run_exit_trap(&mut env, dispatch, result.exit_code);
+    env.jobs.wait_all();
+    code
+}
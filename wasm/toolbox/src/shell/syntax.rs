@@ -0,0 +1,109 @@
+//! Parsing a script without running it: the syntax tree `Sandbox::parse_shell`
+//! asks for with `sh --sandbox-ast`. Subshells started as child instances
+//! are handed their command in the same form.
+
+use serde::Deserialize;
+use serde::de::Deserializer;
+use serde::ser::{Error, SerializeStruct, Serializer};
+use serde_json::json;
+
+use super::ast::Program;
+use super::parser::{self, Parser};
+use crate::host;
+
+/// Parse `script` and report its syntax tree to the host, or the syntax
+/// error it fails with. Returns the exit status `sh -n` would.
+pub fn report_ast(script: &str) -> i32 {
+    // Substitutions are parsed as the tree is serialized, so their syntax
+    // errors surface here too
+    let tree = Parser::new(script)
+        .parse_program()
+        .and_then(|program| serde_json::to_value(program).map_err(|e| e.to_string()));
+    let (report, code) = match tree {
+        Ok(program) => (json!({ "ok": program }), 0),
+        Err(e) => (json!({ "error": e }), 2),
+    };
+    host::report(&report.to_string());
+    code
+}
+
+/// A command or process substitution: its script, and the program it
+/// parses to so the commands it runs can be reviewed like any other. A
+/// script that does not parse fails the whole tree, so no command can hide
+/// inside it.
+fn substitution<S: Serializer>(
+    script: &str,
+    output: Option<bool>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let program: Program = Parser::new(script).parse_program().map_err(S::Error::custom)?;
+    let fields = if output.is_some() { 3 } else { 2 };
+    let mut state = serializer.serialize_struct("Substitution", fields)?;
+    state.serialize_field("script", script)?;
+    state.serialize_field("program", &program)?;
+    if let Some(output) = output {
+        state.serialize_field("output", &output)?;
+    }
+    state.end()
+}
+
+/// Serialize `$(cmd)` for `WordPart::CommandSub`.
+pub fn command_sub<S: Serializer>(script: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    substitution(script, None, serializer)
+}
+
+/// Serialize `<(cmd)` or `>(cmd)` for `WordPart::ProcessSub`, where
+/// `output` is true for `>(cmd)`.
+pub fn process_sub<S: Serializer>(
+    script: &str,
+    output: &bool,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    substitution(script, Some(*output), serializer)
+}
+
+/// Serialize the expression of `$(( ))`, `(( ))` or `for (( ))`: its text,
+/// and the word it expands as before it is evaluated, so the command
+/// substitutions inside it can be reviewed like any other.
+pub fn arithmetic<S: Serializer>(expression: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("Arithmetic", 2)?;
+    state.serialize_field("expression", expression)?;
+    state.serialize_field("word", &parser::parse_word_from_str(expression))?;
+    state.end()
+}
+
+/// An arithmetic expression read back from its serialized form, which only
+/// needs the text: it is expanded again when it is evaluated.
+#[derive(Deserialize)]
+struct Arithmetic {
+    expression: String,
+}
+
+/// Deserialize the expression of `$(( ))`, `(( ))` or `for (( ))`.
+pub fn arithmetic_expression<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<String, D::Error> {
+    Ok(Arithmetic::deserialize(deserializer)?.expression)
+}
+
+/// A substitution read back from its serialized form, which only needs the
+/// script: the program is parsed again when the substitution runs.
+#[derive(Deserialize)]
+struct Substitution {
+    script: String,
+    #[serde(default)]
+    output: bool,
+}
+
+/// Deserialize `$(cmd)` for `WordPart::CommandSub`.
+pub fn command_sub_script<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Substitution::deserialize(deserializer)?.script)
+}
+
+/// Deserialize `<(cmd)` or `>(cmd)` for `WordPart::ProcessSub`.
+pub fn process_sub_script<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<(String, bool), D::Error> {
+    let substitution = Substitution::deserialize(deserializer)?;
+    Ok((substitution.script, substitution.output))
+}
@@ -7,10 +7,12 @@ pub enum Token {
     And,                    // &&
     Or,                     // ||
     Semi,                   // ;
+    DSemi,                  // ;; (ends a case arm)
     Newline,                // \n
     Amp,                    // &
     LParen,                 // (
     RParen,                 // )
+    DParen(String),         // (( expr ))
 
     // Redirections
     Less,                   // <
@@ -21,6 +23,8 @@ pub enum Token {
     DLess,                  // << (here-doc)
     DLessDash,              // <<- (here-doc strip tabs)
     TLess,                  // <<< (here-string)
+    AndGreat,               // &> (stdout and stderr)
+    AndDGreat,              // &>> (append stdout and stderr)
 
     // Numbered redirections
     IoNumber(i32),          // A number before < or >
@@ -72,6 +76,23 @@ impl Token {
         )
     }
 
+    /// Checks if this token is a redirection operator.
+    pub fn is_redirect(&self) -> bool {
+        matches!(
+            self,
+            Token::Less
+                | Token::Great
+                | Token::DGreat
+                | Token::GreatAnd
+                | Token::LessAnd
+                | Token::DLess
+                | Token::DLessDash
+                | Token::TLess
+                | Token::AndGreat
+                | Token::AndDGreat
+        )
+    }
+
     /// Checks if this token can start a command.
     pub fn is_command_start(&self) -> bool {
         matches!(
@@ -83,6 +104,7 @@ impl Token {
                 | Token::Until
                 | Token::Case
                 | Token::LParen
+                | Token::DParen(_)
                 | Token::LBrace
                 | Token::Function
                 | Token::Bang
@@ -0,0 +1,185 @@
+//! `trap` and the signals the host sends the shell.
+//!
+//! Besides real signals, traps can be set on the pseudo-signals EXIT (the
+//! shell or subshell is exiting), ERR (a command failed where `set -e`
+//! would exit) and RETURN (a function or sourced script finished). The
+//! host simulates INT when the caller stops waiting for a command and TERM
+//! when it times out; the shell picks them up between commands.
+
+use std::sync::Mutex;
+
+use super::builtins::BuiltinResult;
+use super::env::ShellEnv;
+use super::exec::{self, DispatchFn, ExecResult};
+use super::jobs;
+use crate::signal;
+
+/// Traps that are not real signals.
+const PSEUDO_SIGNALS: &[&str] = &["EXIT", "ERR", "RETURN"];
+
+/// Every signal received from the host, in order. Kept for the whole
+/// process so subshells and the shell that started them both see them.
+static RECEIVED: Mutex<Vec<i32>> = Mutex::new(Vec::new());
+
+/// Handle any signals received since `env` last looked. Returns the result
+/// to stop with if a signal ends the shell.
+pub fn check_signals(env: &mut ShellEnv, dispatch: DispatchFn) -> Option<ExecResult> {
+    let mut received = RECEIVED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(signal) = signal::take() {
+        received.push(signal);
+    }
+    let pending: Vec<i32> = received.get(env.signals_handled..)?.to_vec();
+    drop(received);
+
+    for signal in pending {
+        env.signals_handled += 1;
+        let name = signal_name(signal);
+        if !env.traps.contains_key(&name) {
+            // The default action ends the shell
+            return Some(ExecResult::exit(128 + signal));
+        }
+        if let Some(result) = run_trap(&name, env, dispatch) {
+            return Some(result);
+        }
+    }
+    None
+}
+
+/// Run the trap set for `name`, if any, keeping `$?` across it. Returns
+/// the result to stop with if the trap exits the shell.
+pub fn run_trap(name: &str, env: &mut ShellEnv, dispatch: DispatchFn) -> Option<ExecResult> {
+    if env.traps.get(name).is_none_or(|action| action.is_empty()) {
+        return None;
+    }
+
+    // A trap does not fire again while it runs
+    let action = env.traps.remove(name)?;
+    let status = env.last_status;
+    let result = exec::exec_script(&action, env, dispatch);
+    env.traps.entry(name.to_string()).or_insert(action);
+
+    if result.should_exit {
+        return Some(result);
+    }
+    env.last_status = status;
+    None
+}
+
+/// Run the EXIT trap, once, for a shell exiting with `status`. Returns the
+/// status to exit with, which the trap can change by calling `exit`.
+pub fn run_exit_trap(env: &mut ShellEnv, dispatch: DispatchFn, status: i32) -> i32 {
+    let Some(action) = env.traps.remove("EXIT") else {
+        return status;
+    };
+    env.last_status = status;
+    match exec::exec_script(&action, env, dispatch) {
+        result if result.should_exit => result.exit_code,
+        _ => status,
+    }
+}
+
+/// Drop the traps a subshell does not inherit: all but ignored signals.
+pub fn reset_for_subshell(env: &mut ShellEnv) {
+    env.traps.retain(|_, action| action.is_empty());
+}
+
+/// The name a trap is stored under: `EXIT` for 0, or the signal's name
+/// without `SIG`.
+fn signal_name(signal: i32) -> String {
+    if signal == 0 {
+        return "EXIT".to_string();
+    }
+    jobs::SIGNALS
+        .iter()
+        .find(|(_, number, _)| *number == signal)
+        .map(|(name, _, _)| name.to_string())
+        .unwrap_or_else(|| signal.to_string())
+}
+
+/// Look up a trap's signal by name or number.
+fn parse_spec(spec: &str) -> Option<String> {
+    let upper = spec.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    if PSEUDO_SIGNALS.contains(&name) {
+        return Some(name.to_string());
+    }
+    jobs::parse_signal(spec).map(signal_name)
+}
+
+/// How `trap -p` shows a signal.
+fn display_name(name: &str) -> String {
+    if PSEUDO_SIGNALS.contains(&name) {
+        name.to_string()
+    } else {
+        format!("SIG{}", name)
+    }
+}
+
+pub fn builtin_trap(args: &[String], env: &mut ShellEnv) -> BuiltinResult {
+    let args = match args.first().map(String::as_str) {
+        Some("-l") => return jobs::list_signals(&[], env),
+        Some("-p") => return print_traps(&args[1..], env),
+        Some("--") => &args[1..],
+        _ => args,
+    };
+    if args.is_empty() {
+        return print_traps(&[], env);
+    }
+
+    // A lone signal, or `-` as the action, restores the default
+    let (action, specs) = match args {
+        [spec] if parse_spec(spec).is_some() => (None, args),
+        [action, specs @ ..] if action == "-" => (None, specs),
+        [action, specs @ ..] => (Some(action), specs),
+        [] => unreachable!(),
+    };
+
+    let mut status = 0;
+    for spec in specs {
+        match parse_spec(spec) {
+            Some(name) => match action {
+                Some(action) => {
+                    env.traps.insert(name, action.clone());
+                }
+                None => {
+                    env.traps.remove(&name);
+                }
+            },
+            None => {
+                env.eprint(&format!("trap: {}: invalid signal specification\n", spec));
+                status = 1;
+            }
+        }
+    }
+    BuiltinResult::code(status)
+}
+
+/// Print traps as commands that would set them again.
+fn print_traps(specs: &[String], env: &ShellEnv) -> BuiltinResult {
+    let mut names: Vec<String> = Vec::new();
+    let mut status = 0;
+    if specs.is_empty() {
+        names.extend(env.traps.keys().cloned());
+        names.sort();
+    }
+    for spec in specs {
+        match parse_spec(spec) {
+            Some(name) => names.push(name),
+            None => {
+                env.eprint(&format!("trap: {}: invalid signal specification\n", spec));
+                status = 1;
+            }
+        }
+    }
+
+    for name in names {
+        if let Some(action) = env.traps.get(&name) {
+            env.print(&format!(
+                "trap -- '{}' {}\n",
+                action.replace('\'', "'\\''"),
+                display_name(&name)
+            ));
+        }
+    }
+    BuiltinResult::code(status)
+}
@@ -0,0 +1,16 @@
+// Host-provided function for signals sent to this instance.
+// It is linked from the "sandbox" module by the Wasmtime host.
+#[link(wasm_import_module = "sandbox")]
+unsafe extern "C" {
+    fn __sandbox_take_signal() -> i32;
+}
+
+/// Take the signal the host sent this instance, if one is waiting: SIGINT
+/// when the caller stopped waiting for the command, or SIGTERM when it ran
+/// out of time.
+pub fn take() -> Option<i32> {
+    match unsafe { __sandbox_take_signal() } {
+        0 => None,
+        signal => Some(signal),
+    }
+}
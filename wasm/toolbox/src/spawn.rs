@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::{Value, json};
+
+// Host-provided functions for running commands as child instances.
+// These are linked from the "sandbox" module by the Wasmtime host.
+#[link(wasm_import_module = "sandbox")]
+unsafe extern "C" {
+    fn __sandbox_spawn(req_ptr: i32, req_len: i32) -> i32;
+    fn __sandbox_wait(job: i32) -> i32;
+    fn __sandbox_poll(job: i32) -> i32;
+    fn __sandbox_kill(job: i32, signal: i32) -> i32;
+    fn __sandbox_spawn_response_len() -> i32;
+    fn __sandbox_spawn_response_read(buf_ptr: i32, buf_len: i32) -> i32;
+}
+
+/// A pipeline of toolbox commands to run as child instances.
+///
+/// The host runs every stage concurrently, connecting neighbouring stages
+/// with bounded in-memory pipes.
+pub struct Pipeline {
+    /// The argv of every stage, first to last.
+    pub stages: Vec<Vec<String>>,
+    /// The state of every stage that runs a subshell, by position.
+    pub subshells: Vec<Option<String>>,
+    /// Environment of every stage.
+    pub env: Vec<(String, String)>,
+    /// Working directory of every stage.
+    pub cwd: Option<String>,
+    /// Stdin of the first stage; `None` shares this process's stdin.
+    pub stdin: Option<Vec<u8>>,
+    /// Capture the last stage's stdout instead of sharing this process's.
+    pub capture_stdout: bool,
+    /// Capture every stage's stderr instead of sharing this process's.
+    pub capture_stderr: bool,
+    /// Throw away the last stage's stdout, as for `> /dev/null`.
+    pub discard_stdout: bool,
+    /// Throw away every stage's stderr.
+    pub discard_stderr: bool,
+    /// Send every stage's stderr to this process's stdout, as for `2>&1`.
+    pub stderr_to_stdout: bool,
+}
+
+/// A started pipeline.
+#[derive(Debug)]
+pub struct Job(i32);
+
+/// A finished pipeline.
+pub struct Finished {
+    /// Exit code of every stage, first to last.
+    pub codes: Vec<i32>,
+    /// Captured stdout (empty unless requested).
+    pub stdout: Vec<u8>,
+    /// Captured stderr (empty unless requested).
+    pub stderr: Vec<u8>,
+    /// Stages that failed to run to completion, as `command: error`.
+    pub errors: Vec<String>,
+    /// How many bytes of the given stdin the first stage read.
+    pub stdin_read: usize,
+    /// Fuel every stage consumed.
+    pub fuel: Vec<u64>,
+    /// How long every stage ran for.
+    pub durations: Vec<Duration>,
+}
+
+impl Pipeline {
+    /// Start the pipeline without waiting for it.
+    pub fn spawn(&self) -> Result<Job, String> {
+        let req = json!({
+            "stages": self.stages,
+            "subshells": self.subshells,
+            "env": self.env,
+            "cwd": self.cwd,
+            "stdin": self.stdin.as_ref().map(|b| BASE64.encode(b)),
+            "capture_stdout": self.capture_stdout,
+            "capture_stderr": self.capture_stderr,
+            "discard_stdout": self.discard_stdout,
+            "discard_stderr": self.discard_stderr,
+            "stderr_to_stdout": self.stderr_to_stdout,
+        });
+        let req_bytes = serde_json::to_vec(&req).map_err(|e| format!("serialize error: {e}"))?;
+
+        let job = unsafe { __sandbox_spawn(req_bytes.as_ptr() as i32, req_bytes.len() as i32) };
+        match job {
+            -2 => Err(read_response()?["error"]
+                .as_str()
+                .unwrap_or("spawn failed")
+                .to_string()),
+            job if job <= 0 => Err("spawn bridge error: failed to communicate with host".into()),
+            job => Ok(Job(job)),
+        }
+    }
+
+    /// Run the pipeline to completion.
+    pub fn run(&self) -> Result<Finished, String> {
+        self.spawn()?.wait()
+    }
+}
+
+impl Job {
+    /// Whether every stage has exited, without waiting.
+    pub fn is_finished(&self) -> bool {
+        unsafe { __sandbox_poll(self.0) != 0 }
+    }
+
+    /// Send `signal` to every stage still running; they exit with
+    /// `128 + signal`.
+    pub fn kill(&self, signal: i32) -> Result<(), String> {
+        if unsafe { __sandbox_kill(self.0, signal) } < 0 {
+            return Err("spawn bridge error: unknown job".into());
+        }
+        Ok(())
+    }
+
+    /// Block until every stage has exited.
+    pub fn wait(self) -> Result<Finished, String> {
+        if unsafe { __sandbox_wait(self.0) } < 0 {
+            return Err("spawn bridge error: unknown job".into());
+        }
+        let resp = read_response()?;
+        if let Some(err) = resp.get("error").and_then(|v| v.as_str()) {
+            return Err(err.to_string());
+        }
+
+        let decode = |field: &str| match resp.get(field).and_then(|v| v.as_str()) {
+            Some(encoded) => BASE64
+                .decode(encoded)
+                .map_err(|e| format!("deserialize error: {e}")),
+            None => Ok(Vec::new()),
+        };
+        let codes = resp
+            .get("codes")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let errors = resp
+            .get("errors")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let fuel = resp
+            .get("fuel")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let durations = resp
+            .get("durations_us")
+            .and_then(|v| serde_json::from_value::<Vec<u64>>(v.clone()).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(Duration::from_micros)
+            .collect();
+
+        Ok(Finished {
+            codes,
+            stdout: decode("stdout")?,
+            stderr: decode("stderr")?,
+            errors,
+            stdin_read: resp.get("stdin_read").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+            fuel,
+            durations,
+        })
+    }
+}
+
+/// Read and parse the JSON response to the last spawn or wait.
+fn read_response() -> Result<Value, String> {
+    let resp_len = unsafe { __sandbox_spawn_response_len() };
+    if resp_len <= 0 {
+        return Err("spawn bridge error: empty response".into());
+    }
+    let mut resp_buf = vec![0u8; resp_len as usize];
+    let read = unsafe { __sandbox_spawn_response_read(resp_buf.as_mut_ptr() as i32, resp_len) };
+    if read < 0 {
+        return Err("spawn bridge error: failed to read response".into());
+    }
+    resp_buf.truncate(read as usize);
+    serde_json::from_slice(&resp_buf).map_err(|e| format!("deserialize error: {e}"))
+}
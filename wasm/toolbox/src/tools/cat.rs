@@ -6,6 +6,7 @@ pub fn run(args: &[String]) -> i32 {
     let mut number_nonblank = false;
     let mut files = Vec::new();
 
+    let (args, operands) = super::split_at_operands(args);
     let mut i = 0;
     while i < args.len() {
         match args[i].as_str() {
@@ -19,6 +20,7 @@ pub fn run(args: &[String]) -> i32 {
         }
         i += 1;
     }
+    files.extend_from_slice(operands);
 
     // If no files specified, read from stdin
     if files.is_empty() {
@@ -53,6 +55,10 @@ pub fn run(args: &[String]) -> i32 {
         };
 
         if let Err(e) = result {
+            // The reader has gone away (`cat | head -1`), stop quietly
+            if super::is_closed_pipe(&e) {
+                return 141;
+            }
             eprintln!("cat: {}", e);
             exit_code = 1;
         }
@@ -62,7 +68,7 @@ pub fn run(args: &[String]) -> i32 {
 }
 
 fn process_reader<R: BufRead>(
-    reader: R,
+    mut reader: R,
     line_number: &mut usize,
     number_lines: bool,
     number_nonblank: bool,
@@ -70,6 +76,12 @@ fn process_reader<R: BufRead>(
     let stdout = io::stdout();
     let mut handle = stdout.lock();
 
+    // Without numbering, copy bytes through unchanged so binary data survives
+    if !number_lines && !number_nonblank {
+        io::copy(&mut reader, &mut handle)?;
+        return handle.flush();
+    }
+
     for line in reader.lines() {
         let line = line?;
 
@@ -83,8 +95,6 @@ fn process_reader<R: BufRead>(
                 writeln!(handle, "{:6}\t{}", line_number, line)?;
                 *line_number += 1;
             }
-        } else {
-            writeln!(handle, "{}", line)?;
         }
     }
 
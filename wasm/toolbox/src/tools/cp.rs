@@ -6,6 +6,7 @@ pub fn run(args: &[String]) -> i32 {
     let mut recursive = false;
     let mut paths = Vec::new();
 
+    let (args, operands) = super::split_at_operands(args);
     for arg in args {
         match arg.as_str() {
             "-r" | "-R" => recursive = true,
@@ -16,6 +17,7 @@ pub fn run(args: &[String]) -> i32 {
             _ => paths.push(arg.clone()),
         }
     }
+    paths.extend_from_slice(operands);
 
     if paths.len() < 2 {
         eprintln!("cp: missing destination operand");
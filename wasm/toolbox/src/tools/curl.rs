@@ -0,0 +1,770 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::fetch::{self, ErrorKind, FetchError, RawFetchResponse};
+
+/// Options that control how the response is rendered.
+#[derive(Default)]
+struct Options {
+    output_file: Option<String>,
+    dump_header: Option<String>,
+    write_out: Option<String>,
+    include_headers: bool,
+    head: bool,
+    fail: bool,
+    fail_with_body: bool,
+    silent: bool,
+    show_error: bool,
+    verbose: bool,
+}
+
+/// A single `-F` multipart field.
+struct FormPart {
+    name: String,
+    value: Vec<u8>,
+    filename: Option<String>,
+    content_type: Option<String>,
+}
+
+pub fn run(args: &[String]) -> i32 {
+    let mut opts = Options::default();
+    let mut url: Option<String> = None;
+    let mut method: Option<String> = None;
+    let mut headers: HashMap<String, String> = HashMap::new();
+    let mut data: Vec<Vec<u8>> = Vec::new();
+    let mut json_body = false;
+    let mut form: Vec<FormPart> = Vec::new();
+    let mut upload: Option<Vec<u8>> = None;
+    let mut get = false;
+    let mut remote_name = false;
+
+    let args = expand_short_flags(args);
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        let takes_value = option_takes_value(arg);
+        let value = if takes_value {
+            i += 1;
+            match args.get(i) {
+                Some(v) => v.clone(),
+                None => {
+                    eprintln!("curl: option {arg}: requires parameter");
+                    return 2;
+                }
+            }
+        } else {
+            String::new()
+        };
+
+        let result: Result<(), String> = (|| {
+            match arg {
+                "-X" | "--request" => method = Some(value),
+                "-H" | "--header" => {
+                    if let Some((k, v)) = value.split_once(':') {
+                        headers.insert(k.trim().to_string(), v.trim().to_string());
+                    } else if let Some(k) = value.strip_suffix(';') {
+                        headers.insert(k.trim().to_string(), String::new());
+                    }
+                }
+                "-d" | "--data" | "--data-ascii" => {
+                    let mut bytes = read_data_arg(&value)?;
+                    if value.starts_with('@') {
+                        bytes.retain(|b| *b != b'\n' && *b != b'\r');
+                    }
+                    data.push(bytes);
+                }
+                "--data-raw" => data.push(value.into_bytes()),
+                "--data-binary" => data.push(read_data_arg(&value)?),
+                "--data-urlencode" => data.push(urlencode_data(&value)?.into_bytes()),
+                "--json" => {
+                    data.push(read_data_arg(&value)?);
+                    json_body = true;
+                }
+                "-F" | "--form" => form.push(parse_form_part(&value, true)?),
+                "--form-string" => form.push(parse_form_part(&value, false)?),
+                "-T" | "--upload-file" => upload = Some(read_file(&value)?),
+                "-G" | "--get" => get = true,
+                "-o" | "--output" => opts.output_file = Some(value),
+                "-O" | "--remote-name" => remote_name = true,
+                "-D" | "--dump-header" => opts.dump_header = Some(value),
+                "-w" | "--write-out" => {
+                    opts.write_out = Some(match value.strip_prefix('@') {
+                        Some(file) => String::from_utf8_lossy(&read_file(file)?).to_string(),
+                        None => value,
+                    });
+                }
+                "-u" | "--user" => {
+                    let encoded = STANDARD.encode(value.as_bytes());
+                    headers.insert("Authorization".to_string(), format!("Basic {encoded}"));
+                }
+                "-A" | "--user-agent" => {
+                    headers.insert("User-Agent".to_string(), value);
+                }
+                "-e" | "--referer" => {
+                    headers.insert("Referer".to_string(), value);
+                }
+                // Only inline cookie strings are supported; cookie jars are not
+                "-b" | "--cookie" if value.contains('=') => {
+                    headers.insert("Cookie".to_string(), value);
+                }
+                "--url" => url = Some(value),
+                "-I" | "--head" => opts.head = true,
+                "-i" | "--include" => opts.include_headers = true,
+                "-f" | "--fail" => opts.fail = true,
+                "--fail-with-body" => opts.fail_with_body = true,
+                "-s" | "--silent" => opts.silent = true,
+                "-S" | "--show-error" => opts.show_error = true,
+                "-v" | "--verbose" => opts.verbose = true,
+                _ if takes_value => {
+                    // Known value-taking flag we don't act on (timeouts, retries, ...)
+                }
+                arg if (!arg.starts_with('-') || arg == "-") && url.is_none() => {
+                    url = Some(arg.to_string());
+                }
+                _ => {
+                    // Redirects are always followed by the host; other flags are no-ops
+                }
+            }
+            Ok(())
+        })();
+
+        if let Err(e) = result {
+            eprintln!("curl: {e}");
+            return 26;
+        }
+        i += 1;
+    }
+
+    let mut url = match url {
+        Some(u) => u,
+        None => {
+            eprintln!("curl: no URL specified");
+            return 2;
+        }
+    };
+
+    let mut body: Option<Vec<u8>> = None;
+    if !data.is_empty() {
+        let joined = if json_body {
+            data.concat()
+        } else {
+            data.join(&b'&')
+        };
+        if get {
+            url.push(if url.contains('?') { '&' } else { '?' });
+            url.push_str(&String::from_utf8_lossy(&joined));
+        } else {
+            body = Some(joined);
+            if json_body {
+                headers
+                    .entry("Content-Type".to_string())
+                    .or_insert_with(|| "application/json".to_string());
+                headers
+                    .entry("Accept".to_string())
+                    .or_insert_with(|| "application/json".to_string());
+            } else if !headers
+                .keys()
+                .any(|k| k.eq_ignore_ascii_case("content-type"))
+            {
+                headers.insert(
+                    "Content-Type".to_string(),
+                    "application/x-www-form-urlencoded".to_string(),
+                );
+            }
+        }
+    }
+
+    if !form.is_empty() {
+        let boundary = multipart_boundary();
+        body = Some(encode_multipart(&form, &boundary));
+        headers.insert(
+            "Content-Type".to_string(),
+            format!("multipart/form-data; boundary={boundary}"),
+        );
+    }
+
+    let uploading = upload.is_some();
+    if let Some(bytes) = upload {
+        body = Some(bytes);
+    }
+
+    let method = match method {
+        Some(m) => m,
+        None if opts.head => "HEAD".to_string(),
+        None if uploading => "PUT".to_string(),
+        None if body.is_some() => "POST".to_string(),
+        None => "GET".to_string(),
+    };
+
+    if remote_name && opts.output_file.is_none() {
+        match remote_file_name(&url) {
+            Some(name) => opts.output_file = Some(name),
+            None => {
+                eprintln!("curl: Remote file name has no length!");
+                return 23;
+            }
+        }
+    }
+
+    if opts.verbose {
+        let (host, path) = split_url(&url);
+        eprintln!("> {method} {path} HTTP/1.1");
+        eprintln!("> Host: {host}");
+        let mut names: Vec<&String> = headers.keys().collect();
+        names.sort();
+        for name in names {
+            eprintln!("> {}: {}", name, headers[name]);
+        }
+        eprintln!(">");
+    }
+
+    let started = Instant::now();
+    let result = fetch::fetch_bytes(&url, &method, &headers, body.as_deref());
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let upload_size = body.as_ref().map(|b| b.len()).unwrap_or(0);
+    let mut ctx = WriteOut {
+        url: &url,
+        method: &method,
+        upload_size,
+        elapsed,
+        resp: None,
+        exit_code: 0,
+        error: None,
+    };
+
+    let resp = match result {
+        Ok(resp) => resp,
+        Err(e) => {
+            let code = exit_code_for(&e);
+            if !opts.silent || opts.show_error {
+                eprintln!("curl: ({code}) {e}");
+            }
+            ctx.exit_code = code;
+            ctx.error = Some(e.message);
+            if let Some(template) = &opts.write_out {
+                ctx.render(template);
+            }
+            return code;
+        }
+    };
+
+    let header_block = format_headers(&resp);
+    if opts.verbose {
+        for line in header_block.lines() {
+            if !line.is_empty() {
+                eprintln!("< {line}");
+            }
+        }
+        eprintln!("<");
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    if let Some(target) = &opts.dump_header {
+        if target == "-" {
+            let _ = out.write_all(header_block.as_bytes());
+        } else if let Err(e) = write_file(target, header_block.as_bytes()) {
+            eprintln!("curl: (23) {target}: {e}");
+            return 23;
+        }
+    }
+
+    let http_error = resp.status >= 400 && (opts.fail || opts.fail_with_body);
+    let exit_code = if http_error { 22 } else { 0 };
+    if http_error && (!opts.silent || opts.show_error) {
+        eprintln!(
+            "curl: (22) The requested URL returned error: {}",
+            resp.status
+        );
+    }
+
+    if !http_error || opts.fail_with_body {
+        let mut output = Vec::new();
+        if opts.head || opts.include_headers {
+            output.extend_from_slice(header_block.as_bytes());
+        }
+        if !opts.head {
+            output.extend_from_slice(&resp.body);
+        }
+
+        match &opts.output_file {
+            Some(path) => {
+                if let Err(e) = write_file(path, &output) {
+                    eprintln!("curl: (23) {path}: {e}");
+                    return 23;
+                }
+            }
+            None => {
+                let _ = out.write_all(&output);
+            }
+        }
+    }
+    let _ = out.flush();
+    drop(out);
+
+    if let Some(template) = &opts.write_out {
+        ctx.exit_code = exit_code;
+        ctx.resp = Some(&resp);
+        ctx.render(template);
+    }
+
+    exit_code
+}
+
+/// Variables available to `-w` templates.
+struct WriteOut<'a> {
+    url: &'a str,
+    method: &'a str,
+    upload_size: usize,
+    elapsed: f64,
+    resp: Option<&'a RawFetchResponse>,
+    exit_code: i32,
+    error: Option<String>,
+}
+
+impl WriteOut<'_> {
+    fn render(&self, template: &str) {
+        let mut to_stderr = false;
+        let mut buf = String::new();
+        let chars: Vec<char> = template.chars().collect();
+        let mut i = 0;
+
+        let flush = |buf: &mut String, to_stderr: bool| {
+            if to_stderr {
+                let _ = io::stderr().write_all(buf.as_bytes());
+            } else {
+                let _ = io::stdout().write_all(buf.as_bytes());
+            }
+            buf.clear();
+        };
+
+        while i < chars.len() {
+            let rest: String = chars[i..].iter().take(8).collect();
+            match chars[i] {
+                '\\' if i + 1 < chars.len() => {
+                    match chars[i + 1] {
+                        'n' => buf.push('\n'),
+                        'r' => buf.push('\r'),
+                        't' => buf.push('\t'),
+                        '\\' => buf.push('\\'),
+                        c => {
+                            buf.push('\\');
+                            buf.push(c);
+                        }
+                    }
+                    i += 2;
+                }
+                '%' if chars.get(i + 1) == Some(&'%') => {
+                    buf.push('%');
+                    i += 2;
+                }
+                '%' if chars.get(i + 1) == Some(&'{') || rest == "%header{" => {
+                    let start = if rest == "%header{" { i + 8 } else { i + 2 };
+                    match chars[start..].iter().position(|c| *c == '}') {
+                        Some(len) => {
+                            let name: String = chars[start..start + len].iter().collect();
+                            if rest == "%header{" {
+                                if let Some(resp) = self.resp {
+                                    buf.push_str(header_value(resp, &name).unwrap_or(""));
+                                }
+                            } else if name == "stdout" || name == "stderr" {
+                                flush(&mut buf, to_stderr);
+                                to_stderr = name == "stderr";
+                            } else {
+                                buf.push_str(&self.variable(&name));
+                            }
+                            i = start + len + 1;
+                        }
+                        None => {
+                            buf.push('%');
+                            i += 1;
+                        }
+                    }
+                }
+                c => {
+                    buf.push(c);
+                    i += 1;
+                }
+            }
+        }
+
+        flush(&mut buf, to_stderr);
+    }
+
+    fn variable(&self, name: &str) -> String {
+        let resp = self.resp;
+        let status = resp.map(|r| r.status).unwrap_or(0);
+        match name {
+            "http_code" | "response_code" => format!("{status:03}"),
+            "content_type" => resp
+                .and_then(|r| header_value(r, "content-type"))
+                .unwrap_or("")
+                .to_string(),
+            "size_download" => resp.map(|r| r.body.len()).unwrap_or(0).to_string(),
+            "size_header" => resp
+                .map(|r| format_headers(r).len())
+                .unwrap_or(0)
+                .to_string(),
+            "size_upload" | "size_request" => self.upload_size.to_string(),
+            "url" | "url_effective" => self.url.to_string(),
+            "method" => self.method.to_string(),
+            "scheme" => self
+                .url
+                .split_once("://")
+                .map(|(s, _)| s.to_uppercase())
+                .unwrap_or_default(),
+            "num_headers" => resp.map(|r| r.headers.len()).unwrap_or(0).to_string(),
+            "exitcode" => self.exit_code.to_string(),
+            "errormsg" => self.error.clone().unwrap_or_default(),
+            "time_total" | "time_starttransfer" => format!("{:.6}", self.elapsed),
+            "time_namelookup" | "time_connect" | "time_appconnect" | "time_pretransfer"
+            | "time_redirect" => "0.000000".to_string(),
+            "header_json" => {
+                let map: serde_json::Map<String, serde_json::Value> = resp
+                    .map(|r| {
+                        r.headers
+                            .iter()
+                            .map(|(k, v)| (k.to_lowercase(), serde_json::json!([v])))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                serde_json::Value::Object(map).to_string()
+            }
+            "json" => serde_json::json!({
+                "http_code": status,
+                "response_code": status,
+                "content_type": resp.and_then(|r| header_value(r, "content-type")),
+                "size_download": resp.map(|r| r.body.len()).unwrap_or(0),
+                "url_effective": self.url,
+                "method": self.method,
+                "exitcode": self.exit_code,
+                "errormsg": self.error,
+                "time_total": self.elapsed,
+            })
+            .to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+fn parse_form_part(spec: &str, allow_files: bool) -> Result<FormPart, String> {
+    let (name, rest) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("illegally formatted input field: {spec}"))?;
+
+    let mut part = FormPart {
+        name: name.to_string(),
+        value: Vec::new(),
+        filename: None,
+        content_type: None,
+    };
+
+    if !allow_files {
+        part.value = rest.as_bytes().to_vec();
+        return Ok(part);
+    }
+
+    // Split off ;type= and ;filename= attributes
+    let mut segments = rest.split(';');
+    let content = segments.next().unwrap_or("");
+    let mut filename_override = None;
+    for seg in segments {
+        if let Some(t) = seg.strip_prefix("type=") {
+            part.content_type = Some(t.to_string());
+        } else if let Some(f) = seg.strip_prefix("filename=") {
+            filename_override = Some(f.to_string());
+        }
+    }
+
+    if let Some(file) = content.strip_prefix('@') {
+        part.value = read_file(file)?;
+        let base = Path::new(file)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| file.to_string());
+        part.filename = Some(filename_override.unwrap_or(base));
+        if part.content_type.is_none() {
+            part.content_type = Some("application/octet-stream".to_string());
+        }
+    } else if let Some(file) = content.strip_prefix('<') {
+        part.value = read_file(file)?;
+        part.filename = filename_override;
+    } else {
+        part.value = content.as_bytes().to_vec();
+        part.filename = filename_override;
+    }
+
+    Ok(part)
+}
+
+fn encode_multipart(parts: &[FormPart], boundary: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    for part in parts {
+        body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+        let mut disposition = format!("Content-Disposition: form-data; name=\"{}\"", part.name);
+        if let Some(filename) = &part.filename {
+            disposition.push_str(&format!("; filename=\"{filename}\""));
+        }
+        body.extend_from_slice(disposition.as_bytes());
+        body.extend_from_slice(b"\r\n");
+        if let Some(ct) = &part.content_type {
+            body.extend_from_slice(format!("Content-Type: {ct}\r\n").as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+        body.extend_from_slice(&part.value);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    body
+}
+
+fn multipart_boundary() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("------------------------{:016x}", nanos as u64)
+}
+
+/// Read a file argument; `-` reads stdin.
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    if path == "-" {
+        let mut buf = Vec::new();
+        io::stdin()
+            .read_to_end(&mut buf)
+            .map_err(|e| format!("stdin: {e}"))?;
+        return Ok(buf);
+    }
+    fs::read(path)
+        .map_err(|e| format!("Failed to open/read local data from file/application: {path}: {e}"))
+}
+
+/// Read a `-d` style argument: `@file` reads a file, `@-` reads stdin.
+fn read_data_arg(value: &str) -> Result<Vec<u8>, String> {
+    match value.strip_prefix('@') {
+        Some(file) => read_file(file),
+        None => Ok(value.as_bytes().to_vec()),
+    }
+}
+
+/// Encode a `--data-urlencode` argument (`content`, `=content`, `name=content`,
+/// `@file` or `name@file`).
+fn urlencode_data(value: &str) -> Result<String, String> {
+    let (name, encoded) = if let Some((name, content)) = value.split_once('=') {
+        (name, urlencode(content.as_bytes()))
+    } else if let Some((name, file)) = value.split_once('@') {
+        (name, urlencode(&read_file(file)?))
+    } else {
+        ("", urlencode(value.as_bytes()))
+    };
+    Ok(if name.is_empty() {
+        encoded
+    } else {
+        format!("{name}={encoded}")
+    })
+}
+
+fn urlencode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+fn write_file(path: &str, contents: &[u8]) -> io::Result<()> {
+    if path == "-" {
+        return io::stdout().write_all(contents);
+    }
+    if path == "/dev/null" {
+        return Ok(());
+    }
+    fs::write(path, contents)
+}
+
+/// Expand clustered short flags (`-sSL`, `-XPOST`) into separate arguments.
+fn expand_short_flags(args: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut expect_value = false;
+    for arg in args {
+        if expect_value {
+            out.push(arg.clone());
+            expect_value = false;
+            continue;
+        }
+        if arg.starts_with("--") || !arg.starts_with('-') || arg.len() <= 2 {
+            expect_value = option_takes_value(arg);
+            out.push(arg.clone());
+            continue;
+        }
+        let chars: Vec<char> = arg[1..].chars().collect();
+        for (idx, c) in chars.iter().enumerate() {
+            let flag = format!("-{c}");
+            if option_takes_value(&flag) {
+                out.push(flag);
+                let rest: String = chars[idx + 1..].iter().collect();
+                if rest.is_empty() {
+                    expect_value = true;
+                } else {
+                    out.push(rest);
+                }
+                break;
+            }
+            out.push(flag);
+        }
+    }
+    out
+}
+
+fn option_takes_value(arg: &str) -> bool {
+    matches!(
+        arg,
+        "-X" | "--request"
+            | "-H"
+            | "--header"
+            | "-d"
+            | "--data"
+            | "--data-ascii"
+            | "--data-raw"
+            | "--data-binary"
+            | "--data-urlencode"
+            | "--json"
+            | "-F"
+            | "--form"
+            | "--form-string"
+            | "-T"
+            | "--upload-file"
+            | "-o"
+            | "--output"
+            | "-D"
+            | "--dump-header"
+            | "-w"
+            | "--write-out"
+            | "-u"
+            | "--user"
+            | "-A"
+            | "--user-agent"
+            | "-e"
+            | "--referer"
+            | "-b"
+            | "--cookie"
+            | "-c"
+            | "--cookie-jar"
+            | "--url"
+            | "-m"
+            | "--max-time"
+            | "--connect-timeout"
+            | "--retry"
+            | "--retry-delay"
+            | "--retry-max-time"
+            | "--max-redirs"
+            | "--max-filesize"
+            | "-r"
+            | "--range"
+            | "-x"
+            | "--proxy"
+            | "--resolve"
+            | "--cacert"
+            | "-E"
+            | "--cert"
+            | "--key"
+    )
+}
+
+fn header_value<'a>(resp: &'a RawFetchResponse, name: &str) -> Option<&'a str> {
+    resp.headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Format the status line and headers the way `curl -i` prints them.
+pub fn format_headers(resp: &RawFetchResponse) -> String {
+    let mut out = match reason_phrase(resp.status) {
+        "" => format!("HTTP/1.1 {}\r\n", resp.status),
+        reason => format!("HTTP/1.1 {} {}\r\n", resp.status, reason),
+    };
+    let mut names: Vec<&String> = resp.headers.keys().collect();
+    names.sort();
+    for name in names {
+        out.push_str(&format!("{}: {}\r\n", name, resp.headers[name]));
+    }
+    out.push_str("\r\n");
+    out
+}
+
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        410 => "Gone",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+/// Map a host fetch error to the exit code real curl would use.
+fn exit_code_for(err: &FetchError) -> i32 {
+    match err.kind {
+        ErrorKind::InvalidUrl => 3,
+        ErrorKind::Dns => 6,
+        ErrorKind::Blocked => 7,
+        ErrorKind::Timeout => 28,
+        ErrorKind::TooManyRedirects => 47,
+        ErrorKind::ResponseTooLarge => 63,
+        ErrorKind::RequestTooLarge => 25,
+        ErrorKind::Http => 56,
+        ErrorKind::Other => 1,
+    }
+}
+
+fn split_url(url: &str) -> (&str, &str) {
+    let without_scheme = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    match without_scheme.find('/') {
+        Some(pos) => (&without_scheme[..pos], &without_scheme[pos..]),
+        None => (without_scheme, "/"),
+    }
+}
+
+/// Last path segment of a URL, used by `-O` and `wget`.
+pub fn remote_file_name(url: &str) -> Option<String> {
+    let (_, path) = split_url(url);
+    let path = path.split(['?', '#']).next().unwrap_or("");
+    path.rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+}
@@ -1,4 +1,10 @@
 pub fn run(args: &[String]) -> i32 {
+    print!("{}", render(args));
+    0
+}
+
+/// The text `echo` prints for the given arguments.
+pub fn render(args: &[String]) -> String {
     let mut no_newline = false;
     let mut start = 0;
 
@@ -7,12 +13,9 @@ pub fn run(args: &[String]) -> i32 {
         start = 1;
     }
 
-    let output = args[start..].join(" ");
-    if no_newline {
-        print!("{}", output);
-    } else {
-        println!("{}", output);
+    let mut output = args[start..].join(" ");
+    if !no_newline {
+        output.push('\n');
     }
-
-    0
+    output
 }
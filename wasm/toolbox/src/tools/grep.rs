@@ -10,6 +10,7 @@ pub fn run(args: &[String]) -> i32 {
     let mut invert = false;
     let mut recursive = false;
     let mut files_with_matches = false;
+    let mut quiet = false;
     let mut pattern_str: Option<String> = None;
     let mut files: Vec<String> = Vec::new();
 
@@ -22,6 +23,7 @@ pub fn run(args: &[String]) -> i32 {
             "-v" => invert = true,
             "-r" | "-R" => recursive = true,
             "-l" => files_with_matches = true,
+            "-q" | "--quiet" | "--silent" => quiet = true,
             "-e" => {
                 i += 1;
                 if i >= args.len() {
@@ -40,6 +42,7 @@ pub fn run(args: &[String]) -> i32 {
                         'v' => invert = true,
                         'r' | 'R' => recursive = true,
                         'l' => files_with_matches = true,
+                        'q' => quiet = true,
                         _ => {
                             eprintln!("grep: invalid option -- '{}'", ch);
                             return 2;
@@ -117,7 +120,11 @@ pub fn run(args: &[String]) -> i32 {
                 count_only,
                 invert,
                 files_with_matches,
+                quiet,
             );
+            if result && quiet {
+                return 0;
+            }
             if result {
                 found_match = true;
             }
@@ -137,7 +144,11 @@ pub fn run(args: &[String]) -> i32 {
                         count_only,
                         invert,
                         files_with_matches,
+                        quiet,
                     );
+                    if result && quiet {
+                        return 0;
+                    }
                     if result {
                         found_match = true;
                     }
@@ -175,6 +186,7 @@ fn search_reader<R: BufRead>(
     count_only: bool,
     invert: bool,
     files_with_matches: bool,
+    quiet: bool,
 ) -> bool {
     let mut match_count = 0;
 
@@ -190,6 +202,10 @@ fn search_reader<R: BufRead>(
         if matches {
             match_count += 1;
 
+            if quiet {
+                return true;
+            }
+
             if files_with_matches {
                 println!("{}", filename);
                 return true;
@@ -0,0 +1,53 @@
+use std::io::Read;
+
+use serde_json::Value;
+
+use crate::host;
+
+/// `hostcall NAME [JSON]`: call a host function registered by the embedder
+/// and print its JSON result.
+///
+/// The arguments default to `null`; `-` reads them from stdin.
+pub fn run(args: &[String]) -> i32 {
+    let (name, json) = match args {
+        [name] => (name, None),
+        [name, json] => (name, Some(json.as_str())),
+        _ => {
+            eprintln!("usage: hostcall NAME [JSON]");
+            return 2;
+        }
+    };
+
+    let mut input = String::new();
+    let json = match json {
+        Some("-") => {
+            if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+                eprintln!("hostcall: stdin: {e}");
+                return 2;
+            }
+            Some(input.as_str())
+        }
+        json => json,
+    };
+    let call_args = match json {
+        Some(text) => match serde_json::from_str::<Value>(text) {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!("hostcall: invalid JSON arguments: {e}");
+                return 2;
+            }
+        },
+        None => Value::Null,
+    };
+
+    match host::call(name, &call_args) {
+        Ok(value) => {
+            println!("{value}");
+            0
+        }
+        Err(e) => {
+            eprintln!("hostcall: {name}: {e}");
+            1
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Syntax tree the parser produces and the interpreter walks.
+
+use std::rc::Rc;
+
+use super::value::Value;
+
+#[derive(Debug)]
+pub enum Expr {
+    Identity,
+    Literal(Value),
+    /// A string literal, with the `@format` applied to its interpolations.
+    Str(Option<Rc<str>>, Vec<StrPart>),
+    /// `@base64` on its own: formats `.`.
+    Format(Rc<str>),
+    /// `target[key]`; `.foo` is `Index(Identity, "foo")`.
+    Index(Box<Expr>, Box<Expr>),
+    /// `target[from:to]`
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// `target[]`
+    Iterate(Box<Expr>),
+    /// `try body catch handler`; `expr?` has no handler.
+    Try(Box<Expr>, Option<Box<Expr>>),
+    /// `[...]`
+    Array(Option<Box<Expr>>),
+    /// `{key: value, ...}` with shorthands already expanded.
+    Object(Vec<(Expr, Expr)>),
+    Neg(Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    /// `a // b`
+    Alt(Box<Expr>, Box<Expr>),
+    /// `path = value`, `path |= update`, `path += value`, ...
+    Update(UpdateOp, Box<Expr>, Box<Expr>),
+    /// `if a then b elif c then d else e end`; a missing `else` is `.`.
+    If(Vec<(Expr, Expr)>, Option<Box<Expr>>),
+    Reduce {
+        source: Box<Expr>,
+        pattern: Pattern,
+        init: Box<Expr>,
+        update: Box<Expr>,
+    },
+    Foreach {
+        source: Box<Expr>,
+        pattern: Pattern,
+        init: Box<Expr>,
+        update: Box<Expr>,
+        extract: Option<Box<Expr>>,
+    },
+    /// `def name: body; rest`
+    Def(Box<FuncDef>, Box<Expr>),
+    Call(Rc<str>, Vec<Expr>),
+    Var(Rc<str>),
+    /// `source as pattern ?// pattern | body`
+    Bind(Box<Expr>, Vec<Pattern>, Box<Expr>),
+    Label(Rc<str>, Box<Expr>),
+    Break(Rc<str>),
+    /// `$__loc__`, with the line it is on.
+    Loc(usize),
+}
+
+#[derive(Debug)]
+pub enum StrPart {
+    Lit(String),
+    Interp(Expr),
+}
+
+#[derive(Debug)]
+pub struct FuncDef {
+    pub name: Rc<str>,
+    pub params: Vec<Param>,
+    pub body: Expr,
+}
+
+#[derive(Debug)]
+pub struct Param {
+    pub name: Rc<str>,
+    /// `$name`: the argument is evaluated and bound as a variable too.
+    pub is_var: bool,
+}
+
+#[derive(Debug)]
+pub enum Pattern {
+    Var(Rc<str>),
+    Array(Vec<Pattern>),
+    Object(Vec<ObjectPattern>),
+}
+
+/// One `key: pattern` entry; `$name` alone binds the value under `name`.
+#[derive(Debug)]
+pub struct ObjectPattern {
+    pub key: Expr,
+    pub var: Option<Rc<str>>,
+    pub pattern: Option<Pattern>,
+}
+
+impl Pattern {
+    /// Every variable the pattern binds, in order.
+    pub fn variables(&self, names: &mut Vec<Rc<str>>) {
+        match self {
+            Pattern::Var(name) => names.push(name.clone()),
+            Pattern::Array(items) => items.iter().for_each(|p| p.variables(names)),
+            Pattern::Object(entries) => {
+                for entry in entries {
+                    names.extend(entry.var.clone());
+                    if let Some(pattern) = &entry.pattern {
+                        pattern.variables(names);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateOp {
+    /// `=`
+    Assign,
+    /// `|=`
+    Modify,
+    /// `+=`, `-=`, ...
+    Arith(BinOp),
+    /// `//=`
+    Alt,
+}
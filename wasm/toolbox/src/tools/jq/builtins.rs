@@ -0,0 +1,1173 @@
+//! Builtins implemented in Rust: the ones that need the interpreter
+//! (`path`, `limit`, `input`, ...), loops that would recurse too deeply if
+//! written in jq, and plain functions of their input and arguments.
+//! Everything else is defined in jq in `builtins.jq`.
+
+use std::rc::Rc;
+
+use super::ast::Expr;
+use super::interp::{Env, Flow, Interp, Out, Pv, Res, error, fail};
+use super::value::{Map, Value};
+use super::{format, ops, time};
+
+/// Every native builtin, as `(name, arity)`.
+const NATIVES: &[(&str, usize)] = &[
+    ("empty", 0),
+    ("not", 0),
+    ("error", 0),
+    ("error", 1),
+    ("path", 1),
+    ("getpath", 1),
+    ("setpath", 2),
+    ("delpaths", 1),
+    ("recurse", 0),
+    ("recurse", 1),
+    ("range", 1),
+    ("range", 2),
+    ("range", 3),
+    ("limit", 2),
+    ("first", 1),
+    ("until", 2),
+    ("while", 2),
+    ("repeat", 1),
+    ("input", 0),
+    ("inputs", 0),
+    ("debug", 0),
+    ("stderr", 0),
+    ("input_filename", 0),
+    ("input_line_number", 0),
+    ("halt", 0),
+    ("halt_error", 0),
+    ("halt_error", 1),
+    ("sort_by", 1),
+    ("group_by", 1),
+    ("unique_by", 1),
+    ("min_by", 1),
+    ("max_by", 1),
+    ("sub", 2),
+    ("sub", 3),
+    ("builtins", 0),
+    ("_match_impl", 3),
+    ("split", 1),
+    ("split", 2),
+    ("length", 0),
+    ("utf8bytelength", 0),
+    ("keys", 0),
+    ("keys_unsorted", 0),
+    ("has", 1),
+    ("contains", 1),
+    ("type", 0),
+    ("tostring", 0),
+    ("tojson", 0),
+    ("fromjson", 0),
+    ("tonumber", 0),
+    ("ascii_downcase", 0),
+    ("ascii_upcase", 0),
+    ("explode", 0),
+    ("implode", 0),
+    ("ltrimstr", 1),
+    ("rtrimstr", 1),
+    ("startswith", 1),
+    ("endswith", 1),
+    ("trim", 0),
+    ("ltrim", 0),
+    ("rtrim", 0),
+    ("join", 1),
+    ("add", 0),
+    ("any", 0),
+    ("all", 0),
+    ("flatten", 0),
+    ("flatten", 1),
+    ("sort", 0),
+    ("unique", 0),
+    ("min", 0),
+    ("max", 0),
+    ("reverse", 0),
+    ("indices", 1),
+    ("index", 1),
+    ("rindex", 1),
+    ("infinite", 0),
+    ("nan", 0),
+    ("isinfinite", 0),
+    ("isnan", 0),
+    ("isnormal", 0),
+    ("abs", 0),
+    ("toarray", 0),
+    ("format", 1),
+    ("now", 0),
+    ("mktime", 0),
+    ("gmtime", 0),
+    ("localtime", 0),
+    ("strftime", 1),
+    ("strflocaltime", 1),
+    ("strptime", 1),
+    ("have_literal_numbers", 0),
+    ("have_decnum", 0),
+    ("frexp", 0),
+    ("modf", 0),
+];
+
+type Math1 = fn(f64) -> f64;
+type Math2 = fn(f64, f64) -> f64;
+
+/// Math functions of one number.
+const MATH_1: &[(&str, Math1)] = &[
+    ("floor", f64::floor),
+    ("ceil", f64::ceil),
+    ("round", f64::round),
+    ("rint", f64::round_ties_even),
+    ("nearbyint", f64::round_ties_even),
+    ("trunc", f64::trunc),
+    ("fabs", f64::abs),
+    ("sqrt", f64::sqrt),
+    ("cbrt", f64::cbrt),
+    ("exp", f64::exp),
+    ("exp2", f64::exp2),
+    ("exp10", exp10),
+    ("expm1", f64::exp_m1),
+    ("log", f64::ln),
+    ("log2", f64::log2),
+    ("log10", f64::log10),
+    ("log1p", f64::ln_1p),
+    ("logb", logb),
+    ("significand", significand),
+    ("gamma", lgamma),
+    ("lgamma", lgamma),
+    ("tgamma", tgamma),
+    ("sin", f64::sin),
+    ("cos", f64::cos),
+    ("tan", f64::tan),
+    ("asin", f64::asin),
+    ("acos", f64::acos),
+    ("atan", f64::atan),
+    ("sinh", f64::sinh),
+    ("cosh", f64::cosh),
+    ("tanh", f64::tanh),
+    ("asinh", f64::asinh),
+    ("acosh", f64::acosh),
+    ("atanh", f64::atanh),
+];
+
+/// Math functions of two numbers, called as `f(a; b)`.
+const MATH_2: &[(&str, Math2)] = &[
+    ("pow", f64::powf),
+    ("atan2", f64::atan2),
+    ("fmin", f64::min),
+    ("fmax", f64::max),
+    ("fmod", |a, b| a % b),
+    ("copysign", f64::copysign),
+    ("hypot", f64::hypot),
+    ("drem", |a, b| a - b * (a / b).round_ties_even()),
+    ("ldexp", |a, b| a * b.exp2()),
+    ("scalb", |a, b| a * b.exp2()),
+    ("scalbln", |a, b| a * b.exp2()),
+];
+
+pub fn is_native(name: &str, arity: usize) -> bool {
+    NATIVES.contains(&(name, arity))
+        || (arity == 0 && MATH_1.iter().any(|(n, _)| *n == name))
+        || (arity == 2 && MATH_2.iter().any(|(n, _)| *n == name))
+}
+
+/// Calls the native builtin `name`; the compile-time check has already
+/// made sure it exists.
+pub fn call(
+    interp: &Interp,
+    name: &str,
+    args: &'static [Expr],
+    env: &Rc<Env>,
+    input: Pv,
+    out: Out,
+) -> Res {
+    let arg = |i: usize| &args[i];
+    match (name, args.len()) {
+        ("empty", 0) => Ok(()),
+        ("error", 0) => Err(Flow::Error(input.value)),
+        ("path", 1) => {
+            for path in interp.paths(arg(0), env, &input.value)? {
+                interp.emit(&input, Value::array(path), out)?;
+            }
+            Ok(())
+        }
+        ("getpath", 1) => interp.eval(arg(0), env, Pv::value(input.value.clone()), &mut |path| {
+            let Value::Array(path) = path.value else {
+                return fail("Path must be specified as an array");
+            };
+            let value = ops::getpath(&input.value, &path)?;
+            let extended = input.path.as_ref().map(|prefix| {
+                let mut prefix = prefix.clone();
+                prefix.extend(path.iter().cloned());
+                prefix
+            });
+            out(Pv {
+                value,
+                path: extended,
+            })
+        }),
+        ("recurse", 0) => recurse_all(interp, input, out),
+        ("recurse" | "repeat", 1) => recurse(interp, arg(0), env, input, out),
+        ("range", _) => {
+            let (from, upto, by) = match args {
+                [upto] => (None, upto, None),
+                [from, upto] => (Some(from), upto, None),
+                [from, upto, by] => (Some(from), upto, Some(by)),
+                _ => unreachable!(),
+            };
+            let value = input.value.clone();
+            let each = |expr: Option<&'static Expr>,
+                        default: f64,
+                        f: &mut dyn FnMut(Value) -> Res| match expr {
+                Some(expr) => {
+                    interp.eval(expr, env, Pv::value(value.clone()), &mut |pv| f(pv.value))
+                }
+                None => f(Value::from(default)),
+            };
+            each(from, 0.0, &mut |from| {
+                each(Some(upto), 0.0, &mut |upto| {
+                    each(by, 1.0, &mut |by| {
+                        let (Some(mut n), Some(upto), Some(by)) =
+                            (from.as_f64(), upto.as_f64(), by.as_f64())
+                        else {
+                            return fail("Range bounds must be numeric");
+                        };
+                        while (by > 0.0 && n < upto) || (by < 0.0 && n > upto) {
+                            interp.emit(&input, Value::from(n), out)?;
+                            n += by;
+                        }
+                        Ok(())
+                    })
+                })
+            })
+        }
+        ("limit", 2) => interp.eval(
+            arg(0),
+            env,
+            Pv::value(input.value.clone()),
+            &mut |n| match n.value.as_f64() {
+                Some(n) if n <= 0.0 => {
+                    if n < 0.0 {
+                        interp.eval(arg(1), env, input.clone(), out)
+                    } else {
+                        Ok(())
+                    }
+                }
+                Some(n) => take(interp, arg(1), env, input.clone(), n, out),
+                None => interp.eval(arg(1), env, input.clone(), out),
+            },
+        ),
+        ("first", 1) => take(interp, arg(0), env, input, 1.0, out),
+        ("until", 2) => until(interp, arg(0), arg(1), env, input, out),
+        ("while", 2) => while_loop(interp, arg(0), arg(1), env, input, out),
+        ("input", 0) => match interp.next_input() {
+            Some(Ok(value)) => interp.emit(&input, value, out),
+            Some(Err(message)) => fail(message),
+            None => fail("No more inputs"),
+        },
+        ("inputs", 0) => {
+            while let Some(next) = interp.next_input() {
+                interp.emit(&input, next.map_err(error)?, out)?;
+            }
+            Ok(())
+        }
+        ("debug", 0) => {
+            eprintln!("[\"DEBUG:\",{}]", input.value.to_json());
+            out(input)
+        }
+        ("stderr", 0) => {
+            eprint!("{}", input.value.to_json());
+            out(input)
+        }
+        ("input_filename", 0) => {
+            let file = interp.location.borrow().0.clone();
+            interp.emit(&input, file.map_or(Value::Null, Value::String), out)
+        }
+        ("input_line_number", 0) => {
+            let line = interp.location.borrow().1;
+            interp.emit(&input, Value::from(line), out)
+        }
+        ("halt", 0) => Err(Flow::Halt(0)),
+        ("halt_error", 0) => halt_error(&input.value, 5),
+        ("halt_error", 1) => interp.eval(
+            arg(0),
+            env,
+            Pv::value(input.value.clone()),
+            &mut |code| match code.value {
+                Value::Number(code, _) => halt_error(&input.value, code as i32),
+                _ => fail("halt_error/1: number required"),
+            },
+        ),
+        ("sort_by" | "group_by" | "unique_by" | "min_by" | "max_by", 1) => {
+            let Value::Array(items) = &input.value else {
+                return fail(format!(
+                    "{} cannot be sorted, as it is not an array",
+                    input.value.describe()
+                ));
+            };
+            let mut keyed = Vec::with_capacity(items.len());
+            for item in items.iter() {
+                keyed.push((
+                    Value::array(interp.collect(arg(0), env, item)?),
+                    item.clone(),
+                ));
+            }
+            let result = match name {
+                "min_by" => keyed
+                    .into_iter()
+                    .min_by(|a, b| a.0.cmp(&b.0))
+                    .map_or(Value::Null, |e| e.1),
+                "max_by" => keyed
+                    .into_iter()
+                    .max_by(|a, b| a.0.cmp(&b.0))
+                    .map_or(Value::Null, |e| e.1),
+                _ => {
+                    keyed.sort_by(|a, b| a.0.cmp(&b.0));
+                    match name {
+                        "sort_by" => Value::array(keyed.into_iter().map(|e| e.1).collect()),
+                        _ => {
+                            let mut groups: Vec<(Value, Vec<Value>)> = Vec::new();
+                            for (key, item) in keyed {
+                                match groups.last_mut() {
+                                    Some((last, group)) if *last == key => group.push(item),
+                                    _ => groups.push((key, vec![item])),
+                                }
+                            }
+                            let groups = groups.into_iter().map(|(_, group)| group);
+                            if name == "group_by" {
+                                Value::array(groups.map(Value::array).collect())
+                            } else {
+                                Value::array(
+                                    groups
+                                        .filter_map(|group| group.into_iter().next())
+                                        .collect(),
+                                )
+                            }
+                        }
+                    }
+                }
+            };
+            interp.emit(&input, result, out)
+        }
+        ("sub", _) => {
+            let flags = args.get(2);
+            let value = input.value.clone();
+            let each_flag = |f: &mut dyn FnMut(Value) -> Res| match flags {
+                Some(flags) => {
+                    interp.eval(flags, env, Pv::value(value.clone()), &mut |pv| f(pv.value))
+                }
+                None => f(Value::Null),
+            };
+            each_flag(&mut |flags| {
+                interp.eval(arg(0), env, Pv::value(value.clone()), &mut |re| {
+                    let matches = regex_matches(interp, &value, &re.value, &flags, false)?;
+                    let Value::String(text) = &value else {
+                        unreachable!("regex_matches checks the input is a string");
+                    };
+                    substitute(
+                        interp,
+                        text,
+                        &matches,
+                        0,
+                        0,
+                        String::new(),
+                        arg(1),
+                        env,
+                        &mut |s| interp.emit(&input, Value::from(s), out),
+                    )
+                })
+            })
+        }
+        ("builtins", 0) => {
+            let mut names: Vec<Value> = NATIVES
+                .iter()
+                .filter(|(name, _)| !name.starts_with('_'))
+                .map(|(name, arity)| Value::from(format!("{name}/{arity}")))
+                .collect();
+            names.extend(
+                MATH_1
+                    .iter()
+                    .map(|(name, _)| Value::from(format!("{name}/0"))),
+            );
+            names.extend(
+                MATH_2
+                    .iter()
+                    .map(|(name, _)| Value::from(format!("{name}/2"))),
+            );
+            names.extend(interp.prelude_names().map(Value::from));
+            interp.emit(&input, Value::array(names), out)
+        }
+        ("_match_impl", 3) => interp.cartesian(
+            args,
+            env,
+            &input.value,
+            &mut vec![Value::Null; 3],
+            &mut |values| {
+                let test = values[2].is_truthy();
+                let matches = regex_matches(interp, &input.value, &values[0], &values[1], test)?;
+                let result = if test {
+                    Value::Bool(!matches.is_empty())
+                } else {
+                    Value::array(matches.into_iter().map(|m| m.object).collect())
+                };
+                interp.emit(&input, result, out)
+            },
+        ),
+        ("split", 2) => interp.cartesian(
+            args,
+            env,
+            &input.value,
+            &mut vec![Value::Null; 2],
+            &mut |values| {
+                let flags = match &values[1] {
+                    Value::String(flags) => Value::from(format!("g{flags}")),
+                    _ => Value::str("g"),
+                };
+                let matches = regex_matches(interp, &input.value, &values[0], &flags, false)?;
+                let Value::String(text) = &input.value else {
+                    unreachable!("regex_matches checks the input is a string");
+                };
+                let mut parts = Vec::new();
+                let mut start = 0;
+                for m in &matches {
+                    parts.push(Value::str(&text[start..m.start]));
+                    start = m.end;
+                }
+                parts.push(Value::str(&text[start..]));
+                interp.emit(&input, Value::array(parts), out)
+            },
+        ),
+        _ => interp.cartesian(
+            args,
+            env,
+            &input.value,
+            &mut vec![Value::Null; args.len()],
+            &mut |values| {
+                let result = function(name, &input.value, values)?;
+                interp.emit(&input, result, out)
+            },
+        ),
+    }
+}
+
+/// Builtins that map their input and argument values to a single output.
+fn function(name: &str, input: &Value, args: &[Value]) -> Result<Value, Flow> {
+    let string = |what: &str| match input {
+        Value::String(s) => Ok(s.clone()),
+        _ => fail(format!("{} {what}", input.describe())),
+    };
+    let number = || match input {
+        Value::Number(n, _) => Ok(*n),
+        _ => fail(format!("{} number required", input.describe())),
+    };
+    let array = || match input {
+        Value::Array(items) => Ok(items.clone()),
+        _ => fail(format!("Cannot iterate over {}", input.describe())),
+    };
+    Ok(match (name, args) {
+        ("not", []) => Value::Bool(!input.is_truthy()),
+        ("error", [message]) => return Err(Flow::Error(message.clone())),
+        ("setpath", [path, value]) => match path {
+            Value::Array(path) => ops::setpath(input.clone(), path, value.clone())?,
+            _ => return fail("Path must be specified as an array"),
+        },
+        ("delpaths", [paths]) => match paths {
+            Value::Array(paths) => ops::delpaths(input.clone(), paths.to_vec())?,
+            _ => return fail("Paths must be specified as an array"),
+        },
+        ("split", [sep]) => match (input, sep) {
+            (Value::String(s), Value::String(sep)) => ops::split(s, sep),
+            _ => return fail("split input and separator must be strings"),
+        },
+        ("length", []) => match input {
+            Value::Null => Value::from(0.0),
+            Value::Number(n, _) => Value::from(n.abs()),
+            Value::String(s) => Value::from(s.chars().count()),
+            Value::Array(items) => Value::from(items.len()),
+            Value::Object(map) => Value::from(map.len()),
+            Value::Bool(_) => return fail(format!("{} has no length", input.describe())),
+        },
+        ("utf8bytelength", []) => Value::from(string("only strings have UTF-8 byte length")?.len()),
+        ("keys" | "keys_unsorted", []) => match input {
+            Value::Object(map) => {
+                let mut keys: Vec<Value> = map.keys().map(|k| Value::String(k.clone())).collect();
+                if name == "keys" {
+                    keys.sort();
+                }
+                Value::array(keys)
+            }
+            Value::Array(items) => Value::array((0..items.len()).map(Value::from).collect()),
+            _ => return fail(format!("{} has no keys", input.describe())),
+        },
+        ("has", [key]) => Value::Bool(ops::has(input, key)?),
+        ("contains", [other]) => Value::Bool(ops::contains(input, other)?),
+        ("type", []) => Value::str(input.type_name()),
+        ("tostring", []) => Value::from(input.to_text()),
+        ("tojson", []) => Value::from(input.to_json()),
+        ("fromjson", []) => {
+            let text = string("only strings can be parsed")?;
+            super::value::parse_json(&text)
+                .map_err(|e| error(format!("{e} (while parsing '{text}')")))?
+        }
+        ("tonumber", []) => match input {
+            Value::Number(..) => input.clone(),
+            Value::String(s) => parse_number(s.trim())
+                .ok_or_else(|| error(format!("Cannot parse '{s}' as JSON")))?,
+            _ => return fail(format!("{} cannot be parsed as a number", input.describe())),
+        },
+        ("ascii_downcase" | "ascii_upcase", []) => {
+            let Value::String(s) = input else {
+                return fail(format!("{name} input must be a string"));
+            };
+            Value::from(if name == "ascii_downcase" {
+                s.to_ascii_lowercase()
+            } else {
+                s.to_ascii_uppercase()
+            })
+        }
+        ("explode", []) => {
+            let Value::String(s) = input else {
+                return fail("explode input must be a string");
+            };
+            Value::array(s.chars().map(|c| Value::from(c as u32 as f64)).collect())
+        }
+        ("implode", []) => {
+            let Value::Array(codes) = input else {
+                return fail("implode input must be an array");
+            };
+            let mut s = String::new();
+            for code in codes.iter() {
+                let Value::Number(code, _) = code else {
+                    return fail("Unicode codepoint must be numeric");
+                };
+                let Some(c) = char::from_u32(*code as u32).filter(|_| *code >= 0.0) else {
+                    return fail("Invalid codepoint literal");
+                };
+                s.push(c);
+            }
+            Value::from(s)
+        }
+        ("ltrimstr" | "rtrimstr", [affix]) => match (input, affix) {
+            (Value::String(s), Value::String(affix)) => {
+                let trimmed = if name == "ltrimstr" {
+                    s.strip_prefix(&**affix)
+                } else {
+                    s.strip_suffix(&**affix)
+                };
+                trimmed.map_or_else(|| input.clone(), Value::str)
+            }
+            _ => input.clone(),
+        },
+        ("startswith" | "endswith", [affix]) => match (input, affix) {
+            (Value::String(s), Value::String(affix)) => Value::Bool(if name == "startswith" {
+                s.starts_with(&**affix)
+            } else {
+                s.ends_with(&**affix)
+            }),
+            _ => return fail(format!("{name}() requires string inputs")),
+        },
+        ("trim" | "ltrim" | "rtrim", []) => {
+            let Value::String(s) = input else {
+                return fail("trim input must be a string");
+            };
+            Value::str(match name {
+                "trim" => s.trim(),
+                "ltrim" => s.trim_start(),
+                _ => s.trim_end(),
+            })
+        }
+        ("join", [sep]) => {
+            let items = values(input)?;
+            let Value::String(sep) = sep else {
+                return fail(format!("{} is not a valid separator", sep.describe()));
+            };
+            let mut joined = String::new();
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    joined.push_str(sep);
+                }
+                match item {
+                    Value::Null => {}
+                    Value::String(s) => joined.push_str(s),
+                    Value::Number(..) | Value::Bool(_) => joined.push_str(&item.to_json()),
+                    _ => {
+                        return fail(format!(
+                            "{} and {} cannot be added",
+                            Value::from(joined).describe(),
+                            item.describe()
+                        ));
+                    }
+                }
+            }
+            Value::from(joined)
+        }
+        ("add", []) => {
+            let mut total = Value::Null;
+            for item in values(input)? {
+                total = ops::add(&total, &item)?;
+            }
+            total
+        }
+        ("any", []) => Value::Bool(values(input)?.iter().any(Value::is_truthy)),
+        ("all", []) => Value::Bool(values(input)?.iter().all(Value::is_truthy)),
+        ("flatten", depth) => {
+            let depth = match depth {
+                [] => f64::INFINITY,
+                [Value::Number(n, _)] if *n >= 0.0 => *n,
+                [Value::Number(..)] => return fail("flatten depth must not be negative"),
+                _ => return fail("flatten depth must be a number"),
+            };
+            let mut flat = Vec::new();
+            flatten(&array()?, depth, &mut flat);
+            Value::array(flat)
+        }
+        ("sort" | "unique", []) => {
+            let Value::Array(items) = input else {
+                return fail(format!(
+                    "{} cannot be sorted, as it is not an array",
+                    input.describe()
+                ));
+            };
+            let mut items = items.to_vec();
+            items.sort();
+            if name == "unique" {
+                items.dedup();
+            }
+            Value::array(items)
+        }
+        ("min", []) => array()?.iter().min().cloned().unwrap_or(Value::Null),
+        ("max", []) => array()?.iter().max().cloned().unwrap_or(Value::Null),
+        ("reverse", []) => match input {
+            Value::Null => Value::array(Vec::new()),
+            Value::String(s) => Value::from(s.chars().rev().collect::<String>()),
+            Value::Array(items) => Value::array(items.iter().rev().cloned().collect()),
+            _ => return fail(format!("Cannot index {} with number", input.type_name())),
+        },
+        ("indices" | "index" | "rindex", [needle]) => {
+            let found = match (input, needle) {
+                (Value::Null, _) => Value::Null,
+                (Value::String(s), Value::String(needle)) => string_indices(s, needle),
+                (Value::Array(items), Value::Array(needle)) => ops::indices(items, needle),
+                (Value::Array(items), needle) => ops::indices(items, std::slice::from_ref(needle)),
+                _ => ops::index(input, &Value::array(vec![needle.clone()]))?,
+            };
+            match (name, found) {
+                ("indices", found) => found,
+                (_, Value::Array(found)) => {
+                    let pick = if name == "index" {
+                        found.first()
+                    } else {
+                        found.last()
+                    };
+                    pick.cloned().unwrap_or(Value::Null)
+                }
+                (_, other) => other,
+            }
+        }
+        ("infinite", []) => Value::from(f64::INFINITY),
+        ("nan", []) => Value::from(f64::NAN),
+        ("isinfinite", []) => Value::Bool(number()?.is_infinite()),
+        ("isnan", []) => Value::Bool(number()?.is_nan()),
+        ("isnormal", []) => Value::Bool(number()?.is_normal()),
+        ("abs", []) => match input {
+            Value::Number(n, _) if *n < 0.0 => Value::from(-n),
+            Value::Number(..) => input.clone(),
+            _ => return fail(format!("{} has no absolute value", input.describe())),
+        },
+        ("toarray", []) => match input {
+            Value::Array(_) => input.clone(),
+            _ => Value::array(vec![input.clone()]),
+        },
+        ("format", [Value::String(name)]) => Value::from(format::apply(name, input)?),
+        ("format", [other]) => return fail(format!("{} is not a valid format", other.describe())),
+        ("now", []) => Value::from(time::now()),
+        ("mktime", []) => Value::from(time::mktime(input)?),
+        ("gmtime" | "localtime", []) => time::gmtime(number()?),
+        ("strftime" | "strflocaltime", [fmt]) => Value::from(time::strftime(input, fmt)?),
+        ("strptime", [fmt]) => time::strptime(input, fmt)?,
+        ("have_literal_numbers", []) => Value::Bool(true),
+        ("have_decnum", []) => Value::Bool(false),
+        ("frexp", []) => {
+            let (mantissa, exponent) = frexp(number()?);
+            Value::array(vec![Value::from(mantissa), Value::from(exponent as f64)])
+        }
+        ("modf", []) => {
+            let n = number()?;
+            let whole = n.trunc();
+            let fraction = if n.is_infinite() {
+                0.0f64.copysign(n)
+            } else {
+                n - whole
+            };
+            Value::array(vec![Value::from(fraction), Value::from(whole)])
+        }
+        (_, []) => match MATH_1.iter().find(|(n, _)| *n == name) {
+            Some((_, f)) => Value::from(f(number()?)),
+            None => return fail(format!("{name}/0 is not defined")),
+        },
+        (_, [a, b]) => match MATH_2.iter().find(|(n, _)| *n == name) {
+            Some((_, f)) => match (a.as_f64(), b.as_f64()) {
+                (Some(a), Some(b)) => Value::from(f(a, b)),
+                _ => return fail(format!("{name}/2 number required")),
+            },
+            None => return fail(format!("{name}/2 is not defined")),
+        },
+        _ => return fail(format!("{name}/{} is not defined", args.len())),
+    })
+}
+
+/// The values of an array or object, as `.[]` would produce them.
+fn values(input: &Value) -> Result<Vec<Value>, Flow> {
+    match input {
+        Value::Array(items) => Ok(items.to_vec()),
+        Value::Object(map) => Ok(map.values().cloned().collect()),
+        Value::Null => fail("Cannot iterate over null"),
+        other => fail(format!("Cannot iterate over {}", other.describe())),
+    }
+}
+
+fn flatten(items: &[Value], depth: f64, flat: &mut Vec<Value>) {
+    for item in items {
+        match item {
+            Value::Array(inner) if depth > 0.0 => flatten(inner, depth - 1.0, flat),
+            other => flat.push(other.clone()),
+        }
+    }
+}
+
+/// Codepoint offsets of every occurrence of `needle`, overlapping ones
+/// included.
+fn string_indices(s: &str, needle: &str) -> Value {
+    if needle.is_empty() {
+        return Value::array(Vec::new());
+    }
+    let found = s
+        .char_indices()
+        .enumerate()
+        .filter(|(_, (byte, _))| s[*byte..].starts_with(needle))
+        .map(|(i, _)| Value::from(i))
+        .collect();
+    Value::array(found)
+}
+
+/// A number in JSON syntax, as `tonumber` accepts it.
+fn parse_number(text: &str) -> Option<Value> {
+    let valid = !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'));
+    if !valid {
+        return None;
+    }
+    let n: f64 = text.parse().ok()?;
+    if text.chars().all(|c| c.is_ascii_digit()) {
+        let digits = text.trim_start_matches('0');
+        return Some(Value::integer_literal(
+            if digits.is_empty() { "0" } else { digits },
+            n,
+        ));
+    }
+    Some(Value::from(n))
+}
+
+fn halt_error(value: &Value, code: i32) -> Res {
+    match value {
+        Value::String(s) => eprint!("{s}"),
+        other => eprintln!("{}", other.to_json()),
+    }
+    Err(Flow::Halt(code))
+}
+
+/// Runs `f` and stops it after `n` outputs.
+fn take(interp: &Interp, f: &'static Expr, env: &Rc<Env>, input: Pv, n: f64, out: Out) -> Res {
+    let id = interp.fresh_id();
+    let mut count = 0.0;
+    let result = interp.eval(f, env, input, &mut |pv| {
+        count += 1.0;
+        out(pv)?;
+        if count >= n {
+            Err(Flow::Break(id))
+        } else {
+            Ok(())
+        }
+    });
+    match result {
+        Err(Flow::Break(broken)) if broken == id => Ok(()),
+        result => result,
+    }
+}
+
+/// `..`: the input and everything inside it, depth first.
+fn recurse_all(interp: &Interp, input: Pv, out: Out) -> Res {
+    out(input.clone())?;
+    if matches!(input.value, Value::Array(_) | Value::Object(_)) {
+        interp.iterate(&input, &mut |child| recurse_all(interp, child, out))?;
+    }
+    Ok(())
+}
+
+/// `recurse(f)` and `repeat(f)`, looping rather than recursing while `f` has one output.
+fn recurse(interp: &Interp, f: &'static Expr, env: &Rc<Env>, mut current: Pv, out: Out) -> Res {
+    loop {
+        out(current.clone())?;
+        let mut next = interp.collect_pv(f, env, current)?;
+        if next.len() != 1 {
+            return next
+                .into_iter()
+                .try_for_each(|pv| recurse(interp, f, env, pv, out));
+        }
+        current = next.remove(0);
+    }
+}
+
+fn while_loop(
+    interp: &Interp,
+    cond: &'static Expr,
+    update: &'static Expr,
+    env: &Rc<Env>,
+    mut current: Pv,
+    out: Out,
+) -> Res {
+    loop {
+        let conds = interp.collect(cond, env, &current.value)?;
+        if conds.len() != 1 {
+            for c in conds {
+                if c.is_truthy() {
+                    out(current.clone())?;
+                    for next in interp.collect_pv(update, env, current.clone())? {
+                        while_loop(interp, cond, update, env, next, out)?;
+                    }
+                }
+            }
+            return Ok(());
+        }
+        if !conds[0].is_truthy() {
+            return Ok(());
+        }
+        out(current.clone())?;
+        let mut next = interp.collect_pv(update, env, current)?;
+        if next.len() != 1 {
+            return next
+                .into_iter()
+                .try_for_each(|pv| while_loop(interp, cond, update, env, pv, out));
+        }
+        current = next.remove(0);
+    }
+}
+
+fn until(
+    interp: &Interp,
+    cond: &'static Expr,
+    update: &'static Expr,
+    env: &Rc<Env>,
+    mut current: Pv,
+    out: Out,
+) -> Res {
+    loop {
+        let conds = interp.collect(cond, env, &current.value)?;
+        if conds.len() != 1 {
+            for c in conds {
+                if c.is_truthy() {
+                    out(current.clone())?;
+                } else {
+                    for next in interp.collect_pv(update, env, current.clone())? {
+                        until(interp, cond, update, env, next, out)?;
+                    }
+                }
+            }
+            return Ok(());
+        }
+        if conds[0].is_truthy() {
+            return out(current);
+        }
+        let mut next = interp.collect_pv(update, env, current)?;
+        if next.len() != 1 {
+            return next
+                .into_iter()
+                .try_for_each(|pv| until(interp, cond, update, env, pv, out));
+        }
+        current = next.remove(0);
+    }
+}
+
+/// A regex match: its byte range in the input and the object `match`
+/// outputs for it.
+struct Match {
+    start: usize,
+    end: usize,
+    object: Value,
+    /// Named captures, for `sub` replacements.
+    captures: Value,
+}
+
+fn regex_matches(
+    interp: &Interp,
+    input: &Value,
+    re: &Value,
+    flags: &Value,
+    test: bool,
+) -> Result<Vec<Match>, Flow> {
+    let Value::String(text) = input else {
+        return fail(format!(
+            "{} cannot be matched, as it is not a string",
+            input.describe()
+        ));
+    };
+    let Value::String(pattern) = re else {
+        return fail(format!("{} is not a string", re.describe()));
+    };
+    let flags = match flags {
+        Value::Null => "",
+        Value::String(flags) => flags,
+        other => return fail(format!("{} is not a string", other.describe())),
+    };
+    let (mut global, mut skip_empty) = (false, false);
+    let mut builder = regex::RegexBuilder::new(pattern);
+    for flag in flags.chars() {
+        match flag {
+            'g' => global = true,
+            'n' => skip_empty = true,
+            'i' => {
+                builder.case_insensitive(true);
+            }
+            'x' => {
+                builder.ignore_whitespace(true);
+            }
+            // Oniguruma's single-line mode only anchors `$` at the very end,
+            // which is already the default here
+            'p' => {
+                builder.dot_matches_new_line(true);
+            }
+            's' | 'l' => {}
+            _ => return fail(format!("{flags} is not a valid modifier string")),
+        }
+    }
+    let key = format!("{flags}/{pattern}");
+    let cached = interp.regexes.borrow().get(&key).cloned();
+    let regex = match cached {
+        Some(regex) => regex,
+        None => {
+            let regex = builder.build().map_err(|e| {
+                // the regex crate's message spans several lines; keep the gist
+                let e = e.to_string();
+                let reason = e.lines().last().unwrap_or_default();
+                let reason = reason.trim_start_matches("error: ");
+                error(format!(
+                    "{pattern} (at offset 0) is not a valid regex: {reason}"
+                ))
+            })?;
+            interp.regexes.borrow_mut().insert(key, regex.clone());
+            regex
+        }
+    };
+
+    let names: Vec<Option<&str>> = regex.capture_names().skip(1).collect();
+    let mut matches = Vec::new();
+    // codepoint offsets are counted incrementally from the previous match
+    let (mut base_byte, mut base_chars) = (0, 0);
+    for caps in regex.captures_iter(text) {
+        let whole = caps.get(0).expect("group 0 always participates");
+        if skip_empty && whole.is_empty() {
+            continue;
+        }
+        // like jq, a global search stops once it reaches the end of the text
+        if whole.is_empty() && whole.start() == text.len() && !matches.is_empty() {
+            break;
+        }
+        if test {
+            matches.push(Match {
+                start: whole.start(),
+                end: whole.end(),
+                object: Value::Null,
+                captures: Value::Null,
+            });
+            break;
+        }
+        base_chars += text[base_byte..whole.start()].chars().count();
+        base_byte = whole.start();
+        let offset = |byte: usize| base_chars + text[base_byte..byte].chars().count();
+        let entry = |offset: Value, length: usize, string: Value, name: Option<Value>| {
+            let mut map = Map::new();
+            map.insert(Rc::from("offset"), offset);
+            map.insert(Rc::from("length"), Value::from(length));
+            map.insert(Rc::from("string"), string);
+            if let Some(name) = name {
+                map.insert(Rc::from("name"), name);
+            }
+            map
+        };
+        let mut captures = Vec::new();
+        let mut named = Map::new();
+        for (i, name) in names.iter().enumerate() {
+            let name_value = name.map_or(Value::Null, Value::str);
+            let capture = match caps.get(i + 1) {
+                Some(m) => entry(
+                    Value::from(offset(m.start())),
+                    m.as_str().chars().count(),
+                    Value::str(m.as_str()),
+                    Some(name_value),
+                ),
+                None => {
+                    // jq lists an unmatched group's fields in this order
+                    let mut map = Map::new();
+                    map.insert(Rc::from("offset"), Value::from(-1.0));
+                    map.insert(Rc::from("string"), Value::Null);
+                    map.insert(Rc::from("length"), Value::from(0.0));
+                    map.insert(Rc::from("name"), name_value);
+                    map
+                }
+            };
+            if let Some(name) = name {
+                named.insert(Rc::from(*name), capture["string"].clone());
+            }
+            captures.push(Value::object(capture));
+        }
+        let mut object = entry(
+            Value::from(offset(whole.start())),
+            whole.as_str().chars().count(),
+            Value::str(whole.as_str()),
+            None,
+        );
+        object.insert(Rc::from("captures"), Value::array(captures));
+        matches.push(Match {
+            start: whole.start(),
+            end: whole.end(),
+            object: Value::object(object),
+            captures: Value::object(named),
+        });
+        if !global {
+            break;
+        }
+    }
+    Ok(matches)
+}
+
+/// `sub`: replaces each match with the outputs of `replacement`, run on
+/// the match's named captures; several outputs give several results.
+#[allow(clippy::too_many_arguments)]
+fn substitute(
+    interp: &Interp,
+    text: &str,
+    matches: &[Match],
+    index: usize,
+    copied: usize,
+    result: String,
+    replacement: &'static Expr,
+    env: &Rc<Env>,
+    out: &mut dyn FnMut(String) -> Res,
+) -> Res {
+    let Some(m) = matches.get(index) else {
+        return out(result + &text[copied..]);
+    };
+    interp.eval(replacement, env, Pv::value(m.captures.clone()), &mut |pv| {
+        let Value::String(s) = &pv.value else {
+            return fail(format!(
+                "{} cannot be added to a string",
+                pv.value.describe()
+            ));
+        };
+        let next = format!("{result}{}{s}", &text[copied..m.start]);
+        substitute(
+            interp,
+            text,
+            matches,
+            index + 1,
+            m.end,
+            next,
+            replacement,
+            env,
+            out,
+        )
+    })
+}
+
+fn exp10(n: f64) -> f64 {
+    10f64.powf(n)
+}
+
+fn frexp(n: f64) -> (f64, i32) {
+    if n == 0.0 || !n.is_finite() {
+        return (n, 0);
+    }
+    let mut exponent = n.abs().log2().floor() as i32 + 1;
+    let mut mantissa = n / 2f64.powi(exponent);
+    // log2 can be off by one right at powers of two
+    if mantissa.abs() >= 1.0 {
+        mantissa /= 2.0;
+        exponent += 1;
+    } else if mantissa.abs() < 0.5 {
+        mantissa *= 2.0;
+        exponent -= 1;
+    }
+    (mantissa, exponent)
+}
+
+fn logb(n: f64) -> f64 {
+    if n == 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if !n.is_finite() {
+        return n.abs();
+    }
+    (frexp(n).1 - 1) as f64
+}
+
+fn significand(n: f64) -> f64 {
+    if n == 0.0 || !n.is_finite() {
+        return n;
+    }
+    frexp(n).0 * 2.0
+}
+
+/// Γ(x) computed exactly for the positive integers small enough to be finite,
+/// where the approximation below would be visibly off.
+fn factorial(x: f64) -> Option<f64> {
+    if x != x.floor() || !(1.0..=171.0).contains(&x) {
+        return None;
+    }
+    Some((1..x as u32).fold(1.0, |product, n| product * n as f64))
+}
+
+/// Lanczos approximation of ln|Γ(x)|.
+fn lgamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if let Some(factorial) = factorial(x) {
+        return factorial.ln();
+    }
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin().abs()).ln() - lgamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let mut sum = COEFFICIENTS[0];
+    for (i, c) in COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += c / (x + i as f64);
+    }
+    let t = x + G + 0.5;
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+fn tgamma(x: f64) -> f64 {
+    if x == x.floor() && x <= 0.0 {
+        return f64::NAN;
+    }
+    if let Some(factorial) = factorial(x) {
+        return factorial;
+    }
+    let magnitude = lgamma(x).exp();
+    // Γ is negative between consecutive negative integers with odd floor
+    if x < 0.0 && (x.floor() as i64) % 2 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
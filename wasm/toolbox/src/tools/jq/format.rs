@@ -0,0 +1,137 @@
+//! The `@name` string formats: `@csv`, `@base64`, `@uri`, ...
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+
+use super::interp::{Flow, fail};
+use super::value::Value;
+
+/// Formats `value` with the format `@name`.
+pub fn apply(name: &str, value: &Value) -> Result<String, Flow> {
+    Ok(match name {
+        "text" => value.to_text(),
+        "json" => value.to_json(),
+        "html" => {
+            let mut out = String::new();
+            for c in value.to_text().chars() {
+                match c {
+                    '<' => out.push_str("&lt;"),
+                    '>' => out.push_str("&gt;"),
+                    '&' => out.push_str("&amp;"),
+                    '\'' => out.push_str("&#39;"),
+                    '"' => out.push_str("&quot;"),
+                    c => out.push(c),
+                }
+            }
+            out
+        }
+        "uri" => {
+            let mut out = String::new();
+            for byte in value.to_text().bytes() {
+                if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+                    out.push(byte as char);
+                } else {
+                    out.push_str(&format!("%{byte:02X}"));
+                }
+            }
+            out
+        }
+        "csv" | "tsv" => {
+            let Value::Array(items) = value else {
+                return fail(format!(
+                    "{} cannot be {name}-formatted, only an array can be",
+                    value.describe()
+                ));
+            };
+            let mut fields = Vec::with_capacity(items.len());
+            for item in items.iter() {
+                fields.push(match item {
+                    Value::Null => String::new(),
+                    Value::Bool(_) | Value::Number(..) => item.to_json(),
+                    Value::String(s) if name == "csv" => format!("\"{}\"", s.replace('"', "\"\"")),
+                    Value::String(s) => s
+                        .replace('\\', "\\\\")
+                        .replace('\t', "\\t")
+                        .replace('\n', "\\n")
+                        .replace('\r', "\\r"),
+                    _ => return fail(format!("{} is not valid in a {name} row", item.describe())),
+                });
+            }
+            fields.join(if name == "csv" { "," } else { "\t" })
+        }
+        "sh" => {
+            let quote = |item: &Value| match item {
+                Value::String(s) => Ok(format!("'{}'", s.replace('\'', "'\\''"))),
+                Value::Array(_) | Value::Object(_) => {
+                    fail(format!("{} can not be escaped for shell", item.describe()))
+                }
+                other => Ok(other.to_json()),
+            };
+            match value {
+                Value::Array(items) => items
+                    .iter()
+                    .map(quote)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(" "),
+                other => quote(other)?,
+            }
+        }
+        "base64" => STANDARD.encode(value.to_text()),
+        "base64d" => {
+            let text = value.to_text();
+            let decoded = STANDARD_NO_PAD
+                .decode(text.trim_end_matches('='))
+                .map_err(|_| {
+                    super::interp::error(format!(
+                        "{} is not valid base64 data",
+                        Value::from(text.clone()).describe()
+                    ))
+                })?;
+            String::from_utf8_lossy(&decoded).into_owned()
+        }
+        "base32" => base32_encode(value.to_text().as_bytes()),
+        "base32d" => {
+            let decoded = base32_decode(&value.to_text()).ok_or_else(|| {
+                super::interp::error(format!("{} is not valid base32 data", value.describe()))
+            })?;
+            String::from_utf8_lossy(&decoded).into_owned()
+        }
+        _ => return fail(format!("{name} is not a valid format")),
+    })
+}
+
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(5) {
+        let mut block = [0u8; 5];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let bits = block.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+        let symbols = (chunk.len() * 8).div_ceil(5);
+        for i in 0..8 {
+            if i < symbols {
+                out.push(BASE32[((bits >> (35 - i * 5)) & 31) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u64, 0);
+    for c in text.trim_end_matches('=').bytes() {
+        let value = BASE32.iter().position(|b| *b == c)? as u64;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,1171 @@
+//! Evaluates jq programs. A filter hands each of its outputs to a callback,
+//! so generators such as `range(1e9)` and `repeat` stream instead of being
+//! collected, and `limit`/`first` stop them by breaking out to a label.
+//!
+//! With path tracking on, every output also carries where in the input it
+//! was found; that is how `path(f)`, `del(f)` and the assignment operators
+//! see which locations a filter refers to.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use super::ast::{Expr, FuncDef, ObjectPattern, Pattern, StrPart, UpdateOp};
+use super::value::{Map, Value};
+use super::{builtins, format, ops};
+
+/// How evaluation stops early.
+pub enum Flow {
+    /// `error(value)` or a failed operation, catchable with `try`.
+    Error(Value),
+    /// `break $label`, unwinding to the label with this id.
+    Break(usize),
+    /// `halt` or `halt_error`: stop the program with this exit status.
+    Halt(i32),
+}
+
+pub type Res = Result<(), Flow>;
+
+/// Where outputs go.
+pub type Out<'o> = &'o mut dyn FnMut(Pv) -> Res;
+
+pub type InputReader = Box<dyn FnOnce() -> VecDeque<Input>>;
+
+pub fn error(message: impl Into<String>) -> Flow {
+    Flow::Error(Value::from(message.into()))
+}
+
+pub fn fail<T>(message: impl Into<String>) -> Result<T, Flow> {
+    Err(error(message))
+}
+
+/// A value and, when paths are being tracked, where in the input it is.
+#[derive(Clone)]
+pub struct Pv {
+    pub value: Value,
+    pub path: Option<Vec<Value>>,
+}
+
+impl Pv {
+    pub fn value(value: Value) -> Pv {
+        Pv { value, path: None }
+    }
+
+    /// Extends the path, if tracked, by one key.
+    pub fn child(&self, value: Value, key: impl FnOnce() -> Value) -> Pv {
+        let path = self.path.as_ref().map(|path| {
+            let mut path = path.clone();
+            path.push(key());
+            path
+        });
+        Pv { value, path }
+    }
+}
+
+/// Names in scope, innermost first.
+pub enum Env {
+    Top,
+    Var {
+        name: Rc<str>,
+        value: Value,
+        /// A `$name` parameter, which can also be called as `name`.
+        callable: bool,
+        parent: Rc<Env>,
+    },
+    Def {
+        def: &'static FuncDef,
+        parent: Rc<Env>,
+    },
+    /// A filter parameter: calling it runs the argument in the caller's scope.
+    Closure {
+        name: Rc<str>,
+        body: &'static Expr,
+        env: Rc<Env>,
+        parent: Rc<Env>,
+    },
+    Label {
+        name: Rc<str>,
+        id: usize,
+        parent: Rc<Env>,
+    },
+}
+
+enum Found<'e> {
+    Def(&'static FuncDef, &'e Rc<Env>),
+    Closure(&'static Expr, &'e Rc<Env>),
+    Value(&'e Value),
+}
+
+impl Env {
+    fn parent(&self) -> Option<&Rc<Env>> {
+        match self {
+            Env::Top => None,
+            Env::Var { parent, .. }
+            | Env::Def { parent, .. }
+            | Env::Closure { parent, .. }
+            | Env::Label { parent, .. } => Some(parent),
+        }
+    }
+
+    fn function<'e>(self: &'e Rc<Env>, name: &str, arity: usize) -> Option<Found<'e>> {
+        let mut node = self;
+        loop {
+            match &**node {
+                Env::Def { def, .. } if &*def.name == name && def.params.len() == arity => {
+                    return Some(Found::Def(def, node));
+                }
+                Env::Closure {
+                    name: n, body, env, ..
+                } if arity == 0 && &**n == name => {
+                    return Some(Found::Closure(body, env));
+                }
+                Env::Var {
+                    name: n,
+                    value,
+                    callable: true,
+                    ..
+                } if arity == 0 && &**n == name => return Some(Found::Value(value)),
+                _ => {}
+            }
+            node = node.parent()?;
+        }
+    }
+
+    pub fn var(&self, name: &str) -> Option<&Value> {
+        let mut node = self;
+        loop {
+            if let Env::Var { name: n, value, .. } = node
+                && &**n == name
+            {
+                return Some(value);
+            }
+            node = node.parent()?;
+        }
+    }
+
+    fn label(&self, name: &str) -> Option<usize> {
+        let mut node = self;
+        loop {
+            if let Env::Label { name: n, id, .. } = node
+                && &**n == name
+            {
+                return Some(*id);
+            }
+            node = node.parent()?;
+        }
+    }
+}
+
+/// One value of the input stream, or why it could not be parsed.
+pub struct Input {
+    pub value: Result<Value, String>,
+    /// The file it came from; `None` for stdin.
+    pub file: Option<Rc<str>>,
+    /// Lines read up to the end of the value, for error messages.
+    pub line: usize,
+}
+
+/// Nested evaluations allowed before giving up: every output is passed on
+/// down the native stack, so this bounds recursion well within the stack.
+const MAX_DEPTH: usize = 1000;
+
+pub struct Interp {
+    /// Functions the jq-language prelude defines.
+    defs: HashMap<(&'static str, usize), &'static FuncDef>,
+    /// Global variables (`$ENV`, `--arg` values), where every scope starts.
+    pub top: Rc<Env>,
+    next_id: Cell<usize>,
+    depth: Cell<usize>,
+    pub inputs: RefCell<VecDeque<Input>>,
+    /// Reads the inputs on first use, for `-n` programs that may never ask.
+    pub reader: RefCell<Option<InputReader>>,
+    /// File and line of the input being processed.
+    pub location: RefCell<(Option<Rc<str>>, usize)>,
+    pub regexes: RefCell<HashMap<String, regex::Regex>>,
+}
+
+/// `$__loc__`: where in the program it appears.
+fn location(line: usize) -> Value {
+    let mut map = Map::new();
+    map.insert(Rc::from("file"), Value::str("<top-level>"));
+    map.insert(Rc::from("line"), Value::from(line));
+    Value::object(map)
+}
+
+/// Parsed once per run and kept for its whole length, so the tree can be
+/// borrowed by closures and scopes without reference counting every node.
+pub fn leak(expr: Expr) -> &'static Expr {
+    Box::leak(Box::new(expr))
+}
+
+impl Interp {
+    pub fn new(prelude: &'static Expr, globals: Vec<(Rc<str>, Value)>) -> Interp {
+        let mut defs = HashMap::new();
+        let mut expr = prelude;
+        while let Expr::Def(def, rest) = expr {
+            defs.insert((&*def.name, def.params.len()), &**def);
+            expr = rest;
+        }
+        let top = globals
+            .into_iter()
+            .fold(Rc::new(Env::Top), |parent, (name, value)| {
+                Rc::new(Env::Var {
+                    name,
+                    value,
+                    callable: false,
+                    parent,
+                })
+            });
+        Interp {
+            defs,
+            top,
+            next_id: Cell::new(0),
+            depth: Cell::new(0),
+            inputs: RefCell::new(VecDeque::new()),
+            reader: RefCell::new(None),
+            location: RefCell::new((None, 0)),
+            regexes: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn fresh_id(&self) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+
+    /// Names of the prelude's functions, as `name/arity`.
+    pub fn prelude_names(&self) -> impl Iterator<Item = String> + '_ {
+        self.defs
+            .keys()
+            .filter(|(name, _)| !name.starts_with('_'))
+            .map(|(name, arity)| format!("{name}/{arity}"))
+    }
+
+    /// Reports functions, variables and labels used but never defined.
+    pub fn check(&self, expr: &Expr, errors: &mut Vec<String>) {
+        let mut scope = Scope::default();
+        for name in globals(&self.top) {
+            scope.vars.push(name);
+        }
+        self.check_expr(expr, &mut scope, errors);
+    }
+
+    fn check_expr(&self, expr: &Expr, scope: &mut Scope, errors: &mut Vec<String>) {
+        let mut check = |expr: &Expr, scope: &mut Scope| self.check_expr(expr, scope, errors);
+        match expr {
+            Expr::Identity | Expr::Literal(_) | Expr::Format(_) | Expr::Loc(_) => {}
+            Expr::Str(_, parts) => {
+                for part in parts {
+                    if let StrPart::Interp(e) = part {
+                        check(e, scope);
+                    }
+                }
+            }
+            Expr::Index(a, b) | Expr::Pipe(a, b) | Expr::Comma(a, b) | Expr::Binary(_, a, b) => {
+                check(a, scope);
+                check(b, scope);
+            }
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Alt(a, b) | Expr::Update(_, a, b) => {
+                check(a, scope);
+                check(b, scope);
+            }
+            Expr::Slice(target, from, to) => {
+                check(target, scope);
+                for e in [from, to].into_iter().flatten() {
+                    check(e, scope);
+                }
+            }
+            Expr::Iterate(e) | Expr::Neg(e) => check(e, scope),
+            Expr::Try(body, handler) => {
+                check(body, scope);
+                if let Some(handler) = handler {
+                    check(handler, scope);
+                }
+            }
+            Expr::Array(inner) => {
+                if let Some(inner) = inner {
+                    check(inner, scope);
+                }
+            }
+            Expr::Object(entries) => {
+                for (key, value) in entries {
+                    check(key, scope);
+                    check(value, scope);
+                }
+            }
+            Expr::If(branches, otherwise) => {
+                for (cond, body) in branches {
+                    check(cond, scope);
+                    check(body, scope);
+                }
+                if let Some(otherwise) = otherwise {
+                    check(otherwise, scope);
+                }
+            }
+            Expr::Reduce {
+                source,
+                pattern,
+                init,
+                update,
+            } => {
+                check(source, scope);
+                check(init, scope);
+                let mark = scope.vars.len();
+                self.check_pattern(pattern, scope, errors);
+                self.check_expr(update, scope, errors);
+                scope.vars.truncate(mark);
+            }
+            Expr::Foreach {
+                source,
+                pattern,
+                init,
+                update,
+                extract,
+            } => {
+                check(source, scope);
+                check(init, scope);
+                let mark = scope.vars.len();
+                self.check_pattern(pattern, scope, errors);
+                self.check_expr(update, scope, errors);
+                if let Some(extract) = extract {
+                    self.check_expr(extract, scope, errors);
+                }
+                scope.vars.truncate(mark);
+            }
+            Expr::Def(def, rest) => {
+                let marks = (scope.vars.len(), scope.funcs.len());
+                scope.funcs.push((def.name.clone(), def.params.len()));
+                for param in &def.params {
+                    scope.funcs.push((param.name.clone(), 0));
+                    if param.is_var {
+                        scope.vars.push(param.name.clone());
+                    }
+                }
+                self.check_expr(&def.body, scope, errors);
+                scope.vars.truncate(marks.0);
+                scope.funcs.truncate(marks.1 + 1);
+                self.check_expr(rest, scope, errors);
+                scope.funcs.truncate(marks.1);
+            }
+            Expr::Call(name, args) => {
+                for arg in args {
+                    check(arg, scope);
+                }
+                let arity = args.len();
+                let defined = scope.funcs.iter().any(|(n, a)| n == name && *a == arity)
+                    || self.defs.contains_key(&(&**name, arity))
+                    || builtins::is_native(name, arity);
+                if !defined {
+                    errors.push(format!("{name}/{arity} is not defined"));
+                }
+            }
+            Expr::Var(name) => {
+                if !scope.vars.contains(name) && &**name != "ENV" {
+                    errors.push(format!("${name} is not defined"));
+                }
+            }
+            Expr::Bind(source, patterns, body) => {
+                check(source, scope);
+                let mark = scope.vars.len();
+                for pattern in patterns {
+                    self.check_pattern(pattern, scope, errors);
+                }
+                self.check_expr(body, scope, errors);
+                scope.vars.truncate(mark);
+            }
+            Expr::Label(name, body) => {
+                scope.labels.push(name.clone());
+                check(body, scope);
+                scope.labels.pop();
+            }
+            Expr::Break(name) => {
+                if !scope.labels.contains(name) {
+                    errors.push(format!("$*label-{name} is not defined"));
+                }
+            }
+        }
+    }
+
+    fn check_pattern(&self, pattern: &Pattern, scope: &mut Scope, errors: &mut Vec<String>) {
+        match pattern {
+            Pattern::Var(name) => scope.vars.push(name.clone()),
+            Pattern::Array(items) => {
+                for item in items {
+                    self.check_pattern(item, scope, errors);
+                }
+            }
+            Pattern::Object(entries) => {
+                for entry in entries {
+                    self.check_expr(&entry.key, scope, errors);
+                    scope.vars.extend(entry.var.clone());
+                    if let Some(pattern) = &entry.pattern {
+                        self.check_pattern(pattern, scope, errors);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs `expr` on `input`, passing every output to `out`.
+    pub fn eval(&self, expr: &'static Expr, env: &Rc<Env>, input: Pv, out: Out) -> Res {
+        let depth = self.depth.get();
+        if depth >= MAX_DEPTH {
+            return fail("maximum recursion depth exceeded");
+        }
+        self.depth.set(depth + 1);
+        let result = self.eval_at(expr, env, input, out);
+        self.depth.set(depth);
+        result
+    }
+
+    fn eval_at(&self, expr: &'static Expr, env: &Rc<Env>, input: Pv, out: Out) -> Res {
+        match expr {
+            Expr::Identity => out(input),
+            Expr::Literal(value) => self.emit(&input, value.clone(), out),
+            Expr::Str(format, parts) => {
+                let value = input.value.clone();
+                self.interpolate(
+                    format.as_deref(),
+                    parts,
+                    env,
+                    &value,
+                    String::new(),
+                    &mut |s| self.emit(&input, Value::from(s), out),
+                )
+            }
+            Expr::Format(name) => {
+                let text = format::apply(name, &input.value)?;
+                self.emit(&input, Value::from(text), out)
+            }
+            Expr::Index(target, key) => {
+                self.eval(key, env, Pv::value(input.value.clone()), &mut |key| {
+                    self.eval(target, env, input.clone(), &mut |container| {
+                        let value = ops::index(&container.value, &key.value)?;
+                        out(container.child(value, || key.value.clone()))
+                    })
+                })
+            }
+            Expr::Slice(target, from, to) => self.eval_slice(target, from, to, env, input, out),
+            Expr::Iterate(target) => self.eval(target, env, input, &mut |container| {
+                self.iterate(&container, out)
+            }),
+            Expr::Try(body, handler) => self.eval_try(body, handler.as_deref(), env, input, out),
+            Expr::Array(None) => self.emit(&input, Value::array(Vec::new()), out),
+            Expr::Array(Some(inner)) => {
+                let items = self.collect(inner, env, &input.value)?;
+                self.emit(&input, Value::array(items), out)
+            }
+            Expr::Object(entries) => {
+                let value = input.value.clone();
+                self.object(entries, env, &value, Map::new(), &mut |object| {
+                    self.emit(&input, object, out)
+                })
+            }
+            Expr::Neg(operand) => self.eval(
+                operand,
+                env,
+                Pv::value(input.value.clone()),
+                &mut |pv| match pv.value {
+                    Value::Number(n, _) => self.emit(&input, Value::from(-n), out),
+                    other => fail(format!("{} cannot be negated", other.describe())),
+                },
+            ),
+            Expr::Pipe(left, right) => {
+                self.eval(left, env, input, &mut |pv| self.eval(right, env, pv, out))
+            }
+            Expr::Comma(left, right) => {
+                self.eval(left, env, input.clone(), out)?;
+                self.eval(right, env, input, out)
+            }
+            Expr::Binary(op, left, right) => {
+                self.eval(right, env, Pv::value(input.value.clone()), &mut |r| {
+                    self.eval(left, env, Pv::value(input.value.clone()), &mut |l| {
+                        self.emit(&input, ops::binary(*op, &l.value, &r.value)?, out)
+                    })
+                })
+            }
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                let is_and = matches!(expr, Expr::And(..));
+                self.eval(left, env, Pv::value(input.value.clone()), &mut |l| {
+                    if l.value.is_truthy() != is_and {
+                        return self.emit(&input, Value::Bool(!is_and), out);
+                    }
+                    self.eval(right, env, Pv::value(input.value.clone()), &mut |r| {
+                        self.emit(&input, Value::Bool(r.value.is_truthy()), out)
+                    })
+                })
+            }
+            Expr::Alt(left, right) => self.eval_alt(left, right, env, input, out),
+            Expr::Update(op, lhs, rhs) => self.update(*op, lhs, rhs, env, input, out),
+            Expr::If(branches, otherwise) => {
+                self.branch(branches, otherwise.as_deref(), env, input, out)
+            }
+            Expr::Reduce {
+                source,
+                pattern,
+                init,
+                update,
+            } => self.eval_reduce(source, pattern, init, update, env, input, out),
+            Expr::Foreach {
+                source,
+                pattern,
+                init,
+                update,
+                extract,
+            } => self.eval_foreach(
+                source,
+                pattern,
+                init,
+                update,
+                extract.as_deref(),
+                env,
+                input,
+                out,
+            ),
+            Expr::Def(def, rest) => {
+                let env = Rc::new(Env::Def {
+                    def,
+                    parent: env.clone(),
+                });
+                self.eval(rest, &env, input, out)
+            }
+            Expr::Call(name, args) => match env.function(name, args.len()) {
+                Some(Found::Closure(body, scope)) => self.eval(body, scope, input, out),
+                Some(Found::Value(value)) => self.emit(&input, value.clone(), out),
+                Some(Found::Def(def, scope)) => self.call(def, scope, args, env, input, out),
+                None => match self.defs.get(&(&**name, args.len())) {
+                    Some(def) => self.call(def, &self.top, args, env, input, out),
+                    None => builtins::call(self, name, args, env, input, out),
+                },
+            },
+            Expr::Var(name) => {
+                let value = match env.var(name) {
+                    Some(value) => value.clone(),
+                    None => return fail(format!("${name} is not defined")),
+                };
+                self.emit(&input, value, out)
+            }
+            Expr::Bind(source, patterns, body) => {
+                self.eval_bind(source, patterns, body, env, input, out)
+            }
+            Expr::Label(name, body) => {
+                let id = self.fresh_id();
+                let env = Rc::new(Env::Label {
+                    name: name.clone(),
+                    id,
+                    parent: env.clone(),
+                });
+                match self.eval(body, &env, input, out) {
+                    Err(Flow::Break(broken)) if broken == id => Ok(()),
+                    result => result,
+                }
+            }
+            Expr::Break(name) => match env.label(name) {
+                Some(id) => Err(Flow::Break(id)),
+                None => fail(format!("$*label-{name} is not defined")),
+            },
+            Expr::Loc(line) => self.emit(&input, location(*line), out),
+        }
+    }
+
+    #[inline(never)]
+    fn eval_slice(
+        &self,
+        target: &'static Expr,
+        from: &'static Option<Box<Expr>>,
+        to: &'static Option<Box<Expr>>,
+        env: &Rc<Env>,
+        input: Pv,
+        out: Out,
+    ) -> Res {
+        let bound = |bound: &'static Option<Box<Expr>>, f: &mut dyn FnMut(Value) -> Res| match bound
+        {
+            Some(e) => self.eval(
+                e,
+                env,
+                Pv::value(input.value.clone()),
+                &mut |pv| f(pv.value),
+            ),
+            None => f(Value::Null),
+        };
+        bound(to, &mut |to| {
+            bound(from, &mut |from| {
+                self.eval(target, env, input.clone(), &mut |container| {
+                    let value = ops::slice(&container.value, &from, &to)?;
+                    out(container.child(value, || ops::slice_key(&from, &to)))
+                })
+            })
+        })
+    }
+
+    #[inline(never)]
+    fn eval_try(
+        &self,
+        body: &'static Expr,
+        handler: Option<&'static Expr>,
+        env: &Rc<Env>,
+        input: Pv,
+        out: Out,
+    ) -> Res {
+        match self.trap(out, &mut |out| self.eval(body, env, input.clone(), out)) {
+            Ok(result) => result,
+            Err(error) => match handler {
+                Some(handler) => self.eval(handler, env, Pv::value(error), &mut |pv| {
+                    self.emit(&input, pv.value, out)
+                }),
+                None => Ok(()),
+            },
+        }
+    }
+
+    #[inline(never)]
+    fn eval_alt(
+        &self,
+        left: &'static Expr,
+        right: &'static Expr,
+        env: &Rc<Env>,
+        input: Pv,
+        out: Out,
+    ) -> Res {
+        let mut any = false;
+        let trapped = self.trap(out, &mut |out| {
+            self.eval(left, env, input.clone(), &mut |pv| {
+                if pv.value.is_truthy() {
+                    any = true;
+                    out(pv)?;
+                }
+                Ok(())
+            })
+        });
+        if let Ok(result) = trapped {
+            result?;
+        }
+        if any {
+            Ok(())
+        } else {
+            self.eval(right, env, input, out)
+        }
+    }
+
+    #[inline(never)]
+    #[allow(clippy::too_many_arguments)]
+    fn eval_reduce(
+        &self,
+        source: &'static Expr,
+        pattern: &'static Pattern,
+        init: &'static Expr,
+        update: &'static Expr,
+        env: &Rc<Env>,
+        input: Pv,
+        out: Out,
+    ) -> Res {
+        self.eval(init, env, input.clone(), &mut |init| {
+            let mut state = Some(init);
+            self.eval(source, env, Pv::value(input.value.clone()), &mut |item| {
+                self.bind(pattern, item.value, env, &input.value, &mut |scope| {
+                    let current = state.take().unwrap_or_else(|| Pv::value(Value::Null));
+                    self.eval(update, &scope, current, &mut |next| {
+                        state = Some(next);
+                        Ok(())
+                    })
+                })
+            })?;
+            match state.take() {
+                Some(result) => out(result),
+                None => self.emit(&input, Value::Null, out),
+            }
+        })
+    }
+
+    #[inline(never)]
+    #[allow(clippy::too_many_arguments)]
+    fn eval_foreach(
+        &self,
+        source: &'static Expr,
+        pattern: &'static Pattern,
+        init: &'static Expr,
+        update: &'static Expr,
+        extract: Option<&'static Expr>,
+        env: &Rc<Env>,
+        input: Pv,
+        out: Out,
+    ) -> Res {
+        self.eval(init, env, input.clone(), &mut |init| {
+            let mut state = init;
+            self.eval(source, env, Pv::value(input.value.clone()), &mut |item| {
+                self.bind(pattern, item.value, env, &input.value, &mut |scope| {
+                    let current = state.clone();
+                    self.eval(update, &scope, current, &mut |next| {
+                        state = next.clone();
+                        match extract {
+                            Some(extract) => self.eval(extract, &scope, next, out),
+                            None => out(next),
+                        }
+                    })
+                })
+            })
+        })
+    }
+
+    #[inline(never)]
+    fn eval_bind(
+        &self,
+        source: &'static Expr,
+        patterns: &'static [Pattern],
+        body: &'static Expr,
+        env: &Rc<Env>,
+        input: Pv,
+        out: Out,
+    ) -> Res {
+        self.eval(source, env, Pv::value(input.value.clone()), &mut |pv| {
+            if let [pattern] = patterns {
+                return self.bind(pattern, pv.value, env, &input.value, &mut |scope| {
+                    self.eval(body, &scope, input.clone(), out)
+                });
+            }
+            // `?//`: every variable of every alternative is bound, to null
+            // when the alternative in use does not mention it
+            let mut names = Vec::new();
+            for pattern in patterns {
+                pattern.variables(&mut names);
+            }
+            let base = names.into_iter().fold(env.clone(), |parent, name| {
+                Rc::new(Env::Var {
+                    name,
+                    value: Value::Null,
+                    callable: false,
+                    parent,
+                })
+            });
+            for (i, pattern) in patterns.iter().enumerate() {
+                let attempt = self.trap(out, &mut |out| {
+                    self.bind(
+                        pattern,
+                        pv.value.clone(),
+                        &base,
+                        &input.value,
+                        &mut |scope| self.eval(body, &scope, input.clone(), out),
+                    )
+                });
+                match attempt {
+                    Ok(result) => return result,
+                    Err(error) if i + 1 == patterns.len() => return Err(Flow::Error(error)),
+                    Err(_) => {}
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Outputs `value`, which is not a location in the input: fine unless
+    /// paths are being tracked.
+    pub fn emit(&self, input: &Pv, value: Value, out: Out) -> Res {
+        if input.path.is_some() {
+            return fail(format!(
+                "Invalid path expression with result {}",
+                value.preview()
+            ));
+        }
+        out(Pv::value(value))
+    }
+
+    /// Runs `body`, telling errors it raises itself (returned as `Err`)
+    /// apart from anything `out` raises downstream, which passes through.
+    pub fn trap(&self, out: Out, body: &mut dyn FnMut(Out) -> Res) -> Result<Res, Value> {
+        let id = self.fresh_id();
+        let mut downstream = None;
+        let result = body(&mut |pv| {
+            out(pv).map_err(|flow| {
+                downstream = Some(flow);
+                Flow::Break(id)
+            })
+        });
+        match result {
+            Err(Flow::Break(broken)) if broken == id => {
+                Ok(Err(downstream.take().unwrap_or(Flow::Break(id))))
+            }
+            Err(Flow::Error(error)) => Err(error),
+            result => Ok(result),
+        }
+    }
+
+    /// Every output of `expr` on `input`.
+    pub fn collect(
+        &self,
+        expr: &'static Expr,
+        env: &Rc<Env>,
+        input: &Value,
+    ) -> Result<Vec<Value>, Flow> {
+        let mut values = Vec::new();
+        self.eval(expr, env, Pv::value(input.clone()), &mut |pv| {
+            values.push(pv.value);
+            Ok(())
+        })?;
+        Ok(values)
+    }
+
+    /// Every output of `expr`, keeping paths.
+    pub fn collect_pv(
+        &self,
+        expr: &'static Expr,
+        env: &Rc<Env>,
+        input: Pv,
+    ) -> Result<Vec<Pv>, Flow> {
+        let mut outputs = Vec::new();
+        self.eval(expr, env, input, &mut |pv| {
+            outputs.push(pv);
+            Ok(())
+        })?;
+        Ok(outputs)
+    }
+
+    /// The first output of `expr`, without running it any further.
+    pub fn first(&self, expr: &'static Expr, env: &Rc<Env>, input: Pv) -> Result<Option<Pv>, Flow> {
+        let id = self.fresh_id();
+        let mut found = None;
+        let result = self.eval(expr, env, input, &mut |pv| {
+            found = Some(pv);
+            Err(Flow::Break(id))
+        });
+        match result {
+            Err(Flow::Break(broken)) if broken == id => Ok(found),
+            result => result.map(|()| None),
+        }
+    }
+
+    /// The paths of `expr`'s outputs, relative to `input`.
+    pub fn paths(
+        &self,
+        expr: &'static Expr,
+        env: &Rc<Env>,
+        input: &Value,
+    ) -> Result<Vec<Vec<Value>>, Flow> {
+        let mut paths = Vec::new();
+        let start = Pv {
+            value: input.clone(),
+            path: Some(Vec::new()),
+        };
+        self.eval(expr, env, start, &mut |pv| {
+            paths.push(pv.path.unwrap_or_default());
+            Ok(())
+        })?;
+        Ok(paths)
+    }
+
+    pub fn iterate(&self, container: &Pv, out: Out) -> Res {
+        match &container.value {
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    out(container.child(item.clone(), || Value::from(i)))?;
+                }
+                Ok(())
+            }
+            Value::Object(map) => {
+                for (key, item) in map.iter() {
+                    out(container.child(item.clone(), || Value::String(key.clone())))?;
+                }
+                Ok(())
+            }
+            Value::Null => fail("Cannot iterate over null"),
+            other => fail(format!("Cannot iterate over {}", other.describe())),
+        }
+    }
+
+    /// Evaluates `args` against `input` and calls `f` with every
+    /// combination of their values, the last argument varying slowest.
+    pub fn cartesian(
+        &self,
+        args: &'static [Expr],
+        env: &Rc<Env>,
+        input: &Value,
+        values: &mut Vec<Value>,
+        f: &mut dyn FnMut(&[Value]) -> Res,
+    ) -> Res {
+        let Some((last, rest)) = args.split_last() else {
+            return f(values);
+        };
+        self.eval(last, env, Pv::value(input.clone()), &mut |pv| {
+            values[rest.len()] = pv.value;
+            self.cartesian(rest, env, input, values, f)
+        })
+    }
+
+    fn call(
+        &self,
+        def: &'static FuncDef,
+        scope: &Rc<Env>,
+        args: &'static [Expr],
+        caller: &Rc<Env>,
+        input: Pv,
+        out: Out,
+    ) -> Res {
+        self.bind_params(def, 0, scope.clone(), args, caller, &input, out)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn bind_params(
+        &self,
+        def: &'static FuncDef,
+        index: usize,
+        scope: Rc<Env>,
+        args: &'static [Expr],
+        caller: &Rc<Env>,
+        input: &Pv,
+        out: Out,
+    ) -> Res {
+        let Some(param) = def.params.get(index) else {
+            return self.eval(&def.body, &scope, input.clone(), out);
+        };
+        let arg = &args[index];
+        if !param.is_var {
+            let scope = Rc::new(Env::Closure {
+                name: param.name.clone(),
+                body: arg,
+                env: caller.clone(),
+                parent: scope,
+            });
+            return self.bind_params(def, index + 1, scope, args, caller, input, out);
+        }
+        self.eval(arg, caller, Pv::value(input.value.clone()), &mut |pv| {
+            let scope = Rc::new(Env::Var {
+                name: param.name.clone(),
+                value: pv.value,
+                callable: true,
+                parent: scope.clone(),
+            });
+            self.bind_params(def, index + 1, scope, args, caller, input, out)
+        })
+    }
+
+    fn branch(
+        &self,
+        branches: &'static [(Expr, Expr)],
+        otherwise: Option<&'static Expr>,
+        env: &Rc<Env>,
+        input: Pv,
+        out: Out,
+    ) -> Res {
+        let Some(((cond, then), rest)) = branches.split_first() else {
+            return match otherwise {
+                Some(otherwise) => self.eval(otherwise, env, input, out),
+                None => out(input),
+            };
+        };
+        self.eval(cond, env, Pv::value(input.value.clone()), &mut |pv| {
+            if pv.value.is_truthy() {
+                self.eval(then, env, input.clone(), out)
+            } else {
+                self.branch(rest, otherwise, env, input.clone(), out)
+            }
+        })
+    }
+
+    /// String interpolation. Parts are filled in from the last, which makes
+    /// the last interpolation vary slowest, as in jq.
+    fn interpolate(
+        &self,
+        format: Option<&str>,
+        parts: &'static [StrPart],
+        env: &Rc<Env>,
+        input: &Value,
+        tail: String,
+        out: &mut dyn FnMut(String) -> Res,
+    ) -> Res {
+        let Some((last, rest)) = parts.split_last() else {
+            return out(tail);
+        };
+        match last {
+            StrPart::Lit(text) => {
+                self.interpolate(format, rest, env, input, format!("{text}{tail}"), out)
+            }
+            StrPart::Interp(expr) => self.eval(expr, env, Pv::value(input.clone()), &mut |pv| {
+                let text = match format {
+                    Some(name) => format::apply(name, &pv.value)?,
+                    None => pv.value.to_text(),
+                };
+                self.interpolate(format, rest, env, input, format!("{text}{tail}"), out)
+            }),
+        }
+    }
+
+    fn object(
+        &self,
+        entries: &'static [(Expr, Expr)],
+        env: &Rc<Env>,
+        input: &Value,
+        map: Map,
+        out: &mut dyn FnMut(Value) -> Res,
+    ) -> Res {
+        let Some(((key, value), rest)) = entries.split_first() else {
+            return out(Value::object(map));
+        };
+        self.eval(key, env, Pv::value(input.clone()), &mut |key| {
+            let Value::String(key) = key.value else {
+                return fail(format!("Cannot use {} as object key", key.value.describe()));
+            };
+            self.eval(value, env, Pv::value(input.clone()), &mut |value| {
+                let mut map = map.clone();
+                map.insert(key.clone(), value.value);
+                self.object(rest, env, input, map, out)
+            })
+        })
+    }
+
+    /// Destructures `value` with `pattern`, calling `f` with the scope
+    /// holding its variables; `(expr)` keys are evaluated against `input`.
+    fn bind(
+        &self,
+        pattern: &'static Pattern,
+        value: Value,
+        env: &Rc<Env>,
+        input: &Value,
+        f: &mut dyn FnMut(Rc<Env>) -> Res,
+    ) -> Res {
+        match pattern {
+            Pattern::Var(name) => f(Rc::new(Env::Var {
+                name: name.clone(),
+                value,
+                callable: false,
+                parent: env.clone(),
+            })),
+            Pattern::Array(items) => {
+                if !matches!(value, Value::Array(_) | Value::Null) {
+                    return fail(format!("Cannot index {} with number", value.type_name()));
+                }
+                self.bind_items(items, 0, &value, env.clone(), input, f)
+            }
+            Pattern::Object(entries) => self.bind_entries(entries, &value, env.clone(), input, f),
+        }
+    }
+
+    fn bind_items(
+        &self,
+        items: &'static [Pattern],
+        index: usize,
+        value: &Value,
+        env: Rc<Env>,
+        input: &Value,
+        f: &mut dyn FnMut(Rc<Env>) -> Res,
+    ) -> Res {
+        let Some((pattern, rest)) = items.split_first() else {
+            return f(env);
+        };
+        let item = ops::index(value, &Value::from(index))?;
+        self.bind(pattern, item, &env, input, &mut |scope| {
+            self.bind_items(rest, index + 1, value, scope, input, f)
+        })
+    }
+
+    fn bind_entries(
+        &self,
+        entries: &'static [ObjectPattern],
+        value: &Value,
+        env: Rc<Env>,
+        input: &Value,
+        f: &mut dyn FnMut(Rc<Env>) -> Res,
+    ) -> Res {
+        let Some((entry, rest)) = entries.split_first() else {
+            return f(env);
+        };
+        self.eval(&entry.key, &env, Pv::value(input.clone()), &mut |key| {
+            if !matches!(key.value, Value::String(_)) {
+                return fail(format!(
+                    "Cannot index {} with {}",
+                    value.type_name(),
+                    key.value.type_name()
+                ));
+            }
+            let item = ops::index(value, &key.value)?;
+            let mut scope = env.clone();
+            if let Some(name) = &entry.var {
+                scope = Rc::new(Env::Var {
+                    name: name.clone(),
+                    value: item.clone(),
+                    callable: false,
+                    parent: scope,
+                });
+            }
+            match &entry.pattern {
+                Some(pattern) => self.bind(pattern, item, &scope, input, &mut |scope| {
+                    self.bind_entries(rest, value, scope, input, f)
+                }),
+                None => self.bind_entries(rest, value, scope, input, f),
+            }
+        })
+    }
+
+    /// `=`, `|=` and the arithmetic assignments, over the paths `lhs` names.
+    fn update(
+        &self,
+        op: UpdateOp,
+        lhs: &'static Expr,
+        rhs: &'static Expr,
+        env: &Rc<Env>,
+        input: Pv,
+        out: Out,
+    ) -> Res {
+        let root = input.value.clone();
+        let paths = self.paths(lhs, env, &root)?;
+        if op == UpdateOp::Modify {
+            let mut result = root;
+            let mut deleted = Vec::new();
+            for path in paths {
+                let old = ops::getpath(&result, &path)?;
+                match self.first(rhs, env, Pv::value(old))? {
+                    Some(new) => result = ops::setpath(result, &path, new.value)?,
+                    None => deleted.push(Value::array(path)),
+                }
+            }
+            if !deleted.is_empty() {
+                result = ops::delpaths(result, deleted)?;
+            }
+            return self.emit(&input, result, out);
+        }
+        self.eval(rhs, env, Pv::value(root.clone()), &mut |pv| {
+            let mut result = root.clone();
+            for path in &paths {
+                let new = match op {
+                    UpdateOp::Arith(binary) => {
+                        ops::binary(binary, &ops::getpath(&result, path)?, &pv.value)?
+                    }
+                    UpdateOp::Alt => match ops::getpath(&result, path)? {
+                        old if old.is_truthy() => old,
+                        _ => pv.value.clone(),
+                    },
+                    _ => pv.value.clone(),
+                };
+                result = ops::setpath(result, path, new)?;
+            }
+            self.emit(&input, result, out)
+        })
+    }
+
+    /// The next value of the input stream, for the main loop and `input`.
+    pub fn next_input(&self) -> Option<Result<Value, String>> {
+        let reader = self.reader.borrow_mut().take();
+        if let Some(read) = reader {
+            *self.inputs.borrow_mut() = read();
+        }
+        let input = self.inputs.borrow_mut().pop_front()?;
+        *self.location.borrow_mut() = (input.file.clone(), input.line);
+        Some(input.value)
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    funcs: Vec<(Rc<str>, usize)>,
+    vars: Vec<Rc<str>>,
+    labels: Vec<Rc<str>>,
+}
+
+fn globals(env: &Env) -> Vec<Rc<str>> {
+    let mut names = Vec::new();
+    let mut node = env;
+    while let Env::Var { name, parent, .. } = node {
+        names.push(name.clone());
+        node = parent;
+    }
+    names
+}
@@ -0,0 +1,288 @@
+//! Splits a jq program into tokens. String interpolations are lexed
+//! recursively, so a string token carries the tokens of each `\(...)`.
+
+use std::rc::Rc;
+
+use super::value::Value;
+
+#[derive(Debug, Clone)]
+pub enum Tok {
+    /// A name or keyword.
+    Ident(Rc<str>),
+    /// `.name`
+    Field(Rc<str>),
+    /// `$name`
+    Var(Rc<str>),
+    /// `@name`
+    Format(Rc<str>),
+    Num(Value),
+    Str(Vec<LexPart>),
+    Op(&'static str),
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+pub enum LexPart {
+    Lit(String),
+    Interp(Vec<Token>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub tok: Tok,
+    /// Byte offset into the program, for error messages.
+    pub pos: usize,
+}
+
+/// Longest first, so `//=` wins over `//` and `/`.
+const OPS: &[&str] = &[
+    "?//", "//=", "|=", "+=", "-=", "*=", "/=", "%=", "==", "!=", "<=", ">=", "//", "..", ".", "[",
+    "]", "{", "}", "(", ")", "|", ",", ":", ";", "=", "<", ">", "+", "-", "*", "/", "%", "?",
+];
+
+pub struct LexError {
+    pub message: String,
+    pub pos: usize,
+}
+
+pub fn tokenize(src: &str) -> Result<Vec<Token>, LexError> {
+    let mut lexer = Lexer { src, pos: 0 };
+    let tokens = lexer.tokens(false)?;
+    Ok(tokens)
+}
+
+struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+fn is_ident_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_'
+}
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+impl Lexer<'_> {
+    fn error<T>(&self, message: impl Into<String>, pos: usize) -> Result<T, LexError> {
+        Err(LexError {
+            message: message.into(),
+            pos,
+        })
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.src.as_bytes().get(self.pos + offset).copied()
+    }
+
+    /// Lexes to the end of input, or to the `)` closing an interpolation.
+    fn tokens(&mut self, interpolation: bool) -> Result<Vec<Token>, LexError> {
+        let mut tokens = Vec::new();
+        let mut depth = 0usize;
+        loop {
+            self.skip_space();
+            let start = self.pos;
+            let Some(c) = self.peek() else {
+                if interpolation {
+                    return self.error("unterminated string interpolation", start);
+                }
+                tokens.push(Token {
+                    tok: Tok::Eof,
+                    pos: start,
+                });
+                return Ok(tokens);
+            };
+            let tok = match c {
+                b'"' => {
+                    self.pos += 1;
+                    self.string()?
+                }
+                b'.' if self.peek_at(1).is_some_and(is_ident_start) => {
+                    self.pos += 1;
+                    Tok::Field(self.ident())
+                }
+                b'.' if self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) => self.number(),
+                b'$' if self.peek_at(1).is_some_and(is_ident_start) => {
+                    self.pos += 1;
+                    Tok::Var(self.ident())
+                }
+                b'@' if self.peek_at(1).is_some_and(is_ident_start) => {
+                    self.pos += 1;
+                    Tok::Format(self.ident())
+                }
+                c if is_ident_start(c) => Tok::Ident(self.ident()),
+                c if c.is_ascii_digit() => self.number(),
+                _ => {
+                    let rest = &self.src[self.pos..];
+                    let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) else {
+                        let c = rest.chars().next().unwrap_or_default();
+                        return self.error(
+                            format!("syntax error, unexpected INVALID_CHARACTER '{c}'"),
+                            start,
+                        );
+                    };
+                    self.pos += op.len();
+                    match *op {
+                        "(" => depth += 1,
+                        ")" if interpolation && depth == 0 => return Ok(self.finish(tokens)),
+                        ")" => depth = depth.saturating_sub(1),
+                        _ => {}
+                    }
+                    Tok::Op(op)
+                }
+            };
+            tokens.push(Token { tok, pos: start });
+        }
+    }
+
+    fn finish(&self, mut tokens: Vec<Token>) -> Vec<Token> {
+        tokens.push(Token {
+            tok: Tok::Eof,
+            pos: self.pos,
+        });
+        tokens
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() {
+                self.pos += 1;
+            } else if c == b'#' {
+                while self.peek().is_some_and(|c| c != b'\n') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// A name, including module-qualified `a::b`.
+    fn ident(&mut self) -> Rc<str> {
+        let start = self.pos;
+        loop {
+            while self.peek().is_some_and(is_ident_char) {
+                self.pos += 1;
+            }
+            if self.src[self.pos..].starts_with("::") && self.peek_at(2).is_some_and(is_ident_start)
+            {
+                self.pos += 2;
+            } else {
+                break;
+            }
+        }
+        Rc::from(&self.src[start..self.pos])
+    }
+
+    fn number(&mut self) -> Tok {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let mut integer = true;
+        if self.peek() == Some(b'.') && self.peek_at(1) != Some(b'.') {
+            integer = false;
+            self.pos += 1;
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1;
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            let sign = usize::from(matches!(self.peek_at(1), Some(b'+' | b'-')));
+            if self.peek_at(1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                integer = false;
+                self.pos += 1 + sign;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+            }
+        }
+        let text = &self.src[start..self.pos];
+        let value: f64 = text.parse().unwrap_or(f64::NAN);
+        if integer {
+            let digits = text.trim_start_matches('0');
+            Tok::Num(Value::integer_literal(
+                if digits.is_empty() { "0" } else { digits },
+                value,
+            ))
+        } else {
+            Tok::Num(Value::from(value))
+        }
+    }
+
+    /// The rest of a string literal after its opening quote.
+    fn string(&mut self) -> Result<Tok, LexError> {
+        let start = self.pos - 1;
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        loop {
+            let Some(c) = self.src[self.pos..].chars().next() else {
+                return self.error("unterminated string literal", start);
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => break,
+                '\\' => {
+                    let Some(escape) = self.src[self.pos..].chars().next() else {
+                        return self.error("unterminated string literal", start);
+                    };
+                    self.pos += escape.len_utf8();
+                    match escape {
+                        '"' | '\\' | '/' => text.push(escape),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'u' => text.push(self.unicode_escape()?),
+                        '(' => {
+                            if !text.is_empty() {
+                                parts.push(LexPart::Lit(std::mem::take(&mut text)));
+                            }
+                            parts.push(LexPart::Interp(self.tokens(true)?));
+                        }
+                        other => {
+                            return self.error(format!("invalid escape '\\{other}'"), self.pos - 2);
+                        }
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() || parts.is_empty() {
+            parts.push(LexPart::Lit(text));
+        }
+        Ok(Tok::Str(parts))
+    }
+
+    fn hex4(&mut self) -> Result<u32, LexError> {
+        let digits = self.src.get(self.pos..self.pos + 4).unwrap_or("");
+        match u32::from_str_radix(digits, 16) {
+            Ok(n) if digits.len() == 4 => {
+                self.pos += 4;
+                Ok(n)
+            }
+            _ => self.error("invalid \\u escape", self.pos),
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, LexError> {
+        let high = self.hex4()?;
+        if (0xD800..0xDC00).contains(&high) && self.src[self.pos..].starts_with("\\u") {
+            let save = self.pos;
+            self.pos += 2;
+            let low = self.hex4()?;
+            if (0xDC00..0xE000).contains(&low) {
+                let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                return Ok(char::from_u32(code).unwrap_or('\u{FFFD}'));
+            }
+            self.pos = save;
+        }
+        Ok(char::from_u32(high).unwrap_or('\u{FFFD}'))
+    }
+}
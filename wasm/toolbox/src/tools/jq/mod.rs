@@ -0,0 +1,588 @@
+//! `jq`: a parser and interpreter for the jq language. Builtins that need
+//! the interpreter or run hot are native (`builtins.rs`); the rest are
+//! written in jq (`builtins.jq`) and parsed at startup.
+
+mod ast;
+mod builtins;
+mod format;
+mod interp;
+mod lexer;
+mod ops;
+mod parser;
+mod time;
+mod value;
+
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use interp::{Flow, Input, Interp, Pv, leak};
+use value::{Map, Style, Value};
+
+/// The version `jq --version` reports; the language follows jq 1.7.
+const VERSION: &str = "jq-1.7.1";
+
+const PRELUDE: &str = concat!(include_str!("builtins.jq"), ".");
+
+#[derive(Clone, Default)]
+struct Options {
+    filter: Option<String>,
+    files: Vec<String>,
+    null_input: bool,
+    raw_input: bool,
+    slurp: bool,
+    raw_output: bool,
+    join_output: bool,
+    nul_output: bool,
+    exit_status: bool,
+    seq: bool,
+    stream: bool,
+    compact: bool,
+    tab: bool,
+    indent: Option<usize>,
+    sort_keys: bool,
+    ascii: bool,
+    named: Vec<(String, Value)>,
+    positional: Vec<Value>,
+}
+
+pub fn run(args: &[String]) -> i32 {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(code) => return code,
+    };
+    let program_text = options.filter.clone().unwrap_or_else(|| ".".to_string());
+
+    let program = match parser::parse(&program_text) {
+        Ok(program) => leak(program),
+        Err(e) => {
+            let line = program_text[..e.pos.min(program_text.len())]
+                .matches('\n')
+                .count()
+                + 1;
+            eprintln!(
+                "jq: error: {} at <top-level>, line {line}:\n{program_text}",
+                e.message
+            );
+            eprintln!("jq: 1 compile error");
+            return 3;
+        }
+    };
+    let prelude =
+        leak(parser::parse(PRELUDE).unwrap_or_else(|e| panic!("jq prelude: {}", e.message)));
+
+    let interp = Interp::new(prelude, globals(&options));
+    let mut errors = Vec::new();
+    interp.check(program, &mut errors);
+    if !errors.is_empty() {
+        for message in &errors {
+            eprintln!("jq: error: {message} at <top-level>, line 1:\n{program_text}");
+        }
+        match errors.len() {
+            1 => eprintln!("jq: 1 compile error"),
+            n => eprintln!("jq: {n} compile errors"),
+        }
+        return 3;
+    }
+
+    let mut status = 0;
+    if options.null_input && options.files.is_empty() {
+        // like jq, `-n` leaves stdin alone unless `input` or `inputs` asks
+        let options = options.clone();
+        *interp.reader.borrow_mut() =
+            Some(Box::new(move || read_inputs(&options).unwrap_or_default()));
+    } else {
+        match read_inputs(&options) {
+            Ok(inputs) => *interp.inputs.borrow_mut() = inputs,
+            Err(code) => status = code,
+        }
+    }
+
+    let style = Style {
+        indent: if options.compact || options.tab {
+            0
+        } else {
+            options.indent.unwrap_or(2)
+        },
+        tab: options.tab && !options.compact,
+        sort_keys: options.sort_keys,
+        ascii: options.ascii,
+    };
+    let stdout = io::stdout();
+    let mut stdout = io::BufWriter::new(stdout.lock());
+    let mut last_output = None;
+    let mut text = String::new();
+
+    let mut run_one = |input: Value| -> Option<i32> {
+        let result = interp.eval(program, &interp.top, Pv::value(input), &mut |pv| {
+            text.clear();
+            if options.seq {
+                text.push('\x1e');
+            }
+            match &pv.value {
+                Value::String(s) if options.raw_output => text.push_str(s),
+                other => value::write_value(&mut text, other, &style, 0),
+            }
+            if options.nul_output {
+                text.push('\0');
+            } else if !options.join_output {
+                text.push('\n');
+            }
+            let _ = stdout.write_all(text.as_bytes());
+            last_output = Some(pv.value);
+            Ok(())
+        });
+        let _ = stdout.flush();
+        match result {
+            Ok(()) | Err(Flow::Break(_)) => None,
+            Err(Flow::Halt(code)) => Some(code),
+            Err(Flow::Error(error)) => {
+                let at = if options.null_input {
+                    "<unknown>".to_string()
+                } else {
+                    location(&interp)
+                };
+                match error {
+                    Value::String(message) => eprintln!("jq: error (at {at}): {message}"),
+                    other => eprintln!("jq: error (at {at}) (not a string): {}", other.to_json()),
+                }
+                status = 5;
+                None
+            }
+        }
+    };
+
+    if options.null_input {
+        if let Some(code) = run_one(Value::Null) {
+            return code;
+        }
+    } else {
+        while let Some(next) = interp.next_input() {
+            match next {
+                Ok(input) => {
+                    if let Some(code) = run_one(input) {
+                        return code;
+                    }
+                }
+                Err(message) => {
+                    eprintln!("jq: error (at {}): {message}", location(&interp));
+                    return 2;
+                }
+            }
+        }
+    }
+
+    if status == 0 && options.exit_status {
+        return match last_output {
+            None => 4,
+            Some(value) if !value.is_truthy() => 1,
+            Some(_) => 0,
+        };
+    }
+    status
+}
+
+fn location(interp: &Interp) -> String {
+    let (file, line) = &*interp.location.borrow();
+    format!("{}:{line}", file.as_deref().unwrap_or("<stdin>"))
+}
+
+fn parse_options(args: &[String]) -> Result<Options, i32> {
+    let mut options = Options::default();
+    let mut positional_args = None;
+    let mut from_file = false;
+    let mut i = 0;
+    // The `count` values following option `args[i]`, advancing past them
+    let take = |i: &mut usize, count: usize| -> Result<Vec<String>, i32> {
+        let values = args.get(*i + 1..*i + 1 + count).ok_or_else(|| {
+            eprintln!(
+                "jq: {} takes {count} parameter{}",
+                args[*i],
+                if count == 1 { "" } else { "s" }
+            );
+            2
+        })?;
+        *i += count;
+        Ok(values.to_vec())
+    };
+    let mut only_positional = false;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        if only_positional || !arg.starts_with('-') || arg == "-" {
+            if options.filter.is_none() && !from_file {
+                options.filter = Some(arg.to_string());
+            } else if let Some(json) = positional_args {
+                let value = if json {
+                    value::parse_json(arg).map_err(|_| {
+                        eprintln!("jq: Invalid JSON text passed to --jsonargs");
+                        2
+                    })?
+                } else {
+                    Value::str(arg)
+                };
+                options.positional.push(value);
+            } else {
+                options.files.push(arg.to_string());
+            }
+            i += 1;
+            continue;
+        }
+        match arg {
+            "--" => only_positional = true,
+            "-h" | "--help" => {
+                print_usage();
+                return Err(0);
+            }
+            "-V" | "--version" => {
+                println!("{VERSION}");
+                return Err(0);
+            }
+            "--null-input" => options.null_input = true,
+            "--raw-input" => options.raw_input = true,
+            "--slurp" => options.slurp = true,
+            "--raw-output" => options.raw_output = true,
+            "--join-output" => {
+                options.raw_output = true;
+                options.join_output = true;
+            }
+            "--raw-output0" => {
+                options.raw_output = true;
+                options.nul_output = true;
+            }
+            "--ascii-output" => options.ascii = true,
+            "--compact-output" => options.compact = true,
+            "--sort-keys" => options.sort_keys = true,
+            "--exit-status" => options.exit_status = true,
+            "--tab" => options.tab = true,
+            "--seq" => options.seq = true,
+            "--stream" => options.stream = true,
+            // Output is never colored and always flushed per input
+            "--color-output" | "--monochrome-output" | "--unbuffered" => {}
+            "--indent" => {
+                let value = take(&mut i, 1)?;
+                match value[0].parse::<usize>() {
+                    Ok(n) if n <= 7 => options.indent = Some(n),
+                    Ok(_) => {
+                        eprintln!("jq: Cannot indent more than 7 characters");
+                        return Err(2);
+                    }
+                    Err(_) => {
+                        eprintln!("jq: --indent takes a number");
+                        return Err(2);
+                    }
+                }
+            }
+            "--arg" => {
+                let value = take(&mut i, 2)?;
+                options
+                    .named
+                    .push((value[0].clone(), Value::str(&value[1])));
+            }
+            "--argjson" => {
+                let value = take(&mut i, 2)?;
+                let Ok(json) = value::parse_json(&value[1]) else {
+                    eprintln!("jq: Invalid JSON text passed to --argjson");
+                    return Err(2);
+                };
+                options.named.push((value[0].clone(), json));
+            }
+            "--slurpfile" | "--rawfile" => {
+                let value = take(&mut i, 2)?;
+                let text = std::fs::read_to_string(&value[1]).map_err(|e| {
+                    eprintln!("jq: error: Could not open {}: {}", value[1], io_error(&e));
+                    2
+                })?;
+                let content = if arg == "--rawfile" {
+                    Value::from(text)
+                } else {
+                    let mut values = Vec::new();
+                    for item in serde_json::Deserializer::from_str(&text).into_iter::<Value>() {
+                        values.push(item.map_err(|e| {
+                            eprintln!("jq: Bad JSON in --slurpfile {} {}: {e}", value[0], value[1]);
+                            2
+                        })?);
+                    }
+                    Value::array(values)
+                };
+                options.named.push((value[0].clone(), content));
+            }
+            "--args" => positional_args = Some(false),
+            "--jsonargs" => positional_args = Some(true),
+            "-f" | "--from-file" => {
+                let value = take(&mut i, 1)?;
+                let text = std::fs::read_to_string(&value[0]).map_err(|e| {
+                    eprintln!("jq: error: Could not open {}: {}", value[0], io_error(&e));
+                    2
+                })?;
+                // a filter given earlier was really the first input file
+                if let Some(filter) = options.filter.take() {
+                    options.files.push(filter);
+                }
+                options.filter = Some(text);
+                from_file = true;
+            }
+            "-L" => {
+                take(&mut i, 1)?;
+            }
+            flags if !flags.starts_with("--") => {
+                for flag in flags[1..].chars() {
+                    match flag {
+                        'n' => options.null_input = true,
+                        'R' => options.raw_input = true,
+                        's' => options.slurp = true,
+                        'r' => options.raw_output = true,
+                        'j' => {
+                            options.raw_output = true;
+                            options.join_output = true;
+                        }
+                        'a' => options.ascii = true,
+                        'c' => options.compact = true,
+                        'S' => options.sort_keys = true,
+                        'e' => options.exit_status = true,
+                        'C' | 'M' => {}
+                        _ => return Err(unknown_option(arg)),
+                    }
+                }
+            }
+            _ => return Err(unknown_option(arg)),
+        }
+        i += 1;
+    }
+    Ok(options)
+}
+
+fn unknown_option(arg: &str) -> i32 {
+    eprintln!("jq: Unknown option: {arg}");
+    eprintln!("Use jq --help for help with command-line options.");
+    2
+}
+
+/// `$ENV`, `$ARGS` and the `--arg`-style variables.
+fn globals(options: &Options) -> Vec<(Rc<str>, Value)> {
+    let env: Map = std::env::vars()
+        .map(|(k, v)| (Rc::from(k), Value::from(v)))
+        .collect();
+    let named: Map = options
+        .named
+        .iter()
+        .map(|(name, value)| (Rc::from(name.as_str()), value.clone()))
+        .collect();
+    let mut args = Map::new();
+    args.insert(
+        Rc::from("positional"),
+        Value::array(options.positional.clone()),
+    );
+    args.insert(Rc::from("named"), Value::object(named));
+
+    let mut globals = vec![
+        (Rc::from("ENV"), Value::object(env)),
+        (Rc::from("ARGS"), Value::object(args)),
+    ];
+    for (name, value) in &options.named {
+        globals.push((Rc::from(name.as_str()), value.clone()));
+    }
+    globals
+}
+
+/// Reads every input file (or stdin) and splits it into the values the
+/// program will run on, following `-R`, `-s` and `--stream`.
+fn read_inputs(options: &Options) -> Result<std::collections::VecDeque<Input>, i32> {
+    let mut status = Ok(());
+    let mut sources: Vec<(Option<Rc<str>>, String)> = Vec::new();
+    if options.files.is_empty() {
+        let mut text = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut text) {
+            eprintln!("jq: error: Could not read stdin: {}", io_error(&e));
+            return Err(2);
+        }
+        sources.push((Some(Rc::from("<stdin>")), text));
+    } else {
+        for file in &options.files {
+            if file == "-" {
+                let mut text = String::new();
+                let _ = io::stdin().read_to_string(&mut text);
+                sources.push((Some(Rc::from("<stdin>")), text));
+                continue;
+            }
+            match std::fs::read_to_string(file) {
+                Ok(text) => sources.push((Some(Rc::from(file.as_str())), text)),
+                Err(e) => {
+                    eprintln!("jq: error: Could not open {file}: {}", io_error(&e));
+                    status = Err(2);
+                }
+            }
+        }
+    }
+
+    let mut inputs = std::collections::VecDeque::new();
+    let last_file = sources.last().and_then(|(file, _)| file.clone());
+    if options.raw_input {
+        if options.slurp {
+            let text: String = sources.iter().map(|(_, text)| text.as_str()).collect();
+            let line = text.matches('\n').count();
+            inputs.push_back(Input {
+                value: Ok(Value::from(text)),
+                file: last_file,
+                line,
+            });
+        } else {
+            for (file, text) in &sources {
+                for (i, line) in text.split_inclusive('\n').enumerate() {
+                    inputs.push_back(Input {
+                        value: Ok(Value::str(line.strip_suffix('\n').unwrap_or(line))),
+                        file: file.clone(),
+                        line: i + 1,
+                    });
+                }
+            }
+        }
+        return status.map(|()| inputs);
+    }
+
+    for (file, text) in &sources {
+        let mut stream = serde_json::Deserializer::from_str(text).into_iter::<Value>();
+        // errors name the line a value ends on, counting its line break
+        let (mut counted, mut line) = (0, 0);
+        while let Some(next) = stream.next() {
+            let end = stream.byte_offset();
+            let end = end
+                + text[end..]
+                    .find(|c| !matches!(c, ' ' | '\t' | '\r'))
+                    .unwrap_or(text.len() - end);
+            let end = if text[end..].starts_with('\n') {
+                end + 1
+            } else {
+                end
+            };
+            line += text[counted.min(end)..end].matches('\n').count();
+            counted = counted.max(end);
+            let failed = next.is_err();
+            inputs.push_back(Input {
+                value: next.map_err(|e| e.to_string()),
+                file: file.clone(),
+                line,
+            });
+            if failed {
+                break;
+            }
+        }
+    }
+
+    if options.stream {
+        inputs = inputs
+            .into_iter()
+            .flat_map(|input| {
+                let events = match &input.value {
+                    Ok(value) => {
+                        let mut events = Vec::new();
+                        stream_events(value, &mut Vec::new(), &mut events);
+                        events.into_iter().map(Ok).collect()
+                    }
+                    Err(message) => vec![Err(message.clone())],
+                };
+                events.into_iter().map(move |value| Input {
+                    value,
+                    file: input.file.clone(),
+                    line: input.line,
+                })
+            })
+            .collect();
+    }
+
+    if options.slurp {
+        let mut values = Vec::new();
+        let mut failure = None;
+        for input in inputs.drain(..) {
+            match input.value {
+                Ok(value) => values.push(value),
+                Err(message) => {
+                    failure = Some(Input {
+                        value: Err(message),
+                        ..input
+                    });
+                    break;
+                }
+            }
+        }
+        let line = sources
+            .iter()
+            .map(|(_, text)| text.matches('\n').count())
+            .sum();
+        inputs.push_back(failure.unwrap_or(Input {
+            value: Ok(Value::array(values)),
+            file: last_file,
+            line,
+        }));
+    }
+    status.map(|()| inputs)
+}
+
+/// `--stream`: a value as `[path, leaf]` events, with a `[path]` event
+/// closing each non-empty array or object at its last key.
+fn stream_events(value: &Value, path: &mut Vec<Value>, events: &mut Vec<Value>) {
+    let children: Vec<(Value, &Value)> = match value {
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (Value::from(i), v))
+            .collect(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| (Value::String(k.clone()), v))
+            .collect(),
+        _ => Vec::new(),
+    };
+    if children.is_empty() {
+        events.push(Value::array(vec![
+            Value::array(path.clone()),
+            value.clone(),
+        ]));
+        return;
+    }
+    let mut last = Value::Null;
+    for (key, child) in children {
+        path.push(key.clone());
+        stream_events(child, path, events);
+        path.pop();
+        last = key;
+    }
+    let mut closing = path.clone();
+    closing.push(last);
+    events.push(Value::array(vec![Value::array(closing)]));
+}
+
+/// An I/O error as the C library words it.
+fn io_error(e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::NotFound => "No such file or directory".to_string(),
+        io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+        io::ErrorKind::IsADirectory => "Is a directory".to_string(),
+        _ => e.to_string(),
+    }
+}
+
+fn print_usage() {
+    println!("Usage: jq [OPTIONS] FILTER [FILES...]");
+    println!("       jq [OPTIONS] --args FILTER [ARGUMENTS...]");
+    println!();
+    println!("Runs the jq program FILTER on each JSON value in the FILES (or stdin).");
+    println!();
+    println!("  -n, --null-input          use null as the single input value");
+    println!("  -R, --raw-input           read each line as a string");
+    println!("  -s, --slurp               read all inputs into one array");
+    println!("  -r, --raw-output          print strings without quotes");
+    println!("  -j, --join-output         like -r, without newlines between outputs");
+    println!("      --raw-output0         like -r, with NUL after each output");
+    println!("  -a, --ascii-output        escape non-ASCII characters");
+    println!("  -c, --compact-output      print each value on one line");
+    println!("  -S, --sort-keys           sort object keys");
+    println!("      --tab, --indent n     indent with a tab or n spaces");
+    println!("  -e, --exit-status         exit 1 if the last output is false or null");
+    println!("  -f, --from-file file      read the filter from a file");
+    println!("      --stream              read inputs as [path, leaf] events");
+    println!("      --seq                 prefix outputs with the RS character");
+    println!("      --arg name value      set $name to the string value");
+    println!("      --argjson name json   set $name to the JSON value");
+    println!("      --slurpfile name f    set $name to an array of the JSON values in f");
+    println!("      --rawfile name f      set $name to the contents of f");
+    println!("      --args, --jsonargs    remaining arguments are $ARGS.positional");
+}
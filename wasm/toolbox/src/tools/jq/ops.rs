@@ -0,0 +1,433 @@
+//! Operations on values: indexing, slicing, arithmetic and the path
+//! functions assignments are built on.
+
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use super::ast::BinOp;
+use super::interp::{Flow, fail};
+use super::value::{Map, Value};
+
+/// How a key is named in "Cannot index" errors.
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(s) => format!("string \"{s}\""),
+        other => other.type_name().to_string(),
+    }
+}
+
+fn index_error<T>(value: &Value, key: &Value) -> Result<T, Flow> {
+    fail(format!(
+        "Cannot index {} with {}",
+        value.type_name(),
+        key_name(key)
+    ))
+}
+
+/// An array index as jq reads it: fractions are dropped and negative
+/// indices count from the end.
+fn array_index(n: f64, len: usize) -> Option<usize> {
+    let i = n.floor();
+    let i = if i < 0.0 { i + len as f64 } else { i };
+    (i >= 0.0 && i < len as f64).then_some(i as usize)
+}
+
+/// `value[key]`
+pub fn index(value: &Value, key: &Value) -> Result<Value, Flow> {
+    match (value, key) {
+        (Value::Object(map), Value::String(k)) => Ok(map.get(k).cloned().unwrap_or(Value::Null)),
+        (Value::Array(items), Value::Number(n, _)) => {
+            Ok(array_index(*n, items.len()).map_or(Value::Null, |i| items[i].clone()))
+        }
+        (Value::Array(items), Value::Array(needle)) => Ok(indices(items, needle)),
+        (Value::Null, Value::String(_) | Value::Number(..)) => Ok(Value::Null),
+        (Value::Array(_) | Value::String(_) | Value::Null, Value::Object(bounds)) => {
+            let bound = |name: &str| bounds.get(name).cloned().unwrap_or(Value::Null);
+            slice(value, &bound("start"), &bound("end"))
+        }
+        _ => index_error(value, key),
+    }
+}
+
+/// Positions where `needle` occurs as a run of `items`.
+pub fn indices(items: &[Value], needle: &[Value]) -> Value {
+    if needle.is_empty() {
+        return Value::Null;
+    }
+    let found = items
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle)
+        .map(|(i, _)| Value::from(i))
+        .collect();
+    Value::array(found)
+}
+
+/// The path element `{"start": from, "end": to}` a slice stands for.
+pub fn slice_key(from: &Value, to: &Value) -> Value {
+    let mut map = Map::new();
+    map.insert(Rc::from("start"), from.clone());
+    map.insert(Rc::from("end"), to.clone());
+    Value::object(map)
+}
+
+/// Resolves slice bounds against a length: negative bounds count from the
+/// end, the start rounds down and the end rounds up.
+fn slice_range(from: &Value, to: &Value, len: usize) -> Result<(usize, usize), Flow> {
+    let bound = |bound: &Value, default: usize, round: fn(f64) -> f64| match bound {
+        Value::Null => Ok(default),
+        Value::Number(n, _) => {
+            let n = round(*n);
+            let n = if n < 0.0 { n + len as f64 } else { n };
+            Ok(n.clamp(0.0, len as f64) as usize)
+        }
+        _ => fail("Start and end indices of an array slice must be numbers"),
+    };
+    let start = bound(from, 0, f64::floor)?;
+    let end = bound(to, len, f64::ceil)?;
+    Ok((start, end.max(start)))
+}
+
+/// `value[from:to]`; strings are sliced by codepoint.
+pub fn slice(value: &Value, from: &Value, to: &Value) -> Result<Value, Flow> {
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Array(items) => {
+            let (start, end) = slice_range(from, to, items.len())?;
+            Ok(Value::array(items[start..end].to_vec()))
+        }
+        Value::String(s) => {
+            let (start, end) = slice_range(from, to, s.chars().count())?;
+            Ok(Value::from(
+                s.chars().skip(start).take(end - start).collect::<String>(),
+            ))
+        }
+        other => fail(format!("Cannot index {} with object", other.type_name())),
+    }
+}
+
+pub fn binary(op: BinOp, l: &Value, r: &Value) -> Result<Value, Flow> {
+    let ordering = || l.cmp(r);
+    let result = match op {
+        BinOp::Add => return add(l, r),
+        BinOp::Sub => return subtract(l, r),
+        BinOp::Mul => return multiply(l, r),
+        BinOp::Div => return divide(l, r),
+        BinOp::Mod => return modulo(l, r),
+        BinOp::Eq => ordering() == Ordering::Equal,
+        BinOp::Ne => ordering() != Ordering::Equal,
+        BinOp::Lt => ordering() == Ordering::Less,
+        BinOp::Le => ordering() != Ordering::Greater,
+        BinOp::Gt => ordering() == Ordering::Greater,
+        BinOp::Ge => ordering() != Ordering::Less,
+    };
+    Ok(Value::Bool(result))
+}
+
+pub fn add(l: &Value, r: &Value) -> Result<Value, Flow> {
+    Ok(match (l, r) {
+        (Value::Null, other) | (other, Value::Null) => other.clone(),
+        (Value::Number(a, _), Value::Number(b, _)) => Value::from(a + b),
+        (Value::String(a), Value::String(b)) => Value::from(format!("{a}{b}")),
+        (Value::Array(a), Value::Array(b)) => {
+            Value::array(a.iter().chain(b.iter()).cloned().collect())
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            let mut map = (**a).clone();
+            map.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+            Value::object(map)
+        }
+        _ => {
+            return fail(format!(
+                "{} and {} cannot be added",
+                l.describe(),
+                r.describe()
+            ));
+        }
+    })
+}
+
+fn subtract(l: &Value, r: &Value) -> Result<Value, Flow> {
+    Ok(match (l, r) {
+        (Value::Number(a, _), Value::Number(b, _)) => Value::from(a - b),
+        (Value::Array(a), Value::Array(b)) => {
+            Value::array(a.iter().filter(|v| !b.contains(v)).cloned().collect())
+        }
+        _ => {
+            return fail(format!(
+                "{} and {} cannot be subtracted",
+                l.describe(),
+                r.describe()
+            ));
+        }
+    })
+}
+
+fn multiply(l: &Value, r: &Value) -> Result<Value, Flow> {
+    Ok(match (l, r) {
+        (Value::Number(a, _), Value::Number(b, _)) => Value::from(a * b),
+        (Value::String(s), Value::Number(n, _)) | (Value::Number(n, _), Value::String(s)) => {
+            if *n <= 0.0 || n.is_nan() {
+                Value::Null
+            } else {
+                Value::from(s.repeat((*n as usize).max(1)))
+            }
+        }
+        (Value::Object(_), Value::Object(_)) => deep_merge(l, r),
+        _ => {
+            return fail(format!(
+                "{} and {} cannot be multiplied",
+                l.describe(),
+                r.describe()
+            ));
+        }
+    })
+}
+
+/// `*` on objects: like `+`, but objects under the same key merge too.
+fn deep_merge(l: &Value, r: &Value) -> Value {
+    match (l, r) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut map = (**a).clone();
+            for (key, value) in b.iter() {
+                let merged = match map.get(key) {
+                    Some(old) => deep_merge(old, value),
+                    None => value.clone(),
+                };
+                map.insert(key.clone(), merged);
+            }
+            Value::object(map)
+        }
+        _ => r.clone(),
+    }
+}
+
+fn divide(l: &Value, r: &Value) -> Result<Value, Flow> {
+    Ok(match (l, r) {
+        (Value::Number(_, _), Value::Number(b, _)) if *b == 0.0 => {
+            return fail(format!(
+                "{} and {} cannot be divided because the divisor is zero",
+                l.describe(),
+                r.describe()
+            ));
+        }
+        (Value::Number(a, _), Value::Number(b, _)) => Value::from(a / b),
+        (Value::String(s), Value::String(sep)) => split(s, sep),
+        _ => {
+            return fail(format!(
+                "{} and {} cannot be divided",
+                l.describe(),
+                r.describe()
+            ));
+        }
+    })
+}
+
+fn modulo(l: &Value, r: &Value) -> Result<Value, Flow> {
+    match (l, r) {
+        (Value::Number(a, _), Value::Number(b, _)) => {
+            let (a, b) = (*a as i64, *b as i64);
+            if b == 0 {
+                return fail(format!(
+                    "{} and {} cannot be divided (remainder) because the divisor is zero",
+                    l.describe(),
+                    r.describe()
+                ));
+            }
+            Ok(Value::from(a.wrapping_rem(b) as f64))
+        }
+        _ => fail(format!(
+            "{} and {} cannot be divided",
+            l.describe(),
+            r.describe()
+        )),
+    }
+}
+
+/// `split(sep)` on a literal separator; an empty separator splits into
+/// characters.
+pub fn split(s: &str, sep: &str) -> Value {
+    if s.is_empty() {
+        return Value::array(Vec::new());
+    }
+    let parts: Vec<Value> = if sep.is_empty() {
+        s.chars().map(|c| Value::from(c.to_string())).collect()
+    } else {
+        s.split(sep).map(Value::str).collect()
+    };
+    Value::array(parts)
+}
+
+pub fn getpath(value: &Value, path: &[Value]) -> Result<Value, Flow> {
+    let mut current = value.clone();
+    for key in path {
+        if let Value::Null = current {
+            return Ok(Value::Null);
+        }
+        current = index(&current, key)?;
+    }
+    Ok(current)
+}
+
+/// Sets the value at `path`, creating objects and arrays on the way.
+pub fn setpath(root: Value, path: &[Value], new: Value) -> Result<Value, Flow> {
+    let Some((key, rest)) = path.split_first() else {
+        return Ok(new);
+    };
+    match (root, key) {
+        (root @ (Value::Object(_) | Value::Null), Value::String(k)) => {
+            let mut map = match root {
+                Value::Object(map) => map,
+                _ => Rc::new(Map::new()),
+            };
+            let slot = Rc::make_mut(&mut map)
+                .entry(k.clone())
+                .or_insert(Value::Null);
+            let child = std::mem::replace(slot, Value::Null);
+            *slot = setpath(child, rest, new)?;
+            Ok(Value::Object(map))
+        }
+        (root @ (Value::Array(_) | Value::Null), Value::Number(n, _)) => {
+            let mut items = match root {
+                Value::Array(items) => items,
+                _ => Rc::new(Vec::new()),
+            };
+            let len = items.len() as f64;
+            let i = n.floor();
+            let i = if i < 0.0 { i + len } else { i };
+            if i < 0.0 {
+                return fail("Out of bounds negative array index");
+            }
+            let i = i as usize;
+            let vec = Rc::make_mut(&mut items);
+            if i >= vec.len() {
+                vec.resize(i + 1, Value::Null);
+            }
+            let child = std::mem::replace(&mut vec[i], Value::Null);
+            vec[i] = setpath(child, rest, new)?;
+            Ok(Value::Array(items))
+        }
+        (root @ (Value::Array(_) | Value::Null), Value::Object(bounds)) => {
+            let bound = |name: &str| bounds.get(name).cloned().unwrap_or(Value::Null);
+            let items = match &root {
+                Value::Array(items) => (**items).clone(),
+                _ => Vec::new(),
+            };
+            let (start, end) = slice_range(&bound("start"), &bound("end"), items.len())?;
+            let current = Value::array(items[start..end].to_vec());
+            let Value::Array(replacement) = setpath(current, rest, new)? else {
+                return fail("A slice of an array can only be assigned another array");
+            };
+            let mut spliced = items[..start].to_vec();
+            spliced.extend(replacement.iter().cloned());
+            spliced.extend(items[end..].iter().cloned());
+            Ok(Value::array(spliced))
+        }
+        (root, key) => index_error(&root, key),
+    }
+}
+
+/// Deletes every path, deepest and last first so earlier deletions do not
+/// shift the positions later ones refer to.
+pub fn delpaths(mut root: Value, mut paths: Vec<Value>) -> Result<Value, Flow> {
+    paths.sort();
+    paths.dedup();
+    for path in paths.iter().rev() {
+        let Value::Array(path) = path else {
+            return fail("Path must be specified as an array");
+        };
+        root = delpath(root, path)?;
+    }
+    Ok(root)
+}
+
+fn delpath(root: Value, path: &[Value]) -> Result<Value, Flow> {
+    let Some((key, rest)) = path.split_first() else {
+        return Ok(Value::Null);
+    };
+    if let Value::Null = root {
+        return Ok(Value::Null);
+    }
+    if !rest.is_empty() {
+        let child = index(&root, key)?;
+        if let Value::Null = child {
+            return Ok(root);
+        }
+        let child = delpath(child, rest)?;
+        return setpath(root, std::slice::from_ref(key), child);
+    }
+    match (root, key) {
+        (Value::Object(mut map), Value::String(k)) => {
+            Rc::make_mut(&mut map).shift_remove(k);
+            Ok(Value::Object(map))
+        }
+        (Value::Array(mut items), Value::Number(n, _)) => {
+            if let Some(i) = array_index(*n, items.len()) {
+                Rc::make_mut(&mut items).remove(i);
+            }
+            Ok(Value::Array(items))
+        }
+        (Value::Array(items), Value::Object(bounds)) => {
+            let bound = |name: &str| bounds.get(name).cloned().unwrap_or(Value::Null);
+            let (start, end) = slice_range(&bound("start"), &bound("end"), items.len())?;
+            let mut kept = items[..start].to_vec();
+            kept.extend(items[end..].iter().cloned());
+            Ok(Value::array(kept))
+        }
+        (root @ (Value::Object(_) | Value::Array(_)), key) => fail(format!(
+            "Cannot delete field at {} index of {}",
+            key.type_name(),
+            root.type_name()
+        )),
+        (root, _) => fail(format!("Cannot delete fields from {}", root.type_name())),
+    }
+}
+
+pub fn has(value: &Value, key: &Value) -> Result<bool, Flow> {
+    match (value, key) {
+        (Value::Object(map), Value::String(k)) => Ok(map.contains_key(k)),
+        (Value::Array(items), Value::Number(n, _)) => Ok(*n >= 0.0 && *n < items.len() as f64),
+        _ => fail(format!(
+            "Cannot check whether {} has a {} key",
+            value.type_name(),
+            key.type_name()
+        )),
+    }
+}
+
+/// `a | contains(b)`: substrings, subsets of arrays and objects, recursively.
+pub fn contains(a: &Value, b: &Value) -> Result<bool, Flow> {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, wanted) in b.iter() {
+                match a.get(key) {
+                    Some(value) if contains(value, wanted)? => {}
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for wanted in b.iter() {
+                let mut found = false;
+                for value in a.iter() {
+                    if contains(value, wanted)? {
+                        found = true;
+                        break;
+                    }
+                }
+                if !found {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (Value::String(a), Value::String(b)) => Ok(a.contains(&**b)),
+        _ if a.type_name() == b.type_name() => Ok(a == b),
+        _ => fail(format!(
+            "{} and {} cannot have their containment checked",
+            a.describe(),
+            b.describe()
+        )),
+    }
+}
@@ -0,0 +1,733 @@
+//! Recursive-descent parser for jq programs. Operator precedence, loosest
+//! first: `|`, `,`, `//`, assignment, `or`, `and`, comparison, `+ -`,
+//! `* / %`, unary minus, then postfix terms (`.a`, `[i]`, `?`, `as`).
+
+use std::rc::Rc;
+
+use super::ast::{BinOp, Expr, FuncDef, ObjectPattern, Param, Pattern, StrPart, UpdateOp};
+use super::lexer::{self, LexPart, Tok, Token};
+use super::value::Value;
+
+pub struct ParseError {
+    pub message: String,
+    pub pos: usize,
+}
+
+/// Words that cannot name a function.
+const KEYWORDS: &[&str] = &[
+    "def", "if", "then", "elif", "else", "end", "as", "reduce", "foreach", "try", "catch", "label",
+    "import", "include", "and", "or", "__loc__",
+];
+
+pub fn parse(src: &str) -> Result<Expr, ParseError> {
+    let tokens = lexer::tokenize(src).map_err(|e| ParseError {
+        message: e.message,
+        pos: e.pos,
+    })?;
+    let mut parser = Parser {
+        src,
+        tokens,
+        pos: 0,
+        no_comma: false,
+    };
+    if parser.at_ident("import") || parser.at_ident("include") || parser.at_ident("module") {
+        return parser.error("modules are not supported");
+    }
+    let expr = parser.pipe()?;
+    parser.expect_eof()?;
+    Ok(expr)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Inside an object value, where `,` separates entries.
+    no_comma: bool,
+}
+
+type Parsed<T> = Result<T, ParseError>;
+
+impl Parser<'_> {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].tok
+    }
+
+    fn peek_at(&self, offset: usize) -> &Tok {
+        let index = (self.pos + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].tok
+    }
+
+    fn next(&mut self) -> Tok {
+        let tok = self.tokens[self.pos].tok.clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn line(&self) -> usize {
+        let pos = self.tokens[self.pos].pos;
+        self.src[..pos].matches('\n').count() + 1
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Parsed<T> {
+        Err(ParseError {
+            message: message.into(),
+            pos: self.tokens[self.pos].pos,
+        })
+    }
+
+    fn unexpected<T>(&self) -> Parsed<T> {
+        let found = match self.peek() {
+            Tok::Ident(name) if KEYWORDS.contains(&&**name) => name.to_string(),
+            Tok::Ident(_) => "IDENT".to_string(),
+            Tok::Field(_) => "FIELD".to_string(),
+            Tok::Var(_) => "'$'".to_string(),
+            Tok::Format(_) => "FORMAT".to_string(),
+            Tok::Num(_) => "LITERAL".to_string(),
+            Tok::Str(_) => "QQSTRING_START".to_string(),
+            Tok::Op(op) if op.len() == 1 => format!("'{op}'"),
+            Tok::Op(op) => op.to_string(),
+            Tok::Eof => "$end".to_string(),
+        };
+        self.error(format!(
+            "syntax error, unexpected {found} (Unix shell quoting issues?)"
+        ))
+    }
+
+    fn at_op(&self, op: &str) -> bool {
+        matches!(self.peek(), Tok::Op(o) if *o == op)
+    }
+
+    fn at_ident(&self, word: &str) -> bool {
+        matches!(self.peek(), Tok::Ident(name) if &**name == word)
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        let found = self.at_op(op);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn eat_ident(&mut self, word: &str) -> bool {
+        let found = self.at_ident(word);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn expect_op(&mut self, op: &str) -> Parsed<()> {
+        if self.eat_op(op) {
+            Ok(())
+        } else {
+            self.unexpected()
+        }
+    }
+
+    fn expect_ident(&mut self, word: &str) -> Parsed<()> {
+        if self.eat_ident(word) {
+            Ok(())
+        } else {
+            self.unexpected()
+        }
+    }
+
+    fn expect_eof(&self) -> Parsed<()> {
+        if matches!(self.peek(), Tok::Eof) {
+            Ok(())
+        } else {
+            self.unexpected()
+        }
+    }
+
+    /// Parses with `,` allowed or not, restoring the outer setting after.
+    fn nested<T>(
+        &mut self,
+        no_comma: bool,
+        parse: impl FnOnce(&mut Self) -> Parsed<T>,
+    ) -> Parsed<T> {
+        let outer = std::mem::replace(&mut self.no_comma, no_comma);
+        let result = parse(self);
+        self.no_comma = outer;
+        result
+    }
+
+    fn pipe(&mut self) -> Parsed<Expr> {
+        if self.at_ident("def") {
+            let def = self.def()?;
+            let rest = self.pipe()?;
+            return Ok(Expr::Def(Box::new(def), Box::new(rest)));
+        }
+        let left = self.comma()?;
+        if self.eat_op("|") {
+            let right = self.pipe()?;
+            return Ok(Expr::Pipe(Box::new(left), Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn comma(&mut self) -> Parsed<Expr> {
+        let mut left = self.alternative()?;
+        while !self.no_comma && self.eat_op(",") {
+            let right = self.alternative()?;
+            left = Expr::Comma(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn alternative(&mut self) -> Parsed<Expr> {
+        let left = self.assignment()?;
+        if self.eat_op("//") {
+            let right = self.alternative()?;
+            return Ok(Expr::Alt(Box::new(left), Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn assignment(&mut self) -> Parsed<Expr> {
+        let left = self.or()?;
+        let op = match self.peek() {
+            Tok::Op("=") => UpdateOp::Assign,
+            Tok::Op("|=") => UpdateOp::Modify,
+            Tok::Op("+=") => UpdateOp::Arith(BinOp::Add),
+            Tok::Op("-=") => UpdateOp::Arith(BinOp::Sub),
+            Tok::Op("*=") => UpdateOp::Arith(BinOp::Mul),
+            Tok::Op("/=") => UpdateOp::Arith(BinOp::Div),
+            Tok::Op("%=") => UpdateOp::Arith(BinOp::Mod),
+            Tok::Op("//=") => UpdateOp::Alt,
+            _ => return Ok(left),
+        };
+        self.next();
+        let right = self.or()?;
+        Ok(Expr::Update(op, Box::new(left), Box::new(right)))
+    }
+
+    fn or(&mut self) -> Parsed<Expr> {
+        let mut left = self.and()?;
+        while self.eat_ident("or") {
+            let right = self.and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Parsed<Expr> {
+        let mut left = self.comparison()?;
+        while self.eat_ident("and") {
+            let right = self.comparison()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> Parsed<Expr> {
+        let left = self.additive()?;
+        let op = match self.peek() {
+            Tok::Op("==") => BinOp::Eq,
+            Tok::Op("!=") => BinOp::Ne,
+            Tok::Op("<") => BinOp::Lt,
+            Tok::Op("<=") => BinOp::Le,
+            Tok::Op(">") => BinOp::Gt,
+            Tok::Op(">=") => BinOp::Ge,
+            _ => return Ok(left),
+        };
+        self.next();
+        let right = self.additive()?;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn additive(&mut self) -> Parsed<Expr> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Tok::Op("+") => BinOp::Add,
+                Tok::Op("-") => BinOp::Sub,
+                _ => return Ok(left),
+            };
+            self.next();
+            let right = self.multiplicative()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Parsed<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Tok::Op("*") => BinOp::Mul,
+                Tok::Op("/") => BinOp::Div,
+                Tok::Op("%") => BinOp::Mod,
+                _ => return Ok(left),
+            };
+            self.next();
+            let right = self.unary()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Parsed<Expr> {
+        if !self.eat_op("-") {
+            return self.postfix(true);
+        }
+        Ok(match self.unary()? {
+            Expr::Literal(Value::Number(n, literal)) => {
+                let literal = literal.map(|text| Rc::from(format!("-{text}")));
+                Expr::Literal(Value::Number(-n, literal))
+            }
+            operand => Expr::Neg(Box::new(operand)),
+        })
+    }
+
+    /// A term with its suffixes; with `allow_as`, a following `as` binding
+    /// takes the rest of the pipeline as its body.
+    fn postfix(&mut self, allow_as: bool) -> Parsed<Expr> {
+        let mut term = self.term()?;
+        loop {
+            match self.peek().clone() {
+                Tok::Field(name) => {
+                    self.next();
+                    term =
+                        Expr::Index(Box::new(term), Box::new(Expr::Literal(Value::String(name))));
+                }
+                Tok::Op(".") if matches!(self.peek_at(1), Tok::Str(_)) => {
+                    self.next();
+                    let key = self.string(None)?;
+                    term = Expr::Index(Box::new(term), Box::new(key));
+                }
+                Tok::Op(".") if matches!(self.peek_at(1), Tok::Op("[")) => {
+                    self.next();
+                }
+                Tok::Op("[") => {
+                    self.next();
+                    term = self.brackets(term)?;
+                }
+                Tok::Op("?") => {
+                    self.next();
+                    term = Expr::Try(Box::new(term), None);
+                }
+                Tok::Ident(word) if allow_as && &*word == "as" => {
+                    self.next();
+                    let mut patterns = vec![self.pattern()?];
+                    while self.eat_op("?//") {
+                        patterns.push(self.pattern()?);
+                    }
+                    self.expect_op("|")?;
+                    let body = self.pipe()?;
+                    return Ok(Expr::Bind(Box::new(term), patterns, Box::new(body)));
+                }
+                _ => return Ok(term),
+            }
+        }
+    }
+
+    /// What follows `[` after a term: `]`, `i]`, `from:to]`.
+    fn brackets(&mut self, target: Expr) -> Parsed<Expr> {
+        let target = Box::new(target);
+        if self.eat_op("]") {
+            return Ok(Expr::Iterate(target));
+        }
+        if self.eat_op(":") {
+            let to = self.nested(false, Self::pipe)?;
+            self.expect_op("]")?;
+            return Ok(Expr::Slice(target, None, Some(Box::new(to))));
+        }
+        let index = self.nested(false, Self::pipe)?;
+        if self.eat_op(":") {
+            let to = if self.at_op("]") {
+                None
+            } else {
+                Some(Box::new(self.nested(false, Self::pipe)?))
+            };
+            self.expect_op("]")?;
+            return Ok(Expr::Slice(target, Some(Box::new(index)), to));
+        }
+        self.expect_op("]")?;
+        Ok(Expr::Index(target, Box::new(index)))
+    }
+
+    fn term(&mut self) -> Parsed<Expr> {
+        match self.peek().clone() {
+            Tok::Op(".") => {
+                self.next();
+                if matches!(self.peek(), Tok::Str(_)) {
+                    let key = self.string(None)?;
+                    return Ok(Expr::Index(Box::new(Expr::Identity), Box::new(key)));
+                }
+                Ok(Expr::Identity)
+            }
+            Tok::Op("..") => {
+                self.next();
+                Ok(Expr::Call(Rc::from("recurse"), Vec::new()))
+            }
+            Tok::Field(name) => {
+                self.next();
+                let key = Expr::Literal(Value::String(name));
+                Ok(Expr::Index(Box::new(Expr::Identity), Box::new(key)))
+            }
+            Tok::Num(value) => {
+                self.next();
+                Ok(Expr::Literal(value))
+            }
+            Tok::Str(_) => self.string(None),
+            Tok::Format(name) => {
+                self.next();
+                if matches!(self.peek(), Tok::Str(_)) {
+                    self.string(Some(name))
+                } else {
+                    Ok(Expr::Format(name))
+                }
+            }
+            Tok::Var(name) => {
+                let line = self.line();
+                self.next();
+                if &*name == "__loc__" {
+                    Ok(Expr::Loc(line))
+                } else {
+                    Ok(Expr::Var(name))
+                }
+            }
+            Tok::Op("(") => {
+                self.next();
+                let inner = self.nested(false, Self::pipe)?;
+                self.expect_op(")")?;
+                Ok(inner)
+            }
+            Tok::Op("[") => {
+                self.next();
+                if self.eat_op("]") {
+                    return Ok(Expr::Array(None));
+                }
+                let inner = self.nested(false, Self::pipe)?;
+                self.expect_op("]")?;
+                Ok(Expr::Array(Some(Box::new(inner))))
+            }
+            Tok::Op("{") => {
+                self.next();
+                self.object()
+            }
+            Tok::Ident(word) => match &*word {
+                "if" => {
+                    self.next();
+                    self.if_expr()
+                }
+                "try" => {
+                    self.next();
+                    let body = self.postfix(false)?;
+                    let handler = if self.eat_ident("catch") {
+                        Some(Box::new(self.postfix(false)?))
+                    } else {
+                        None
+                    };
+                    Ok(Expr::Try(Box::new(body), handler))
+                }
+                "reduce" => {
+                    self.next();
+                    let source = self.postfix(false)?;
+                    self.expect_ident("as")?;
+                    let pattern = self.pattern()?;
+                    self.expect_op("(")?;
+                    let init = self.nested(false, Self::pipe)?;
+                    self.expect_op(";")?;
+                    let update = self.nested(false, Self::pipe)?;
+                    self.expect_op(")")?;
+                    Ok(Expr::Reduce {
+                        source: Box::new(source),
+                        pattern,
+                        init: Box::new(init),
+                        update: Box::new(update),
+                    })
+                }
+                "foreach" => {
+                    self.next();
+                    let source = self.postfix(false)?;
+                    self.expect_ident("as")?;
+                    let pattern = self.pattern()?;
+                    self.expect_op("(")?;
+                    let init = self.nested(false, Self::pipe)?;
+                    self.expect_op(";")?;
+                    let update = self.nested(false, Self::pipe)?;
+                    let extract = if self.eat_op(";") {
+                        Some(Box::new(self.nested(false, Self::pipe)?))
+                    } else {
+                        None
+                    };
+                    self.expect_op(")")?;
+                    Ok(Expr::Foreach {
+                        source: Box::new(source),
+                        pattern,
+                        init: Box::new(init),
+                        update: Box::new(update),
+                        extract,
+                    })
+                }
+                "label" => {
+                    self.next();
+                    let Tok::Var(name) = self.next() else {
+                        return self.unexpected();
+                    };
+                    self.expect_op("|")?;
+                    let body = self.pipe()?;
+                    Ok(Expr::Label(name, Box::new(body)))
+                }
+                "break" => {
+                    self.next();
+                    match self.next() {
+                        Tok::Var(name) => Ok(Expr::Break(name)),
+                        _ => self.error("break requires a label to break to"),
+                    }
+                }
+                "def" => {
+                    let def = self.def()?;
+                    let rest = self.pipe()?;
+                    Ok(Expr::Def(Box::new(def), Box::new(rest)))
+                }
+                word if KEYWORDS.contains(&word) => self.unexpected(),
+                "null" | "true" | "false" => {
+                    self.next();
+                    Ok(Expr::Literal(match &*word {
+                        "null" => Value::Null,
+                        word => Value::Bool(word == "true"),
+                    }))
+                }
+                _ => {
+                    self.next();
+                    let mut args = Vec::new();
+                    if self.eat_op("(") {
+                        loop {
+                            args.push(self.nested(false, Self::pipe)?);
+                            if !self.eat_op(";") {
+                                break;
+                            }
+                        }
+                        self.expect_op(")")?;
+                    }
+                    Ok(Expr::Call(word, args))
+                }
+            },
+            _ => self.unexpected(),
+        }
+    }
+
+    fn if_expr(&mut self) -> Parsed<Expr> {
+        let mut branches = Vec::new();
+        loop {
+            let cond = self.nested(false, Self::pipe)?;
+            self.expect_ident("then")?;
+            let body = self.nested(false, Self::pipe)?;
+            branches.push((cond, body));
+            if !self.eat_ident("elif") {
+                break;
+            }
+        }
+        let otherwise = if self.eat_ident("else") {
+            Some(Box::new(self.nested(false, Self::pipe)?))
+        } else {
+            None
+        };
+        self.expect_ident("end")?;
+        Ok(Expr::If(branches, otherwise))
+    }
+
+    fn def(&mut self) -> Parsed<FuncDef> {
+        self.expect_ident("def")?;
+        let name = match self.next() {
+            Tok::Ident(name) if !KEYWORDS.contains(&&*name) => name,
+            _ => {
+                self.pos -= 1;
+                return self.unexpected();
+            }
+        };
+        let mut params = Vec::new();
+        if self.eat_op("(") {
+            loop {
+                match self.next() {
+                    Tok::Ident(name) => params.push(Param {
+                        name,
+                        is_var: false,
+                    }),
+                    Tok::Var(name) => params.push(Param { name, is_var: true }),
+                    _ => {
+                        self.pos -= 1;
+                        return self.unexpected();
+                    }
+                }
+                if !self.eat_op(";") {
+                    break;
+                }
+            }
+            self.expect_op(")")?;
+        }
+        self.expect_op(":")?;
+        let body = self.nested(false, Self::pipe)?;
+        self.expect_op(";")?;
+        Ok(FuncDef { name, params, body })
+    }
+
+    /// A string literal token, with `format` applied to its interpolations.
+    fn string(&mut self, format: Option<Rc<str>>) -> Parsed<Expr> {
+        let Tok::Str(parts) = self.next() else {
+            return self.unexpected();
+        };
+        let mut out = Vec::new();
+        for part in parts {
+            match part {
+                LexPart::Lit(text) => out.push(StrPart::Lit(text)),
+                LexPart::Interp(tokens) => {
+                    let mut inner = Parser {
+                        src: self.src,
+                        tokens,
+                        pos: 0,
+                        no_comma: false,
+                    };
+                    let expr = inner.pipe()?;
+                    inner.expect_eof()?;
+                    out.push(StrPart::Interp(expr));
+                }
+            }
+        }
+        if format.is_none()
+            && let [StrPart::Lit(text)] = out.as_slice()
+        {
+            return Ok(Expr::Literal(Value::str(text)));
+        }
+        Ok(Expr::Str(format, out))
+    }
+
+    fn object(&mut self) -> Parsed<Expr> {
+        let mut entries = Vec::new();
+        while !self.eat_op("}") {
+            let (key, shorthand) = match self.peek().clone() {
+                Tok::Var(name) => {
+                    let line = self.line();
+                    self.next();
+                    let value = if &*name == "__loc__" {
+                        Expr::Loc(line)
+                    } else {
+                        Expr::Var(name.clone())
+                    };
+                    (Expr::Literal(Value::String(name)), Some(value))
+                }
+                Tok::Ident(name) => {
+                    self.next();
+                    let value = Expr::Index(
+                        Box::new(Expr::Identity),
+                        Box::new(Expr::Literal(Value::String(name.clone()))),
+                    );
+                    (Expr::Literal(Value::String(name)), Some(value))
+                }
+                Tok::Num(value) => {
+                    self.next();
+                    (Expr::Literal(value), None)
+                }
+                Tok::Str(_) | Tok::Format(_) => {
+                    // Parse the key twice: once as the key, once for `{"a"}` as `."a"`
+                    let start = self.pos;
+                    let key = self.term()?;
+                    let end = self.pos;
+                    self.pos = start;
+                    let again = self.term()?;
+                    self.pos = end;
+                    (
+                        key,
+                        Some(Expr::Index(Box::new(Expr::Identity), Box::new(again))),
+                    )
+                }
+                Tok::Op("(") => {
+                    self.next();
+                    let key = self.nested(false, Self::pipe)?;
+                    self.expect_op(")")?;
+                    (key, None)
+                }
+                _ => return self.unexpected(),
+            };
+            let value = if self.eat_op(":") {
+                self.nested(true, Self::pipe)?
+            } else if let Some(value) = shorthand {
+                value
+            } else {
+                return self.unexpected();
+            };
+            entries.push((key, value));
+            if !self.eat_op(",") {
+                self.expect_op("}")?;
+                break;
+            }
+        }
+        Ok(Expr::Object(entries))
+    }
+
+    fn pattern(&mut self) -> Parsed<Pattern> {
+        match self.next() {
+            Tok::Var(name) => Ok(Pattern::Var(name)),
+            Tok::Op("[") => {
+                let mut items = Vec::new();
+                loop {
+                    items.push(self.pattern()?);
+                    if !self.eat_op(",") {
+                        break;
+                    }
+                }
+                self.expect_op("]")?;
+                Ok(Pattern::Array(items))
+            }
+            Tok::Op("{") => {
+                let mut entries = Vec::new();
+                loop {
+                    entries.push(self.object_pattern()?);
+                    if !self.eat_op(",") {
+                        break;
+                    }
+                }
+                self.expect_op("}")?;
+                Ok(Pattern::Object(entries))
+            }
+            _ => {
+                self.pos -= 1;
+                self.unexpected()
+            }
+        }
+    }
+
+    fn object_pattern(&mut self) -> Parsed<ObjectPattern> {
+        let (key, var) = match self.peek().clone() {
+            Tok::Var(name) => {
+                self.next();
+                let key = Expr::Literal(Value::String(name.clone()));
+                if !self.eat_op(":") {
+                    return Ok(ObjectPattern {
+                        key,
+                        var: Some(name),
+                        pattern: None,
+                    });
+                }
+                return Ok(ObjectPattern {
+                    key,
+                    var: Some(name),
+                    pattern: Some(self.pattern()?),
+                });
+            }
+            Tok::Ident(name) => {
+                self.next();
+                (Expr::Literal(Value::String(name)), None)
+            }
+            Tok::Str(_) => (self.string(None)?, None),
+            Tok::Op("(") => {
+                self.next();
+                let key = self.nested(false, Self::pipe)?;
+                self.expect_op(")")?;
+                (key, None)
+            }
+            _ => return self.unexpected(),
+        };
+        self.expect_op(":")?;
+        Ok(ObjectPattern {
+            key,
+            var,
+            pattern: Some(self.pattern()?),
+        })
+    }
+}
@@ -0,0 +1,414 @@
+//! Date builtins. Times are UTC, so `localtime` and `strflocaltime` are
+//! the same as `gmtime` and `strftime`. Broken-down times are jq's
+//! `[year, month (0-11), day, hours, minutes, seconds, weekday, yearday]`.
+
+use super::interp::{Flow, fail};
+use super::value::Value;
+
+const DAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+struct Tm {
+    year: i64,
+    /// 0-11
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: f64,
+    weekday: i64,
+    yearday: i64,
+}
+
+pub fn now() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn weekday_and_yearday(year: i64, month: i64, day: i64) -> (i64, i64) {
+    let days = days_from_civil(year, month + 1, day);
+    let weekday = (days + 4).rem_euclid(7);
+    let yearday = days - days_from_civil(year, 1, 1);
+    (weekday, yearday)
+}
+
+fn from_seconds(seconds: f64) -> Tm {
+    let days = (seconds / 86400.0).floor();
+    let (year, month, day) = civil_from_days(days as i64);
+    let rest = seconds - days * 86400.0;
+    let (weekday, yearday) = weekday_and_yearday(year, month - 1, day);
+    Tm {
+        year,
+        month: month - 1,
+        day,
+        hour: (rest / 3600.0) as i64,
+        minute: (rest % 3600.0 / 60.0) as i64,
+        second: rest % 60.0,
+        weekday,
+        yearday,
+    }
+}
+
+fn to_value(tm: &Tm) -> Value {
+    let fields = [
+        tm.year as f64,
+        tm.month as f64,
+        tm.day as f64,
+        tm.hour as f64,
+        tm.minute as f64,
+    ];
+    let mut items: Vec<Value> = fields.into_iter().map(Value::from).collect();
+    items.push(Value::from(tm.second));
+    items.push(Value::from(tm.weekday as f64));
+    items.push(Value::from(tm.yearday as f64));
+    Value::array(items)
+}
+
+fn from_value(value: &Value, function: &str) -> Result<Tm, Flow> {
+    let Value::Array(items) = value else {
+        return fail(format!("{function} requires parsed datetime inputs"));
+    };
+    let mut fields = [0.0; 8];
+    if items.len() < 6 {
+        return fail(format!("{function} requires array of 6 numbers"));
+    }
+    for (field, item) in fields.iter_mut().zip(items.iter()) {
+        let Value::Number(n, _) = item else {
+            return fail(format!("{function} requires parsed datetime inputs"));
+        };
+        *field = *n;
+    }
+    let (year, month, day) = (fields[0] as i64, fields[1] as i64, fields[2] as i64);
+    let (weekday, yearday) = weekday_and_yearday(year, month, day);
+    Ok(Tm {
+        year,
+        month,
+        day,
+        hour: fields[3] as i64,
+        minute: fields[4] as i64,
+        second: fields[5],
+        weekday,
+        yearday,
+    })
+}
+
+pub fn gmtime(seconds: f64) -> Value {
+    to_value(&from_seconds(seconds))
+}
+
+/// The ISO 8601 week-numbering year and week of `tm`: weeks start on
+/// Monday and week 1 is the one holding the year's first Thursday.
+fn iso_week(tm: &Tm) -> (i64, i64) {
+    let weekday = (tm.weekday + 6) % 7;
+    let week = (tm.yearday - weekday + 10) / 7;
+    let weeks_in = |year: i64| {
+        let jan1 = weekday_and_yearday(year, 0, 1).0;
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        if jan1 == 4 || (leap && jan1 == 3) {
+            53
+        } else {
+            52
+        }
+    };
+    if week < 1 {
+        (tm.year - 1, weeks_in(tm.year - 1))
+    } else if week > weeks_in(tm.year) {
+        (tm.year + 1, 1)
+    } else {
+        (tm.year, week)
+    }
+}
+
+pub fn mktime(value: &Value) -> Result<f64, Flow> {
+    if !matches!(value, Value::Array(_)) {
+        return fail("mktime requires array of 6 numbers");
+    }
+    let tm = from_value(value, "mktime")?;
+    let days = days_from_civil(tm.year, tm.month + 1, tm.day);
+    Ok((days * 86400 + tm.hour * 3600 + tm.minute * 60) as f64 + tm.second.floor())
+}
+
+pub fn strftime(value: &Value, format: &Value) -> Result<String, Flow> {
+    let tm = match value {
+        Value::Number(n, _) => from_seconds(*n),
+        other => from_value(other, "strftime/1")?,
+    };
+    let Value::String(format) = format else {
+        return fail("strftime/1 requires a string format");
+    };
+    let hour12 = if tm.hour % 12 == 0 { 12 } else { tm.hour % 12 };
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let Some(spec) = chars.next() else {
+            out.push('%');
+            break;
+        };
+        let text = match spec {
+            'Y' => tm.year.to_string(),
+            'C' => format!("{:02}", tm.year / 100),
+            'y' => format!("{:02}", tm.year % 100),
+            'm' => format!("{:02}", tm.month + 1),
+            'd' => format!("{:02}", tm.day),
+            'e' => format!("{:2}", tm.day),
+            'H' => format!("{:02}", tm.hour),
+            'k' => format!("{:2}", tm.hour),
+            'I' => format!("{hour12:02}"),
+            'l' => format!("{hour12:2}"),
+            'M' => format!("{:02}", tm.minute),
+            'S' => format!("{:02}", tm.second as i64),
+            'j' => format!("{:03}", tm.yearday + 1),
+            'a' => DAYS[tm.weekday as usize % 7][..3].to_string(),
+            'A' => DAYS[tm.weekday as usize % 7].to_string(),
+            'b' | 'h' => MONTHS[tm.month as usize % 12][..3].to_string(),
+            'B' => MONTHS[tm.month as usize % 12].to_string(),
+            'p' => if tm.hour < 12 { "AM" } else { "PM" }.to_string(),
+            'u' => (if tm.weekday == 0 { 7 } else { tm.weekday }).to_string(),
+            'w' => tm.weekday.to_string(),
+            'U' => format!("{:02}", (tm.yearday + 7 - tm.weekday) / 7),
+            'W' => format!("{:02}", (tm.yearday + 7 - (tm.weekday + 6) % 7) / 7),
+            'G' => iso_week(&tm).0.to_string(),
+            'g' => format!("{:02}", iso_week(&tm).0 % 100),
+            'V' => format!("{:02}", iso_week(&tm).1),
+            's' => {
+                let days = days_from_civil(tm.year, tm.month + 1, tm.day);
+                (days * 86400 + tm.hour * 3600 + tm.minute * 60 + tm.second as i64).to_string()
+            }
+            'Z' => "UTC".to_string(),
+            'z' => "+0000".to_string(),
+            'T' => format!("{:02}:{:02}:{:02}", tm.hour, tm.minute, tm.second as i64),
+            'R' => format!("{:02}:{:02}", tm.hour, tm.minute),
+            'r' => format!(
+                "{hour12:02}:{:02}:{:02} {}",
+                tm.minute,
+                tm.second as i64,
+                if tm.hour < 12 { "AM" } else { "PM" }
+            ),
+            'D' | 'x' => format!("{:02}/{:02}/{:02}", tm.month + 1, tm.day, tm.year % 100),
+            'F' => format!("{}-{:02}-{:02}", tm.year, tm.month + 1, tm.day),
+            'X' => format!("{:02}:{:02}:{:02}", tm.hour, tm.minute, tm.second as i64),
+            'c' => format!(
+                "{} {} {:2} {:02}:{:02}:{:02} {}",
+                &DAYS[tm.weekday as usize % 7][..3],
+                &MONTHS[tm.month as usize % 12][..3],
+                tm.day,
+                tm.hour,
+                tm.minute,
+                tm.second as i64,
+                tm.year
+            ),
+            'n' => "\n".to_string(),
+            't' => "\t".to_string(),
+            '%' => "%".to_string(),
+            other => format!("%{other}"),
+        };
+        out.push_str(&text);
+    }
+    Ok(out)
+}
+
+pub fn strptime(value: &Value, format: &Value) -> Result<Value, Flow> {
+    let (Value::String(text), Value::String(format)) = (value, format) else {
+        return fail("strptime/1 requires string inputs and arguments");
+    };
+    let mismatch = || {
+        fail(format!(
+            "date \"{text}\" does not match format \"{format}\""
+        ))
+    };
+    let mut tm = Tm {
+        year: 1900,
+        month: 0,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0.0,
+        weekday: 0,
+        yearday: 0,
+    };
+    let mut pm = None;
+    let mut epoch = None;
+    let mut rest: &str = text;
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            rest = rest.trim_start();
+            continue;
+        }
+        if c != '%' {
+            match rest.strip_prefix(c) {
+                Some(after) => rest = after,
+                None => return mismatch(),
+            }
+            continue;
+        }
+        let Some(spec) = chars.next() else {
+            return mismatch();
+        };
+        let parsed = match spec {
+            'Y' => number(&mut rest, 4).map(|n| tm.year = n),
+            'm' => number(&mut rest, 2).map(|n| tm.month = n - 1),
+            'd' | 'e' => number(&mut rest, 2).map(|n| tm.day = n),
+            'H' | 'k' => number(&mut rest, 2).map(|n| tm.hour = n),
+            'I' | 'l' => number(&mut rest, 2).map(|n| tm.hour = n % 12),
+            'M' => number(&mut rest, 2).map(|n| tm.minute = n),
+            'S' => number(&mut rest, 2).map(|n| tm.second = n as f64),
+            'j' => number(&mut rest, 3).map(|n| tm.yearday = n - 1),
+            'y' => number(&mut rest, 2).map(|n| tm.year = if n < 69 { 2000 + n } else { 1900 + n }),
+            's' => number(&mut rest, 20).map(|n| epoch = Some(n)),
+            'z' => {
+                rest = rest.trim_start();
+                if let Some(after) = rest.strip_prefix('Z') {
+                    rest = after;
+                    Some(())
+                } else {
+                    number(&mut rest, 4).map(|_| ())
+                }
+            }
+            'Z' => {
+                let name_len = rest.chars().take_while(char::is_ascii_alphabetic).count();
+                rest = &rest[name_len..];
+                Some(())
+            }
+            'p' => {
+                let upper = rest.get(..2).map(str::to_ascii_uppercase);
+                match upper.as_deref() {
+                    Some(half @ ("AM" | "PM")) => {
+                        pm = Some(half == "PM");
+                        rest = &rest[2..];
+                        Some(())
+                    }
+                    _ => None,
+                }
+            }
+            'a' | 'A' => name(&mut rest, &DAYS).map(|_| ()),
+            'b' | 'B' | 'h' => name(&mut rest, &MONTHS).map(|i| tm.month = i as i64),
+            'T' => (|| {
+                tm.hour = number(&mut rest, 2)?;
+                rest = rest.strip_prefix(':')?;
+                tm.minute = number(&mut rest, 2)?;
+                rest = rest.strip_prefix(':')?;
+                tm.second = number(&mut rest, 2)? as f64;
+                Some(())
+            })(),
+            'F' => (|| {
+                tm.year = number(&mut rest, 4)?;
+                rest = rest.strip_prefix('-')?;
+                tm.month = number(&mut rest, 2)? - 1;
+                rest = rest.strip_prefix('-')?;
+                tm.day = number(&mut rest, 2)?;
+                Some(())
+            })(),
+            '%' => rest.strip_prefix('%').map(|after| rest = after),
+            'n' | 't' => {
+                rest = rest.trim_start();
+                Some(())
+            }
+            _ => None,
+        };
+        if parsed.is_none() {
+            return mismatch();
+        }
+    }
+    if !rest.is_empty() {
+        return mismatch();
+    }
+    if let Some(seconds) = epoch {
+        return Ok(gmtime(seconds as f64));
+    }
+    if pm == Some(true) {
+        tm.hour += 12;
+    }
+    let (weekday, yearday) = weekday_and_yearday(tm.year, tm.month, tm.day);
+    tm.weekday = weekday;
+    tm.yearday = yearday;
+    Ok(to_value(&tm))
+}
+
+/// A number of at most `max_digits` digits, after optional space and sign.
+fn number(rest: &mut &str, max_digits: usize) -> Option<i64> {
+    let trimmed = rest.trim_start();
+    let sign = usize::from(trimmed.starts_with(['+', '-']));
+    let digits = trimmed[sign..]
+        .chars()
+        .take(max_digits)
+        .take_while(char::is_ascii_digit)
+        .count();
+    if digits == 0 {
+        return None;
+    }
+    let n = trimmed[..sign + digits].parse().ok()?;
+    *rest = &trimmed[sign + digits..];
+    Some(n)
+}
+
+/// Matches a day or month name, full or abbreviated to three letters.
+fn name(rest: &mut &str, names: &[&str]) -> Option<usize> {
+    for (i, full) in names.iter().enumerate() {
+        for candidate in [*full, &full[..3]] {
+            if rest.len() >= candidate.len()
+                && rest[..candidate.len()].eq_ignore_ascii_case(candidate)
+            {
+                *rest = &rest[candidate.len()..];
+                return Some(i);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,376 @@
+//! JSON values as jq sees them: numbers are doubles, objects keep their
+//! insertion order, and everything is cheap to clone.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::rc::Rc;
+
+use indexmap::IndexMap;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+pub type Map = IndexMap<Rc<str>, Value>;
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// A double, plus the integer literal it came from when printing the
+    /// double would not reproduce it (large ids, nanosecond timestamps).
+    Number(f64, Option<Rc<str>>),
+    String(Rc<str>),
+    Array(Rc<Vec<Value>>),
+    Object(Rc<Map>),
+}
+
+impl Value {
+    pub fn str(s: &str) -> Value {
+        Value::String(Rc::from(s))
+    }
+
+    pub fn array(items: Vec<Value>) -> Value {
+        Value::Array(Rc::new(items))
+    }
+
+    pub fn object(map: Map) -> Value {
+        Value::Object(Rc::new(map))
+    }
+
+    /// An integer parsed from text, keeping the text when the double loses it.
+    pub fn integer_literal(text: &str, value: f64) -> Value {
+        let literal = (format_number(value) != text).then(|| Rc::from(text));
+        Value::Number(value, literal)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(..) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+
+    /// Everything but `null` and `false` is true.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Null | Value::Bool(false))
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n, _) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Compact JSON, as `tojson` and error messages print it.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        write_value(&mut out, self, &Style::default(), 0);
+        out
+    }
+
+    /// `tostring`: strings as they are, anything else as JSON.
+    pub fn to_text(&self) -> String {
+        match self {
+            Value::String(s) => s.to_string(),
+            other => other.to_json(),
+        }
+    }
+
+    /// The value's JSON for error messages, with long values cut short.
+    pub fn preview(&self) -> String {
+        let json = self.to_json();
+        if json.len() <= 14 {
+            return json;
+        }
+        let mut end = 11;
+        while !json.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}...", &json[..end])
+    }
+
+    /// `type (value)` for error messages.
+    pub fn describe(&self) -> String {
+        format!("{} ({})", self.type_name(), self.preview())
+    }
+
+    fn type_rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(..) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n, None)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Value {
+        Value::Number(n as f64, None)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(Rc::from(s))
+    }
+}
+
+/// jq's total order: null < false < true < numbers < strings < arrays <
+/// objects. Objects compare their sorted keys first, then the values under
+/// those keys.
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Number(a, _), Value::Number(b, _)) => a.partial_cmp(b).unwrap_or_else(|| {
+                // nan sorts below every number
+                a.is_nan().cmp(&b.is_nan()).reverse()
+            }),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Array(a), Value::Array(b)) => a.iter().cmp(b.iter()),
+            (Value::Object(a), Value::Object(b)) => {
+                let mut a_keys: Vec<&Rc<str>> = a.keys().collect();
+                let mut b_keys: Vec<&Rc<str>> = b.keys().collect();
+                a_keys.sort();
+                b_keys.sort();
+                a_keys.cmp(&b_keys).then_with(|| {
+                    a_keys
+                        .iter()
+                        .map(|k| &a[*k])
+                        .cmp(a_keys.iter().map(|k| &b[*k]))
+                })
+            }
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+/// How output is laid out.
+#[derive(Default, Clone)]
+pub struct Style {
+    /// Spaces per level; 0 prints everything on one line.
+    pub indent: usize,
+    pub tab: bool,
+    pub sort_keys: bool,
+    pub ascii: bool,
+}
+
+pub fn write_value(out: &mut String, value: &Value, style: &Style, level: usize) {
+    let pretty = style.indent > 0 || style.tab;
+    let newline = |out: &mut String, level: usize| {
+        if pretty {
+            out.push('\n');
+            if style.tab {
+                out.extend(std::iter::repeat_n('\t', level));
+            } else {
+                out.extend(std::iter::repeat_n(' ', level * style.indent));
+            }
+        }
+    };
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(_, Some(literal)) => out.push_str(literal),
+        Value::Number(n, None) => out.push_str(&format_number(*n)),
+        Value::String(s) => write_string(out, s, style.ascii),
+        Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                write_value(out, item, style, level + 1);
+            }
+            newline(out, level);
+            out.push(']');
+        }
+        Value::Object(map) if map.is_empty() => out.push_str("{}"),
+        Value::Object(map) => {
+            out.push('{');
+            let mut entries: Vec<(&Rc<str>, &Value)> = map.iter().collect();
+            if style.sort_keys {
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+            }
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                write_string(out, key, style.ascii);
+                out.push_str(if pretty { ": " } else { ":" });
+                write_value(out, item, style, level + 1);
+            }
+            newline(out, level);
+            out.push('}');
+        }
+    }
+}
+
+pub fn write_string(out: &mut String, s: &str, ascii: bool) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                out.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c if ascii && !c.is_ascii() => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{unit:04x}"));
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Shortest round-trip digits, laid out like jq: plain notation unless the
+/// exponent is below -4 or the number has 16+ trailing zeros.
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "null".to_string();
+    }
+    let n = n.clamp(f64::MIN, f64::MAX);
+    if n == 0.0 {
+        return if n.is_sign_negative() { "-0" } else { "0" }.to_string();
+    }
+    let sci = format!("{:e}", n.abs());
+    let (mantissa, exponent) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let decimal_point = exponent.parse::<i32>().unwrap_or(0) + 1;
+    let count = digits.len() as i32;
+
+    let mut out = String::new();
+    if n < 0.0 {
+        out.push('-');
+    }
+    if decimal_point <= -4 || decimal_point > count + 15 {
+        out.push_str(&digits[..1]);
+        if count > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        let exponent = decimal_point - 1;
+        out.push_str(&format!(
+            "e{}{:02}",
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        ));
+    } else if decimal_point <= 0 {
+        out.push_str("0.");
+        out.extend(std::iter::repeat_n('0', (-decimal_point) as usize));
+        out.push_str(&digits);
+    } else if decimal_point >= count {
+        out.push_str(&digits);
+        out.extend(std::iter::repeat_n('0', (decimal_point - count) as usize));
+    } else {
+        let (whole, fraction) = digits.split_at(decimal_point as usize);
+        out.push_str(whole);
+        out.push('.');
+        out.push_str(fraction);
+    }
+    out
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON value")
+            }
+
+            fn visit_unit<E>(self) -> Result<Value, E> {
+                Ok(Value::Null)
+            }
+
+            fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+                Ok(Value::Bool(b))
+            }
+
+            fn visit_i64<E>(self, n: i64) -> Result<Value, E> {
+                Ok(Value::integer_literal(&n.to_string(), n as f64))
+            }
+
+            fn visit_u64<E>(self, n: u64) -> Result<Value, E> {
+                Ok(Value::integer_literal(&n.to_string(), n as f64))
+            }
+
+            fn visit_f64<E>(self, n: f64) -> Result<Value, E> {
+                Ok(Value::from(n))
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+                Ok(Value::str(s))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+                let mut items = Vec::new();
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(Value::array(items))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Value, A::Error> {
+                let mut map = Map::new();
+                while let Some((key, value)) = access.next_entry::<String, Value>()? {
+                    map.insert(Rc::from(key), value);
+                }
+                Ok(Value::object(map))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Parses one JSON text, e.g. for `fromjson` and `--argjson`.
+pub fn parse_json(text: &str) -> Result<Value, String> {
+    serde_json::from_str(text).map_err(|e| e.to_string())
+}
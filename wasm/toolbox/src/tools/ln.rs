@@ -6,6 +6,7 @@ pub fn run(args: &[String]) -> i32 {
     let mut force = false;
     let mut positional = Vec::new();
 
+    let (args, operands) = super::split_at_operands(args);
     for arg in args {
         match arg.as_str() {
             "-s" => symbolic = true,
@@ -29,6 +30,7 @@ pub fn run(args: &[String]) -> i32 {
             _ => positional.push(arg.clone()),
         }
     }
+    positional.extend_from_slice(operands);
 
     if positional.len() < 2 {
         eprintln!("ln: missing file operand");
@@ -4,6 +4,7 @@ pub fn run(args: &[String]) -> i32 {
     let mut parents = false;
     let mut dirs = Vec::new();
 
+    let (args, operands) = super::split_at_operands(args);
     for arg in args {
         match arg.as_str() {
             "-p" => parents = true,
@@ -14,6 +15,7 @@ pub fn run(args: &[String]) -> i32 {
             _ => dirs.push(arg.clone()),
         }
     }
+    dirs.extend_from_slice(operands);
 
     if dirs.is_empty() {
         eprintln!("mkdir: missing operand");
@@ -67,6 +67,16 @@ pub mod git;
 // JavaScript runtime
 pub mod node;
 
+// Python runtime
+pub mod python;
+
+// Networking
+pub mod curl;
+pub mod wget;
+
+// Host functions
+pub mod hostcall;
+
 // Shell utils
 pub mod basename;
 pub mod dirname;
@@ -82,3 +92,23 @@ pub mod hostname;
 pub mod printenv;
 pub mod date;
 pub mod expr;
+pub mod yes;
+
+/// Split `args` at the first `--`: the arguments before it are parsed as
+/// options and operands as usual, while everything after it is an operand
+/// however it looks, so `rm -- -f` removes a file named `-f`.
+pub fn split_at_operands(args: &[String]) -> (&[String], &[String]) {
+    match args.iter().position(|arg| arg == "--") {
+        Some(end) => (&args[..end], &args[end + 1..]),
+        None => (args, &[]),
+    }
+}
+
+/// Whether a failed write to stdout means its reader has gone away, as when
+/// `head` exits early in `cat file | head -1`. A pipe closed by the host
+/// fails writes with `EIO` rather than `EPIPE`, so both count.
+pub fn is_closed_pipe(err: &std::io::Error) -> bool {
+    // WASI's errno for EIO, which `std` reports as an uncategorized error
+    const WASI_EIO: i32 = 29;
+    err.kind() == std::io::ErrorKind::BrokenPipe || err.raw_os_error() == Some(WASI_EIO)
+}
@@ -4,12 +4,14 @@ use std::path::Path;
 pub fn run(args: &[String]) -> i32 {
     let mut paths = Vec::new();
 
+    let (args, operands) = super::split_at_operands(args);
     for arg in args {
         if arg.starts_with('-') {
             continue; // Ignore flags for now
         }
         paths.push(arg.clone());
     }
+    paths.extend_from_slice(operands);
 
     if paths.len() < 2 {
         eprintln!("mv: missing destination operand");
@@ -0,0 +1,806 @@
+// Node builtins for the `node` tool: `Buffer`, `process`, `path` and `fs`.
+// Evaluated after `runtime.js`; `modules.js` exposes them to `require`/`import`.
+//
+// Filesystem and stdio go through the `__node_fs` / `__node_write` natives in
+// `mod.rs`. Binary data crosses that boundary as latin1 strings (one char per
+// byte), converted to and from `Buffer`s here.
+(function (global) {
+  'use strict';
+
+  function define(target, name, value) {
+    Object.defineProperty(target, name, {
+      value: value,
+      writable: true,
+      enumerable: false,
+      configurable: true,
+    });
+  }
+
+  const init = JSON.parse(__node_init);
+
+  // --- Encodings ---
+
+  const BASE64 = 'ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/';
+
+  function normalizeEncoding(encoding) {
+    const enc = String(encoding || 'utf8').toLowerCase();
+    switch (enc) {
+      case 'utf8':
+      case 'utf-8':
+        return 'utf8';
+      case 'ucs2':
+      case 'ucs-2':
+      case 'utf16le':
+      case 'utf-16le':
+        return 'utf16le';
+      case 'latin1':
+      case 'binary':
+        return 'latin1';
+      case 'ascii':
+      case 'hex':
+      case 'base64':
+      case 'base64url':
+        return enc;
+      default:
+        return null;
+    }
+  }
+
+  function checkEncoding(encoding) {
+    const enc = normalizeEncoding(encoding);
+    if (enc === null) throw new TypeError('Unknown encoding: ' + encoding);
+    return enc;
+  }
+
+  function utf8Encode(str) {
+    const out = [];
+    for (let i = 0; i < str.length; i++) {
+      let c = str.charCodeAt(i);
+      if (c >= 0xd800 && c <= 0xdbff && i + 1 < str.length) {
+        const next = str.charCodeAt(i + 1);
+        if (next >= 0xdc00 && next <= 0xdfff) {
+          c = 0x10000 + ((c - 0xd800) << 10) + (next - 0xdc00);
+          i++;
+        } else {
+          c = 0xfffd;
+        }
+      } else if (c >= 0xd800 && c <= 0xdfff) {
+        c = 0xfffd;
+      }
+      if (c < 0x80) {
+        out.push(c);
+      } else if (c < 0x800) {
+        out.push(0xc0 | (c >> 6), 0x80 | (c & 0x3f));
+      } else if (c < 0x10000) {
+        out.push(0xe0 | (c >> 12), 0x80 | ((c >> 6) & 0x3f), 0x80 | (c & 0x3f));
+      } else {
+        out.push(
+          0xf0 | (c >> 18),
+          0x80 | ((c >> 12) & 0x3f),
+          0x80 | ((c >> 6) & 0x3f),
+          0x80 | (c & 0x3f),
+        );
+      }
+    }
+    return out;
+  }
+
+  function utf8Decode(bytes, start, end) {
+    let out = '';
+    let i = start;
+    while (i < end) {
+      const b = bytes[i];
+      let c = 0xfffd;
+      let need = 0;
+      if (b < 0x80) {
+        c = b;
+      } else if (b >= 0xc2 && b < 0xe0) {
+        need = 1;
+        c = b & 0x1f;
+      } else if (b >= 0xe0 && b < 0xf0) {
+        need = 2;
+        c = b & 0x0f;
+      } else if (b >= 0xf0 && b < 0xf5) {
+        need = 3;
+        c = b & 0x07;
+      }
+      let j = 1;
+      for (; j <= need; j++) {
+        const cont = bytes[i + j];
+        if (i + j >= end || (cont & 0xc0) !== 0x80) break;
+        c = (c << 6) | (cont & 0x3f);
+      }
+      if (j <= need) {
+        c = 0xfffd;
+        i += j;
+      } else {
+        i += need + 1;
+      }
+      out += String.fromCodePoint(c);
+    }
+    return out;
+  }
+
+  function base64Encode(bytes, start, end, url) {
+    let out = '';
+    for (let i = start; i < end; i += 3) {
+      const n = (bytes[i] << 16) | ((i + 1 < end ? bytes[i + 1] : 0) << 8) | (i + 2 < end ? bytes[i + 2] : 0);
+      out += BASE64[(n >> 18) & 63] + BASE64[(n >> 12) & 63];
+      out += i + 1 < end ? BASE64[(n >> 6) & 63] : url ? '' : '=';
+      out += i + 2 < end ? BASE64[n & 63] : url ? '' : '=';
+    }
+    return url ? out.replace(/\+/g, '-').replace(/\//g, '_') : out;
+  }
+
+  function base64Decode(str) {
+    const out = [];
+    let bits = 0;
+    let value = 0;
+    for (const ch of str) {
+      let idx = BASE64.indexOf(ch);
+      if (ch === '-') idx = 62;
+      if (ch === '_') idx = 63;
+      if (idx < 0) {
+        if (ch === '=') break;
+        continue;
+      }
+      value = (value << 6) | idx;
+      bits += 6;
+      if (bits >= 8) {
+        bits -= 8;
+        out.push((value >> bits) & 0xff);
+      }
+    }
+    return out;
+  }
+
+  function encode(str, encoding) {
+    str = String(str);
+    switch (checkEncoding(encoding)) {
+      case 'utf8':
+        return utf8Encode(str);
+      case 'latin1':
+      case 'ascii':
+        return Array.from(str, (ch) => ch.charCodeAt(0) & 0xff);
+      case 'utf16le': {
+        const out = [];
+        for (let i = 0; i < str.length; i++) {
+          const c = str.charCodeAt(i);
+          out.push(c & 0xff, c >> 8);
+        }
+        return out;
+      }
+      case 'hex': {
+        const out = [];
+        for (let i = 0; i + 1 < str.length; i += 2) {
+          const byte = parseInt(str.substr(i, 2), 16);
+          if (Number.isNaN(byte)) break;
+          out.push(byte);
+        }
+        return out;
+      }
+      case 'base64':
+      case 'base64url':
+        return base64Decode(str);
+    }
+  }
+
+  function decode(bytes, encoding, start, end) {
+    switch (checkEncoding(encoding)) {
+      case 'utf8':
+        return utf8Decode(bytes, start, end);
+      case 'latin1':
+        return latin1(bytes, start, end);
+      case 'ascii': {
+        let out = '';
+        for (let i = start; i < end; i++) out += String.fromCharCode(bytes[i] & 0x7f);
+        return out;
+      }
+      case 'utf16le': {
+        let out = '';
+        for (let i = start; i + 1 < end; i += 2) out += String.fromCharCode(bytes[i] | (bytes[i + 1] << 8));
+        return out;
+      }
+      case 'hex': {
+        let out = '';
+        for (let i = start; i < end; i++) out += (bytes[i] < 16 ? '0' : '') + bytes[i].toString(16);
+        return out;
+      }
+      case 'base64':
+        return base64Encode(bytes, start, end, false);
+      case 'base64url':
+        return base64Encode(bytes, start, end, true);
+    }
+  }
+
+  function latin1(bytes, start, end) {
+    let out = '';
+    for (let i = start; i < end; i += 8192) {
+      out += String.fromCharCode.apply(null, bytes.subarray(i, Math.min(end, i + 8192)));
+    }
+    return out;
+  }
+
+  // --- Buffer ---
+
+  class Buffer extends Uint8Array {
+    static from(value, encodingOrOffset, length) {
+      if (typeof value === 'string') return new Buffer(encode(value, encodingOrOffset));
+      if (value instanceof ArrayBuffer) {
+        const offset = encodingOrOffset === undefined ? 0 : encodingOrOffset;
+        const len = length === undefined ? value.byteLength - offset : length;
+        return new Buffer(value, offset, len);
+      }
+      if (ArrayBuffer.isView(value)) {
+        return new Buffer(new Uint8Array(value.buffer, value.byteOffset, value.byteLength));
+      }
+      if (value && value.type === 'Buffer' && Array.isArray(value.data)) return new Buffer(value.data);
+      if (value != null && typeof value === 'object') return new Buffer(Array.from(value));
+      throw new TypeError(
+        'The first argument must be of type string or an instance of Buffer, ArrayBuffer, or Array',
+      );
+    }
+
+    static alloc(size, fill, encoding) {
+      const buf = new Buffer(size);
+      if (fill !== undefined) buf.fill(fill, 0, size, encoding);
+      return buf;
+    }
+
+    static allocUnsafe(size) {
+      return new Buffer(size);
+    }
+
+    static byteLength(value, encoding) {
+      if (typeof value !== 'string') return value.byteLength;
+      return encode(value, encoding).length;
+    }
+
+    static concat(list, totalLength) {
+      if (totalLength === undefined) totalLength = list.reduce((n, b) => n + b.length, 0);
+      const out = Buffer.alloc(totalLength);
+      let offset = 0;
+      for (const buf of list) {
+        if (offset >= totalLength) break;
+        const chunk = buf.subarray(0, totalLength - offset);
+        out.set(chunk, offset);
+        offset += chunk.length;
+      }
+      return out;
+    }
+
+    static isBuffer(value) {
+      return value instanceof Buffer;
+    }
+
+    static isEncoding(encoding) {
+      return typeof encoding === 'string' && normalizeEncoding(encoding) !== null;
+    }
+
+    static compare(a, b) {
+      return a.compare(b);
+    }
+
+    fill(value, start, end, encoding) {
+      if (typeof start === 'string') {
+        encoding = start;
+        start = 0;
+        end = this.length;
+      }
+      if (typeof value === 'string') {
+        const bytes = encode(value, encoding);
+        start = start === undefined ? 0 : start;
+        end = end === undefined ? this.length : end;
+        if (bytes.length === 0) return super.fill(0, start, end);
+        for (let i = start; i < end; i++) this[i] = bytes[(i - start) % bytes.length];
+        return this;
+      }
+      return super.fill(value, start, end);
+    }
+
+    toString(encoding, start, end) {
+      start = Math.max(0, start === undefined ? 0 : start | 0);
+      end = Math.min(this.length, end === undefined ? this.length : end | 0);
+      if (end <= start) return '';
+      return decode(this, encoding, start, end);
+    }
+
+    write(string, offset, length, encoding) {
+      if (typeof offset === 'string') {
+        encoding = offset;
+        offset = 0;
+      } else if (typeof length === 'string') {
+        encoding = length;
+        length = undefined;
+      }
+      offset = offset === undefined ? 0 : offset;
+      const bytes = encode(string, encoding);
+      const count = Math.min(bytes.length, this.length - offset, length === undefined ? Infinity : length);
+      for (let i = 0; i < count; i++) this[offset + i] = bytes[i];
+      return count;
+    }
+
+    toJSON() {
+      return { type: 'Buffer', data: Array.from(this) };
+    }
+
+    equals(other) {
+      return this.compare(other) === 0;
+    }
+
+    compare(other) {
+      const len = Math.min(this.length, other.length);
+      for (let i = 0; i < len; i++) {
+        if (this[i] !== other[i]) return this[i] < other[i] ? -1 : 1;
+      }
+      return this.length === other.length ? 0 : this.length < other.length ? -1 : 1;
+    }
+
+    // Node's slice() shares memory with the original, unlike Uint8Array's
+    slice(start, end) {
+      return this.subarray(start, end);
+    }
+
+    subarray(start, end) {
+      const view = Uint8Array.prototype.subarray.call(this, start, end);
+      return new Buffer(view.buffer, view.byteOffset, view.length);
+    }
+
+    indexOf(value, byteOffset, encoding) {
+      if (typeof value === 'number') return super.indexOf(value, byteOffset);
+      const needle = typeof value === 'string' ? encode(value, encoding) : Array.from(value);
+      const from = byteOffset === undefined ? 0 : byteOffset;
+      outer: for (let i = from; i <= this.length - needle.length; i++) {
+        for (let j = 0; j < needle.length; j++) {
+          if (this[i + j] !== needle[j]) continue outer;
+        }
+        return i;
+      }
+      return -1;
+    }
+
+    includes(value, byteOffset, encoding) {
+      return this.indexOf(value, byteOffset, encoding) !== -1;
+    }
+  }
+
+  function toLatin1(data, encoding) {
+    if (typeof data === 'string') {
+      // Plain ASCII is already its own latin1 encoding
+      if (checkEncoding(encoding) === 'utf8' && /^[\x00-\x7f]*$/.test(data)) return data;
+      return latin1(Uint8Array.from(encode(data, encoding)), 0, Buffer.byteLength(data, encoding));
+    }
+    if (ArrayBuffer.isView(data)) {
+      const bytes = new Uint8Array(data.buffer, data.byteOffset, data.byteLength);
+      return latin1(bytes, 0, bytes.length);
+    }
+    if (data instanceof ArrayBuffer) return latin1(new Uint8Array(data), 0, data.byteLength);
+    throw new TypeError(
+      'The "data" argument must be of type string or an instance of Buffer, TypedArray, or DataView',
+    );
+  }
+
+  function fromLatin1(str) {
+    const buf = Buffer.alloc(str.length);
+    for (let i = 0; i < str.length; i++) buf[i] = str.charCodeAt(i);
+    return buf;
+  }
+
+  // --- process ---
+
+  function writer(fd) {
+    return {
+      fd: fd,
+      isTTY: false,
+      write(chunk, encoding, callback) {
+        if (typeof encoding === 'function') callback = encoding;
+        if (typeof chunk === 'string' && (encoding === undefined || typeof encoding === 'function')) {
+          __node_write(fd, chunk, false);
+        } else {
+          __node_write(fd, toLatin1(chunk, typeof encoding === 'string' ? encoding : 'utf8'), true);
+        }
+        if (typeof callback === 'function') queueMicrotask(callback);
+        return true;
+      },
+    };
+  }
+
+  const startTime = Date.now();
+
+  const process = {
+    title: 'node',
+    version: 'v0.1.0',
+    versions: { node: '0.1.0' },
+    platform: 'linux',
+    arch: 'wasm32',
+    pid: 1,
+    ppid: 0,
+    execPath: init.execPath,
+    argv: init.argv,
+    argv0: 'node',
+    execArgv: [],
+    env: init.env,
+    exitCode: undefined,
+    stdout: writer(1),
+    stderr: writer(2),
+    cwd() {
+      return fsCall('cwd', {}, 'uv_cwd');
+    },
+    chdir(directory) {
+      fsCall('chdir', { path: String(directory) }, 'chdir');
+    },
+    exit(code) {
+      __node_exit(code === undefined ? process.exitCode : code);
+    },
+    nextTick(callback, ...args) {
+      queueMicrotask(() => callback(...args));
+    },
+    uptime() {
+      return (Date.now() - startTime) / 1000;
+    },
+    hrtime(previous) {
+      const now = Date.now();
+      const time = [Math.floor(now / 1000), (now % 1000) * 1e6];
+      if (!previous) return time;
+      let sec = time[0] - previous[0];
+      let nsec = time[1] - previous[1];
+      if (nsec < 0) {
+        sec -= 1;
+        nsec += 1e9;
+      }
+      return [sec, nsec];
+    },
+    memoryUsage() {
+      return { rss: 0, heapTotal: 0, heapUsed: 0, external: 0, arrayBuffers: 0 };
+    },
+  };
+  process.hrtime.bigint = function () {
+    return BigInt(Date.now()) * 1000000n;
+  };
+
+  // --- path (POSIX) ---
+
+  function assertPath(p) {
+    if (typeof p !== 'string') {
+      throw new TypeError('The "path" argument must be of type string. Received ' + typeof p);
+    }
+  }
+
+  function normalizeSegments(p, absolute) {
+    const out = [];
+    for (const seg of p.split('/')) {
+      if (seg === '' || seg === '.') continue;
+      if (seg === '..') {
+        if (out.length > 0 && out[out.length - 1] !== '..') out.pop();
+        else if (!absolute) out.push('..');
+      } else {
+        out.push(seg);
+      }
+    }
+    return out.join('/');
+  }
+
+  const path = {
+    sep: '/',
+    delimiter: ':',
+    normalize(p) {
+      assertPath(p);
+      if (p === '') return '.';
+      const absolute = p.startsWith('/');
+      const trailing = p.endsWith('/');
+      let out = normalizeSegments(p, absolute);
+      if (out === '' && !absolute) out = '.';
+      if (out !== '' && trailing) out += '/';
+      return (absolute ? '/' : '') + out;
+    },
+    join(...parts) {
+      parts.forEach(assertPath);
+      const joined = parts.filter((p) => p !== '').join('/');
+      return joined === '' ? '.' : path.normalize(joined);
+    },
+    resolve(...parts) {
+      let resolved = '';
+      for (let i = parts.length - 1; i >= 0 && !resolved.startsWith('/'); i--) {
+        assertPath(parts[i]);
+        if (parts[i] !== '') resolved = parts[i] + (resolved ? '/' + resolved : '');
+      }
+      if (!resolved.startsWith('/')) resolved = process.cwd() + (resolved ? '/' + resolved : '');
+      return '/' + normalizeSegments(resolved, true);
+    },
+    isAbsolute(p) {
+      assertPath(p);
+      return p.startsWith('/');
+    },
+    relative(from, to) {
+      const a = path.resolve(from).split('/').filter(Boolean);
+      const b = path.resolve(to).split('/').filter(Boolean);
+      let i = 0;
+      while (i < a.length && i < b.length && a[i] === b[i]) i++;
+      return a.slice(i).map(() => '..').concat(b.slice(i)).join('/');
+    },
+    dirname(p) {
+      assertPath(p);
+      if (p === '') return '.';
+      const trimmed = p.replace(/\/+$/, '');
+      if (trimmed === '') return '/';
+      const idx = trimmed.lastIndexOf('/');
+      if (idx === -1) return '.';
+      if (idx === 0) return '/';
+      return trimmed.slice(0, idx).replace(/\/+$/, '') || '/';
+    },
+    basename(p, ext) {
+      assertPath(p);
+      const trimmed = p.replace(/\/+$/, '');
+      let base = trimmed.slice(trimmed.lastIndexOf('/') + 1);
+      if (ext !== undefined && base.endsWith(ext) && base !== ext) base = base.slice(0, -ext.length);
+      return base;
+    },
+    extname(p) {
+      const base = path.basename(p);
+      const idx = base.lastIndexOf('.');
+      return idx <= 0 ? '' : base.slice(idx);
+    },
+    parse(p) {
+      assertPath(p);
+      const root = p.startsWith('/') ? '/' : '';
+      const base = path.basename(p);
+      const ext = path.extname(p);
+      let dir = path.dirname(p);
+      if (dir === '.' && !p.includes('/')) dir = '';
+      return { root: root, dir: dir, base: base, ext: ext, name: ext ? base.slice(0, -ext.length) : base };
+    },
+    format(obj) {
+      const dir = obj.dir || obj.root || '';
+      const base = obj.base || (obj.name || '') + (obj.ext || '');
+      if (!dir) return base;
+      return dir === obj.root ? dir + base : dir + '/' + base;
+    },
+    toNamespacedPath(p) {
+      return p;
+    },
+  };
+  path.posix = path;
+
+  // --- fs ---
+
+  function fsError(err, syscall, p, dest) {
+    let message = err.code + ': ' + err.message + ', ' + syscall;
+    if (p !== undefined) message += " '" + p + "'";
+    if (dest !== undefined) message += " -> '" + dest + "'";
+    const e = new Error(err.code.startsWith('ERR_') ? err.message : message);
+    e.code = err.code;
+    if (!err.code.startsWith('ERR_')) e.syscall = syscall;
+    if (p !== undefined) e.path = p;
+    if (dest !== undefined) e.dest = dest;
+    return e;
+  }
+
+  function fsCall(op, params, syscall) {
+    const reply = JSON.parse(__node_fs(op, JSON.stringify(params)));
+    if (reply.error) throw fsError(reply.error, syscall, params.path, params.dest);
+    return reply.ok;
+  }
+
+  function toPath(p) {
+    if (typeof p === 'string') return p;
+    if (p instanceof Uint8Array) return Buffer.from(p).toString();
+    if (p && typeof p.href === 'string' && p.protocol === 'file:') return decodeURIComponent(p.pathname);
+    throw new TypeError('The "path" argument must be of type string or an instance of Buffer or URL');
+  }
+
+  function options(opts, defaults) {
+    if (typeof opts === 'string') return Object.assign({}, defaults, { encoding: opts });
+    return Object.assign({}, defaults, opts || {});
+  }
+
+  class Stats {
+    constructor(raw) {
+      this._type = raw.type;
+      this.dev = 0;
+      this.ino = 0;
+      this.mode = raw.mode;
+      this.nlink = 1;
+      this.uid = 0;
+      this.gid = 0;
+      this.size = raw.size;
+      this.blksize = 4096;
+      this.blocks = Math.ceil(raw.size / 512);
+      this.atimeMs = raw.atimeMs;
+      this.mtimeMs = raw.mtimeMs;
+      this.ctimeMs = raw.mtimeMs;
+      this.birthtimeMs = raw.birthtimeMs;
+      this.atime = new Date(raw.atimeMs);
+      this.mtime = new Date(raw.mtimeMs);
+      this.ctime = new Date(raw.mtimeMs);
+      this.birthtime = new Date(raw.birthtimeMs);
+    }
+    isFile() {
+      return this._type === 'file';
+    }
+    isDirectory() {
+      return this._type === 'dir';
+    }
+    isSymbolicLink() {
+      return this._type === 'symlink';
+    }
+    isFIFO() {
+      return false;
+    }
+    isSocket() {
+      return false;
+    }
+    isBlockDevice() {
+      return false;
+    }
+    isCharacterDevice() {
+      return false;
+    }
+  }
+
+  class Dirent {
+    constructor(name, type, parentPath) {
+      this.name = name;
+      this.parentPath = parentPath;
+      this.path = parentPath;
+      this._type = type;
+    }
+    isFile() {
+      return this._type === 'file';
+    }
+    isDirectory() {
+      return this._type === 'dir';
+    }
+    isSymbolicLink() {
+      return this._type === 'symlink';
+    }
+  }
+
+  const fs = {
+    constants: { F_OK: 0, R_OK: 4, W_OK: 2, X_OK: 1, COPYFILE_EXCL: 1 },
+    Stats: Stats,
+    Dirent: Dirent,
+
+    readFileSync(file, opts) {
+      const o = options(opts, { encoding: null });
+      const data = fromLatin1(fsCall('read', { path: toPath(file) }, 'open'));
+      return o.encoding ? data.toString(o.encoding) : data;
+    },
+    writeFileSync(file, data, opts) {
+      const o = options(opts, { encoding: 'utf8', flag: 'w' });
+      fsCall(
+        'write',
+        { path: toPath(file), data: toLatin1(data, o.encoding), append: o.flag.startsWith('a') },
+        'open',
+      );
+    },
+    appendFileSync(file, data, opts) {
+      fs.writeFileSync(file, data, Object.assign(options(opts, { encoding: 'utf8' }), { flag: 'a' }));
+    },
+    readdirSync(dir, opts) {
+      const o = options(opts, {});
+      const root = toPath(dir);
+      const out = [];
+      const walk = (sub) => {
+        const full = sub ? root.replace(/\/+$/, '') + '/' + sub : root;
+        for (const entry of fsCall('readdir', { path: full }, 'scandir')) {
+          const name = sub ? sub + '/' + entry.name : entry.name;
+          out.push(o.withFileTypes ? new Dirent(entry.name, entry.type, full) : name);
+          if (o.recursive && entry.type === 'dir') walk(name);
+        }
+      };
+      walk('');
+      return out;
+    },
+    statSync(file, opts) {
+      return statImpl(file, opts, true);
+    },
+    lstatSync(file, opts) {
+      return statImpl(file, opts, false);
+    },
+    existsSync(file) {
+      try {
+        fsCall('stat', { path: toPath(file), follow: true }, 'stat');
+        return true;
+      } catch (e) {
+        return false;
+      }
+    },
+    accessSync(file) {
+      fsCall('stat', { path: toPath(file), follow: true }, 'access');
+    },
+    mkdirSync(dir, opts) {
+      const o = typeof opts === 'number' ? {} : options(opts, {});
+      fsCall('mkdir', { path: toPath(dir), recursive: !!o.recursive }, 'mkdir');
+    },
+    rmSync(file, opts) {
+      const o = options(opts, {});
+      fsCall('rm', { path: toPath(file), recursive: !!o.recursive, force: !!o.force }, 'rm');
+    },
+    rmdirSync(dir, opts) {
+      const o = options(opts, {});
+      if (o.recursive) fsCall('rm', { path: toPath(dir), recursive: true, force: false }, 'rmdir');
+      else fsCall('rmdir', { path: toPath(dir) }, 'rmdir');
+    },
+    unlinkSync(file) {
+      fsCall('unlink', { path: toPath(file) }, 'unlink');
+    },
+    renameSync(from, to) {
+      fsCall('rename', { path: toPath(from), dest: toPath(to) }, 'rename');
+    },
+    copyFileSync(from, to, mode) {
+      if (mode & fs.constants.COPYFILE_EXCL && fs.existsSync(to)) {
+        throw fsError({ code: 'EEXIST', message: 'file already exists' }, 'copyfile', toPath(from), toPath(to));
+      }
+      fsCall('copyFile', { path: toPath(from), dest: toPath(to) }, 'copyfile');
+    },
+    realpathSync(file) {
+      return fsCall('realpath', { path: toPath(file) }, 'realpath');
+    },
+  };
+
+  function statImpl(file, opts, follow) {
+    const o = options(opts, { throwIfNoEntry: true });
+    try {
+      return new Stats(fsCall('stat', { path: toPath(file), follow: follow }, follow ? 'stat' : 'lstat'));
+    } catch (e) {
+      if (e.code === 'ENOENT' && !o.throwIfNoEntry) return undefined;
+      throw e;
+    }
+  }
+
+  // Promise and callback variants wrap the synchronous implementations
+  const ASYNC_OPS = [
+    'readFile',
+    'writeFile',
+    'appendFile',
+    'readdir',
+    'stat',
+    'lstat',
+    'access',
+    'mkdir',
+    'rm',
+    'rmdir',
+    'unlink',
+    'rename',
+    'copyFile',
+    'realpath',
+  ];
+
+  const promises = { constants: fs.constants };
+  for (const name of ASYNC_OPS) {
+    const sync = fs[name + 'Sync'];
+    promises[name] = function (...args) {
+      return new Promise((resolve) => resolve(sync(...args)));
+    };
+    fs[name] = function (...args) {
+      const callback = args.pop();
+      if (typeof callback !== 'function') {
+        throw new TypeError('The "cb" argument must be of type function');
+      }
+      promises[name](...args).then(
+        (value) => callback(null, value),
+        (err) => callback(err),
+      );
+    };
+  }
+  fs.exists = function (file, callback) {
+    const exists = fs.existsSync(file);
+    queueMicrotask(() => callback(exists));
+  };
+  fs.promises = promises;
+
+  // --- Builtin module table (used by modules.js) ---
+
+  const builtins = {
+    buffer: { Buffer: Buffer },
+    fs: fs,
+    'fs/promises': promises,
+    path: path,
+    'path/posix': path,
+    process: process,
+  };
+
+  define(global, 'Buffer', Buffer);
+  define(global, 'process', process);
+  define(global, '__node_builtins', builtins);
+})(globalThis);
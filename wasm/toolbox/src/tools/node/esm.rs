@@ -0,0 +1,766 @@
+//! ES module syntax lowering for the JS module loader.
+//!
+//! Boa evaluates everything as classic scripts inside a CommonJS-style
+//! wrapper, so static `import`/`export` statements are rewritten into calls on
+//! the wrapper's hidden parameters:
+//!
+//! - `__esm_imports[i]` — namespace of the i-th imported module, linked by
+//!   `modules.js` before the body runs;
+//! - `__esm_export({ name: () => local }, [star indices])` — live export
+//!   getters, emitted as a prefix on the first line;
+//! - `__esm_import(spec)` — dynamic `import()`;
+//! - `__esm_meta` — `import.meta`.
+//!
+//! The rewrite works on a token stream (strings, templates, regexes and
+//! comments are skipped correctly) and keeps every statement on its original
+//! line so error positions still match the source.
+
+use serde_json::json;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Kind {
+    Ident,
+    Punct,
+    Str,
+    Template,
+    Regex,
+    Number,
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct Token {
+    pub kind: Kind,
+    pub start: usize,
+    pub end: usize,
+    /// Open brackets enclosing the token (a bracket pair shares one depth).
+    pub depth: usize,
+    pub newline_before: bool,
+}
+
+/// Result of [`transform`].
+pub struct Transformed {
+    pub code: String,
+    /// Specifiers of static imports and re-exports, indexed like `__esm_imports`.
+    pub imports: Vec<String>,
+    /// Whether the source used any module syntax (`import`/`export`/`import.meta`).
+    pub esm: bool,
+}
+
+impl Transformed {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({ "code": self.code, "imports": self.imports, "esm": self.esm })
+    }
+}
+
+pub fn transform(source: &str) -> Transformed {
+    let tokens = tokenize(source);
+    let mut rewriter = Rewriter {
+        src: source,
+        tokens: &tokens,
+        edits: Vec::new(),
+        imports: Vec::new(),
+        exports: Vec::new(),
+        star_exports: Vec::new(),
+        esm: false,
+    };
+    rewriter.run();
+
+    let mut code = String::with_capacity(source.len() + 64);
+    if rewriter.esm {
+        code.push_str(&rewriter.export_prefix());
+    }
+    let mut pos = 0;
+    let mut edits = rewriter.edits;
+    edits.sort_by_key(|e| e.0);
+    if source.starts_with("#!") {
+        // Keep the shebang line, but as a comment
+        edits.insert(0, (0, 2, "//".to_string()));
+    }
+    for (start, end, text) in edits {
+        code.push_str(&source[pos..start]);
+        code.push_str(&text);
+        // Keep line numbers stable when a multi-line statement is replaced
+        let removed = source[start..end].matches('\n').count();
+        let added = text.matches('\n').count();
+        for _ in added..removed {
+            code.push('\n');
+        }
+        pos = end;
+    }
+    code.push_str(&source[pos..]);
+
+    Transformed {
+        code,
+        imports: rewriter.imports,
+        esm: rewriter.esm,
+    }
+}
+
+struct Rewriter<'a> {
+    src: &'a str,
+    tokens: &'a [Token],
+    edits: Vec<(usize, usize, String)>,
+    imports: Vec<String>,
+    /// (exported name, getter expression)
+    exports: Vec<(String, String)>,
+    star_exports: Vec<usize>,
+    esm: bool,
+}
+
+impl Rewriter<'_> {
+    fn text(&self, i: usize) -> &str {
+        self.tokens
+            .get(i)
+            .map(|t| &self.src[t.start..t.end])
+            .unwrap_or("")
+    }
+
+    fn is(&self, i: usize, text: &str) -> bool {
+        self.tokens.get(i).is_some_and(|t| t.kind != Kind::Str) && self.text(i) == text
+    }
+
+    fn kind(&self, i: usize) -> Option<Kind> {
+        self.tokens.get(i).map(|t| t.kind)
+    }
+
+    fn run(&mut self) {
+        let mut i = 0;
+        while i < self.tokens.len() {
+            let after_dot = i > 0 && self.is(i - 1, ".");
+            if self.kind(i) != Some(Kind::Ident) || after_dot {
+                i += 1;
+                continue;
+            }
+            let tok = self.tokens[i];
+            match self.text(i) {
+                "import" if self.is(i + 1, "(") => {
+                    self.edits
+                        .push((tok.start, tok.end, "__esm_import".to_string()));
+                    i += 1;
+                }
+                "import" if self.is(i + 1, ".") && self.is(i + 2, "meta") => {
+                    self.esm = true;
+                    let end = self.tokens[i + 2].end;
+                    self.edits.push((tok.start, end, "__esm_meta".to_string()));
+                    i += 3;
+                }
+                "import" if tok.depth == 0 => match self.import_statement(i) {
+                    Some(next) => i = next,
+                    None => i += 1,
+                },
+                "export" if tok.depth == 0 => match self.export_statement(i) {
+                    Some(next) => i = next,
+                    None => i += 1,
+                },
+                _ => i += 1,
+            }
+        }
+    }
+
+    fn add_import(&mut self, spec_token: usize) -> usize {
+        let spec = unquote(self.text(spec_token));
+        self.imports.push(spec);
+        self.imports.len() - 1
+    }
+
+    /// Include trailing import attributes (`with { type: 'json' }`) and `;`.
+    fn statement_end(&self, mut next: usize) -> usize {
+        if (self.is(next, "with") || self.is(next, "assert"))
+            && self.is(next + 1, "{")
+            && let Some(close) = self.matching_close(next + 1)
+        {
+            next = close + 1;
+        }
+        if self.is(next, ";") {
+            next += 1;
+        }
+        next
+    }
+
+    /// `import ... from 'spec'` → `const` bindings from `__esm_imports[i]`.
+    fn import_statement(&mut self, i: usize) -> Option<usize> {
+        let mut j = i + 1;
+        let mut bindings: Vec<(String, Option<String>)> = Vec::new();
+
+        if self.kind(j) != Some(Kind::Str) {
+            if self.kind(j) == Some(Kind::Ident) {
+                bindings.push((self.text(j).to_string(), Some("default".to_string())));
+                j += 1;
+                if self.is(j, ",") {
+                    j += 1;
+                }
+            }
+            if self.is(j, "*") {
+                if !self.is(j + 1, "as") {
+                    return None;
+                }
+                bindings.push((self.text(j + 2).to_string(), None));
+                j += 3;
+            } else if self.is(j, "{") {
+                let close = self.matching_close(j)?;
+                for (imported, local) in self.specifier_list(j + 1, close) {
+                    bindings.push((local, Some(imported)));
+                }
+                j = close + 1;
+            }
+            if !self.is(j, "from") {
+                return None;
+            }
+            j += 1;
+        }
+        if self.kind(j) != Some(Kind::Str) {
+            return None;
+        }
+
+        self.esm = true;
+        let index = self.add_import(j);
+        let next = self.statement_end(j + 1);
+
+        let decls: Vec<String> = bindings
+            .iter()
+            .map(|(local, imported)| match imported {
+                Some(name) => format!("{local} = __esm_imports[{index}][{}]", quote(name)),
+                None => format!("{local} = __esm_imports[{index}]"),
+            })
+            .collect();
+        let text = if decls.is_empty() {
+            String::new()
+        } else {
+            format!("const {};", decls.join(", "))
+        };
+        self.replace(i, next, text);
+        Some(next)
+    }
+
+    fn export_statement(&mut self, i: usize) -> Option<usize> {
+        let j = i + 1;
+        match self.text(j) {
+            "default" => self.export_default(i, j + 1),
+            "const" | "let" | "var" => {
+                self.esm = true;
+                self.strip(i, j);
+                let names = self.declarator_names(j + 1);
+                for name in names {
+                    self.exports.push((name.clone(), name));
+                }
+                Some(j + 1)
+            }
+            "function" | "async" | "class" => {
+                let name_at = self.declaration_name(j)?;
+                self.esm = true;
+                self.strip(i, j);
+                let name = self.text(name_at).to_string();
+                self.exports.push((name.clone(), name));
+                Some(j + 1)
+            }
+            "{" => {
+                let close = self.matching_close(j)?;
+                let specifiers = self.specifier_list(j + 1, close);
+                self.esm = true;
+                let mut next = close + 1;
+                if self.is(next, "from") && self.kind(next + 1) == Some(Kind::Str) {
+                    let index = self.add_import(next + 1);
+                    for (local, exported) in specifiers {
+                        let getter = format!("__esm_imports[{index}][{}]", quote(&local));
+                        self.exports.push((exported, getter));
+                    }
+                    next += 2;
+                } else {
+                    for (local, exported) in specifiers {
+                        self.exports.push((exported, local));
+                    }
+                }
+                let next = self.statement_end(next);
+                self.replace(i, next, String::new());
+                Some(next)
+            }
+            "*" => {
+                let (alias, from) = if self.is(j + 1, "as") {
+                    (Some(unquote(self.text(j + 2))), j + 3)
+                } else {
+                    (None, j + 1)
+                };
+                if !self.is(from, "from") || self.kind(from + 1) != Some(Kind::Str) {
+                    return None;
+                }
+                self.esm = true;
+                let index = self.add_import(from + 1);
+                match alias {
+                    Some(name) => self.exports.push((name, format!("__esm_imports[{index}]"))),
+                    None => self.star_exports.push(index),
+                }
+                let next = self.statement_end(from + 2);
+                self.replace(i, next, String::new());
+                Some(next)
+            }
+            _ => None,
+        }
+    }
+
+    fn export_default(&mut self, i: usize, k: usize) -> Option<usize> {
+        self.esm = true;
+        let is_function = self.is(k, "function")
+            || (self.is(k, "async")
+                && self.is(k + 1, "function")
+                && !self.tokens[k + 1].newline_before);
+        if is_function || self.is(k, "class") {
+            if let Some(name_at) = self.declaration_name(k) {
+                self.strip(i, k);
+                let name = self.text(name_at).to_string();
+                self.exports.push(("default".to_string(), name));
+                return Some(k);
+            }
+            // Anonymous: becomes an expression, which needs its own `;`
+            if let Some(open) =
+                (k..self.tokens.len()).find(|&n| self.is(n, "{") && self.tokens[n].depth == 0)
+                && let Some(close) = self.matching_close(open)
+            {
+                let end = self.tokens[close].end;
+                self.edits.push((end, end, ";".to_string()));
+            }
+        }
+        self.replace_between(i, k, "var __esm_default = ");
+        self.exports
+            .push(("default".to_string(), "__esm_default".to_string()));
+        Some(k)
+    }
+
+    /// Name token of `function* name`, `async function name` or `class name`.
+    fn declaration_name(&self, mut j: usize) -> Option<usize> {
+        if self.is(j, "async") {
+            j += 1;
+        }
+        if self.is(j, "function") {
+            j += 1;
+            if self.is(j, "*") {
+                j += 1;
+            }
+        } else if self.is(j, "class") {
+            j += 1;
+            if self.is(j, "extends") {
+                return None;
+            }
+        } else {
+            return None;
+        }
+        (self.kind(j) == Some(Kind::Ident)).then_some(j)
+    }
+
+    /// `a, b as c, 'x' as d` between braces → (source name, bound name) pairs.
+    fn specifier_list(&self, start: usize, close: usize) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        let mut j = start;
+        while j < close {
+            if self.is(j, ",") {
+                j += 1;
+                continue;
+            }
+            let name = unquote(self.text(j));
+            if self.is(j + 1, "as") {
+                out.push((name, unquote(self.text(j + 2))));
+                j += 3;
+            } else {
+                out.push((name.clone(), name));
+                j += 1;
+            }
+        }
+        out
+    }
+
+    /// Binding names declared by `const a = 1, { b, c: [d] } = e` starting at `j`.
+    fn declarator_names(&self, mut j: usize) -> Vec<String> {
+        let mut names = Vec::new();
+        let depth = self.tokens.get(j).map(|t| t.depth).unwrap_or(0);
+        loop {
+            match self.kind(j) {
+                Some(Kind::Ident) => {
+                    names.push(self.text(j).to_string());
+                    j += 1;
+                }
+                Some(Kind::Punct) if self.is(j, "{") || self.is(j, "[") => {
+                    let Some(close) = self.matching_close(j) else {
+                        break;
+                    };
+                    self.pattern_names(j, close, &mut names);
+                    j = close + 1;
+                }
+                _ => break,
+            }
+            // Skip the initializer up to the next declarator or statement end
+            while let Some(tok) = self.tokens.get(j) {
+                if tok.depth == depth {
+                    if self.is(j, ",") {
+                        break;
+                    }
+                    if self.is(j, ";") {
+                        return names;
+                    }
+                    let continues = j > 0
+                        && self.kind(j - 1) == Some(Kind::Punct)
+                        && !matches!(self.text(j - 1), ")" | "]" | "}");
+                    if tok.newline_before
+                        && !continues
+                        && !self.is(j, "=")
+                        && tok.kind == Kind::Ident
+                    {
+                        return names;
+                    }
+                }
+                j += 1;
+            }
+            if !self.is(j, ",") {
+                break;
+            }
+            j += 1;
+        }
+        names
+    }
+
+    /// Collect binding names from the destructuring pattern `open..=close`.
+    fn pattern_names(&self, open: usize, close: usize, names: &mut Vec<String>) {
+        let depth = self.tokens[open].depth + 1;
+        let mut element_start = open + 1;
+        for j in open + 1..=close {
+            let at_boundary = j == close || (self.tokens[j].depth == depth && self.is(j, ","));
+            if !at_boundary {
+                continue;
+            }
+            if element_start < j {
+                self.element_name(element_start, j, depth, names);
+            }
+            element_start = j + 1;
+        }
+    }
+
+    fn element_name(&self, start: usize, end: usize, depth: usize, names: &mut Vec<String>) {
+        let mut target = start;
+        if self.is(target, "...") {
+            target += 1;
+        }
+        // `key: target` (object patterns); computed keys sit one level deeper
+        if let Some(colon) =
+            (target..end).find(|&n| self.tokens[n].depth == depth && self.is(n, ":"))
+        {
+            target = colon + 1;
+        }
+        if self.is(target, "{") || self.is(target, "[") {
+            if let Some(close) = self.matching_close(target) {
+                self.pattern_names(target, close, names);
+            }
+        } else if self.kind(target) == Some(Kind::Ident) {
+            names.push(self.text(target).to_string());
+        }
+    }
+
+    fn matching_close(&self, open: usize) -> Option<usize> {
+        let depth = self.tokens[open].depth;
+        (open + 1..self.tokens.len())
+            .find(|&n| self.tokens[n].depth == depth && matches!(self.text(n), "}" | ")" | "]"))
+    }
+
+    /// Remove tokens `from..to` (exclusive) up to the start of token `to`.
+    fn strip(&mut self, from: usize, to: usize) {
+        self.replace_between(from, to, "");
+    }
+
+    fn replace_between(&mut self, from: usize, to: usize, text: &str) {
+        let start = self.tokens[from].start;
+        let end = self
+            .tokens
+            .get(to)
+            .map(|t| t.start)
+            .unwrap_or(self.src.len());
+        self.edits.push((start, end, text.to_string()));
+    }
+
+    /// Replace whole tokens `from..to` (exclusive).
+    fn replace(&mut self, from: usize, to: usize, text: String) {
+        let start = self.tokens[from].start;
+        let end = self.tokens[to - 1].end;
+        self.edits.push((start, end, text));
+    }
+
+    fn export_prefix(&self) -> String {
+        let getters: Vec<String> = self
+            .exports
+            .iter()
+            .map(|(name, getter)| format!("{}: () => {getter}", quote(name)))
+            .collect();
+        let stars: Vec<String> = self.star_exports.iter().map(|i| i.to_string()).collect();
+        format!(
+            "__esm_export({{ {} }}, [{}]);",
+            getters.join(", "),
+            stars.join(", ")
+        )
+    }
+}
+
+pub(super) fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+/// Decode a string literal token (or return an identifier unchanged).
+fn unquote(token: &str) -> String {
+    let Some(quote) = token.chars().next().filter(|c| *c == '\'' || *c == '"') else {
+        return token.to_string();
+    };
+    let inner = token
+        .strip_prefix(quote)
+        .and_then(|t| t.strip_suffix(quote))
+        .unwrap_or(token);
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                if let Some(ch) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    out.push(ch);
+                }
+            }
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+const REGEX_KEYWORDS: &[&str] = &[
+    "return",
+    "typeof",
+    "instanceof",
+    "in",
+    "of",
+    "new",
+    "delete",
+    "void",
+    "throw",
+    "case",
+    "do",
+    "else",
+    "yield",
+    "await",
+];
+
+pub(super) fn tokenize(src: &str) -> Vec<Token> {
+    let bytes = src.as_bytes();
+    let mut tokens: Vec<Token> = Vec::new();
+    // Open brackets; `$` marks a template substitution `${`
+    let mut stack: Vec<u8> = Vec::new();
+    let mut i = 0;
+    let mut newline = false;
+
+    if src.starts_with("#!") {
+        i = src.find('\n').unwrap_or(src.len());
+    }
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let depth = stack.len();
+
+        match c {
+            b'\n' | b'\r' => {
+                newline = true;
+                i += 1;
+                continue;
+            }
+            b' ' | b'\t' | 0x0b | 0x0c => {
+                i += 1;
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = src[i + 2..]
+                    .find("*/")
+                    .map(|p| i + 2 + p + 2)
+                    .unwrap_or(bytes.len());
+                newline |= src[i..end].contains('\n');
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+
+        let kind = match c {
+            b'\'' | b'"' => {
+                i = skip_string(bytes, i);
+                Kind::Str
+            }
+            b'`' => {
+                i = skip_template(bytes, i + 1, &mut stack);
+                Kind::Template
+            }
+            b'}' if stack.last() == Some(&b'$') => {
+                stack.pop();
+                i = skip_template(bytes, i + 1, &mut stack);
+                tokens.push(Token {
+                    kind: Kind::Template,
+                    start,
+                    end: i,
+                    depth: stack.len(),
+                    newline_before: newline,
+                });
+                newline = false;
+                continue;
+            }
+            b'/' if regex_allowed(src, tokens.last()) => {
+                i = skip_regex(bytes, i + 1);
+                Kind::Regex
+            }
+            b'0'..=b'9' => {
+                i = skip_number(bytes, i);
+                Kind::Number
+            }
+            b'.' if bytes.get(i + 1).is_some_and(u8::is_ascii_digit) => {
+                i = skip_number(bytes, i);
+                Kind::Number
+            }
+            _ if is_ident_start(src, i) => {
+                i += 1;
+                while i < bytes.len() && is_ident_continue(src, i) {
+                    i += 1;
+                }
+                Kind::Ident
+            }
+            _ => {
+                i += punct_len(&src[i..]);
+                match c {
+                    b'(' | b'[' | b'{' => stack.push(c),
+                    b')' | b']' | b'}' => {
+                        stack.pop();
+                    }
+                    _ => {}
+                }
+                Kind::Punct
+            }
+        };
+
+        // Closing brackets share the depth of their opening bracket
+        let depth = if matches!(c, b')' | b']' | b'}') && kind == Kind::Punct {
+            stack.len()
+        } else {
+            depth
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: i,
+            depth,
+            newline_before: newline,
+        });
+        newline = false;
+    }
+    tokens
+}
+
+fn regex_allowed(src: &str, prev: Option<&Token>) -> bool {
+    let Some(prev) = prev else { return true };
+    let text = &src[prev.start..prev.end];
+    match prev.kind {
+        Kind::Number | Kind::Str | Kind::Template | Kind::Regex => false,
+        Kind::Ident => REGEX_KEYWORDS.contains(&text),
+        Kind::Punct => !matches!(text, ")" | "]" | "++" | "--"),
+    }
+}
+
+fn punct_len(rest: &str) -> usize {
+    for p in ["...", "=>", "++", "--", "?."] {
+        if rest.starts_with(p) {
+            return p.len();
+        }
+    }
+    rest.chars().next().map(char::len_utf8).unwrap_or(1)
+}
+
+fn is_ident_start(src: &str, i: usize) -> bool {
+    let c = src[i..].chars().next().unwrap_or(' ');
+    c.is_alphabetic() || c == '_' || c == '$' || c == '#' || c == '\\'
+}
+
+fn is_ident_continue(src: &str, i: usize) -> bool {
+    if !src.is_char_boundary(i) {
+        return true;
+    }
+    let c = src[i..].chars().next().unwrap_or(' ');
+    c.is_alphanumeric() || c == '_' || c == '$' || c == '\\' || c == '\u{200c}' || c == '\u{200d}'
+}
+
+fn skip_string(bytes: &[u8], mut i: usize) -> usize {
+    let quote = bytes[i];
+    i += 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Scan template text from `i`, stopping after the closing backtick or
+/// after a `${` (pushing a marker so the matching `}` resumes the template).
+fn skip_template(bytes: &[u8], mut i: usize, stack: &mut Vec<u8>) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'`' => return i + 1,
+            b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                stack.push(b'$');
+                return i + 2;
+            }
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+fn skip_regex(bytes: &[u8], mut i: usize) -> usize {
+    let mut in_class = false;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'[' => in_class = true,
+            b']' => in_class = false,
+            b'/' if !in_class => {
+                i += 1;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'$') {
+                    i += 1;
+                }
+                return i;
+            }
+            b'\n' => return i,
+            _ => {}
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+fn skip_number(bytes: &[u8], mut i: usize) -> usize {
+    let hex = bytes[i] == b'0' && matches!(bytes.get(i + 1), Some(b'x' | b'X'));
+    while i < bytes.len() {
+        let c = bytes[i];
+        let exponent_sign =
+            !hex && matches!(c, b'+' | b'-') && i > 0 && matches!(bytes[i - 1], b'e' | b'E');
+        if c.is_ascii_alphanumeric() || c == b'.' || c == b'_' || exponent_sign {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    i
+}
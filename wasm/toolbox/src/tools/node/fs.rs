@@ -0,0 +1,193 @@
+//! Filesystem operations behind the `fs` module of the JS runtime.
+//!
+//! Each op takes its arguments as a JSON object and returns a JSON value.
+//! File contents cross the boundary as latin1 strings (one char per byte),
+//! which `Buffer` converts back to bytes on the JS side.
+
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use serde_json::{Value, json};
+
+/// A failed op, reported to JS as a Node-style error (`err.code`).
+pub struct FsError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl From<io::Error> for FsError {
+    fn from(e: io::Error) -> Self {
+        let (code, message) = match e.kind() {
+            ErrorKind::NotFound => ("ENOENT", "no such file or directory"),
+            ErrorKind::PermissionDenied => ("EACCES", "permission denied"),
+            ErrorKind::AlreadyExists => ("EEXIST", "file already exists"),
+            ErrorKind::IsADirectory => ("EISDIR", "illegal operation on a directory"),
+            ErrorKind::NotADirectory => ("ENOTDIR", "not a directory"),
+            ErrorKind::DirectoryNotEmpty => ("ENOTEMPTY", "directory not empty"),
+            ErrorKind::InvalidInput => ("EINVAL", "invalid argument"),
+            _ => return Self::other("EIO", e.to_string()),
+        };
+        Self::other(code, message)
+    }
+}
+
+impl FsError {
+    fn other(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn call(op: &str, args: &Value) -> Result<Value, FsError> {
+    let path = args["path"].as_str().unwrap_or_default();
+    let flag = |name: &str| args[name].as_bool().unwrap_or(false);
+
+    match op {
+        "read" => {
+            let bytes = fs::read(path)?;
+            Ok(Value::String(bytes.iter().map(|&b| b as char).collect()))
+        }
+        "readText" => {
+            let bytes = fs::read(path)?;
+            Ok(Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+        }
+        "write" => {
+            let data = latin1_bytes(args["data"].as_str().unwrap_or_default());
+            if flag("append") {
+                fs::OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(path)?
+                    .write_all(&data)?;
+            } else {
+                fs::write(path, data)?;
+            }
+            Ok(Value::Null)
+        }
+        "readdir" => {
+            let mut entries = Vec::new();
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                let kind = entry.file_type()?;
+                entries.push(json!({
+                    "name": entry.file_name().to_string_lossy(),
+                    "type": file_type_name(&kind),
+                }));
+            }
+            entries.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+            Ok(Value::Array(entries))
+        }
+        "stat" => {
+            let meta = if flag("follow") {
+                fs::metadata(path)?
+            } else {
+                fs::symlink_metadata(path)?
+            };
+            Ok(stat_json(&meta))
+        }
+        "mkdir" => {
+            if flag("recursive") {
+                fs::create_dir_all(path)?;
+            } else {
+                fs::create_dir(path)?;
+            }
+            Ok(Value::Null)
+        }
+        "rm" => {
+            let meta = match fs::symlink_metadata(path) {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == ErrorKind::NotFound && flag("force") => {
+                    return Ok(Value::Null);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if meta.is_dir() {
+                if !flag("recursive") {
+                    return Err(FsError::other(
+                        "ERR_FS_EISDIR",
+                        "Path is a directory (use recursive: true)",
+                    ));
+                }
+                fs::remove_dir_all(path)?;
+            } else {
+                fs::remove_file(path)?;
+            }
+            Ok(Value::Null)
+        }
+        "unlink" => {
+            fs::remove_file(path)?;
+            Ok(Value::Null)
+        }
+        "rmdir" => {
+            fs::remove_dir(path)?;
+            Ok(Value::Null)
+        }
+        "rename" => {
+            fs::rename(path, args["dest"].as_str().unwrap_or_default())?;
+            Ok(Value::Null)
+        }
+        "copyFile" => {
+            fs::copy(path, args["dest"].as_str().unwrap_or_default())?;
+            Ok(Value::Null)
+        }
+        "realpath" => {
+            let real = fs::canonicalize(path)?;
+            Ok(Value::String(real.to_string_lossy().into_owned()))
+        }
+        "cwd" => {
+            let cwd = std::env::current_dir()?;
+            Ok(Value::String(cwd.to_string_lossy().into_owned()))
+        }
+        "chdir" => {
+            std::env::set_current_dir(Path::new(path))?;
+            Ok(Value::Null)
+        }
+        _ => Err(FsError::other("ENOSYS", format!("unknown fs op '{op}'"))),
+    }
+}
+
+/// Convert a latin1 string from JS back to the bytes it encodes.
+fn latin1_bytes(data: &str) -> Vec<u8> {
+    data.chars().map(|c| c as u32 as u8).collect()
+}
+
+fn file_type_name(kind: &fs::FileType) -> &'static str {
+    if kind.is_dir() {
+        "dir"
+    } else if kind.is_symlink() {
+        "symlink"
+    } else if kind.is_file() {
+        "file"
+    } else {
+        "other"
+    }
+}
+
+fn stat_json(meta: &fs::Metadata) -> Value {
+    let millis = |t: io::Result<std::time::SystemTime>| {
+        t.ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as f64)
+            .unwrap_or(0.0)
+    };
+    let kind = file_type_name(&meta.file_type());
+    // WASI exposes no permission bits; report conventional defaults
+    let mode = match kind {
+        "dir" => 0o040755,
+        "symlink" => 0o120777,
+        _ if meta.permissions().readonly() => 0o100444,
+        _ => 0o100644,
+    };
+    json!({
+        "type": kind,
+        "size": meta.len(),
+        "mode": mode,
+        "mtimeMs": millis(meta.modified()),
+        "atimeMs": millis(meta.accessed()),
+        "birthtimeMs": millis(meta.created()),
+    })
+}
@@ -0,0 +1,639 @@
+mod esm;
+mod fs;
+mod ts;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use boa_engine::property::Attribute;
+use boa_engine::{
+    Context, JsError, JsNativeError, JsResult, JsString, JsValue, NativeFunction, Source, js_string,
+};
+use boa_runtime::console::{Console, DefaultLogger};
+
+use crate::{fetch, host};
+
+/// JS prelude installing timers, `fetch`, `host` and the event-loop hooks.
+const RUNTIME_JS: &str = include_str!("runtime.js");
+/// Node builtins (`Buffer`, `process`, `path`, `fs`).
+const BUILTINS_JS: &str = include_str!("builtins.js");
+/// CommonJS/ES module loader providing `require` and `import`.
+const MODULES_JS: &str = include_str!("modules.js");
+
+/// Path reported as `process.execPath` / `process.argv[0]`.
+const EXEC_PATH: &str = "/usr/local/bin/node";
+
+pub fn run(args: &[String]) -> i32 {
+    let mut args = args;
+    let mut input = InputType::default();
+    let mut eval_input = None;
+    while let Some(option) = args.first() {
+        match option.as_str() {
+            // Type stripping is always on
+            "--experimental-strip-types" | "--experimental-transform-types" | "--no-warnings" => {}
+            // Internal: run `-e` code for `Sandbox::eval_js` with this JSON `input`
+            _ if option.starts_with("--sandbox-eval=") => {
+                eval_input = option.strip_prefix("--sandbox-eval=");
+            }
+            _ => match option.strip_prefix("--input-type=") {
+                Some(value) => match InputType::parse(value) {
+                    Some(parsed) => input = parsed,
+                    None => {
+                        eprintln!("node: invalid value for --input-type: {value}");
+                        return 1;
+                    }
+                },
+                None => break,
+            },
+        }
+        args = &args[1..];
+    }
+
+    if args.is_empty() {
+        eprintln!("Usage: node [options] [script.js | script.ts] [arguments]");
+        eprintln!("Options:");
+        eprintln!("  -e, --eval <code>   Evaluate JavaScript code");
+        eprintln!("  -p, --print <code>  Evaluate and print result");
+        eprintln!("  --input-type=<type> How to run -e/-p code: module, commonjs,");
+        eprintln!("                      module-typescript or commonjs-typescript");
+        eprintln!("  --version           Print version");
+        return 1;
+    }
+
+    // Handle --version
+    if args[0] == "--version" || args[0] == "-v" {
+        println!("node v0.1.0 (boa-engine/wasm-sandbox)");
+        return 0;
+    }
+
+    let mut context = Context::default();
+
+    // Register console object globally (console.log, console.error, etc.)
+    if let Err(e) = Console::register_with_logger(DefaultLogger, &mut context) {
+        eprintln!("node: failed to register console: {e}");
+        return 1;
+    }
+
+    match args[0].as_str() {
+        "-e" | "--eval" | "-p" | "--print" => {
+            if args.len() < 2 {
+                eprintln!("node: {} requires an argument", args[0]);
+                return 1;
+            }
+            let output = match (args[0].as_str(), eval_input) {
+                ("-p" | "--print", _) => Output::Print,
+                (_, Some(_)) => Output::Report,
+                (_, None) => Output::Discard,
+            };
+            let installed = install_runtime(&mut context, None, &args[2..])
+                .and_then(|()| set_eval_input(&mut context, eval_input));
+            if let Err(e) = installed {
+                eprintln!("node: failed to initialise runtime: {e}");
+                return 1;
+            }
+            execute(
+                &mut context,
+                Entry::Eval {
+                    code: &args[1],
+                    output,
+                    input,
+                },
+            )
+        }
+        _ => {
+            // Treat as a file path
+            let file_path = &args[0];
+            if let Err(e) = std::fs::File::open(file_path) {
+                eprintln!("node: cannot open '{}': {}", file_path, e);
+                return 1;
+            }
+            let script = absolute(Path::new(file_path));
+            if let Err(e) = install_runtime(&mut context, Some(&script), &args[1..]) {
+                eprintln!("node: failed to initialise runtime: {e}");
+                return 1;
+            }
+            execute(&mut context, Entry::File(&script))
+        }
+    }
+}
+
+/// What `node` was asked to run.
+enum Entry<'a> {
+    /// `-e`/`-p` code: a global script, or an ES module if it uses module syntax.
+    Eval {
+        code: &'a str,
+        output: Output,
+        input: InputType,
+    },
+    /// A script file, loaded through the module system.
+    File(&'a Path),
+}
+
+/// What to do with the value of `-e`/`-p` code.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// `-e`: nothing.
+    Discard,
+    /// `-p`: print it.
+    Print,
+    /// `--sandbox-eval`: report it to the host as JSON, once the loop is idle.
+    Report,
+}
+
+impl Output {
+    /// The `mode` argument of `__node_main`.
+    fn mode(self) -> JsValue {
+        match self {
+            Output::Discard => JsValue::undefined(),
+            Output::Print => JsString::from("print").into(),
+            Output::Report => JsString::from("report").into(),
+        }
+    }
+}
+
+/// `--input-type`: how to treat `-e`/`-p` code.
+#[derive(Clone, Copy, Default)]
+struct InputType {
+    /// `Some(true)` for `module`, `Some(false)` for `commonjs`, `None` to
+    /// decide from the code's syntax.
+    module: Option<bool>,
+    /// Strip TypeScript syntax first (see `ts.rs`).
+    typescript: bool,
+}
+
+impl InputType {
+    fn parse(value: &str) -> Option<Self> {
+        let (format, typescript) = match value.strip_suffix("-typescript") {
+            Some(format) => (format, true),
+            None => (value, false),
+        };
+        let module = match format {
+            "module" => true,
+            "commonjs" => false,
+            _ => return None,
+        };
+        Some(Self {
+            module: Some(module),
+            typescript,
+        })
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    match std::env::current_dir() {
+        Ok(cwd) if path.is_relative() => cwd.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Register host natives and evaluate the JS preludes.
+///
+/// `script` and `script_args` become `process.argv[1..]`.
+fn install_runtime(
+    context: &mut Context,
+    script: Option<&Path>,
+    script_args: &[String],
+) -> JsResult<()> {
+    let mut argv = vec![EXEC_PATH.to_string()];
+    argv.extend(script.map(|p| p.to_string_lossy().into_owned()));
+    argv.extend(script_args.iter().cloned());
+    // TOOLBOX_CMD is the toolbox's own dispatch variable, not user environment
+    let env: HashMap<String, String> = std::env::vars()
+        .filter(|(k, _)| k != "TOOLBOX_CMD")
+        .collect();
+    let init = serde_json::json!({ "argv": argv, "env": env, "execPath": EXEC_PATH });
+
+    context
+        .register_global_property(
+            js_string!("__node_init"),
+            JsString::from(init.to_string().as_str()),
+            Attribute::WRITABLE | Attribute::NON_ENUMERABLE | Attribute::CONFIGURABLE,
+        )
+        .expect("failed to register runtime init data");
+    register_native(context, js_string!("__node_fetch"), js_fetch);
+    register_native(context, js_string!("__node_host_call"), js_host_call);
+    register_native(context, js_string!("__node_fs"), js_fs);
+    register_native(context, js_string!("__node_write"), js_write);
+    register_native(context, js_string!("__node_transform"), js_transform);
+    register_native(context, js_string!("__node_strip_types"), js_strip_types);
+    context.eval(Source::from_bytes(RUNTIME_JS))?;
+    context.eval(Source::from_bytes(BUILTINS_JS))?;
+    context.eval(Source::from_bytes(MODULES_JS))?;
+    Ok(())
+}
+
+/// Expose the `--sandbox-eval` JSON as the global `input`.
+fn set_eval_input(context: &mut Context, json: Option<&str>) -> JsResult<()> {
+    let Some(json) = json else {
+        return Ok(());
+    };
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| JsNativeError::syntax().with_message(format!("invalid input JSON: {e}")))?;
+    let value = JsValue::from_json(&value, context)?;
+    context.register_global_property(js_string!("input"), value, Attribute::all())
+}
+
+fn register_native(
+    context: &mut Context,
+    name: JsString,
+    f: fn(&JsValue, &[JsValue], &mut Context) -> JsResult<JsValue>,
+) {
+    let callable: JsValue = NativeFunction::from_fn_ptr(f)
+        .to_js_function(context.realm())
+        .into();
+    context
+        .register_global_property(
+            name,
+            callable,
+            Attribute::WRITABLE | Attribute::NON_ENUMERABLE | Attribute::CONFIGURABLE,
+        )
+        .expect("failed to register native function");
+}
+
+/// Run the entry point, then run the event loop until no work is left.
+fn execute(context: &mut Context, entry: Entry<'_>) -> i32 {
+    let report = matches!(
+        entry,
+        Entry::Eval {
+            output: Output::Report,
+            ..
+        }
+    );
+    let result = run_main(context, entry).and_then(|()| run_event_loop(context));
+    if report {
+        report_outcome(context, &result);
+    }
+    exit_status(context, result)
+}
+
+/// Send the script's value or exception to the host for `Sandbox::eval_js`
+/// (see `__node_eval_outcome` in `runtime.js`). An exception goes with the
+/// engine's trace of it, as `report_error` prints it.
+fn report_outcome(context: &mut Context, result: &JsResult<()>) {
+    let (error, trace) = match result {
+        Ok(()) => (JsValue::undefined(), JsValue::undefined()),
+        Err(err) => (err.to_opaque(context), JsString::from(err.to_string()).into()),
+    };
+    let args = [JsValue::from(result.is_err()), error, trace];
+    let outcome = call_global(context, js_string!("__node_eval_outcome"), &args)
+        .and_then(|json| json.to_string(context));
+    match outcome {
+        Ok(json) => host::report(&json.to_std_string_escaped()),
+        Err(err) => {
+            let error = serde_json::json!({ "name": "Error", "message": err.to_string() });
+            host::report(&serde_json::json!({ "error": error }).to_string());
+        }
+    }
+}
+
+/// Files go through `modules.js` (`__node_run_main`), as does `-e` code using
+/// module syntax. Other `-e`/`-p` code is evaluated as a global script;
+/// scripts using top-level `await` fail to parse as such and are re-run
+/// wrapped in an async function whose promise the loop awaits.
+fn run_main(context: &mut Context, entry: Entry<'_>) -> JsResult<()> {
+    let (source, output, input) = match entry {
+        Entry::File(path) => {
+            let path = JsString::from(path.to_string_lossy().as_ref());
+            call_global(context, js_string!("__node_run_main"), &[path.into()])?;
+            return Ok(());
+        }
+        Entry::Eval {
+            code,
+            output,
+            input,
+        } => (code, output, input),
+    };
+
+    let stripped;
+    let code = if input.typescript {
+        stripped = ts::strip(source);
+        stripped.as_str()
+    } else {
+        source
+    };
+    let script = esm::transform(code);
+    if input.module.unwrap_or(script.esm) && output != Output::Print {
+        // The loader strips types itself, keeping `source` for error locations
+        let args = [
+            JsString::from(source).into(),
+            JsString::from("[eval]").into(),
+            JsValue::from(input.typescript),
+            output.mode(),
+        ];
+        call_global(context, js_string!("__node_run_source"), &args)?;
+        return Ok(());
+    }
+
+    // Module syntax in code forced to run as a script is left for the parser to reject
+    let code = if script.esm {
+        code
+    } else {
+        script.code.as_str()
+    };
+    let result = match eval_source(context, code, None) {
+        Ok(value) => match output {
+            Output::Discard => Ok(()),
+            Output::Print => {
+                let text = value.to_string(context)?;
+                println!("{}", text.to_std_string_escaped());
+                Ok(())
+            }
+            Output::Report => {
+                call_global(context, js_string!("__node_main"), &[value, output.mode()]).map(drop)
+            }
+        },
+        Err(err) if is_top_level_await_error(&err, code) => eval_async(context, code, output),
+        Err(err) => Err(err),
+    };
+    result.map_err(|err| annotate_error(context, err, source))
+}
+
+/// Attach the failing line of `-e` source to a syntax error (see
+/// `__node_annotate` in `modules.js`).
+fn annotate_error(context: &mut Context, err: JsError, source: &str) -> JsError {
+    let value = err.to_opaque(context);
+    let args = [
+        value.clone(),
+        JsString::from("[eval]").into(),
+        JsString::from(source).into(),
+    ];
+    let _ = call_global(context, js_string!("__node_annotate"), &args);
+    JsError::from_opaque(value)
+}
+
+fn call_global(context: &mut Context, name: JsString, args: &[JsValue]) -> JsResult<JsValue> {
+    let function = context.global_object().get(name, context)?;
+    let function = function
+        .as_callable()
+        .ok_or_else(|| JsNativeError::typ().with_message("runtime hook is not callable"))?;
+    function.call(&JsValue::undefined(), args, context)
+}
+
+/// Map the run's outcome to an exit code.
+///
+/// `process.exit()` unwinds by throwing, so its code wins over any error;
+/// otherwise an error exits 1 and a clean run exits with `process.exitCode`.
+fn exit_status(context: &mut Context, result: JsResult<()>) -> i32 {
+    let failed = JsValue::from(result.is_err());
+    let status = call_global(context, js_string!("__node_exit_status"), &[failed])
+        .ok()
+        .and_then(|v| v.as_number());
+
+    match (status, result) {
+        (Some(code), _) => code as i32,
+        (None, Err(err)) => {
+            report_error(context, &err);
+            1
+        }
+        (None, Ok(())) => 0,
+    }
+}
+
+/// Print an uncaught error, preceded by its source location when known
+/// (`file:line`, the source line and a caret, as Node prints syntax errors).
+fn report_error(context: &mut Context, err: &JsError) {
+    let value = err.to_opaque(context);
+    let banner = call_global(context, js_string!("__node_error_banner"), &[value])
+        .ok()
+        .and_then(|v| v.as_string().map(|s| s.to_std_string_escaped()));
+    if let Some(banner) = banner {
+        eprintln!("{banner}\n");
+    }
+    eprintln!("{err}");
+}
+
+fn eval_source(context: &mut Context, code: &str, path: Option<&Path>) -> JsResult<JsValue> {
+    let source = Source::from_bytes(code);
+    match path {
+        Some(path) => context.eval(source.with_path(path)),
+        None => context.eval(source),
+    }
+}
+
+fn is_top_level_await_error(err: &JsError, code: &str) -> bool {
+    code.contains("await") && err.to_string().starts_with("SyntaxError")
+}
+
+/// Run `code` as the body of an async function and hand its promise to the loop.
+fn eval_async(context: &mut Context, code: &str, output: Output) -> JsResult<()> {
+    // The prefix stays on the first line so reported line numbers still match
+    let promise = if output == Output::Discard {
+        eval_source(context, &format!("(async () => {{{code}\n}})()"), None)?
+    } else {
+        let body = return_last_statement(code);
+        eval_source(context, &format!("(async () => {{{body}\n}})()"), None)?
+    };
+    call_global(
+        context,
+        js_string!("__node_main"),
+        &[promise, output.mode()],
+    )?;
+    Ok(())
+}
+
+/// Turn the last statement of `code` into a `return` if it is an expression,
+/// so an async-wrapped script resolves to what its completion value would be.
+fn return_last_statement(code: &str) -> String {
+    use esm::Kind;
+
+    let tokens = esm::tokenize(code);
+    let text = |t: &esm::Token| &code[t.start..t.end];
+    let mut end = tokens.len();
+    while end > 0 && tokens[end - 1].depth == 0 && text(&tokens[end - 1]) == ";" {
+        end -= 1;
+    }
+
+    // Statements end at a top-level `;`, after a top-level block, or where a
+    // line break ends an expression (automatic semicolon insertion)
+    let mut start = 0;
+    for i in 1..end {
+        let (prev, token) = (&tokens[i - 1], &tokens[i]);
+        if token.depth != 0 {
+            continue;
+        }
+        let starts_operand = matches!(
+            token.kind,
+            Kind::Ident | Kind::Str | Kind::Number | Kind::Template
+        );
+        let boundary = match text(prev) {
+            ";" => prev.depth == 0,
+            "}" => prev.depth == 0 && (starts_operand || token.newline_before),
+            _ => token.newline_before && starts_operand && ends_operand(prev, text(prev)),
+        };
+        if boundary {
+            start = i;
+        }
+    }
+
+    let Some(first) = tokens[..end].get(start) else {
+        return code.to_string();
+    };
+    let keyword = text(first);
+    let next = tokens.get(start + 1).map(text);
+    let declaration = matches!(
+        keyword,
+        "{" | "var"
+            | "let"
+            | "const"
+            | "function"
+            | "class"
+            | "if"
+            | "else"
+            | "for"
+            | "while"
+            | "do"
+            | "switch"
+            | "try"
+            | "return"
+            | "throw"
+            | "break"
+            | "continue"
+            | "import"
+            | "export"
+    ) || (keyword == "async" && next == Some("function"))
+        || next == Some(":");
+    if first.kind == Kind::Punct && keyword != "(" && keyword != "[" || declaration {
+        return code.to_string();
+    }
+    format!("{}return {}", &code[..first.start], &code[first.start..])
+}
+
+/// Whether `token` can end an expression, so a line break after it may end
+/// the statement.
+fn ends_operand(token: &esm::Token, text: &str) -> bool {
+    match token.kind {
+        esm::Kind::Punct => matches!(text, ")" | "]" | "}" | "++" | "--"),
+        _ => true,
+    }
+}
+
+/// Drain Boa's job queue, then fire timers one at a time until idle.
+fn run_event_loop(context: &mut Context) -> JsResult<()> {
+    loop {
+        let _ = context.run_jobs();
+        let more = context.eval(Source::from_bytes("__node_tick()"))?;
+        if !more.to_boolean() {
+            return Ok(());
+        }
+    }
+}
+
+/// `__node_fetch(requestJson)`: perform a request via the host bridge.
+///
+/// Takes and returns JSON strings; `runtime.js` wraps the result in a `Response`.
+fn js_fetch(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let request = args
+        .first()
+        .cloned()
+        .unwrap_or_else(JsValue::undefined)
+        .to_string(context)?
+        .to_std_string_escaped();
+    let request: serde_json::Value = serde_json::from_str(&request)
+        .map_err(|e| JsNativeError::typ().with_message(format!("fetch failed: {e}")))?;
+
+    let url = request["url"].as_str().unwrap_or_default();
+    let method = request["method"].as_str().unwrap_or("GET");
+    let headers: HashMap<String, String> =
+        serde_json::from_value(request["headers"].clone()).unwrap_or_default();
+    let body = request["body"].as_str();
+
+    match fetch::fetch(url, method, &headers, body) {
+        Ok(resp) => {
+            let json = serde_json::json!({
+                "status": resp.status,
+                "headers": resp.headers,
+                "body": resp.body,
+            });
+            Ok(JsValue::from(JsString::from(json.to_string().as_str())))
+        }
+        Err(e) => Err(JsNativeError::typ()
+            .with_message(format!("fetch failed: {e}"))
+            .into()),
+    }
+}
+
+/// `__node_host_call(name, argsJson)`: call a host function (see `host.rs`).
+///
+/// Returns `{"ok": value}` or `{"error": message}` as JSON; `runtime.js`
+/// turns errors into rejections.
+fn js_host_call(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let name = arg_string(args, 0, context)?;
+    let call_args: serde_json::Value =
+        serde_json::from_str(&arg_string(args, 1, context)?).unwrap_or_default();
+
+    let reply = match host::call(&name, &call_args) {
+        Ok(value) => serde_json::json!({ "ok": value }),
+        Err(e) => serde_json::json!({ "error": e }),
+    };
+    Ok(JsValue::from(JsString::from(reply.to_string().as_str())))
+}
+
+/// `__node_fs(op, argsJson)`: run a filesystem op from `fs.rs`.
+///
+/// Returns `{"ok": value}` or `{"error": {"code", "message"}}` as JSON;
+/// `builtins.js` turns errors into Node-style exceptions.
+fn js_fs(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let op = arg_string(args, 0, context)?;
+    let params: serde_json::Value =
+        serde_json::from_str(&arg_string(args, 1, context)?).unwrap_or_default();
+
+    let reply = match fs::call(&op, &params) {
+        Ok(value) => serde_json::json!({ "ok": value }),
+        Err(e) => serde_json::json!({ "error": { "code": e.code, "message": e.message } }),
+    };
+    Ok(JsValue::from(JsString::from(reply.to_string().as_str())))
+}
+
+/// `__node_write(fd, data, latin1)`: write to stdout (1) or stderr (2).
+///
+/// With `latin1`, each char of `data` is one raw byte (used for `Buffer`s).
+fn js_write(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let fd = args
+        .first()
+        .cloned()
+        .unwrap_or_else(JsValue::undefined)
+        .to_number(context)?;
+    let data = arg_string(args, 1, context)?;
+    let bytes = if args.get(2).is_some_and(|v| v.to_boolean()) {
+        data.chars().map(|c| c as u32 as u8).collect()
+    } else {
+        data.into_bytes()
+    };
+
+    let result = if fd == 2.0 {
+        let mut err = io::stderr();
+        err.write_all(&bytes).and_then(|()| err.flush())
+    } else {
+        let mut out = io::stdout();
+        out.write_all(&bytes).and_then(|()| out.flush())
+    };
+    result.map_err(|e| JsNativeError::error().with_message(e.to_string()))?;
+    Ok(JsValue::undefined())
+}
+
+/// `__node_transform(source)`: lower ES module syntax (see `esm.rs`).
+///
+/// Returns `{"code", "imports", "esm"}` as JSON.
+fn js_transform(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let source = arg_string(args, 0, context)?;
+    let module = esm::transform(&source);
+    Ok(JsValue::from(JsString::from(
+        module.to_json().to_string().as_str(),
+    )))
+}
+
+/// `__node_strip_types(source)`: strip TypeScript syntax (see `ts.rs`).
+fn js_strip_types(_this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+    let source = arg_string(args, 0, context)?;
+    Ok(JsValue::from(JsString::from(ts::strip(&source).as_str())))
+}
+
+fn arg_string(args: &[JsValue], index: usize, context: &mut Context) -> JsResult<String> {
+    Ok(args
+        .get(index)
+        .cloned()
+        .unwrap_or_else(JsValue::undefined)
+        .to_string(context)?
+        .to_std_string_escaped())
+}
@@ -0,0 +1,499 @@
+// Module loader for the `node` tool: CommonJS `require`, ES module
+// `import`/`export`, JSON modules, TypeScript and packages under
+// `node_modules`. Evaluated after `builtins.js`.
+//
+// Every module is evaluated as a function wrapping its (transformed) source.
+// TypeScript files are first stripped of types by `__node_strip_types` (see
+// `ts.rs`). ES modules are lowered by `__node_transform` (see `esm.rs`); their
+// imports are loaded before the body runs and passed in as `__esm_imports`.
+// A module whose body uses top-level `await` compiles to an async function,
+// and modules importing it wait for it before running.
+(function (global) {
+  'use strict';
+
+  function define(target, name, value) {
+    Object.defineProperty(target, name, {
+      value: value,
+      writable: true,
+      enumerable: false,
+      configurable: true,
+    });
+  }
+
+  const builtins = global.__node_builtins;
+  const path = builtins.path;
+  const hasOwn = (obj, key) => Object.prototype.hasOwnProperty.call(obj, key);
+
+  const WRAPPER_PARAMS =
+    'exports, require, module, __filename, __dirname, __esm_imports, __esm_export, __esm_import, __esm_meta';
+  const EXTENSIONS = ['.js', '.mjs', '.cjs', '.json', '.ts', '.mts', '.cts'];
+  const TYPESCRIPT_EXTENSIONS = ['.ts', '.mts', '.cts'];
+  // TypeScript sources imported by their output name (`./util.js` -> `./util.ts`)
+  const TYPESCRIPT_SOURCES = { '.js': '.ts', '.mjs': '.mts', '.cjs': '.cts' };
+
+  // --- Filesystem helpers ---
+
+  function fsOp(op, params) {
+    return JSON.parse(__node_fs(op, JSON.stringify(params)));
+  }
+
+  function fileType(p) {
+    const reply = fsOp('stat', { path: p, follow: true });
+    return reply.error ? null : reply.ok.type;
+  }
+
+  function readText(p) {
+    const reply = fsOp('readText', { path: p });
+    if (reply.error) {
+      const err = new Error(reply.error.code + ': ' + reply.error.message + ", open '" + p + "'");
+      err.code = reply.error.code;
+      throw err;
+    }
+    return reply.ok;
+  }
+
+  const packageCache = Object.create(null);
+
+  function readPackage(dir) {
+    if (hasOwn(packageCache, dir)) return packageCache[dir];
+    let pkg = null;
+    const file = path.join(dir, 'package.json');
+    if (fileType(file) === 'file') {
+      try {
+        pkg = JSON.parse(readText(file));
+      } catch (e) {
+        throw new Error('Invalid package config ' + file + ': ' + e.message);
+      }
+    }
+    packageCache[dir] = pkg;
+    return pkg;
+  }
+
+  function packageType(dir) {
+    for (;;) {
+      const pkg = readPackage(dir);
+      if (pkg) return pkg.type;
+      if (dir === '/') return undefined;
+      dir = path.dirname(dir);
+    }
+  }
+
+  // --- Resolution ---
+
+  function notFound(request, fromDir) {
+    const err = new Error("Cannot find module '" + request + "' from '" + fromDir + "'");
+    err.code = 'MODULE_NOT_FOUND';
+    return err;
+  }
+
+  function tryFile(p) {
+    return fileType(p) === 'file' ? p : null;
+  }
+
+  function tryExtensions(p) {
+    if (tryFile(p)) return p;
+    for (const ext of EXTENSIONS) {
+      if (tryFile(p + ext)) return p + ext;
+    }
+    const ext = path.extname(p);
+    if (hasOwn(TYPESCRIPT_SOURCES, ext)) {
+      const source = p.slice(0, -ext.length) + TYPESCRIPT_SOURCES[ext];
+      if (tryFile(source)) return source;
+    }
+    return null;
+  }
+
+  function tryIndex(dir) {
+    for (const ext of EXTENSIONS) {
+      if (tryFile(dir + '/index' + ext)) return dir + '/index' + ext;
+    }
+    return null;
+  }
+
+  function resolvePath(p) {
+    const file = tryExtensions(p);
+    if (file) return file;
+    if (fileType(p) !== 'dir') return null;
+    const pkg = readPackage(p);
+    if (pkg && typeof pkg.main === 'string') {
+      const main = path.resolve(p, pkg.main);
+      const resolved = tryExtensions(main) || tryIndex(main);
+      if (resolved) return resolved;
+    }
+    return tryIndex(p);
+  }
+
+  // Pick a target from a package.json `exports` value for the given conditions
+  function pickTarget(target, conditions) {
+    if (typeof target === 'string') return target;
+    if (Array.isArray(target)) {
+      for (const t of target) {
+        const picked = pickTarget(t, conditions);
+        if (picked) return picked;
+      }
+      return null;
+    }
+    if (target && typeof target === 'object') {
+      for (const key of Object.keys(target)) {
+        if (key === 'default' || conditions.includes(key)) {
+          const picked = pickTarget(target[key], conditions);
+          if (picked) return picked;
+        }
+      }
+    }
+    return null;
+  }
+
+  function substitute(target, match) {
+    if (typeof target === 'string') return target.split('*').join(match);
+    if (Array.isArray(target)) return target.map((t) => substitute(t, match));
+    if (target && typeof target === 'object') {
+      const out = {};
+      for (const key of Object.keys(target)) out[key] = substitute(target[key], match);
+      return out;
+    }
+    return target;
+  }
+
+  function resolveExports(pkgDir, exportsField, subpath, conditions) {
+    const isSubpathMap =
+      exportsField && typeof exportsField === 'object' && !Array.isArray(exportsField) &&
+      Object.keys(exportsField).some((k) => k.startsWith('.'));
+    let target = null;
+    if (!isSubpathMap) {
+      if (subpath === '.') target = exportsField;
+    } else if (hasOwn(exportsField, subpath)) {
+      target = exportsField[subpath];
+    } else {
+      for (const key of Object.keys(exportsField)) {
+        const star = key.indexOf('*');
+        if (star < 0) continue;
+        const prefix = key.slice(0, star);
+        const suffix = key.slice(star + 1);
+        if (subpath.startsWith(prefix) && subpath.endsWith(suffix) && subpath.length >= key.length - 1) {
+          const match = subpath.slice(prefix.length, subpath.length - suffix.length);
+          target = substitute(exportsField[key], match);
+          break;
+        }
+      }
+    }
+    const picked = pickTarget(target, conditions);
+    return picked ? path.resolve(pkgDir, picked) : null;
+  }
+
+  function resolveModule(request, fromDir, isImport) {
+    if (request.startsWith('node:') || hasOwn(builtins, request)) {
+      const name = request.startsWith('node:') ? request.slice(5) : request;
+      if (hasOwn(builtins, name)) return 'node:' + name;
+      throw notFound(request, fromDir);
+    }
+    if (request.startsWith('file://')) request = decodeURIComponent(request.slice(7));
+
+    if (request === '.' || request === '..' || /^\.{0,2}\//.test(request)) {
+      const resolved = resolvePath(path.resolve(fromDir, request));
+      if (resolved) return resolved;
+      throw notFound(request, fromDir);
+    }
+
+    // Bare specifier: look for the package in node_modules up the tree
+    const parts = request.split('/');
+    const name = request.startsWith('@') ? parts.slice(0, 2).join('/') : parts[0];
+    const subpath = '.' + request.slice(name.length);
+    const conditions = isImport ? ['import', 'node'] : ['require', 'node'];
+    let dir = fromDir;
+    for (;;) {
+      const pkgDir = path.join(dir, 'node_modules', name);
+      if (path.basename(dir) !== 'node_modules' && fileType(pkgDir) === 'dir') {
+        const pkg = readPackage(pkgDir);
+        if (pkg && pkg.exports != null) {
+          const resolved = resolveExports(pkgDir, pkg.exports, subpath, conditions);
+          if (resolved && tryFile(resolved)) return resolved;
+          const err = new Error("Package subpath '" + subpath + "' is not defined by \"exports\" in " + pkgDir);
+          err.code = 'ERR_PACKAGE_PATH_NOT_EXPORTED';
+          throw err;
+        }
+        const resolved = resolvePath(path.resolve(pkgDir, subpath));
+        if (resolved) return resolved;
+      }
+      if (dir === '/') break;
+      dir = path.dirname(dir);
+    }
+    throw notFound(request, fromDir);
+  }
+
+  // --- Modules ---
+
+  const cache = Object.create(null);
+  let mainModule;
+
+  class Module {
+    constructor(filename, parent) {
+      this.id = filename;
+      this.filename = filename;
+      this.path = path.dirname(filename);
+      this.exports = {};
+      this.parent = parent || null;
+      this.children = [];
+      this.loaded = false;
+      this.esm = false;
+      // Promise while an async (top-level await) module is still running
+      this.pending = null;
+      this.linking = false;
+      this.require = makeRequire(this);
+      if (parent) parent.children.push(this);
+    }
+  }
+
+  function builtinModule(id) {
+    if (!hasOwn(cache, id)) {
+      cache[id] = { id: id, filename: id, exports: builtins[id.slice(5)], loaded: true, esm: false, pending: null };
+    }
+    return cache[id];
+  }
+
+  function loadModule(filename, parent) {
+    if (filename.startsWith('node:')) return builtinModule(filename);
+    if (hasOwn(cache, filename)) return cache[filename];
+    const mod = new Module(filename, parent);
+    cache[filename] = mod;
+    try {
+      evaluate(mod, readText(filename));
+    } catch (e) {
+      delete cache[filename];
+      throw e;
+    }
+    return mod;
+  }
+
+  // The namespace object an ES module sees when importing `mod`
+  function namespaceOf(mod) {
+    if (mod.esm) return mod.exports;
+    if (mod.namespace && mod.namespaceFor === mod.exports) return mod.namespace;
+    const exports = mod.exports;
+    const ns = Object.create(null);
+    if (exports !== null && (typeof exports === 'object' || typeof exports === 'function')) {
+      for (const key of Object.keys(exports)) {
+        if (key !== 'default') {
+          Object.defineProperty(ns, key, { enumerable: true, get: () => mod.exports[key] });
+        }
+      }
+    }
+    Object.defineProperty(ns, 'default', { enumerable: true, get: () => mod.exports });
+    mod.namespace = ns;
+    mod.namespaceFor = exports;
+    return ns;
+  }
+
+  // Compile a module body into its wrapper function. Indirect eval keeps the
+  // wrapper in global scope, so module code cannot see the loader's locals.
+  // Syntax errors are annotated with their line of `source` (see `annotate`).
+  function compile(code, strict, filename, source) {
+    const head = 'function (' + WRAPPER_PARAMS + ') {' + (strict ? '"use strict";' : '');
+    // The wrapper stays on the first line so reported line numbers still match
+    let wrapped = '(' + head + code + '\n})';
+    try {
+      try {
+        return { fn: (0, eval)(wrapped), async: false };
+      } catch (e) {
+        if (!(e && e.name === 'SyntaxError' && /\bawait\b/.test(code))) throw e;
+        wrapped = '(async ' + head + code + '\n})';
+        return { fn: (0, eval)(wrapped), async: true };
+      }
+    } catch (e) {
+      annotate(e, filename, source, wrapped);
+      throw e;
+    }
+  }
+
+  // Evaluate `source` as the body of `mod`; `format` forces 'esm' or 'cjs'
+  // and `typescript` forces type stripping (else both follow the extension)
+  function evaluate(mod, source, format, typescript) {
+    const filename = mod.filename;
+    const ext = path.extname(filename);
+    if (ext === '.json' && !format) {
+      try {
+        mod.exports = JSON.parse(source);
+      } catch (e) {
+        throw new SyntaxError(filename + ': ' + e.message);
+      }
+      mod.loaded = true;
+      return;
+    }
+
+    if (typescript === undefined) typescript = TYPESCRIPT_EXTENSIONS.includes(ext);
+    const code = typescript ? __node_strip_types(source) : source;
+    const lowered = JSON.parse(__node_transform(code));
+    if (format) mod.esm = format === 'esm';
+    else if (ext === '.mjs' || ext === '.mts') mod.esm = true;
+    else if (ext === '.cjs' || ext === '.cts') mod.esm = false;
+    else mod.esm = lowered.esm || packageType(mod.path) === 'module';
+
+    const compiled = compile(lowered.code, mod.esm, filename, source);
+    const dynamicImport = (spec) => importModule(String(spec), mod.path);
+
+    if (!mod.esm) {
+      const result = compiled.fn.call(
+        mod.exports, mod.exports, mod.require, mod, filename, mod.path, [], esmExporter(mod, []), dynamicImport,
+        undefined,
+      );
+      finish(mod, compiled.async ? result : null);
+      return;
+    }
+
+    mod.exports = Object.create(null);
+    Object.defineProperty(mod.exports, Symbol.toStringTag, { value: 'Module' });
+    const meta = {
+      url: 'file://' + filename,
+      filename: filename,
+      dirname: mod.path,
+      resolve: (spec) => {
+        const resolved = resolveModule(String(spec), mod.path, true);
+        return resolved.startsWith('node:') ? resolved : 'file://' + resolved;
+      },
+    };
+
+    mod.linking = true;
+    let deps;
+    try {
+      deps = lowered.imports.map((spec) => loadModule(resolveModule(spec, mod.path, true), mod));
+    } finally {
+      mod.linking = false;
+    }
+
+    const run = () => {
+      const namespaces = deps.map(namespaceOf);
+      return compiled.fn.call(
+        undefined, mod.exports, mod.require, mod, filename, mod.path, namespaces,
+        esmExporter(mod, namespaces), dynamicImport, meta,
+      );
+    };
+    // Wait for imported async modules, except ones still linking (import cycles)
+    const waits = deps.filter((d) => d.pending && !d.linking).map((d) => d.pending);
+    if (waits.length > 0) {
+      finish(mod, Promise.all(waits).then(run));
+    } else {
+      const result = run();
+      finish(mod, compiled.async ? result : null);
+    }
+  }
+
+  function finish(mod, pending) {
+    if (!pending) {
+      mod.loaded = true;
+      return;
+    }
+    mod.pending = Promise.resolve(pending).then(() => {
+      mod.pending = null;
+      mod.loaded = true;
+    });
+  }
+
+  function esmExporter(mod, namespaces) {
+    return function (getters, stars) {
+      for (const name of Object.keys(getters)) {
+        Object.defineProperty(mod.exports, name, { enumerable: true, configurable: true, get: getters[name] });
+      }
+      for (const index of stars) {
+        const ns = namespaces[index];
+        for (const key of Object.keys(ns)) {
+          if (key !== 'default' && !hasOwn(mod.exports, key)) {
+            Object.defineProperty(mod.exports, key, { enumerable: true, get: () => ns[key] });
+          }
+        }
+      }
+    };
+  }
+
+  function importModule(spec, fromDir) {
+    return new Promise((resolve) => {
+      const mod = loadModule(resolveModule(spec, fromDir, true), null);
+      resolve(mod.pending ? mod.pending.then(() => namespaceOf(mod)) : namespaceOf(mod));
+    });
+  }
+
+  function makeRequire(mod) {
+    function require(id) {
+      if (typeof id !== 'string' || id === '') {
+        throw new TypeError('The "id" argument must be a non-empty string');
+      }
+      const dep = loadModule(resolveModule(id, mod.path, false), mod);
+      if (dep.esm && dep.pending) {
+        const err = new Error("require() cannot be used on an ES module with top-level await: '" + id + "'");
+        err.code = 'ERR_REQUIRE_ASYNC_MODULE';
+        throw err;
+      }
+      return dep.exports;
+    }
+    require.resolve = (id) => resolveModule(String(id), mod.path, false);
+    require.cache = cache;
+    Object.defineProperty(require, 'main', { get: () => mainModule, enumerable: true });
+    return require;
+  }
+
+  // --- Error locations ---
+  //
+  // Boa's syntax errors end in "at line N, col M". Type stripping and the
+  // module transform keep lines in place, so that line of the original source
+  // (TypeScript included) is shown above the error, as Node does.
+
+  const bannerKey = Symbol('banner');
+
+  // `code` is the text the engine parsed, when its first line carries a
+  // wrapper prefix that shifts first-line columns
+  function annotate(err, filename, source, code) {
+    if (!err || typeof err !== 'object' || err.name !== 'SyntaxError' || hasOwn(err, bannerKey)) return;
+    const match = /at line (\d+), col(?:umn)? (\d+)/.exec(String(err.message));
+    if (!match) return;
+    const lines = String(source).split('\n');
+    const line = Number(match[1]);
+    if (line < 1 || line > lines.length) return;
+    const text = lines[line - 1].replace(/\r$/, '');
+    let column = Number(match[2]) - 1;
+    if (line === 1 && code !== undefined) column -= code.split('\n')[0].length - lines[0].length;
+    let banner = filename + ':' + line + '\n' + text;
+    if (column >= 0 && column <= text.length) banner += '\n' + ' '.repeat(column) + '^';
+    Object.defineProperty(err, bannerKey, { value: banner });
+  }
+
+  // --- Entry points (called from mod.rs) ---
+
+  // With `mode` 'report' (`Sandbox::eval_js`), the module's default export
+  // is the result
+  function runEntry(mod, source, format, typescript, mode) {
+    mainModule = mod;
+    cache[mod.filename] = mod;
+    evaluate(mod, source, format, typescript);
+    if (mode === 'report') {
+      __node_main(Promise.resolve(mod.pending).then(() => mod.exports.default), mode);
+    } else if (mod.pending) {
+      __node_main(mod.pending);
+    }
+  }
+
+  define(global, '__node_run_main', function (filename) {
+    runEntry(new Module(filename, null), readText(filename));
+  });
+
+  // `-e` code using module syntax runs as an ES module rooted at the cwd
+  define(global, '__node_run_source', function (code, name, typescript, mode) {
+    runEntry(new Module(path.join(process.cwd(), name), null), code, 'esm', typescript, mode);
+  });
+
+  define(global, '__node_annotate', function (err, filename, source) {
+    annotate(err, filename, source);
+  });
+
+  // Location banner to print above an uncaught error, if it has one
+  define(global, '__node_error_banner', function (err) {
+    return err && typeof err === 'object' && hasOwn(err, bannerKey) ? err[bannerKey] : undefined;
+  });
+
+  // Globals for `-e`/`-p` scripts, which resolve relative to the cwd
+  const evalModule = new Module(path.join(process.cwd(), '[eval]'), null);
+  define(global, 'require', evalModule.require);
+  define(global, 'module', evalModule);
+  define(global, 'exports', evalModule.exports);
+  define(global, '__filename', '[eval]');
+  define(global, '__dirname', '.');
+  define(global, '__esm_import', (spec) => importModule(String(spec), process.cwd()));
+})(globalThis);
@@ -0,0 +1,376 @@
+// Runtime prelude for the `node` tool, evaluated once before user code.
+//
+// Host natives are exposed as `__node_*` globals by `mod.rs`; everything
+// user-facing (timers, fetch, ...) is built on top of them here. The Rust
+// side drives the event loop by calling `__node_tick()` whenever Boa's job
+// queue is empty.
+(function (global) {
+  'use strict';
+
+  function define(target, name, value) {
+    Object.defineProperty(target, name, {
+      value: value,
+      writable: true,
+      enumerable: false,
+      configurable: true,
+    });
+  }
+
+  // --- Event loop state ---
+
+  let pendingError = null;
+  let hasPendingError = false;
+
+  // Set by `process.exit()`, which unwinds the script by throwing `exitSignal`
+  let exitCode = null;
+  const exitSignal = { toString: () => 'process.exit()' };
+
+  function reportUncaught(err) {
+    if (!hasPendingError && exitCode === null) {
+      pendingError = err;
+      hasPendingError = true;
+    }
+  }
+
+  // --- Timers ---
+  //
+  // Timers run on a virtual clock: once the microtask queue drains, the loop
+  // jumps straight to the next due timer instead of sleeping (the sandbox has
+  // no real sleep, same as the `sleep` command).
+
+  let clock = 0;
+  let nextTimerId = 1;
+  let sequence = 0;
+  const timers = new Map();
+
+  class Timeout {
+    constructor(id) {
+      this._id = id;
+    }
+    ref() {
+      const t = timers.get(this._id);
+      if (t) t.ref = true;
+      return this;
+    }
+    unref() {
+      const t = timers.get(this._id);
+      if (t) t.ref = false;
+      return this;
+    }
+    hasRef() {
+      const t = timers.get(this._id);
+      return t ? t.ref : false;
+    }
+    refresh() {
+      const t = timers.get(this._id);
+      if (t) {
+        t.due = clock + t.delay;
+        t.seq = sequence++;
+      }
+      return this;
+    }
+    [Symbol.toPrimitive]() {
+      return this._id;
+    }
+  }
+
+  function addTimer(callback, delay, args, repeat, clamp) {
+    if (typeof callback !== 'function') {
+      throw new TypeError('The "callback" argument must be of type function');
+    }
+    delay = Number(delay);
+    if (clamp && !(delay >= 1 && delay <= 2147483647)) delay = 1;
+    if (!clamp) delay = 0;
+    const id = nextTimerId++;
+    timers.set(id, {
+      id: id,
+      callback: callback,
+      args: args,
+      delay: delay,
+      due: clock + delay,
+      repeat: repeat,
+      ref: true,
+      seq: sequence++,
+    });
+    return new Timeout(id);
+  }
+
+  function clearTimer(timer) {
+    if (timer == null) return;
+    timers.delete(typeof timer === 'object' ? timer._id : Number(timer));
+  }
+
+  function runNextTimer() {
+    let next = null;
+    let keepAlive = false;
+    for (const t of timers.values()) {
+      if (t.ref) keepAlive = true;
+      if (!next || t.due < next.due || (t.due === next.due && t.seq < next.seq)) {
+        next = t;
+      }
+    }
+    // Unref'd timers alone don't keep the process alive
+    if (!next || !keepAlive) return false;
+
+    if (next.due > clock) clock = next.due;
+    if (next.repeat) {
+      next.due = clock + next.delay;
+      next.seq = sequence++;
+    } else {
+      timers.delete(next.id);
+    }
+    next.callback.apply(undefined, next.args);
+    return true;
+  }
+
+  define(global, 'setTimeout', function setTimeout(callback, delay, ...args) {
+    return addTimer(callback, delay, args, false, true);
+  });
+  define(global, 'setInterval', function setInterval(callback, delay, ...args) {
+    return addTimer(callback, delay, args, true, true);
+  });
+  define(global, 'setImmediate', function setImmediate(callback, ...args) {
+    return addTimer(callback, 0, args, false, false);
+  });
+  define(global, 'clearTimeout', clearTimer);
+  define(global, 'clearInterval', clearTimer);
+  define(global, 'clearImmediate', clearTimer);
+
+  define(global, 'queueMicrotask', function queueMicrotask(callback) {
+    if (typeof callback !== 'function') {
+      throw new TypeError('The "callback" argument must be of type function');
+    }
+    Promise.resolve().then(function () {
+      try {
+        callback();
+      } catch (err) {
+        reportUncaught(err);
+      }
+    });
+  });
+
+  // --- fetch ---
+
+  class Headers {
+    constructor(init) {
+      this._map = new Map();
+      if (init == null) return;
+      if (init instanceof Headers) {
+        init.forEach((value, name) => this.append(name, value));
+      } else if (Array.isArray(init)) {
+        for (const [name, value] of init) this.append(name, value);
+      } else {
+        for (const name of Object.keys(init)) this.append(name, init[name]);
+      }
+    }
+    append(name, value) {
+      const key = String(name).toLowerCase();
+      const current = this._map.get(key);
+      this._map.set(key, current === undefined ? String(value) : current + ', ' + value);
+    }
+    set(name, value) {
+      this._map.set(String(name).toLowerCase(), String(value));
+    }
+    get(name) {
+      const value = this._map.get(String(name).toLowerCase());
+      return value === undefined ? null : value;
+    }
+    has(name) {
+      return this._map.has(String(name).toLowerCase());
+    }
+    delete(name) {
+      this._map.delete(String(name).toLowerCase());
+    }
+    forEach(callback, thisArg) {
+      for (const [name, value] of this.entries()) callback.call(thisArg, value, name, this);
+    }
+    entries() {
+      const sorted = Array.from(this._map.entries());
+      sorted.sort((a, b) => (a[0] < b[0] ? -1 : a[0] > b[0] ? 1 : 0));
+      return sorted[Symbol.iterator]();
+    }
+    keys() {
+      return Array.from(this.entries(), (e) => e[0])[Symbol.iterator]();
+    }
+    values() {
+      return Array.from(this.entries(), (e) => e[1])[Symbol.iterator]();
+    }
+    [Symbol.iterator]() {
+      return this.entries();
+    }
+  }
+
+  class Response {
+    constructor(body, init) {
+      init = init || {};
+      this._body = body == null ? '' : String(body);
+      this.status = init.status === undefined ? 200 : init.status;
+      this.statusText = init.statusText || '';
+      this.headers = new Headers(init.headers);
+      this.ok = this.status >= 200 && this.status < 300;
+      this.url = init.url || '';
+      this.redirected = false;
+      this.type = 'basic';
+      this.bodyUsed = false;
+    }
+    _consume() {
+      if (this.bodyUsed) {
+        return Promise.reject(new TypeError('Body is unusable: Body has already been read'));
+      }
+      this.bodyUsed = true;
+      return Promise.resolve(this._body);
+    }
+    text() {
+      return this._consume();
+    }
+    json() {
+      return this._consume().then((text) => JSON.parse(text));
+    }
+    clone() {
+      if (this.bodyUsed) throw new TypeError('Response.clone: Body has already been consumed.');
+      return new Response(this._body, {
+        status: this.status,
+        statusText: this.statusText,
+        headers: this.headers,
+        url: this.url,
+      });
+    }
+  }
+
+  function fetch(input, init) {
+    init = init || {};
+    const url = typeof input === 'object' && input !== null && 'url' in input ? input.url : String(input);
+    const method = String(init.method || (input && input.method) || 'GET').toUpperCase();
+    const headers = {};
+    new Headers(init.headers || (input && input.headers)).forEach((value, name) => {
+      headers[name] = value;
+    });
+    const body = init.body == null ? null : String(init.body);
+
+    // The request runs as a job so callers can chain handlers before it
+    // completes, and so Promise.all over several fetches behaves as usual.
+    return Promise.resolve().then(function () {
+      const raw = JSON.parse(
+        __node_fetch(JSON.stringify({ url: url, method: method, headers: headers, body: body })),
+      );
+      return new Response(raw.body, {
+        status: raw.status,
+        headers: raw.headers,
+        url: url,
+      });
+    });
+  }
+
+  define(global, 'Headers', Headers);
+  define(global, 'Response', Response);
+  define(global, 'fetch', fetch);
+
+  // --- Host functions ---
+  //
+  // `host.call(name, args)` invokes a function the embedder registered with
+  // `Sandbox::register_host_function`. Like fetch, the call runs as a job.
+
+  const host = {
+    call(name, args) {
+      let request;
+      try {
+        request = JSON.stringify(args === undefined ? null : args);
+      } catch (err) {
+        return Promise.reject(err);
+      }
+      name = String(name);
+      return Promise.resolve().then(function () {
+        const reply = JSON.parse(__node_host_call(name, request === undefined ? 'null' : request));
+        if ('error' in reply) throw new Error(reply.error);
+        return reply.ok;
+      });
+    },
+  };
+
+  define(global, 'host', Object.freeze(host));
+
+  // --- Hooks for the Rust event loop ---
+
+  // Value of `-e` code run for `Sandbox::eval_js`, see `__node_eval_outcome`
+  let evalResult;
+
+  // Track the value (or promise) of a script run; a rejection is reported
+  // like an uncaught exception. Mode 'print' prints the resolved value and
+  // 'report' keeps it for `__node_eval_outcome`.
+  define(global, '__node_main', function (value, mode) {
+    Promise.resolve(value).then(
+      function (result) {
+        if (mode === 'print') console.log(String(result));
+        if (mode === 'report') evalResult = result;
+      },
+      reportUncaught,
+    );
+  });
+
+  // Boa leaves `err.stack` unset, so the stack is the engine's `trace` of
+  // the error, as printed for an uncaught one, when there is one
+  function describeError(err, trace) {
+    if (err === null || typeof err !== 'object') {
+      return { name: 'Error', message: String(err), stack: null };
+    }
+    const name = err.name === undefined ? 'Error' : String(err.name);
+    const message = err.message === undefined ? '' : String(err.message);
+    if (typeof err.stack === 'string') return { name: name, message: message, stack: err.stack };
+    let stack = trace === undefined ? null : String(trace);
+    const banner = global.__node_error_banner(err);
+    if (banner !== undefined) stack = banner + '\n\n' + (stack === null ? name + ': ' + message : stack);
+    return { name: name, message: message, stack: stack };
+  }
+
+  // JSON outcome of a `Sandbox::eval_js` run: `{"ok": value}` with the
+  // script's value (undefined becomes null), or `{"error": {name, message,
+  // stack}}` for an uncaught exception, whose `trace` the engine gives, or a
+  // non-zero `process.exit()`.
+  define(global, '__node_eval_outcome', function (failed, error, trace) {
+    if (exitCode === 0) {
+      failed = false;
+    } else if (exitCode !== null) {
+      failed = true;
+      error = new Error('process.exit(' + exitCode + ')');
+      trace = undefined;
+    }
+    if (!failed) {
+      try {
+        const json = JSON.stringify(evalResult);
+        return '{"ok":' + (json === undefined ? 'null' : json) + '}';
+      } catch (err) {
+        error = err;
+        trace = undefined;
+      }
+    }
+    return JSON.stringify({ error: describeError(error, trace) });
+  });
+
+  define(global, '__node_exit', function (code) {
+    exitCode = code === undefined || code === null ? 0 : Number(code) | 0;
+    throw exitSignal;
+  });
+
+  // Exit code for the finished run: the `process.exit()` code if it was
+  // called, else `process.exitCode`, or undefined if `failed` (the Rust side
+  // then reports the error and exits 1).
+  define(global, '__node_exit_status', function (failed) {
+    if (exitCode !== null) return exitCode;
+    if (failed) return undefined;
+    const code = global.process && global.process.exitCode;
+    return code === undefined || code === null ? 0 : Number(code) | 0;
+  });
+
+  // Rethrow any pending uncaught error, otherwise run the next timer.
+  // Returns false once there is nothing left to do.
+  define(global, '__node_tick', function () {
+    if (exitCode !== null) return false;
+    if (hasPendingError) {
+      const err = pendingError;
+      pendingError = null;
+      hasPendingError = false;
+      throw err;
+    }
+    return runNextTimer();
+  });
+})(globalThis);
@@ -0,0 +1,1167 @@
+//! TypeScript type stripping for the JS module loader.
+//!
+//! TypeScript-only syntax is blanked out with spaces: type annotations,
+//! `interface`s, `type` aliases, `declare`d items, generic parameters and
+//! arguments, `as`/`satisfies`, non-null `!`, accessibility modifiers,
+//! overload signatures and type-only imports/exports. Every remaining token
+//! keeps its line and column, so positions reported for the stripped code
+//! point straight at the `.ts` source.
+//!
+//! The few constructs with runtime meaning are lowered in place, on their own
+//! lines: `enum`s become objects built by an IIFE, constructor parameter
+//! properties become `this.x = x` assignments, `import x = require(...)`
+//! becomes a `const` and `export =` assigns `module.exports`.
+//!
+//! Like Node's type stripping this is syntax-directed and does no type
+//! checking; namespaces, decorators and JSX are not supported.
+
+use std::collections::{HashMap, HashSet};
+
+use super::esm::{Kind, Token, quote, tokenize};
+
+/// Words that cannot end an expression, so a following `<`, `!` or `as` is not
+/// a type argument list, non-null assertion or type assertion.
+const KEYWORDS: &[&str] = &[
+    "return",
+    "typeof",
+    "instanceof",
+    "in",
+    "of",
+    "new",
+    "delete",
+    "void",
+    "throw",
+    "case",
+    "do",
+    "else",
+    "yield",
+    "await",
+    "if",
+    "while",
+    "for",
+    "switch",
+    "catch",
+    "with",
+    "extends",
+    "export",
+    "import",
+    "default",
+    "let",
+    "const",
+    "var",
+    "function",
+    "class",
+    "as",
+    "satisfies",
+];
+
+/// Prefixes of a type operand (`keyof T`, `readonly T[]`, `new () => T`, ...).
+const TYPE_PREFIXES: &[&str] = &[
+    "keyof", "typeof", "readonly", "unique", "infer", "asserts", "new", "abstract",
+];
+
+/// Class member modifiers; the TypeScript-only ones are erased.
+const MEMBER_MODIFIERS: &[&str] = &[
+    "public",
+    "private",
+    "protected",
+    "readonly",
+    "override",
+    "declare",
+    "abstract",
+    "static",
+    "async",
+    "get",
+    "set",
+    "accessor",
+];
+
+/// Constructor parameter modifiers that declare a parameter property.
+const PARAM_MODIFIERS: &[&str] = &["public", "private", "protected", "readonly", "override"];
+
+/// What follows `declare` in an ambient declaration.
+const DECLARE_KINDS: &[&str] = &[
+    "const",
+    "let",
+    "var",
+    "function",
+    "async",
+    "class",
+    "abstract",
+    "enum",
+    "module",
+    "namespace",
+    "global",
+    "interface",
+    "type",
+];
+
+/// Tokens allowed at the top level of `<...>` when deciding whether a `<` in
+/// an expression opens type arguments rather than a comparison.
+const TYPE_ARGUMENT_PUNCT: &[&str] = &[
+    ",", ".", "|", "&", "?", ":", "[", "]", "(", ")", "{", "}", "<", ">", "=>", "...", "-",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Erase {
+    Keep,
+    Blank,
+    /// Blank, but leave a `;` so the statement before cannot run into the next.
+    Statement,
+}
+
+pub fn strip(source: &str) -> String {
+    let tokens = tokenize(source);
+    let mut stripper = Stripper::new(source, &tokens);
+    stripper.run();
+    stripper.output()
+}
+
+struct Stripper<'a> {
+    src: &'a str,
+    tokens: &'a [Token],
+    /// Innermost open bracket enclosing each token.
+    parent: Vec<Option<usize>>,
+    /// Matching close bracket of each open bracket.
+    close: Vec<Option<usize>>,
+    erase: Vec<Erase>,
+    /// Tokens replaced by an edit; the scan skips them.
+    replaced: Vec<bool>,
+    edits: Vec<(usize, usize, String)>,
+    /// Class bodies (by open brace) and whether the class `extends` another.
+    classes: HashMap<usize, bool>,
+    /// Parameter lists already stripped.
+    params_done: HashSet<usize>,
+    /// `{...}` lists of import/export specifiers, where `as` renames.
+    specifiers: HashSet<usize>,
+}
+
+impl<'a> Stripper<'a> {
+    fn new(src: &'a str, tokens: &'a [Token]) -> Self {
+        let mut parent = vec![None; tokens.len()];
+        let mut close = vec![None; tokens.len()];
+        let mut stack: Vec<usize> = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            let text = &src[token.start..token.end];
+            match token.kind {
+                Kind::Punct if matches!(text, "(" | "[" | "{") => {
+                    parent[i] = stack.last().copied();
+                    stack.push(i);
+                }
+                Kind::Punct if matches!(text, ")" | "]" | "}") => {
+                    if let Some(open) = stack.pop() {
+                        close[open] = Some(i);
+                    }
+                    parent[i] = stack.last().copied();
+                }
+                // Template text between substitutions: `}` resumes, `${` opens
+                Kind::Template => {
+                    if text.starts_with('}') {
+                        stack.pop();
+                    }
+                    parent[i] = stack.last().copied();
+                    if text.len() > 1 && text.ends_with("${") {
+                        stack.push(i);
+                    }
+                }
+                _ => parent[i] = stack.last().copied(),
+            }
+        }
+        Self {
+            src,
+            tokens,
+            parent,
+            close,
+            erase: vec![Erase::Keep; tokens.len()],
+            replaced: vec![false; tokens.len()],
+            edits: Vec::new(),
+            classes: HashMap::new(),
+            params_done: HashSet::new(),
+            specifiers: HashSet::new(),
+        }
+    }
+
+    fn text(&self, i: usize) -> &'a str {
+        self.tokens
+            .get(i)
+            .map(|t| &self.src[t.start..t.end])
+            .unwrap_or("")
+    }
+
+    fn is(&self, i: usize, text: &str) -> bool {
+        self.tokens
+            .get(i)
+            .is_some_and(|t| matches!(t.kind, Kind::Ident | Kind::Punct))
+            && self.text(i) == text
+    }
+
+    fn kind(&self, i: usize) -> Option<Kind> {
+        self.tokens.get(i).map(|t| t.kind)
+    }
+
+    fn same_line(&self, i: usize) -> bool {
+        self.tokens.get(i).is_some_and(|t| !t.newline_before)
+    }
+
+    /// Index just past the bracket pair opened at `open`.
+    fn after_close(&self, open: usize) -> usize {
+        self.close[open].map_or(self.tokens.len(), |c| c + 1)
+    }
+
+    /// The closest earlier token that survives stripping.
+    fn prev(&self, i: usize) -> Option<usize> {
+        (0..i).rev().find(|&j| self.erase[j] == Erase::Keep)
+    }
+
+    fn done(&self, i: usize) -> bool {
+        self.erase[i] != Erase::Keep || self.replaced[i]
+    }
+
+    fn is_expression_end(&self, i: usize) -> bool {
+        match self.kind(i) {
+            Some(Kind::Number | Kind::Str | Kind::Regex) => true,
+            Some(Kind::Template) => self.text(i).len() > 1 && self.text(i).ends_with('`'),
+            Some(Kind::Ident) => !KEYWORDS.contains(&self.text(i)),
+            Some(Kind::Punct) => matches!(self.text(i), ")" | "]"),
+            None => false,
+        }
+    }
+
+    fn erase(&mut self, from: usize, to: usize) {
+        for i in from..to.min(self.tokens.len()) {
+            if self.erase[i] == Erase::Keep {
+                self.erase[i] = Erase::Blank;
+            }
+        }
+    }
+
+    fn erase_statement(&mut self, from: usize, to: usize) {
+        self.erase(from, to);
+        if from < self.tokens.len() {
+            self.erase[from] = Erase::Statement;
+        }
+    }
+
+    /// Replace whole tokens `from..to` (exclusive).
+    fn replace(&mut self, from: usize, to: usize, text: String) {
+        let start = self.tokens[from].start;
+        let end = self.tokens[to - 1].end;
+        self.edits.push((start, end, text));
+        for i in from..to {
+            self.replaced[i] = true;
+        }
+    }
+
+    fn insert(&mut self, pos: usize, text: String) {
+        self.edits.push((pos, pos, text));
+    }
+
+    fn include_semicolon(&self, end: usize) -> usize {
+        if self.is(end, ";") { end + 1 } else { end }
+    }
+
+    fn run(&mut self) {
+        for i in 0..self.tokens.len() {
+            if !self.done(i) {
+                self.visit(i);
+            }
+        }
+    }
+
+    fn visit(&mut self, i: usize) {
+        if self.in_class_body(i) && self.member_start(i) {
+            self.class_member(i);
+            return;
+        }
+        let prev = self.prev(i);
+        if prev.is_some_and(|p| self.is(p, ".") || self.is(p, "?.")) {
+            return;
+        }
+
+        match (self.kind(i), self.text(i)) {
+            (Some(Kind::Ident), "interface" | "type" | "declare") if self.statement_start(i) => {
+                if let Some(end) = self.type_only_declaration(i) {
+                    self.erase_statement(i, end);
+                }
+            }
+            (Some(Kind::Ident), "abstract") if self.is(i + 1, "class") && self.same_line(i + 1) => {
+                self.erase(i, i + 1);
+            }
+            (Some(Kind::Ident), "enum")
+                if self.kind(i + 1) == Some(Kind::Ident) && self.is(i + 2, "{") =>
+            {
+                self.lower_enum(i);
+            }
+            (Some(Kind::Ident), "export") if self.statement_start(i) => self.export(i),
+            (Some(Kind::Ident), "import") if !self.is(i + 1, "(") && !self.is(i + 1, ".") => {
+                self.import(i)
+            }
+            (Some(Kind::Ident), "class") => self.class_heading(i),
+            (Some(Kind::Ident), "function") => self.function(i),
+            (Some(Kind::Ident), "let" | "const" | "var")
+                if matches!(self.kind(i + 1), Some(Kind::Ident))
+                    || self.is(i + 1, "{")
+                    || self.is(i + 1, "[") =>
+            {
+                self.declaration(i)
+            }
+            (Some(Kind::Ident), "as" | "satisfies") => {
+                let in_specifiers = self.parent[i].is_some_and(|p| self.specifiers.contains(&p));
+                let after_value =
+                    prev.is_some_and(|p| self.is_expression_end(p) || self.is(p, "}"));
+                if after_value && !in_specifiers {
+                    let end = self.skip_type(i + 1);
+                    self.erase(i, end);
+                }
+            }
+            (Some(Kind::Punct), "(") => self.paren(i),
+            (Some(Kind::Punct), "<") => self.angle(i, prev),
+            (Some(Kind::Punct), "!") => {
+                // Non-null assertion: `x!.y`, `f()!` (but not `x != y`)
+                let Some(p) = prev else { return };
+                let attached = self.tokens[p].end == self.tokens[i].start;
+                let not_equal =
+                    self.is(i + 1, "=") && self.tokens[i].end == self.tokens[i + 1].start;
+                if attached && !not_equal && self.is_expression_end(p) {
+                    self.erase(i, i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn statement_start(&self, i: usize) -> bool {
+        match self.prev(i) {
+            None => true,
+            Some(p) => self.tokens[i].newline_before || matches!(self.text(p), ";" | "{" | "}"),
+        }
+    }
+
+    // --- Types ---
+
+    /// Index of the first token after the type starting at `i`.
+    fn skip_type(&self, mut i: usize) -> usize {
+        if self.is(i, "|") || self.is(i, "&") {
+            i += 1;
+        }
+        loop {
+            i = self.skip_type_operand(i);
+            // Array types and indexed access: `T[]`, `T["key"]`
+            while self.is(i, "[") && self.same_line(i) {
+                i = self.after_close(i);
+            }
+            if (self.is(i, "is") && self.same_line(i)) || self.is(i, "|") || self.is(i, "&") {
+                i += 1;
+                continue;
+            }
+            // Conditional type: `A extends B ? C : D`
+            if self.is(i, "extends") && self.same_line(i) {
+                i = self.skip_type(i + 1);
+                if self.is(i, "?") {
+                    i = self.skip_type(i + 1);
+                    if self.is(i, ":") {
+                        i += 1;
+                        continue;
+                    }
+                }
+            }
+            return i;
+        }
+    }
+
+    fn skip_type_operand(&self, mut i: usize) -> usize {
+        while self.kind(i) == Some(Kind::Ident)
+            && TYPE_PREFIXES.contains(&self.text(i))
+            && self.starts_type(i + 1)
+        {
+            i += 1;
+        }
+        match self.kind(i) {
+            None => i,
+            Some(Kind::Punct) => match self.text(i) {
+                // Parenthesized or function type: `(A | B)`, `(x: A) => B`
+                "(" => self.function_type_end(i),
+                // Generic function type: `<T>(x: T) => T`
+                "<" => match self.angle_end(i, false) {
+                    Some(end) if self.is(end, "(") => self.function_type_end(end),
+                    _ => i,
+                },
+                "{" | "[" => self.after_close(i),
+                "-" if self.kind(i + 1) == Some(Kind::Number) => i + 2,
+                _ => i,
+            },
+            Some(Kind::Template) => {
+                let text = self.text(i);
+                if text.len() > 1 && text.ends_with('`') {
+                    return i + 1;
+                }
+                // Template literal type with substitutions: find its tail
+                (i + 1..self.tokens.len())
+                    .find(|&j| {
+                        self.kind(j) == Some(Kind::Template)
+                            && self.parent[j] == self.parent[i]
+                            && self.text(j).starts_with('}')
+                            && self.text(j).ends_with('`')
+                    })
+                    .map_or(self.tokens.len(), |j| j + 1)
+            }
+            Some(Kind::Ident) => {
+                let import = self.text(i) == "import";
+                i += 1;
+                loop {
+                    if self.is(i, ".") && self.kind(i + 1) == Some(Kind::Ident) {
+                        i += 2;
+                    } else if self.is(i, "<") {
+                        match self.angle_end(i, false) {
+                            Some(end) => i = end,
+                            None => return i,
+                        }
+                    } else if import && self.is(i, "(") {
+                        i = self.after_close(i);
+                    } else {
+                        return i;
+                    }
+                }
+            }
+            Some(_) => i + 1,
+        }
+    }
+
+    fn function_type_end(&self, open: usize) -> usize {
+        let end = self.after_close(open);
+        if self.is(end, "=>") {
+            self.skip_type(end + 1)
+        } else {
+            end
+        }
+    }
+
+    fn starts_type(&self, i: usize) -> bool {
+        match self.kind(i) {
+            Some(Kind::Ident | Kind::Str | Kind::Number | Kind::Template) => true,
+            Some(Kind::Punct) => matches!(self.text(i), "(" | "[" | "{" | "<" | "-"),
+            _ => false,
+        }
+    }
+
+    /// Index just past the `>` matching the `<` at `i`.
+    ///
+    /// With `strict`, give up on anything that cannot appear in type
+    /// arguments, so that comparisons like `a < b && c > d` are left alone.
+    fn angle_end(&self, i: usize, strict: bool) -> Option<usize> {
+        let base = self.tokens[i].depth;
+        let mut level = 0;
+        for j in i..self.tokens.len().min(i + 512) {
+            let token = self.tokens[j];
+            if token.depth < base {
+                return None;
+            }
+            if token.depth > base {
+                continue;
+            }
+            let text = self.text(j);
+            match token.kind {
+                Kind::Punct => match text {
+                    "<" => level += 1,
+                    ">" => {
+                        level -= 1;
+                        if level == 0 {
+                            return Some(j + 1);
+                        }
+                    }
+                    ";" => return None,
+                    // `&&` and `||` are two adjacent tokens
+                    "&" | "|"
+                        if strict
+                            && self.text(j + 1) == text
+                            && self.tokens[j + 1].start == token.end =>
+                    {
+                        return None;
+                    }
+                    _ if strict && !TYPE_ARGUMENT_PUNCT.contains(&text) => return None,
+                    _ => {}
+                },
+                Kind::Regex if strict => return None,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// `<` in an expression: type arguments (`f<T>(x)`, `new Map<K, V>()`),
+    /// a generic arrow function (`<T>(x: T) => x`) or a type assertion (`<T>x`).
+    fn angle(&mut self, i: usize, prev: Option<usize>) {
+        if prev.is_some_and(|p| self.is_expression_end(p)) {
+            let Some(end) = self.angle_end(i, true) else {
+                return;
+            };
+            let template =
+                self.kind(end) == Some(Kind::Template) && self.text(end).starts_with('`');
+            if self.is(end, "(") || template {
+                self.erase(i, end);
+            }
+        } else if let Some(end) = self.angle_end(i, false) {
+            // `<` cannot start an expression in plain JS
+            self.erase(i, end);
+        }
+    }
+
+    // --- Declarations ---
+
+    /// End of a declaration that has no runtime meaning, starting at keyword `k`.
+    fn type_only_declaration(&self, k: usize) -> Option<usize> {
+        let named = self.kind(k + 1) == Some(Kind::Ident) && self.same_line(k + 1);
+        match self.text(k) {
+            "interface"
+                if named
+                    && (self.is(k + 2, "{")
+                        || self.is(k + 2, "extends")
+                        || self.is(k + 2, "<")) =>
+            {
+                Some(self.braced_declaration_end(k))
+            }
+            "type" if named && (self.is(k + 2, "=") || self.is(k + 2, "<")) => {
+                let mut j = k + 2;
+                if self.is(j, "<") {
+                    j = self.angle_end(j, false)?;
+                }
+                if !self.is(j, "=") {
+                    return None;
+                }
+                Some(self.include_semicolon(self.skip_type(j + 1)))
+            }
+            "declare" if self.same_line(k + 1) && DECLARE_KINDS.contains(&self.text(k + 1)) => {
+                Some(self.ambient_declaration_end(k + 1))
+            }
+            _ => None,
+        }
+    }
+
+    /// End of a declaration with a `{...}` body, or of one ending in `;`
+    /// (`declare module "x";`).
+    fn braced_declaration_end(&self, k: usize) -> usize {
+        let depth = self.tokens[k].depth;
+        for j in k..self.tokens.len() {
+            if self.tokens[j].depth < depth {
+                return j;
+            }
+            if self.tokens[j].depth == depth {
+                if self.is(j, "{") {
+                    return self.after_close(j);
+                }
+                if self.is(j, ";") {
+                    return j + 1;
+                }
+            }
+        }
+        self.tokens.len()
+    }
+
+    /// End of the `declare`d item whose keyword is at `k`.
+    fn ambient_declaration_end(&self, k: usize) -> usize {
+        match self.text(k) {
+            "const" | "let" | "var" if !self.is(k + 1, "enum") => {
+                let mut j = k + 1;
+                loop {
+                    j = self.binding_end(j);
+                    if self.is(j, ":") {
+                        j = self.skip_type(j + 1);
+                    }
+                    if self.is(j, "=") {
+                        j = self.skip_expression(j + 1);
+                    }
+                    if !self.is(j, ",") {
+                        return self.include_semicolon(j);
+                    }
+                    j += 1;
+                }
+            }
+            "function" | "async" => {
+                let depth = self.tokens[k].depth;
+                let Some(open) = (k..self.tokens.len())
+                    .find(|&j| self.tokens[j].depth == depth && self.is(j, "("))
+                else {
+                    return self.tokens.len();
+                };
+                let mut end = self.after_close(open);
+                if self.is(end, ":") {
+                    end = self.skip_type(end + 1);
+                }
+                self.include_semicolon(end)
+            }
+            "type" => self
+                .type_only_declaration(k)
+                .unwrap_or_else(|| self.braced_declaration_end(k)),
+            _ => self.braced_declaration_end(k),
+        }
+    }
+
+    fn binding_end(&self, j: usize) -> usize {
+        if self.is(j, "{") || self.is(j, "[") {
+            self.after_close(j)
+        } else {
+            j + 1
+        }
+    }
+
+    /// Skip an initializer up to the next `,` or `;` at its level, or to the
+    /// end of its statement.
+    fn skip_expression(&self, start: usize) -> usize {
+        let depth = self.tokens.get(start).map_or(0, |t| t.depth);
+        for k in start..self.tokens.len() {
+            let token = self.tokens[k];
+            if token.depth < depth {
+                return k;
+            }
+            if token.depth == depth {
+                if self.is(k, ",") || self.is(k, ";") {
+                    return k;
+                }
+                let new_statement = token.newline_before
+                    && token.kind != Kind::Punct
+                    && self.is_expression_end(k - 1);
+                if k > start && new_statement {
+                    return k;
+                }
+            }
+        }
+        self.tokens.len()
+    }
+
+    /// `let`/`const`/`var`: strip `!` and type annotations from each declarator.
+    fn declaration(&mut self, i: usize) {
+        if self.is(i, "const") && self.is(i + 1, "enum") {
+            return;
+        }
+        let mut j = i + 1;
+        loop {
+            if !(self.kind(j) == Some(Kind::Ident) || self.is(j, "{") || self.is(j, "[")) {
+                return;
+            }
+            j = self.binding_end(j);
+            if self.is(j, "!") && self.is(j + 1, ":") {
+                self.erase(j, j + 1);
+                j += 1;
+            }
+            if self.is(j, ":") {
+                let end = self.skip_type(j + 1);
+                self.erase(j, end);
+                j = end;
+            }
+            if self.is(j, "in") || self.is(j, "of") {
+                return;
+            }
+            if self.is(j, "=") {
+                j = self.skip_expression(j + 1);
+            }
+            if !self.is(j, ",") {
+                return;
+            }
+            j += 1;
+        }
+    }
+
+    fn function(&mut self, i: usize) {
+        let mut j = i + 1;
+        if self.is(j, "*") {
+            j += 1;
+        }
+        if self.kind(j) == Some(Kind::Ident) {
+            j += 1;
+        }
+        if self.is(j, "<")
+            && let Some(end) = self.angle_end(j, false)
+        {
+            self.erase(j, end);
+            j = end;
+        }
+        if !self.is(j, "(") || self.params_done.contains(&j) {
+            return;
+        }
+        let end = self.params(j, None);
+        if !self.is(end, "{") {
+            // Overload signature: drop it along with `export`/`async`/...
+            let mut start = i;
+            while let Some(p) = self.prev(start) {
+                if !matches!(self.text(p), "export" | "default" | "async" | "declare") {
+                    break;
+                }
+                start = p;
+            }
+            self.erase_statement(start, self.include_semicolon(end));
+        }
+    }
+
+    /// A `(` not handled elsewhere: strip it if it is the parameter list of
+    /// an arrow function, an object literal method or a `catch` clause.
+    fn paren(&mut self, i: usize) {
+        if self.params_done.contains(&i) {
+            return;
+        }
+        let Some(close) = self.close[i] else { return };
+        let arrow = match self.text(close + 1) {
+            "=>" => true,
+            ":" => self.is(self.skip_type(close + 2), "=>"),
+            _ => false,
+        };
+        let prev = self.prev(i);
+        let method = !arrow
+            && prev.is_some_and(|p| {
+                let named = match self.kind(p) {
+                    Some(Kind::Ident) => !KEYWORDS.contains(&self.text(p)),
+                    Some(Kind::Str | Kind::Number) => true,
+                    _ => self.is(p, "]"),
+                };
+                let mut body = close + 1;
+                if self.is(body, ":") {
+                    body = self.skip_type(body + 1);
+                }
+                named && self.is(body, "{")
+            });
+        let catch = prev.is_some_and(|p| self.is(p, "catch"));
+        if arrow || method || catch {
+            self.params(i, None);
+        }
+    }
+
+    /// Strip a parameter list and its return type; returns the index after
+    /// the return type. For a constructor, `class_extends` is whether its
+    /// class has a superclass, and parameter properties are lowered.
+    fn params(&mut self, open: usize, class_extends: Option<bool>) -> usize {
+        self.params_done.insert(open);
+        let close = self.close[open].unwrap_or(self.tokens.len());
+        let depth = self.tokens[open].depth + 1;
+        let mut properties = Vec::new();
+        let mut start = open + 1;
+        while start < close {
+            let end = (start..close)
+                .find(|&k| self.tokens[k].depth == depth && self.is(k, ","))
+                .unwrap_or(close);
+            if let Some(name) = self.param(start, end, close) {
+                properties.push(name);
+            }
+            start = end + 1;
+        }
+
+        let mut end = close + 1;
+        if self.is(end, ":") {
+            let type_end = self.skip_type(end + 1);
+            self.erase(end, type_end);
+            end = type_end;
+        }
+        if let Some(class_extends) = class_extends
+            && !properties.is_empty()
+            && self.is(end, "{")
+        {
+            self.assign_properties(end, class_extends, &properties);
+        }
+        end
+    }
+
+    /// Strip one parameter (`start..end`); returns its name if it declares a
+    /// parameter property.
+    fn param(&mut self, start: usize, end: usize, close: usize) -> Option<String> {
+        let mut j = start;
+        let mut property = false;
+        while PARAM_MODIFIERS.contains(&self.text(j))
+            && j + 1 < end
+            && (self.kind(j + 1) == Some(Kind::Ident) || self.is(j + 1, "{") || self.is(j + 1, "["))
+        {
+            self.erase(j, j + 1);
+            property = true;
+            j += 1;
+        }
+        // `this` parameter: `function (this: Window, ...)`
+        if self.is(j, "this") && self.is(j + 1, ":") {
+            let to = if end < close { end + 1 } else { end };
+            self.erase(start, to);
+            return None;
+        }
+        if self.is(j, "...") {
+            j += 1;
+        }
+        let name = (self.kind(j) == Some(Kind::Ident)).then(|| self.text(j).to_string());
+        j = self.binding_end(j);
+        if self.is(j, "?") {
+            self.erase(j, j + 1);
+            j += 1;
+        }
+        if self.is(j, ":") {
+            let type_end = self.skip_type(j + 1).min(end);
+            self.erase(j, type_end);
+        }
+        name.filter(|_| property)
+    }
+
+    /// Lower parameter properties to assignments at the start of the
+    /// constructor body (after `super(...)` in a derived class).
+    fn assign_properties(&mut self, body: usize, class_extends: bool, names: &[String]) {
+        let assignments: String = names.iter().map(|n| format!(" this.{n} = {n};")).collect();
+        let close = self.close[body].unwrap_or(self.tokens.len());
+        let super_call = (body + 1..close)
+            .find(|&k| self.parent[k] == Some(body) && self.is(k, "super") && self.is(k + 1, "("))
+            .filter(|_| class_extends);
+        match super_call {
+            Some(k) => {
+                let after = self.after_close(k + 1);
+                if self.is(after, ";") {
+                    self.insert(self.tokens[after].end, assignments);
+                } else {
+                    self.insert(self.tokens[after - 1].end, format!(";{assignments}"));
+                }
+            }
+            None => self.insert(self.tokens[body].end, assignments),
+        }
+    }
+
+    // --- Classes ---
+
+    /// Strip generics and `implements` from a class heading and remember its body.
+    fn class_heading(&mut self, i: usize) {
+        let depth = self.tokens[i].depth;
+        let mut extends = false;
+        let mut j = i + 1;
+        while j < self.tokens.len() {
+            let token = self.tokens[j];
+            if token.depth < depth || (token.depth == depth && (self.is(j, ";") || self.is(j, ")")))
+            {
+                return;
+            }
+            if token.depth == depth {
+                match self.text(j) {
+                    "{" if token.kind == Kind::Punct => {
+                        self.classes.insert(j, extends);
+                        return;
+                    }
+                    "<" if self.kind(j - 1) == Some(Kind::Ident) => {
+                        if let Some(end) = self.angle_end(j, false) {
+                            self.erase(j, end);
+                            j = end;
+                            continue;
+                        }
+                    }
+                    "extends" => extends = true,
+                    "implements" => {
+                        let body = (j..self.tokens.len())
+                            .find(|&k| self.tokens[k].depth == depth && self.is(k, "{"))
+                            .unwrap_or(self.tokens.len());
+                        self.erase(j, body);
+                        j = body;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            j += 1;
+        }
+    }
+
+    fn in_class_body(&self, i: usize) -> bool {
+        self.parent[i].is_some_and(|p| self.classes.contains_key(&p))
+    }
+
+    /// Whether token `i`, directly inside a class body, starts a member.
+    fn member_start(&self, i: usize) -> bool {
+        let Some(p) = self.prev(i) else { return false };
+        if Some(p) == self.parent[i] {
+            return true;
+        }
+        if self.parent[p] != self.parent[i] {
+            return false;
+        }
+        let starts_name = matches!(self.kind(i), Some(Kind::Ident | Kind::Str | Kind::Number))
+            || self.is(i, "[")
+            || self.is(i, "*");
+        match self.text(p) {
+            ";" => true,
+            "}" => starts_name,
+            _ => self.tokens[i].newline_before && starts_name && self.is_expression_end(p),
+        }
+    }
+
+    fn class_member(&mut self, start: usize) {
+        let class_open = self.parent[start].unwrap_or_default();
+        let mut j = start;
+        let mut ambient = false;
+        while self.kind(j) == Some(Kind::Ident)
+            && MEMBER_MODIFIERS.contains(&self.text(j))
+            && self.member_name_follows(j + 1)
+        {
+            match self.text(j) {
+                "declare" | "abstract" => ambient = true,
+                modifier if PARAM_MODIFIERS.contains(&modifier) => self.erase(j, j + 1),
+                _ => {}
+            }
+            j += 1;
+        }
+        if self.is(j, "*") {
+            j += 1;
+        }
+        // Index signature: `[key: string]: T;`
+        if self.is(j, "[") && self.kind(j + 1) == Some(Kind::Ident) && self.is(j + 2, ":") {
+            let mut end = self.after_close(j);
+            if self.is(end, ":") {
+                end = self.skip_type(end + 1);
+            }
+            self.erase(start, self.include_semicolon(end));
+            return;
+        }
+
+        let name = j;
+        j = match self.kind(j) {
+            _ if self.is(j, "[") => self.after_close(j),
+            Some(Kind::Ident | Kind::Str | Kind::Number) => j + 1,
+            _ => return,
+        };
+        if self.is(j, "?") || self.is(j, "!") {
+            self.erase(j, j + 1);
+            j += 1;
+        }
+        if self.is(j, "<")
+            && let Some(end) = self.angle_end(j, false)
+        {
+            self.erase(j, end);
+            j = end;
+        }
+        if self.is(j, "(") {
+            let constructor = self.text(name) == "constructor";
+            let class_extends = self.classes.get(&class_open).copied().unwrap_or(false);
+            let end = self.params(j, constructor.then_some(class_extends));
+            // No body: an overload or abstract method signature
+            if ambient || !self.is(end, "{") {
+                self.erase(start, self.member_end(class_open, end));
+            }
+            return;
+        }
+        if ambient {
+            self.erase(start, self.member_end(class_open, j));
+        } else if self.is(j, ":") {
+            let end = self.skip_type(j + 1);
+            self.erase(j, end);
+        }
+    }
+
+    fn member_name_follows(&self, i: usize) -> bool {
+        self.same_line(i)
+            && (matches!(self.kind(i), Some(Kind::Ident | Kind::Str | Kind::Number))
+                || self.is(i, "[")
+                || self.is(i, "*")
+                || self.is(i, "{"))
+    }
+
+    /// End of a bodiless member from `from`: its `;`, or the next member.
+    fn member_end(&self, class_open: usize, from: usize) -> usize {
+        for k in from..self.tokens.len() {
+            if self.parent[k] != Some(class_open) {
+                if self.tokens[k].depth <= self.tokens[class_open].depth {
+                    return k;
+                }
+                continue;
+            }
+            if self.is(k, ";") {
+                return k + 1;
+            }
+            if k > from && self.member_start(k) {
+                return k;
+            }
+        }
+        self.tokens.len()
+    }
+
+    // --- Enums ---
+
+    /// `enum E { A, B = 5 }` becomes an IIFE filling `E` with both the name
+    /// to value and (for numbers) value to name mappings.
+    fn lower_enum(&mut self, i: usize) {
+        let name = self.text(i + 1);
+        let open = i + 2;
+        let Some(close) = self.close[open] else {
+            return;
+        };
+        if let Some(p) = self.prev(i).filter(|&p| self.is(p, "const")) {
+            self.erase(p, p + 1);
+        }
+        self.replace(
+            i,
+            open + 1,
+            format!(
+                "var {name} = (function ({name}) {{ let __enum_next = 0; \
+                 const __enum = (key, value = __enum_next) => {{ {name}[key] = value; \
+                 if (typeof value === \"number\") {{ {name}[value] = key; __enum_next = value + 1; }} \
+                 return value; }};"
+            ),
+        );
+
+        let depth = self.tokens[open].depth + 1;
+        let mut start = open + 1;
+        while start < close {
+            let end = (start..close)
+                .find(|&k| self.tokens[k].depth == depth && self.is(k, ","))
+                .unwrap_or(close);
+            if start < end {
+                let member = self.text(start);
+                let key = match self.kind(start) {
+                    Some(Kind::Str) => member.to_string(),
+                    _ => quote(member),
+                };
+                let value = if self.is(start + 1, "=") && start + 2 < end {
+                    let from = self.tokens[start + 2].start;
+                    format!(", ({})", &self.src[from..self.tokens[end - 1].end])
+                } else {
+                    String::new()
+                };
+                // Later initializers may refer to earlier members by name
+                let bindable = self.kind(start) == Some(Kind::Ident) && !KEYWORDS.contains(&member);
+                let binding = if bindable && member != name {
+                    format!("const {member} = ")
+                } else {
+                    String::new()
+                };
+                self.replace(start, end, format!("{binding}__enum({key}{value});"));
+            }
+            if end < close {
+                self.erase(end, end + 1);
+            }
+            start = end + 1;
+        }
+        self.replace(
+            close,
+            close + 1,
+            format!("return {name}; }})({name} || {{}});"),
+        );
+    }
+
+    // --- Imports and exports ---
+
+    fn import(&mut self, i: usize) {
+        // `import type X from`, but not a default import named `type`
+        let default_named_type = self.is(i + 2, "from") || self.is(i + 2, ",");
+        if self.is(i + 1, "type") && !default_named_type {
+            let end = self.module_clause_end(i);
+            self.erase_statement(i, end);
+            return;
+        }
+        // `import fs = require("fs")`
+        if self.kind(i + 1) == Some(Kind::Ident) && self.is(i + 2, "=") {
+            self.replace(i, i + 1, "const".to_string());
+            return;
+        }
+        let depth = self.tokens[i].depth;
+        for j in i + 1..self.tokens.len() {
+            if self.kind(j) == Some(Kind::Str) || self.tokens[j].depth < depth {
+                return;
+            }
+            if self.tokens[j].depth == depth && self.is(j, "{") {
+                self.type_specifiers(j);
+                return;
+            }
+        }
+    }
+
+    fn export(&mut self, i: usize) {
+        if let Some(end) = self.type_only_declaration(i + 1) {
+            self.erase_statement(i, end);
+        } else if self.is(i + 1, "default")
+            && self.is(i + 2, "interface")
+            && let Some(end) = self.type_only_declaration(i + 2)
+        {
+            self.erase_statement(i, end);
+        } else if self.is(i + 1, "type") && (self.is(i + 2, "{") || self.is(i + 2, "*")) {
+            let end = self.module_clause_end(i);
+            self.erase_statement(i, end);
+        } else if self.is(i + 1, "=") {
+            self.replace(i, i + 1, "module.exports".to_string());
+        } else if self.is(i + 1, "as") && self.is(i + 2, "namespace") {
+            let end = self.include_semicolon(i + 4);
+            self.erase_statement(i, end);
+        } else if self.is(i + 1, "{") {
+            self.type_specifiers(i + 1);
+        }
+    }
+
+    /// End of an import/export statement: past its `from "..."` (or its
+    /// specifier list), any `with {...}` attributes and the `;`.
+    fn module_clause_end(&self, i: usize) -> usize {
+        let depth = self.tokens[i].depth;
+        let mut end = self.tokens.len();
+        for j in i + 1..self.tokens.len() {
+            if self.tokens[j].depth < depth {
+                return j;
+            }
+            if self.kind(j) == Some(Kind::Str) {
+                end = j + 1;
+                break;
+            }
+            if self.tokens[j].depth == depth
+                && self.is(j, "{")
+                && !self.is(self.after_close(j), "from")
+            {
+                // `export type { A };`
+                return self.include_semicolon(self.after_close(j));
+            }
+        }
+        if (self.is(end, "with") || self.is(end, "assert")) && self.is(end + 1, "{") {
+            end = self.after_close(end + 1);
+        }
+        self.include_semicolon(end)
+    }
+
+    /// Drop `type X` entries from an import/export specifier list.
+    fn type_specifiers(&mut self, open: usize) {
+        self.specifiers.insert(open);
+        let Some(close) = self.close[open] else {
+            return;
+        };
+        let mut start = open + 1;
+        while start < close {
+            let end = (start..close).find(|&k| self.is(k, ",")).unwrap_or(close);
+            let type_only = self.is(start, "type")
+                && self.kind(start + 1) == Some(Kind::Ident)
+                && !(self.is(start + 1, "as") && end == start + 3);
+            if type_only {
+                self.erase(start, if end < close { end + 1 } else { end });
+            }
+            start = end + 1;
+        }
+    }
+
+    // --- Output ---
+
+    fn output(self) -> String {
+        let mut edits = self.edits;
+        for (i, erase) in self.erase.iter().enumerate() {
+            if *erase == Erase::Keep {
+                continue;
+            }
+            let token = self.tokens[i];
+            let mut blank: String = self.src[token.start..token.end]
+                .chars()
+                .map(|c| if c == '\n' || c == '\r' { c } else { ' ' })
+                .collect();
+            if *erase == Erase::Statement {
+                blank.replace_range(0..1, ";");
+            }
+            edits.push((token.start, token.end, blank));
+        }
+        edits.sort_by_key(|e| (e.0, e.1));
+
+        let mut code = String::with_capacity(self.src.len());
+        let mut pos = 0;
+        for (start, end, text) in edits {
+            if start < pos {
+                continue;
+            }
+            code.push_str(&self.src[pos..start]);
+            code.push_str(&text);
+            // Keep line numbers stable when a multi-line construct is replaced
+            let removed = self.src[start..end].matches('\n').count();
+            let added = text.matches('\n').count();
+            for _ in added..removed {
+                code.push('\n');
+            }
+            pos = end;
+        }
+        code.push_str(&self.src[pos..]);
+        code
+    }
+}
@@ -1,74 +1,301 @@
-use std::io::{self, Write};
+use std::fmt::Write;
 
 pub fn run(args: &[String]) -> i32 {
+    print!("{}", render(args));
+    0
+}
+
+/// The text `printf` prints for the given arguments.
+pub fn render(args: &[String]) -> String {
+    let mut out = String::new();
     if args.is_empty() {
-        return 0;
+        return out;
     }
 
     let format = &args[0];
     let params = &args[1..];
     let mut param_idx = 0;
 
-    let stdout = io::stdout();
-    let mut out = stdout.lock();
-
     let chars: Vec<char> = format.chars().collect();
-    let mut i = 0;
 
-    while i < chars.len() {
-        if chars[i] == '\\' && i + 1 < chars.len() {
-            match chars[i + 1] {
-                'n' => {
-                    writeln!(out).ok();
-                }
-                't' => {
-                    write!(out, "\t").ok();
+    // The format is reused until the arguments run out
+    loop {
+        let mut i = 0;
+
+        while i < chars.len() {
+            if chars[i] == '\\' && i + 1 < chars.len() {
+                match chars[i + 1] {
+                    'n' => {
+                        writeln!(out).ok();
+                    }
+                    't' => {
+                        write!(out, "\t").ok();
+                    }
+                    '\\' => {
+                        write!(out, "\\").ok();
+                    }
+                    '"' => {
+                        write!(out, "\"").ok();
+                    }
+                    '0' => {
+                        write!(out, "\0").ok();
+                    }
+                    _ => {
+                        write!(out, "\\{}", chars[i + 1]).ok();
+                    }
                 }
-                '\\' => {
-                    write!(out, "\\").ok();
+                i += 2;
+            } else if chars[i] == '%' && i + 1 < chars.len() {
+                if chars[i + 1] == '%' {
+                    out.push('%');
+                    i += 2;
+                    continue;
                 }
-                '"' => {
-                    write!(out, "\"").ok();
+                // %[flags][width][.precision]conversion, where `*` takes the
+                // width or precision from the arguments
+                let mut spec = Spec::default();
+                let mut j = i + 1;
+                while let Some(&flag) = chars.get(j) {
+                    match flag {
+                        '-' => spec.left = true,
+                        '0' => spec.zero = true,
+                        '+' => spec.plus = true,
+                        ' ' => spec.space = true,
+                        '#' => spec.alt = true,
+                        _ => break,
+                    }
+                    j += 1;
                 }
-                '0' => {
-                    write!(out, "\0").ok();
+                let mut next_param = || {
+                    let param = params.get(param_idx).map_or("", String::as_str);
+                    param_idx += 1;
+                    param
+                };
+                if chars.get(j) == Some(&'*') {
+                    let width = parse_int(next_param());
+                    spec.left |= width < 0;
+                    spec.width = width.unsigned_abs() as usize;
+                    j += 1;
+                } else {
+                    spec.width = digits(&chars, &mut j);
                 }
-                _ => {
-                    write!(out, "\\{}", chars[i + 1]).ok();
+                if chars.get(j) == Some(&'.') {
+                    j += 1;
+                    spec.precision = if chars.get(j) == Some(&'*') {
+                        j += 1;
+                        usize::try_from(parse_int(next_param())).ok()
+                    } else {
+                        Some(digits(&chars, &mut j))
+                    };
                 }
-            }
-            i += 2;
-        } else if chars[i] == '%' && i + 1 < chars.len() {
-            match chars[i + 1] {
-                's' => {
-                    if param_idx < params.len() {
-                        write!(out, "{}", params[param_idx]).ok();
-                        param_idx += 1;
+                match chars.get(j) {
+                    Some(&conversion) if "scdiuxXofFeEgG".contains(conversion) => {
+                        out.push_str(&format_one(conversion, &spec, next_param()));
+                        i = j + 1;
                     }
-                    i += 2;
-                }
-                'd' => {
-                    if param_idx < params.len() {
-                        let n: i64 = params[param_idx].parse().unwrap_or(0);
-                        write!(out, "{}", n).ok();
-                        param_idx += 1;
+                    // Not a conversion after all: print it as it stands
+                    _ => {
+                        let end = (j + 1).min(chars.len());
+                        out.extend(&chars[i..end]);
+                        i = end;
                     }
-                    i += 2;
-                }
-                '%' => {
-                    write!(out, "%").ok();
-                    i += 2;
-                }
-                _ => {
-                    write!(out, "%{}", chars[i + 1]).ok();
-                    i += 2;
                 }
+            } else {
+                write!(out, "{}", chars[i]).ok();
+                i += 1;
             }
+        }
+
+        if param_idx == 0 || param_idx >= params.len() {
+            break;
+        }
+    }
+
+    out
+}
+
+/// Flags, width and precision of one conversion.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// Read a run of decimal digits starting at `chars[*j]`, as a number.
+fn digits(chars: &[char], j: &mut usize) -> usize {
+    let mut n = 0usize;
+    while let Some(d) = chars.get(*j).and_then(|c| c.to_digit(10)) {
+        n = n.saturating_mul(10).saturating_add(d as usize);
+        *j += 1;
+    }
+    n
+}
+
+/// An integer argument: decimal, hex with `0x`, octal with a leading `0`,
+/// or the character code of what follows a leading quote. Anything else
+/// counts as 0.
+fn parse_int(arg: &str) -> i64 {
+    let arg = arg.trim();
+    if let Some(quoted) = arg.strip_prefix(['\'', '"']) {
+        return quoted.chars().next().map_or(0, |c| c as i64);
+    }
+    let (negative, digits) = match arg.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, arg.strip_prefix('+').unwrap_or(arg)),
+    };
+    let magnitude = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    };
+    let magnitude = magnitude.unwrap_or(0);
+    if negative { -magnitude } else { magnitude }
+}
+
+/// A floating-point argument, which may also be given as an integer.
+fn parse_float(arg: &str) -> f64 {
+    let arg = arg.trim();
+    if arg.starts_with(['\'', '"']) {
+        return parse_int(arg) as f64;
+    }
+    arg.parse()
+        .unwrap_or_else(|_| if arg.is_empty() { 0.0 } else { parse_int(arg) as f64 })
+}
+
+/// Format `arg` for one conversion.
+fn format_one(conversion: char, spec: &Spec, arg: &str) -> String {
+    let (sign, body) = match conversion {
+        's' => {
+            let text: String = match spec.precision {
+                Some(precision) => arg.chars().take(precision).collect(),
+                None => arg.to_string(),
+            };
+            return pad(spec, "", &text, false);
+        }
+        'c' => return pad(spec, "", &arg.chars().take(1).collect::<String>(), false),
+        'd' | 'i' => {
+            let n = parse_int(arg);
+            (sign_of(n < 0, spec), integer_digits(n.unsigned_abs().to_string(), spec))
+        }
+        'u' | 'x' | 'X' | 'o' => {
+            let n = parse_int(arg) as u64;
+            let body = match conversion {
+                'u' => n.to_string(),
+                'x' => format!("{n:x}"),
+                'X' => format!("{n:X}"),
+                _ => format!("{n:o}"),
+            };
+            let body = integer_digits(body, spec);
+            let prefix = match conversion {
+                'x' if spec.alt && n != 0 => "0x",
+                'X' if spec.alt && n != 0 => "0X",
+                'o' if spec.alt && !body.starts_with('0') => "0",
+                _ => "",
+            };
+            (prefix, body)
+        }
+        _ => {
+            let n = parse_float(arg);
+            let precision = spec.precision.unwrap_or(6);
+            let body = match conversion {
+                'f' | 'F' => format!("{:.*}", precision, n.abs()),
+                'e' | 'E' => exponential(n.abs(), precision),
+                _ => general(n.abs(), precision, spec.alt),
+            };
+            let body = if conversion.is_ascii_uppercase() {
+                body.to_uppercase()
+            } else {
+                body
+            };
+            (sign_of(n.is_sign_negative() && n != 0.0, spec), body)
+        }
+    };
+    // A precision on an integer turns off zero padding
+    let zero = !(spec.precision.is_some() && "diuxXo".contains(conversion));
+    pad(spec, sign, &body, zero)
+}
+
+fn sign_of(negative: bool, spec: &Spec) -> &'static str {
+    match () {
+        _ if negative => "-",
+        _ if spec.plus => "+",
+        _ if spec.space => " ",
+        _ => "",
+    }
+}
+
+/// Integer digits padded with zeros to the precision, where a precision
+/// of 0 prints 0 as nothing at all.
+fn integer_digits(digits: String, spec: &Spec) -> String {
+    match spec.precision {
+        Some(0) if digits == "0" => String::new(),
+        Some(precision) if digits.len() < precision => {
+            format!("{}{}", "0".repeat(precision - digits.len()), digits)
+        }
+        _ => digits,
+    }
+}
+
+/// `n` as `d.ddde+xx`, the way C's `%e` writes it.
+fn exponential(n: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, n);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{mantissa}e{sign}{:02}", exponent.abs())
+}
+
+/// `n` the way C's `%g` writes it: as `%e` for very large or small numbers
+/// and as `%f` otherwise, without trailing zeros unless `alt` is set.
+fn general(n: f64, precision: usize, alt: bool) -> String {
+    let precision = precision.max(1);
+    let exponent = if n == 0.0 {
+        0
+    } else {
+        exponential(n, precision - 1)
+            .split_once('e')
+            .and_then(|(_, e)| e.parse::<i32>().ok())
+            .unwrap_or(0)
+    };
+    let formatted = if exponent < -4 || exponent >= precision as i32 {
+        exponential(n, precision - 1)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exponent) as usize, n)
+    };
+    if alt {
+        return formatted;
+    }
+    let trim = |digits: &str| {
+        if digits.contains('.') {
+            digits.trim_end_matches('0').trim_end_matches('.').to_string()
         } else {
-            write!(out, "{}", chars[i]).ok();
-            i += 1;
+            digits.to_string()
         }
+    };
+    match formatted.split_once('e') {
+        Some((mantissa, exponent)) => format!("{}e{}", trim(mantissa), exponent),
+        None => trim(&formatted),
     }
+}
 
-    0
+/// Pad `sign` and `body` out to the width: on the right with `-`, with
+/// zeros after the sign with `0` (where `zero` allows it), and with spaces
+/// on the left otherwise.
+fn pad(spec: &Spec, sign: &str, body: &str, zero: bool) -> String {
+    let len = sign.chars().count() + body.chars().count();
+    let fill = spec.width.saturating_sub(len);
+    if spec.left {
+        format!("{sign}{body}{}", " ".repeat(fill))
+    } else if spec.zero && zero {
+        format!("{sign}{}{body}", "0".repeat(fill))
+    } else {
+        format!("{}{sign}{body}", " ".repeat(fill))
+    }
 }
@@ -0,0 +1,367 @@
+//! Syntax tree the parser produces and the interpreter walks.
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use super::value::Value;
+
+pub type Block = Vec<Stmt>;
+
+#[derive(Debug)]
+pub struct Stmt {
+    /// Line the statement starts on, for tracebacks.
+    pub line: usize,
+    pub kind: StmtKind,
+}
+
+#[derive(Debug)]
+pub enum StmtKind {
+    Expr(Expr),
+    /// `a = b = value`: every target gets the same value.
+    Assign {
+        targets: Vec<Expr>,
+        value: Expr,
+    },
+    AugAssign {
+        target: Expr,
+        op: BinOp,
+        value: Expr,
+    },
+    /// `name: annotation [= value]`. The annotation itself is not kept; class
+    /// bodies record the name in `__annotations__`.
+    AnnAssign {
+        target: Expr,
+        value: Option<Expr>,
+    },
+    If {
+        test: Expr,
+        body: Block,
+        orelse: Block,
+    },
+    While {
+        test: Expr,
+        body: Block,
+        orelse: Block,
+    },
+    For {
+        target: Expr,
+        iter: Expr,
+        body: Block,
+        orelse: Block,
+    },
+    Break,
+    Continue,
+    Pass,
+    Return(Option<Expr>),
+    FunctionDef(Rc<FunctionDef>),
+    ClassDef {
+        name: Rc<str>,
+        bases: Vec<Arg>,
+        body: Block,
+        decorators: Vec<Expr>,
+    },
+    Try {
+        body: Block,
+        handlers: Vec<Handler>,
+        orelse: Block,
+        finally: Block,
+    },
+    Raise {
+        exc: Option<Expr>,
+        cause: Option<Expr>,
+    },
+    /// `import a.b as c, d`
+    Import(Vec<(String, Option<Rc<str>>)>),
+    /// `from .module import a as b`; no names means `*`.
+    ImportFrom {
+        module: String,
+        level: usize,
+        names: Vec<(Rc<str>, Option<Rc<str>>)>,
+    },
+    Global(Vec<Rc<str>>),
+    Nonlocal(Vec<Rc<str>>),
+    Assert {
+        test: Expr,
+        msg: Option<Expr>,
+    },
+    Del(Vec<Expr>),
+    With {
+        items: Vec<(Expr, Option<Expr>)>,
+        body: Block,
+    },
+}
+
+#[derive(Debug)]
+pub struct FunctionDef {
+    pub name: Rc<str>,
+    pub params: Vec<Param>,
+    pub body: Block,
+    pub decorators: Vec<Expr>,
+    /// The body contains `yield`, so calling it makes a generator.
+    pub is_generator: bool,
+    /// Names the body binds, which are local unless declared `global` or
+    /// `nonlocal`.
+    pub locals: HashSet<Rc<str>>,
+}
+
+#[derive(Debug)]
+pub struct Param {
+    pub name: Rc<str>,
+    pub kind: ParamKind,
+    pub default: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    Positional,
+    /// `*args`
+    VarArgs,
+    /// After `*` or `*args`.
+    KeywordOnly,
+    /// `**kwargs`
+    VarKeywords,
+}
+
+#[derive(Debug)]
+pub struct Handler {
+    pub kind: Option<Expr>,
+    pub name: Option<Rc<str>>,
+    pub body: Block,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    Name(Rc<str>),
+    Const(Value),
+    FString(Vec<FPart>),
+    List(Vec<Expr>),
+    Tuple(Vec<Expr>),
+    Set(Vec<Expr>),
+    /// A `None` key is a `**mapping` entry.
+    Dict(Vec<(Option<Expr>, Expr)>),
+    Comp(Box<Comprehension>),
+    BinOp(Box<Expr>, BinOp, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, Vec<(CmpOp, Expr)>),
+    Call(Box<Expr>, Vec<Arg>),
+    Attribute(Box<Expr>, Rc<str>),
+    Subscript(Box<Expr>, Box<Expr>),
+    Slice(Option<Box<Expr>>, Option<Box<Expr>>, Option<Box<Expr>>),
+    IfExp(Box<Expr>, Box<Expr>, Box<Expr>),
+    Lambda(Rc<FunctionDef>),
+    Starred(Box<Expr>),
+    Yield(Option<Box<Expr>>),
+    YieldFrom(Box<Expr>),
+    /// `name := value`
+    Named(Rc<str>, Box<Expr>),
+}
+
+#[derive(Debug)]
+pub enum FPart {
+    Literal(String),
+    Field {
+        expr: Expr,
+        /// `!r`, `!s` or `!a`
+        conversion: Option<char>,
+        spec: Vec<FPart>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompKind {
+    List,
+    Set,
+    Dict,
+    /// Generator expressions are evaluated eagerly, like generators.
+    Generator,
+}
+
+#[derive(Debug)]
+pub struct Comprehension {
+    pub kind: CompKind,
+    pub elt: Expr,
+    /// The value of a dict comprehension; `elt` is the key.
+    pub value: Option<Expr>,
+    pub generators: Vec<Generator>,
+}
+
+#[derive(Debug)]
+pub struct Generator {
+    pub target: Expr,
+    pub iter: Expr,
+    pub ifs: Vec<Expr>,
+}
+
+#[derive(Debug)]
+pub enum Arg {
+    Positional(Expr),
+    Starred(Expr),
+    Keyword(Rc<str>, Expr),
+    DoubleStarred(Expr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Mod,
+    Pow,
+    MatMul,
+    LShift,
+    RShift,
+    BitOr,
+    BitXor,
+    BitAnd,
+}
+
+impl BinOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::FloorDiv => "//",
+            BinOp::Mod => "%",
+            BinOp::Pow => "**",
+            BinOp::MatMul => "@",
+            BinOp::LShift => "<<",
+            BinOp::RShift => ">>",
+            BinOp::BitOr => "|",
+            BinOp::BitXor => "^",
+            BinOp::BitAnd => "&",
+        }
+    }
+
+    /// The dunder method implementing the operator, e.g. `__add__`.
+    pub fn dunder(self) -> (&'static str, &'static str) {
+        match self {
+            BinOp::Add => ("__add__", "__radd__"),
+            BinOp::Sub => ("__sub__", "__rsub__"),
+            BinOp::Mul => ("__mul__", "__rmul__"),
+            BinOp::Div => ("__truediv__", "__rtruediv__"),
+            BinOp::FloorDiv => ("__floordiv__", "__rfloordiv__"),
+            BinOp::Mod => ("__mod__", "__rmod__"),
+            BinOp::Pow => ("__pow__", "__rpow__"),
+            BinOp::MatMul => ("__matmul__", "__rmatmul__"),
+            BinOp::LShift => ("__lshift__", "__rlshift__"),
+            BinOp::RShift => ("__rshift__", "__rrshift__"),
+            BinOp::BitOr => ("__or__", "__ror__"),
+            BinOp::BitXor => ("__xor__", "__rxor__"),
+            BinOp::BitAnd => ("__and__", "__rand__"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Pos,
+    Invert,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtE,
+    Gt,
+    GtE,
+    In,
+    NotIn,
+    Is,
+    IsNot,
+}
+
+/// The names a function body binds: assignment, loop, `with`, `except`,
+/// `import`, `def`, `class` and `del` targets, not looking into nested
+/// functions and classes.
+pub fn bound_names(body: &[Stmt], names: &mut HashSet<Rc<str>>) {
+    fn targets(target: &Expr, names: &mut HashSet<Rc<str>>) {
+        match target {
+            Expr::Name(name) => {
+                names.insert(name.clone());
+            }
+            Expr::List(items) | Expr::Tuple(items) => {
+                for item in items {
+                    targets(item, names);
+                }
+            }
+            Expr::Starred(inner) => targets(inner, names),
+            _ => {}
+        }
+    }
+    for stmt in body {
+        match &stmt.kind {
+            StmtKind::Assign { targets: list, .. } => {
+                for target in list {
+                    targets(target, names);
+                }
+            }
+            StmtKind::AugAssign { target, .. } | StmtKind::AnnAssign { target, .. } => {
+                targets(target, names);
+            }
+            StmtKind::If { body, orelse, .. } | StmtKind::While { body, orelse, .. } => {
+                bound_names(body, names);
+                bound_names(orelse, names);
+            }
+            StmtKind::For { target, body, orelse, .. } => {
+                targets(target, names);
+                bound_names(body, names);
+                bound_names(orelse, names);
+            }
+            StmtKind::FunctionDef(def) => {
+                names.insert(def.name.clone());
+            }
+            StmtKind::ClassDef { name, .. } => {
+                names.insert(name.clone());
+            }
+            StmtKind::Try { body, handlers, orelse, finally } => {
+                bound_names(body, names);
+                for handler in handlers {
+                    if let Some(name) = &handler.name {
+                        names.insert(name.clone());
+                    }
+                    bound_names(&handler.body, names);
+                }
+                bound_names(orelse, names);
+                bound_names(finally, names);
+            }
+            StmtKind::Import(modules) => {
+                for (module, alias) in modules {
+                    let name = match alias {
+                        Some(alias) => alias.clone(),
+                        None => Rc::from(module.split('.').next().unwrap_or(module)),
+                    };
+                    names.insert(name);
+                }
+            }
+            StmtKind::ImportFrom { names: imported, .. } => {
+                for (name, alias) in imported {
+                    names.insert(alias.as_ref().unwrap_or(name).clone());
+                }
+            }
+            StmtKind::Del(list) => {
+                for target in list {
+                    targets(target, names);
+                }
+            }
+            StmtKind::With { items, body } => {
+                for (_, target) in items {
+                    if let Some(target) = target {
+                        targets(target, names);
+                    }
+                }
+                bound_names(body, names);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,1168 @@
+//! The `builtins` module: builtin functions and the constructors of the
+//! builtin types.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::rc::Rc;
+
+use indexmap::IndexMap;
+
+use super::error::{PyErr, PyResult, int_too_large, raise};
+use super::format::{ascii_escape, format_value, str_repr};
+use super::interp::Interp;
+use super::methods;
+use super::modules;
+use super::value::*;
+
+/// Builtin functions, by name.
+const FUNCTIONS: &[&str] = &[
+    "abs", "all", "any", "ascii", "bin", "callable", "chr", "delattr", "dir", "divmod",
+    "enumerate", "exit", "filter", "format", "getattr", "hasattr", "hash", "hex", "id", "input",
+    "isinstance", "issubclass", "iter", "len", "map", "max", "min", "next", "oct", "open", "ord",
+    "pow", "print", "quit", "repr", "reversed", "round", "setattr", "sorted", "sum", "vars", "zip",
+    "__import__",
+];
+
+/// Builtin types, by name.
+const TYPES: &[(&str, Type)] = &[
+    ("object", Type::Object),
+    ("bool", Type::Bool),
+    ("int", Type::Int),
+    ("float", Type::Float),
+    ("str", Type::Str),
+    ("list", Type::List),
+    ("tuple", Type::Tuple),
+    ("dict", Type::Dict),
+    ("set", Type::Set),
+    ("frozenset", Type::FrozenSet),
+    ("range", Type::Range),
+    ("slice", Type::Slice),
+    ("type", Type::Type),
+    ("property", Type::Property),
+    ("staticmethod", Type::StaticMethod),
+    ("classmethod", Type::ClassMethod),
+    ("super", Type::Super),
+];
+
+pub fn install(builtins: &mut HashMap<Rc<str>, Value>) {
+    for &name in FUNCTIONS {
+        builtins.insert(Rc::from(name), Value::Builtin(name));
+    }
+    for &(name, kind) in TYPES {
+        builtins.insert(Rc::from(name), Value::Type(kind));
+    }
+    builtins.insert(Rc::from("NotImplemented"), Value::NotImplemented);
+    builtins.insert(Rc::from("Ellipsis"), Value::Ellipsis);
+    builtins.insert(Rc::from("__name__"), Value::str("builtins"));
+}
+
+/// Call builtin function `name`.
+pub fn call(interp: &mut Interp, name: &str, mut args: Args) -> PyResult<Value> {
+    if name.contains('.') {
+        return match name {
+            "BaseException.__init__" => {
+                let Some(Value::Instance(instance)) = args.pos.first() else {
+                    return raise("TypeError", "descriptor '__init__' requires an exception");
+                };
+                let rest = Value::tuple(args.pos[1..].to_vec());
+                instance.attrs.borrow_mut().insert(Rc::from("args"), rest);
+                Ok(Value::None)
+            }
+            "OSError.__init__" => {
+                let Some(Value::Instance(instance)) = args.pos.first() else {
+                    return raise("TypeError", "descriptor '__init__' requires an exception");
+                };
+                init_os_error(instance, &args.pos[1..]);
+                Ok(Value::None)
+            }
+            "BaseException.with_traceback" => Ok(args.pos.first().cloned().unwrap_or(Value::None)),
+            _ => modules::call(interp, name, args),
+        };
+    }
+    match name {
+        "print" => {
+            let sep = match args.take_kw("sep") {
+                Some(Value::None) | None => " ".to_string(),
+                Some(Value::Str(s)) => s.to_string(),
+                Some(other) => {
+                    return raise(
+                        "TypeError",
+                        format!("sep must be None or a string, not {}", other.type_name()),
+                    );
+                }
+            };
+            let end = match args.take_kw("end") {
+                Some(Value::None) | None => "\n".to_string(),
+                Some(Value::Str(s)) => s.to_string(),
+                Some(other) => {
+                    return raise(
+                        "TypeError",
+                        format!("end must be None or a string, not {}", other.type_name()),
+                    );
+                }
+            };
+            let file = args.take_kw("file").unwrap_or(Value::None);
+            let flush = args.take_kw("flush");
+            no_keywords("print", &args)?;
+            let mut text = String::new();
+            for (i, value) in args.pos.iter().enumerate() {
+                if i > 0 {
+                    text.push_str(&sep);
+                }
+                text.push_str(&interp.to_str(value)?);
+            }
+            text.push_str(&end);
+            if file.is_none() {
+                interp.write_out(&text);
+                if let Some(flush) = flush
+                    && interp.truthy(&flush)?
+                {
+                    interp.flush();
+                }
+            } else {
+                let write = interp.get_attr(&file, &Rc::from("write"))?;
+                interp.call(&write, Args::new(vec![Value::str(&text)]))?;
+            }
+            Ok(Value::None)
+        }
+        "len" => {
+            let [value] = exact::<1>("len", args)?;
+            Ok(Value::Int(interp.len(&value)? as i64))
+        }
+        "repr" => {
+            let [value] = exact::<1>("repr", args)?;
+            Ok(Value::str(&interp.repr(&value)?))
+        }
+        "ascii" => {
+            let [value] = exact::<1>("ascii", args)?;
+            Ok(Value::str(&ascii_escape(&interp.repr(&value)?)))
+        }
+        "abs" => {
+            let [value] = exact::<1>("abs", args)?;
+            match value {
+                Value::Int(n) => match n.checked_abs() {
+                    Some(n) => Ok(Value::Int(n)),
+                    None => int_too_large(),
+                },
+                Value::Bool(b) => Ok(Value::Int(b as i64)),
+                Value::Float(x) => Ok(Value::Float(x.abs())),
+                other => match interp.dunder(&other, "__abs__", Vec::new())? {
+                    Some(result) => Ok(result),
+                    None => raise(
+                        "TypeError",
+                        format!("bad operand type for abs(): '{}'", other.type_name()),
+                    ),
+                },
+            }
+        }
+        "all" | "any" => {
+            let [iterable] = exact::<1>(name, args)?;
+            let want = name == "any";
+            let iterator = interp.iter(&iterable)?;
+            while let Some(item) = interp.next(&iterator)? {
+                if interp.truthy(&item)? == want {
+                    return Ok(Value::Bool(want));
+                }
+            }
+            Ok(Value::Bool(!want))
+        }
+        "bin" | "oct" | "hex" => {
+            let [value] = exact::<1>(name, args)?;
+            let n = index_value(interp, &value)?;
+            let digits = match name {
+                "bin" => format!("0b{:b}", n.unsigned_abs()),
+                "oct" => format!("0o{:o}", n.unsigned_abs()),
+                _ => format!("0x{:x}", n.unsigned_abs()),
+            };
+            Ok(Value::str(&if n < 0 { format!("-{digits}") } else { digits }))
+        }
+        "callable" => {
+            let [value] = exact::<1>("callable", args)?;
+            Ok(Value::Bool(match &value {
+                Value::Function(_)
+                | Value::Builtin(_)
+                | Value::Method(_)
+                | Value::BuiltinMethod(_)
+                | Value::Type(_)
+                | Value::Class(_) => true,
+                Value::Instance(instance) => instance.class.lookup("__call__").is_some(),
+                _ => false,
+            }))
+        }
+        "chr" => {
+            let [value] = exact::<1>("chr", args)?;
+            let n = index_value(interp, &value)?;
+            match u32::try_from(n).ok().and_then(char::from_u32) {
+                Some(c) => Ok(Value::str(&c.to_string())),
+                None => raise("ValueError", "chr() arg not in range(0x110000)"),
+            }
+        }
+        "ord" => {
+            let [value] = exact::<1>("ord", args)?;
+            let Value::Str(s) = &value else {
+                return raise(
+                    "TypeError",
+                    format!(
+                        "ord() expected string of length 1, but {} found",
+                        value.type_name()
+                    ),
+                );
+            };
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Value::Int(c as i64)),
+                _ => raise(
+                    "TypeError",
+                    format!(
+                        "ord() expected a character, but string of length {} found",
+                        s.chars().count()
+                    ),
+                ),
+            }
+        }
+        "divmod" => {
+            let [a, b] = exact::<2>("divmod", args)?;
+            let quotient = interp.binop(super::ast::BinOp::FloorDiv, a.clone(), b.clone())?;
+            let remainder = interp.binop(super::ast::BinOp::Mod, a, b)?;
+            Ok(Value::tuple(vec![quotient, remainder]))
+        }
+        "pow" => {
+            let modulus = args.arg(2, "mod").unwrap_or(Value::None);
+            let base = args.arg(0, "base").unwrap_or(Value::None);
+            let exp = args.arg(1, "exp").unwrap_or(Value::None);
+            if modulus.is_none() {
+                return interp.binop(super::ast::BinOp::Pow, base, exp);
+            }
+            let (Value::Int(base), Value::Int(exp), Value::Int(modulus)) = (&base, &exp, &modulus)
+            else {
+                return raise(
+                    "TypeError",
+                    "pow() 3rd argument not allowed unless all arguments are integers",
+                );
+            };
+            if *modulus == 0 {
+                return raise("ValueError", "pow() 3rd argument cannot be 0");
+            }
+            if *exp < 0 {
+                return raise("ValueError", "base is not invertible for the given modulus");
+            }
+            let m = *modulus as i128;
+            let mut result: i128 = 1;
+            let mut b = (*base as i128).rem_euclid(m);
+            let mut e = *exp;
+            while e > 0 {
+                if e & 1 == 1 {
+                    result = result * b % m;
+                }
+                b = b * b % m;
+                e >>= 1;
+            }
+            let mut result = result.rem_euclid(m);
+            if m < 0 && result != 0 {
+                result += m;
+            }
+            Ok(Value::Int(result as i64))
+        }
+        "round" => {
+            let ndigits = args.arg(1, "ndigits").unwrap_or(Value::None);
+            let number = args.arg(0, "number").unwrap_or(Value::None);
+            round(interp, number, ndigits)
+        }
+        "sum" => {
+            let start = args.arg(1, "start").unwrap_or(Value::Int(0));
+            let Some(iterable) = args.pos.first().cloned() else {
+                return raise("TypeError", "sum() takes at least 1 positional argument (0 given)");
+            };
+            if let Value::Str(_) = start {
+                return raise(
+                    "TypeError",
+                    "sum() can't sum strings [use ''.join(seq) instead]",
+                );
+            }
+            sum(interp, &iterable, start)
+        }
+        "min" | "max" => min_max(interp, name, args),
+        "sorted" => {
+            let key = args.take_kw("key").unwrap_or(Value::None);
+            let reverse = args.take_kw("reverse").unwrap_or(Value::Bool(false));
+            let [iterable] = exact::<1>("sorted", args)?;
+            let mut items = interp.collect(&iterable)?;
+            let reverse = interp.truthy(&reverse)?;
+            interp.sort(&mut items, &key, reverse)?;
+            Ok(Value::list(items))
+        }
+        "reversed" => {
+            let [sequence] = exact::<1>("reversed", args)?;
+            let mut items = match &sequence {
+                Value::List(_) | Value::Tuple(_) | Value::Str(_) | Value::Range(..) | Value::Dict(_) => {
+                    interp.collect(&sequence)?
+                }
+                Value::Instance(_) => {
+                    if let Some(result) = interp.dunder(&sequence, "__reversed__", Vec::new())? {
+                        return Ok(result);
+                    }
+                    let len = interp.len(&sequence)?;
+                    let mut items = Vec::with_capacity(len);
+                    for i in 0..len {
+                        items.push(interp.get_item(&sequence, &Value::Int(i as i64))?);
+                    }
+                    items
+                }
+                other => {
+                    return raise(
+                        "TypeError",
+                        format!("'{}' object is not reversible", other.type_name()),
+                    );
+                }
+            };
+            items.reverse();
+            Ok(Value::iterator(Iter::Values(items.into_iter())))
+        }
+        "enumerate" => {
+            let start = args.arg(1, "start").unwrap_or(Value::Int(0));
+            let iterable = args.arg(0, "iterable").unwrap_or(Value::None);
+            let start = index_value(interp, &start)?;
+            let iterator = interp.iter(&iterable)?;
+            Ok(Value::iterator(Iter::Enumerate(iterator, start)))
+        }
+        "zip" => {
+            let strict = args.take_kw("strict");
+            no_keywords("zip", &args)?;
+            if let Some(strict) = strict
+                && interp.truthy(&strict)?
+            {
+                // Strict zips are checked up front
+                let mut lists = Vec::new();
+                for iterable in &args.pos {
+                    lists.push(interp.collect(iterable)?);
+                }
+                if let Some(first) = lists.first() {
+                    for (i, list) in lists.iter().enumerate().skip(1) {
+                        if list.len() != first.len() {
+                            let which = if list.len() > first.len() { "longer" } else { "shorter" };
+                            return raise(
+                                "ValueError",
+                                format!("zip() argument {} is {which} than argument 1", i + 1),
+                            );
+                        }
+                    }
+                }
+                let len = lists.first().map_or(0, Vec::len);
+                let rows: Vec<Value> = (0..len)
+                    .map(|i| Value::tuple(lists.iter().map(|l| l[i].clone()).collect()))
+                    .collect();
+                return Ok(Value::iterator(Iter::Values(rows.into_iter())));
+            }
+            let mut iterators = Vec::new();
+            for iterable in &args.pos {
+                iterators.push(interp.iter(iterable)?);
+            }
+            Ok(Value::iterator(Iter::Zip(iterators)))
+        }
+        "map" => {
+            no_keywords("map", &args)?;
+            if args.pos.len() < 2 {
+                return raise("TypeError", "map() must have at least two arguments.");
+            }
+            let function = args.pos[0].clone();
+            let mut iterators = Vec::new();
+            for iterable in &args.pos[1..] {
+                iterators.push(interp.iter(iterable)?);
+            }
+            Ok(Value::iterator(Iter::Map(function, iterators)))
+        }
+        "filter" => {
+            let [function, iterable] = exact::<2>("filter", args)?;
+            let iterator = interp.iter(&iterable)?;
+            Ok(Value::iterator(Iter::Filter(function, iterator)))
+        }
+        "iter" => match args.pos.len() {
+            1 => interp.iter(&args.pos[0]),
+            2 => {
+                // iter(callable, sentinel), collected up front
+                let (function, sentinel) = (args.pos[0].clone(), args.pos[1].clone());
+                let mut items = Vec::new();
+                loop {
+                    let value = interp.call(&function, Args::default())?;
+                    if interp.eq(&value, &sentinel)? {
+                        break;
+                    }
+                    items.push(value);
+                }
+                Ok(Value::iterator(Iter::Values(items.into_iter())))
+            }
+            n => raise("TypeError", format!("iter expected at most 2 arguments, got {n}")),
+        },
+        "next" => {
+            let iterator = args.pos.first().cloned().unwrap_or(Value::None);
+            let default = args.pos.get(1).cloned();
+            if !matches!(iterator, Value::Iterator(_) | Value::Instance(_)) {
+                return raise(
+                    "TypeError",
+                    format!("'{}' object is not an iterator", iterator.type_name()),
+                );
+            }
+            match interp.next(&iterator)? {
+                Some(value) => Ok(value),
+                None => match default {
+                    Some(default) => Ok(default),
+                    None => Err(PyErr::with_arg("StopIteration", Value::None)),
+                },
+            }
+        }
+        "isinstance" => {
+            let [value, kind] = exact::<2>("isinstance", args)?;
+            Ok(Value::Bool(isinstance(&value, &kind)?))
+        }
+        "issubclass" => {
+            let [class, kind] = exact::<2>("issubclass", args)?;
+            Ok(Value::Bool(issubclass(&class, &kind)?))
+        }
+        "getattr" => {
+            let object = args.pos.first().cloned().unwrap_or(Value::None);
+            let Some(Value::Str(name)) = args.pos.get(1).cloned() else {
+                return raise("TypeError", "attribute name must be string");
+            };
+            match interp.get_attr(&object, &name) {
+                Ok(value) => Ok(value),
+                Err(e) if args.pos.len() > 2 && interp.is_error(&e, "AttributeError") => {
+                    Ok(args.pos[2].clone())
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "hasattr" => {
+            let [object, name] = exact::<2>("hasattr", args)?;
+            let Value::Str(name) = name else {
+                return raise("TypeError", "attribute name must be string");
+            };
+            match interp.get_attr(&object, &name) {
+                Ok(_) => Ok(Value::Bool(true)),
+                Err(e) if interp.is_error(&e, "AttributeError") => Ok(Value::Bool(false)),
+                Err(e) => Err(e),
+            }
+        }
+        "setattr" => {
+            let [object, name, value] = exact::<3>("setattr", args)?;
+            let Value::Str(name) = name else {
+                return raise("TypeError", "attribute name must be string");
+            };
+            interp.set_attr(&object, &name, value)?;
+            Ok(Value::None)
+        }
+        "delattr" => {
+            let [object, name] = exact::<2>("delattr", args)?;
+            let Value::Str(name) = name else {
+                return raise("TypeError", "attribute name must be string");
+            };
+            interp.del_attr(&object, &name)?;
+            Ok(Value::None)
+        }
+        "hash" => {
+            let [value] = exact::<1>("hash", args)?;
+            Ok(Value::Int(interp.hash(&value)?))
+        }
+        "id" => {
+            let [value] = exact::<1>("id", args)?;
+            Ok(Value::Int(match value.identity() {
+                Some(id) => id as i64,
+                None => interp.hash(&value).unwrap_or(0),
+            }))
+        }
+        "format" => {
+            let value = args.pos.first().cloned().unwrap_or(Value::None);
+            let spec = match args.pos.get(1) {
+                Some(Value::Str(spec)) => spec.to_string(),
+                Some(_) => return raise("TypeError", "format() argument 2 must be str"),
+                None => String::new(),
+            };
+            Ok(Value::str(&format_value(interp, &value, &spec)?))
+        }
+        "input" => {
+            if let Some(prompt) = args.pos.first() {
+                let prompt = interp.to_str(prompt)?;
+                interp.write_out(&prompt);
+            }
+            interp.flush();
+            let mut line = String::new();
+            match std::io::stdin().lock().read_line(&mut line) {
+                Ok(0) => raise("EOFError", "EOF when reading a line"),
+                Ok(_) => {
+                    if line.ends_with('\n') {
+                        line.pop();
+                        if line.ends_with('\r') {
+                            line.pop();
+                        }
+                    }
+                    Ok(Value::str(&line))
+                }
+                Err(e) => Err(super::error::os_error(&e, "<stdin>")),
+            }
+        }
+        "open" => methods::open(interp, args),
+        "dir" => {
+            let names = match args.pos.first() {
+                Some(object) => dir(object),
+                None => Vec::new(),
+            };
+            let mut names: Vec<Rc<str>> = names;
+            names.sort();
+            names.dedup();
+            Ok(Value::list(names.into_iter().map(Value::Str).collect()))
+        }
+        "vars" => {
+            let [object] = exact::<1>("vars", args)?;
+            interp.get_attr(&object, &Rc::from("__dict__"))
+        }
+        "exit" | "quit" => {
+            let code = args.pos.first().cloned().unwrap_or(Value::None);
+            Err(PyErr::with_arg("SystemExit", code))
+        }
+        "__import__" => {
+            let Some(Value::Str(module)) = args.pos.first() else {
+                return raise("TypeError", "__import__() argument 1 must be str");
+            };
+            let module = module.clone();
+            interp.import(&module)?;
+            let top = module.split('.').next().unwrap_or("");
+            Ok(interp.modules[top].clone())
+        }
+        _ => raise("NameError", format!("name '{name}' is not defined")),
+    }
+}
+
+/// Exactly `N` positional arguments and no keywords.
+pub fn exact<const N: usize>(name: &str, args: Args) -> PyResult<[Value; N]> {
+    no_keywords(name, &args)?;
+    let count = args.pos.len();
+    match <[Value; N]>::try_from(args.pos) {
+        Ok(values) => Ok(values),
+        Err(_) if N == 0 => raise(
+            "TypeError",
+            format!("{name}() takes no arguments ({count} given)"),
+        ),
+        Err(_) if N == 1 => raise(
+            "TypeError",
+            format!("{name}() takes exactly one argument ({count} given)"),
+        ),
+        Err(_) => raise(
+            "TypeError",
+            format!("{name} expected {N} arguments, got {count}"),
+        ),
+    }
+}
+
+pub fn no_keywords(name: &str, args: &Args) -> PyResult<()> {
+    match args.kw.first() {
+        Some((key, _)) => raise(
+            "TypeError",
+            format!("{name}() got an unexpected keyword argument '{key}'"),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// An integer argument, accepting bools and `__index__`.
+pub fn index_value(interp: &mut Interp, value: &Value) -> PyResult<i64> {
+    match value {
+        Value::Int(n) => Ok(*n),
+        Value::Bool(b) => Ok(*b as i64),
+        Value::Instance(_) => match interp.dunder(value, "__index__", Vec::new())? {
+            Some(Value::Int(n)) => Ok(n),
+            _ => raise(
+                "TypeError",
+                format!(
+                    "'{}' object cannot be interpreted as an integer",
+                    value.type_name()
+                ),
+            ),
+        },
+        other => raise(
+            "TypeError",
+            format!(
+                "'{}' object cannot be interpreted as an integer",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+pub fn isinstance(value: &Value, kind: &Value) -> PyResult<bool> {
+    match kind {
+        Value::Type(kind) => Ok(match value {
+            Value::Instance(_) => *kind == Type::Object,
+            Value::Class(_) => *kind == Type::Type || *kind == Type::Object,
+            other => other.type_of().is_subtype(*kind),
+        }),
+        Value::Class(class) => Ok(match value {
+            Value::Instance(instance) => instance.class.is_subclass(class),
+            _ => false,
+        }),
+        Value::Tuple(kinds) => {
+            for kind in kinds.iter() {
+                if isinstance(value, kind)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        other => raise(
+            "TypeError",
+            format!(
+                "isinstance() arg 2 must be a type, a tuple of types, or a union, not {}",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+fn issubclass(class: &Value, kind: &Value) -> PyResult<bool> {
+    match (class, kind) {
+        (_, Value::Tuple(kinds)) => {
+            for kind in kinds.iter() {
+                if issubclass(class, kind)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        (Value::Type(a), Value::Type(b)) => Ok(a.is_subtype(*b)),
+        (Value::Class(a), Value::Class(b)) => Ok(a.is_subclass(b)),
+        (Value::Class(_), Value::Type(Type::Object)) => Ok(true),
+        (Value::Class(_) | Value::Type(_), _) => Ok(false),
+        _ => raise("TypeError", "issubclass() arg 1 must be a class"),
+    }
+}
+
+fn round(interp: &mut Interp, number: Value, ndigits: Value) -> PyResult<Value> {
+    match (&number, &ndigits) {
+        (Value::Int(_) | Value::Bool(_), Value::None) => Ok(Value::Int(index_value(interp, &number)?)),
+        (Value::Int(n), Value::Int(digits)) => {
+            if *digits >= 0 {
+                return Ok(Value::Int(*n));
+            }
+            let Some(scale) = 10i64.checked_pow((-digits) as u32) else {
+                return Ok(Value::Int(0));
+            };
+            let low = n.div_euclid(scale) * scale;
+            let rest = n - low;
+            let rounded = match (rest * 2).cmp(&scale) {
+                std::cmp::Ordering::Less => low,
+                std::cmp::Ordering::Greater => low + scale,
+                std::cmp::Ordering::Equal if (low / scale) % 2 == 0 => low,
+                std::cmp::Ordering::Equal => low + scale,
+            };
+            Ok(Value::Int(rounded))
+        }
+        (Value::Float(x), Value::None) => {
+            if x.is_nan() {
+                return raise("ValueError", "cannot convert float NaN to integer");
+            }
+            if x.is_infinite() {
+                return raise("OverflowError", "cannot convert float infinity to integer");
+            }
+            Ok(Value::Int(x.round_ties_even() as i64))
+        }
+        (Value::Float(x), Value::Int(digits)) => {
+            if !x.is_finite() {
+                return Ok(number.clone());
+            }
+            if *digits >= 0 {
+                // Formatting rounds the exact binary value, as Python does
+                let digits = (*digits).min(300) as usize;
+                let text = format!("{x:.digits$}");
+                return Ok(Value::Float(text.parse().unwrap_or(*x)));
+            }
+            let scale = 10f64.powi((-digits) as i32);
+            Ok(Value::Float((x / scale).round_ties_even() * scale))
+        }
+        (Value::Instance(_), _) => {
+            let args = if ndigits.is_none() { Vec::new() } else { vec![ndigits.clone()] };
+            match interp.dunder(&number, "__round__", args)? {
+                Some(result) => Ok(result),
+                None => raise(
+                    "TypeError",
+                    format!(
+                        "type {} doesn't define __round__ method",
+                        number.type_name()
+                    ),
+                ),
+            }
+        }
+        (_, Value::None | Value::Int(_)) => raise(
+            "TypeError",
+            format!(
+                "type {} doesn't define __round__ method",
+                number.type_name()
+            ),
+        ),
+        _ => raise(
+            "TypeError",
+            format!(
+                "'{}' object cannot be interpreted as an integer",
+                ndigits.type_name()
+            ),
+        ),
+    }
+}
+
+fn sum(interp: &mut Interp, iterable: &Value, start: Value) -> PyResult<Value> {
+    let iterator = interp.iter(iterable)?;
+    let mut total = start;
+    // Floats are added with Neumaier compensation, like Python 3.12
+    let mut compensation = 0.0;
+    while let Some(item) = interp.next(&iterator)? {
+        let addend = match item {
+            Value::Float(x) => Some(x),
+            Value::Int(n) if matches!(total, Value::Float(_)) => Some(n as f64),
+            _ => None,
+        };
+        match (&total, addend) {
+            (Value::Float(t), Some(x)) => {
+                let t = *t;
+                let new = t + x;
+                if t.abs() >= x.abs() {
+                    compensation += (t - new) + x;
+                } else {
+                    compensation += (x - new) + t;
+                }
+                total = Value::Float(new);
+            }
+            _ => {
+                if let Value::Float(t) = total {
+                    total = Value::Float(t + compensation);
+                    compensation = 0.0;
+                }
+                total = interp.binop(super::ast::BinOp::Add, total, item)?;
+            }
+        }
+    }
+    if let Value::Float(t) = total
+        && compensation != 0.0
+        && compensation.is_finite()
+    {
+        total = Value::Float(t + compensation);
+    }
+    Ok(total)
+}
+
+fn min_max(interp: &mut Interp, name: &str, mut args: Args) -> PyResult<Value> {
+    let key = args.take_kw("key").unwrap_or(Value::None);
+    let default = args.take_kw("default");
+    no_keywords(name, &args)?;
+    let items = match args.pos.len() {
+        0 => {
+            return raise(
+                "TypeError",
+                format!("{name} expected at least 1 argument, got 0"),
+            );
+        }
+        1 => interp.collect(&args.pos[0])?,
+        _ => {
+            if default.is_some() {
+                return raise(
+                    "TypeError",
+                    format!(
+                        "Cannot specify a default for {name}() with multiple positional arguments"
+                    ),
+                );
+            }
+            args.pos
+        }
+    };
+    let mut best: Option<(Value, Value)> = None;
+    for item in items {
+        let score = if key.is_none() {
+            item.clone()
+        } else {
+            interp.call(&key, Args::new(vec![item.clone()]))?
+        };
+        let better = match &best {
+            None => true,
+            Some((_, best_score)) if name == "min" => interp.lt(&score, best_score)?,
+            Some((_, best_score)) => interp.lt(best_score, &score)?,
+        };
+        if better {
+            best = Some((item, score));
+        }
+    }
+    match (best, default) {
+        (Some((item, _)), _) => Ok(item),
+        (None, Some(default)) => Ok(default),
+        (None, None) => raise("ValueError", format!("{name}() iterable argument is empty")),
+    }
+}
+
+/// The attribute names `dir()` lists.
+fn dir(object: &Value) -> Vec<Rc<str>> {
+    let mut names = Vec::new();
+    match object {
+        Value::Instance(instance) => {
+            names.extend(instance.attrs.borrow().keys().cloned());
+            names.extend(instance.class.attrs.borrow().keys().cloned());
+            for class in &instance.class.mro {
+                names.extend(class.attrs.borrow().keys().cloned());
+            }
+        }
+        Value::Class(class) => {
+            names.extend(class.attrs.borrow().keys().cloned());
+            for class in &class.mro {
+                names.extend(class.attrs.borrow().keys().cloned());
+            }
+        }
+        Value::Module(module) => names.extend(module.scope.vars.borrow().keys().cloned()),
+        other => {
+            let kind = match other {
+                Value::Type(kind) => *kind,
+                other => other.type_of(),
+            };
+            names.extend(methods::names(kind).iter().map(|name| Rc::from(*name)));
+        }
+    }
+    names
+}
+
+/// Call a builtin type, e.g. `int("3")` or `dict(a=1)`.
+pub fn construct(interp: &mut Interp, kind: Type, mut args: Args) -> PyResult<Value> {
+    match kind {
+        Type::Object => {
+            no_keywords("object", &args)?;
+            if !args.pos.is_empty() {
+                return raise("TypeError", "object() takes no arguments");
+            }
+            let class = interp.object_class.clone();
+            Ok(Value::Instance(Rc::new(Instance {
+                class,
+                attrs: Default::default(),
+            })))
+        }
+        Type::NoneType => Ok(Value::None),
+        Type::Bool => match args.pos.first() {
+            Some(value) => {
+                let value = value.clone();
+                Ok(Value::Bool(interp.truthy(&value)?))
+            }
+            None => Ok(Value::Bool(false)),
+        },
+        Type::Int => {
+            let base = args.arg(1, "base");
+            let value = args.pos.first().cloned().unwrap_or(Value::Int(0));
+            int(interp, &value, base)
+        }
+        Type::Float => {
+            let value = args.pos.first().cloned().unwrap_or(Value::Float(0.0));
+            float(interp, &value)
+        }
+        Type::Str => match args.pos.first() {
+            Some(value) => {
+                let value = value.clone();
+                Ok(Value::str(&interp.to_str(&value)?))
+            }
+            None => Ok(Value::str("")),
+        },
+        Type::List => match args.pos.first() {
+            Some(value) => {
+                let value = value.clone();
+                Ok(Value::list(interp.collect(&value)?))
+            }
+            None => Ok(Value::list(Vec::new())),
+        },
+        Type::Tuple => match args.pos.first() {
+            Some(Value::Tuple(items)) => Ok(Value::Tuple(items.clone())),
+            Some(value) => {
+                let value = value.clone();
+                Ok(Value::tuple(interp.collect(&value)?))
+            }
+            None => Ok(Value::tuple(Vec::new())),
+        },
+        Type::Dict | Type::OrderedDict | Type::Counter | Type::DefaultDict => {
+            let dict_kind = match kind {
+                Type::OrderedDict => DictKind::Ordered,
+                Type::Counter => DictKind::Counter,
+                Type::DefaultDict => {
+                    if args.pos.is_empty() {
+                        DictKind::Default(Value::None)
+                    } else {
+                        DictKind::Default(args.pos.remove(0))
+                    }
+                }
+                _ => DictKind::Dict,
+            };
+            let dict = Value::dict(Dict {
+                map: Default::default(),
+                kind: dict_kind,
+            });
+            methods::call(interp, &dict, "update", args)?;
+            Ok(dict)
+        }
+        Type::Set | Type::FrozenSet => {
+            let mut set = Set {
+                map: Default::default(),
+                frozen: kind == Type::FrozenSet,
+            };
+            if let Some(value) = args.pos.first() {
+                let value = value.clone();
+                for item in interp.collect(&value)? {
+                    let key = interp.key(&item)?;
+                    set.map.insert(key, item);
+                }
+            }
+            Ok(Value::Set(shared(set)))
+        }
+        Type::Range => {
+            no_keywords("range", &args)?;
+            let mut bounds = Vec::with_capacity(3);
+            for value in &args.pos {
+                bounds.push(index_value(interp, value)?);
+            }
+            let (start, stop, step) = match bounds[..] {
+                [stop] => (0, stop, 1),
+                [start, stop] => (start, stop, 1),
+                [start, stop, step] => (start, stop, step),
+                _ => {
+                    return raise(
+                        "TypeError",
+                        format!("range expected at most 3 arguments, got {}", bounds.len()),
+                    );
+                }
+            };
+            if step == 0 {
+                return raise("ValueError", "range() arg 3 must not be zero");
+            }
+            Ok(Value::Range(start, stop, step))
+        }
+        Type::Slice => {
+            let parts = match args.pos.len() {
+                1 => [Value::None, args.pos[0].clone(), Value::None],
+                2 => [args.pos[0].clone(), args.pos[1].clone(), Value::None],
+                3 => [args.pos[0].clone(), args.pos[1].clone(), args.pos[2].clone()],
+                n => {
+                    return raise(
+                        "TypeError",
+                        format!("slice expected at most 3 arguments, got {n}"),
+                    );
+                }
+            };
+            Ok(Value::Slice(Rc::new(parts)))
+        }
+        Type::Type => match args.pos.len() {
+            1 => Ok(interp.type_of(&args.pos[0])),
+            3 => {
+                let Value::Str(name) = args.pos[0].clone() else {
+                    return raise("TypeError", "type.__new__() argument 1 must be str");
+                };
+                let mut bases = Vec::new();
+                for base in interp.collect(&args.pos[1])? {
+                    match base {
+                        Value::Class(class) => bases.push(class),
+                        Value::Type(Type::Object) => {}
+                        _ => return raise("TypeError", "type() bases must be classes"),
+                    }
+                }
+                let mut attrs = IndexMap::new();
+                for (key, value) in interp.mapping_items(&args.pos[2])? {
+                    if let Value::Str(key) = key {
+                        attrs.insert(key, value);
+                    }
+                }
+                interp.new_class(name, bases, attrs, Rc::from("__main__"))
+            }
+            _ => raise("TypeError", "type() takes 1 or 3 arguments"),
+        },
+        Type::Property => {
+            let fget = args.arg(0, "fget").unwrap_or(Value::None);
+            let fset = args.arg(1, "fset").unwrap_or(Value::None);
+            let fdel = args.arg(2, "fdel").unwrap_or(Value::None);
+            Ok(Value::Property(Rc::new(Property { fget, fset, fdel })))
+        }
+        Type::StaticMethod | Type::ClassMethod => {
+            let [function] = exact::<1>(kind.name(), args)?;
+            Ok(if kind == Type::StaticMethod {
+                Value::StaticMethod(Rc::new(function))
+            } else {
+                Value::ClassMethod(Rc::new(function))
+            })
+        }
+        Type::Super => match &args.pos[..] {
+            [Value::Class(class), object] => Ok(Value::Super(Rc::new((class.clone(), object.clone())))),
+            _ => raise("RuntimeError", "super(): no arguments"),
+        },
+        other => raise(
+            "TypeError",
+            format!("cannot create '{}' instances", other.name()),
+        ),
+    }
+}
+
+/// `int(value[, base])`.
+pub fn int(interp: &mut Interp, value: &Value, base: Option<Value>) -> PyResult<Value> {
+    if let Some(base) = base {
+        let Value::Str(text) = value else {
+            return raise("TypeError", "int() can't convert non-string with explicit base");
+        };
+        let base = index_value(interp, &base)?;
+        if base != 0 && !(2..=36).contains(&base) {
+            return raise("ValueError", "int() base must be >= 2 and <= 36, or 0");
+        }
+        return parse_int(text, base as u32);
+    }
+    match value {
+        Value::Int(n) => Ok(Value::Int(*n)),
+        Value::Bool(b) => Ok(Value::Int(*b as i64)),
+        Value::Float(x) => {
+            if x.is_nan() {
+                return raise("ValueError", "cannot convert float NaN to integer");
+            }
+            if x.is_infinite() {
+                return raise("OverflowError", "cannot convert float infinity to integer");
+            }
+            let truncated = x.trunc();
+            if !(i64::MIN as f64..i64::MAX as f64).contains(&truncated) {
+                return int_too_large();
+            }
+            Ok(Value::Int(truncated as i64))
+        }
+        Value::Str(text) => parse_int(text, 10),
+        Value::Instance(_) => {
+            for name in ["__int__", "__index__", "__trunc__"] {
+                if let Some(result) = interp.dunder(value, name, Vec::new())? {
+                    return Ok(result);
+                }
+            }
+            raise(
+                "TypeError",
+                format!(
+                    "int() argument must be a string, a bytes-like object or a real number, not '{}'",
+                    value.type_name()
+                ),
+            )
+        }
+        other => raise(
+            "TypeError",
+            format!(
+                "int() argument must be a string, a bytes-like object or a real number, not '{}'",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+fn parse_int(text: &str, base: u32) -> PyResult<Value> {
+    let invalid = || {
+        raise(
+            "ValueError",
+            format!(
+                "invalid literal for int() with base {base}: {}",
+                str_repr(text)
+            ),
+        )
+    };
+    let trimmed = text.trim();
+    let (negative, digits) = match trimmed.as_bytes().first() {
+        Some(b'-') => (true, &trimmed[1..]),
+        Some(b'+') => (false, &trimmed[1..]),
+        _ => (false, trimmed),
+    };
+    let lower = digits.to_ascii_lowercase();
+    let (base, digits) = match (base, lower.get(..2)) {
+        (0 | 16, Some("0x")) => (16, &digits[2..]),
+        (0 | 8, Some("0o")) => (8, &digits[2..]),
+        (0 | 2, Some("0b")) => (2, &digits[2..]),
+        (0, _) => {
+            if digits.len() > 1 && digits.starts_with('0') && digits.bytes().any(|b| b != b'0' && b != b'_') {
+                return invalid();
+            }
+            (10, digits)
+        }
+        (base, _) => (base, digits),
+    };
+    if digits.is_empty()
+        || digits.starts_with('_')
+        || digits.ends_with('_')
+        || digits.contains("__")
+    {
+        return invalid();
+    }
+    let cleaned: String = digits.chars().filter(|c| *c != '_').collect();
+    let signed = if negative { format!("-{cleaned}") } else { cleaned };
+    match i64::from_str_radix(&signed, base) {
+        Ok(n) => Ok(Value::Int(n)),
+        Err(e) => match e.kind() {
+            std::num::IntErrorKind::PosOverflow | std::num::IntErrorKind::NegOverflow => {
+                int_too_large()
+            }
+            _ => invalid(),
+        },
+    }
+}
+
+/// `float(value)`.
+pub fn float(interp: &mut Interp, value: &Value) -> PyResult<Value> {
+    match value {
+        Value::Float(x) => Ok(Value::Float(*x)),
+        Value::Int(n) => Ok(Value::Float(*n as f64)),
+        Value::Bool(b) => Ok(Value::Float(*b as i64 as f64)),
+        Value::Str(text) => {
+            let trimmed = text.trim();
+            let lower = trimmed.to_ascii_lowercase();
+            let unsigned = lower.trim_start_matches(['+', '-']);
+            let parsed = match unsigned {
+                "inf" | "infinity" | "nan" => lower.replace("infinity", "inf").parse::<f64>().ok(),
+                _ if unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+                    && !trimmed.contains("__")
+                    && !trimmed.ends_with('_') =>
+                {
+                    lower.replace('_', "").parse::<f64>().ok()
+                }
+                _ => None,
+            };
+            match parsed {
+                Some(x) => Ok(Value::Float(x)),
+                None => raise(
+                    "ValueError",
+                    format!("could not convert string to float: {}", str_repr(text)),
+                ),
+            }
+        }
+        Value::Instance(_) => {
+            for name in ["__float__", "__index__"] {
+                if let Some(result) = interp.dunder(value, name, Vec::new())? {
+                    return float(interp, &result);
+                }
+            }
+            raise(
+                "TypeError",
+                format!(
+                    "float() argument must be a string or a real number, not '{}'",
+                    value.type_name()
+                ),
+            )
+        }
+        other => raise(
+            "TypeError",
+            format!(
+                "float() argument must be a string or a real number, not '{}'",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+/// Set the `args`, `errno`, `strerror` and `filename` of an `OSError`, which
+/// takes them as `OSError(errno, strerror[, filename])`.
+pub fn init_os_error(instance: &Instance, args: &[Value]) {
+    let mut attrs = instance.attrs.borrow_mut();
+    let (errno, strerror, filename) = match args {
+        [errno, strerror, rest @ ..] if rest.len() <= 1 => (
+            errno.clone(),
+            strerror.clone(),
+            rest.first().cloned().unwrap_or(Value::None),
+        ),
+        _ => (Value::None, Value::None, Value::None),
+    };
+    let shown = if args.len() == 3 { &args[..2] } else { args };
+    attrs.insert(Rc::from("args"), Value::tuple(shown.to_vec()));
+    attrs.insert(Rc::from("errno"), errno);
+    attrs.insert(Rc::from("strerror"), strerror);
+    attrs.insert(Rc::from("filename"), filename);
+}
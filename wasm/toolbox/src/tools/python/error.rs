@@ -0,0 +1,85 @@
+//! Python exceptions as they propagate through the interpreter.
+
+use std::rc::Rc;
+
+use super::value::Value;
+
+pub type PyResult<T> = Result<T, Box<PyErr>>;
+
+/// An exception on its way up the stack.
+#[derive(Clone)]
+pub struct PyErr {
+    pub exc: Exc,
+    /// Where it passed through, innermost first.
+    pub traceback: Vec<Frame>,
+    /// The current frame's line has been recorded.
+    pub located: bool,
+}
+
+#[derive(Clone)]
+pub enum Exc {
+    /// Raised by the interpreter: the builtin exception's name and its
+    /// argument, turned into an instance when Python code looks at it.
+    Pending { kind: &'static str, arg: Value },
+    /// An exception instance.
+    Raised(Value),
+}
+
+#[derive(Clone)]
+pub struct Frame {
+    pub file: Rc<str>,
+    pub line: usize,
+    pub name: Rc<str>,
+}
+
+impl PyErr {
+    pub fn new(kind: &'static str, msg: impl Into<String>) -> Box<PyErr> {
+        Self::with_arg(kind, Value::str(&msg.into()))
+    }
+
+    pub fn with_arg(kind: &'static str, arg: Value) -> Box<PyErr> {
+        Box::new(PyErr {
+            exc: Exc::Pending { kind, arg },
+            traceback: Vec::new(),
+            located: false,
+        })
+    }
+
+    pub fn raised(value: Value) -> Box<PyErr> {
+        Box::new(PyErr {
+            exc: Exc::Raised(value),
+            traceback: Vec::new(),
+            located: false,
+        })
+    }
+}
+
+/// `Err` of a builtin exception with a message.
+pub fn raise<T>(kind: &'static str, msg: impl Into<String>) -> PyResult<T> {
+    Err(PyErr::new(kind, msg))
+}
+
+/// `Err` for an int too large for the interpreter's 64-bit ints. Python's
+/// ints are unbounded, so this is a missing feature, not an overflow.
+pub fn int_too_large<T>() -> PyResult<T> {
+    raise(
+        "NotImplementedError",
+        "integers beyond 64 bits are not supported",
+    )
+}
+
+/// The exception an I/O error becomes, e.g. `FileNotFoundError`.
+pub fn os_error(err: &std::io::Error, path: &str) -> Box<PyErr> {
+    use std::io::ErrorKind;
+    let (kind, errno, text) = match err.kind() {
+        ErrorKind::NotFound => ("FileNotFoundError", 2, "No such file or directory"),
+        ErrorKind::PermissionDenied => ("PermissionError", 13, "Permission denied"),
+        ErrorKind::AlreadyExists => ("FileExistsError", 17, "File exists"),
+        ErrorKind::IsADirectory => ("IsADirectoryError", 21, "Is a directory"),
+        ErrorKind::NotADirectory => ("NotADirectoryError", 20, "Not a directory"),
+        ErrorKind::DirectoryNotEmpty => ("OSError", 39, "Directory not empty"),
+        _ => return PyErr::new("OSError", format!("{err}: '{path}'")),
+    };
+    let arg = Value::tuple(vec![Value::Int(errno), Value::str(text), Value::str(path)]);
+    PyErr::with_arg(kind, arg)
+}
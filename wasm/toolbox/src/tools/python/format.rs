@@ -0,0 +1,753 @@
+//! Number and string formatting: `repr` of floats and strings, the format
+//! specification mini-language, `%` formatting and `str.format`.
+
+use std::rc::Rc;
+
+use super::error::{PyResult, raise};
+use super::interp::Interp;
+use super::value::{Args, Value};
+
+/// `repr()` of a float: the shortest string that round-trips, in
+/// positional notation for exponents from -4 to 15.
+pub fn float_repr(x: f64) -> String {
+    if x.is_nan() {
+        return "nan".to_string();
+    }
+    if x.is_infinite() {
+        return if x > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    if x == 0.0 {
+        return if x.is_sign_negative() { "-0.0" } else { "0.0" }.to_string();
+    }
+    let scientific = format!("{:e}", x.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let sign = if x < 0.0 { "-" } else { "" };
+    if (-4..16).contains(&exponent) {
+        if exponent >= 0 {
+            let point = exponent as usize + 1;
+            let (int, frac) = if digits.len() > point {
+                (digits[..point].to_string(), digits[point..].to_string())
+            } else {
+                (format!("{digits:0<point$}"), "0".to_string())
+            };
+            format!("{sign}{int}.{frac}")
+        } else {
+            let zeros = "0".repeat((-exponent - 1) as usize);
+            format!("{sign}0.{zeros}{digits}")
+        }
+    } else {
+        let mantissa = match digits.len() {
+            1 => digits,
+            _ => format!("{}.{}", &digits[..1], &digits[1..]),
+        };
+        let exp_sign = if exponent < 0 { '-' } else { '+' };
+        format!("{sign}{mantissa}e{exp_sign}{:02}", exponent.abs())
+    }
+}
+
+/// `repr()` of a string: quoted, with unprintable characters escaped.
+pub fn str_repr(s: &str) -> String {
+    let quote = if s.contains('\'') && !s.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push(quote);
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if (c as u32) < 0x20 || (0x7f..0xa0).contains(&(c as u32)) => {
+                out.push_str(&format!("\\x{:02x}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push(quote);
+    out
+}
+
+/// `ascii()`: a `repr` with non-ASCII characters escaped.
+pub fn ascii_escape(repr: &str) -> String {
+    let mut out = String::with_capacity(repr.len());
+    for c in repr.chars() {
+        match c as u32 {
+            0..=0x7f => out.push(c),
+            n @ 0x80..=0xff => out.push_str(&format!("\\x{n:02x}")),
+            n @ 0x100..=0xffff => out.push_str(&format!("\\u{n:04x}")),
+            n => out.push_str(&format!("\\U{n:08x}")),
+        }
+    }
+    out
+}
+
+/// A parsed format specification, `[[fill]align][sign][#][0][width][,][.precision][type]`.
+#[derive(Debug, Clone, Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    sign: Option<char>,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    grouping: Option<char>,
+    precision: Option<usize>,
+    kind: Option<char>,
+}
+
+fn parse_spec(spec: &str) -> PyResult<Spec> {
+    let chars: Vec<char> = spec.chars().collect();
+    let mut parsed = Spec::default();
+    let mut i = 0;
+    if chars.len() >= 2 && "<>=^".contains(chars[1]) {
+        parsed.fill = Some(chars[0]);
+        parsed.align = Some(chars[1]);
+        i = 2;
+    } else if !chars.is_empty() && "<>=^".contains(chars[0]) {
+        parsed.align = Some(chars[0]);
+        i = 1;
+    }
+    if i < chars.len() && "+- ".contains(chars[i]) {
+        parsed.sign = Some(chars[i]);
+        i += 1;
+    }
+    if i < chars.len() && chars[i] == '#' {
+        parsed.alternate = true;
+        i += 1;
+    }
+    if i < chars.len() && chars[i] == '0' {
+        parsed.zero = true;
+        i += 1;
+    }
+    let number = |i: &mut usize| {
+        let start = *i;
+        while *i < chars.len() && chars[*i].is_ascii_digit() {
+            *i += 1;
+        }
+        chars[start..*i].iter().collect::<String>().parse::<usize>().ok()
+    };
+    parsed.width = number(&mut i).unwrap_or(0);
+    if i < chars.len() && (chars[i] == ',' || chars[i] == '_') {
+        parsed.grouping = Some(chars[i]);
+        i += 1;
+    }
+    if i < chars.len() && chars[i] == '.' {
+        i += 1;
+        match number(&mut i) {
+            Some(p) => parsed.precision = Some(p),
+            None => return raise("ValueError", "Format specifier missing precision"),
+        }
+    }
+    if i < chars.len() {
+        parsed.kind = Some(chars[i]);
+        i += 1;
+    }
+    if i != chars.len() {
+        return raise("ValueError", "Invalid format specifier");
+    }
+    Ok(parsed)
+}
+
+/// Pad `body` to the spec's width. Numbers align right by default and
+/// `=` puts the padding between the sign and the digits.
+fn pad(sign: &str, body: &str, spec: &Spec, numeric: bool) -> String {
+    let (fill, align) = match (spec.fill, spec.align) {
+        (fill, Some(align)) => (fill.unwrap_or(' '), align),
+        (None, None) if spec.zero && numeric => ('0', '='),
+        (fill, None) => (fill.unwrap_or(' '), if numeric { '>' } else { '<' }),
+    };
+    let len = sign.chars().count() + body.chars().count();
+    if len >= spec.width {
+        return format!("{sign}{body}");
+    }
+    let padding = spec.width - len;
+    let fill_str = |n: usize| fill.to_string().repeat(n);
+    match align {
+        '<' => format!("{sign}{body}{}", fill_str(padding)),
+        '^' => format!(
+            "{}{sign}{body}{}",
+            fill_str(padding / 2),
+            fill_str(padding - padding / 2)
+        ),
+        '=' => format!("{sign}{}{body}", fill_str(padding)),
+        _ => format!("{}{sign}{body}", fill_str(padding)),
+    }
+}
+
+fn group_digits(digits: &str, separator: char, every: usize) -> String {
+    let chars: Vec<char> = digits.chars().collect();
+    let mut out = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if i > 0 && (chars.len() - i).is_multiple_of(every) {
+            out.push(separator);
+        }
+        out.push(*c);
+    }
+    out
+}
+
+fn sign_of(negative: bool, spec: &Spec) -> &'static str {
+    match (negative, spec.sign) {
+        (true, _) => "-",
+        (false, Some('+')) => "+",
+        (false, Some(' ')) => " ",
+        _ => "",
+    }
+}
+
+fn format_int(n: i64, spec: &Spec) -> PyResult<String> {
+    let magnitude = n.unsigned_abs();
+    let (digits, prefix, every) = match spec.kind {
+        None | Some('d') | Some('n') => (magnitude.to_string(), "", 3),
+        Some('b') => (format!("{magnitude:b}"), "0b", 4),
+        Some('o') => (format!("{magnitude:o}"), "0o", 4),
+        Some('x') => (format!("{magnitude:x}"), "0x", 4),
+        Some('X') => (format!("{magnitude:X}"), "0X", 4),
+        Some('c') => {
+            let c = u32::try_from(n)
+                .ok()
+                .and_then(char::from_u32)
+                .map(String::from);
+            return match c {
+                Some(c) => Ok(pad("", &c, spec, false)),
+                None => raise("OverflowError", "%c arg not in range(0x110000)"),
+            };
+        }
+        Some('e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%') => return format_float(n as f64, spec),
+        Some(other) => {
+            return raise(
+                "ValueError",
+                format!("Unknown format code '{other}' for object of type 'int'"),
+            );
+        }
+    };
+    let digits = match spec.grouping {
+        Some(separator) => group_digits(&digits, separator, every),
+        None => digits,
+    };
+    let prefix = if spec.alternate { prefix } else { "" };
+    let sign = format!("{}{prefix}", sign_of(n < 0, spec));
+    Ok(pad(&sign, &digits, spec, true))
+}
+
+fn strip_zeros(body: &str) -> String {
+    if !body.contains('.') {
+        return body.to_string();
+    }
+    body.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn exponent_form(x: f64, precision: usize, upper: bool) -> String {
+    let text = format!("{x:.precision$e}");
+    let (mantissa, exponent) = text.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let e = if upper { 'E' } else { 'e' };
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{mantissa}{e}{sign}{:02}", exponent.abs())
+}
+
+/// `g` formatting; `repr_style` is the no-type form, which keeps a `.0`.
+fn general_form(x: f64, precision: usize, alternate: bool, upper: bool, repr_style: bool) -> String {
+    let precision = precision.max(1);
+    let exponent = if x == 0.0 {
+        0
+    } else {
+        let text = format!("{:.*e}", precision - 1, x);
+        text.split_once('e').unwrap().1.parse::<i32>().unwrap()
+    };
+    if (-4..precision as i32).contains(&exponent) {
+        let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+        let body = format!("{x:.decimals$}");
+        let mut body = if alternate { body } else { strip_zeros(&body) };
+        if repr_style && !body.contains('.') {
+            body.push_str(".0");
+        }
+        body
+    } else {
+        let body = exponent_form(x, precision - 1, upper);
+        if alternate {
+            return body;
+        }
+        let (mantissa, exponent) = body.split_once(['e', 'E']).unwrap();
+        let e = if upper { 'E' } else { 'e' };
+        format!("{}{e}{exponent}", strip_zeros(mantissa))
+    }
+}
+
+fn format_float(x: f64, spec: &Spec) -> PyResult<String> {
+    let negative = x.is_sign_negative() && !x.is_nan();
+    let magnitude = x.abs();
+    let upper = matches!(spec.kind, Some('F' | 'E' | 'G'));
+    let mut body = if !magnitude.is_finite() {
+        let text = if magnitude.is_nan() { "nan" } else { "inf" };
+        let text = if upper { text.to_uppercase() } else { text.to_string() };
+        if spec.kind == Some('%') {
+            format!("{text}%")
+        } else {
+            text
+        }
+    } else {
+        match spec.kind {
+            Some('f' | 'F') => format!("{magnitude:.*}", spec.precision.unwrap_or(6)),
+            Some('e' | 'E') => exponent_form(magnitude, spec.precision.unwrap_or(6), upper),
+            Some('g' | 'G') => general_form(
+                magnitude,
+                spec.precision.unwrap_or(6),
+                spec.alternate,
+                upper,
+                false,
+            ),
+            Some('%') => format!("{:.*}%", spec.precision.unwrap_or(6), magnitude * 100.0),
+            None => match spec.precision {
+                Some(p) => general_form(magnitude, p, spec.alternate, false, true),
+                None => float_repr(magnitude),
+            },
+            Some(other) => {
+                return raise(
+                    "ValueError",
+                    format!("Unknown format code '{other}' for object of type 'float'"),
+                );
+            }
+        }
+    };
+    if let Some(separator) = spec.grouping {
+        let split = body.find(|c: char| !c.is_ascii_digit()).unwrap_or(body.len());
+        body = format!(
+            "{}{}",
+            group_digits(&body[..split], separator, 3),
+            &body[split..]
+        );
+    }
+    Ok(pad(sign_of(negative, spec), &body, spec, true))
+}
+
+fn format_str(s: &str, spec: &Spec) -> PyResult<String> {
+    if let Some(kind) = spec.kind
+        && kind != 's'
+    {
+        return raise(
+            "ValueError",
+            format!("Unknown format code '{kind}' for object of type 'str'"),
+        );
+    }
+    if spec.align == Some('=') {
+        return raise(
+            "ValueError",
+            "'=' alignment not allowed in string format specifier",
+        );
+    }
+    let body: String = match spec.precision {
+        Some(p) => s.chars().take(p).collect(),
+        None => s.to_string(),
+    };
+    Ok(pad("", &body, spec, false))
+}
+
+/// `format(value, spec)`.
+pub fn format_value(interp: &mut Interp, value: &Value, spec: &str) -> PyResult<String> {
+    match value {
+        Value::Str(s) if spec.is_empty() => Ok(s.to_string()),
+        Value::Str(s) => format_str(s, &parse_spec(spec)?),
+        Value::Int(n) => format_int(*n, &parse_spec(spec)?),
+        Value::Float(x) => format_float(*x, &parse_spec(spec)?),
+        Value::Bool(b) => {
+            let parsed = parse_spec(spec)?;
+            if parsed.kind.is_none() {
+                format_str(if *b { "True" } else { "False" }, &parsed)
+            } else {
+                format_int(*b as i64, &parsed)
+            }
+        }
+        Value::Instance(instance) => {
+            if let Some(method) = instance.class.lookup("__format__") {
+                let method = interp.bind(method, value);
+                let result = interp.call(&method, Args::new(vec![Value::str(spec)]))?;
+                return match result {
+                    Value::Str(s) => Ok(s.to_string()),
+                    other => raise(
+                        "TypeError",
+                        format!("__format__ must return a str, not {}", other.type_name()),
+                    ),
+                };
+            }
+            let text = interp.to_str(value)?;
+            if spec.is_empty() {
+                Ok(text)
+            } else {
+                format_str(&text, &parse_spec(spec)?)
+            }
+        }
+        other => {
+            let text = interp.to_str(other)?;
+            if spec.is_empty() {
+                Ok(text)
+            } else {
+                format_str(&text, &parse_spec(spec)?)
+            }
+        }
+    }
+}
+
+/// `format % args`.
+pub fn percent_format(interp: &mut Interp, format: &str, args: &Value) -> PyResult<String> {
+    let positional: Vec<Value> = match args {
+        Value::Tuple(items) => items.to_vec(),
+        Value::Dict(_) => Vec::new(),
+        other => vec![other.clone()],
+    };
+    let mapping = matches!(args, Value::Dict(_)).then(|| args.clone());
+    let mut next_arg = 0;
+    let mut out = String::new();
+    let chars: Vec<char> = format.chars().collect();
+    let mut i = 0;
+    let take = |next_arg: &mut usize| -> PyResult<Value> {
+        match positional.get(*next_arg) {
+            Some(value) => {
+                *next_arg += 1;
+                Ok(value.clone())
+            }
+            None if mapping.is_some() && *next_arg == 0 => {
+                *next_arg += 1;
+                Ok(args.clone())
+            }
+            None => raise("TypeError", "not enough arguments for format string"),
+        }
+    };
+    while i < chars.len() {
+        if chars[i] != '%' {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        let mut value = None;
+        if chars.get(i) == Some(&'(') {
+            let end = match chars[i..].iter().position(|&c| c == ')') {
+                Some(offset) => i + offset,
+                None => return raise("ValueError", "incomplete format key"),
+            };
+            let key: String = chars[i + 1..end].iter().collect();
+            let Some(mapping) = &mapping else {
+                return raise("TypeError", "format requires a mapping");
+            };
+            value = Some(interp.get_item(mapping, &Value::str(&key))?);
+            i = end + 1;
+        }
+        let mut spec = Spec::default();
+        while let Some(&c) = chars.get(i) {
+            match c {
+                '-' => spec.align = Some('<'),
+                '+' => spec.sign = Some('+'),
+                ' ' => {
+                    if spec.sign.is_none() {
+                        spec.sign = Some(' ');
+                    }
+                }
+                '#' => spec.alternate = true,
+                '0' => spec.zero = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        if chars.get(i) == Some(&'*') {
+            spec.width = match take(&mut next_arg)? {
+                Value::Int(n) => n.max(0) as usize,
+                _ => return raise("TypeError", "* wants int"),
+            };
+            i += 1;
+        } else {
+            while let Some(d) = chars.get(i).and_then(|c| c.to_digit(10)) {
+                spec.width = spec.width * 10 + d as usize;
+                i += 1;
+            }
+        }
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            let mut precision = 0;
+            if chars.get(i) == Some(&'*') {
+                precision = match take(&mut next_arg)? {
+                    Value::Int(n) => n.max(0) as usize,
+                    _ => return raise("TypeError", "* wants int"),
+                };
+                i += 1;
+            } else {
+                while let Some(d) = chars.get(i).and_then(|c| c.to_digit(10)) {
+                    precision = precision * 10 + d as usize;
+                    i += 1;
+                }
+            }
+            spec.precision = Some(precision);
+        }
+        while matches!(chars.get(i), Some('h' | 'l' | 'L')) {
+            i += 1;
+        }
+        let Some(&conversion) = chars.get(i) else {
+            return raise("ValueError", "incomplete format");
+        };
+        i += 1;
+        if conversion == '%' {
+            out.push('%');
+            continue;
+        }
+        if spec.zero && spec.align.is_some() {
+            spec.zero = false;
+        }
+        let value = match value {
+            Some(value) => value,
+            None => take(&mut next_arg)?,
+        };
+        let text = match conversion {
+            's' | 'r' | 'a' => {
+                let text = match conversion {
+                    's' => interp.to_str(&value)?,
+                    'r' => interp.repr(&value)?,
+                    _ => ascii_escape(&interp.repr(&value)?),
+                };
+                spec.zero = false;
+                format_str(&text, &spec)?
+            }
+            'd' | 'i' | 'u' | 'x' | 'X' | 'o' | 'c' => {
+                let n = match (&value, conversion) {
+                    (Value::Int(n), _) => *n,
+                    (Value::Bool(b), _) => *b as i64,
+                    (Value::Float(x), 'd' | 'i' | 'u') => x.trunc() as i64,
+                    (Value::Str(s), 'c') if s.chars().count() == 1 => {
+                        s.chars().next().unwrap() as i64
+                    }
+                    _ => {
+                        return raise(
+                            "TypeError",
+                            format!(
+                                "%{conversion} format: a real number is required, not {}",
+                                value.type_name()
+                            ),
+                        );
+                    }
+                };
+                if let Some(precision) = spec.precision.take()
+                    && spec.width < precision
+                    && conversion != 'c'
+                {
+                    spec.width = precision + usize::from(n < 0);
+                    spec.zero = true;
+                }
+                spec.kind = Some(match conversion {
+                    'i' | 'u' => 'd',
+                    other => other,
+                });
+                format_int(n, &spec)?
+            }
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                let x = match value {
+                    Value::Int(n) => n as f64,
+                    Value::Bool(b) => b as i64 as f64,
+                    Value::Float(x) => x,
+                    _ => {
+                        return raise(
+                            "TypeError",
+                            format!("must be real number, not {}", value.type_name()),
+                        );
+                    }
+                };
+                spec.kind = Some(conversion);
+                format_float(x, &spec)?
+            }
+            other => {
+                return raise(
+                    "ValueError",
+                    format!(
+                        "unsupported format character '{other}' (0x{:x})",
+                        other as u32
+                    ),
+                );
+            }
+        };
+        out.push_str(&text);
+    }
+    if mapping.is_none() && next_arg < positional.len() {
+        return raise(
+            "TypeError",
+            "not all arguments converted during string formatting",
+        );
+    }
+    Ok(out)
+}
+
+/// `format.format(*args, **kwargs)`.
+pub fn str_format(interp: &mut Interp, format: &str, args: &Args) -> PyResult<String> {
+    let mut numbering = Numbering::default();
+    format_fields(interp, format, args, &mut numbering, 0)
+}
+
+/// How `str.format` fields pick positional arguments; the two styles can't mix.
+#[derive(Default)]
+struct Numbering {
+    next: usize,
+    automatic: bool,
+    manual: bool,
+}
+
+fn format_fields(
+    interp: &mut Interp,
+    format: &str,
+    args: &Args,
+    numbering: &mut Numbering,
+    depth: usize,
+) -> PyResult<String> {
+    if depth > 2 {
+        return raise("ValueError", "Max string recursion exceeded");
+    }
+    let chars: Vec<char> = format.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '}' {
+            if chars.get(i + 1) == Some(&'}') {
+                out.push('}');
+                i += 2;
+                continue;
+            }
+            return raise("ValueError", "Single '}' encountered in format string");
+        }
+        if c != '{' {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        if chars.get(i + 1) == Some(&'{') {
+            out.push('{');
+            i += 2;
+            continue;
+        }
+        let mut depth_here = 1;
+        let mut j = i + 1;
+        while j < chars.len() {
+            match chars[j] {
+                '{' => depth_here += 1,
+                '}' => {
+                    depth_here -= 1;
+                    if depth_here == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            j += 1;
+        }
+        if j >= chars.len() {
+            return raise("ValueError", "expected '}' before end of string");
+        }
+        let field: String = chars[i + 1..j].iter().collect();
+        i = j + 1;
+
+        let (field, spec) = match field.find(':') {
+            Some(colon) => (field[..colon].to_string(), field[colon + 1..].to_string()),
+            None => (field, String::new()),
+        };
+        let (name, conversion) = match field.rfind('!') {
+            Some(bang) => (
+                field[..bang].to_string(),
+                field[bang + 1..].chars().next(),
+            ),
+            None => (field, None),
+        };
+        let split = name.find(['.', '[']).unwrap_or(name.len());
+        let (first, mut rest) = name.split_at(split);
+        let mut value = if first.is_empty() {
+            if numbering.manual {
+                return raise(
+                    "ValueError",
+                    "cannot switch from manual field specification to automatic field numbering",
+                );
+            }
+            numbering.automatic = true;
+            let value = args.pos.get(numbering.next).cloned();
+            numbering.next += 1;
+            match value {
+                Some(value) => value,
+                None => {
+                    return raise(
+                        "IndexError",
+                        format!(
+                            "Replacement index {} out of range for positional args tuple",
+                            numbering.next - 1
+                        ),
+                    );
+                }
+            }
+        } else if let Ok(index) = first.parse::<usize>() {
+            if numbering.automatic {
+                return raise(
+                    "ValueError",
+                    "cannot switch from automatic field numbering to manual field specification",
+                );
+            }
+            numbering.manual = true;
+            match args.pos.get(index) {
+                Some(value) => value.clone(),
+                None => {
+                    return raise(
+                        "IndexError",
+                        format!(
+                            "Replacement index {index} out of range for positional args tuple"
+                        ),
+                    );
+                }
+            }
+        } else {
+            match args.kw.iter().find(|(k, _)| &**k == first) {
+                Some((_, value)) => value.clone(),
+                None => return Err(super::error::PyErr::with_arg("KeyError", Value::str(first))),
+            }
+        };
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                value = interp.get_attr(&value, &Rc::from(&after[..end]))?;
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let Some(end) = after.find(']') else {
+                    return raise("ValueError", "Missing ']' in format string");
+                };
+                let key = &after[..end];
+                let key = match key.parse::<i64>() {
+                    Ok(n) => Value::Int(n),
+                    Err(_) => Value::str(key),
+                };
+                value = interp.get_item(&value, &key)?;
+                rest = &after[end + 1..];
+            } else {
+                return raise("ValueError", "Only '.' or '[' may follow ']' in format field specifier");
+            }
+        }
+        let value = match conversion {
+            Some('r') => Value::str(&interp.repr(&value)?),
+            Some('s') => Value::str(&interp.to_str(&value)?),
+            Some('a') => Value::str(&ascii_escape(&interp.repr(&value)?)),
+            Some(other) => {
+                return raise(
+                    "ValueError",
+                    format!("Unknown conversion specifier {other}"),
+                );
+            }
+            None => value,
+        };
+        let spec = if spec.contains('{') {
+            format_fields(interp, &spec, args, numbering, depth + 1)?
+        } else {
+            spec
+        };
+        out.push_str(&format_value(interp, &value, &spec)?);
+    }
+    Ok(out)
+}
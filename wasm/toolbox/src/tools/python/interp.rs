@@ -0,0 +1,2138 @@
+//! The tree-walking interpreter: statements, expressions, calls, classes,
+//! attributes and imports. Operators and protocols are in `ops.rs`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+use indexmap::IndexMap;
+
+use super::ast::*;
+use super::builtins;
+use super::error::{Exc, Frame, PyErr, PyResult, raise};
+use super::format;
+use super::methods;
+use super::modules;
+use super::parser;
+use super::value::*;
+
+/// How a block finished.
+pub enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/// Builtin exception classes and their bases, parents first.
+const EXCEPTIONS: &[(&str, Option<&str>)] = &[
+    ("BaseException", None),
+    ("SystemExit", Some("BaseException")),
+    ("KeyboardInterrupt", Some("BaseException")),
+    ("GeneratorExit", Some("BaseException")),
+    ("Exception", Some("BaseException")),
+    ("ArithmeticError", Some("Exception")),
+    ("ZeroDivisionError", Some("ArithmeticError")),
+    ("OverflowError", Some("ArithmeticError")),
+    ("FloatingPointError", Some("ArithmeticError")),
+    ("AssertionError", Some("Exception")),
+    ("AttributeError", Some("Exception")),
+    ("EOFError", Some("Exception")),
+    ("ImportError", Some("Exception")),
+    ("ModuleNotFoundError", Some("ImportError")),
+    ("LookupError", Some("Exception")),
+    ("MemoryError", Some("Exception")),
+    ("IndexError", Some("LookupError")),
+    ("KeyError", Some("LookupError")),
+    ("NameError", Some("Exception")),
+    ("UnboundLocalError", Some("NameError")),
+    ("OSError", Some("Exception")),
+    ("FileNotFoundError", Some("OSError")),
+    ("FileExistsError", Some("OSError")),
+    ("IsADirectoryError", Some("OSError")),
+    ("NotADirectoryError", Some("OSError")),
+    ("PermissionError", Some("OSError")),
+    ("TimeoutError", Some("OSError")),
+    ("RuntimeError", Some("Exception")),
+    ("NotImplementedError", Some("RuntimeError")),
+    ("RecursionError", Some("RuntimeError")),
+    ("StopIteration", Some("Exception")),
+    ("SyntaxError", Some("Exception")),
+    ("IndentationError", Some("SyntaxError")),
+    ("TypeError", Some("Exception")),
+    ("ValueError", Some("Exception")),
+    ("UnicodeError", Some("ValueError")),
+    ("UnicodeDecodeError", Some("UnicodeError")),
+    ("UnicodeEncodeError", Some("UnicodeError")),
+    ("JSONDecodeError", Some("ValueError")),
+    ("re.error", Some("Exception")),
+    ("Warning", Some("Exception")),
+    ("UserWarning", Some("Warning")),
+    ("DeprecationWarning", Some("Warning")),
+];
+
+pub struct Interp {
+    pub builtins: HashMap<Rc<str>, Value>,
+    /// Imported modules by name.
+    pub modules: HashMap<String, Value>,
+    exceptions: HashMap<&'static str, Rc<Class>>,
+    /// The class of `object()` instances.
+    pub object_class: Rc<Class>,
+    depth: usize,
+    pub recursion_limit: usize,
+    /// The running file and function of every active frame.
+    frames: Vec<(Rc<str>, Rc<str>)>,
+    /// Values yielded by the running generator bodies.
+    generators: Vec<Vec<Value>>,
+    /// Exceptions being handled, for a bare `raise`.
+    handling: Vec<PyErr>,
+    /// Source of every file run, for tracebacks.
+    sources: HashMap<Rc<str>, Rc<str>>,
+    /// Buffered standard output.
+    out: Vec<u8>,
+    pub argv: Vec<String>,
+    /// `sys.path`: where `import` looks for `.py` files.
+    pub sys_path: Value,
+    /// Objects whose `repr` is being built, to show cycles as `[...]`.
+    pub repr_active: Vec<usize>,
+}
+
+impl Interp {
+    pub fn new(argv: Vec<String>, path: Vec<String>) -> Interp {
+        let object_class = Rc::new(Class {
+            name: Rc::from("object"),
+            bases: Vec::new(),
+            mro: Vec::new(),
+            attrs: RefCell::new(IndexMap::new()),
+            module: Rc::from("builtins"),
+        });
+        let mut interp = Interp {
+            builtins: HashMap::new(),
+            modules: HashMap::new(),
+            exceptions: HashMap::new(),
+            object_class,
+            depth: 0,
+            recursion_limit: 200,
+            frames: Vec::new(),
+            generators: Vec::new(),
+            handling: Vec::new(),
+            sources: HashMap::new(),
+            out: Vec::new(),
+            argv,
+            sys_path: Value::list(path.iter().map(|p| Value::str(p)).collect()),
+            repr_active: Vec::new(),
+        };
+        for &(name, base) in EXCEPTIONS {
+            let bases: Vec<Rc<Class>> = base
+                .map(|base| interp.exceptions[base].clone())
+                .into_iter()
+                .collect();
+            let mut mro = Vec::new();
+            for base in &bases {
+                mro.push(base.clone());
+                mro.extend(base.mro.iter().cloned());
+            }
+            let mut attrs = IndexMap::new();
+            if base.is_none() {
+                attrs.insert(Rc::from("__init__"), Value::Builtin("BaseException.__init__"));
+                attrs.insert(Rc::from("with_traceback"), Value::Builtin("BaseException.with_traceback"));
+            }
+            if name == "OSError" {
+                attrs.insert(Rc::from("__init__"), Value::Builtin("OSError.__init__"));
+            }
+            let (module, short) = match name.split_once('.') {
+                Some((module, short)) => (module, short),
+                None if name == "JSONDecodeError" => ("json", name),
+                None => ("builtins", name),
+            };
+            let class = Rc::new(Class {
+                name: Rc::from(short),
+                bases,
+                mro,
+                attrs: RefCell::new(attrs),
+                module: Rc::from(module),
+            });
+            interp.exceptions.insert(name, class.clone());
+            if module == "builtins" {
+                interp.builtins.insert(Rc::from(name), Value::Class(class));
+            }
+        }
+        for alias in ["IOError", "EnvironmentError"] {
+            let os_error = Value::Class(interp.exceptions["OSError"].clone());
+            interp.builtins.insert(Rc::from(alias), os_error);
+        }
+        builtins::install(&mut interp.builtins);
+        interp
+    }
+
+    /// A builtin exception class, e.g. `ValueError`.
+    pub fn exception_class(&self, kind: &str) -> Rc<Class> {
+        self.exceptions
+            .get(kind)
+            .cloned()
+            .unwrap_or_else(|| self.exceptions["Exception"].clone())
+    }
+
+    // ---- Running code ----
+
+    /// Run `source` as the `__main__` module, returning the exit status.
+    pub fn run_main(&mut self, source: &str, file: &str) -> i32 {
+        let file: Rc<str> = Rc::from(file);
+        self.sources.insert(file.clone(), Rc::from(source));
+        let body = match parser::parse_module(source) {
+            Ok(body) => body,
+            Err(e) => {
+                self.flush();
+                eprintln!("  File \"{}\", line {}", file, e.line);
+                if let Some(text) = source.lines().nth(e.line.saturating_sub(1)) {
+                    eprintln!("    {}", text.trim());
+                }
+                eprintln!("SyntaxError: {}", e.msg);
+                return 1;
+            }
+        };
+        let scope = Scope::new(ScopeKind::Module, None);
+        let module = Rc::new(Module {
+            name: Rc::from("__main__"),
+            scope: scope.clone(),
+        });
+        module.set("__name__", Value::str("__main__"));
+        module.set("__file__", Value::Str(file.clone()));
+        module.set("__doc__", docstring(&body));
+        self.modules
+            .insert("__main__".to_string(), Value::Module(module));
+        self.frames.push((file, Rc::from("<module>")));
+        let result = self.exec_block(&body, &scope);
+        self.frames.pop();
+        let status = match result {
+            Ok(_) => 0,
+            Err(e) => self.report_uncaught(e),
+        };
+        self.flush();
+        status
+    }
+
+    /// Print an uncaught exception's traceback, returning the exit status.
+    /// `SystemExit` exits quietly with its code.
+    fn report_uncaught(&mut self, mut err: Box<PyErr>) -> i32 {
+        let exc = self.exception_value(&mut err);
+        self.flush();
+        if let Value::Instance(instance) = &exc
+            && instance.class.has_ancestor("SystemExit")
+        {
+            let code = match instance.attrs.borrow().get("args") {
+                Some(Value::Tuple(args)) => args.first().cloned().unwrap_or(Value::None),
+                _ => Value::None,
+            };
+            return match code {
+                Value::None => 0,
+                Value::Int(n) => n as i32,
+                Value::Bool(b) => b as i32,
+                other => {
+                    let text = self.to_str(&other).unwrap_or_default();
+                    eprintln!("{text}");
+                    1
+                }
+            };
+        }
+        let mut text = String::from("Traceback (most recent call last):\n");
+        for frame in err.traceback.iter().rev() {
+            text.push_str(&format!(
+                "  File \"{}\", line {}, in {}\n",
+                frame.file, frame.line, frame.name
+            ));
+            if let Some(source) = self.sources.get(&frame.file)
+                && let Some(line) = source.lines().nth(frame.line.saturating_sub(1))
+            {
+                text.push_str(&format!("    {}\n", line.trim()));
+            }
+        }
+        text.push_str(&self.describe_exception(&exc));
+        eprintln!("{text}");
+        1
+    }
+
+    /// `Type: message`, as the last line of a traceback shows it.
+    pub fn describe_exception(&mut self, exc: &Value) -> String {
+        let name = match exc {
+            Value::Instance(instance) => {
+                let class = &instance.class;
+                match &*class.module {
+                    "builtins" | "__main__" => class.name.to_string(),
+                    module => format!("{module}.{}", class.name),
+                }
+            }
+            other => other.type_name().to_string(),
+        };
+        match self.to_str(exc) {
+            Ok(message) if message.is_empty() => name,
+            Ok(message) => format!("{name}: {message}"),
+            Err(_) => name,
+        }
+    }
+
+    /// Run a module's source in a fresh module scope.
+    pub fn run_module(&mut self, name: &str, source: &str, file: &str) -> PyResult<Value> {
+        let file: Rc<str> = Rc::from(file);
+        self.sources.insert(file.clone(), Rc::from(source));
+        let body = parser::parse_module(source).map_err(|e| {
+            PyErr::new(
+                "SyntaxError",
+                format!("{} ({}, line {})", e.msg, file, e.line),
+            )
+        })?;
+        let scope = Scope::new(ScopeKind::Module, None);
+        let module = Rc::new(Module {
+            name: Rc::from(name),
+            scope: scope.clone(),
+        });
+        module.set("__name__", Value::str(name));
+        module.set("__file__", Value::Str(file.clone()));
+        module.set("__doc__", docstring(&body));
+        let value = Value::Module(module);
+        // Registered before running, so circular imports see it
+        self.modules.insert(name.to_string(), value.clone());
+        self.frames.push((file, Rc::from("<module>")));
+        let result = self.exec_block(&body, &scope);
+        self.frames.pop();
+        if let Err(mut e) = result {
+            self.modules.remove(name);
+            e.located = false;
+            return Err(e);
+        }
+        Ok(value)
+    }
+
+    // ---- Output ----
+
+    pub fn write_out(&mut self, text: &str) {
+        self.out.extend_from_slice(text.as_bytes());
+        if self.out.len() >= 8192 {
+            self.flush();
+        }
+    }
+
+    pub fn write_err(&mut self, text: &str) {
+        self.flush();
+        let _ = std::io::stderr().write_all(text.as_bytes());
+    }
+
+    pub fn flush(&mut self) {
+        if !self.out.is_empty() {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(&self.out);
+            let _ = stdout.flush();
+            self.out.clear();
+        }
+    }
+
+    // ---- Statements ----
+
+    pub fn exec_block(&mut self, block: &[Stmt], scope: &Rc<Scope>) -> PyResult<Flow> {
+        for stmt in block {
+            match self.exec_stmt(stmt, scope) {
+                Ok(Flow::Normal) => {}
+                Ok(flow) => return Ok(flow),
+                Err(mut e) => {
+                    if !e.located {
+                        let (file, name) = self.frames.last().cloned().unwrap_or_else(|| {
+                            (Rc::from("<string>"), Rc::from("<module>"))
+                        });
+                        e.traceback.push(Frame {
+                            file,
+                            line: stmt.line,
+                            name,
+                        });
+                        e.located = true;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec_stmt(&mut self, stmt: &Stmt, scope: &Rc<Scope>) -> PyResult<Flow> {
+        match &stmt.kind {
+            StmtKind::Expr(expr) => {
+                self.eval(expr, scope)?;
+            }
+            StmtKind::Assign { targets, value } => {
+                let value = self.eval(value, scope)?;
+                for target in targets {
+                    self.assign(target, value.clone(), scope)?;
+                }
+            }
+            StmtKind::AugAssign { target, op, value } => self.aug_assign(target, *op, value, scope)?,
+            StmtKind::AnnAssign { target, value } => {
+                if scope.kind == ScopeKind::Class
+                    && let Expr::Name(name) = target
+                {
+                    let annotations = scope
+                        .vars
+                        .borrow_mut()
+                        .entry(Rc::from("__annotations__"))
+                        .or_insert_with(|| Value::dict(Dict::default()))
+                        .clone();
+                    if let Value::Dict(annotations) = annotations {
+                        annotations.borrow_mut().set_str(name, Value::None);
+                    }
+                }
+                if let Some(value) = value {
+                    let value = self.eval(value, scope)?;
+                    self.assign(target, value, scope)?;
+                }
+            }
+            StmtKind::If { test, body, orelse } => {
+                let test = self.eval(test, scope)?;
+                return if self.truthy(&test)? {
+                    self.exec_block(body, scope)
+                } else {
+                    self.exec_block(orelse, scope)
+                };
+            }
+            StmtKind::While { test, body, orelse } => {
+                loop {
+                    let value = self.eval(test, scope)?;
+                    if !self.truthy(&value)? {
+                        return self.exec_block(orelse, scope);
+                    }
+                    match self.exec_block(body, scope)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            StmtKind::For {
+                target,
+                iter,
+                body,
+                orelse,
+            } => {
+                let iterable = self.eval(iter, scope)?;
+                let iterator = self.iter(&iterable)?;
+                while let Some(item) = self.next(&iterator)? {
+                    self.assign(target, item, scope)?;
+                    match self.exec_block(body, scope)? {
+                        Flow::Break => return Ok(Flow::Normal),
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+                return self.exec_block(orelse, scope);
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+            StmtKind::Pass => {}
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value, scope)?,
+                    None => Value::None,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StmtKind::FunctionDef(def) => {
+                let mut function = self.make_function(def, scope)?;
+                for decorator in def.decorators.iter().rev() {
+                    let decorator = self.eval(decorator, scope)?;
+                    function = self.call(&decorator, Args::new(vec![function]))?;
+                }
+                self.store(&def.name, function, scope);
+            }
+            StmtKind::ClassDef {
+                name,
+                bases,
+                body,
+                decorators,
+            } => {
+                let mut class = self.make_class(name, bases, body, scope)?;
+                for decorator in decorators.iter().rev() {
+                    let decorator = self.eval(decorator, scope)?;
+                    class = self.call(&decorator, Args::new(vec![class]))?;
+                }
+                self.store(name, class, scope);
+            }
+            StmtKind::Try {
+                body,
+                handlers,
+                orelse,
+                finally,
+            } => return self.exec_try(body, handlers, orelse, finally, scope),
+            StmtKind::Raise { exc, cause } => {
+                let Some(exc) = exc else {
+                    return match self.handling.last() {
+                        Some(err) => {
+                            let mut err = Box::new(err.clone());
+                            err.located = false;
+                            Err(err)
+                        }
+                        None => raise("RuntimeError", "No active exception to reraise"),
+                    };
+                };
+                let exc = self.eval(exc, scope)?;
+                let exc = self.make_exception(exc)?;
+                if let Some(cause) = cause {
+                    let cause = self.eval(cause, scope)?;
+                    if let Value::Instance(instance) = &exc {
+                        instance
+                            .attrs
+                            .borrow_mut()
+                            .insert(Rc::from("__cause__"), cause);
+                    }
+                }
+                return Err(PyErr::raised(exc));
+            }
+            StmtKind::Import(names) => {
+                for (name, alias) in names {
+                    let module = self.import_dotted(name)?;
+                    match alias {
+                        Some(alias) => self.store(alias, module, scope),
+                        None => {
+                            let top = name.split('.').next().unwrap();
+                            let top_module = self.modules[top].clone();
+                            self.store(&Rc::from(top), top_module, scope);
+                        }
+                    }
+                }
+            }
+            StmtKind::ImportFrom {
+                module,
+                level,
+                names,
+            } => self.import_from(module, *level, names, scope)?,
+            StmtKind::Global(names) => {
+                let mut declared = scope.declared.borrow_mut();
+                for name in names {
+                    declared.insert(name.clone(), Declared::Global);
+                }
+            }
+            StmtKind::Nonlocal(names) => {
+                let mut declared = scope.declared.borrow_mut();
+                for name in names {
+                    declared.insert(name.clone(), Declared::Nonlocal);
+                }
+            }
+            StmtKind::Assert { test, msg } => {
+                let value = self.eval(test, scope)?;
+                if !self.truthy(&value)? {
+                    let arg = match msg {
+                        Some(msg) => self.eval(msg, scope)?,
+                        None => return Err(PyErr::with_arg("AssertionError", Value::None)),
+                    };
+                    return Err(PyErr::with_arg("AssertionError", arg));
+                }
+            }
+            StmtKind::Del(targets) => {
+                for target in targets {
+                    self.delete(target, scope)?;
+                }
+            }
+            StmtKind::With { items, body } => return self.exec_with(items, body, scope),
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec_try(
+        &mut self,
+        body: &[Stmt],
+        handlers: &[Handler],
+        orelse: &[Stmt],
+        finally: &[Stmt],
+        scope: &Rc<Scope>,
+    ) -> PyResult<Flow> {
+        let result = match self.exec_block(body, scope) {
+            Ok(Flow::Normal) => self.exec_block(orelse, scope),
+            Ok(flow) => Ok(flow),
+            Err(mut err) if !handlers.is_empty() => {
+                let exc = self.exception_value(&mut err);
+                let mut chosen = None;
+                for handler in handlers {
+                    let matched = match &handler.kind {
+                        None => true,
+                        Some(kind) => {
+                            let kind = self.eval(kind, scope)?;
+                            self.exception_matches(&exc, &kind)?
+                        }
+                    };
+                    if matched {
+                        chosen = Some(handler);
+                        break;
+                    }
+                }
+                match chosen {
+                    Some(handler) => {
+                        if let Some(name) = &handler.name {
+                            self.store(name, exc, scope);
+                        }
+                        self.handling.push(*err);
+                        let result = self.exec_block(&handler.body, scope);
+                        self.handling.pop();
+                        if let Some(name) = &handler.name {
+                            scope.vars.borrow_mut().shift_remove(name);
+                        }
+                        result
+                    }
+                    None => Err(err),
+                }
+            }
+            Err(err) => Err(err),
+        };
+        if finally.is_empty() {
+            return result;
+        }
+        match self.exec_block(finally, scope)? {
+            Flow::Normal => result,
+            flow => Ok(flow),
+        }
+    }
+
+    fn exec_with(
+        &mut self,
+        items: &[(Expr, Option<Expr>)],
+        body: &[Stmt],
+        scope: &Rc<Scope>,
+    ) -> PyResult<Flow> {
+        let Some(((context, target), rest)) = items.split_first() else {
+            return self.exec_block(body, scope);
+        };
+        let manager = self.eval(context, scope)?;
+        let enter = self.get_attr(&manager, &Rc::from("__enter__"))?;
+        let exit = self.get_attr(&manager, &Rc::from("__exit__"))?;
+        let entered = self.call(&enter, Args::default())?;
+        if let Some(target) = target {
+            self.assign(target, entered, scope)?;
+        }
+        match self.exec_with(rest, body, scope) {
+            Ok(flow) => {
+                self.call(&exit, Args::new(vec![Value::None, Value::None, Value::None]))?;
+                Ok(flow)
+            }
+            Err(mut err) => {
+                let exc = self.exception_value(&mut err);
+                let kind = self.type_of(&exc);
+                let suppress = self.call(&exit, Args::new(vec![kind, exc, Value::None]))?;
+                if self.truthy(&suppress)? {
+                    Ok(Flow::Normal)
+                } else {
+                    Err(err)
+                }
+            }
+        }
+    }
+
+    fn aug_assign(&mut self, target: &Expr, op: BinOp, value: &Expr, scope: &Rc<Scope>) -> PyResult<()> {
+        match target {
+            Expr::Name(name) => {
+                let current = self.lookup(name, scope)?;
+                let value = self.eval(value, scope)?;
+                let result = self.inplace_binop(op, current, value)?;
+                self.store(name, result, scope);
+            }
+            Expr::Attribute(object, name) => {
+                let object = self.eval(object, scope)?;
+                let current = self.get_attr(&object, name)?;
+                let value = self.eval(value, scope)?;
+                let result = self.inplace_binop(op, current, value)?;
+                self.set_attr(&object, name, result)?;
+            }
+            Expr::Subscript(object, index) => {
+                let object = self.eval(object, scope)?;
+                let index = self.eval(index, scope)?;
+                let current = self.get_item(&object, &index)?;
+                let value = self.eval(value, scope)?;
+                let result = self.inplace_binop(op, current, value)?;
+                self.set_item(&object, index, result)?;
+            }
+            _ => return raise("SyntaxError", "illegal expression for augmented assignment"),
+        }
+        Ok(())
+    }
+
+    // ---- Names ----
+
+    pub fn lookup(&self, name: &Rc<str>, scope: &Rc<Scope>) -> PyResult<Value> {
+        if let Some(def) = &scope.function
+            && def.locals.contains(name)
+            && !scope.declared.borrow().contains_key(name)
+            && !scope.vars.borrow().contains_key(name)
+        {
+            return raise(
+                "UnboundLocalError",
+                format!("cannot access local variable '{name}' where it is not associated with a value"),
+            );
+        }
+        let mut current = scope;
+        loop {
+            if let Some(value) = current.vars.borrow().get(name) {
+                return Ok(value.clone());
+            }
+            match &current.parent {
+                Some(parent) => current = parent,
+                None => break,
+            }
+        }
+        match self.builtins.get(name) {
+            Some(value) => Ok(value.clone()),
+            None => raise("NameError", format!("name '{name}' is not defined")),
+        }
+    }
+
+    pub fn store(&mut self, name: &Rc<str>, value: Value, scope: &Rc<Scope>) {
+        let declared = scope.declared.borrow().get(name).copied();
+        match declared {
+            Some(Declared::Global) => {
+                scope.globals().vars.borrow_mut().insert(name.clone(), value);
+            }
+            Some(Declared::Nonlocal) => {
+                let mut current = scope.parent.clone();
+                while let Some(outer) = current {
+                    if outer.kind != ScopeKind::Module && outer.vars.borrow().contains_key(name) {
+                        outer.vars.borrow_mut().insert(name.clone(), value);
+                        return;
+                    }
+                    current = outer.parent.clone();
+                }
+                scope.vars.borrow_mut().insert(name.clone(), value);
+            }
+            None => {
+                scope.vars.borrow_mut().insert(name.clone(), value);
+            }
+        }
+    }
+
+    pub fn assign(&mut self, target: &Expr, value: Value, scope: &Rc<Scope>) -> PyResult<()> {
+        match target {
+            Expr::Name(name) => self.store(name, value, scope),
+            Expr::Attribute(object, name) => {
+                let object = self.eval(object, scope)?;
+                self.set_attr(&object, name, value)?;
+            }
+            Expr::Subscript(object, index) => {
+                let object = self.eval(object, scope)?;
+                let index = self.eval(index, scope)?;
+                self.set_item(&object, index, value)?;
+            }
+            Expr::Tuple(targets) | Expr::List(targets) => {
+                let values = self.collect(&value)?;
+                let starred = targets.iter().position(|t| matches!(t, Expr::Starred(_)));
+                match starred {
+                    None => {
+                        if values.len() != targets.len() {
+                            return if values.len() > targets.len() {
+                                raise(
+                                    "ValueError",
+                                    format!("too many values to unpack (expected {})", targets.len()),
+                                )
+                            } else {
+                                raise(
+                                    "ValueError",
+                                    format!(
+                                        "not enough values to unpack (expected {}, got {})",
+                                        targets.len(),
+                                        values.len()
+                                    ),
+                                )
+                            };
+                        }
+                        for (target, value) in targets.iter().zip(values) {
+                            self.assign(target, value, scope)?;
+                        }
+                    }
+                    Some(star) => {
+                        let after = targets.len() - star - 1;
+                        if values.len() < star + after {
+                            return raise(
+                                "ValueError",
+                                format!(
+                                    "not enough values to unpack (expected at least {}, got {})",
+                                    targets.len() - 1,
+                                    values.len()
+                                ),
+                            );
+                        }
+                        let mut values = values;
+                        let tail = values.split_off(values.len() - after);
+                        let middle = values.split_off(star);
+                        for (target, value) in targets[..star].iter().zip(values) {
+                            self.assign(target, value, scope)?;
+                        }
+                        if let Expr::Starred(inner) = &targets[star] {
+                            self.assign(inner, Value::list(middle), scope)?;
+                        }
+                        for (target, value) in targets[star + 1..].iter().zip(tail) {
+                            self.assign(target, value, scope)?;
+                        }
+                    }
+                }
+            }
+            Expr::Starred(_) => {
+                return raise("SyntaxError", "starred assignment target must be in a list or tuple");
+            }
+            _ => return raise("SyntaxError", "cannot assign to expression"),
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, target: &Expr, scope: &Rc<Scope>) -> PyResult<()> {
+        match target {
+            Expr::Name(name) => {
+                let global = scope.declared.borrow().get(name) == Some(&Declared::Global);
+                let owner = if global { scope.globals() } else { scope.clone() };
+                if owner.vars.borrow_mut().shift_remove(name).is_none() {
+                    return raise("NameError", format!("name '{name}' is not defined"));
+                }
+            }
+            Expr::Attribute(object, name) => {
+                let object = self.eval(object, scope)?;
+                self.del_attr(&object, name)?;
+            }
+            Expr::Subscript(object, index) => {
+                let object = self.eval(object, scope)?;
+                let index = self.eval(index, scope)?;
+                self.del_item(&object, &index)?;
+            }
+            Expr::Tuple(targets) | Expr::List(targets) => {
+                for target in targets {
+                    self.delete(target, scope)?;
+                }
+            }
+            _ => return raise("SyntaxError", "cannot delete expression"),
+        }
+        Ok(())
+    }
+
+    // ---- Expressions ----
+
+    pub fn eval(&mut self, expr: &Expr, scope: &Rc<Scope>) -> PyResult<Value> {
+        match expr {
+            Expr::Name(name) => self.lookup(name, scope),
+            Expr::Const(value) => Ok(value.clone()),
+            Expr::FString(parts) => Ok(Value::str(&self.eval_fstring(parts, scope)?)),
+            Expr::List(items) => Ok(Value::list(self.eval_items(items, scope)?)),
+            Expr::Tuple(items) => Ok(Value::tuple(self.eval_items(items, scope)?)),
+            Expr::Set(items) => {
+                let items = self.eval_items(items, scope)?;
+                let mut set = Set::default();
+                for item in items {
+                    set.map.insert(self.key(&item)?, item);
+                }
+                Ok(Value::Set(shared(set)))
+            }
+            Expr::Dict(entries) => {
+                let mut dict = Dict::default();
+                for (key, value) in entries {
+                    match key {
+                        Some(key) => {
+                            let key = self.eval(key, scope)?;
+                            let value = self.eval(value, scope)?;
+                            dict.insert(self.key(&key)?, key, value);
+                        }
+                        None => {
+                            let mapping = self.eval(value, scope)?;
+                            for (key, value) in self.mapping_items(&mapping)? {
+                                dict.insert(self.key(&key)?, key, value);
+                            }
+                        }
+                    }
+                }
+                Ok(Value::dict(dict))
+            }
+            Expr::Comp(comp) => self.eval_comprehension(comp, scope),
+            Expr::BinOp(left, op, right) => {
+                let left = self.eval(left, scope)?;
+                let right = self.eval(right, scope)?;
+                self.binop(*op, left, right)
+            }
+            Expr::Unary(op, operand) => {
+                let operand = self.eval(operand, scope)?;
+                self.unary(*op, operand)
+            }
+            Expr::And(left, right) => {
+                let left = self.eval(left, scope)?;
+                if !self.truthy(&left)? {
+                    return Ok(left);
+                }
+                self.eval(right, scope)
+            }
+            Expr::Or(left, right) => {
+                let left = self.eval(left, scope)?;
+                if self.truthy(&left)? {
+                    return Ok(left);
+                }
+                self.eval(right, scope)
+            }
+            Expr::Compare(left, comparisons) => {
+                let mut left = self.eval(left, scope)?;
+                for (op, right) in comparisons {
+                    let right = self.eval(right, scope)?;
+                    if !self.compare(*op, &left, &right)? {
+                        return Ok(Value::Bool(false));
+                    }
+                    left = right;
+                }
+                Ok(Value::Bool(true))
+            }
+            Expr::Call(function, args) => {
+                let function = self.eval(function, scope)?;
+                if matches!(function, Value::Type(Type::Super)) && args.is_empty() {
+                    return self.implicit_super(scope);
+                }
+                let args = self.eval_args(args, scope)?;
+                self.call(&function, args)
+            }
+            Expr::Attribute(object, name) => {
+                let object = self.eval(object, scope)?;
+                self.get_attr(&object, name)
+            }
+            Expr::Subscript(object, index) => {
+                let object = self.eval(object, scope)?;
+                let index = self.eval(index, scope)?;
+                self.get_item(&object, &index)
+            }
+            Expr::Slice(lower, upper, step) => {
+                let mut bound = |expr: &Option<Box<Expr>>| -> PyResult<Value> {
+                    match expr {
+                        Some(expr) => self.eval(expr, scope),
+                        None => Ok(Value::None),
+                    }
+                };
+                let lower = bound(lower)?;
+                let upper = bound(upper)?;
+                let step = bound(step)?;
+                Ok(Value::Slice(Rc::new([lower, upper, step])))
+            }
+            Expr::IfExp(test, body, orelse) => {
+                let test = self.eval(test, scope)?;
+                if self.truthy(&test)? {
+                    self.eval(body, scope)
+                } else {
+                    self.eval(orelse, scope)
+                }
+            }
+            Expr::Lambda(def) => self.make_function(def, scope),
+            Expr::Starred(_) => raise("SyntaxError", "can't use starred expression here"),
+            Expr::Yield(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value, scope)?,
+                    None => Value::None,
+                };
+                match self.generators.last_mut() {
+                    Some(yielded) => yielded.push(value),
+                    None => return raise("SyntaxError", "'yield' outside function"),
+                }
+                Ok(Value::None)
+            }
+            Expr::YieldFrom(iterable) => {
+                let iterable = self.eval(iterable, scope)?;
+                let values = self.collect(&iterable)?;
+                match self.generators.last_mut() {
+                    Some(yielded) => yielded.extend(values),
+                    None => return raise("SyntaxError", "'yield' outside function"),
+                }
+                Ok(Value::None)
+            }
+            Expr::Named(name, value) => {
+                let value = self.eval(value, scope)?;
+                let mut target = scope.clone();
+                while target.kind == ScopeKind::Comprehension {
+                    let parent = target.parent.clone().unwrap();
+                    target = parent;
+                }
+                self.store(name, value.clone(), &target);
+                Ok(value)
+            }
+        }
+    }
+
+    fn eval_items(&mut self, items: &[Expr], scope: &Rc<Scope>) -> PyResult<Vec<Value>> {
+        let mut values = Vec::with_capacity(items.len());
+        for item in items {
+            match item {
+                Expr::Starred(inner) => {
+                    let iterable = self.eval(inner, scope)?;
+                    values.extend(self.collect(&iterable)?);
+                }
+                other => values.push(self.eval(other, scope)?),
+            }
+        }
+        Ok(values)
+    }
+
+    fn eval_args(&mut self, args: &[Arg], scope: &Rc<Scope>) -> PyResult<Args> {
+        let mut out = Args::default();
+        for arg in args {
+            match arg {
+                Arg::Positional(expr) => out.pos.push(self.eval(expr, scope)?),
+                Arg::Starred(expr) => {
+                    let iterable = self.eval(expr, scope)?;
+                    out.pos.extend(self.collect(&iterable)?);
+                }
+                Arg::Keyword(name, expr) => {
+                    let value = self.eval(expr, scope)?;
+                    out.kw.push((name.clone(), value));
+                }
+                Arg::DoubleStarred(expr) => {
+                    let mapping = self.eval(expr, scope)?;
+                    for (key, value) in self.mapping_items(&mapping)? {
+                        match key {
+                            Value::Str(name) => out.kw.push((name, value)),
+                            _ => return raise("TypeError", "keywords must be strings"),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// The key/value pairs of a mapping, for `**` and `dict.update`.
+    pub fn mapping_items(&mut self, mapping: &Value) -> PyResult<Vec<(Value, Value)>> {
+        match mapping {
+            Value::Dict(dict) => Ok(dict.borrow().items()),
+            other => {
+                let keys_method = match self.get_attr(other, &Rc::from("keys")) {
+                    Ok(method) => method,
+                    Err(_) => {
+                        return raise(
+                            "TypeError",
+                            format!("'{}' object is not a mapping", other.type_name()),
+                        );
+                    }
+                };
+                let keys = self.call(&keys_method, Args::default())?;
+                let mut items = Vec::new();
+                for key in self.collect(&keys)? {
+                    let value = self.get_item(other, &key)?;
+                    items.push((key, value));
+                }
+                Ok(items)
+            }
+        }
+    }
+
+    fn eval_fstring(&mut self, parts: &[FPart], scope: &Rc<Scope>) -> PyResult<String> {
+        let mut out = String::new();
+        for part in parts {
+            match part {
+                FPart::Literal(text) => out.push_str(text),
+                FPart::Field {
+                    expr,
+                    conversion,
+                    spec,
+                } => {
+                    let value = self.eval(expr, scope)?;
+                    let value = match conversion {
+                        Some('r') => Value::str(&self.repr(&value)?),
+                        Some('s') => Value::str(&self.to_str(&value)?),
+                        Some('a') => Value::str(&format::ascii_escape(&self.repr(&value)?)),
+                        _ => value,
+                    };
+                    let spec = self.eval_fstring(spec, scope)?;
+                    out.push_str(&format::format_value(self, &value, &spec)?);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn eval_comprehension(&mut self, comp: &Comprehension, scope: &Rc<Scope>) -> PyResult<Value> {
+        let inner = Scope::new(ScopeKind::Comprehension, Some(scope.clone()));
+        let mut sink = match comp.kind {
+            CompKind::List | CompKind::Generator => Sink::List(Vec::new()),
+            CompKind::Set => Sink::Set(Set::default()),
+            CompKind::Dict => Sink::Dict(Dict::default()),
+        };
+        self.comprehension_level(comp, 0, &inner, &mut sink)?;
+        Ok(match sink {
+            Sink::List(items) if comp.kind == CompKind::Generator => {
+                Value::iterator(Iter::Values(items.into_iter()))
+            }
+            Sink::List(items) => Value::list(items),
+            Sink::Set(set) => Value::Set(shared(set)),
+            Sink::Dict(dict) => Value::dict(dict),
+        })
+    }
+
+    fn comprehension_level(
+        &mut self,
+        comp: &Comprehension,
+        level: usize,
+        scope: &Rc<Scope>,
+        sink: &mut Sink,
+    ) -> PyResult<()> {
+        let Some(generator) = comp.generators.get(level) else {
+            let element = self.eval(&comp.elt, scope)?;
+            match sink {
+                Sink::List(items) => items.push(element),
+                Sink::Set(set) => {
+                    let key = self.key(&element)?;
+                    set.map.insert(key, element);
+                }
+                Sink::Dict(dict) => {
+                    let value = self.eval(comp.value.as_ref().unwrap(), scope)?;
+                    let key = self.key(&element)?;
+                    dict.insert(key, element, value);
+                }
+            }
+            return Ok(());
+        };
+        let iterable = self.eval(&generator.iter, scope)?;
+        let iterator = self.iter(&iterable)?;
+        'items: while let Some(item) = self.next(&iterator)? {
+            self.assign(&generator.target, item, scope)?;
+            for condition in &generator.ifs {
+                let value = self.eval(condition, scope)?;
+                if !self.truthy(&value)? {
+                    continue 'items;
+                }
+            }
+            self.comprehension_level(comp, level + 1, scope, sink)?;
+        }
+        Ok(())
+    }
+
+    // ---- Functions and calls ----
+
+    fn make_function(&mut self, def: &Rc<FunctionDef>, scope: &Rc<Scope>) -> PyResult<Value> {
+        let mut defaults = Vec::with_capacity(def.params.len());
+        for param in &def.params {
+            defaults.push(match &param.default {
+                Some(default) => Some(self.eval(default, scope)?),
+                None => None,
+            });
+        }
+        // Methods see the scope around the class, not the class body
+        let mut enclosing = scope.clone();
+        if enclosing.kind == ScopeKind::Class {
+            enclosing = enclosing.parent.clone().unwrap();
+        }
+        let file = self
+            .frames
+            .last()
+            .map(|(file, _)| file.clone())
+            .unwrap_or_else(|| Rc::from("<string>"));
+        Ok(Value::Function(Rc::new(Function {
+            def: def.clone(),
+            defaults,
+            scope: enclosing,
+            file,
+            class: RefCell::new(Weak::new()),
+            attrs: RefCell::new(HashMap::new()),
+        })))
+    }
+
+    pub fn call(&mut self, function: &Value, mut args: Args) -> PyResult<Value> {
+        match function {
+            Value::Function(f) => self.call_function(f, args),
+            Value::Builtin(name) => builtins::call(self, name, args),
+            Value::Method(method) => {
+                args.pos.insert(0, method.0.clone());
+                self.call(&method.1, args)
+            }
+            Value::BuiltinMethod(method) => methods::call(self, &method.0, &method.1, args),
+            Value::Type(kind) => builtins::construct(self, *kind, args),
+            Value::Class(class) => self.instantiate(class, args),
+            Value::StaticMethod(inner) => self.call(inner, args),
+            Value::Instance(instance) => match instance.class.lookup("__call__") {
+                Some(method) => {
+                    let method = self.bind(method, function);
+                    self.call(&method, args)
+                }
+                None => raise(
+                    "TypeError",
+                    format!("'{}' object is not callable", instance.class.name),
+                ),
+            },
+            other => raise(
+                "TypeError",
+                format!("'{}' object is not callable", other.type_name()),
+            ),
+        }
+    }
+
+    fn call_function(&mut self, function: &Rc<Function>, args: Args) -> PyResult<Value> {
+        if self.depth >= self.recursion_limit {
+            return raise("RecursionError", "maximum recursion depth exceeded");
+        }
+        let local = Scope::call(function.def.clone(), function.scope.clone());
+        let first = self.bind_args(function, args, &local)?;
+        if let Some(class) = function.class.borrow().upgrade() {
+            let mut vars = local.vars.borrow_mut();
+            vars.insert(Rc::from("__class__"), Value::Class(class));
+            if let Some(first) = first {
+                vars.insert(Rc::from("__self__"), first);
+            }
+        }
+        let generator = function.def.is_generator;
+        if generator {
+            self.generators.push(Vec::new());
+        }
+        self.depth += 1;
+        self.frames
+            .push((function.file.clone(), function.def.name.clone()));
+        let result = self.exec_block(&function.def.body, &local);
+        self.frames.pop();
+        self.depth -= 1;
+        // Generators run to completion; iterating them replays what they yielded
+        let yielded = if generator {
+            self.generators.pop()
+        } else {
+            None
+        };
+        match result {
+            Ok(flow) => match yielded {
+                Some(values) => Ok(Value::iterator(Iter::Values(values.into_iter()))),
+                None => match flow {
+                    Flow::Return(value) => Ok(value),
+                    _ => Ok(Value::None),
+                },
+            },
+            Err(mut e) => {
+                e.located = false;
+                Err(e)
+            }
+        }
+    }
+
+    /// Bind call arguments to the function's parameters in `local`,
+    /// returning the first positional argument.
+    fn bind_args(&mut self, function: &Function, args: Args, local: &Rc<Scope>) -> PyResult<Option<Value>> {
+        let def = &function.def;
+        let name = function.qualname();
+        let params = &def.params;
+        let first = args.pos.first().cloned();
+        let mut vars = local.vars.borrow_mut();
+        let mut assigned = vec![false; params.len()];
+        let mut positional = args.pos.into_iter();
+        let positional_count = params
+            .iter()
+            .filter(|p| p.kind == ParamKind::Positional)
+            .count();
+        let given = positional.len();
+        for (i, param) in params.iter().enumerate() {
+            if param.kind != ParamKind::Positional {
+                continue;
+            }
+            match positional.next() {
+                Some(value) => {
+                    vars.insert(param.name.clone(), value);
+                    assigned[i] = true;
+                }
+                None => break,
+            }
+        }
+        let rest: Vec<Value> = positional.collect();
+        match params.iter().position(|p| p.kind == ParamKind::VarArgs) {
+            Some(i) => {
+                vars.insert(params[i].name.clone(), Value::tuple(rest));
+                assigned[i] = true;
+            }
+            None if !rest.is_empty() => {
+                let takes = match positional_count {
+                    1 => "1 positional argument".to_string(),
+                    n => format!("{n} positional arguments"),
+                };
+                let was = if given == 1 { "was" } else { "were" };
+                return raise(
+                    "TypeError",
+                    format!("{name}() takes {takes} but {given} {was} given"),
+                );
+            }
+            None => {}
+        }
+        let var_keywords = params.iter().position(|p| p.kind == ParamKind::VarKeywords);
+        let mut extra = Dict::default();
+        for (key, value) in args.kw {
+            let target = params.iter().position(|p| {
+                p.name == key && matches!(p.kind, ParamKind::Positional | ParamKind::KeywordOnly)
+            });
+            match target {
+                Some(i) => {
+                    if assigned[i] {
+                        return raise(
+                            "TypeError",
+                            format!("{name}() got multiple values for argument '{key}'"),
+                        );
+                    }
+                    vars.insert(key, value);
+                    assigned[i] = true;
+                }
+                None if var_keywords.is_some() => {
+                    extra.insert(Key::Str(key.clone()), Value::Str(key), value);
+                }
+                None => {
+                    return raise(
+                        "TypeError",
+                        format!("{name}() got an unexpected keyword argument '{key}'"),
+                    );
+                }
+            }
+        }
+        if let Some(i) = var_keywords {
+            vars.insert(params[i].name.clone(), Value::dict(extra));
+            assigned[i] = true;
+        }
+        let mut missing = Vec::new();
+        for (i, param) in params.iter().enumerate() {
+            if assigned[i] {
+                continue;
+            }
+            match &function.defaults[i] {
+                Some(default) => {
+                    vars.insert(param.name.clone(), default.clone());
+                }
+                None => missing.push(param),
+            }
+        }
+        if let Some(param) = missing.first() {
+            let kind = if param.kind == ParamKind::KeywordOnly {
+                "keyword-only"
+            } else {
+                "positional"
+            };
+            let names: Vec<String> = missing.iter().map(|p| format!("'{}'", p.name)).collect();
+            let count = missing.len();
+            let plural = if count == 1 { "" } else { "s" };
+            return raise(
+                "TypeError",
+                format!(
+                    "{name}() missing {count} required {kind} argument{plural}: {}",
+                    join_names(&names)
+                ),
+            );
+        }
+        Ok(first)
+    }
+
+    /// `super()` inside a method: the method's class and first argument.
+    fn implicit_super(&mut self, scope: &Rc<Scope>) -> PyResult<Value> {
+        let class = self.lookup(&Rc::from("__class__"), scope);
+        let object = self.lookup(&Rc::from("__self__"), scope);
+        match (class, object) {
+            (Ok(Value::Class(class)), Ok(object)) => Ok(Value::Super(Rc::new((class, object)))),
+            _ => raise("RuntimeError", "super(): no arguments"),
+        }
+    }
+
+    /// The value an attribute found on a class gives when looked up on `object`.
+    pub fn bind(&self, attr: Value, object: &Value) -> Value {
+        match attr {
+            Value::Function(_) | Value::Builtin(_) => Value::Method(Rc::new((object.clone(), attr))),
+            Value::ClassMethod(function) => {
+                let class = match object {
+                    Value::Instance(instance) => Value::Class(instance.class.clone()),
+                    other => other.clone(),
+                };
+                Value::Method(Rc::new((class, (*function).clone())))
+            }
+            Value::StaticMethod(function) => (*function).clone(),
+            other => other,
+        }
+    }
+
+    // ---- Classes ----
+
+    fn make_class(
+        &mut self,
+        name: &Rc<str>,
+        base_args: &[Arg],
+        body: &[Stmt],
+        scope: &Rc<Scope>,
+    ) -> PyResult<Value> {
+        let mut bases = Vec::new();
+        let mut metaclass = None;
+        for arg in base_args {
+            let base = match arg {
+                Arg::Positional(expr) => self.eval(expr, scope)?,
+                Arg::Keyword(keyword, expr) if &**keyword == "metaclass" => {
+                    metaclass = Some(self.eval(expr, scope)?);
+                    continue;
+                }
+                // Other class keywords are accepted and ignored
+                Arg::Keyword(..) => continue,
+                _ => return raise("TypeError", "unsupported class base"),
+            };
+            match base {
+                Value::Class(class) => bases.push(class),
+                Value::Type(Type::Object) => {}
+                Value::Type(kind) => {
+                    return raise(
+                        "TypeError",
+                        format!("subclassing the builtin type '{}' is not supported", kind.name()),
+                    );
+                }
+                other => {
+                    return raise(
+                        "TypeError",
+                        format!("bases must be classes, not {}", other.type_name()),
+                    );
+                }
+            }
+        }
+        let class_scope = Scope::new(ScopeKind::Class, Some(scope.clone()));
+        let module = match scope.globals().vars.borrow().get("__name__") {
+            Some(Value::Str(name)) => name.clone(),
+            _ => Rc::from("__main__"),
+        };
+        class_scope
+            .vars
+            .borrow_mut()
+            .insert(Rc::from("__module__"), Value::Str(module.clone()));
+        class_scope
+            .vars
+            .borrow_mut()
+            .insert(Rc::from("__doc__"), docstring(body));
+        self.exec_block(body, &class_scope)?;
+        let mut attrs = class_scope.vars.take();
+        // Only the dunder methods of a class-based metaclass are honoured, looked up
+        // through this attribute by `dunder`
+        if let Some(metaclass @ Value::Class(_)) = metaclass {
+            attrs.insert(Rc::from("__metaclass__"), metaclass);
+        }
+        self.new_class(name.clone(), bases, attrs, module)
+    }
+
+    /// Create a class, as a `class` statement or `type(name, bases, dict)` does.
+    pub fn new_class(
+        &mut self,
+        name: Rc<str>,
+        bases: Vec<Rc<Class>>,
+        attrs: IndexMap<Rc<str>, Value>,
+        module: Rc<str>,
+    ) -> PyResult<Value> {
+        let mro = linearize(&bases)?;
+        let class = Rc::new(Class {
+            name,
+            bases,
+            mro,
+            attrs: RefCell::new(attrs),
+            module,
+        });
+        for value in class.attrs.borrow().values() {
+            let functions = match value {
+                Value::Function(f) => vec![f.clone()],
+                Value::StaticMethod(inner) | Value::ClassMethod(inner) => match &**inner {
+                    Value::Function(f) => vec![f.clone()],
+                    _ => Vec::new(),
+                },
+                Value::Property(property) => [&property.fget, &property.fset, &property.fdel]
+                    .into_iter()
+                    .filter_map(|v| match v {
+                        Value::Function(f) => Some(f.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            for function in functions {
+                *function.class.borrow_mut() = Rc::downgrade(&class);
+            }
+        }
+        let hook = class
+            .mro
+            .iter()
+            .find_map(|base| base.attrs.borrow().get("__init_subclass__").cloned());
+        if let Some(hook) = hook {
+            let hook = match hook {
+                Value::ClassMethod(inner) => (*inner).clone(),
+                other => other,
+            };
+            self.call(&hook, Args::new(vec![Value::Class(class.clone())]))?;
+        }
+        Ok(Value::Class(class))
+    }
+
+    fn instantiate(&mut self, class: &Rc<Class>, args: Args) -> PyResult<Value> {
+        let instance = Rc::new(Instance {
+            class: class.clone(),
+            attrs: RefCell::new(IndexMap::new()),
+        });
+        let value = Value::Instance(instance.clone());
+        let is_exception = class.has_ancestor("BaseException");
+        if is_exception {
+            instance
+                .attrs
+                .borrow_mut()
+                .insert(Rc::from("args"), Value::tuple(args.pos.clone()));
+        }
+        if let Some(new) = class.lookup("__new__") {
+            let new = match new {
+                Value::StaticMethod(inner) => (*inner).clone(),
+                other => other,
+            };
+            let mut new_args = Args::new(vec![Value::Class(class.clone())]);
+            new_args.pos.extend(args.pos.iter().cloned());
+            new_args.kw = args.kw.clone();
+            let created = self.call(&new, new_args)?;
+            let same_class = matches!(&created, Value::Instance(i) if i.class.is_subclass(class));
+            if !same_class {
+                return Ok(created);
+            }
+            if let Some(init) = class.lookup("__init__") {
+                let init = self.bind(init, &created);
+                self.call(&init, args)?;
+            }
+            return Ok(created);
+        }
+        match class.lookup("__init__") {
+            Some(init) => {
+                let init = self.bind(init, &value);
+                let result = self.call(&init, args)?;
+                if !result.is_none() {
+                    return raise(
+                        "TypeError",
+                        format!(
+                            "__init__() should return None, not '{}'",
+                            result.type_name()
+                        ),
+                    );
+                }
+            }
+            None if !is_exception && (!args.pos.is_empty() || !args.kw.is_empty()) => {
+                return raise(
+                    "TypeError",
+                    format!("{}() takes no arguments", class.name),
+                );
+            }
+            None => {}
+        }
+        Ok(value)
+    }
+
+    /// `type(value)`.
+    pub fn type_of(&self, value: &Value) -> Value {
+        match value {
+            Value::Instance(instance) => Value::Class(instance.class.clone()),
+            other => Value::Type(other.type_of()),
+        }
+    }
+
+    // ---- Attributes ----
+
+    pub fn get_attr(&mut self, object: &Value, name: &Rc<str>) -> PyResult<Value> {
+        match object {
+            Value::Instance(instance) => self.instance_attr(object, instance, name),
+            Value::Class(class) => {
+                if let Some(value) = class.lookup(name) {
+                    return match value {
+                        Value::ClassMethod(function) => Ok(Value::Method(Rc::new((
+                            object.clone(),
+                            (*function).clone(),
+                        )))),
+                        Value::StaticMethod(function) => Ok((*function).clone()),
+                        other => self.descriptor_get(other, Value::None, object),
+                    };
+                }
+                match &**name {
+                    "__name__" | "__qualname__" => Ok(Value::Str(class.name.clone())),
+                    "__module__" => Ok(Value::Str(class.module.clone())),
+                    "__bases__" => Ok(Value::tuple(
+                        class.bases.iter().map(|b| Value::Class(b.clone())).collect(),
+                    )),
+                    "__mro__" => {
+                        let mut mro = vec![object.clone()];
+                        mro.extend(class.mro.iter().map(|c| Value::Class(c.clone())));
+                        mro.push(Value::Type(Type::Object));
+                        Ok(Value::tuple(mro))
+                    }
+                    "__dict__" => {
+                        let mut dict = Dict::default();
+                        for (key, value) in class.attrs.borrow().iter() {
+                            dict.set_str(key, value.clone());
+                        }
+                        Ok(Value::dict(dict))
+                    }
+                    "__class__" => Ok(Value::Type(Type::Type)),
+                    _ if methods::OBJECT_METHODS.contains(&&**name) => Ok(Value::BuiltinMethod(
+                        Rc::new((Value::Type(Type::Object), name.clone())),
+                    )),
+                    _ => raise(
+                        "AttributeError",
+                        format!("type object '{}' has no attribute '{name}'", class.name),
+                    ),
+                }
+            }
+            Value::Module(module) => match module.get(name) {
+                Some(value) => Ok(value),
+                None => {
+                    // Submodules imported later, like `os.path`
+                    let full = format!("{}.{name}", module.name);
+                    if let Some(submodule) = self.modules.get(&full) {
+                        return Ok(submodule.clone());
+                    }
+                    if &**name == "__dict__" {
+                        let mut dict = Dict::default();
+                        for (key, value) in module.scope.vars.borrow().iter() {
+                            dict.set_str(key, value.clone());
+                        }
+                        return Ok(Value::dict(dict));
+                    }
+                    raise(
+                        "AttributeError",
+                        format!("module '{}' has no attribute '{name}'", module.name),
+                    )
+                }
+            },
+            Value::Super(sup) => self.super_attr(&sup.0, &sup.1, name),
+            Value::Function(function) => {
+                if let Some(value) = function.attrs.borrow().get(name) {
+                    return Ok(value.clone());
+                }
+                match &**name {
+                    "__name__" => Ok(Value::Str(function.def.name.clone())),
+                    "__qualname__" => Ok(Value::str(&function.qualname())),
+                    "__doc__" => Ok(docstring(&function.def.body)),
+                    "__module__" => Ok(match function.scope.globals().vars.borrow().get("__name__") {
+                        Some(name) => name.clone(),
+                        None => Value::None,
+                    }),
+                    "__dict__" => {
+                        let mut dict = Dict::default();
+                        for (key, value) in function.attrs.borrow().iter() {
+                            dict.set_str(key, value.clone());
+                        }
+                        Ok(Value::dict(dict))
+                    }
+                    "__defaults__" => {
+                        let defaults: Vec<Value> =
+                            function.defaults.iter().flatten().cloned().collect();
+                        Ok(if defaults.is_empty() {
+                            Value::None
+                        } else {
+                            Value::tuple(defaults)
+                        })
+                    }
+                    "__class__" => Ok(Value::Type(Type::Function)),
+                    _ => raise(
+                        "AttributeError",
+                        format!("'function' object has no attribute '{name}'"),
+                    ),
+                }
+            }
+            Value::Method(method) => match &**name {
+                "__self__" => Ok(method.0.clone()),
+                "__func__" => Ok(method.1.clone()),
+                _ => self.get_attr(&method.1.clone(), name),
+            },
+            _ => match methods::attribute(self, object, name)? {
+                Some(value) => Ok(value),
+                None => raise(
+                    "AttributeError",
+                    match object {
+                        Value::Type(kind) => format!(
+                            "type object '{}' has no attribute '{name}'",
+                            kind.name()
+                        ),
+                        other => format!(
+                            "'{}' object has no attribute '{name}'",
+                            other.type_name()
+                        ),
+                    },
+                ),
+            },
+        }
+    }
+
+    fn instance_attr(&mut self, object: &Value, instance: &Rc<Instance>, name: &Rc<str>) -> PyResult<Value> {
+        let class_attr = instance.class.lookup(name);
+        if let Some(Value::Property(property)) = &class_attr {
+            if property.fget.is_none() {
+                return raise("AttributeError", format!("property '{name}' has no getter"));
+            }
+            return self.call(&property.fget, Args::new(vec![object.clone()]));
+        }
+        if let Some(value) = instance.attrs.borrow().get(name) {
+            return Ok(value.clone());
+        }
+        if let Some(value) = class_attr {
+            if let Value::Instance(_) = value {
+                let class = Value::Class(instance.class.clone());
+                return self.descriptor_get(value, object.clone(), &class);
+            }
+            return Ok(self.bind(value, object));
+        }
+        match &**name {
+            "__class__" => return Ok(Value::Class(instance.class.clone())),
+            "__dict__" => {
+                let mut dict = Dict::default();
+                for (key, value) in instance.attrs.borrow().iter() {
+                    dict.set_str(key, value.clone());
+                }
+                return Ok(Value::dict(dict));
+            }
+            "code" if instance.class.has_ancestor("SystemExit") => {
+                return Ok(match instance.attrs.borrow().get("args") {
+                    Some(Value::Tuple(args)) if args.len() == 1 => args[0].clone(),
+                    Some(Value::Tuple(args)) if args.is_empty() => Value::None,
+                    Some(args) => args.clone(),
+                    None => Value::None,
+                });
+            }
+            "__cause__" | "__context__" if instance.class.has_ancestor("BaseException") => {
+                return Ok(Value::None);
+            }
+            _ => {}
+        }
+        if methods::OBJECT_METHODS.contains(&&**name) {
+            return Ok(Value::BuiltinMethod(Rc::new((object.clone(), name.clone()))));
+        }
+        if let Some(getattr) = instance.class.lookup("__getattr__") {
+            let getattr = self.bind(getattr, object);
+            return self.call(&getattr, Args::new(vec![Value::Str(name.clone())]));
+        }
+        raise(
+            "AttributeError",
+            format!(
+                "'{}' object has no attribute '{name}'",
+                instance.class.name
+            ),
+        )
+    }
+
+    /// A class attribute as seen through `instance` (`None` when looked
+    /// up on the class): objects with `__get__` compute it.
+    fn descriptor_get(&mut self, value: Value, instance: Value, class: &Value) -> PyResult<Value> {
+        if let Value::Instance(descriptor) = &value
+            && let Some(get) = descriptor.class.lookup("__get__")
+        {
+            let get = self.bind(get, &value);
+            return self.call(&get, Args::new(vec![instance, class.clone()]));
+        }
+        Ok(value)
+    }
+
+    fn super_attr(&mut self, class: &Rc<Class>, object: &Value, name: &Rc<str>) -> PyResult<Value> {
+        let object_class = match object {
+            Value::Instance(instance) => instance.class.clone(),
+            Value::Class(class) => class.clone(),
+            _ => return raise("TypeError", "super() argument must be an instance or class"),
+        };
+        let mut chain = vec![object_class.clone()];
+        chain.extend(object_class.mro.iter().cloned());
+        let start = chain
+            .iter()
+            .position(|c| Rc::ptr_eq(c, class))
+            .map_or(chain.len(), |i| i + 1);
+        for ancestor in &chain[start..] {
+            let found = ancestor.attrs.borrow().get(name).cloned();
+            if let Some(value) = found {
+                if let (Value::Property(property), Value::Instance(_)) = (&value, object) {
+                    return self.call(&property.fget, Args::new(vec![object.clone()]));
+                }
+                return Ok(match object {
+                    Value::Class(_) => match value {
+                        Value::ClassMethod(function) => {
+                            Value::Method(Rc::new((object.clone(), (*function).clone())))
+                        }
+                        Value::StaticMethod(function) => (*function).clone(),
+                        other => other,
+                    },
+                    _ => self.bind(value, object),
+                });
+            }
+        }
+        if methods::OBJECT_METHODS.contains(&&**name) {
+            return Ok(Value::BuiltinMethod(Rc::new((object.clone(), name.clone()))));
+        }
+        raise(
+            "AttributeError",
+            format!("'super' object has no attribute '{name}'"),
+        )
+    }
+
+    pub fn set_attr(&mut self, object: &Value, name: &Rc<str>, value: Value) -> PyResult<()> {
+        match object {
+            Value::Instance(instance) => {
+                match instance.class.lookup(name) {
+                    Some(Value::Property(property)) => {
+                        if property.fset.is_none() {
+                            return raise(
+                                "AttributeError",
+                                format!(
+                                    "property '{name}' of '{}' object has no setter",
+                                    instance.class.name
+                                ),
+                            );
+                        }
+                        self.call(&property.fset, Args::new(vec![object.clone(), value]))?;
+                        return Ok(());
+                    }
+                    _ => {
+                        if let Some(setattr) = instance.class.lookup("__setattr__") {
+                            let setattr = self.bind(setattr, object);
+                            self.call(&setattr, Args::new(vec![Value::Str(name.clone()), value]))?;
+                            return Ok(());
+                        }
+                    }
+                }
+                instance.attrs.borrow_mut().insert(name.clone(), value);
+                Ok(())
+            }
+            Value::Class(class) => {
+                class.attrs.borrow_mut().insert(name.clone(), value);
+                Ok(())
+            }
+            Value::Function(function) => {
+                function.attrs.borrow_mut().insert(name.clone(), value);
+                Ok(())
+            }
+            Value::Module(module) => {
+                module.set(name, value);
+                Ok(())
+            }
+            other => raise(
+                "AttributeError",
+                format!(
+                    "'{}' object has no attribute '{name}' and no __dict__ for setting new attributes",
+                    other.type_name()
+                ),
+            ),
+        }
+    }
+
+    pub fn del_attr(&mut self, object: &Value, name: &Rc<str>) -> PyResult<()> {
+        let removed = match object {
+            Value::Instance(instance) => {
+                if let Some(Value::Property(property)) = instance.class.lookup(name)
+                    && !property.fdel.is_none()
+                {
+                    self.call(&property.fdel, Args::new(vec![object.clone()]))?;
+                    return Ok(());
+                }
+                if let Some(delattr) = instance.class.lookup("__delattr__") {
+                    let delattr = self.bind(delattr, object);
+                    self.call(&delattr, Args::new(vec![Value::Str(name.clone())]))?;
+                    return Ok(());
+                }
+                instance.attrs.borrow_mut().shift_remove(name).is_some()
+            }
+            Value::Class(class) => class.attrs.borrow_mut().shift_remove(name).is_some(),
+            Value::Function(function) => function.attrs.borrow_mut().remove(name).is_some(),
+            Value::Module(module) => module.scope.vars.borrow_mut().shift_remove(name).is_some(),
+            _ => false,
+        };
+        if removed {
+            Ok(())
+        } else {
+            raise(
+                "AttributeError",
+                format!("'{}' object has no attribute '{name}'", object.type_name()),
+            )
+        }
+    }
+
+    // ---- Exceptions ----
+
+    /// The exception instance an error carries, creating it if the
+    /// interpreter raised it by name.
+    pub fn exception_value(&mut self, err: &mut PyErr) -> Value {
+        if let Exc::Pending { kind, arg } = &err.exc {
+            let class = self.exception_class(kind);
+            let args = match arg {
+                Value::None if *kind != "KeyError" => Vec::new(),
+                arg => vec![arg.clone()],
+            };
+            let is_os_error = class.has_ancestor("OSError");
+            let mut attrs = IndexMap::new();
+            attrs.insert(Rc::from("args"), Value::tuple(args.clone()));
+            let instance = Rc::new(Instance {
+                class,
+                attrs: RefCell::new(attrs),
+            });
+            if is_os_error {
+                // Raised as `(errno, strerror, filename)` by `error::os_error`
+                let args = match args.as_slice() {
+                    [Value::Tuple(items)] => items.to_vec(),
+                    _ => args,
+                };
+                builtins::init_os_error(&instance, &args);
+            }
+            let instance = Value::Instance(instance);
+            err.exc = Exc::Raised(instance);
+        }
+        match &err.exc {
+            Exc::Raised(value) => value.clone(),
+            Exc::Pending { .. } => unreachable!(),
+        }
+    }
+
+    /// Whether an error is (a subclass of) the builtin exception `kind`.
+    pub fn is_error(&self, err: &PyErr, kind: &str) -> bool {
+        if let Exc::Pending { kind: pending, .. } = &err.exc {
+            let class = self.exception_class(pending);
+            return class.has_ancestor(kind);
+        }
+        match &err.exc {
+            Exc::Raised(Value::Instance(instance)) => instance.class.has_ancestor(kind),
+            _ => false,
+        }
+    }
+
+    fn exception_matches(&mut self, exc: &Value, kind: &Value) -> PyResult<bool> {
+        match kind {
+            Value::Class(class) => Ok(match exc {
+                Value::Instance(instance) => instance.class.is_subclass(class),
+                _ => false,
+            }),
+            Value::Tuple(kinds) => {
+                for kind in kinds.iter() {
+                    if self.exception_matches(exc, kind)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Value::Type(Type::Object) => Ok(true),
+            _ => raise(
+                "TypeError",
+                "catching classes that do not inherit from BaseException is not allowed",
+            ),
+        }
+    }
+
+    /// The exception `raise value` raises: an instance, or a class to call.
+    fn make_exception(&mut self, value: Value) -> PyResult<Value> {
+        match &value {
+            Value::Class(class) if class.has_ancestor("BaseException") => {
+                self.instantiate(class, Args::default())
+            }
+            Value::Instance(instance) if instance.class.has_ancestor("BaseException") => Ok(value),
+            _ => raise("TypeError", "exceptions must derive from BaseException"),
+        }
+    }
+
+    // ---- Imports ----
+
+    /// Import `a.b.c`, importing each parent first; returns the last.
+    fn import_dotted(&mut self, name: &str) -> PyResult<Value> {
+        let mut module = Value::None;
+        let mut prefix = String::new();
+        for part in name.split('.') {
+            if !prefix.is_empty() {
+                prefix.push('.');
+            }
+            prefix.push_str(part);
+            let child = self.import(&prefix)?;
+            if let Value::Module(parent) = &module
+                && parent.get(part).is_none()
+            {
+                parent.set(part, child.clone());
+            }
+            module = child;
+        }
+        Ok(module)
+    }
+
+    pub fn import(&mut self, name: &str) -> PyResult<Value> {
+        if let Some(module) = self.modules.get(name) {
+            return Ok(module.clone());
+        }
+        if let Some(module) = modules::load(self, name)? {
+            self.modules.insert(name.to_string(), module.clone());
+            return Ok(module);
+        }
+        if let Some(path) = self.find_module(name) {
+            let source = std::fs::read_to_string(&path)
+                .map_err(|e| super::error::os_error(&e, &path.to_string_lossy()))?;
+            let module = self.run_module(name, &source, &path.to_string_lossy())?;
+            if path.ends_with("__init__.py")
+                && let Value::Module(module) = &module
+            {
+                module.set("__package__", Value::str(name));
+                let dir = path.parent().unwrap().to_string_lossy().into_owned();
+                module.set("__path__", Value::list(vec![Value::str(&dir)]));
+            }
+            return Ok(module);
+        }
+        Err(PyErr::new(
+            "ModuleNotFoundError",
+            format!("No module named '{name}'"),
+        ))
+    }
+
+    /// Find `name` as a `.py` file or package on `sys.path`.
+    fn find_module(&mut self, name: &str) -> Option<PathBuf> {
+        let relative: PathBuf = name.split('.').collect();
+        let dirs: Vec<String> = match &self.sys_path {
+            Value::List(list) => list
+                .borrow()
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+        for dir in dirs {
+            let base = if dir.is_empty() {
+                PathBuf::from(".")
+            } else {
+                PathBuf::from(dir)
+            };
+            let file = base.join(&relative).with_extension("py");
+            if file.is_file() {
+                return Some(file);
+            }
+            let package = base.join(&relative).join("__init__.py");
+            if package.is_file() {
+                return Some(package);
+            }
+        }
+        None
+    }
+
+    fn import_from(
+        &mut self,
+        module: &str,
+        level: usize,
+        names: &[(Rc<str>, Option<Rc<str>>)],
+        scope: &Rc<Scope>,
+    ) -> PyResult<()> {
+        let full = if level > 0 {
+            let globals = scope.globals();
+            let vars = globals.vars.borrow();
+            let package = match (vars.get("__package__"), vars.get("__name__")) {
+                (Some(Value::Str(package)), _) => package.to_string(),
+                (_, Some(Value::Str(name))) => match name.rsplit_once('.') {
+                    Some((parent, _)) => parent.to_string(),
+                    None => String::new(),
+                },
+                _ => String::new(),
+            };
+            drop(vars);
+            let mut parts: Vec<&str> = package.split('.').filter(|p| !p.is_empty()).collect();
+            if parts.is_empty() || level - 1 > parts.len() {
+                return raise(
+                    "ImportError",
+                    "attempted relative import with no known parent package",
+                );
+            }
+            parts.truncate(parts.len() - (level - 1));
+            let mut base = parts.join(".");
+            if !module.is_empty() {
+                base.push('.');
+                base.push_str(module);
+            }
+            base
+        } else {
+            module.to_string()
+        };
+        let imported = self.import_dotted(&full)?;
+        let Value::Module(source) = &imported else {
+            return raise("ImportError", format!("'{full}' is not a module"));
+        };
+        if names.is_empty() {
+            let public: Vec<Rc<str>> = match source.get("__all__") {
+                Some(all) => self
+                    .collect(&all)?
+                    .into_iter()
+                    .filter_map(|v| match v {
+                        Value::Str(s) => Some(s),
+                        _ => None,
+                    })
+                    .collect(),
+                None => source
+                    .scope
+                    .vars
+                    .borrow()
+                    .keys()
+                    .filter(|k| !k.starts_with('_'))
+                    .cloned()
+                    .collect(),
+            };
+            for name in public {
+                if let Some(value) = source.get(&name) {
+                    self.store(&name, value, scope);
+                }
+            }
+            return Ok(());
+        }
+        for (name, alias) in names {
+            let value = match source.get(name) {
+                Some(value) => value,
+                None => match self.import(&format!("{full}.{name}")) {
+                    Ok(value) => value,
+                    Err(_) => {
+                        return raise(
+                            "ImportError",
+                            format!("cannot import name '{name}' from '{full}'"),
+                        );
+                    }
+                },
+            };
+            self.store(alias.as_ref().unwrap_or(name), value, scope);
+        }
+        Ok(())
+    }
+
+    /// Directory of the running script, for `sys.path[0]`.
+    pub fn script_dir(script: &str) -> String {
+        match Path::new(script).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_string_lossy().into_owned(),
+            _ => String::new(),
+        }
+    }
+}
+
+enum Sink {
+    List(Vec<Value>),
+    Set(Set),
+    Dict(Dict),
+}
+
+/// The docstring of a body: its first statement, if that is a string.
+fn docstring(body: &[Stmt]) -> Value {
+    match body.first().map(|stmt| &stmt.kind) {
+        Some(StmtKind::Expr(Expr::Const(value @ Value::Str(_)))) => value.clone(),
+        _ => Value::None,
+    }
+}
+
+fn join_names(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [one] => one.clone(),
+        [rest @ .., last] => format!("{} and {last}", rest.join(", ")),
+    }
+}
+
+/// C3 linearization of the bases' ancestors.
+fn linearize(bases: &[Rc<Class>]) -> PyResult<Vec<Rc<Class>>> {
+    let mut sequences: Vec<Vec<Rc<Class>>> = bases
+        .iter()
+        .map(|base| {
+            let mut sequence = vec![base.clone()];
+            sequence.extend(base.mro.iter().cloned());
+            sequence
+        })
+        .collect();
+    sequences.push(bases.to_vec());
+    let mut mro = Vec::new();
+    loop {
+        sequences.retain(|s| !s.is_empty());
+        if sequences.is_empty() {
+            return Ok(mro);
+        }
+        let candidate = sequences
+            .iter()
+            .map(|s| s[0].clone())
+            .find(|c| {
+                !sequences
+                    .iter()
+                    .any(|s| s[1..].iter().any(|x| Rc::ptr_eq(x, c)))
+            });
+        let Some(next) = candidate else {
+            return raise(
+                "TypeError",
+                "Cannot create a consistent method resolution order (MRO)",
+            );
+        };
+        for sequence in &mut sequences {
+            if Rc::ptr_eq(&sequence[0], &next) {
+                sequence.remove(0);
+            }
+        }
+        mro.push(next);
+    }
+}
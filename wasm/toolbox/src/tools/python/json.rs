@@ -0,0 +1,580 @@
+//! The `json` module: `dumps`/`loads` and their file variants.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use indexmap::IndexMap;
+
+use super::builtins::no_keywords;
+use super::error::{PyErr, PyResult, int_too_large, raise};
+use super::format::float_repr;
+use super::interp::Interp;
+use super::value::*;
+
+/// `json.<name>(...)`.
+pub fn call(interp: &mut Interp, name: &str, mut args: Args) -> PyResult<Value> {
+    match name {
+        "dumps" | "dump" => {
+            let value = args.arg(0, "obj").unwrap_or(Value::None);
+            let file = if name == "dump" {
+                Some(args.arg(1, "fp").unwrap_or(Value::None))
+            } else {
+                None
+            };
+            let options = Options::from_args(interp, &mut args)?;
+            no_keywords(name, &args)?;
+            let mut out = String::new();
+            let mut encoder = Encoder {
+                interp,
+                options,
+                active: Vec::new(),
+            };
+            encoder.encode(&value, 0, &mut out)?;
+            match file {
+                Some(file) => {
+                    let write = interp.get_attr(&file, &Rc::from("write"))?;
+                    interp.call(&write, Args::new(vec![Value::str(&out)]))?;
+                    Ok(Value::None)
+                }
+                None => Ok(Value::str(&out)),
+            }
+        }
+        "loads" | "load" => {
+            let source = args.arg(0, if name == "load" { "fp" } else { "s" });
+            let source = source.unwrap_or(Value::None);
+            let object_hook = args.take_kw("object_hook").unwrap_or(Value::None);
+            let parse_float = args.take_kw("parse_float").unwrap_or(Value::None);
+            let parse_int = args.take_kw("parse_int").unwrap_or(Value::None);
+            no_keywords(name, &args)?;
+            let text = if name == "load" {
+                let read = interp.get_attr(&source, &Rc::from("read"))?;
+                interp.call(&read, Args::default())?
+            } else {
+                source
+            };
+            let Value::Str(text) = text else {
+                return raise(
+                    "TypeError",
+                    format!(
+                        "the JSON object must be str, bytes or bytearray, not {}",
+                        text.type_name()
+                    ),
+                );
+            };
+            let mut decoder = Decoder {
+                interp,
+                text: &text,
+                pos: 0,
+                object_hook,
+                parse_float,
+                parse_int,
+            };
+            decoder.document()
+        }
+        _ => raise("AttributeError", format!("module 'json' has no attribute '{name}'")),
+    }
+}
+
+// ---- Encoding ----
+
+struct Options {
+    indent: Option<String>,
+    item_separator: String,
+    key_separator: String,
+    sort_keys: bool,
+    ensure_ascii: bool,
+    allow_nan: bool,
+    skip_keys: bool,
+    default: Value,
+}
+
+impl Options {
+    fn from_args(interp: &mut Interp, args: &mut Args) -> PyResult<Options> {
+        let indent = match args.take_kw("indent") {
+            None | Some(Value::None) => None,
+            Some(Value::Int(n)) => Some(" ".repeat(n.max(0) as usize)),
+            Some(Value::Str(s)) => Some(s.to_string()),
+            Some(other) => {
+                return raise(
+                    "TypeError",
+                    format!("indent must be an int or str, not {}", other.type_name()),
+                );
+            }
+        };
+        let (item_separator, key_separator) = match args.take_kw("separators") {
+            None | Some(Value::None) => {
+                let item = if indent.is_some() { "," } else { ", " };
+                (item.to_string(), ": ".to_string())
+            }
+            Some(separators) => match &interp.collect(&separators)?[..] {
+                [Value::Str(item), Value::Str(key)] => (item.to_string(), key.to_string()),
+                _ => return raise("ValueError", "separators must be a (item, key) pair of strings"),
+            },
+        };
+        let mut flag = |name: &str, default: bool| -> PyResult<bool> {
+            match args.take_kw(name) {
+                Some(value) => interp.truthy(&value),
+                None => Ok(default),
+            }
+        };
+        let sort_keys = flag("sort_keys", false)?;
+        let ensure_ascii = flag("ensure_ascii", true)?;
+        let allow_nan = flag("allow_nan", true)?;
+        let skip_keys = flag("skipkeys", false)?;
+        flag("check_circular", true)?;
+        let default = args.take_kw("default").unwrap_or(Value::None);
+        args.take_kw("cls");
+        Ok(Options {
+            indent,
+            item_separator,
+            key_separator,
+            sort_keys,
+            ensure_ascii,
+            allow_nan,
+            skip_keys,
+            default,
+        })
+    }
+}
+
+struct Encoder<'a> {
+    interp: &'a mut Interp,
+    options: Options,
+    /// Containers being encoded, to detect cycles.
+    active: Vec<usize>,
+}
+
+impl Encoder<'_> {
+    fn encode(&mut self, value: &Value, level: usize, out: &mut String) -> PyResult<()> {
+        match value {
+            Value::None => out.push_str("null"),
+            Value::Bool(true) => out.push_str("true"),
+            Value::Bool(false) => out.push_str("false"),
+            Value::Int(n) => out.push_str(&n.to_string()),
+            Value::Float(x) => out.push_str(&self.float(*x)?),
+            Value::Str(s) => self.string(s, out),
+            Value::List(_) | Value::Tuple(_) => {
+                let items = value.list_items();
+                self.enter(value)?;
+                if items.is_empty() {
+                    out.push_str("[]");
+                } else {
+                    out.push('[');
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 {
+                            out.push_str(&self.options.item_separator);
+                        }
+                        self.newline(level + 1, out);
+                        self.encode(item, level + 1, out)?;
+                    }
+                    self.newline(level, out);
+                    out.push(']');
+                }
+                self.active.pop();
+            }
+            Value::Dict(dict) => {
+                let mut items = dict.borrow().items();
+                self.enter(value)?;
+                let mut keyed = Vec::with_capacity(items.len());
+                for (key, item) in items.drain(..) {
+                    let key = match &key {
+                        Value::Str(s) => s.to_string(),
+                        Value::Int(n) => n.to_string(),
+                        Value::Bool(b) => b.to_string(),
+                        Value::None => "null".to_string(),
+                        Value::Float(x) => self.float(*x)?,
+                        _ if self.options.skip_keys => continue,
+                        other => {
+                            return raise(
+                                "TypeError",
+                                format!(
+                                    "keys must be str, int, float, bool or None, not {}",
+                                    other.type_name()
+                                ),
+                            );
+                        }
+                    };
+                    keyed.push((key, item));
+                }
+                if self.options.sort_keys {
+                    keyed.sort_by(|a, b| a.0.cmp(&b.0));
+                }
+                if keyed.is_empty() {
+                    out.push_str("{}");
+                } else {
+                    out.push('{');
+                    for (i, (key, item)) in keyed.iter().enumerate() {
+                        if i > 0 {
+                            out.push_str(&self.options.item_separator);
+                        }
+                        self.newline(level + 1, out);
+                        self.string(key, out);
+                        out.push_str(&self.options.key_separator);
+                        self.encode(item, level + 1, out)?;
+                    }
+                    self.newline(level, out);
+                    out.push('}');
+                }
+                self.active.pop();
+            }
+            other => {
+                if self.options.default.is_none() {
+                    return raise(
+                        "TypeError",
+                        format!(
+                            "Object of type {} is not JSON serializable",
+                            other.type_name()
+                        ),
+                    );
+                }
+                self.enter(value)?;
+                let default = self.options.default.clone();
+                let replacement = self.interp.call(&default, Args::new(vec![other.clone()]))?;
+                self.encode(&replacement, level, out)?;
+                self.active.pop();
+            }
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, value: &Value) -> PyResult<()> {
+        let id = value.identity().unwrap_or(0);
+        if id != 0 && self.active.contains(&id) {
+            return raise("ValueError", "Circular reference detected");
+        }
+        self.active.push(id);
+        Ok(())
+    }
+
+    fn newline(&self, level: usize, out: &mut String) {
+        if let Some(indent) = &self.options.indent {
+            out.push('\n');
+            for _ in 0..level {
+                out.push_str(indent);
+            }
+        }
+    }
+
+    fn float(&self, x: f64) -> PyResult<String> {
+        if x.is_finite() {
+            return Ok(float_repr(x));
+        }
+        if !self.options.allow_nan {
+            return raise(
+                "ValueError",
+                format!("Out of range float values are not JSON compliant: {}", float_repr(x)),
+            );
+        }
+        Ok(if x.is_nan() {
+            "NaN"
+        } else if x > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }
+        .to_string())
+    }
+
+    fn string(&self, s: &str, out: &mut String) {
+        out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                '\x08' => out.push_str("\\b"),
+                '\x0c' => out.push_str("\\f"),
+                c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c if self.options.ensure_ascii && !c.is_ascii() => {
+                    let mut units = [0u16; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        out.push_str(&format!("\\u{unit:04x}"));
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+// ---- Decoding ----
+
+struct Decoder<'a> {
+    interp: &'a mut Interp,
+    text: &'a str,
+    /// Byte offset of the next character.
+    pos: usize,
+    object_hook: Value,
+    parse_float: Value,
+    parse_int: Value,
+}
+
+impl Decoder<'_> {
+    fn document(&mut self) -> PyResult<Value> {
+        self.skip_whitespace();
+        let value = self.value()?;
+        self.skip_whitespace();
+        if self.pos < self.text.len() {
+            return Err(self.error("Extra data", self.pos));
+        }
+        Ok(value)
+    }
+
+    /// `JSONDecodeError` with the line and column of byte offset `at`.
+    fn error(&self, msg: &str, at: usize) -> Box<PyErr> {
+        let before = &self.text[..at];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rfind('\n').map_or(before.chars().count(), |i| before[i + 1..].chars().count()) + 1;
+        let char_pos = before.chars().count();
+        let message = format!("{msg}: line {line} column {column} (char {char_pos})");
+        let mut attrs = IndexMap::new();
+        attrs.insert(Rc::from("args"), Value::tuple(vec![Value::str(&message)]));
+        attrs.insert(Rc::from("msg"), Value::str(msg));
+        attrs.insert(Rc::from("doc"), Value::str(self.text));
+        attrs.insert(Rc::from("pos"), Value::Int(char_pos as i64));
+        attrs.insert(Rc::from("lineno"), Value::Int(line as i64));
+        attrs.insert(Rc::from("colno"), Value::Int(column as i64));
+        PyErr::raised(Value::Instance(Rc::new(Instance {
+            class: self.interp.exception_class("JSONDecodeError"),
+            attrs: RefCell::new(attrs),
+        })))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> PyResult<Value> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => {
+                self.pos += 1;
+                Ok(Value::str(&self.string()?))
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => {
+                let rest = &self.text[self.pos..];
+                for (word, value) in [
+                    ("null", Value::None),
+                    ("true", Value::Bool(true)),
+                    ("false", Value::Bool(false)),
+                    ("NaN", Value::Float(f64::NAN)),
+                    ("Infinity", Value::Float(f64::INFINITY)),
+                    ("-Infinity", Value::Float(f64::NEG_INFINITY)),
+                ] {
+                    if rest.starts_with(word) {
+                        self.pos += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(self.error("Expecting value", self.pos))
+            }
+        }
+    }
+
+    fn object(&mut self) -> PyResult<Value> {
+        self.pos += 1;
+        let mut dict = Dict::default();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return self.finish_object(dict);
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error(
+                    "Expecting property name enclosed in double quotes",
+                    self.pos,
+                ));
+            }
+            self.pos += 1;
+            let key = self.string()?;
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.error("Expecting ':' delimiter", self.pos));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let value = self.value()?;
+            dict.set_str(&key, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                }
+                Some(b'}') => {
+                    self.pos += 1;
+                    return self.finish_object(dict);
+                }
+                _ => return Err(self.error("Expecting ',' delimiter", self.pos)),
+            }
+        }
+    }
+
+    fn finish_object(&mut self, dict: Dict) -> PyResult<Value> {
+        let dict = Value::dict(dict);
+        if self.object_hook.is_none() {
+            return Ok(dict);
+        }
+        let hook = self.object_hook.clone();
+        self.interp.call(&hook, Args::new(vec![dict]))
+    }
+
+    fn array(&mut self) -> PyResult<Value> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::list(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::list(items));
+                }
+                _ => return Err(self.error("Expecting ',' delimiter", self.pos)),
+            }
+        }
+    }
+
+    /// The rest of a string whose opening quote was consumed.
+    fn string(&mut self) -> PyResult<String> {
+        let start = self.pos - 1;
+        let mut out = String::new();
+        loop {
+            let rest = &self.text[self.pos..];
+            let Some(i) = rest.find(|c: char| c == '"' || c == '\\' || (c as u32) < 0x20) else {
+                return Err(self.error("Unterminated string starting at", start));
+            };
+            out.push_str(&rest[..i]);
+            self.pos += i;
+            match self.text.as_bytes()[self.pos] {
+                b'"' => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                b'\\' => {
+                    let escape = self.text.as_bytes().get(self.pos + 1).copied();
+                    let c = match escape {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\x08',
+                        Some(b'f') => '\x0c',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let unit = self.hex4(self.pos + 2)?;
+                            self.pos += 6;
+                            let c = if (0xd800..0xdc00).contains(&unit)
+                                && self.text[self.pos..].starts_with("\\u")
+                                && let Ok(low) = self.hex4(self.pos + 2)
+                                && (0xdc00..0xe000).contains(&low)
+                            {
+                                self.pos += 6;
+                                char::from_u32(0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00))
+                            } else {
+                                char::from_u32(unit)
+                            };
+                            out.push(c.unwrap_or('\u{fffd}'));
+                            continue;
+                        }
+                        _ => return Err(self.error("Invalid \\escape", self.pos)),
+                    };
+                    out.push(c);
+                    self.pos += 2;
+                }
+                _ => return Err(self.error("Invalid control character at", self.pos)),
+            }
+        }
+    }
+
+    fn hex4(&self, at: usize) -> PyResult<u32> {
+        self.text
+            .get(at..at + 4)
+            .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("Invalid \\uXXXX escape", at - 1))
+    }
+
+    fn number(&mut self) -> PyResult<Value> {
+        let start = self.pos;
+        let bytes = self.text.as_bytes();
+        let mut end = start;
+        if bytes[end] == b'-' {
+            end += 1;
+        }
+        let digits_start = end;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+        }
+        if end == digits_start {
+            if self.text[end..].starts_with("Infinity") {
+                self.pos = end + "Infinity".len();
+                return Ok(Value::Float(f64::NEG_INFINITY));
+            }
+            return Err(self.error("Expecting value", start));
+        }
+        // A leading zero ends the number
+        if bytes[digits_start] == b'0' {
+            end = digits_start + 1;
+        }
+        let mut is_float = false;
+        if end + 1 < bytes.len() && bytes[end] == b'.' && bytes[end + 1].is_ascii_digit() {
+            is_float = true;
+            end += 1;
+            while end < bytes.len() && bytes[end].is_ascii_digit() {
+                end += 1;
+            }
+        }
+        if end < bytes.len() && matches!(bytes[end], b'e' | b'E') {
+            let mut exponent = end + 1;
+            if exponent < bytes.len() && matches!(bytes[exponent], b'+' | b'-') {
+                exponent += 1;
+            }
+            if exponent < bytes.len() && bytes[exponent].is_ascii_digit() {
+                is_float = true;
+                end = exponent;
+                while end < bytes.len() && bytes[end].is_ascii_digit() {
+                    end += 1;
+                }
+            }
+        }
+        self.pos = end;
+        let literal = &self.text[start..end];
+        let hook = if is_float {
+            &self.parse_float
+        } else {
+            &self.parse_int
+        };
+        if !hook.is_none() {
+            let hook = hook.clone();
+            return self.interp.call(&hook, Args::new(vec![Value::str(literal)]));
+        }
+        if is_float {
+            return Ok(Value::Float(literal.parse().unwrap_or(f64::NAN)));
+        }
+        match literal.parse::<i64>() {
+            Ok(n) => Ok(Value::Int(n)),
+            Err(_) => int_too_large(),
+        }
+    }
+}
@@ -0,0 +1,463 @@
+//! Tokenizer: turns source into tokens, with `Indent`/`Dedent` marking
+//! block structure and `Newline` ending each logical line.
+
+use std::rc::Rc;
+
+/// A syntax error at a line of the source.
+#[derive(Debug, Clone)]
+pub struct SyntaxError {
+    pub msg: String,
+    pub line: usize,
+}
+
+impl SyntaxError {
+    pub fn new(msg: impl Into<String>, line: usize) -> Self {
+        Self {
+            msg: msg.into(),
+            line,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tok {
+    Name(Rc<str>),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    /// The body of an f-string, escapes still in place; the parser splits
+    /// it into literal text and fields.
+    FStr { body: String, raw: bool },
+    Op(&'static str),
+    Newline,
+    Indent,
+    Dedent,
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub tok: Tok,
+    pub line: usize,
+}
+
+/// Operators, longest first so the first match is the right one.
+const OPERATORS: &[&str] = &[
+    "**=", "//=", ">>=", "<<=", "...", "->", ":=", "**", "//", "<<", ">>", "<=", ">=", "==", "!=",
+    "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "@=", "+", "-", "*", "/", "%", "@", "&", "|",
+    "^", "~", "<", ">", "(", ")", "[", "]", "{", "}", ",", ":", ".", ";", "=",
+];
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, SyntaxError> {
+    let mut lexer = Lexer {
+        chars: source.chars().collect(),
+        pos: 0,
+        line: 1,
+        tokens: Vec::new(),
+        indents: vec![0],
+        brackets: Vec::new(),
+        line_start: true,
+    };
+    lexer.run()?;
+    Ok(lexer.tokens)
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    tokens: Vec<Token>,
+    indents: Vec<usize>,
+    /// Open brackets and their lines; newlines inside them are ignored.
+    brackets: Vec<(char, usize)>,
+    line_start: bool,
+}
+
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn push(&mut self, tok: Tok) {
+        self.tokens.push(Token {
+            tok,
+            line: self.line,
+        });
+    }
+
+    fn error<T>(&self, msg: impl Into<String>) -> Result<T, SyntaxError> {
+        Err(SyntaxError::new(msg, self.line))
+    }
+
+    fn run(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            if self.line_start && self.brackets.is_empty() {
+                self.line_start = false;
+                if !self.indentation()? {
+                    break;
+                }
+            }
+            let Some(c) = self.peek() else { break };
+            match c {
+                '\n' => {
+                    self.pos += 1;
+                    if self.brackets.is_empty() {
+                        self.push(Tok::Newline);
+                        self.line_start = true;
+                    }
+                    self.line += 1;
+                }
+                ' ' | '\t' | '\r' | '\x0c' => self.pos += 1,
+                '#' => self.skip_comment(),
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('\n') => {
+                            self.pos += 1;
+                            self.line += 1;
+                        }
+                        Some('\r') if self.peek_at(1) == Some('\n') => {
+                            self.pos += 2;
+                            self.line += 1;
+                        }
+                        _ => return self.error("unexpected character after line continuation character"),
+                    }
+                }
+                c if c.is_ascii_digit() => self.number()?,
+                '.' if self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) => self.number()?,
+                c if c.is_alphabetic() || c == '_' => self.name_or_string()?,
+                '"' | '\'' => self.string(false, false)?,
+                _ => self.operator()?,
+            }
+        }
+        if let Some(&(open, line)) = self.brackets.last() {
+            return Err(SyntaxError::new(format!("'{open}' was never closed"), line));
+        }
+        if self
+            .tokens
+            .last()
+            .is_some_and(|t| !matches!(t.tok, Tok::Newline | Tok::Dedent))
+        {
+            self.push(Tok::Newline);
+        }
+        while self.indents.len() > 1 {
+            self.indents.pop();
+            self.push(Tok::Dedent);
+        }
+        self.push(Tok::Eof);
+        Ok(())
+    }
+
+    /// Measure the indentation of a new line and emit `Indent`/`Dedent`.
+    /// Blank and comment-only lines are skipped. Returns false at the end
+    /// of the source.
+    fn indentation(&mut self) -> Result<bool, SyntaxError> {
+        loop {
+            let mut column = 0;
+            while let Some(c) = self.peek() {
+                match c {
+                    ' ' => column += 1,
+                    '\t' => column = (column / 8 + 1) * 8,
+                    '\x0c' => column = 0,
+                    _ => break,
+                }
+                self.pos += 1;
+            }
+            match self.peek() {
+                None => return Ok(false),
+                Some('\n') => {
+                    self.pos += 1;
+                    self.line += 1;
+                }
+                Some('\r') if self.peek_at(1) == Some('\n') => {
+                    self.pos += 2;
+                    self.line += 1;
+                }
+                Some('#') => self.skip_comment(),
+                Some(_) => {
+                    let current = *self.indents.last().unwrap();
+                    if column > current {
+                        self.indents.push(column);
+                        self.push(Tok::Indent);
+                    } else {
+                        while column < *self.indents.last().unwrap() {
+                            self.indents.pop();
+                            self.push(Tok::Dedent);
+                        }
+                        if column != *self.indents.last().unwrap() {
+                            return self
+                                .error("unindent does not match any outer indentation level");
+                        }
+                    }
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<(), SyntaxError> {
+        let start = self.pos;
+        if self.peek() == Some('0')
+            && let Some(radix) = match self.peek_at(1) {
+                Some('x' | 'X') => Some(16),
+                Some('o' | 'O') => Some(8),
+                Some('b' | 'B') => Some(2),
+                _ => None,
+            }
+        {
+            self.pos += 2;
+            let digits_start = self.pos;
+            while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+                self.pos += 1;
+            }
+            let digits: String = self.chars[digits_start..self.pos]
+                .iter()
+                .filter(|&&c| c != '_')
+                .collect();
+            return match i64::from_str_radix(&digits, radix) {
+                Ok(n) => {
+                    self.push(Tok::Int(n));
+                    Ok(())
+                }
+                Err(_) => self.error("invalid number literal"),
+            };
+        }
+
+        let mut is_float = false;
+        let digits = |lexer: &mut Lexer| {
+            while lexer
+                .peek()
+                .is_some_and(|c| c.is_ascii_digit() || c == '_')
+            {
+                lexer.pos += 1;
+            }
+        };
+        digits(self);
+        if self.peek() == Some('.') {
+            is_float = true;
+            self.pos += 1;
+            digits(self);
+        }
+        if matches!(self.peek(), Some('e' | 'E'))
+            && (self.peek_at(1).is_some_and(|c| c.is_ascii_digit())
+                || (matches!(self.peek_at(1), Some('+' | '-'))
+                    && self.peek_at(2).is_some_and(|c| c.is_ascii_digit())))
+        {
+            is_float = true;
+            self.pos += 2;
+            digits(self);
+        }
+        if matches!(self.peek(), Some('j' | 'J')) {
+            return self.error("complex numbers are not supported");
+        }
+        if self.peek().is_some_and(|c| c.is_alphabetic() || c == '_') {
+            return self.error("invalid decimal literal");
+        }
+        let text: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|&&c| c != '_')
+            .collect();
+        if is_float {
+            match text.parse::<f64>() {
+                Ok(f) => self.push(Tok::Float(f)),
+                Err(_) => return self.error("invalid decimal literal"),
+            }
+        } else {
+            match text.parse::<i64>() {
+                Ok(n) => self.push(Tok::Int(n)),
+                Err(_) => {
+                    return self.error("integers beyond 64 bits are not supported");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn name_or_string(&mut self) -> Result<(), SyntaxError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        if matches!(self.peek(), Some('"' | '\''))
+            && word.len() <= 2
+            && word.chars().all(|c| "rRbBuUfF".contains(c))
+        {
+            let lower = word.to_ascii_lowercase();
+            let raw = lower.contains('r');
+            let fstring = lower.contains('f');
+            if lower.chars().filter(|&c| c == 'r').count() > 1
+                || (fstring && lower.contains('b'))
+                || lower == "ur"
+                || lower == "ru"
+                || lower == "uu"
+            {
+                return self.error("invalid string prefix");
+            }
+            return self.string(raw, fstring);
+        }
+        self.push(Tok::Name(word.into()));
+        Ok(())
+    }
+
+    fn string(&mut self, raw: bool, fstring: bool) -> Result<(), SyntaxError> {
+        let quote = self.peek().unwrap();
+        let start_line = self.line;
+        let triple = self.peek_at(1) == Some(quote) && self.peek_at(2) == Some(quote);
+        self.pos += if triple { 3 } else { 1 };
+        let mut body = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                let msg = if triple {
+                    "unterminated triple-quoted string literal"
+                } else {
+                    "unterminated string literal"
+                };
+                return Err(SyntaxError::new(msg, start_line));
+            };
+            if c == quote {
+                if !triple {
+                    self.pos += 1;
+                    break;
+                }
+                if self.peek_at(1) == Some(quote) && self.peek_at(2) == Some(quote) {
+                    self.pos += 3;
+                    break;
+                }
+            }
+            if c == '\n' {
+                if !triple {
+                    return Err(SyntaxError::new("unterminated string literal", start_line));
+                }
+                self.line += 1;
+            }
+            if c == '\\'
+                && let Some(next) = self.peek_at(1)
+            {
+                body.push(c);
+                body.push(next);
+                if next == '\n' {
+                    self.line += 1;
+                }
+                self.pos += 2;
+                continue;
+            }
+            body.push(c);
+            self.pos += 1;
+        }
+        let tok = if fstring {
+            Tok::FStr { body, raw }
+        } else if raw {
+            Tok::Str(body)
+        } else {
+            Tok::Str(unescape(&body).map_err(|msg| SyntaxError::new(msg, start_line))?)
+        };
+        self.tokens.push(Token {
+            tok,
+            line: start_line,
+        });
+        Ok(())
+    }
+
+    fn operator(&mut self) -> Result<(), SyntaxError> {
+        let Some(op) = OPERATORS.iter().copied().find(|op| {
+            op.chars()
+                .enumerate()
+                .all(|(i, c)| self.peek_at(i) == Some(c))
+        }) else {
+            let c = self.peek().unwrap();
+            return self.error(format!("invalid character '{c}' (U+{:04X})", c as u32));
+        };
+        match op {
+            "(" | "[" | "{" => self.brackets.push((op.chars().next().unwrap(), self.line)),
+            ")" | "]" | "}" => {
+                let close = op.chars().next().unwrap();
+                match self.brackets.pop() {
+                    Some((open, _)) if matches!((open, close), ('(', ')') | ('[', ']') | ('{', '}')) => {}
+                    Some((open, _)) => {
+                        return self.error(format!(
+                            "closing parenthesis '{close}' does not match opening parenthesis '{open}'"
+                        ));
+                    }
+                    None => return self.error(format!("unmatched '{close}'")),
+                }
+            }
+            _ => {}
+        }
+        self.pos += op.chars().count();
+        self.push(Tok::Op(op));
+        Ok(())
+    }
+}
+
+/// Process the backslash escapes of a (non-raw) string literal.
+pub fn unescape(body: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let Some(e) = chars.next() else {
+            out.push('\\');
+            break;
+        };
+        match e {
+            '\n' => {}
+            '\\' => out.push('\\'),
+            '\'' => out.push('\''),
+            '"' => out.push('"'),
+            'a' => out.push('\x07'),
+            'b' => out.push('\x08'),
+            'f' => out.push('\x0c'),
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            'v' => out.push('\x0b'),
+            '0'..='7' => {
+                let mut value = e.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            value = value * 8 + d;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                out.push(char::from_u32(value).unwrap_or('\u{fffd}'));
+            }
+            'x' | 'u' | 'U' => {
+                let len = match e {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                let digits: String = (0..len).filter_map(|_| chars.next()).collect();
+                let value = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|_| digits.len() == len)
+                    .ok_or_else(|| format!("truncated \\{e}{digits} escape"))?;
+                let c = char::from_u32(value)
+                    .ok_or_else(|| format!("illegal Unicode character \\{e}{digits}"))?;
+                out.push(c);
+            }
+            other => {
+                out.push('\\');
+                out.push(other);
+            }
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,355 @@
+"""Container datatypes: deque and namedtuple, plus Counter, OrderedDict
+and defaultdict."""
+
+from _collections import Counter, OrderedDict, defaultdict
+
+__all__ = ["ChainMap", "Counter", "OrderedDict", "defaultdict", "deque", "namedtuple"]
+
+
+class deque:
+    """A double-ended queue, with an optional maximum length."""
+
+    def __init__(self, iterable=(), maxlen=None):
+        if maxlen is not None and maxlen < 0:
+            raise ValueError("maxlen must be non-negative")
+        self._maxlen = maxlen
+        self._items = []
+        # Items before _head have been popped from the left
+        self._head = 0
+        self.extend(iterable)
+
+    @property
+    def maxlen(self):
+        return self._maxlen
+
+    def _live(self):
+        if self._head:
+            del self._items[:self._head]
+            self._head = 0
+        return self._items
+
+    def _trim_left(self):
+        if self._maxlen is not None:
+            while len(self) > self._maxlen:
+                self.popleft()
+
+    def _trim_right(self):
+        if self._maxlen is not None:
+            while len(self) > self._maxlen:
+                self.pop()
+
+    def append(self, x):
+        self._items.append(x)
+        self._trim_left()
+
+    def appendleft(self, x):
+        self._live().insert(0, x)
+        self._trim_right()
+
+    def extend(self, iterable):
+        for x in list(iterable):
+            self.append(x)
+
+    def extendleft(self, iterable):
+        for x in list(iterable):
+            self.appendleft(x)
+
+    def pop(self):
+        if not len(self):
+            raise IndexError("pop from an empty deque")
+        return self._items.pop()
+
+    def popleft(self):
+        if not len(self):
+            raise IndexError("pop from an empty deque")
+        x = self._items[self._head]
+        self._items[self._head] = None
+        self._head += 1
+        if self._head > 32 and self._head * 2 > len(self._items):
+            self._live()
+        return x
+
+    def clear(self):
+        self._items = []
+        self._head = 0
+
+    def copy(self):
+        return deque(self, self._maxlen)
+
+    def count(self, x):
+        return self._live().count(x)
+
+    def index(self, x, *args):
+        return self._live().index(x, *args)
+
+    def insert(self, i, x):
+        if self._maxlen is not None and len(self) >= self._maxlen:
+            raise IndexError("deque already at its maximum size")
+        self._live().insert(i, x)
+
+    def remove(self, value):
+        items = self._live()
+        if value not in items:
+            raise ValueError(f"{value!r} is not in deque")
+        items.remove(value)
+
+    def reverse(self):
+        self._live().reverse()
+
+    def rotate(self, n=1):
+        items = self._live()
+        if items:
+            n %= len(items)
+            if n:
+                items[:] = items[-n:] + items[:-n]
+
+    def __len__(self):
+        return len(self._items) - self._head
+
+    def __bool__(self):
+        return len(self) > 0
+
+    def __iter__(self):
+        return iter(self._live()[:])
+
+    def __reversed__(self):
+        return reversed(self._live()[:])
+
+    def __contains__(self, x):
+        return x in self._live()
+
+    def __getitem__(self, i):
+        if isinstance(i, slice):
+            raise TypeError("sequence index must be integer, not 'slice'")
+        try:
+            return self._live()[i]
+        except IndexError:
+            raise IndexError("deque index out of range") from None
+
+    def __setitem__(self, i, x):
+        self._live()[i] = x
+
+    def __delitem__(self, i):
+        del self._live()[i]
+
+    def __eq__(self, other):
+        if not isinstance(other, deque):
+            return NotImplemented
+        return self._live() == other._live()
+
+    def __add__(self, other):
+        result = self.copy()
+        result.extend(other)
+        return result
+
+    def __iadd__(self, other):
+        self.extend(other)
+        return self
+
+    def __repr__(self):
+        if self._maxlen is None:
+            return f"deque({self._live()!r})"
+        return f"deque({self._live()!r}, maxlen={self._maxlen})"
+
+    __hash__ = None
+
+
+def namedtuple(typename, field_names, *, rename=False, defaults=None, module=None):
+    """A class of tuple-like records with named fields."""
+    if isinstance(field_names, str):
+        field_names = field_names.replace(",", " ").split()
+    fields = tuple(str(name) for name in field_names)
+    for name in (typename,) + fields:
+        if not name.isidentifier():
+            raise ValueError(f"Type names and field names must be valid identifiers: {name!r}")
+    seen = set()
+    for name in fields:
+        if name.startswith("_") and not rename:
+            raise ValueError(f"Field names cannot start with an underscore: {name!r}")
+        if name in seen:
+            raise ValueError(f"Encountered duplicate field name: {name!r}")
+        seen.add(name)
+    field_defaults = {}
+    if defaults is not None:
+        defaults = tuple(defaults)
+        if len(defaults) > len(fields):
+            raise TypeError("Got more default values than field names")
+        field_defaults = dict(zip(fields[len(fields) - len(defaults):], defaults))
+
+    def __init__(self, *args, **kwargs):
+        if len(args) > len(fields):
+            raise TypeError(
+                f"{typename}.__new__() takes {len(fields) + 1} positional arguments "
+                f"but {len(args) + 1} were given"
+            )
+        values = list(args)
+        for name in fields[len(args):]:
+            if name in kwargs:
+                values.append(kwargs.pop(name))
+            elif name in field_defaults:
+                values.append(field_defaults[name])
+            else:
+                missing = [n for n in fields[len(values):] if n not in kwargs and n not in field_defaults]
+                raise TypeError(
+                    f"{typename}.__new__() missing {len(missing)} required positional "
+                    f"argument{'s' if len(missing) > 1 else ''}: "
+                    + ", ".join(repr(n) for n in missing)
+                )
+        if kwargs:
+            name = next(iter(kwargs))
+            if name in fields:
+                raise TypeError(f"{typename}.__new__() got multiple values for argument '{name}'")
+            raise TypeError(f"{typename}.__new__() got an unexpected keyword argument '{name}'")
+        object.__setattr__(self, "_values", tuple(values))
+
+    def __setattr__(self, name, value):
+        raise AttributeError(f"can't set attribute")
+
+    def __repr__(self):
+        parts = ", ".join(f"{name}={value!r}" for name, value in zip(fields, self._values))
+        return f"{typename}({parts})"
+
+    def __iter__(self):
+        return iter(self._values)
+
+    def __len__(self):
+        return len(fields)
+
+    def __getitem__(self, index):
+        return self._values[index]
+
+    def __eq__(self, other):
+        if isinstance(other, tuple):
+            return self._values == other
+        if hasattr(other, "_values") and hasattr(other, "_fields"):
+            return self._values == other._values
+        return NotImplemented
+
+    def __ne__(self, other):
+        return not self == other
+
+    def __lt__(self, other):
+        return self._values < tuple(other)
+
+    def __le__(self, other):
+        return self._values <= tuple(other)
+
+    def __gt__(self, other):
+        return self._values > tuple(other)
+
+    def __ge__(self, other):
+        return self._values >= tuple(other)
+
+    def __hash__(self):
+        return hash(self._values)
+
+    def __contains__(self, value):
+        return value in self._values
+
+    def __add__(self, other):
+        return self._values + tuple(other)
+
+    def _asdict(self):
+        return dict(zip(fields, self._values))
+
+    def _replace(self, **changes):
+        values = self._asdict()
+        for name in changes:
+            if name not in values:
+                raise ValueError(f"Got unexpected field names: {list(changes)!r}")
+        values.update(changes)
+        return cls(**values)
+
+    def count(self, value):
+        return self._values.count(value)
+
+    def index(self, value, *args):
+        return self._values.index(value, *args)
+
+    def _make(klass, iterable):
+        return klass(*iterable)
+
+    namespace = {
+        "__init__": __init__,
+        "__setattr__": __setattr__,
+        "__repr__": __repr__,
+        "__iter__": __iter__,
+        "__len__": __len__,
+        "__getitem__": __getitem__,
+        "__eq__": __eq__,
+        "__ne__": __ne__,
+        "__lt__": __lt__,
+        "__le__": __le__,
+        "__gt__": __gt__,
+        "__ge__": __ge__,
+        "__hash__": __hash__,
+        "__contains__": __contains__,
+        "__add__": __add__,
+        "_asdict": _asdict,
+        "_replace": _replace,
+        "_make": classmethod(_make),
+        "count": count,
+        "index": index,
+        "_fields": fields,
+        "_field_defaults": field_defaults,
+        "__match_args__": fields,
+    }
+    for i, name in enumerate(fields):
+        namespace[name] = property(lambda self, i=i: self._values[i])
+    cls = type(typename, (), namespace)
+    cls.__module__ = module if module is not None else "__main__"
+    return cls
+
+
+class ChainMap:
+    """Several mappings searched in order as one."""
+
+    def __init__(self, *maps):
+        self.maps = list(maps) or [{}]
+
+    def __getitem__(self, key):
+        for mapping in self.maps:
+            if key in mapping:
+                return mapping[key]
+        raise KeyError(key)
+
+    def get(self, key, default=None):
+        return self[key] if key in self else default
+
+    def __contains__(self, key):
+        return any(key in m for m in self.maps)
+
+    def __setitem__(self, key, value):
+        self.maps[0][key] = value
+
+    def __delitem__(self, key):
+        del self.maps[0][key]
+
+    def keys(self):
+        seen = {}
+        for mapping in reversed(self.maps):
+            for key in mapping:
+                seen[key] = None
+        return list(seen)
+
+    def __iter__(self):
+        return iter(self.keys())
+
+    def __len__(self):
+        return len(self.keys())
+
+    def items(self):
+        return [(key, self[key]) for key in self.keys()]
+
+    def values(self):
+        return [self[key] for key in self.keys()]
+
+    def new_child(self, m=None):
+        return ChainMap({} if m is None else m, *self.maps)
+
+    @property
+    def parents(self):
+        return ChainMap(*self.maps[1:])
+
+    def __repr__(self):
+        return f"ChainMap({', '.join(repr(m) for m in self.maps)})"
@@ -0,0 +1,227 @@
+"""CSV file reading and writing."""
+
+import _csv
+
+__all__ = [
+    "QUOTE_MINIMAL", "QUOTE_ALL", "QUOTE_NONNUMERIC", "QUOTE_NONE", "Error", "Dialect",
+    "excel", "excel_tab", "unix_dialect", "reader", "writer", "DictReader", "DictWriter",
+    "register_dialect", "get_dialect", "list_dialects", "field_size_limit",
+]
+
+QUOTE_MINIMAL, QUOTE_ALL, QUOTE_NONNUMERIC, QUOTE_NONE = range(4)
+
+
+class Error(Exception):
+    pass
+
+
+class Dialect:
+    delimiter = ","
+    quotechar = '"'
+    escapechar = None
+    doublequote = True
+    skipinitialspace = False
+    lineterminator = "\r\n"
+    quoting = QUOTE_MINIMAL
+    strict = False
+
+
+class excel(Dialect):
+    pass
+
+
+class excel_tab(excel):
+    delimiter = "\t"
+
+
+class unix_dialect(Dialect):
+    lineterminator = "\n"
+    quoting = QUOTE_ALL
+
+
+_dialects = {"excel": excel, "excel-tab": excel_tab, "unix": unix_dialect}
+
+_OPTIONS = (
+    "delimiter", "quotechar", "escapechar", "doublequote", "skipinitialspace",
+    "lineterminator", "quoting", "strict",
+)
+
+
+class _Options:
+    def __init__(self, dialect, params):
+        if isinstance(dialect, str):
+            dialect = get_dialect(dialect)
+        for name in _OPTIONS:
+            setattr(self, name, params.pop(name, getattr(dialect, name, getattr(Dialect, name))))
+        if params:
+            raise TypeError(f"'{next(iter(params))}' is an invalid keyword argument for this function")
+        if not isinstance(self.delimiter, str) or len(self.delimiter) != 1:
+            raise TypeError('"delimiter" must be a 1-character string')
+        if self.quotechar is None and self.quoting != QUOTE_NONE:
+            raise TypeError("quotechar must be set if quoting enabled")
+
+
+def register_dialect(name, dialect=None, **params):
+    _dialects[name] = _Options(dialect or Dialect, params)
+
+
+def unregister_dialect(name):
+    if name not in _dialects:
+        raise Error("unknown dialect")
+    del _dialects[name]
+
+
+def get_dialect(name):
+    if name not in _dialects:
+        raise Error("unknown dialect")
+    return _dialects[name]
+
+
+def list_dialects():
+    return list(_dialects)
+
+
+def field_size_limit(new_limit=None):
+    return 131072
+
+
+class reader:
+    def __init__(self, csvfile, dialect="excel", **params):
+        self.dialect = _Options(dialect, params)
+        self._lines = iter(csvfile)
+        self.line_num = 0
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        d = self.dialect
+        row = _csv.read_record(
+            self._lines, d.delimiter, d.quotechar, d.doublequote,
+            d.skipinitialspace, d.escapechar, d.quoting,
+        )
+        if row is None:
+            raise StopIteration
+        self.line_num += 1
+        return row
+
+
+class writer:
+    def __init__(self, csvfile, dialect="excel", **params):
+        self.dialect = _Options(dialect, params)
+        self._file = csvfile
+
+    def _field(self, value):
+        d = self.dialect
+        if value is None:
+            text = ""
+        elif isinstance(value, float):
+            text = repr(value)
+        else:
+            text = str(value)
+        if d.quoting == QUOTE_NONE:
+            specials = [d.delimiter, "\r", "\n"]
+            if d.quotechar:
+                specials.append(d.quotechar)
+            for special in specials:
+                if special in text:
+                    if d.escapechar is None:
+                        raise Error("need to escape, but no escapechar set")
+                    text = text.replace(special, d.escapechar + special)
+            return text
+        if d.quoting == QUOTE_ALL:
+            quote = True
+        elif d.quoting == QUOTE_NONNUMERIC:
+            quote = not isinstance(value, (int, float)) or isinstance(value, bool)
+        else:
+            quote = (
+                d.delimiter in text or d.quotechar in text or "\r" in text or "\n" in text
+                or text.startswith(" ") and d.skipinitialspace
+            )
+        if d.quotechar in text:
+            if d.doublequote:
+                text = text.replace(d.quotechar, d.quotechar * 2)
+            elif d.escapechar is not None:
+                text = text.replace(d.quotechar, d.escapechar + d.quotechar)
+            else:
+                raise Error("need to escape, but no escapechar set")
+        if quote:
+            return d.quotechar + text + d.quotechar
+        return text
+
+    def writerow(self, row):
+        fields = [self._field(value) for value in row]
+        if len(fields) == 1 and fields[0] == "":
+            quote = self.dialect.quotechar or ""
+            fields = [quote * 2]
+        line = self.dialect.delimiter.join(fields) + self.dialect.lineterminator
+        return self._file.write(line)
+
+    def writerows(self, rows):
+        for row in rows:
+            self.writerow(row)
+
+
+class DictReader:
+    def __init__(self, f, fieldnames=None, restkey=None, restval=None, dialect="excel", **params):
+        self.reader = reader(f, dialect, **params)
+        self._fieldnames = fieldnames
+        self.restkey = restkey
+        self.restval = restval
+        self.line_num = 0
+
+    @property
+    def fieldnames(self):
+        if self._fieldnames is None:
+            try:
+                self._fieldnames = next(self.reader)
+            except StopIteration:
+                pass
+            self.line_num = self.reader.line_num
+        return self._fieldnames
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        names = self.fieldnames
+        row = next(self.reader)
+        while row == []:
+            row = next(self.reader)
+        self.line_num = self.reader.line_num
+        result = dict(zip(names, row))
+        if len(names) < len(row):
+            result[self.restkey] = row[len(names):]
+        elif len(names) > len(row):
+            for name in names[len(row):]:
+                result[name] = self.restval
+        return result
+
+
+class DictWriter:
+    def __init__(self, f, fieldnames, restval="", extrasaction="raise", dialect="excel", **params):
+        if extrasaction not in ("raise", "ignore"):
+            raise ValueError(f"extrasaction ({extrasaction}) must be 'raise' or 'ignore'")
+        self.fieldnames = list(fieldnames)
+        self.restval = restval
+        self.extrasaction = extrasaction
+        self.writer = writer(f, dialect, **params)
+
+    def writeheader(self):
+        return self.writer.writerow(self.fieldnames)
+
+    def _row(self, rowdict):
+        if self.extrasaction == "raise":
+            wrong = [k for k in rowdict if k not in self.fieldnames]
+            if wrong:
+                raise ValueError(
+                    "dict contains fields not in fieldnames: " + ", ".join(repr(k) for k in wrong)
+                )
+        return [rowdict.get(key, self.restval) for key in self.fieldnames]
+
+    def writerow(self, rowdict):
+        return self.writer.writerow(self._row(rowdict))
+
+    def writerows(self, rowdicts):
+        for rowdict in rowdicts:
+            self.writerow(rowdict)
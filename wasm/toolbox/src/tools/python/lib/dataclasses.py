@@ -0,0 +1,272 @@
+"""Generate __init__, __repr__, __eq__ and friends from class annotations."""
+
+import copy as _copy
+
+__all__ = [
+    "dataclass", "field", "Field", "fields", "asdict", "astuple", "replace",
+    "is_dataclass", "FrozenInstanceError", "MISSING", "KW_ONLY", "InitVar",
+]
+
+
+class _MissingType:
+    def __repr__(self):
+        return "MISSING"
+
+
+MISSING = _MissingType()
+
+
+class KW_ONLY:
+    pass
+
+
+class InitVar:
+    def __class_getitem__(cls, item):
+        return cls
+
+
+class FrozenInstanceError(AttributeError):
+    pass
+
+
+class Field:
+    def __init__(self, default, default_factory, init, repr, hash, compare, metadata, kw_only):
+        self.name = None
+        self.type = None
+        self.default = default
+        self.default_factory = default_factory
+        self.init = init
+        self.repr = repr
+        self.hash = hash
+        self.compare = compare
+        self.metadata = metadata if metadata is not None else {}
+        self.kw_only = kw_only
+
+    def __repr__(self):
+        return (
+            f"Field(name={self.name!r},default={self.default!r},"
+            f"default_factory={self.default_factory!r},init={self.init!r},"
+            f"repr={self.repr!r},hash={self.hash!r},compare={self.compare!r},"
+            f"metadata={self.metadata!r},kw_only={self.kw_only!r})"
+        )
+
+
+def field(*, default=MISSING, default_factory=MISSING, init=True, repr=True,
+          hash=None, compare=True, metadata=None, kw_only=MISSING):
+    if default is not MISSING and default_factory is not MISSING:
+        raise ValueError("cannot specify both default and default_factory")
+    return Field(default, default_factory, init, repr, hash, compare, metadata, kw_only)
+
+
+def fields(class_or_instance):
+    try:
+        found = class_or_instance.__dataclass_fields__
+    except AttributeError:
+        raise TypeError("must be called with a dataclass type or instance")
+    return tuple(found.values())
+
+
+def is_dataclass(obj):
+    return hasattr(obj, "__dataclass_fields__")
+
+
+def _is_instance(obj):
+    return is_dataclass(obj) and not isinstance(obj, type)
+
+
+def asdict(obj, *, dict_factory=dict):
+    if not _is_instance(obj):
+        raise TypeError("asdict() should be called on dataclass instances")
+    return _convert(obj, dict_factory, True)
+
+
+def astuple(obj, *, tuple_factory=tuple):
+    if not _is_instance(obj):
+        raise TypeError("astuple() should be called on dataclass instances")
+    return _convert(obj, tuple_factory, False)
+
+
+def _convert(obj, factory, as_dict):
+    if _is_instance(obj):
+        if as_dict:
+            return factory([(f.name, _convert(getattr(obj, f.name), factory, True)) for f in fields(obj)])
+        return factory([_convert(getattr(obj, f.name), factory, False) for f in fields(obj)])
+    if isinstance(obj, list):
+        return [_convert(v, factory, as_dict) for v in obj]
+    if isinstance(obj, tuple):
+        return tuple(_convert(v, factory, as_dict) for v in obj)
+    if isinstance(obj, dict):
+        return {_convert(k, factory, as_dict): _convert(v, factory, as_dict) for k, v in obj.items()}
+    return _copy.deepcopy(obj)
+
+
+def replace(obj, /, **changes):
+    if not _is_instance(obj):
+        raise TypeError("replace() should be called on dataclass instances")
+    for f in fields(obj):
+        if not f.init:
+            if f.name in changes:
+                raise ValueError(f"field {f.name} is declared with init=False, it cannot be specified with replace()")
+            continue
+        if f.name not in changes:
+            changes[f.name] = getattr(obj, f.name)
+    return type(obj)(**changes)
+
+
+def _collect_fields(cls, kw_only):
+    collected = {}
+    for base in reversed(cls.__mro__[1:]):
+        collected.update(getattr(base, "__dataclass_fields__", {}))
+    annotations = cls.__dict__.get("__annotations__", {})
+    after_marker = kw_only
+    for name in annotations:
+        default = cls.__dict__.get(name, MISSING)
+        if default is KW_ONLY:
+            after_marker = True
+            continue
+        if isinstance(default, Field):
+            f = default
+        else:
+            if isinstance(default, (list, dict, set)):
+                raise ValueError(
+                    f"mutable default {type(default)} for field {name} is not allowed: use default_factory"
+                )
+            f = field(default=default)
+        f.name = name
+        if f.kw_only is MISSING:
+            f.kw_only = after_marker
+        collected[name] = f
+        if f.default is MISSING and name in cls.__dict__:
+            delattr(cls, name)
+        elif f.default is not MISSING:
+            setattr(cls, name, f.default)
+    return collected
+
+
+def _make_init(cls, all_fields, frozen):
+    init_fields = [f for f in all_fields if f.init]
+    positional = [f for f in init_fields if not f.kw_only]
+    keyword = [f for f in init_fields if f.kw_only]
+    seen_default = False
+    for f in positional:
+        if f.default is not MISSING or f.default_factory is not MISSING:
+            seen_default = True
+        elif seen_default:
+            raise TypeError(f"non-default argument '{f.name}' follows default argument")
+
+    def __init__(self, *args, **kwargs):
+        if len(args) > len(positional):
+            raise TypeError(
+                f"{cls.__qualname__}.__init__() takes {len(positional) + 1} positional "
+                f"arguments but {len(args) + 1} were given"
+            )
+        values = {}
+        for f, value in zip(positional, args):
+            values[f.name] = value
+        for name, value in kwargs.items():
+            if name not in [f.name for f in init_fields]:
+                raise TypeError(f"{cls.__qualname__}.__init__() got an unexpected keyword argument '{name}'")
+            if name in values:
+                raise TypeError(f"{cls.__qualname__}.__init__() got multiple values for argument '{name}'")
+            values[name] = value
+        missing = [f.name for f in init_fields if f.name not in values
+                   and f.default is MISSING and f.default_factory is MISSING]
+        if missing:
+            kind = "keyword-only" if all(f.kw_only for f in init_fields if f.name in missing) else "positional"
+            names = " and ".join(", ".join(repr(m) for m in missing).rsplit(", ", 1))
+            plural = "argument" if len(missing) == 1 else "arguments"
+            raise TypeError(f"{cls.__qualname__}.__init__() missing {len(missing)} required {kind} {plural}: {names}")
+        for f in all_fields:
+            if f.name in values:
+                value = values[f.name]
+            elif f.default_factory is not MISSING:
+                value = f.default_factory()
+            elif f.default is not MISSING:
+                value = f.default
+            else:
+                continue
+            object.__setattr__(self, f.name, value)
+        post_init = getattr(self, "__post_init__", None)
+        if post_init is not None:
+            post_init()
+
+    return __init__
+
+
+def _tuple(obj, names):
+    return tuple(getattr(obj, name) for name in names)
+
+
+def _process(cls, init, repr, eq, order, unsafe_hash, frozen, kw_only):
+    own = _collect_fields(cls, kw_only)
+    cls.__dataclass_fields__ = own
+    all_fields = list(own.values())
+    compared = [f.name for f in all_fields if f.compare]
+    if init and "__init__" not in cls.__dict__:
+        cls.__init__ = _make_init(cls, all_fields, frozen)
+    if repr and "__repr__" not in cls.__dict__:
+        shown = [f.name for f in all_fields if f.repr]
+
+        def __repr__(self):
+            parts = ", ".join(f"{name}={getattr(self, name)!r}" for name in shown)
+            return f"{type(self).__qualname__}({parts})"
+
+        cls.__repr__ = __repr__
+    if eq and "__eq__" not in cls.__dict__:
+
+        def __eq__(self, other):
+            if type(other) is not type(self):
+                return NotImplemented
+            return _tuple(self, compared) == _tuple(other, compared)
+
+        cls.__eq__ = __eq__
+    if order:
+        for name, test in (
+            ("__lt__", lambda a, b: a < b),
+            ("__le__", lambda a, b: a <= b),
+            ("__gt__", lambda a, b: a > b),
+            ("__ge__", lambda a, b: a >= b),
+        ):
+            if name in cls.__dict__:
+                raise TypeError(f"Cannot overwrite attribute {name} in class {cls.__name__}. Consider using functools.total_ordering")
+            setattr(cls, name, _comparison(test, compared))
+    if frozen:
+
+        def __setattr__(self, name, value):
+            raise FrozenInstanceError(f"cannot assign to field '{name}'")
+
+        def __delattr__(self, name):
+            raise FrozenInstanceError(f"cannot delete field '{name}'")
+
+        cls.__setattr__ = __setattr__
+        cls.__delattr__ = __delattr__
+    hashed = [f.name for f in all_fields if (f.compare if f.hash is None else f.hash)]
+    if unsafe_hash or (eq and frozen):
+
+        def __hash__(self):
+            return hash(_tuple(self, hashed))
+
+        cls.__hash__ = __hash__
+    elif eq and "__hash__" not in cls.__dict__:
+        cls.__hash__ = None
+    cls.__match_args__ = tuple(f.name for f in all_fields if f.init and not f.kw_only)
+    return cls
+
+
+def _comparison(test, compared):
+    def compare(self, other):
+        if type(other) is not type(self):
+            return NotImplemented
+        return test(_tuple(self, compared), _tuple(other, compared))
+
+    return compare
+
+
+def dataclass(cls=None, /, *, init=True, repr=True, eq=True, order=False,
+              unsafe_hash=False, frozen=False, match_args=True, kw_only=False, slots=False):
+    def wrap(cls):
+        return _process(cls, init, repr, eq, order, unsafe_hash, frozen, kw_only)
+
+    if cls is None:
+        return wrap
+    return wrap(cls)
@@ -0,0 +1,233 @@
+"""Enumerations: Enum, IntEnum, StrEnum, auto and unique."""
+
+__all__ = ["EnumMeta", "EnumType", "Enum", "IntEnum", "StrEnum", "auto", "unique"]
+
+
+class auto:
+    """Placeholder replaced by the next enum value."""
+
+    def __repr__(self):
+        return "auto()"
+
+
+class EnumType:
+    def __iter__(cls):
+        return iter([cls._member_map_[name] for name in cls._member_names_])
+
+    def __reversed__(cls):
+        return iter([cls._member_map_[name] for name in reversed(cls._member_names_)])
+
+    def __len__(cls):
+        return len(cls._member_names_)
+
+    def __contains__(cls, member):
+        if isinstance(member, cls):
+            return True
+        return member in cls._value2member_map_
+
+
+EnumMeta = EnumType
+
+
+def _is_member(name, value):
+    if name.startswith("_"):
+        return False
+    if callable(value) and not isinstance(value, auto):
+        return False
+    return not isinstance(value, (property, staticmethod, classmethod))
+
+
+class Enum(metaclass=EnumType):
+    """Generic enumeration."""
+
+    def __init_subclass__(cls, **kwargs):
+        member_names = []
+        member_map = {}
+        value_map = {}
+        last = None
+        for base in cls.__mro__[1:]:
+            if getattr(base, "_member_names_", None):
+                raise TypeError(f"{cls.__name__}: cannot extend {base.__name__}")
+        for name, value in list(cls.__dict__.items()):
+            if not _is_member(name, value):
+                continue
+            if isinstance(value, auto):
+                value = cls._generate_next_value_(name, 1, len(member_names), last)
+            last = value
+            if value in value_map:
+                # An alias for an existing member
+                member_map[name] = value_map[value]
+                setattr(cls, name, value_map[value])
+                continue
+            member = object.__new__(cls)
+            member._name_ = name
+            member._value_ = value
+            init = cls.__dict__.get("__init__")
+            if init is not None:
+                if isinstance(value, tuple):
+                    init(member, *value)
+                else:
+                    init(member, value)
+            member_names.append(name)
+            member_map[name] = member
+            value_map[value] = member
+            setattr(cls, name, member)
+        cls._member_names_ = member_names
+        cls._member_map_ = member_map
+        cls._value2member_map_ = value_map
+
+    def __new__(cls, value):
+        if isinstance(value, cls):
+            return value
+        if value in cls._value2member_map_:
+            return cls._value2member_map_[value]
+        missing = cls._missing_(value)
+        if isinstance(missing, cls):
+            return missing
+        raise ValueError(f"{value!r} is not a valid {cls.__qualname__}")
+
+    def __class_getitem__(cls, name):
+        return cls._member_map_[name]
+
+    @classmethod
+    def _missing_(cls, value):
+        return None
+
+    @staticmethod
+    def _generate_next_value_(name, start, count, last_value):
+        if last_value is None:
+            return start
+        try:
+            return last_value + 1
+        except TypeError:
+            raise TypeError(f"unable to increment {last_value!r}")
+
+    @property
+    def name(self):
+        return self._name_
+
+    @property
+    def value(self):
+        return self._value_
+
+    def __repr__(self):
+        return f"<{type(self).__name__}.{self._name_}: {self._value_!r}>"
+
+    def __str__(self):
+        return f"{type(self).__name__}.{self._name_}"
+
+    def __format__(self, spec):
+        return format(str(self), spec)
+
+    def __hash__(self):
+        return hash(self._name_)
+
+    def __reduce_ex__(self, protocol):
+        return type(self), (self._value_,)
+
+    def __copy__(self):
+        return self
+
+    def __deepcopy__(self, memo):
+        return self
+
+
+class _ValueEnum(Enum):
+    """Members that compare, hash and format like their values."""
+
+    def __eq__(self, other):
+        if isinstance(other, Enum):
+            other = other._value_
+        return self._value_ == other
+
+    def __ne__(self, other):
+        return not self == other
+
+    def __lt__(self, other):
+        return self._value_ < _plain(other)
+
+    def __le__(self, other):
+        return self._value_ <= _plain(other)
+
+    def __gt__(self, other):
+        return self._value_ > _plain(other)
+
+    def __ge__(self, other):
+        return self._value_ >= _plain(other)
+
+    def __hash__(self):
+        return hash(self._value_)
+
+    def __str__(self):
+        return str(self._value_)
+
+    def __format__(self, spec):
+        return format(self._value_, spec)
+
+
+def _plain(value):
+    return value._value_ if isinstance(value, Enum) else value
+
+
+class IntEnum(_ValueEnum):
+    """Enum whose members are also usable as integers."""
+
+    def __int__(self):
+        return self._value_
+
+    def __index__(self):
+        return self._value_
+
+    def __add__(self, other):
+        return self._value_ + _plain(other)
+
+    def __radd__(self, other):
+        return _plain(other) + self._value_
+
+    def __sub__(self, other):
+        return self._value_ - _plain(other)
+
+    def __rsub__(self, other):
+        return _plain(other) - self._value_
+
+    def __mul__(self, other):
+        return self._value_ * _plain(other)
+
+    def __rmul__(self, other):
+        return _plain(other) * self._value_
+
+    def __and__(self, other):
+        return self._value_ & _plain(other)
+
+    def __or__(self, other):
+        return self._value_ | _plain(other)
+
+
+class StrEnum(_ValueEnum):
+    """Enum whose members are also usable as strings."""
+
+    @staticmethod
+    def _generate_next_value_(name, start, count, last_value):
+        return name.lower()
+
+    def __add__(self, other):
+        return self._value_ + _plain(other)
+
+    def __radd__(self, other):
+        return _plain(other) + self._value_
+
+    def __getattr__(self, name):
+        return getattr(self._value_, name)
+
+
+def unique(enumeration):
+    """Class decorator raising ValueError if any member is an alias."""
+    duplicates = [
+        (name, member.name)
+        for name, member in enumeration._member_map_.items()
+        if name != member.name
+    ]
+    if duplicates:
+        details = ", ".join(f"{alias} -> {name}" for alias, name in duplicates)
+        raise ValueError(f"duplicate values found in {enumeration!r}: {details}")
+    return enumeration
@@ -0,0 +1,211 @@
+"""Higher-order functions and operations on callables."""
+
+WRAPPER_ASSIGNMENTS = ("__module__", "__name__", "__qualname__", "__doc__")
+
+_MISSING = object()
+
+
+def reduce(function, iterable, *initial):
+    iterator = iter(iterable)
+    if initial:
+        value = initial[0]
+    else:
+        try:
+            value = next(iterator)
+        except StopIteration:
+            raise TypeError("reduce() of empty iterable with no initial value") from None
+    for item in iterator:
+        value = function(value, item)
+    return value
+
+
+class partial:
+    """A function with some arguments filled in."""
+
+    def __init__(self, func, *args, **keywords):
+        if not callable(func):
+            raise TypeError("the first argument must be callable")
+        self.func = func
+        self.args = args
+        self.keywords = keywords
+
+    def __call__(self, *args, **keywords):
+        merged = dict(self.keywords)
+        merged.update(keywords)
+        return self.func(*self.args, *args, **merged)
+
+    def __repr__(self):
+        parts = [repr(self.func)]
+        parts.extend(repr(a) for a in self.args)
+        parts.extend(f"{k}={v!r}" for k, v in self.keywords.items())
+        return f"functools.partial({', '.join(parts)})"
+
+
+def update_wrapper(wrapper, wrapped, assigned=WRAPPER_ASSIGNMENTS, updated=()):
+    for attr in assigned:
+        try:
+            value = getattr(wrapped, attr)
+        except AttributeError:
+            continue
+        setattr(wrapper, attr, value)
+    wrapper.__wrapped__ = wrapped
+    return wrapper
+
+
+def wraps(wrapped, assigned=WRAPPER_ASSIGNMENTS, updated=()):
+    return partial(update_wrapper, wrapped=wrapped, assigned=assigned)
+
+
+class _CacheInfo:
+    def __init__(self, hits, misses, maxsize, currsize):
+        self.hits = hits
+        self.misses = misses
+        self.maxsize = maxsize
+        self.currsize = currsize
+
+    def __repr__(self):
+        return (
+            f"CacheInfo(hits={self.hits}, misses={self.misses}, "
+            f"maxsize={self.maxsize}, currsize={self.currsize})"
+        )
+
+
+class _LruWrapper:
+    def __init__(self, func, maxsize):
+        self.__wrapped__ = func
+        self._maxsize = maxsize
+        self._cache = {}
+        self._hits = 0
+        self._misses = 0
+        update_wrapper(self, func)
+
+    def __call__(self, *args, **kwargs):
+        key = args
+        if kwargs:
+            key += (_MISSING,) + tuple(sorted(kwargs.items()))
+        value = self._cache.get(key, _MISSING)
+        if value is not _MISSING:
+            self._hits += 1
+            if self._maxsize is not None:
+                # Most recently used entries live at the end
+                del self._cache[key]
+                self._cache[key] = value
+            return value
+        self._misses += 1
+        value = self.__wrapped__(*args, **kwargs)
+        self._cache[key] = value
+        if self._maxsize is not None and len(self._cache) > self._maxsize:
+            del self._cache[next(iter(self._cache))]
+        return value
+
+    def __get__(self, instance, owner=None):
+        if instance is None:
+            return self
+        return partial(self, instance)
+
+    def cache_info(self):
+        return _CacheInfo(self._hits, self._misses, self._maxsize, len(self._cache))
+
+    def cache_clear(self):
+        self._cache = {}
+        self._hits = self._misses = 0
+
+
+def lru_cache(maxsize=128, typed=False):
+    if callable(maxsize) and not isinstance(maxsize, bool):
+        return _LruWrapper(maxsize, 128)
+    if maxsize is not None and maxsize < 0:
+        maxsize = 0
+
+    def decorator(func):
+        return _LruWrapper(func, maxsize)
+
+    return decorator
+
+
+def cache(func):
+    return _LruWrapper(func, None)
+
+
+def cmp_to_key(mycmp):
+    class K:
+        def __init__(self, obj):
+            self.obj = obj
+
+        def __lt__(self, other):
+            return mycmp(self.obj, other.obj) < 0
+
+        def __gt__(self, other):
+            return mycmp(self.obj, other.obj) > 0
+
+        def __eq__(self, other):
+            return mycmp(self.obj, other.obj) == 0
+
+        def __le__(self, other):
+            return mycmp(self.obj, other.obj) <= 0
+
+        def __ge__(self, other):
+            return mycmp(self.obj, other.obj) >= 0
+
+        __hash__ = None
+
+    return K
+
+
+def total_ordering(cls):
+    """Fill in the missing rich comparisons from one of them and __eq__."""
+    defined = [op for op in ("__lt__", "__le__", "__gt__", "__ge__") if op in cls.__dict__]
+    if not defined:
+        raise ValueError("must define at least one ordering operation: < > <= >=")
+    root = defined[0]
+
+    def from_lt():
+        return {
+            "__gt__": lambda self, other: not (self < other) and self != other,
+            "__le__": lambda self, other: self < other or self == other,
+            "__ge__": lambda self, other: not (self < other),
+        }
+
+    def from_le():
+        return {
+            "__ge__": lambda self, other: not (self <= other) or self == other,
+            "__lt__": lambda self, other: self <= other and not self == other,
+            "__gt__": lambda self, other: not (self <= other),
+        }
+
+    def from_gt():
+        return {
+            "__lt__": lambda self, other: not (self > other) and self != other,
+            "__ge__": lambda self, other: self > other or self == other,
+            "__le__": lambda self, other: not (self > other),
+        }
+
+    def from_ge():
+        return {
+            "__le__": lambda self, other: not (self >= other) or self == other,
+            "__gt__": lambda self, other: self >= other and not self == other,
+            "__lt__": lambda self, other: not (self >= other),
+        }
+
+    table = {"__lt__": from_lt, "__le__": from_le, "__gt__": from_gt, "__ge__": from_ge}
+    for name, method in table[root]().items():
+        if name not in defined:
+            setattr(cls, name, method)
+    return cls
+
+
+class cached_property:
+    """A property computed once per instance."""
+
+    def __init__(self, func):
+        self.func = func
+        self.attrname = func.__name__
+        self.__doc__ = func.__doc__
+
+    def __get__(self, instance, owner=None):
+        if instance is None:
+            return self
+        # The cached value in the instance dict hides this descriptor from now on
+        value = self.func(instance)
+        object.__setattr__(instance, self.attrname, value)
+        return value
@@ -0,0 +1,420 @@
+"""Functions creating iterators for efficient looping."""
+
+_MISSING = object()
+
+
+class count:
+    def __init__(self, start=0, step=1):
+        self._next = start
+        self._step = step
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        value = self._next
+        self._next += self._step
+        return value
+
+    def __repr__(self):
+        if self._step == 1:
+            return f"count({self._next!r})"
+        return f"count({self._next!r}, {self._step!r})"
+
+
+class cycle:
+    def __init__(self, iterable):
+        self._iterator = iter(iterable)
+        self._saved = []
+        self._index = None
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        if self._index is None:
+            try:
+                value = next(self._iterator)
+                self._saved.append(value)
+                return value
+            except StopIteration:
+                if not self._saved:
+                    raise
+                self._index = 0
+        value = self._saved[self._index]
+        self._index = (self._index + 1) % len(self._saved)
+        return value
+
+
+class repeat:
+    def __init__(self, object, times=None):
+        self._object = object
+        self._times = times
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        if self._times is not None:
+            if self._times <= 0:
+                raise StopIteration
+            self._times -= 1
+        return self._object
+
+    def __len__(self):
+        if self._times is None:
+            raise TypeError("len() of unsized object")
+        return max(self._times, 0)
+
+
+class accumulate:
+    def __init__(self, iterable, func=None, *, initial=None):
+        self._iterator = iter(iterable)
+        self._func = func
+        self._total = initial
+        self._started = initial is not None
+        self._pending_initial = initial is not None
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        if self._pending_initial:
+            self._pending_initial = False
+            return self._total
+        value = next(self._iterator)
+        if not self._started:
+            self._started = True
+            self._total = value
+        elif self._func is None:
+            self._total = self._total + value
+        else:
+            self._total = self._func(self._total, value)
+        return self._total
+
+
+class chain:
+    def __init__(self, *iterables):
+        self._iterables = iter(iterables)
+        self._current = iter(())
+
+    @classmethod
+    def from_iterable(cls, iterables):
+        result = cls()
+        result._iterables = iter(iterables)
+        return result
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        while True:
+            try:
+                return next(self._current)
+            except StopIteration:
+                self._current = iter(next(self._iterables))
+
+
+class compress:
+    def __init__(self, data, selectors):
+        self._pairs = zip(data, selectors)
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        while True:
+            value, selected = next(self._pairs)
+            if selected:
+                return value
+
+
+class dropwhile:
+    def __init__(self, predicate, iterable):
+        self._predicate = predicate
+        self._iterator = iter(iterable)
+        self._dropping = True
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        while True:
+            value = next(self._iterator)
+            if self._dropping and self._predicate(value):
+                continue
+            self._dropping = False
+            return value
+
+
+class takewhile:
+    def __init__(self, predicate, iterable):
+        self._predicate = predicate
+        self._iterator = iter(iterable)
+        self._done = False
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        if self._done:
+            raise StopIteration
+        value = next(self._iterator)
+        if self._predicate(value):
+            return value
+        self._done = True
+        raise StopIteration
+
+
+class filterfalse:
+    def __init__(self, predicate, iterable):
+        self._predicate = bool if predicate is None else predicate
+        self._iterator = iter(iterable)
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        while True:
+            value = next(self._iterator)
+            if not self._predicate(value):
+                return value
+
+
+class _Group:
+    def __init__(self, parent, key):
+        self._parent = parent
+        self._key = key
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        parent = self._parent
+        if parent._group is not self or parent._done:
+            raise StopIteration
+        if parent._pending is _MISSING:
+            parent._advance()
+            if parent._done or parent._current_key != self._key:
+                raise StopIteration
+        value = parent._pending
+        parent._pending = _MISSING
+        return value
+
+
+class groupby:
+    def __init__(self, iterable, key=None):
+        self._iterator = iter(iterable)
+        self._keyfunc = (lambda x: x) if key is None else key
+        self._pending = _MISSING
+        self._current_key = _MISSING
+        self._group = None
+        self._done = False
+
+    def _advance(self):
+        try:
+            self._pending = next(self._iterator)
+            self._current_key = self._keyfunc(self._pending)
+        except StopIteration:
+            self._done = True
+            self._pending = _MISSING
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        previous = self._group._key if self._group is not None else _MISSING
+        # Skip what is left of the previous group
+        while not self._done:
+            if self._pending is _MISSING:
+                self._advance()
+                continue
+            if self._group is not None and self._current_key == previous:
+                self._pending = _MISSING
+                continue
+            break
+        if self._done:
+            raise StopIteration
+        self._group = _Group(self, self._current_key)
+        return self._current_key, self._group
+
+
+class islice:
+    def __init__(self, iterable, *args):
+        if len(args) == 1:
+            start, stop, step = 0, args[0], 1
+        elif len(args) in (2, 3):
+            start, stop = args[0], args[1]
+            step = args[2] if len(args) == 3 else 1
+        else:
+            raise TypeError(f"islice expected at most 4 arguments, got {len(args) + 1}")
+        start = 0 if start is None else start
+        step = 1 if step is None else step
+        if start < 0 or (stop is not None and stop < 0):
+            raise ValueError(
+                "Indices for islice() must be None or an integer: 0 <= x <= sys.maxsize."
+            )
+        if step <= 0:
+            raise ValueError("Step for islice() must be a positive integer or None.")
+        self._iterator = iter(iterable)
+        self._next_index = start
+        self._stop = stop
+        self._step = step
+        self._position = 0
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        if self._stop is not None and self._next_index >= self._stop:
+            raise StopIteration
+        while self._position < self._next_index:
+            next(self._iterator)
+            self._position += 1
+        value = next(self._iterator)
+        self._position += 1
+        self._next_index += self._step
+        return value
+
+
+class starmap:
+    def __init__(self, function, iterable):
+        self._function = function
+        self._iterator = iter(iterable)
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        return self._function(*next(self._iterator))
+
+
+class zip_longest:
+    def __init__(self, *iterables, fillvalue=None):
+        self._iterators = [iter(it) for it in iterables]
+        self._active = len(self._iterators)
+        self._fillvalue = fillvalue
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        if not self._iterators or not self._active:
+            raise StopIteration
+        values = []
+        for i, iterator in enumerate(self._iterators):
+            if iterator is None:
+                values.append(self._fillvalue)
+                continue
+            try:
+                values.append(next(iterator))
+            except StopIteration:
+                self._active -= 1
+                if not self._active:
+                    raise
+                self._iterators[i] = None
+                values.append(self._fillvalue)
+        return tuple(values)
+
+
+class pairwise:
+    def __init__(self, iterable):
+        self._iterator = iter(iterable)
+        self._previous = _MISSING
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        if self._previous is _MISSING:
+            self._previous = next(self._iterator)
+        value = next(self._iterator)
+        pair = (self._previous, value)
+        self._previous = value
+        return pair
+
+
+class batched:
+    def __init__(self, iterable, n):
+        if n < 1:
+            raise ValueError("n must be at least one")
+        self._iterator = iter(iterable)
+        self._n = n
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        batch = tuple(islice(self._iterator, self._n))
+        if not batch:
+            raise StopIteration
+        return batch
+
+
+def tee(iterable, n=2):
+    items = list(iterable)
+    return tuple(iter(items) for _ in range(n))
+
+
+def product(*iterables, repeat=1):
+    pools = [tuple(pool) for pool in iterables] * repeat
+    result = [()]
+    for pool in pools:
+        result = [x + (y,) for x in result for y in pool]
+    return iter(result)
+
+
+def permutations(iterable, r=None):
+    pool = tuple(iterable)
+    n = len(pool)
+    r = n if r is None else r
+    if r > n:
+        return iter([])
+    result = []
+
+    def build(prefix, used):
+        if len(prefix) == r:
+            result.append(tuple(pool[i] for i in prefix))
+            return
+        for i in range(n):
+            if i not in used:
+                used.add(i)
+                build(prefix + [i], used)
+                used.discard(i)
+
+    build([], set())
+    return iter(result)
+
+
+def combinations(iterable, r):
+    pool = tuple(iterable)
+    n = len(pool)
+    result = []
+
+    def build(start, prefix):
+        if len(prefix) == r:
+            result.append(tuple(prefix))
+            return
+        for i in range(start, n):
+            build(i + 1, prefix + [pool[i]])
+
+    if r <= n:
+        build(0, [])
+    return iter(result)
+
+
+def combinations_with_replacement(iterable, r):
+    pool = tuple(iterable)
+    n = len(pool)
+    result = []
+
+    def build(start, prefix):
+        if len(prefix) == r:
+            result.append(tuple(prefix))
+            return
+        for i in range(start, n):
+            build(i, prefix + [pool[i]])
+
+    if n or not r:
+        build(0, [])
+    return iter(result)
@@ -0,0 +1,163 @@
+"""Basic statistics for numeric data."""
+
+import math
+from collections import Counter
+
+__all__ = [
+    "StatisticsError", "mean", "fmean", "geometric_mean", "harmonic_mean", "median",
+    "median_low", "median_high", "median_grouped", "mode", "multimode", "pstdev",
+    "pvariance", "stdev", "variance", "quantiles",
+]
+
+
+class StatisticsError(ValueError):
+    pass
+
+
+def _data(data, minimum, message):
+    data = list(data)
+    if len(data) < minimum:
+        raise StatisticsError(message)
+    return data
+
+
+def _mean(data):
+    if all(isinstance(x, int) for x in data):
+        total = sum(data)
+        if total % len(data) == 0:
+            return total // len(data)
+    return math.fsum(data) / len(data)
+
+
+def mean(data):
+    return _mean(_data(data, 1, "mean requires at least one data point"))
+
+
+def fmean(data, weights=None):
+    data = list(data)
+    if weights is None:
+        if not data:
+            raise StatisticsError("fmean requires at least one data point")
+        return math.fsum(data) / len(data)
+    weights = list(weights)
+    if len(weights) != len(data):
+        raise StatisticsError("data and weights must be the same length")
+    total = math.fsum(weights)
+    if not total:
+        raise StatisticsError("sum of weights must be non-zero")
+    return math.fsum(x * w for x, w in zip(data, weights)) / total
+
+
+def geometric_mean(data):
+    data = _data(data, 1, "fmean requires at least one data point")
+    if any(x <= 0 for x in data):
+        raise StatisticsError("geometric mean requires a non-empty dataset containing positive numbers")
+    return math.exp(math.fsum(math.log(x) for x in data) / len(data))
+
+
+def harmonic_mean(data):
+    data = _data(data, 1, "harmonic_mean requires at least one data point")
+    if any(x < 0 for x in data):
+        raise StatisticsError("harmonic mean does not support negative values")
+    if any(x == 0 for x in data):
+        return 0
+    return len(data) / math.fsum(1 / x for x in data)
+
+
+def median(data):
+    data = sorted(_data(data, 1, "no median for empty data"))
+    n = len(data)
+    if n % 2 == 1:
+        return data[n // 2]
+    i = n // 2
+    return (data[i - 1] + data[i]) / 2
+
+
+def median_low(data):
+    data = sorted(_data(data, 1, "no median for empty data"))
+    n = len(data)
+    return data[n // 2] if n % 2 == 1 else data[n // 2 - 1]
+
+
+def median_high(data):
+    data = sorted(_data(data, 1, "no median for empty data"))
+    return data[len(data) // 2]
+
+
+def median_grouped(data, interval=1.0):
+    data = sorted(_data(data, 1, "no median for empty data"))
+    n = len(data)
+    x = data[n // 2]
+    lower = x - interval / 2
+    below = sum(1 for v in data if v < x)
+    frequency = sum(1 for v in data if v == x)
+    return lower + interval * (n / 2 - below) / frequency
+
+
+def mode(data):
+    counts = Counter(data).most_common()
+    if not counts:
+        raise StatisticsError("no mode for empty data")
+    return counts[0][0]
+
+
+def multimode(data):
+    counts = Counter(data).most_common()
+    if not counts:
+        return []
+    top = counts[0][1]
+    return [value for value, count in counts if count == top]
+
+
+def _ss(data, c=None):
+    if c is None:
+        c = _mean(data)
+    return math.fsum((x - c) ** 2 for x in data)
+
+
+def variance(data, xbar=None):
+    data = _data(data, 2, "variance requires at least two data points")
+    return _ss(data, xbar) / (len(data) - 1)
+
+
+def pvariance(data, mu=None):
+    data = _data(data, 1, "pvariance requires at least one data point")
+    return _ss(data, mu) / len(data)
+
+
+def stdev(data, xbar=None):
+    return math.sqrt(variance(data, xbar))
+
+
+def pstdev(data, mu=None):
+    return math.sqrt(pvariance(data, mu))
+
+
+def quantiles(data, *, n=4, method="exclusive"):
+    if n < 1:
+        raise StatisticsError("n must be at least 1")
+    data = sorted(data)
+    ld = len(data)
+    if ld < 2:
+        if ld == 1:
+            return data * (n - 1)
+        raise StatisticsError("must have at least one data point")
+    if method == "inclusive":
+        m = ld - 1
+        result = []
+        for i in range(1, n):
+            j, delta = divmod(i * m, n)
+            interpolated = (data[j] * (n - delta) + data[j + 1] * delta) / n
+            result.append(interpolated)
+        return result
+    if method == "exclusive":
+        m = ld + 1
+        result = []
+        for i in range(1, n):
+            j = i * m // n
+            j = 1 if j < 1 else ld - 1 if j > ld - 1 else j
+            delta = i * m - j * n
+            interpolated = (data[j - 1] * (n - delta) + data[j] * delta) / n
+            result.append(interpolated)
+        return result
+    raise ValueError(f"Unknown method: {method!r}")
@@ -0,0 +1,155 @@
+"""Text wrapping and filling."""
+
+import re
+
+__all__ = ["TextWrapper", "wrap", "fill", "dedent", "indent", "shorten"]
+
+
+class TextWrapper:
+    def __init__(self, width=70, initial_indent="", subsequent_indent="", expand_tabs=True,
+                 replace_whitespace=True, fix_sentence_endings=False, break_long_words=True,
+                 drop_whitespace=True, break_on_hyphens=True, tabsize=8, *, max_lines=None,
+                 placeholder=" [...]"):
+        self.width = width
+        self.initial_indent = initial_indent
+        self.subsequent_indent = subsequent_indent
+        self.expand_tabs = expand_tabs
+        self.replace_whitespace = replace_whitespace
+        self.fix_sentence_endings = fix_sentence_endings
+        self.break_long_words = break_long_words
+        self.drop_whitespace = drop_whitespace
+        self.break_on_hyphens = break_on_hyphens
+        self.tabsize = tabsize
+        self.max_lines = max_lines
+        self.placeholder = placeholder
+
+    def _chunks(self, text):
+        if self.expand_tabs:
+            text = text.expandtabs(self.tabsize)
+        if self.replace_whitespace:
+            for space in "\t\n\x0b\x0c\r":
+                text = text.replace(space, " ")
+        chunks = []
+        for chunk in re.findall(r"\s+|\S+", text):
+            if self.break_on_hyphens and not chunk.isspace():
+                # Break after hyphens inside words, as in "long-term"
+                start = 0
+                for i in range(1, len(chunk) - 1):
+                    if chunk[i] == "-" and chunk[i - 1].isalnum() and chunk[i + 1].isalnum():
+                        chunks.append(chunk[start : i + 1])
+                        start = i + 1
+                chunk = chunk[start:]
+            chunks.append(chunk)
+        return chunks
+
+    def wrap(self, text):
+        if self.width <= 0:
+            raise ValueError(f"invalid width {self.width!r} (must be > 0)")
+        chunks = self._chunks(text)
+        chunks.reverse()
+        lines = []
+        while chunks:
+            current = []
+            length = 0
+            indent = self.subsequent_indent if lines else self.initial_indent
+            width = self.width - len(indent)
+            if self.drop_whitespace and chunks[-1].strip() == "" and lines:
+                chunks.pop()
+            while chunks:
+                size = len(chunks[-1])
+                if length + size <= width:
+                    current.append(chunks.pop())
+                    length += size
+                else:
+                    break
+            if chunks and len(chunks[-1]) > width:
+                self._handle_long_word(chunks, current, length, width)
+                length = sum(len(c) for c in current)
+            if self.drop_whitespace and current and current[-1].strip() == "":
+                length -= len(current[-1])
+                current.pop()
+            if current:
+                if (self.max_lines is None or len(lines) + 1 < self.max_lines
+                        or (not chunks or self.drop_whitespace and len(chunks) == 1
+                            and not chunks[0].strip()) and length <= width):
+                    lines.append(indent + "".join(current))
+                else:
+                    while current:
+                        if current[-1].strip() and length + len(self.placeholder) <= width:
+                            current.append(self.placeholder)
+                            lines.append(indent + "".join(current))
+                            break
+                        length -= len(current[-1])
+                        current.pop()
+                    else:
+                        if lines:
+                            prev = lines[-1].rstrip()
+                            if len(prev) + len(self.placeholder) <= self.width:
+                                lines[-1] = prev + self.placeholder
+                                break
+                        lines.append(indent + self.placeholder.lstrip())
+                    break
+        return lines
+
+    def _handle_long_word(self, chunks, current, length, width):
+        space_left = 1 if width < 1 else width - length
+        if self.break_long_words:
+            chunk = chunks[-1]
+            end = space_left
+            if self.break_on_hyphens and len(chunk) > space_left:
+                hyphen = chunk.rfind("-", 0, space_left)
+                if hyphen > 0 and any(c != "-" for c in chunk[:hyphen]):
+                    end = hyphen + 1
+            current.append(chunk[:end])
+            chunks[-1] = chunk[end:]
+        elif not current:
+            current.append(chunks.pop())
+
+    def fill(self, text):
+        return "\n".join(self.wrap(text))
+
+
+def wrap(text, width=70, **kwargs):
+    return TextWrapper(width=width, **kwargs).wrap(text)
+
+
+def fill(text, width=70, **kwargs):
+    return TextWrapper(width=width, **kwargs).fill(text)
+
+
+def shorten(text, width, **kwargs):
+    wrapper = TextWrapper(width=width, max_lines=1, **kwargs)
+    return wrapper.fill(" ".join(text.strip().split()))
+
+
+def dedent(text):
+    lines = text.split("\n")
+    margin = None
+    for line in lines:
+        stripped = line.lstrip(" \t")
+        if not stripped:
+            continue
+        prefix = line[: len(line) - len(stripped)]
+        if margin is None:
+            margin = prefix
+        else:
+            common = 0
+            while common < min(len(margin), len(prefix)) and margin[common] == prefix[common]:
+                common += 1
+            margin = margin[:common]
+    result = []
+    for line in lines:
+        if not line.strip(" \t"):
+            result.append("")
+        elif margin:
+            result.append(line[len(margin):])
+        else:
+            result.append(line)
+    return "\n".join(result)
+
+
+def indent(text, prefix, predicate=None):
+    if predicate is None:
+        def predicate(line):
+            return line.strip()
+    return "".join(prefix + line if predicate(line) else line for line in text.splitlines(True))
@@ -0,0 +1,172 @@
+"""Runtime stand-ins for type hints; annotations are not evaluated or checked."""
+
+__all__ = [
+    "Any", "Callable", "ClassVar", "Dict", "Final", "FrozenSet", "Generic", "Iterable",
+    "Iterator", "List", "Literal", "Mapping", "NamedTuple", "Optional", "Protocol",
+    "Sequence", "Set", "Tuple", "Type", "TypeVar", "TypedDict", "Union", "cast",
+    "overload", "TYPE_CHECKING", "no_type_check", "get_type_hints", "final",
+]
+
+TYPE_CHECKING = False
+
+
+class _Alias:
+    def __init__(self, name, args=()):
+        self._name = name
+        self.__args__ = args
+
+    def __getitem__(self, args):
+        if not isinstance(args, tuple):
+            args = (args,)
+        return _Alias(self._name, args)
+
+    def __call__(self, *args, **kwargs):
+        raise TypeError(f"Type {self._name} cannot be instantiated")
+
+    def __or__(self, other):
+        return _Alias("Union", (self, other))
+
+    def __ror__(self, other):
+        return _Alias("Union", (other, self))
+
+    def __repr__(self):
+        if not self.__args__:
+            return f"typing.{self._name}"
+        args = ", ".join(_repr(arg) for arg in self.__args__)
+        return f"typing.{self._name}[{args}]"
+
+
+def _repr(value):
+    if isinstance(value, type):
+        return value.__name__
+    if value is ...:
+        return "..."
+    return repr(value)
+
+
+Any = _Alias("Any")
+Callable = _Alias("Callable")
+ClassVar = _Alias("ClassVar")
+Dict = _Alias("Dict")
+Final = _Alias("Final")
+FrozenSet = _Alias("FrozenSet")
+Iterable = _Alias("Iterable")
+Iterator = _Alias("Iterator")
+List = _Alias("List")
+Literal = _Alias("Literal")
+Mapping = _Alias("Mapping")
+MutableMapping = _Alias("MutableMapping")
+MutableSequence = _Alias("MutableSequence")
+Optional = _Alias("Optional")
+Sequence = _Alias("Sequence")
+Set = _Alias("Set")
+Tuple = _Alias("Tuple")
+Type = _Alias("Type")
+Union = _Alias("Union")
+Annotated = _Alias("Annotated")
+Awaitable = _Alias("Awaitable")
+Generator = _Alias("Generator")
+Hashable = _Alias("Hashable")
+Sized = _Alias("Sized")
+Collection = _Alias("Collection")
+Container = _Alias("Container")
+Reversible = _Alias("Reversible")
+AbstractSet = _Alias("AbstractSet")
+DefaultDict = _Alias("DefaultDict")
+Counter = _Alias("Counter")
+Deque = _Alias("Deque")
+OrderedDict = _Alias("OrderedDict")
+ChainMap = _Alias("ChainMap")
+NoReturn = _Alias("NoReturn")
+Never = _Alias("Never")
+Self = _Alias("Self")
+LiteralString = _Alias("LiteralString")
+TypeAlias = _Alias("TypeAlias")
+Required = _Alias("Required")
+NotRequired = _Alias("NotRequired")
+Concatenate = _Alias("Concatenate")
+TypeGuard = _Alias("TypeGuard")
+IO = _Alias("IO")
+TextIO = _Alias("TextIO")
+Pattern = _Alias("Pattern")
+Match = _Alias("Match")
+SupportsInt = _Alias("SupportsInt")
+SupportsFloat = _Alias("SupportsFloat")
+SupportsIndex = _Alias("SupportsIndex")
+
+AnyStr = _Alias("AnyStr")
+Text = str
+
+
+class TypeVar:
+    def __init__(self, name, *constraints, bound=None, covariant=False, contravariant=False):
+        self.__name__ = name
+        self.__constraints__ = constraints
+        self.__bound__ = bound
+
+    def __repr__(self):
+        return "~" + self.__name__
+
+
+class ParamSpec(TypeVar):
+    pass
+
+
+class Generic:
+    def __class_getitem__(cls, params):
+        return cls
+
+
+class Protocol(Generic):
+    pass
+
+
+def NamedTuple(typename, fields=None, **kwargs):
+    from collections import namedtuple
+    if fields is None:
+        fields = kwargs.items()
+    return namedtuple(typename, [name for name, _ in fields])
+
+
+def TypedDict(typename, fields=None, total=True, **kwargs):
+    return dict
+
+
+def NewType(name, tp):
+    def new_type(x):
+        return x
+
+    new_type.__name__ = name
+    return new_type
+
+
+def cast(typ, val):
+    return val
+
+
+def overload(func):
+    return func
+
+
+def final(f):
+    return f
+
+
+def no_type_check(arg):
+    return arg
+
+
+def runtime_checkable(cls):
+    return cls
+
+
+def get_type_hints(obj, globalns=None, localns=None, include_extras=False):
+    return dict(getattr(obj, "__annotations__", {}))
+
+
+def get_origin(tp):
+    return None
+
+
+def get_args(tp):
+    return getattr(tp, "__args__", ())
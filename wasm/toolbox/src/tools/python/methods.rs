@@ -0,0 +1,1748 @@
+//! Methods and attributes of the builtin types: `str`, `list`, `dict`,
+//! `set`, numbers, files, iterators and `object` itself.
+
+use std::io::{BufRead, Read, Write};
+use std::rc::Rc;
+
+use super::ast::BinOp;
+use super::builtins::{exact, index_value, no_keywords};
+use super::error::{PyErr, PyResult, os_error, raise};
+use super::format::str_format;
+use super::interp::Interp;
+use super::ops::{address, exception_args, slice_bounds};
+use super::re;
+use super::value::*;
+
+/// Methods every object has, used when a class doesn't define its own.
+pub const OBJECT_METHODS: &[&str] = &[
+    "__init__",
+    "__new__",
+    "__setattr__",
+    "__getattribute__",
+    "__delattr__",
+    "__repr__",
+    "__str__",
+    "__eq__",
+    "__ne__",
+    "__hash__",
+    "__format__",
+    "__init_subclass__",
+    "__dir__",
+];
+
+const STR_METHODS: &[&str] = &[
+    "capitalize", "casefold", "center", "count", "encode", "endswith", "expandtabs", "find",
+    "format", "format_map", "index", "isalnum", "isalpha", "isascii", "isdecimal", "isdigit",
+    "isidentifier", "islower", "isnumeric", "isprintable", "isspace", "istitle", "isupper", "join",
+    "ljust", "lower", "lstrip", "maketrans", "partition", "removeprefix", "removesuffix",
+    "replace", "rfind", "rindex", "rjust", "rpartition", "rsplit", "rstrip", "split",
+    "splitlines", "startswith", "strip", "swapcase", "title", "translate", "upper", "zfill",
+];
+const LIST_METHODS: &[&str] = &[
+    "append", "clear", "copy", "count", "extend", "index", "insert", "pop", "remove", "reverse",
+    "sort",
+];
+const TUPLE_METHODS: &[&str] = &["count", "index"];
+const DICT_METHODS: &[&str] = &[
+    "clear", "copy", "fromkeys", "get", "items", "keys", "pop", "popitem", "setdefault", "update",
+    "values",
+];
+const COUNTER_METHODS: &[&str] = &[
+    "clear", "copy", "elements", "fromkeys", "get", "items", "keys", "most_common", "pop",
+    "popitem", "setdefault", "subtract", "total", "update", "values",
+];
+const ORDERED_DICT_METHODS: &[&str] = &[
+    "clear", "copy", "fromkeys", "get", "items", "keys", "move_to_end", "pop", "popitem",
+    "setdefault", "update", "values",
+];
+const SET_METHODS: &[&str] = &[
+    "add", "clear", "copy", "difference", "difference_update", "discard", "intersection",
+    "intersection_update", "isdisjoint", "issubset", "issuperset", "pop", "remove",
+    "symmetric_difference", "symmetric_difference_update", "union", "update",
+];
+const FROZENSET_METHODS: &[&str] = &[
+    "copy", "difference", "intersection", "isdisjoint", "issubset", "issuperset",
+    "symmetric_difference", "union",
+];
+const INT_METHODS: &[&str] = &[
+    "as_integer_ratio", "bit_count", "bit_length", "conjugate", "is_integer",
+];
+const FLOAT_METHODS: &[&str] = &["as_integer_ratio", "conjugate", "is_integer"];
+const RANGE_METHODS: &[&str] = &["count", "index"];
+const SLICE_METHODS: &[&str] = &["indices"];
+const FILE_METHODS: &[&str] = &[
+    "__enter__", "__exit__", "__iter__", "__next__", "close", "flush", "isatty", "read",
+    "readable", "readline", "readlines", "seek", "tell", "writable", "write", "writelines",
+];
+const ITERATOR_METHODS: &[&str] = &["__iter__", "__next__"];
+const PROPERTY_METHODS: &[&str] = &["deleter", "getter", "setter"];
+
+/// Method names of a builtin type.
+pub fn names(kind: Type) -> &'static [&'static str] {
+    match kind {
+        Type::Str => STR_METHODS,
+        Type::List => LIST_METHODS,
+        Type::Tuple => TUPLE_METHODS,
+        Type::Dict | Type::DefaultDict => DICT_METHODS,
+        Type::Counter => COUNTER_METHODS,
+        Type::OrderedDict => ORDERED_DICT_METHODS,
+        Type::Set => SET_METHODS,
+        Type::FrozenSet => FROZENSET_METHODS,
+        Type::Int | Type::Bool => INT_METHODS,
+        Type::Float => FLOAT_METHODS,
+        Type::Range => RANGE_METHODS,
+        Type::Slice => SLICE_METHODS,
+        Type::File => FILE_METHODS,
+        Type::Iterator => ITERATOR_METHODS,
+        Type::Property => PROPERTY_METHODS,
+        Type::Pattern => re::PATTERN_METHODS,
+        Type::Match => re::MATCH_METHODS,
+        Type::Object => OBJECT_METHODS,
+        _ => &[],
+    }
+}
+
+/// Methods called on the type rather than an instance.
+fn is_class_method(name: &str) -> bool {
+    matches!(name, "fromkeys" | "maketrans")
+}
+
+/// An attribute of a builtin value; `None` if it has no such attribute.
+pub fn attribute(interp: &mut Interp, object: &Value, name: &Rc<str>) -> PyResult<Option<Value>> {
+    let data = match (object, &**name) {
+        (_, "__class__") => Some(interp.type_of(object)),
+        (Value::Type(kind), "__name__" | "__qualname__") => Some(Value::str(kind.name())),
+        (Value::Type(kind), "__module__") => Some(Value::str(kind.module().unwrap_or("builtins"))),
+        (Value::Type(_), "__doc__") => Some(Value::None),
+        (Value::Int(n), "real" | "numerator") => Some(Value::Int(*n)),
+        (Value::Bool(b), "real" | "numerator") => Some(Value::Int(*b as i64)),
+        (Value::Int(_) | Value::Bool(_), "imag") => Some(Value::Int(0)),
+        (Value::Int(_) | Value::Bool(_), "denominator") => Some(Value::Int(1)),
+        (Value::Float(x), "real") => Some(Value::Float(*x)),
+        (Value::Float(_), "imag") => Some(Value::Float(0.0)),
+        (Value::Range(start, _, _), "start") => Some(Value::Int(*start)),
+        (Value::Range(_, stop, _), "stop") => Some(Value::Int(*stop)),
+        (Value::Range(_, _, step), "step") => Some(Value::Int(*step)),
+        (Value::Slice(parts), "start") => Some(parts[0].clone()),
+        (Value::Slice(parts), "stop") => Some(parts[1].clone()),
+        (Value::Slice(parts), "step") => Some(parts[2].clone()),
+        (Value::Dict(dict), "default_factory") => match &dict.borrow().kind {
+            DictKind::Default(factory) => Some(factory.clone()),
+            _ => None,
+        },
+        (Value::File(file), "name") => Some(Value::Str(file.borrow().name.clone())),
+        (Value::File(file), "mode") => Some(Value::Str(file.borrow().mode.clone())),
+        (Value::File(file), "closed") => {
+            Some(Value::Bool(matches!(file.borrow().handle, Handle::Closed)))
+        }
+        (Value::File(_), "encoding") => Some(Value::str("utf-8")),
+        (Value::Property(property), "fget") => Some(property.fget.clone()),
+        (Value::Property(property), "fset") => Some(property.fset.clone()),
+        (Value::Property(property), "fdel") => Some(property.fdel.clone()),
+        (Value::Property(property), "__doc__") => match &property.fget {
+            Value::Function(_) => Some(interp.get_attr(&property.fget, &Rc::from("__doc__"))?),
+            _ => Some(Value::None),
+        },
+        (Value::StaticMethod(function) | Value::ClassMethod(function), "__func__") => {
+            Some((**function).clone())
+        }
+        (Value::Builtin(builtin), "__name__" | "__qualname__") => {
+            Some(Value::str(builtin.rsplit('.').next().unwrap_or(builtin)))
+        }
+        (Value::BuiltinMethod(method), "__name__" | "__qualname__") => Some(Value::Str(method.1.clone())),
+        (Value::BuiltinMethod(method), "__self__") => Some(method.0.clone()),
+        (Value::Pattern(_) | Value::Match(_), _) => re::attribute(object, name),
+        _ => None,
+    };
+    if data.is_some() {
+        return Ok(data);
+    }
+    let kind = match object {
+        Value::Type(kind) => *kind,
+        other => other.type_of(),
+    };
+    if names(kind).contains(&&**name) || OBJECT_METHODS.contains(&&**name) {
+        return Ok(Some(Value::BuiltinMethod(Rc::new((object.clone(), name.clone())))));
+    }
+    Ok(None)
+}
+
+/// Call method `name` of a builtin value.
+pub fn call(interp: &mut Interp, receiver: &Value, name: &str, mut args: Args) -> PyResult<Value> {
+    // Unbound methods such as `str.lower` take the receiver as the first argument
+    let receiver = match receiver {
+        Value::Type(kind) if !is_class_method(name) => {
+            if args.pos.is_empty() {
+                return raise(
+                    "TypeError",
+                    format!(
+                        "unbound method {}.{name}() needs an argument",
+                        kind.name()
+                    ),
+                );
+            }
+            args.pos.remove(0)
+        }
+        other => other.clone(),
+    };
+    let is_builtin_method = match &receiver {
+        Value::Type(kind) => names(*kind).contains(&name),
+        Value::Instance(_) | Value::Class(_) => false,
+        other => names(other.type_of()).contains(&name),
+    };
+    if !is_builtin_method && OBJECT_METHODS.contains(&name) {
+        return object_method(interp, &receiver, name, args);
+    }
+    match &receiver {
+        Value::Str(s) => str_method(interp, s, name, args),
+        Value::List(list) => list_method(interp, list, name, args),
+        Value::Tuple(items) => tuple_method(interp, items, name, args),
+        Value::Dict(dict) => dict_method(interp, &receiver, dict, name, args),
+        Value::Type(Type::Dict | Type::OrderedDict | Type::Counter | Type::DefaultDict) => {
+            dict_fromkeys(interp, &receiver, args)
+        }
+        Value::Type(Type::Str) => str_maketrans(interp, args),
+        Value::Set(set) => set_method(interp, set, name, args),
+        Value::Int(_) | Value::Bool(_) => int_method(&receiver, name, args),
+        Value::Float(x) => float_method(*x, name, args),
+        Value::Range(..) => range_method(interp, &receiver, name, args),
+        Value::Slice(parts) => {
+            let [len] = exact::<1>("indices", args)?;
+            let len = index_value(interp, &len)?;
+            let (start, stop, step, _) = slice_bounds(parts, len.max(0) as usize)?;
+            Ok(Value::tuple(vec![
+                Value::Int(start),
+                Value::Int(stop),
+                Value::Int(step),
+            ]))
+        }
+        Value::File(file) => file_method(interp, &receiver, file, name, args),
+        Value::Iterator(_) => match name {
+            "__iter__" => Ok(receiver.clone()),
+            _ => match interp.next(&receiver)? {
+                Some(value) => Ok(value),
+                None => Err(PyErr::with_arg("StopIteration", Value::None)),
+            },
+        },
+        Value::Property(property) => {
+            let [function] = exact::<1>(name, args)?;
+            let mut updated = Property {
+                fget: property.fget.clone(),
+                fset: property.fset.clone(),
+                fdel: property.fdel.clone(),
+            };
+            match name {
+                "getter" => updated.fget = function,
+                "setter" => updated.fset = function,
+                _ => updated.fdel = function,
+            }
+            Ok(Value::Property(Rc::new(updated)))
+        }
+        Value::Pattern(_) | Value::Match(_) => re::method(interp, &receiver, name, args),
+        other => raise(
+            "AttributeError",
+            format!(
+                "'{}' object has no attribute '{name}'",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+/// The methods of `object`, for instances without their own.
+fn object_method(interp: &mut Interp, receiver: &Value, name: &str, mut args: Args) -> PyResult<Value> {
+    match name {
+        "__init__" | "__init_subclass__" => Ok(Value::None),
+        "__new__" => match receiver {
+            Value::Class(class) => Ok(Value::Instance(Rc::new(Instance {
+                class: class.clone(),
+                attrs: Default::default(),
+            }))),
+            Value::Type(kind) => super::builtins::construct(interp, *kind, Args::default()),
+            _ => raise("TypeError", "object.__new__(X): X is not a type object"),
+        },
+        "__setattr__" => {
+            let [attr, value] = exact::<2>("__setattr__", args)?;
+            let (Value::Instance(instance), Value::Str(attr)) = (receiver, attr) else {
+                return raise("TypeError", "attribute name must be string");
+            };
+            instance.attrs.borrow_mut().insert(attr, value);
+            Ok(Value::None)
+        }
+        "__delattr__" => {
+            let [attr] = exact::<1>("__delattr__", args)?;
+            let (Value::Instance(instance), Value::Str(attr)) = (receiver, attr) else {
+                return raise("TypeError", "attribute name must be string");
+            };
+            match instance.attrs.borrow_mut().shift_remove(&attr) {
+                Some(_) => Ok(Value::None),
+                None => raise(
+                    "AttributeError",
+                    format!(
+                        "'{}' object has no attribute '{attr}'",
+                        instance.class.name
+                    ),
+                ),
+            }
+        }
+        "__getattribute__" => {
+            let [attr] = exact::<1>("__getattribute__", args)?;
+            let Value::Str(attr) = attr else {
+                return raise("TypeError", "attribute name must be string");
+            };
+            interp.get_attr(receiver, &attr)
+        }
+        "__repr__" | "__str__" => {
+            if let Value::Instance(instance) = receiver {
+                if instance.class.has_ancestor("BaseException") {
+                    let args = exception_args(instance);
+                    if name == "__str__" {
+                        return Ok(Value::str(&interp.exception_str(instance)?));
+                    }
+                    let mut parts = Vec::new();
+                    for arg in &args {
+                        parts.push(interp.repr(arg)?);
+                    }
+                    return Ok(Value::str(&format!(
+                        "{}({})",
+                        instance.class.name,
+                        parts.join(", ")
+                    )));
+                }
+                if name == "__str__" && instance.class.lookup("__repr__").is_some() {
+                    return Ok(Value::str(&interp.repr(receiver)?));
+                }
+                let class = &instance.class;
+                let qualified = match &*class.module {
+                    "builtins" => class.name.to_string(),
+                    module => format!("{module}.{}", class.name),
+                };
+                return Ok(Value::str(&format!(
+                    "<{qualified} object at {}>",
+                    address(receiver)
+                )));
+            }
+            let text = if name == "__str__" {
+                interp.to_str(receiver)?
+            } else {
+                interp.repr(receiver)?
+            };
+            Ok(Value::str(&text))
+        }
+        "__eq__" => {
+            let [other] = exact::<1>("__eq__", args)?;
+            Ok(if receiver.is(&other) {
+                Value::Bool(true)
+            } else {
+                Value::NotImplemented
+            })
+        }
+        "__ne__" => {
+            let [other] = exact::<1>("__ne__", args)?;
+            let equal = interp.eq(receiver, &other)?;
+            Ok(Value::Bool(!equal))
+        }
+        "__hash__" => Ok(Value::Int(receiver.identity().unwrap_or(0) as i64 >> 4)),
+        "__format__" => {
+            let spec = args.pos.pop().unwrap_or(Value::None);
+            match spec {
+                Value::Str(spec) if !spec.is_empty() => raise(
+                    "TypeError",
+                    format!(
+                        "unsupported format string passed to {}.__format__",
+                        receiver.type_name()
+                    ),
+                ),
+                _ => Ok(Value::str(&interp.to_str(receiver)?)),
+            }
+        }
+        "__dir__" => interp.call(&Value::Builtin("dir"), Args::new(vec![receiver.clone()])),
+        _ => raise(
+            "AttributeError",
+            format!(
+                "'{}' object has no attribute '{name}'",
+                receiver.type_name()
+            ),
+        ),
+    }
+}
+
+// ---- str ----
+
+/// Byte offsets of the characters `start..end` of `s`, with Python's
+/// slice rules for negative and out-of-range indices.
+fn char_range(s: &str, start: Option<i64>, end: Option<i64>) -> (usize, usize) {
+    let len = s.chars().count() as i64;
+    let clamp = |n: i64| -> i64 {
+        let n = if n < 0 { n + len } else { n };
+        n.clamp(0, len)
+    };
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(len, clamp);
+    if s.is_ascii() {
+        return (start as usize, (end.max(start)) as usize);
+    }
+    let offset = |n: i64| s.char_indices().nth(n as usize).map_or(s.len(), |(i, _)| i);
+    (offset(start), offset(end.max(start)))
+}
+
+fn char_index(s: &str, byte: usize) -> i64 {
+    if s.is_ascii() {
+        byte as i64
+    } else {
+        s[..byte].chars().count() as i64
+    }
+}
+
+fn optional_int(interp: &mut Interp, value: Option<&Value>) -> PyResult<Option<i64>> {
+    match value {
+        None | Some(Value::None) => Ok(None),
+        Some(value) => Ok(Some(index_value(interp, value)?)),
+    }
+}
+
+fn str_arg<'a>(value: &'a Value, method: &str) -> PyResult<&'a str> {
+    match value {
+        Value::Str(s) => Ok(s),
+        other => raise(
+            "TypeError",
+            format!(
+                "{method}() argument must be str, not {}",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+/// Characters to strip: `None` means whitespace.
+fn strip_set(args: &Args, method: &str) -> PyResult<Option<Vec<char>>> {
+    match args.pos.first() {
+        None | Some(Value::None) => Ok(None),
+        Some(Value::Str(chars)) => Ok(Some(chars.chars().collect())),
+        Some(other) => raise(
+            "TypeError",
+            format!(
+                "{method} arg must be None or str, not {}",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+fn str_method(interp: &mut Interp, s: &Rc<str>, name: &str, mut args: Args) -> PyResult<Value> {
+    let text: &str = s;
+    let result = match name {
+        "upper" => Value::str(&text.to_uppercase()),
+        "lower" | "casefold" => Value::str(&text.to_lowercase()),
+        "swapcase" => Value::str(
+            &text
+                .chars()
+                .map(|c| {
+                    if c.is_uppercase() {
+                        c.to_lowercase().collect::<String>()
+                    } else {
+                        c.to_uppercase().collect::<String>()
+                    }
+                })
+                .collect::<String>(),
+        ),
+        "capitalize" => {
+            let mut chars = text.chars();
+            Value::str(&match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + &chars.as_str().to_lowercase(),
+                None => String::new(),
+            })
+        }
+        "title" => {
+            let mut out = String::with_capacity(text.len());
+            let mut previous_cased = false;
+            for c in text.chars() {
+                if previous_cased {
+                    out.extend(c.to_lowercase());
+                } else {
+                    out.extend(c.to_uppercase());
+                }
+                previous_cased = c.is_alphabetic();
+            }
+            Value::str(&out)
+        }
+        "strip" | "lstrip" | "rstrip" => {
+            let chars = strip_set(&args, name)?;
+            let matches = |c: char| match &chars {
+                Some(set) => set.contains(&c),
+                None => c.is_whitespace(),
+            };
+            Value::str(match name {
+                "strip" => text.trim_matches(matches),
+                "lstrip" => text.trim_start_matches(matches),
+                _ => text.trim_end_matches(matches),
+            })
+        }
+        "split" | "rsplit" => {
+            let sep = args.arg(0, "sep").unwrap_or(Value::None);
+            let maxsplit = args.arg(1, "maxsplit").unwrap_or(Value::Int(-1));
+            let maxsplit = index_value(interp, &maxsplit)?;
+            let parts = match &sep {
+                Value::None => split_whitespace(text, maxsplit, name == "rsplit"),
+                Value::Str(sep) => {
+                    if sep.is_empty() {
+                        return raise("ValueError", "empty separator");
+                    }
+                    let parts: Vec<&str> = match (maxsplit < 0, name == "rsplit") {
+                        (true, _) => text.split(&**sep).collect(),
+                        (false, false) => text.splitn(maxsplit as usize + 1, &**sep).collect(),
+                        (false, true) => {
+                            let mut parts: Vec<&str> =
+                                text.rsplitn(maxsplit as usize + 1, &**sep).collect();
+                            parts.reverse();
+                            parts
+                        }
+                    };
+                    parts.into_iter().map(str::to_string).collect()
+                }
+                other => {
+                    return raise(
+                        "TypeError",
+                        format!("must be str or None, not {}", other.type_name()),
+                    );
+                }
+            };
+            Value::list(parts.iter().map(|p| Value::str(p)).collect())
+        }
+        "splitlines" => {
+            let keepends = match args.arg(0, "keepends") {
+                Some(value) => interp.truthy(&value)?,
+                None => false,
+            };
+            let mut lines = Vec::new();
+            let mut start = 0;
+            let mut chars = text.char_indices().peekable();
+            while let Some((i, c)) = chars.next() {
+                let mut end = i + c.len_utf8();
+                let is_break = matches!(
+                    c,
+                    '\n' | '\r' | '\x0b' | '\x0c' | '\x1c' | '\x1d' | '\x1e' | '\u{85}' | '\u{2028}' | '\u{2029}'
+                );
+                if !is_break {
+                    continue;
+                }
+                if c == '\r' && chars.peek().map(|(_, c)| *c) == Some('\n') {
+                    chars.next();
+                    end += 1;
+                }
+                lines.push(if keepends { &text[start..end] } else { &text[start..i] });
+                start = end;
+            }
+            if start < text.len() {
+                lines.push(&text[start..]);
+            }
+            Value::list(lines.into_iter().map(Value::str).collect())
+        }
+        "join" => {
+            let [iterable] = exact::<1>("join", args)?;
+            let items = interp.collect(&iterable)?;
+            let mut out = String::new();
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(text);
+                }
+                match item {
+                    Value::Str(part) => out.push_str(part),
+                    other => {
+                        return raise(
+                            "TypeError",
+                            format!(
+                                "sequence item {i}: expected str instance, {} found",
+                                other.type_name()
+                            ),
+                        );
+                    }
+                }
+            }
+            Value::str(&out)
+        }
+        "replace" => {
+            let count = args.arg(2, "count").unwrap_or(Value::Int(-1));
+            let count = index_value(interp, &count)?;
+            let (Some(old), Some(new)) = (args.pos.first(), args.pos.get(1)) else {
+                return raise("TypeError", "replace() takes at least 2 arguments");
+            };
+            let old = str_arg(old, "replace")?;
+            let new = str_arg(new, "replace")?;
+            Value::str(&if count < 0 {
+                text.replace(old, new)
+            } else {
+                text.replacen(old, new, count as usize)
+            })
+        }
+        "find" | "rfind" | "index" | "rindex" | "count" => {
+            let Some(needle) = args.pos.first() else {
+                return raise("TypeError", format!("{name}() takes at least 1 argument (0 given)"));
+            };
+            let needle = str_arg(needle, name)?.to_string();
+            let start = optional_int(interp, args.pos.get(1))?;
+            let end = optional_int(interp, args.pos.get(2))?;
+            let (from, to) = char_range(text, start, end);
+            let window = &text[from..to];
+            if name == "count" {
+                let count = if needle.is_empty() {
+                    window.chars().count() + 1
+                } else {
+                    window.matches(&*needle).count()
+                };
+                return Ok(Value::Int(count as i64));
+            }
+            let found = if name.starts_with('r') {
+                window.rfind(&*needle)
+            } else {
+                window.find(&*needle)
+            };
+            // An empty needle past the end of the string is not found
+            let past_end = start.is_some_and(|s| s > text.chars().count() as i64);
+            match found {
+                Some(offset) if !past_end => Value::Int(char_index(text, from + offset)),
+                _ if name.ends_with("index") => return raise("ValueError", "substring not found"),
+                _ => Value::Int(-1),
+            }
+        }
+        "startswith" | "endswith" => {
+            let Some(prefix) = args.pos.first().cloned() else {
+                return raise("TypeError", format!("{name}() takes at least 1 argument (0 given)"));
+            };
+            let start = optional_int(interp, args.pos.get(1))?;
+            let end = optional_int(interp, args.pos.get(2))?;
+            let (from, to) = char_range(text, start, end);
+            let window = &text[from..to];
+            let candidates = match &prefix {
+                Value::Str(p) => vec![p.clone()],
+                Value::Tuple(items) => {
+                    let mut candidates = Vec::new();
+                    for item in items.iter() {
+                        candidates.push(Rc::from(str_arg(item, name)?));
+                    }
+                    candidates
+                }
+                other => {
+                    return raise(
+                        "TypeError",
+                        format!(
+                            "{name} first arg must be str or a tuple of str, not {}",
+                            other.type_name()
+                        ),
+                    );
+                }
+            };
+            Value::Bool(candidates.iter().any(|c| {
+                if name == "startswith" {
+                    window.starts_with(&**c)
+                } else {
+                    window.ends_with(&**c)
+                }
+            }))
+        }
+        "removeprefix" => {
+            let [prefix] = exact::<1>(name, args)?;
+            let prefix = str_arg(&prefix, name)?;
+            Value::str(text.strip_prefix(prefix).unwrap_or(text))
+        }
+        "removesuffix" => {
+            let [suffix] = exact::<1>(name, args)?;
+            let suffix = str_arg(&suffix, name)?;
+            Value::str(text.strip_suffix(suffix).unwrap_or(text))
+        }
+        "partition" | "rpartition" => {
+            let [sep] = exact::<1>(name, args)?;
+            let sep = str_arg(&sep, name)?;
+            if sep.is_empty() {
+                return raise("ValueError", "empty separator");
+            }
+            let found = if name == "partition" {
+                text.find(sep)
+            } else {
+                text.rfind(sep)
+            };
+            let parts = match found {
+                Some(i) => [&text[..i], sep, &text[i + sep.len()..]],
+                None if name == "partition" => [text, "", ""],
+                None => ["", "", text],
+            };
+            Value::tuple(parts.iter().map(|p| Value::str(p)).collect())
+        }
+        "center" | "ljust" | "rjust" => {
+            let width = args.pos.first().cloned().unwrap_or(Value::Int(0));
+            let width = index_value(interp, &width)?.max(0) as usize;
+            let fill = match args.pos.get(1) {
+                Some(Value::Str(f)) if f.chars().count() == 1 => f.chars().next().unwrap(),
+                Some(_) => {
+                    return raise(
+                        "TypeError",
+                        "The fill character must be exactly one character long",
+                    );
+                }
+                None => ' ',
+            };
+            let len = text.chars().count();
+            if len >= width {
+                return Ok(Value::Str(s.clone()));
+            }
+            let total = width - len;
+            let (left, right) = match name {
+                "ljust" => (0, total),
+                "rjust" => (total, 0),
+                // Python puts the odd extra character on the left when the width is odd
+                _ => {
+                    let left = total / 2 + (total & width & 1);
+                    (left, total - left)
+                }
+            };
+            let fill = |n: usize| fill.to_string().repeat(n);
+            Value::str(&format!("{}{text}{}", fill(left), fill(right)))
+        }
+        "zfill" => {
+            let [width] = exact::<1>("zfill", args)?;
+            let width = index_value(interp, &width)?.max(0) as usize;
+            let len = text.chars().count();
+            if len >= width {
+                return Ok(Value::Str(s.clone()));
+            }
+            let zeros = "0".repeat(width - len);
+            Value::str(&match text.strip_prefix(['+', '-']) {
+                Some(rest) => format!("{}{zeros}{rest}", &text[..1]),
+                None => format!("{zeros}{text}"),
+            })
+        }
+        "expandtabs" => {
+            let size = args.arg(0, "tabsize").unwrap_or(Value::Int(8));
+            let size = index_value(interp, &size)?.max(0) as usize;
+            let mut out = String::new();
+            let mut column = 0;
+            for c in text.chars() {
+                match c {
+                    '\t' => {
+                        if size > 0 {
+                            let spaces = size - column % size;
+                            out.push_str(&" ".repeat(spaces));
+                            column += spaces;
+                        }
+                    }
+                    '\n' | '\r' => {
+                        out.push(c);
+                        column = 0;
+                    }
+                    c => {
+                        out.push(c);
+                        column += 1;
+                    }
+                }
+            }
+            Value::str(&out)
+        }
+        "isdigit" | "isdecimal" | "isnumeric" => Value::Bool(
+            !text.is_empty()
+                && text.chars().all(|c| match name {
+                    "isdecimal" => c.is_ascii_digit(),
+                    "isdigit" => c.is_ascii_digit() || "⁰¹²³⁴⁵⁶⁷⁸⁹".contains(c),
+                    _ => c.is_numeric(),
+                }),
+        ),
+        "isalpha" => Value::Bool(!text.is_empty() && text.chars().all(char::is_alphabetic)),
+        "isalnum" => Value::Bool(!text.is_empty() && text.chars().all(char::is_alphanumeric)),
+        "isspace" => Value::Bool(!text.is_empty() && text.chars().all(char::is_whitespace)),
+        "isascii" => Value::Bool(text.is_ascii()),
+        "islower" => Value::Bool(
+            text.chars().any(char::is_lowercase) && !text.chars().any(char::is_uppercase),
+        ),
+        "isupper" => Value::Bool(
+            text.chars().any(char::is_uppercase) && !text.chars().any(char::is_lowercase),
+        ),
+        "istitle" => {
+            let mut cased = false;
+            let mut previous_cased = false;
+            let mut title = true;
+            for c in text.chars() {
+                if c.is_uppercase() {
+                    if previous_cased {
+                        title = false;
+                    }
+                    previous_cased = true;
+                    cased = true;
+                } else if c.is_lowercase() {
+                    if !previous_cased {
+                        title = false;
+                    }
+                    previous_cased = true;
+                    cased = true;
+                } else {
+                    previous_cased = false;
+                }
+            }
+            Value::Bool(title && cased)
+        }
+        "isidentifier" => {
+            let mut chars = text.chars();
+            Value::Bool(
+                chars.next().is_some_and(|c| c == '_' || c.is_alphabetic())
+                    && chars.all(|c| c == '_' || c.is_alphanumeric()),
+            )
+        }
+        "isprintable" => Value::Bool(text.chars().all(|c| !c.is_control())),
+        // There is no bytes type: encoding leaves the text as it is
+        "encode" => Value::Str(s.clone()),
+        "format" => Value::str(&str_format(interp, text, &args)?),
+        "format_map" => {
+            let [mapping] = exact::<1>("format_map", args)?;
+            let mut keywords = Args::default();
+            for (key, value) in interp.mapping_items(&mapping)? {
+                if let Value::Str(key) = key {
+                    keywords.kw.push((key, value));
+                }
+            }
+            Value::str(&str_format(interp, text, &keywords)?)
+        }
+        "translate" => {
+            let [table] = exact::<1>("translate", args)?;
+            let mut out = String::with_capacity(text.len());
+            for c in text.chars() {
+                match interp.get_item(&table, &Value::Int(c as i64)) {
+                    Ok(Value::None) => {}
+                    Ok(Value::Str(replacement)) => out.push_str(&replacement),
+                    Ok(Value::Int(n)) => out.extend(u32::try_from(n).ok().and_then(char::from_u32)),
+                    Ok(_) => return raise("TypeError", "character mapping must return integer, None or str"),
+                    Err(e) if interp.is_error(&e, "LookupError") => out.push(c),
+                    Err(e) => return Err(e),
+                }
+            }
+            Value::str(&out)
+        }
+        "maketrans" => return str_maketrans(interp, args),
+        _ => {
+            return raise(
+                "AttributeError",
+                format!("'str' object has no attribute '{name}'"),
+            );
+        }
+    };
+    Ok(result)
+}
+
+/// `str.split()` with no separator: runs of whitespace.
+fn split_whitespace(text: &str, maxsplit: i64, from_right: bool) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if maxsplit < 0 || words.len() <= maxsplit as usize + 1 {
+        return words.into_iter().map(str::to_string).collect();
+    }
+    let max = maxsplit as usize;
+    if from_right {
+        let mut parts = Vec::new();
+        let mut rest = text.trim_end();
+        for _ in 0..max {
+            let end = rest.trim_end();
+            match end.rfind(char::is_whitespace) {
+                Some(i) => {
+                    let word_start = i + end[i..].chars().next().unwrap().len_utf8();
+                    parts.push(end[word_start..].to_string());
+                    rest = end[..word_start].trim_end();
+                }
+                None => break,
+            }
+        }
+        parts.push(rest.to_string());
+        parts.reverse();
+        parts
+    } else {
+        let mut parts = Vec::new();
+        let mut rest = text.trim_start();
+        for _ in 0..max {
+            match rest.find(char::is_whitespace) {
+                Some(i) => {
+                    parts.push(rest[..i].to_string());
+                    rest = rest[i..].trim_start();
+                }
+                None => break,
+            }
+        }
+        parts.push(rest.to_string());
+        parts
+    }
+}
+
+fn str_maketrans(interp: &mut Interp, args: Args) -> PyResult<Value> {
+    let mut table = Dict::default();
+    match &args.pos[..] {
+        [mapping] => {
+            for (key, value) in interp.mapping_items(mapping)? {
+                let key = match key {
+                    Value::Str(s) if s.chars().count() == 1 => Value::Int(s.chars().next().unwrap() as i64),
+                    Value::Int(n) => Value::Int(n),
+                    _ => {
+                        return raise(
+                            "ValueError",
+                            "string keys in translate table must be of length 1",
+                        );
+                    }
+                };
+                table.insert(interp.key(&key)?, key, value);
+            }
+        }
+        [from, to, rest @ ..] => {
+            let (Value::Str(from), Value::Str(to)) = (from, to) else {
+                return raise("TypeError", "maketrans() arguments must be strings");
+            };
+            if from.chars().count() != to.chars().count() {
+                return raise(
+                    "ValueError",
+                    "the first two maketrans arguments must have equal length",
+                );
+            }
+            for (a, b) in from.chars().zip(to.chars()) {
+                let key = Value::Int(a as i64);
+                table.insert(Key::Int(a as i64), key, Value::Int(b as i64));
+            }
+            if let Some(Value::Str(delete)) = rest.first() {
+                for c in delete.chars() {
+                    table.insert(Key::Int(c as i64), Value::Int(c as i64), Value::None);
+                }
+            }
+        }
+        _ => return raise("TypeError", "maketrans() takes 1 to 3 arguments"),
+    }
+    Ok(Value::dict(table))
+}
+
+// ---- list and tuple ----
+
+fn position(interp: &mut Interp, items: &[Value], value: &Value, args: &[Value]) -> PyResult<Option<usize>> {
+    let len = items.len() as i64;
+    let resolve = |n: i64| if n < 0 { (n + len).max(0) } else { n.min(len) } as usize;
+    let start = match args.first() {
+        Some(v) => resolve(index_value(interp, v)?),
+        None => 0,
+    };
+    let end = match args.get(1) {
+        Some(v) => resolve(index_value(interp, v)?),
+        None => items.len(),
+    };
+    for (i, item) in items.iter().enumerate().take(end).skip(start) {
+        if item.is(value) || interp.eq(item, value)? {
+            return Ok(Some(i));
+        }
+    }
+    Ok(None)
+}
+
+fn list_method(interp: &mut Interp, list: &Shared<Vec<Value>>, name: &str, mut args: Args) -> PyResult<Value> {
+    match name {
+        "append" => {
+            let [value] = exact::<1>("append", args)?;
+            list.borrow_mut().push(value);
+        }
+        "extend" => {
+            let [iterable] = exact::<1>("extend", args)?;
+            let items = interp.collect(&iterable)?;
+            list.borrow_mut().extend(items);
+        }
+        "insert" => {
+            let [index, value] = exact::<2>("insert", args)?;
+            let index = index_value(interp, &index)?;
+            let mut items = list.borrow_mut();
+            let len = items.len() as i64;
+            let at = if index < 0 { (index + len).max(0) } else { index.min(len) };
+            items.insert(at as usize, value);
+        }
+        "pop" => {
+            let index = match args.pos.first() {
+                Some(value) => index_value(interp, value)?,
+                None => -1,
+            };
+            let mut items = list.borrow_mut();
+            if items.is_empty() {
+                return raise("IndexError", "pop from empty list");
+            }
+            let len = items.len() as i64;
+            let at = if index < 0 { index + len } else { index };
+            if at < 0 || at >= len {
+                return raise("IndexError", "pop index out of range");
+            }
+            return Ok(items.remove(at as usize));
+        }
+        "remove" => {
+            let [value] = exact::<1>("remove", args)?;
+            let items = list.borrow().clone();
+            match position(interp, &items, &value, &[])? {
+                Some(i) => {
+                    list.borrow_mut().remove(i);
+                }
+                None => return raise("ValueError", "list.remove(x): x not in list"),
+            }
+        }
+        "index" => {
+            let Some(value) = args.pos.first().cloned() else {
+                return raise("TypeError", "index expected at least 1 argument, got 0");
+            };
+            let items = list.borrow().clone();
+            return match position(interp, &items, &value, &args.pos[1..])? {
+                Some(i) => Ok(Value::Int(i as i64)),
+                None => raise(
+                    "ValueError",
+                    format!("{} is not in list", interp.repr(&value)?),
+                ),
+            };
+        }
+        "count" => {
+            let [value] = exact::<1>("count", args)?;
+            let items = list.borrow().clone();
+            let mut count = 0;
+            for item in &items {
+                if item.is(&value) || interp.eq(item, &value)? {
+                    count += 1;
+                }
+            }
+            return Ok(Value::Int(count));
+        }
+        "sort" => {
+            let key = args.take_kw("key").unwrap_or(Value::None);
+            let reverse = args.take_kw("reverse").unwrap_or(Value::Bool(false));
+            no_keywords("sort", &args)?;
+            if !args.pos.is_empty() {
+                return raise("TypeError", "sort() takes no positional arguments");
+            }
+            let reverse = interp.truthy(&reverse)?;
+            // The list is empty while sorting, so key functions see it that way
+            let mut items = std::mem::take(&mut *list.borrow_mut());
+            let result = interp.sort(&mut items, &key, reverse);
+            *list.borrow_mut() = items;
+            result?;
+        }
+        "reverse" => list.borrow_mut().reverse(),
+        "clear" => list.borrow_mut().clear(),
+        "copy" => return Ok(Value::list(list.borrow().clone())),
+        _ => {
+            return raise(
+                "AttributeError",
+                format!("'list' object has no attribute '{name}'"),
+            );
+        }
+    }
+    Ok(Value::None)
+}
+
+fn tuple_method(interp: &mut Interp, items: &Rc<[Value]>, name: &str, args: Args) -> PyResult<Value> {
+    match name {
+        "count" => {
+            let [value] = exact::<1>("count", args)?;
+            let mut count = 0;
+            for item in items.iter() {
+                if item.is(&value) || interp.eq(item, &value)? {
+                    count += 1;
+                }
+            }
+            Ok(Value::Int(count))
+        }
+        "index" => {
+            let Some(value) = args.pos.first().cloned() else {
+                return raise("TypeError", "index expected at least 1 argument, got 0");
+            };
+            match position(interp, items, &value, &args.pos[1..])? {
+                Some(i) => Ok(Value::Int(i as i64)),
+                None => raise("ValueError", "tuple.index(x): x not in tuple"),
+            }
+        }
+        _ => raise(
+            "AttributeError",
+            format!("'tuple' object has no attribute '{name}'"),
+        ),
+    }
+}
+
+// ---- dict ----
+
+fn dict_method(
+    interp: &mut Interp,
+    receiver: &Value,
+    dict: &Shared<Dict>,
+    name: &str,
+    mut args: Args,
+) -> PyResult<Value> {
+    let counter = matches!(dict.borrow().kind, DictKind::Counter);
+    match name {
+        "get" => {
+            let key = args.pos.first().cloned().unwrap_or(Value::None);
+            let default = args.pos.get(1).cloned().unwrap_or(Value::None);
+            let key = interp.key(&key)?;
+            Ok(dict.borrow().get(&key).unwrap_or(default))
+        }
+        "keys" => Ok(Value::list(dict.borrow().keys())),
+        "values" => Ok(Value::list(dict.borrow().values())),
+        "items" => Ok(Value::list(
+            dict.borrow()
+                .items()
+                .into_iter()
+                .map(|(k, v)| Value::tuple(vec![k, v]))
+                .collect(),
+        )),
+        "pop" => {
+            let Some(key_value) = args.pos.first().cloned() else {
+                return raise("TypeError", "pop expected at least 1 argument, got 0");
+            };
+            let key = interp.key(&key_value)?;
+            let removed = dict.borrow_mut().map.shift_remove(&key);
+            match (removed, args.pos.get(1)) {
+                (Some((_, value)), _) => Ok(value),
+                (None, Some(default)) => Ok(default.clone()),
+                (None, None) => Err(PyErr::with_arg("KeyError", key_value)),
+            }
+        }
+        "popitem" => {
+            let last = match args.arg(0, "last") {
+                Some(value) => interp.truthy(&value)?,
+                None => true,
+            };
+            let mut dict = dict.borrow_mut();
+            let entry = if last {
+                dict.map.pop()
+            } else {
+                dict.map.shift_remove_index(0)
+            };
+            match entry {
+                Some((_, (key, value))) => Ok(Value::tuple(vec![key, value])),
+                None => raise("KeyError", "popitem(): dictionary is empty"),
+            }
+        }
+        "setdefault" => {
+            let key_value = args.pos.first().cloned().unwrap_or(Value::None);
+            let default = args.pos.get(1).cloned().unwrap_or(Value::None);
+            let key = interp.key(&key_value)?;
+            let mut dict = dict.borrow_mut();
+            if let Some(value) = dict.get(&key) {
+                return Ok(value);
+            }
+            dict.insert(key, key_value, default.clone());
+            Ok(default)
+        }
+        "update" | "subtract" => {
+            let sign = if name == "subtract" { -1 } else { 1 };
+            if args.pos.len() > 1 {
+                return raise(
+                    "TypeError",
+                    format!("update expected at most 1 argument, got {}", args.pos.len()),
+                );
+            }
+            let mut pairs = Vec::new();
+            if let Some(source) = args.pos.first() {
+                let is_mapping = match source {
+                    Value::Dict(_) => true,
+                    Value::Instance(instance) => instance.class.lookup("keys").is_some(),
+                    _ => false,
+                };
+                if is_mapping {
+                    pairs = interp.mapping_items(source)?;
+                } else if counter {
+                    for item in interp.collect(source)? {
+                        pairs.push((item, Value::Int(1)));
+                    }
+                } else {
+                    for (i, item) in interp.collect(source)?.into_iter().enumerate() {
+                        let pair = match &item {
+                            Value::Str(_) | Value::Int(_) | Value::Float(_) | Value::None => None,
+                            other => Some(interp.collect(other)?),
+                        };
+                        match pair.as_deref() {
+                            Some([key, value]) => pairs.push((key.clone(), value.clone())),
+                            Some(other) => {
+                                return raise(
+                                    "ValueError",
+                                    format!(
+                                        "dictionary update sequence element #{i} has length {}; 2 is required",
+                                        other.len()
+                                    ),
+                                );
+                            }
+                            None => {
+                                return raise(
+                                    "TypeError",
+                                    format!(
+                                        "cannot convert dictionary update sequence element #{i} to a sequence"
+                                    ),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            for (key, value) in args.kw.drain(..) {
+                pairs.push((Value::Str(key), value));
+            }
+            for (key_value, value) in pairs {
+                let key = interp.key(&key_value)?;
+                let value = if counter {
+                    let current = dict.borrow().get(&key).unwrap_or(Value::Int(0));
+                    if sign < 0 {
+                        interp.binop(BinOp::Sub, current, value)?
+                    } else {
+                        interp.binop(BinOp::Add, current, value)?
+                    }
+                } else {
+                    value
+                };
+                dict.borrow_mut().insert(key, key_value, value);
+            }
+            Ok(Value::None)
+        }
+        "clear" => {
+            dict.borrow_mut().map.clear();
+            Ok(Value::None)
+        }
+        "copy" => Ok(Value::dict(dict.borrow().clone())),
+        "fromkeys" => dict_fromkeys(interp, receiver, args),
+        "most_common" => {
+            let mut items = dict.borrow().items();
+            let mut counts = Vec::with_capacity(items.len());
+            for (_, count) in &items {
+                counts.push(count.clone());
+            }
+            // Stable sort by descending count
+            let mut order: Vec<usize> = (0..items.len()).collect();
+            let mut error = None;
+            order.sort_by(|&a, &b| {
+                if error.is_some() {
+                    return std::cmp::Ordering::Equal;
+                }
+                match interp.lt(&counts[b], &counts[a]) {
+                    Ok(true) => std::cmp::Ordering::Less,
+                    Ok(false) => match interp.lt(&counts[a], &counts[b]) {
+                        Ok(true) => std::cmp::Ordering::Greater,
+                        Ok(false) => std::cmp::Ordering::Equal,
+                        Err(e) => {
+                            error = Some(e);
+                            std::cmp::Ordering::Equal
+                        }
+                    },
+                    Err(e) => {
+                        error = Some(e);
+                        std::cmp::Ordering::Equal
+                    }
+                }
+            });
+            if let Some(e) = error {
+                return Err(e);
+            }
+            let mut sorted: Vec<Value> = order
+                .into_iter()
+                .map(|i| {
+                    let (key, count) = std::mem::replace(&mut items[i], (Value::None, Value::None));
+                    Value::tuple(vec![key, count])
+                })
+                .collect();
+            if let Some(n) = args.pos.first()
+                && !n.is_none()
+            {
+                let n = index_value(interp, n)?.max(0) as usize;
+                sorted.truncate(n);
+            }
+            Ok(Value::list(sorted))
+        }
+        "elements" => {
+            let mut elements = Vec::new();
+            for (key, count) in dict.borrow().items() {
+                if let Value::Int(n) = count {
+                    for _ in 0..n.max(0) {
+                        elements.push(key.clone());
+                    }
+                }
+            }
+            Ok(Value::iterator(Iter::Values(elements.into_iter())))
+        }
+        "total" => {
+            let values = dict.borrow().values();
+            let mut total = Value::Int(0);
+            for value in values {
+                total = interp.binop(BinOp::Add, total, value)?;
+            }
+            Ok(total)
+        }
+        "move_to_end" => {
+            let key_value = args.pos.first().cloned().unwrap_or(Value::None);
+            let last = match args.arg(1, "last") {
+                Some(value) => interp.truthy(&value)?,
+                None => true,
+            };
+            let key = interp.key(&key_value)?;
+            let mut dict = dict.borrow_mut();
+            let Some(index) = dict.map.get_index_of(&key) else {
+                return Err(PyErr::with_arg("KeyError", key_value));
+            };
+            let end = if last { dict.map.len() - 1 } else { 0 };
+            dict.map.move_index(index, end);
+            Ok(Value::None)
+        }
+        _ => raise(
+            "AttributeError",
+            format!(
+                "'{}' object has no attribute '{name}'",
+                receiver.type_name()
+            ),
+        ),
+    }
+}
+
+fn dict_fromkeys(interp: &mut Interp, receiver: &Value, args: Args) -> PyResult<Value> {
+    let kind = match receiver {
+        Value::Type(Type::OrderedDict) => DictKind::Ordered,
+        Value::Type(Type::Counter) => {
+            return raise(
+                "NotImplementedError",
+                "Counter.fromkeys() is undefined.  Use Counter(iterable) instead.",
+            );
+        }
+        Value::Dict(dict) => match dict.borrow().kind {
+            DictKind::Ordered => DictKind::Ordered,
+            _ => DictKind::Dict,
+        },
+        _ => DictKind::Dict,
+    };
+    let Some(iterable) = args.pos.first() else {
+        return raise("TypeError", "fromkeys expected at least 1 argument, got 0");
+    };
+    let value = args.pos.get(1).cloned().unwrap_or(Value::None);
+    let mut dict = Dict {
+        map: Default::default(),
+        kind,
+    };
+    for key in interp.collect(iterable)? {
+        dict.insert(interp.key(&key)?, key, value.clone());
+    }
+    Ok(Value::dict(dict))
+}
+
+/// `Counter + Counter` and `Counter - Counter`: only positive counts are kept.
+pub fn counter_arith(interp: &mut Interp, op: BinOp, a: &Shared<Dict>, b: &Shared<Dict>) -> PyResult<Value> {
+    let mut result = Dict {
+        map: Default::default(),
+        kind: DictKind::Counter,
+    };
+    let a = a.borrow().clone();
+    let b = b.borrow().clone();
+    let mut keys: Vec<(Key, Value)> = a.map.iter().map(|(k, (v, _))| (k.clone(), v.clone())).collect();
+    for (key, (value, _)) in &b.map {
+        if !a.map.contains_key(key) {
+            keys.push((key.clone(), value.clone()));
+        }
+    }
+    for (key, key_value) in keys {
+        let x = a.get(&key).unwrap_or(Value::Int(0));
+        let y = b.get(&key).unwrap_or(Value::Int(0));
+        let count = interp.binop(op, x, y)?;
+        if interp.lt(&Value::Int(0), &count)? {
+            result.insert(key, key_value, count);
+        }
+    }
+    Ok(Value::dict(result))
+}
+
+// ---- set ----
+
+fn set_method(interp: &mut Interp, set: &Shared<Set>, name: &str, args: Args) -> PyResult<Value> {
+    let frozen = set.borrow().frozen;
+    if frozen && !FROZENSET_METHODS.contains(&name) {
+        return raise(
+            "AttributeError",
+            format!("'frozenset' object has no attribute '{name}'"),
+        );
+    }
+    let others = |interp: &mut Interp, args: &Args| -> PyResult<Vec<Set>> {
+        let mut sets = Vec::new();
+        for arg in &args.pos {
+            let mut other = Set::default();
+            for item in interp.collect(arg)? {
+                other.map.insert(interp.key(&item)?, item);
+            }
+            sets.push(other);
+        }
+        Ok(sets)
+    };
+    match name {
+        "add" => {
+            let [item] = exact::<1>("add", args)?;
+            let key = interp.key(&item)?;
+            set.borrow_mut().map.entry(key).or_insert(item);
+        }
+        "remove" => {
+            let [item] = exact::<1>("remove", args)?;
+            let key = interp.key(&item)?;
+            if set.borrow_mut().map.shift_remove(&key).is_none() {
+                return Err(PyErr::with_arg("KeyError", item));
+            }
+        }
+        "discard" => {
+            let [item] = exact::<1>("discard", args)?;
+            let key = interp.key(&item)?;
+            set.borrow_mut().map.shift_remove(&key);
+        }
+        "pop" => {
+            return match set.borrow_mut().map.shift_remove_index(0) {
+                Some((_, item)) => Ok(item),
+                None => raise("KeyError", "pop from an empty set"),
+            };
+        }
+        "clear" => set.borrow_mut().map.clear(),
+        "copy" => return Ok(Value::Set(shared(set.borrow().clone()))),
+        "union" | "intersection" | "difference" | "symmetric_difference" => {
+            let mut result = set.borrow().clone();
+            for other in others(interp, &args)? {
+                apply_set_op(&mut result, &other, name);
+            }
+            return Ok(Value::Set(shared(result)));
+        }
+        "update" | "intersection_update" | "difference_update" | "symmetric_difference_update" => {
+            let op = name.trim_end_matches("_update");
+            let op = if op == "update" { "union" } else { op };
+            for other in others(interp, &args)? {
+                apply_set_op(&mut set.borrow_mut(), &other, op);
+            }
+        }
+        "issubset" | "issuperset" | "isdisjoint" => {
+            let [other] = exact::<1>(name, args)?;
+            let other = others(interp, &Args::new(vec![other]))?.remove(0);
+            let set = set.borrow();
+            return Ok(Value::Bool(match name {
+                "issubset" => set.map.keys().all(|k| other.map.contains_key(k)),
+                "issuperset" => other.map.keys().all(|k| set.map.contains_key(k)),
+                _ => !set.map.keys().any(|k| other.map.contains_key(k)),
+            }));
+        }
+        _ => {
+            return raise(
+                "AttributeError",
+                format!("'set' object has no attribute '{name}'"),
+            );
+        }
+    }
+    Ok(Value::None)
+}
+
+fn apply_set_op(set: &mut Set, other: &Set, op: &str) {
+    match op {
+        "union" => {
+            for (key, value) in &other.map {
+                set.map.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        "intersection" => set.map.retain(|key, _| other.map.contains_key(key)),
+        "difference" => set.map.retain(|key, _| !other.map.contains_key(key)),
+        _ => {
+            for (key, value) in &other.map {
+                if set.map.shift_remove(key).is_none() {
+                    set.map.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+}
+
+// ---- numbers and ranges ----
+
+fn int_method(receiver: &Value, name: &str, args: Args) -> PyResult<Value> {
+    exact::<0>(name, args)?;
+    let n = match receiver {
+        Value::Int(n) => *n,
+        Value::Bool(b) => *b as i64,
+        _ => 0,
+    };
+    Ok(match name {
+        "bit_length" => Value::Int(64 - n.unsigned_abs().leading_zeros() as i64),
+        "bit_count" => Value::Int(n.unsigned_abs().count_ones() as i64),
+        "conjugate" => Value::Int(n),
+        "is_integer" => Value::Bool(true),
+        "as_integer_ratio" => Value::tuple(vec![Value::Int(n), Value::Int(1)]),
+        _ => {
+            return raise(
+                "AttributeError",
+                format!("'int' object has no attribute '{name}'"),
+            );
+        }
+    })
+}
+
+fn float_method(x: f64, name: &str, args: Args) -> PyResult<Value> {
+    exact::<0>(name, args)?;
+    Ok(match name {
+        "is_integer" => Value::Bool(x.is_finite() && x.fract() == 0.0),
+        "conjugate" => Value::Float(x),
+        "as_integer_ratio" => {
+            if !x.is_finite() {
+                return raise("ValueError", "cannot convert Infinity or NaN to integer ratio");
+            }
+            let mut numerator = x;
+            let mut denominator: i64 = 1;
+            while numerator.fract() != 0.0 && denominator < (1 << 52) {
+                numerator *= 2.0;
+                denominator *= 2;
+            }
+            Value::tuple(vec![Value::Int(numerator as i64), Value::Int(denominator)])
+        }
+        _ => {
+            return raise(
+                "AttributeError",
+                format!("'float' object has no attribute '{name}'"),
+            );
+        }
+    })
+}
+
+fn range_method(interp: &mut Interp, range: &Value, name: &str, args: Args) -> PyResult<Value> {
+    let [value] = exact::<1>(name, args)?;
+    let Value::Range(start, _, step) = range else {
+        unreachable!()
+    };
+    let contained = interp.contains(range, &value)?;
+    match name {
+        "count" => Ok(Value::Int(contained as i64)),
+        _ if contained => {
+            let Value::Int(n) = value else {
+                return Ok(Value::Int(0));
+            };
+            Ok(Value::Int((n - start) / step))
+        }
+        _ => raise(
+            "ValueError",
+            format!("{} is not in range", interp.repr(&value)?),
+        ),
+    }
+}
+
+// ---- files ----
+
+/// `open(file, mode='r', ...)`.
+pub fn open(interp: &mut Interp, mut args: Args) -> PyResult<Value> {
+    let path = args.arg(0, "file").unwrap_or(Value::None);
+    let mode = args.arg(1, "mode").unwrap_or(Value::str("r"));
+    for ignored in ["buffering", "encoding", "errors", "newline", "closefd"] {
+        args.take_kw(ignored);
+    }
+    no_keywords("open", &args)?;
+    let path = match &path {
+        Value::Str(path) => path.clone(),
+        Value::Instance(_) => match interp.dunder(&path, "__fspath__", Vec::new())? {
+            Some(Value::Str(path)) => path,
+            _ => return raise("TypeError", "expected str, bytes or os.PathLike object"),
+        },
+        other => {
+            return raise(
+                "TypeError",
+                format!(
+                    "expected str, bytes or os.PathLike object, not {}",
+                    other.type_name()
+                ),
+            );
+        }
+    };
+    let Value::Str(mode) = mode else {
+        return raise("TypeError", "open() argument 'mode' must be str");
+    };
+    let base: String = mode.chars().filter(|c| !matches!(c, 't' | 'b')).collect();
+    let handle = match base.as_str() {
+        "r" => {
+            let bytes = std::fs::read(&*path).map_err(|e| os_error(&e, &path))?;
+            match String::from_utf8(bytes) {
+                Ok(text) => Handle::Read { text, pos: 0 },
+                Err(e) => {
+                    let position = e.utf8_error().valid_up_to();
+                    return raise(
+                        "UnicodeDecodeError",
+                        format!(
+                            "'utf-8' codec can't decode byte 0x{:02x} in position {position}: invalid start byte",
+                            e.as_bytes()[position]
+                        ),
+                    );
+                }
+            }
+        }
+        "w" | "a" | "x" => {
+            let mut options = std::fs::OpenOptions::new();
+            match base.as_str() {
+                "w" => options.write(true).create(true).truncate(true),
+                "a" => options.append(true).create(true),
+                _ => options.write(true).create_new(true),
+            };
+            let file = options.open(&*path).map_err(|e| os_error(&e, &path))?;
+            Handle::Write(std::io::BufWriter::new(file))
+        }
+        _ => return raise("ValueError", format!("invalid mode: '{mode}'")),
+    };
+    Ok(Value::File(shared(File {
+        name: path,
+        mode,
+        handle,
+    })))
+}
+
+/// Read the next line, newline included; `None` at end of file.
+pub fn read_line(file: &Shared<File>) -> PyResult<Option<String>> {
+    let mut file = file.borrow_mut();
+    match &mut file.handle {
+        Handle::Read { text, pos } => {
+            if *pos >= text.len() {
+                return Ok(None);
+            }
+            let end = text[*pos..].find('\n').map_or(text.len(), |i| *pos + i + 1);
+            let line = text[*pos..end].to_string();
+            *pos = end;
+            Ok(Some(line))
+        }
+        Handle::Stdin => {
+            let mut line = String::new();
+            match std::io::stdin().lock().read_line(&mut line) {
+                Ok(0) => Ok(None),
+                Ok(_) => Ok(Some(line)),
+                Err(e) => Err(os_error(&e, "<stdin>")),
+            }
+        }
+        Handle::Closed => raise("ValueError", "I/O operation on closed file."),
+        _ => raise("OSError", "not readable"),
+    }
+}
+
+fn file_method(
+    interp: &mut Interp,
+    receiver: &Value,
+    file: &Shared<File>,
+    name: &str,
+    args: Args,
+) -> PyResult<Value> {
+    match name {
+        "read" => {
+            let size = match args.pos.first() {
+                Some(value) if !value.is_none() => index_value(interp, value)?,
+                _ => -1,
+            };
+            let mut file = file.borrow_mut();
+            match &mut file.handle {
+                Handle::Read { text, pos } => {
+                    let rest = &text[*pos..];
+                    let end = if size < 0 {
+                        rest.len()
+                    } else {
+                        rest.char_indices().nth(size as usize).map_or(rest.len(), |(i, _)| i)
+                    };
+                    let chunk = rest[..end].to_string();
+                    *pos += end;
+                    Ok(Value::str(&chunk))
+                }
+                Handle::Stdin => {
+                    let mut text = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut text)
+                        .map_err(|e| os_error(&e, "<stdin>"))?;
+                    Ok(Value::str(&text))
+                }
+                Handle::Closed => raise("ValueError", "I/O operation on closed file."),
+                _ => raise("OSError", "not readable"),
+            }
+        }
+        "readline" => Ok(Value::str(&read_line(file)?.unwrap_or_default())),
+        "readlines" => {
+            let mut lines = Vec::new();
+            while let Some(line) = read_line(file)? {
+                lines.push(Value::str(&line));
+            }
+            Ok(Value::list(lines))
+        }
+        "write" => {
+            let [text] = exact::<1>("write", args)?;
+            let Value::Str(text) = text else {
+                return raise(
+                    "TypeError",
+                    format!("write() argument must be str, not {}", text.type_name()),
+                );
+            };
+            write_file(interp, file, &text)?;
+            Ok(Value::Int(text.chars().count() as i64))
+        }
+        "writelines" => {
+            let [lines] = exact::<1>("writelines", args)?;
+            for line in interp.collect(&lines)? {
+                let Value::Str(line) = line else {
+                    return raise("TypeError", "write() argument must be str");
+                };
+                write_file(interp, file, &line)?;
+            }
+            Ok(Value::None)
+        }
+        "flush" => {
+            let mut file = file.borrow_mut();
+            match &mut file.handle {
+                Handle::Write(writer) => writer.flush().map_err(|e| os_error(&e, ""))?,
+                Handle::Stdout => {
+                    drop(file);
+                    interp.flush();
+                }
+                _ => {}
+            }
+            Ok(Value::None)
+        }
+        "close" | "__exit__" => {
+            let mut file = file.borrow_mut();
+            let path = file.name.clone();
+            if let Handle::Write(writer) = &mut file.handle {
+                writer.flush().map_err(|e| os_error(&e, &path))?;
+            }
+            if !matches!(file.handle, Handle::Stdin | Handle::Stdout | Handle::Stderr) {
+                file.handle = Handle::Closed;
+            }
+            Ok(Value::None)
+        }
+        "__enter__" => Ok(receiver.clone()),
+        "__iter__" => interp.iter(receiver),
+        "__next__" => match read_line(file)? {
+            Some(line) => Ok(Value::str(&line)),
+            None => Err(PyErr::with_arg("StopIteration", Value::None)),
+        },
+        "seek" => {
+            let [offset] = exact::<1>("seek", args)?;
+            let offset = index_value(interp, &offset)?;
+            if let Handle::Read { text, pos } = &mut file.borrow_mut().handle {
+                *pos = (offset.max(0) as usize).min(text.len());
+            }
+            Ok(Value::Int(offset))
+        }
+        "tell" => Ok(Value::Int(match &file.borrow().handle {
+            Handle::Read { pos, .. } => *pos as i64,
+            _ => 0,
+        })),
+        "readable" => Ok(Value::Bool(matches!(
+            file.borrow().handle,
+            Handle::Read { .. } | Handle::Stdin
+        ))),
+        "writable" => Ok(Value::Bool(matches!(
+            file.borrow().handle,
+            Handle::Write(_) | Handle::Stdout | Handle::Stderr
+        ))),
+        "isatty" => Ok(Value::Bool(false)),
+        _ => raise(
+            "AttributeError",
+            format!("'_io.TextIOWrapper' object has no attribute '{name}'"),
+        ),
+    }
+}
+
+fn write_file(interp: &mut Interp, file: &Shared<File>, text: &str) -> PyResult<()> {
+    let mut file = file.borrow_mut();
+    let path = file.name.clone();
+    match &mut file.handle {
+        Handle::Write(writer) => writer
+            .write_all(text.as_bytes())
+            .map_err(|e| os_error(&e, &path)),
+        Handle::Stdout => {
+            drop(file);
+            interp.write_out(text);
+            Ok(())
+        }
+        Handle::Stderr => {
+            drop(file);
+            interp.write_err(text);
+            Ok(())
+        }
+        Handle::Closed => raise("ValueError", "I/O operation on closed file."),
+        _ => raise("OSError", "not writable"),
+    }
+}
@@ -0,0 +1,116 @@
+//! `python`/`python3`: a tree-walking interpreter for a practical subset of
+//! Python 3 with a small standard library. Native modules (`sys`, `os`,
+//! `os.path`, `math`, `json`, `re`, `time`, `random`, `string`, `copy`) are in
+//! Rust; the rest are Python preludes under `lib/` run on first import.
+
+mod ast;
+mod builtins;
+mod error;
+mod format;
+mod interp;
+mod json;
+mod lexer;
+mod methods;
+mod modules;
+mod ops;
+mod parser;
+mod re;
+mod value;
+
+use std::io::Read;
+
+use interp::Interp;
+
+/// The language version reported by `python --version` and `sys.version`.
+pub const VERSION: &str = "3.12.0";
+
+pub fn run(args: &[String]) -> i32 {
+    let mut args = args;
+    while let Some(option) = args.first() {
+        match option.as_str() {
+            "-V" | "--version" => {
+                println!("Python {VERSION}");
+                return 0;
+            }
+            "-h" | "--help" => {
+                print_usage();
+                return 0;
+            }
+            // Output is never buffered past exit, bytecode is never written and
+            // there is no environment or user site to ignore
+            "-u" | "-B" | "-E" | "-s" | "-S" | "-I" | "-O" | "-OO" | "-q" => {}
+            "-m" => {
+                eprintln!("python: -m is not supported");
+                return 2;
+            }
+            _ => break,
+        }
+        args = &args[1..];
+    }
+
+    let mut path: Vec<String> = std::env::var("PYTHONPATH")
+        .map(|value| {
+            value
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let (source, file, argv) = match args.first().map(String::as_str) {
+        Some("-c") => {
+            let Some(code) = args.get(1) else {
+                eprintln!("Argument expected for the -c option");
+                return 2;
+            };
+            let mut argv = vec!["-c".to_string()];
+            argv.extend(args[2..].iter().cloned());
+            path.insert(0, String::new());
+            (code.clone(), "<string>".to_string(), argv)
+        }
+        None | Some("-") => {
+            let mut source = String::new();
+            if let Err(e) = std::io::stdin().read_to_string(&mut source) {
+                eprintln!("python: cannot read stdin: {e}");
+                return 1;
+            }
+            let mut argv = vec![args.first().cloned().unwrap_or_default()];
+            argv.extend(args.iter().skip(1).cloned());
+            path.insert(0, String::new());
+            (source, "<stdin>".to_string(), argv)
+        }
+        Some(script) => {
+            let source = match std::fs::read_to_string(script) {
+                Ok(source) => source,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    eprintln!(
+                        "python: can't open file '{script}': [Errno 2] No such file or directory"
+                    );
+                    return 2;
+                }
+                Err(e) => {
+                    eprintln!("python: can't open file '{script}': {e}");
+                    return 2;
+                }
+            };
+            path.insert(0, Interp::script_dir(script));
+            (source, script.to_string(), args.to_vec())
+        }
+    };
+
+    let mut interp = Interp::new(argv, path);
+    interp.run_main(&source, &file)
+}
+
+fn print_usage() {
+    println!("usage: python [option] ... [-c cmd | file | -] [arg] ...");
+    println!("Options:");
+    println!("  -c cmd         program passed in as string (terminates option list)");
+    println!("  -V, --version  print the Python version number and exit");
+    println!("  -u, -B, -E, -I, -O, -s, -S, -q");
+    println!("                 accepted for compatibility and ignored");
+    println!("file             program read from script file");
+    println!("-                program read from stdin (default)");
+    println!("arg ...          arguments passed to program in sys.argv[1:]");
+}
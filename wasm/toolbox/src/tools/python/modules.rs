@@ -0,0 +1,1412 @@
+//! The standard library subset: native modules implemented here, and
+//! modules written in Python that are compiled into the binary.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::ast::BinOp;
+use super::builtins::{exact, index_value, no_keywords};
+use super::error::{PyErr, PyResult, int_too_large, os_error, raise};
+use super::interp::Interp;
+use super::value::*;
+use super::{json, re};
+
+/// Native modules and their functions, by qualified name.
+const NATIVE: &[(&str, &[&str])] = &[
+    (
+        "sys",
+        &[
+            "sys.exit", "sys.getdefaultencoding", "sys.getrecursionlimit", "sys.intern",
+            "sys.setrecursionlimit",
+        ],
+    ),
+    (
+        "os",
+        &[
+            "os.chdir", "os.cpu_count", "os.getcwd", "os.getenv", "os.getpid", "os.listdir",
+            "os.makedirs", "os.mkdir", "os.remove", "os.rename", "os.replace", "os.rmdir",
+            "os.unlink", "os.walk",
+        ],
+    ),
+    (
+        "os.path",
+        &[
+            "os.path.abspath", "os.path.basename", "os.path.commonprefix", "os.path.dirname",
+            "os.path.exists", "os.path.expanduser", "os.path.getmtime", "os.path.getsize",
+            "os.path.isabs", "os.path.isdir", "os.path.isfile", "os.path.islink", "os.path.join",
+            "os.path.normpath", "os.path.realpath", "os.path.relpath", "os.path.split",
+            "os.path.splitext",
+        ],
+    ),
+    (
+        "math",
+        &[
+            "math.acos", "math.acosh", "math.asin", "math.asinh", "math.atan", "math.atan2",
+            "math.atanh", "math.cbrt", "math.ceil", "math.comb", "math.copysign", "math.cos",
+            "math.cosh", "math.degrees", "math.dist", "math.exp", "math.exp2", "math.expm1",
+            "math.fabs", "math.factorial", "math.floor", "math.fmod", "math.frexp", "math.fsum",
+            "math.gcd", "math.hypot", "math.isclose", "math.isfinite", "math.isinf", "math.isnan",
+            "math.isqrt", "math.lcm", "math.ldexp", "math.log", "math.log10", "math.log1p",
+            "math.log2", "math.modf", "math.perm", "math.pow", "math.prod", "math.radians",
+            "math.sin", "math.sinh", "math.sqrt", "math.tan", "math.tanh", "math.trunc",
+        ],
+    ),
+    ("json", &["json.dump", "json.dumps", "json.load", "json.loads"]),
+    (
+        "re",
+        &[
+            "re.compile", "re.escape", "re.findall", "re.finditer", "re.fullmatch", "re.match",
+            "re.purge", "re.search", "re.split", "re.sub", "re.subn",
+        ],
+    ),
+    (
+        "time",
+        &[
+            "time.monotonic", "time.perf_counter", "time.perf_counter_ns", "time.process_time",
+            "time.sleep", "time.time", "time.time_ns",
+        ],
+    ),
+    (
+        "random",
+        &[
+            "random.choice", "random.choices", "random.gauss", "random.getrandbits",
+            "random.normalvariate", "random.randint", "random.random", "random.randrange",
+            "random.sample", "random.seed", "random.shuffle", "random.uniform",
+        ],
+    ),
+    ("string", &["string.capwords"]),
+    ("copy", &["copy.copy", "copy.deepcopy"]),
+    ("_csv", &["_csv.read_record"]),
+    ("_collections", &[]),
+];
+
+/// Modules written in Python.
+const PRELUDES: &[(&str, &str)] = &[
+    ("bisect", include_str!("lib/bisect.py")),
+    ("collections", include_str!("lib/collections.py")),
+    ("csv", include_str!("lib/csv.py")),
+    ("dataclasses", include_str!("lib/dataclasses.py")),
+    ("enum", include_str!("lib/enum.py")),
+    ("functools", include_str!("lib/functools.py")),
+    ("heapq", include_str!("lib/heapq.py")),
+    ("io", include_str!("lib/io.py")),
+    ("itertools", include_str!("lib/itertools.py")),
+    ("operator", include_str!("lib/operator.py")),
+    ("statistics", include_str!("lib/statistics.py")),
+    ("textwrap", include_str!("lib/textwrap.py")),
+    ("typing", include_str!("lib/typing.py")),
+];
+
+/// Load standard library module `name`, or `None` if there is no such module.
+pub fn load(interp: &mut Interp, name: &str) -> PyResult<Option<Value>> {
+    if let Some((_, source)) = PRELUDES.iter().find(|(module, _)| *module == name) {
+        let file = format!("<frozen {name}>");
+        return interp.run_module(name, source, &file).map(Some);
+    }
+    let Some((_, functions)) = NATIVE.iter().find(|(module, _)| *module == name) else {
+        return Ok(None);
+    };
+    let module = Rc::new(Module {
+        name: Rc::from(name),
+        scope: Scope::new(ScopeKind::Module, None),
+    });
+    module.set("__name__", Value::str(name));
+    for &function in *functions {
+        module.set(&function[name.len() + 1..], Value::Builtin(function));
+    }
+    let float = |x: f64| Value::Float(x);
+    let text = Value::str;
+    match name {
+        "sys" => {
+            let argv = interp.argv.iter().map(|arg| Value::str(arg)).collect();
+            module.set("argv", Value::list(argv));
+            module.set("path", interp.sys_path.clone());
+            for (attr, handle) in [
+                ("stdin", Handle::Stdin),
+                ("stdout", Handle::Stdout),
+                ("stderr", Handle::Stderr),
+            ] {
+                let file = File {
+                    name: Rc::from(format!("<{attr}>")),
+                    mode: Rc::from(if attr == "stdin" { "r" } else { "w" }),
+                    handle,
+                };
+                module.set(attr, Value::File(shared(file)));
+            }
+            module.set("version", text(super::VERSION));
+            module.set(
+                "version_info",
+                Value::tuple(vec![
+                    Value::Int(3),
+                    Value::Int(12),
+                    Value::Int(0),
+                    text("final"),
+                    Value::Int(0),
+                ]),
+            );
+            module.set("platform", text("wasi"));
+            module.set("byteorder", text("little"));
+            module.set("maxsize", Value::Int(i64::MAX));
+            module.set("executable", text("/usr/bin/python3"));
+            let mut modules = Dict::default();
+            for (name, module) in &interp.modules {
+                modules.set_str(name, module.clone());
+            }
+            module.set("modules", Value::dict(modules));
+        }
+        "os" => {
+            module.set("path", interp.import("os.path")?);
+            module.set("sep", text("/"));
+            module.set("pathsep", text(":"));
+            module.set("linesep", text("\n"));
+            module.set("curdir", text("."));
+            module.set("pardir", text(".."));
+            module.set("devnull", text("/dev/null"));
+            module.set("name", text("posix"));
+            let mut environ = Dict::default();
+            let mut vars: Vec<(String, String)> = std::env::vars().collect();
+            vars.sort();
+            for (key, value) in vars {
+                environ.set_str(&key, Value::str(&value));
+            }
+            module.set("environ", Value::dict(environ));
+        }
+        "os.path" => module.set("sep", text("/")),
+        "math" => {
+            module.set("pi", float(std::f64::consts::PI));
+            module.set("e", float(std::f64::consts::E));
+            module.set("tau", float(std::f64::consts::TAU));
+            module.set("inf", float(f64::INFINITY));
+            module.set("nan", float(f64::NAN));
+        }
+        "json" => {
+            let error = interp.exception_class("JSONDecodeError");
+            module.set("JSONDecodeError", Value::Class(error));
+        }
+        "re" => {
+            for (names, flag) in [
+                (&["I", "IGNORECASE"], re::IGNORECASE),
+                (&["L", "LOCALE"], re::LOCALE),
+                (&["M", "MULTILINE"], re::MULTILINE),
+                (&["S", "DOTALL"], re::DOTALL),
+                (&["U", "UNICODE"], re::UNICODE),
+                (&["X", "VERBOSE"], re::VERBOSE),
+                (&["A", "ASCII"], re::ASCII),
+            ] {
+                for name in names {
+                    module.set(name, Value::Int(flag));
+                }
+            }
+            module.set("NOFLAG", Value::Int(0));
+            module.set("error", Value::Class(interp.exception_class("re.error")));
+            module.set("Pattern", Value::Type(Type::Pattern));
+            module.set("Match", Value::Type(Type::Match));
+        }
+        "string" => {
+            let lower = "abcdefghijklmnopqrstuvwxyz";
+            let upper = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+            let digits = "0123456789";
+            let punctuation = r##"!"#$%&'()*+,-./:;<=>?@[\]^_`{|}~"##;
+            let whitespace = " \t\n\r\x0b\x0c";
+            module.set("ascii_lowercase", text(lower));
+            module.set("ascii_uppercase", text(upper));
+            module.set("ascii_letters", text(&format!("{lower}{upper}")));
+            module.set("digits", text(digits));
+            module.set("hexdigits", text("0123456789abcdefABCDEF"));
+            module.set("octdigits", text("01234567"));
+            module.set("punctuation", text(punctuation));
+            module.set("whitespace", text(whitespace));
+            module.set(
+                "printable",
+                text(&format!("{digits}{lower}{upper}{punctuation}{whitespace}")),
+            );
+        }
+        "_collections" => {
+            module.set("Counter", Value::Type(Type::Counter));
+            module.set("OrderedDict", Value::Type(Type::OrderedDict));
+            module.set("defaultdict", Value::Type(Type::DefaultDict));
+        }
+        _ => {}
+    }
+    Ok(Some(Value::Module(module)))
+}
+
+/// Call native module function `name`, e.g. `math.sqrt`.
+pub fn call(interp: &mut Interp, name: &str, args: Args) -> PyResult<Value> {
+    let (module, function) = name.rsplit_once('.').unwrap_or(("", name));
+    match module {
+        "sys" => sys(interp, function, args),
+        "os" => os(interp, function, args),
+        "os.path" => os_path(interp, function, args),
+        "math" => math(interp, function, args),
+        "json" => json::call(interp, function, args),
+        "re" => re::call(interp, function, args),
+        "time" => time(interp, function, args),
+        "random" => random(interp, function, args),
+        "string" => {
+            let [text] = exact::<1>("capwords", args)?;
+            let Value::Str(text) = text else {
+                return raise("TypeError", "capwords() argument must be str");
+            };
+            let words: Vec<String> = text
+                .split_whitespace()
+                .map(|word| {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) => first.to_uppercase().collect::<String>() + &chars.as_str().to_lowercase(),
+                        None => String::new(),
+                    }
+                })
+                .collect();
+            Ok(Value::str(&words.join(" ")))
+        }
+        "copy" => {
+            let [value] = exact::<1>(function, args)?;
+            if function == "copy" {
+                shallow_copy(interp, &value)
+            } else {
+                deep_copy(interp, &value, &mut HashMap::new())
+            }
+        }
+        "_csv" => read_record(interp, args),
+        _ => raise("NameError", format!("name '{name}' is not defined")),
+    }
+}
+
+// ---- sys ----
+
+fn sys(interp: &mut Interp, function: &str, args: Args) -> PyResult<Value> {
+    match function {
+        "exit" => {
+            let code = args.pos.first().cloned().unwrap_or(Value::None);
+            Err(PyErr::with_arg("SystemExit", code))
+        }
+        "getrecursionlimit" => Ok(Value::Int(interp.recursion_limit as i64)),
+        "setrecursionlimit" => {
+            let [limit] = exact::<1>("setrecursionlimit", args)?;
+            let limit = index_value(interp, &limit)?;
+            if limit < 1 {
+                return raise("ValueError", "recursion limit must be greater or equal than 1");
+            }
+            interp.recursion_limit = limit as usize;
+            Ok(Value::None)
+        }
+        "getdefaultencoding" => Ok(Value::str("utf-8")),
+        _ => {
+            let [value] = exact::<1>(function, args)?;
+            Ok(value)
+        }
+    }
+}
+
+// ---- os and os.path ----
+
+fn path_arg(interp: &mut Interp, value: &Value) -> PyResult<Rc<str>> {
+    match value {
+        Value::Str(path) => Ok(path.clone()),
+        Value::Instance(_) => match interp.dunder(value, "__fspath__", Vec::new())? {
+            Some(Value::Str(path)) => Ok(path),
+            _ => raise("TypeError", "expected str, bytes or os.PathLike object"),
+        },
+        other => raise(
+            "TypeError",
+            format!(
+                "expected str, bytes or os.PathLike object, not {}",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+fn cwd() -> String {
+    std::env::current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "/".to_string())
+}
+
+fn sorted_entries(path: &str) -> PyResult<Vec<(String, bool)>> {
+    let entries = std::fs::read_dir(path).map_err(|e| os_error(&e, path))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| os_error(&e, path))?;
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        names.push((entry.file_name().to_string_lossy().into_owned(), is_dir));
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn os(interp: &mut Interp, function: &str, mut args: Args) -> PyResult<Value> {
+    match function {
+        "getcwd" => Ok(Value::str(&cwd())),
+        "cpu_count" => Ok(Value::Int(1)),
+        "getpid" => Ok(Value::Int(1)),
+        "getenv" => {
+            let key = args.arg(0, "key").unwrap_or(Value::None);
+            let default = args.arg(1, "default").unwrap_or(Value::None);
+            let key = path_arg(interp, &key)?;
+            Ok(std::env::var(&*key).map_or(default, |value| Value::str(&value)))
+        }
+        "listdir" => {
+            let path = match args.arg(0, "path") {
+                Some(path) => path_arg(interp, &path)?,
+                None => Rc::from("."),
+            };
+            let names = sorted_entries(&path)?;
+            Ok(Value::list(names.iter().map(|(name, _)| Value::str(name)).collect()))
+        }
+        "walk" => {
+            let top = args.arg(0, "top").unwrap_or(Value::None);
+            let top = path_arg(interp, &top)?;
+            let mut results = Vec::new();
+            let mut pending = vec![top.to_string()];
+            while let Some(dir) = pending.pop() {
+                // Unreadable directories are skipped, as `os.walk` does by default
+                let Ok(entries) = sorted_entries(&dir) else {
+                    continue;
+                };
+                let (dirs, files): (Vec<_>, Vec<_>) = entries.into_iter().partition(|(_, is_dir)| *is_dir);
+                let join = |name: &str| match dir.ends_with('/') {
+                    true => format!("{dir}{name}"),
+                    false => format!("{dir}/{name}"),
+                };
+                for (name, _) in dirs.iter().rev() {
+                    pending.push(join(name));
+                }
+                let names = |entries: &[(String, bool)]| {
+                    Value::list(entries.iter().map(|(name, _)| Value::str(name)).collect())
+                };
+                results.push(Value::tuple(vec![Value::str(&dir), names(&dirs), names(&files)]));
+            }
+            Ok(Value::iterator(Iter::Values(results.into_iter())))
+        }
+        "makedirs" => {
+            let exist_ok = match args.take_kw("exist_ok") {
+                Some(value) => interp.truthy(&value)?,
+                None => match args.pos.get(2) {
+                    Some(value) => interp.truthy(value)?,
+                    None => false,
+                },
+            };
+            args.take_kw("mode");
+            no_keywords("makedirs", &args)?;
+            let path = args.pos.first().cloned().unwrap_or(Value::None);
+            let path = path_arg(interp, &path)?;
+            if Path::new(&*path).is_dir() {
+                if exist_ok {
+                    return Ok(Value::None);
+                }
+                return raise(
+                    "FileExistsError",
+                    format!("[Errno 17] File exists: '{path}'"),
+                );
+            }
+            std::fs::create_dir_all(&*path).map_err(|e| os_error(&e, &path))?;
+            Ok(Value::None)
+        }
+        "chdir" | "mkdir" | "remove" | "unlink" | "rmdir" => {
+            args.take_kw("mode");
+            let path = args.arg(0, "path").unwrap_or(Value::None);
+            let path = path_arg(interp, &path)?;
+            let result = match function {
+                "chdir" => std::env::set_current_dir(&*path),
+                "mkdir" => std::fs::create_dir(&*path),
+                "rmdir" => std::fs::remove_dir(&*path),
+                _ => std::fs::remove_file(&*path),
+            };
+            result.map_err(|e| os_error(&e, &path))?;
+            Ok(Value::None)
+        }
+        "rename" | "replace" => {
+            let [from, to] = exact::<2>(function, args)?;
+            let from = path_arg(interp, &from)?;
+            let to = path_arg(interp, &to)?;
+            std::fs::rename(&*from, &*to).map_err(|e| os_error(&e, &from))?;
+            Ok(Value::None)
+        }
+        _ => raise("AttributeError", format!("module 'os' has no attribute '{function}'")),
+    }
+}
+
+/// `os.path.normpath`.
+fn normpath(path: &str) -> String {
+    if path.is_empty() {
+        return ".".to_string();
+    }
+    let absolute = path.starts_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|last| *last != "..") => {
+                parts.pop();
+            }
+            ".." if absolute => {}
+            part => parts.push(part),
+        }
+    }
+    let joined = parts.join("/");
+    match (absolute, joined.is_empty()) {
+        (true, _) => format!("/{joined}"),
+        (false, true) => ".".to_string(),
+        (false, false) => joined,
+    }
+}
+
+fn abspath(path: &str) -> String {
+    if path.starts_with('/') {
+        normpath(path)
+    } else {
+        normpath(&format!("{}/{path}", cwd()))
+    }
+}
+
+/// `os.path.split`: the directory and the last component.
+fn split_path(path: &str) -> (String, String) {
+    let i = path.rfind('/').map_or(0, |i| i + 1);
+    let (head, tail) = path.split_at(i);
+    let trimmed = head.trim_end_matches('/');
+    let head = if trimmed.is_empty() { head } else { trimmed };
+    (head.to_string(), tail.to_string())
+}
+
+fn os_path(interp: &mut Interp, function: &str, args: Args) -> PyResult<Value> {
+    if function == "join" {
+        no_keywords("join", &args)?;
+        let mut path = String::new();
+        for (i, part) in args.pos.iter().enumerate() {
+            let part = path_arg(interp, part)?;
+            if part.starts_with('/') || i == 0 {
+                path = part.to_string();
+            } else if path.is_empty() || path.ends_with('/') {
+                path.push_str(&part);
+            } else {
+                path.push('/');
+                path.push_str(&part);
+            }
+        }
+        if args.pos.is_empty() {
+            return raise("TypeError", "join() missing 1 required positional argument: 'a'");
+        }
+        return Ok(Value::str(&path));
+    }
+    if function == "commonprefix" {
+        let [paths] = exact::<1>("commonprefix", args)?;
+        let paths = interp.collect(&paths)?;
+        let mut prefix: Option<String> = None;
+        for path in &paths {
+            let path = path_arg(interp, path)?;
+            prefix = Some(match prefix {
+                None => path.to_string(),
+                Some(prefix) => prefix
+                    .chars()
+                    .zip(path.chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a)
+                    .collect(),
+            });
+        }
+        return Ok(Value::str(&prefix.unwrap_or_default()));
+    }
+    if function == "relpath" {
+        let mut args = args;
+        let path = args.arg(0, "path").unwrap_or(Value::None);
+        let path = abspath(&path_arg(interp, &path)?);
+        let start = match args.arg(1, "start") {
+            Some(start) if !start.is_none() => abspath(&path_arg(interp, &start)?),
+            _ => cwd(),
+        };
+        let path: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+        let start: Vec<&str> = start.split('/').filter(|p| !p.is_empty()).collect();
+        let common = path.iter().zip(&start).take_while(|(a, b)| a == b).count();
+        let mut parts = vec![".."; start.len() - common];
+        parts.extend(&path[common..]);
+        return Ok(Value::str(&if parts.is_empty() {
+            ".".to_string()
+        } else {
+            parts.join("/")
+        }));
+    }
+    let [path] = exact::<1>(function, args)?;
+    let path = path_arg(interp, &path)?;
+    let metadata = || std::fs::metadata(&*path);
+    Ok(match function {
+        "exists" => Value::Bool(metadata().is_ok()),
+        "isfile" => Value::Bool(metadata().is_ok_and(|m| m.is_file())),
+        "isdir" => Value::Bool(metadata().is_ok_and(|m| m.is_dir())),
+        "islink" => Value::Bool(
+            std::fs::symlink_metadata(&*path).is_ok_and(|m| m.file_type().is_symlink()),
+        ),
+        "isabs" => Value::Bool(path.starts_with('/')),
+        "basename" => Value::str(&split_path(&path).1),
+        "dirname" => Value::str(&split_path(&path).0),
+        "split" => {
+            let (head, tail) = split_path(&path);
+            Value::tuple(vec![Value::str(&head), Value::str(&tail)])
+        }
+        "splitext" => {
+            let name_start = path.rfind('/').map_or(0, |i| i + 1);
+            let name = &path[name_start..];
+            let stem_start = name.len() - name.trim_start_matches('.').len();
+            let (root, ext) = match name.rfind('.') {
+                Some(dot) if dot > stem_start => path.split_at(name_start + dot),
+                _ => (&*path, ""),
+            };
+            Value::tuple(vec![Value::str(root), Value::str(ext)])
+        }
+        "normpath" => Value::str(&normpath(&path)),
+        "abspath" => Value::str(&abspath(&path)),
+        "realpath" => Value::str(&match std::fs::canonicalize(&*path) {
+            Ok(real) => real.to_string_lossy().into_owned(),
+            Err(_) => abspath(&path),
+        }),
+        "expanduser" => {
+            let home = std::env::var("HOME").ok();
+            match (path.strip_prefix('~'), home) {
+                (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+                    Value::str(&format!("{}{rest}", home.trim_end_matches('/')))
+                }
+                _ => Value::Str(path.clone()),
+            }
+        }
+        "getsize" => Value::Int(metadata().map_err(|e| os_error(&e, &path))?.len() as i64),
+        "getmtime" => {
+            let modified = metadata()
+                .and_then(|m| m.modified())
+                .map_err(|e| os_error(&e, &path))?;
+            let seconds = modified
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |d| d.as_secs_f64());
+            Value::Float(seconds)
+        }
+        _ => {
+            return raise(
+                "AttributeError",
+                format!("module 'posixpath' has no attribute '{function}'"),
+            );
+        }
+    })
+}
+
+// ---- math ----
+
+fn real(value: &Value) -> PyResult<f64> {
+    match value {
+        Value::Int(n) => Ok(*n as f64),
+        Value::Bool(b) => Ok(*b as i64 as f64),
+        Value::Float(x) => Ok(*x),
+        other => raise(
+            "TypeError",
+            format!("must be real number, not {}", other.type_name()),
+        ),
+    }
+}
+
+fn integer(value: &Value) -> PyResult<i64> {
+    match value {
+        Value::Int(n) => Ok(*n),
+        Value::Bool(b) => Ok(*b as i64),
+        other => raise(
+            "TypeError",
+            format!(
+                "'{}' object cannot be interpreted as an integer",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+fn domain_error<T>() -> PyResult<T> {
+    raise("ValueError", "math domain error")
+}
+
+/// A float result, raising `OverflowError` where Python's `math` does.
+fn checked(x: f64, inputs_finite: bool) -> PyResult<Value> {
+    if x.is_infinite() && inputs_finite {
+        return raise("OverflowError", "math range error");
+    }
+    Ok(Value::Float(x))
+}
+
+/// Float to int for `floor`, `ceil` and `trunc`.
+fn float_to_int(x: f64) -> PyResult<Value> {
+    if x.is_nan() {
+        return raise("ValueError", "cannot convert float NaN to integer");
+    }
+    if x.is_infinite() {
+        return raise("OverflowError", "cannot convert float infinity to integer");
+    }
+    if !(i64::MIN as f64..i64::MAX as f64).contains(&x) {
+        return int_too_large();
+    }
+    Ok(Value::Int(x as i64))
+}
+
+fn gcd(mut a: i64, mut b: i64) -> i64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+fn comb_perm(n: i64, k: i64, comb: bool) -> PyResult<Value> {
+    if n < 0 {
+        return raise("ValueError", "n must be a non-negative integer");
+    }
+    if k < 0 {
+        return raise("ValueError", "k must be a non-negative integer");
+    }
+    if k > n {
+        return Ok(Value::Int(0));
+    }
+    let k = if comb { k.min(n - k) } else { k };
+    let mut result: i128 = 1;
+    for i in 0..k {
+        result *= (n - i) as i128;
+        if comb {
+            result /= (i + 1) as i128;
+        }
+        if result > i64::MAX as i128 {
+            return int_too_large();
+        }
+    }
+    Ok(Value::Int(result as i64))
+}
+
+fn math(interp: &mut Interp, function: &str, mut args: Args) -> PyResult<Value> {
+    match function {
+        "gcd" | "lcm" => {
+            no_keywords(function, &args)?;
+            let mut result = if function == "gcd" { 0 } else { 1 };
+            for arg in &args.pos {
+                let n = integer(arg)?;
+                result = if function == "gcd" {
+                    gcd(result, n)
+                } else if n == 0 || result == 0 {
+                    0
+                } else {
+                    match (result / gcd(result, n)).checked_mul(n) {
+                        Some(lcm) => lcm.abs(),
+                        None => return int_too_large(),
+                    }
+                };
+            }
+            return Ok(Value::Int(result));
+        }
+        "hypot" => {
+            no_keywords(function, &args)?;
+            let mut sum = 0.0;
+            for arg in &args.pos {
+                let x = real(arg)?;
+                sum += x * x;
+            }
+            return Ok(Value::Float(sum.sqrt()));
+        }
+        "fsum" => {
+            let [iterable] = exact::<1>("fsum", args)?;
+            let mut sum = 0.0f64;
+            let mut compensation = 0.0f64;
+            for item in interp.collect(&iterable)? {
+                let x = real(&item)?;
+                let t = sum + x;
+                compensation += if sum.abs() >= x.abs() {
+                    (sum - t) + x
+                } else {
+                    (x - t) + sum
+                };
+                sum = t;
+            }
+            return Ok(Value::Float(sum + compensation));
+        }
+        "prod" => {
+            let start = args.take_kw("start").unwrap_or(Value::Int(1));
+            let [iterable] = exact::<1>("prod", args)?;
+            let mut product = start;
+            for item in interp.collect(&iterable)? {
+                product = interp.binop(BinOp::Mul, product, item)?;
+            }
+            return Ok(product);
+        }
+        "isclose" => {
+            let rel_tol = args.take_kw("rel_tol").map_or(Ok(1e-9), |v| real(&v))?;
+            let abs_tol = args.take_kw("abs_tol").map_or(Ok(0.0), |v| real(&v))?;
+            let [a, b] = exact::<2>("isclose", args)?;
+            let (a, b) = (real(&a)?, real(&b)?);
+            if rel_tol < 0.0 || abs_tol < 0.0 {
+                return raise("ValueError", "tolerances must be non-negative");
+            }
+            if a == b {
+                return Ok(Value::Bool(true));
+            }
+            if a.is_infinite() || b.is_infinite() {
+                return Ok(Value::Bool(false));
+            }
+            let diff = (a - b).abs();
+            return Ok(Value::Bool(
+                diff <= (rel_tol * b).abs() || diff <= (rel_tol * a).abs() || diff <= abs_tol,
+            ));
+        }
+        "log" => {
+            no_keywords("log", &args)?;
+            let (x, base) = match &args.pos[..] {
+                [x] => (real(x)?, None),
+                [x, base] => (real(x)?, Some(real(base)?)),
+                _ => return raise("TypeError", format!("log expected 1 or 2 arguments, got {}", args.pos.len())),
+            };
+            if x <= 0.0 || base.is_some_and(|b| b <= 0.0) {
+                return domain_error();
+            }
+            return Ok(Value::Float(match base {
+                Some(base) => {
+                    if base == 1.0 {
+                        return raise("ZeroDivisionError", "float division by zero");
+                    }
+                    x.ln() / base.ln()
+                }
+                None => x.ln(),
+            }));
+        }
+        "dist" => {
+            let [p, q] = exact::<2>("dist", args)?;
+            let p = interp.collect(&p)?;
+            let q = interp.collect(&q)?;
+            if p.len() != q.len() {
+                return raise("ValueError", "both points must have the same number of dimensions");
+            }
+            let mut sum = 0.0;
+            for (a, b) in p.iter().zip(&q) {
+                let d = real(a)? - real(b)?;
+                sum += d * d;
+            }
+            return Ok(Value::Float(sum.sqrt()));
+        }
+        "comb" | "perm" => {
+            no_keywords(function, &args)?;
+            let (n, k) = match &args.pos[..] {
+                [n] if function == "perm" => (integer(n)?, integer(n)?),
+                [n, Value::None] if function == "perm" => (integer(n)?, integer(n)?),
+                [n, k] => (integer(n)?, integer(k)?),
+                _ => return raise("TypeError", format!("{function} expected 2 arguments, got {}", args.pos.len())),
+            };
+            return comb_perm(n, k, function == "comb");
+        }
+        _ => {}
+    }
+    let two = matches!(
+        function,
+        "atan2" | "copysign" | "fmod" | "ldexp" | "pow"
+    );
+    if two {
+        let [a, b] = exact::<2>(function, args)?;
+        if function == "ldexp" {
+            let (x, i) = (real(&a)?, integer(&b)?);
+            return checked(x * 2f64.powi(i.clamp(-2000, 2000) as i32), x.is_finite());
+        }
+        let (x, y) = (real(&a)?, real(&b)?);
+        return match function {
+            "atan2" => Ok(Value::Float(x.atan2(y))),
+            "copysign" => Ok(Value::Float(x.copysign(y))),
+            "fmod" => {
+                if y == 0.0 || x.is_infinite() {
+                    return domain_error();
+                }
+                Ok(Value::Float(x % y))
+            }
+            _ => {
+                if x == 0.0 && y < 0.0 || x < 0.0 && y.fract() != 0.0 && y.is_finite() {
+                    return domain_error();
+                }
+                checked(x.powf(y), x.is_finite() && y.is_finite())
+            }
+        };
+    }
+    let [value] = exact::<1>(function, args)?;
+    match function {
+        "floor" | "ceil" | "trunc" => {
+            if let Value::Int(_) | Value::Bool(_) = value {
+                return Ok(Value::Int(integer(&value)?));
+            }
+            if let Value::Instance(_) = value {
+                let dunder = format!("__{function}__");
+                if let Some(result) = interp.dunder(&value, &dunder, Vec::new())? {
+                    return Ok(result);
+                }
+            }
+            let x = real(&value)?;
+            float_to_int(match function {
+                "floor" => x.floor(),
+                "ceil" => x.ceil(),
+                _ => x.trunc(),
+            })
+        }
+        "factorial" => {
+            let n = integer(&value)?;
+            if n < 0 {
+                return raise("ValueError", "factorial() not defined for negative values");
+            }
+            let mut result: i64 = 1;
+            for i in 2..=n {
+                result = match result.checked_mul(i) {
+                    Some(result) => result,
+                    None => return int_too_large(),
+                };
+            }
+            Ok(Value::Int(result))
+        }
+        "isqrt" => {
+            let n = integer(&value)?;
+            if n < 0 {
+                return raise("ValueError", "isqrt() argument must be nonnegative");
+            }
+            let mut root = (n as f64).sqrt() as i64;
+            while root * root > n {
+                root -= 1;
+            }
+            while (root + 1).checked_mul(root + 1).is_some_and(|square| square <= n) {
+                root += 1;
+            }
+            Ok(Value::Int(root))
+        }
+        "isnan" => Ok(Value::Bool(real(&value)?.is_nan())),
+        "isinf" => Ok(Value::Bool(real(&value)?.is_infinite())),
+        "isfinite" => Ok(Value::Bool(real(&value)?.is_finite())),
+        "modf" => {
+            let x = real(&value)?;
+            let whole = x.trunc();
+            let fraction = if x.is_infinite() { 0.0f64.copysign(x) } else { x - whole };
+            Ok(Value::tuple(vec![Value::Float(fraction), Value::Float(whole)]))
+        }
+        "frexp" => {
+            let x = real(&value)?;
+            if x == 0.0 || !x.is_finite() {
+                return Ok(Value::tuple(vec![Value::Float(x), Value::Int(0)]));
+            }
+            let exponent = x.abs().log2().floor() as i64 + 1;
+            let mut mantissa = x / 2f64.powi(exponent as i32);
+            let mut exponent = exponent;
+            // Correct rounding at exact powers of two
+            if mantissa.abs() >= 1.0 {
+                mantissa /= 2.0;
+                exponent += 1;
+            } else if mantissa.abs() < 0.5 {
+                mantissa *= 2.0;
+                exponent -= 1;
+            }
+            Ok(Value::tuple(vec![Value::Float(mantissa), Value::Int(exponent)]))
+        }
+        _ => {
+            let x = real(&value)?;
+            let finite = x.is_finite();
+            let result = match function {
+                "sqrt" if x < 0.0 => return domain_error(),
+                "sqrt" => x.sqrt(),
+                "cbrt" => x.cbrt(),
+                "exp" => return checked(x.exp(), finite),
+                "exp2" => return checked(x.exp2(), finite),
+                "expm1" => return checked(x.exp_m1(), finite),
+                "log2" | "log10" if x <= 0.0 => return domain_error(),
+                "log2" => x.log2(),
+                "log10" => x.log10(),
+                "log1p" if x <= -1.0 => return domain_error(),
+                "log1p" => x.ln_1p(),
+                "fabs" => x.abs(),
+                "sin" | "cos" | "tan" if x.is_infinite() => return domain_error(),
+                "sin" => x.sin(),
+                "cos" => x.cos(),
+                "tan" => x.tan(),
+                "asin" | "acos" if !(-1.0..=1.0).contains(&x) => return domain_error(),
+                "asin" => x.asin(),
+                "acos" => x.acos(),
+                "atan" => x.atan(),
+                "sinh" => return checked(x.sinh(), finite),
+                "cosh" => return checked(x.cosh(), finite),
+                "tanh" => x.tanh(),
+                "asinh" => x.asinh(),
+                "acosh" if x < 1.0 => return domain_error(),
+                "acosh" => x.acosh(),
+                "atanh" if x.abs() >= 1.0 => return domain_error(),
+                "atanh" => x.atanh(),
+                "degrees" => x.to_degrees(),
+                "radians" => x.to_radians(),
+                _ => {
+                    return raise(
+                        "AttributeError",
+                        format!("module 'math' has no attribute '{function}'"),
+                    );
+                }
+            };
+            Ok(Value::Float(result))
+        }
+    }
+}
+
+// ---- time ----
+
+thread_local! {
+    static START: Instant = Instant::now();
+}
+
+fn time(interp: &mut Interp, function: &str, args: Args) -> PyResult<Value> {
+    let since_epoch = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let elapsed = || START.with(Instant::elapsed);
+    if function == "sleep" {
+        let [seconds] = exact::<1>("sleep", args)?;
+        let seconds = real(&seconds)?;
+        if seconds < 0.0 {
+            return raise("ValueError", "sleep length must be non-negative");
+        }
+        interp.flush();
+        std::thread::sleep(Duration::from_secs_f64(seconds));
+        return Ok(Value::None);
+    }
+    exact::<0>(function, args)?;
+    Ok(match function {
+        "time" => Value::Float(since_epoch().as_secs_f64()),
+        "time_ns" => Value::Int(since_epoch().as_nanos() as i64),
+        "perf_counter_ns" => Value::Int(elapsed().as_nanos() as i64),
+        _ => Value::Float(elapsed().as_secs_f64()),
+    })
+}
+
+// ---- random ----
+
+thread_local! {
+    static RANDOM_STATE: Cell<u64> = Cell::new(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0x853c_49e6_748f_ea9b, |d| d.as_nanos() as u64),
+    );
+}
+
+/// The next 64 random bits (splitmix64).
+fn next_u64() -> u64 {
+    RANDOM_STATE.with(|state| {
+        let next = state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state.set(next);
+        let mut z = next;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    })
+}
+
+fn next_f64() -> f64 {
+    (next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A uniform integer in `0..n`.
+fn below(n: u64) -> u64 {
+    let zone = u64::MAX - u64::MAX % n;
+    loop {
+        let x = next_u64();
+        if x < zone {
+            return x % n;
+        }
+    }
+}
+
+fn random(interp: &mut Interp, function: &str, mut args: Args) -> PyResult<Value> {
+    match function {
+        "random" => {
+            exact::<0>("random", args)?;
+            Ok(Value::Float(next_f64()))
+        }
+        "seed" => {
+            let seed = match args.arg(0, "a").unwrap_or(Value::None) {
+                Value::None => SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos() as u64),
+                Value::Int(n) => n as u64,
+                Value::Bool(b) => b as u64,
+                Value::Float(x) => x.to_bits(),
+                Value::Str(s) => s
+                    .bytes()
+                    .fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01b3)),
+                other => interp.hash(&other)? as u64,
+            };
+            RANDOM_STATE.with(|state| state.set(seed));
+            Ok(Value::None)
+        }
+        "randint" => {
+            let [a, b] = exact::<2>("randint", args)?;
+            let (a, b) = (index_value(interp, &a)?, index_value(interp, &b)?);
+            if b < a {
+                return raise("ValueError", format!("empty range in randrange({a}, {})", b + 1));
+            }
+            let span = (b as i128 - a as i128 + 1) as u64;
+            Ok(Value::Int(a.wrapping_add(below(span) as i64)))
+        }
+        "randrange" => {
+            no_keywords("randrange", &args)?;
+            let numbers: Vec<i64> = args
+                .pos
+                .iter()
+                .map(|v| index_value(interp, v))
+                .collect::<PyResult<_>>()?;
+            let (start, stop, step) = match numbers[..] {
+                [stop] => (0, stop, 1),
+                [start, stop] => (start, stop, 1),
+                [start, stop, step] => (start, stop, step),
+                _ => return raise("TypeError", "randrange expected 1 to 3 arguments"),
+            };
+            if step == 0 {
+                return raise("ValueError", "zero step for randrange()");
+            }
+            let count = super::ops::range_len(start, stop, step);
+            if count <= 0 {
+                return raise(
+                    "ValueError",
+                    match numbers.len() {
+                        1 => "empty range for randrange()".to_string(),
+                        _ => format!("empty range in randrange({start}, {stop})"),
+                    },
+                );
+            }
+            Ok(Value::Int(start + step * below(count as u64) as i64))
+        }
+        "choice" => {
+            let [population] = exact::<1>("choice", args)?;
+            let items = interp.collect(&population)?;
+            if items.is_empty() {
+                return raise("IndexError", "Cannot choose from an empty sequence");
+            }
+            Ok(items[below(items.len() as u64) as usize].clone())
+        }
+        "choices" => {
+            let weights = args.take_kw("weights").unwrap_or(Value::None);
+            let cum_weights = args.take_kw("cum_weights").unwrap_or(Value::None);
+            let k = args.take_kw("k").unwrap_or(Value::Int(1));
+            let k = index_value(interp, &k)?.max(0) as usize;
+            let population = args.arg(0, "population").unwrap_or(Value::None);
+            let weights = match args.pos.get(1) {
+                Some(weights) => weights.clone(),
+                None => weights,
+            };
+            let items = interp.collect(&population)?;
+            if items.is_empty() {
+                return raise("IndexError", "Cannot choose from an empty sequence");
+            }
+            let mut cumulative = Vec::new();
+            if !cum_weights.is_none() {
+                for weight in interp.collect(&cum_weights)? {
+                    cumulative.push(real(&weight)?);
+                }
+            } else if !weights.is_none() {
+                let mut total = 0.0;
+                for weight in interp.collect(&weights)? {
+                    total += real(&weight)?;
+                    cumulative.push(total);
+                }
+            }
+            if !cumulative.is_empty() && cumulative.len() != items.len() {
+                return raise("ValueError", "The number of weights does not match the population");
+            }
+            let mut chosen = Vec::with_capacity(k);
+            for _ in 0..k {
+                let index = if cumulative.is_empty() {
+                    below(items.len() as u64) as usize
+                } else {
+                    let total = *cumulative.last().unwrap();
+                    if total <= 0.0 {
+                        return raise("ValueError", "Total of weights must be greater than zero");
+                    }
+                    let target = next_f64() * total;
+                    cumulative.partition_point(|&c| c <= target).min(items.len() - 1)
+                };
+                chosen.push(items[index].clone());
+            }
+            Ok(Value::list(chosen))
+        }
+        "shuffle" => {
+            let [list] = exact::<1>("shuffle", args)?;
+            let Value::List(list) = list else {
+                return raise("TypeError", "shuffle() argument must be a list");
+            };
+            let mut items = list.borrow_mut();
+            for i in (1..items.len()).rev() {
+                let j = below(i as u64 + 1) as usize;
+                items.swap(i, j);
+            }
+            Ok(Value::None)
+        }
+        "sample" => {
+            args.take_kw("counts");
+            let k = args.arg(1, "k").unwrap_or(Value::None);
+            let population = args.arg(0, "population").unwrap_or(Value::None);
+            let mut items = interp.collect(&population)?;
+            let k = index_value(interp, &k)?;
+            if k < 0 || k as usize > items.len() {
+                return raise("ValueError", "Sample larger than population or is negative");
+            }
+            for i in 0..k as usize {
+                let j = i + below((items.len() - i) as u64) as usize;
+                items.swap(i, j);
+            }
+            items.truncate(k as usize);
+            Ok(Value::list(items))
+        }
+        "uniform" => {
+            let [a, b] = exact::<2>("uniform", args)?;
+            let (a, b) = (real(&a)?, real(&b)?);
+            Ok(Value::Float(a + (b - a) * next_f64()))
+        }
+        "gauss" | "normalvariate" => {
+            let mu = args.arg(0, "mu").map_or(Ok(0.0), |v| real(&v))?;
+            let sigma = args.arg(1, "sigma").map_or(Ok(1.0), |v| real(&v))?;
+            let u1 = 1.0 - next_f64();
+            let u2 = next_f64();
+            let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+            Ok(Value::Float(mu + sigma * z))
+        }
+        "getrandbits" => {
+            let [k] = exact::<1>("getrandbits", args)?;
+            let k = index_value(interp, &k)?;
+            if !(0..=63).contains(&k) {
+                return raise(
+                    "ValueError",
+                    "number of bits must be between 0 and 63",
+                );
+            }
+            Ok(Value::Int((next_u64() >> (64 - k).min(63)) as i64 & ((1i64 << k) - 1)))
+        }
+        _ => raise(
+            "AttributeError",
+            format!("module 'random' has no attribute '{function}'"),
+        ),
+    }
+}
+
+// ---- copy ----
+
+fn shallow_copy(interp: &mut Interp, value: &Value) -> PyResult<Value> {
+    Ok(match value {
+        Value::List(list) => Value::list(list.borrow().clone()),
+        Value::Dict(dict) => Value::dict(dict.borrow().clone()),
+        Value::Set(set) if !set.borrow().frozen => Value::Set(shared(set.borrow().clone())),
+        Value::Instance(instance) => {
+            if let Some(result) = interp.dunder(value, "__copy__", Vec::new())? {
+                return Ok(result);
+            }
+            Value::Instance(Rc::new(Instance {
+                class: instance.class.clone(),
+                attrs: instance.attrs.clone(),
+            }))
+        }
+        other => other.clone(),
+    })
+}
+
+fn deep_copy(interp: &mut Interp, value: &Value, memo: &mut HashMap<usize, Value>) -> PyResult<Value> {
+    let id = value.identity();
+    if let Some(copied) = id.and_then(|id| memo.get(&id)) {
+        return Ok(copied.clone());
+    }
+    let copied = match value {
+        Value::List(list) => {
+            let copy = shared(Vec::new());
+            memo.insert(id.unwrap(), Value::List(copy.clone()));
+            let items = list.borrow().clone();
+            for item in items {
+                let item = deep_copy(interp, &item, memo)?;
+                copy.borrow_mut().push(item);
+            }
+            return Ok(Value::List(copy));
+        }
+        Value::Tuple(items) => {
+            let mut copies = Vec::with_capacity(items.len());
+            for item in items.iter() {
+                copies.push(deep_copy(interp, item, memo)?);
+            }
+            Value::tuple(copies)
+        }
+        Value::Dict(dict) => {
+            let copy = shared(Dict {
+                map: Default::default(),
+                kind: dict.borrow().kind.clone(),
+            });
+            memo.insert(id.unwrap(), Value::Dict(copy.clone()));
+            let entries: Vec<(Key, Value, Value)> = dict
+                .borrow()
+                .map
+                .iter()
+                .map(|(key, (key_value, value))| (key.clone(), key_value.clone(), value.clone()))
+                .collect();
+            for (key, key_value, value) in entries {
+                let value = deep_copy(interp, &value, memo)?;
+                copy.borrow_mut().insert(key, key_value, value);
+            }
+            return Ok(Value::Dict(copy));
+        }
+        Value::Set(set) => Value::Set(shared(set.borrow().clone())),
+        Value::Instance(instance) => {
+            if let Some(result) = interp.dunder(value, "__deepcopy__", vec![Value::dict(Dict::default())])? {
+                return Ok(result);
+            }
+            let copy = Rc::new(Instance {
+                class: instance.class.clone(),
+                attrs: Default::default(),
+            });
+            memo.insert(id.unwrap(), Value::Instance(copy.clone()));
+            let attrs: Vec<(Rc<str>, Value)> = instance
+                .attrs
+                .borrow()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            for (key, value) in attrs {
+                let value = deep_copy(interp, &value, memo)?;
+                copy.attrs.borrow_mut().insert(key, value);
+            }
+            return Ok(Value::Instance(copy));
+        }
+        other => other.clone(),
+    };
+    if let Some(id) = id {
+        memo.insert(id, copied.clone());
+    }
+    Ok(copied)
+}
+
+// ---- csv ----
+
+#[derive(PartialEq)]
+enum CsvState {
+    StartField,
+    InField,
+    InQuoted,
+    QuoteInQuoted,
+}
+
+/// `_csv.read_record(lines, delimiter, quotechar, doublequote,
+/// skipinitialspace, escapechar, quoting)`: the next record's fields,
+/// reading more lines while a quoted field continues; `None` at the end.
+fn read_record(interp: &mut Interp, args: Args) -> PyResult<Value> {
+    let [lines, delimiter, quotechar, doublequote, skip_space, escapechar, quoting] =
+        exact::<7>("read_record", args)?;
+    let single = |value: &Value| -> Option<char> {
+        value.as_str().and_then(|s| s.chars().next())
+    };
+    let Some(delimiter) = single(&delimiter) else {
+        return raise("TypeError", "\"delimiter\" must be a 1-character string");
+    };
+    let quoting = index_value(interp, &quoting)?;
+    let quotechar = if quoting == 3 { None } else { single(&quotechar) };
+    let escapechar = single(&escapechar);
+    let doublequote = interp.truthy(&doublequote)?;
+    let skip_space = interp.truthy(&skip_space)?;
+    let nonnumeric = quoting == 2;
+
+    let mut fields: Vec<Value> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut state = CsvState::StartField;
+    let mut started = false;
+    let finish = |field: &mut String, quoted: &mut bool, fields: &mut Vec<Value>| -> PyResult<()> {
+        let text = std::mem::take(field);
+        let value = if nonnumeric && !*quoted && !text.is_empty() {
+            match text.trim().parse::<f64>() {
+                Ok(x) => Value::Float(x),
+                Err(_) => return raise("ValueError", format!("could not convert string to float: '{text}'")),
+            }
+        } else {
+            Value::str(&text)
+        };
+        fields.push(value);
+        *quoted = false;
+        Ok(())
+    };
+    loop {
+        let Some(line) = interp.next(&lines)? else {
+            if !started {
+                return Ok(Value::None);
+            }
+            // The data ended inside a quoted field
+            finish(&mut field, &mut quoted, &mut fields)?;
+            return Ok(Value::list(fields));
+        };
+        let Value::Str(line) = line else {
+            return raise(
+                "TypeError",
+                format!(
+                    "iterator should return strings, not {}",
+                    line.type_name()
+                ),
+            );
+        };
+        started = true;
+        let mut escaped = false;
+        for c in line.chars() {
+            if escaped {
+                field.push(c);
+                escaped = false;
+                continue;
+            }
+            if Some(c) == escapechar && state != CsvState::QuoteInQuoted {
+                escaped = true;
+                if state == CsvState::StartField {
+                    state = CsvState::InField;
+                }
+                continue;
+            }
+            let line_end = c == '\n' || c == '\r';
+            match state {
+                CsvState::StartField | CsvState::InField => {
+                    if state == CsvState::StartField && Some(c) == quotechar {
+                        state = CsvState::InQuoted;
+                        quoted = true;
+                    } else if c == delimiter {
+                        finish(&mut field, &mut quoted, &mut fields)?;
+                        state = CsvState::StartField;
+                    } else if line_end {
+                        break;
+                    } else if state == CsvState::StartField && skip_space && c == ' ' {
+                    } else {
+                        field.push(c);
+                        state = CsvState::InField;
+                    }
+                }
+                CsvState::InQuoted => {
+                    if Some(c) == quotechar {
+                        state = if doublequote {
+                            CsvState::QuoteInQuoted
+                        } else {
+                            CsvState::InField
+                        };
+                    } else {
+                        field.push(c);
+                    }
+                }
+                CsvState::QuoteInQuoted => {
+                    if Some(c) == quotechar {
+                        field.push(c);
+                        state = CsvState::InQuoted;
+                    } else if c == delimiter {
+                        finish(&mut field, &mut quoted, &mut fields)?;
+                        state = CsvState::StartField;
+                    } else if line_end {
+                        state = CsvState::InField;
+                        break;
+                    } else {
+                        field.push(c);
+                        state = CsvState::InField;
+                    }
+                }
+            }
+        }
+        if state == CsvState::InQuoted {
+            // A newline inside quotes is part of the field
+            if !line.ends_with('\n') && !line.ends_with('\r') {
+                field.push('\n');
+            }
+            continue;
+        }
+        if state == CsvState::StartField && fields.is_empty() && field.is_empty() && !quoted {
+            return Ok(Value::list(Vec::new()));
+        }
+        finish(&mut field, &mut quoted, &mut fields)?;
+        return Ok(Value::list(fields));
+    }
+}
@@ -0,0 +1,1699 @@
+//! Operators and object protocols: truth, equality, ordering, hashing,
+//! arithmetic, `repr`/`str`, item access and iteration.
+
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use super::ast::{BinOp, CmpOp, UnaryOp};
+use super::error::{PyErr, PyResult, int_too_large, raise};
+use super::format::{float_repr, percent_format, str_repr};
+use super::interp::Interp;
+use super::methods;
+use super::value::*;
+
+/// Largest string or list a repetition may build.
+const MAX_REPEAT: usize = 1 << 28;
+
+impl Interp {
+    /// Call dunder method `name` if the value is an instance defining it, or a class
+    /// whose metaclass does.
+    pub fn dunder(&mut self, object: &Value, name: &str, args: Vec<Value>) -> PyResult<Option<Value>> {
+        let class = match object {
+            Value::Instance(instance) => instance.class.clone(),
+            Value::Class(class) => match class.lookup("__metaclass__") {
+                Some(Value::Class(metaclass)) => metaclass,
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        let Some(method) = class.lookup(name) else {
+            return Ok(None);
+        };
+        let method = self.bind(method, object);
+        self.call(&method, Args::new(args)).map(Some)
+    }
+
+    // ---- Truth and equality ----
+
+    pub fn truthy(&mut self, value: &Value) -> PyResult<bool> {
+        Ok(match value {
+            Value::None => false,
+            Value::Bool(b) => *b,
+            Value::Int(n) => *n != 0,
+            Value::Float(x) => *x != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::List(l) => !l.borrow().is_empty(),
+            Value::Tuple(t) => !t.is_empty(),
+            Value::Dict(d) => !d.borrow().map.is_empty(),
+            Value::Set(s) => !s.borrow().map.is_empty(),
+            Value::Range(..) => self.len(value)? > 0,
+            Value::Instance(_) => {
+                if let Some(result) = self.dunder(value, "__bool__", Vec::new())? {
+                    return match result {
+                        Value::Bool(b) => Ok(b),
+                        other => raise(
+                            "TypeError",
+                            format!("__bool__ should return bool, returned {}", other.type_name()),
+                        ),
+                    };
+                }
+                match self.dunder(value, "__len__", Vec::new())? {
+                    Some(Value::Int(n)) => n != 0,
+                    Some(Value::Bool(b)) => b,
+                    _ => true,
+                }
+            }
+            _ => true,
+        })
+    }
+
+    pub fn eq(&mut self, a: &Value, b: &Value) -> PyResult<bool> {
+        Ok(match (a, b) {
+            (Value::Instance(_), _) | (_, Value::Instance(_)) => {
+                if let Some(result) = self.dunder(a, "__eq__", vec![b.clone()])?
+                    && !matches!(result, Value::NotImplemented)
+                {
+                    return self.truthy(&result);
+                }
+                if let Some(result) = self.dunder(b, "__eq__", vec![a.clone()])?
+                    && !matches!(result, Value::NotImplemented)
+                {
+                    return self.truthy(&result);
+                }
+                a.is(b)
+            }
+            (Value::Str(x), Value::Str(y)) => x == y,
+            (Value::List(x), Value::List(y)) => {
+                if Rc::ptr_eq(x, y) {
+                    return Ok(true);
+                }
+                let x = x.borrow().clone();
+                let y = y.borrow().clone();
+                self.seq_eq(&x, &y)?
+            }
+            (Value::Tuple(x), Value::Tuple(y)) => self.seq_eq(x, y)?,
+            (Value::Dict(x), Value::Dict(y)) => {
+                if Rc::ptr_eq(x, y) {
+                    return Ok(true);
+                }
+                let x = x.borrow().clone();
+                let y = y.borrow().clone();
+                if x.map.len() != y.map.len() {
+                    return Ok(false);
+                }
+                for (key, (_, value)) in &x.map {
+                    match y.map.get(key) {
+                        Some((_, other)) => {
+                            if !self.eq(value, other)? {
+                                return Ok(false);
+                            }
+                        }
+                        None => return Ok(false),
+                    }
+                }
+                true
+            }
+            (Value::Set(x), Value::Set(y)) => {
+                let x = x.borrow();
+                let y = y.borrow();
+                x.map.len() == y.map.len() && x.map.keys().all(|k| y.map.contains_key(k))
+            }
+            (Value::Range(..), Value::Range(..)) => {
+                let x = self.collect(a)?;
+                let y = self.collect(b)?;
+                self.seq_eq(&x, &y)?
+            }
+            (Value::Slice(x), Value::Slice(y)) => self.seq_eq(&x[..], &y[..])?,
+            _ => match (number(a), number(b)) {
+                (Some(x), Some(y)) => x.eq(&y),
+                _ => a.is(b),
+            },
+        })
+    }
+
+    fn seq_eq(&mut self, a: &[Value], b: &[Value]) -> PyResult<bool> {
+        if a.len() != b.len() {
+            return Ok(false);
+        }
+        for (x, y) in a.iter().zip(b) {
+            if !x.is(y) && !self.eq(x, y)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn compare(&mut self, op: CmpOp, a: &Value, b: &Value) -> PyResult<bool> {
+        match op {
+            CmpOp::Eq => self.eq(a, b),
+            CmpOp::NotEq => {
+                if let Some(result) = self.dunder(a, "__ne__", vec![b.clone()])?
+                    && !matches!(result, Value::NotImplemented)
+                {
+                    return self.truthy(&result);
+                }
+                Ok(!self.eq(a, b)?)
+            }
+            CmpOp::Lt | CmpOp::LtE | CmpOp::Gt | CmpOp::GtE => self.order(op, a, b),
+            CmpOp::In => self.contains(b, a),
+            CmpOp::NotIn => Ok(!self.contains(b, a)?),
+            CmpOp::Is => Ok(a.is(b)),
+            CmpOp::IsNot => Ok(!a.is(b)),
+        }
+    }
+
+    /// `a < b` and friends.
+    pub fn order(&mut self, op: CmpOp, a: &Value, b: &Value) -> PyResult<bool> {
+        let (name, reflected, symbol) = match op {
+            CmpOp::Lt => ("__lt__", "__gt__", "<"),
+            CmpOp::LtE => ("__le__", "__ge__", "<="),
+            CmpOp::Gt => ("__gt__", "__lt__", ">"),
+            _ => ("__ge__", "__le__", ">="),
+        };
+        if matches!(a, Value::Instance(_)) || matches!(b, Value::Instance(_)) {
+            if let Some(result) = self.dunder(a, name, vec![b.clone()])?
+                && !matches!(result, Value::NotImplemented)
+            {
+                return self.truthy(&result);
+            }
+            if let Some(result) = self.dunder(b, reflected, vec![a.clone()])?
+                && !matches!(result, Value::NotImplemented)
+            {
+                return self.truthy(&result);
+            }
+        }
+        if let (Value::Set(x), Value::Set(y)) = (a, b) {
+            let x = x.borrow();
+            let y = y.borrow();
+            let subset = x.map.keys().all(|k| y.map.contains_key(k));
+            let superset = y.map.keys().all(|k| x.map.contains_key(k));
+            return Ok(match op {
+                CmpOp::Lt => subset && x.map.len() < y.map.len(),
+                CmpOp::LtE => subset,
+                CmpOp::Gt => superset && x.map.len() > y.map.len(),
+                _ => superset,
+            });
+        }
+        let ordering = match self.ordering(a, b)? {
+            Some(ordering) => ordering,
+            None => {
+                return raise(
+                    "TypeError",
+                    format!(
+                        "'{symbol}' not supported between instances of '{}' and '{}'",
+                        a.type_name(),
+                        b.type_name()
+                    ),
+                );
+            }
+        };
+        Ok(match op {
+            CmpOp::Lt => ordering == Ordering::Less,
+            CmpOp::LtE => ordering != Ordering::Greater,
+            CmpOp::Gt => ordering == Ordering::Greater,
+            _ => ordering != Ordering::Less,
+        })
+    }
+
+    /// The ordering of two builtin values, `None` when they don't compare.
+    /// NaN compares as neither less nor greater.
+    fn ordering(&mut self, a: &Value, b: &Value) -> PyResult<Option<Ordering>> {
+        Ok(match (a, b) {
+            (Value::Str(x), Value::Str(y)) => Some(x.cmp(y)),
+            (Value::List(x), Value::List(y)) => {
+                let x = x.borrow().clone();
+                let y = y.borrow().clone();
+                self.seq_ordering(&x, &y)?
+            }
+            (Value::Tuple(x), Value::Tuple(y)) => self.seq_ordering(x, y)?,
+            _ => match (number(a), number(b)) {
+                (Some(x), Some(y)) => Some(x.partial_cmp(&y).unwrap_or(Ordering::Equal)),
+                _ => None,
+            },
+        })
+    }
+
+    fn seq_ordering(&mut self, a: &[Value], b: &[Value]) -> PyResult<Option<Ordering>> {
+        for (x, y) in a.iter().zip(b) {
+            if x.is(y) || self.eq(x, y)? {
+                continue;
+            }
+            return Ok(Some(if self.order(CmpOp::Lt, x, y)? {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }));
+        }
+        Ok(Some(a.len().cmp(&b.len())))
+    }
+
+    pub fn lt(&mut self, a: &Value, b: &Value) -> PyResult<bool> {
+        self.order(CmpOp::Lt, a, b)
+    }
+
+    /// Stable sort by `<`, optionally by a key function.
+    pub fn sort(&mut self, items: &mut Vec<Value>, key: &Value, reverse: bool) -> PyResult<()> {
+        let keys = if key.is_none() {
+            items.clone()
+        } else {
+            let mut keys = Vec::with_capacity(items.len());
+            for item in items.iter() {
+                keys.push(self.call(key, Args::new(vec![item.clone()]))?);
+            }
+            keys
+        };
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        let mut error = None;
+        // Reversal keeps equal items in their original order, as Python does
+        order.sort_by(|&i, &j| {
+            if error.is_some() {
+                return Ordering::Equal;
+            }
+            let (x, y) = if reverse { (&keys[j], &keys[i]) } else { (&keys[i], &keys[j]) };
+            match self.lt(x, y) {
+                Ok(true) => Ordering::Less,
+                Ok(false) => match self.lt(y, x) {
+                    Ok(true) => Ordering::Greater,
+                    Ok(false) => Ordering::Equal,
+                    Err(e) => {
+                        error = Some(e);
+                        Ordering::Equal
+                    }
+                },
+                Err(e) => {
+                    error = Some(e);
+                    Ordering::Equal
+                }
+            }
+        });
+        if let Some(e) = error {
+            return Err(e);
+        }
+        *items = order.into_iter().map(|i| items[i].clone()).collect();
+        Ok(())
+    }
+
+    // ---- Hashing ----
+
+    /// The dict/set key of a value; unhashable values raise `TypeError`.
+    pub fn key(&mut self, value: &Value) -> PyResult<Key> {
+        Ok(match value {
+            Value::None => Key::None,
+            Value::Bool(b) => Key::Int(*b as i64),
+            Value::Int(n) => Key::Int(*n),
+            Value::Float(x) => {
+                if x.fract() == 0.0 && x.abs() < 9.2e18 {
+                    Key::Int(*x as i64)
+                } else {
+                    Key::Float(x.to_bits())
+                }
+            }
+            Value::Str(s) => Key::Str(s.clone()),
+            Value::Tuple(items) => {
+                let mut keys = Vec::with_capacity(items.len());
+                for item in items.iter() {
+                    keys.push(self.key(item)?);
+                }
+                Key::Tuple(Rc::from(keys))
+            }
+            Value::Type(t) => Key::Type(*t),
+            Value::Ellipsis => Key::Ellipsis,
+            Value::Set(set) if set.borrow().frozen => {
+                // Order-independent: the members' keys sorted by hash
+                let mut keys: Vec<Key> = set.borrow().map.keys().cloned().collect();
+                keys.sort_by_key(hash_key);
+                Key::Tuple(Rc::from(keys))
+            }
+            Value::List(_) | Value::Dict(_) | Value::Set(_) | Value::Slice(_) => {
+                return raise(
+                    "TypeError",
+                    format!("unhashable type: '{}'", value.type_name()),
+                );
+            }
+            Value::Instance(instance) => match instance.class.lookup("__hash__") {
+                Some(Value::None) => {
+                    return raise(
+                        "TypeError",
+                        format!("unhashable type: '{}'", instance.class.name),
+                    );
+                }
+                Some(_) => {
+                    let hash = self.dunder(value, "__hash__", Vec::new())?.unwrap();
+                    let Value::Int(hash) = hash else {
+                        return raise("TypeError", "__hash__ method should return an integer");
+                    };
+                    let class = Rc::as_ptr(&instance.class) as usize;
+                    Key::Tuple(Rc::from([Key::Id(class), Key::Int(hash)]))
+                }
+                None if instance.class.lookup("__eq__").is_some() => {
+                    return raise(
+                        "TypeError",
+                        format!("unhashable type: '{}'", instance.class.name),
+                    );
+                }
+                None => Key::Id(value.identity().unwrap()),
+            },
+            other => Key::Id(other.identity().unwrap_or(0)),
+        })
+    }
+
+    /// `hash(value)`.
+    pub fn hash(&mut self, value: &Value) -> PyResult<i64> {
+        if let Value::Instance(instance) = value
+            && !matches!(instance.class.lookup("__hash__"), None | Some(Value::None))
+            && let Some(Value::Int(hash)) = self.dunder(value, "__hash__", Vec::new())?
+        {
+            return Ok(hash);
+        }
+        Ok(match self.key(value)? {
+            Key::Int(n) => n,
+            key => hash_key(&key) as i64,
+        })
+    }
+
+    // ---- Arithmetic ----
+
+    pub fn binop(&mut self, op: BinOp, left: Value, right: Value) -> PyResult<Value> {
+        if matches!(left, Value::Instance(_)) || matches!(right, Value::Instance(_)) {
+            let (name, reflected) = op.dunder();
+            if let Some(result) = self.dunder(&left, name, vec![right.clone()])?
+                && !matches!(result, Value::NotImplemented)
+            {
+                return Ok(result);
+            }
+            if let Some(result) = self.dunder(&right, reflected, vec![left.clone()])?
+                && !matches!(result, Value::NotImplemented)
+            {
+                return Ok(result);
+            }
+            return self.unsupported(op, &left, &right);
+        }
+        if let Some(result) = arithmetic(op, &left, &right)? {
+            return Ok(result);
+        }
+        match (op, &left, &right) {
+            (BinOp::Add, Value::Str(a), Value::Str(b)) => {
+                let mut s = String::with_capacity(a.len() + b.len());
+                s.push_str(a);
+                s.push_str(b);
+                Ok(Value::str(&s))
+            }
+            (BinOp::Add, Value::List(a), Value::List(b)) => {
+                let mut items = a.borrow().clone();
+                items.extend(b.borrow().iter().cloned());
+                Ok(Value::list(items))
+            }
+            (BinOp::Add, Value::Tuple(a), Value::Tuple(b)) => {
+                let mut items = a.to_vec();
+                items.extend(b.iter().cloned());
+                Ok(Value::tuple(items))
+            }
+            (BinOp::Mul, Value::Str(_) | Value::List(_) | Value::Tuple(_), Value::Int(_) | Value::Bool(_)) => {
+                repeat(&left, int_of(&right))
+            }
+            (BinOp::Mul, Value::Int(_) | Value::Bool(_), Value::Str(_) | Value::List(_) | Value::Tuple(_)) => {
+                repeat(&right, int_of(&left))
+            }
+            (BinOp::Mod, Value::Str(format), _) => {
+                Ok(Value::str(&percent_format(self, format, &right)?))
+            }
+            (BinOp::BitOr, Value::Dict(a), Value::Dict(b)) => {
+                let mut dict = a.borrow().clone();
+                for (key, (key_value, value)) in b.borrow().map.iter() {
+                    dict.insert(key.clone(), key_value.clone(), value.clone());
+                }
+                Ok(Value::dict(dict))
+            }
+            (BinOp::Add | BinOp::Sub, Value::Dict(a), Value::Dict(b))
+                if matches!(a.borrow().kind, DictKind::Counter) =>
+            {
+                methods::counter_arith(self, op, a, b)
+            }
+            (BinOp::BitOr | BinOp::BitAnd | BinOp::Sub | BinOp::BitXor, Value::Set(a), Value::Set(b)) => {
+                let set = set_op(op, &a.borrow(), &b.borrow());
+                let set = Set {
+                    map: set.map,
+                    frozen: a.borrow().frozen,
+                };
+                Ok(Value::Set(shared(set)))
+            }
+            _ => self.unsupported(op, &left, &right),
+        }
+    }
+
+    fn unsupported(&mut self, op: BinOp, left: &Value, right: &Value) -> PyResult<Value> {
+        match (op, left) {
+            (BinOp::Add, Value::Str(_)) => raise(
+                "TypeError",
+                format!(
+                    "can only concatenate str (not \"{}\") to str",
+                    right.type_name()
+                ),
+            ),
+            (BinOp::Add, Value::List(_) | Value::Tuple(_)) => raise(
+                "TypeError",
+                format!(
+                    "can only concatenate {} (not \"{}\") to {}",
+                    left.type_name(),
+                    right.type_name(),
+                    left.type_name()
+                ),
+            ),
+            (BinOp::Mul, Value::Str(_) | Value::List(_) | Value::Tuple(_)) => raise(
+                "TypeError",
+                format!(
+                    "can't multiply sequence by non-int of type '{}'",
+                    right.type_name()
+                ),
+            ),
+            _ => raise(
+                "TypeError",
+                format!(
+                    "unsupported operand type(s) for {}: '{}' and '{}'",
+                    op.symbol(),
+                    left.type_name(),
+                    right.type_name()
+                ),
+            ),
+        }
+    }
+
+    /// `left op= right`: mutable containers change in place.
+    pub fn inplace_binop(&mut self, op: BinOp, left: Value, right: Value) -> PyResult<Value> {
+        match (op, &left) {
+            (_, Value::Instance(_)) => {
+                let name = format!("__i{}", &op.dunder().0[2..]);
+                if let Some(result) = self.dunder(&left, &name, vec![right.clone()])?
+                    && !matches!(result, Value::NotImplemented)
+                {
+                    return Ok(result);
+                }
+                self.binop(op, left, right)
+            }
+            (BinOp::Add, Value::List(list)) => {
+                let items = self.collect(&right)?;
+                list.borrow_mut().extend(items);
+                Ok(left)
+            }
+            (BinOp::Mul, Value::List(list)) => {
+                let (Value::Int(_) | Value::Bool(_)) = right else {
+                    return self.unsupported(op, &left, &right);
+                };
+                let repeated = repeat(&left, int_of(&right))?;
+                let items = repeated.list_items();
+                *list.borrow_mut() = items;
+                Ok(left)
+            }
+            (BinOp::BitOr, Value::Dict(dict)) => {
+                for (key, value) in self.mapping_items(&right)? {
+                    let k = self.key(&key)?;
+                    dict.borrow_mut().insert(k, key, value);
+                }
+                Ok(left)
+            }
+            (BinOp::BitOr | BinOp::BitAnd | BinOp::Sub | BinOp::BitXor, Value::Set(set))
+                if !set.borrow().frozen =>
+            {
+                let Value::Set(other) = &right else {
+                    return self.unsupported(op, &left, &right);
+                };
+                let other = other.borrow().clone();
+                let result = set_op(op, &set.borrow(), &other);
+                set.borrow_mut().map = result.map;
+                Ok(left)
+            }
+            _ => self.binop(op, left, right),
+        }
+    }
+
+    pub fn unary(&mut self, op: UnaryOp, operand: Value) -> PyResult<Value> {
+        if op == UnaryOp::Not {
+            return Ok(Value::Bool(!self.truthy(&operand)?));
+        }
+        let (name, symbol) = match op {
+            UnaryOp::Neg => ("__neg__", "-"),
+            UnaryOp::Pos => ("__pos__", "+"),
+            _ => ("__invert__", "~"),
+        };
+        if let Some(result) = self.dunder(&operand, name, Vec::new())? {
+            return Ok(result);
+        }
+        match (op, &operand) {
+            (UnaryOp::Neg, Value::Int(_) | Value::Bool(_)) => match int_of(&operand).checked_neg() {
+                Some(n) => Ok(Value::Int(n)),
+                None => int_too_large(),
+            },
+            (UnaryOp::Neg, Value::Float(x)) => Ok(Value::Float(-x)),
+            (UnaryOp::Pos, Value::Int(_) | Value::Bool(_)) => Ok(Value::Int(int_of(&operand))),
+            (UnaryOp::Pos, Value::Float(_)) => Ok(operand),
+            (UnaryOp::Invert, Value::Int(_) | Value::Bool(_)) => Ok(Value::Int(!int_of(&operand))),
+            (UnaryOp::Pos, Value::Dict(d)) if matches!(d.borrow().kind, DictKind::Counter) => {
+                let empty = shared(Dict {
+                    map: Default::default(),
+                    kind: DictKind::Counter,
+                });
+                methods::counter_arith(self, BinOp::Add, d, &empty)
+            }
+            _ => raise(
+                "TypeError",
+                format!(
+                    "bad operand type for unary {symbol}: '{}'",
+                    operand.type_name()
+                ),
+            ),
+        }
+    }
+
+    // ---- Containers ----
+
+    /// `item in container`.
+    pub fn contains(&mut self, container: &Value, item: &Value) -> PyResult<bool> {
+        match container {
+            Value::Str(s) => match item {
+                Value::Str(needle) => Ok(s.contains(&**needle)),
+                other => raise(
+                    "TypeError",
+                    format!(
+                        "'in <string>' requires string as left operand, not {}",
+                        other.type_name()
+                    ),
+                ),
+            },
+            Value::List(list) => {
+                let items = list.borrow().clone();
+                self.any_eq(&items, item)
+            }
+            Value::Tuple(items) => self.any_eq(items, item),
+            Value::Dict(dict) => {
+                let key = self.key(item)?;
+                Ok(dict.borrow().map.contains_key(&key))
+            }
+            Value::Set(set) => {
+                let key = match item {
+                    // A set can be looked up in a set of frozensets
+                    Value::Set(inner) => {
+                        let frozen = Value::Set(shared(Set {
+                            map: inner.borrow().map.clone(),
+                            frozen: true,
+                        }));
+                        self.key(&frozen)?
+                    }
+                    other => self.key(other)?,
+                };
+                Ok(set.borrow().map.contains_key(&key))
+            }
+            Value::Range(start, stop, step) => {
+                let n = match item {
+                    Value::Int(n) => *n,
+                    Value::Bool(b) => *b as i64,
+                    Value::Float(x) if x.fract() == 0.0 => *x as i64,
+                    _ => return Ok(false),
+                };
+                let within = if *step > 0 {
+                    *start <= n && n < *stop
+                } else {
+                    *stop < n && n <= *start
+                };
+                Ok(within && (n - start) % step == 0)
+            }
+            Value::Instance(_) | Value::Class(_) => {
+                if let Some(result) = self.dunder(container, "__contains__", vec![item.clone()])? {
+                    return self.truthy(&result);
+                }
+                let iterator = self.iter(container)?;
+                while let Some(value) = self.next(&iterator)? {
+                    if value.is(item) || self.eq(&value, item)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Value::Iterator(_) => {
+                while let Some(value) = self.next(container)? {
+                    if value.is(item) || self.eq(&value, item)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            other => raise(
+                "TypeError",
+                format!(
+                    "argument of type '{}' is not iterable",
+                    other.type_name()
+                ),
+            ),
+        }
+    }
+
+    fn any_eq(&mut self, items: &[Value], item: &Value) -> PyResult<bool> {
+        for value in items {
+            if value.is(item) || self.eq(value, item)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn len(&mut self, value: &Value) -> PyResult<usize> {
+        Ok(match value {
+            Value::Str(s) => s.chars().count(),
+            Value::List(l) => l.borrow().len(),
+            Value::Tuple(t) => t.len(),
+            Value::Dict(d) => d.borrow().map.len(),
+            Value::Set(s) => s.borrow().map.len(),
+            Value::Range(start, stop, step) => range_len(*start, *stop, *step) as usize,
+            Value::Instance(_) | Value::Class(_) => match self.dunder(value, "__len__", Vec::new())? {
+                Some(Value::Int(n)) if n >= 0 => n as usize,
+                Some(Value::Int(_)) => return raise("ValueError", "__len__() should return >= 0"),
+                Some(other) => {
+                    return raise(
+                        "TypeError",
+                        format!(
+                            "'{}' object cannot be interpreted as an integer",
+                            other.type_name()
+                        ),
+                    );
+                }
+                None => {
+                    return raise(
+                        "TypeError",
+                        format!("object of type '{}' has no len()", value.type_name()),
+                    );
+                }
+            },
+            other => {
+                return raise(
+                    "TypeError",
+                    format!("object of type '{}' has no len()", other.type_name()),
+                );
+            }
+        })
+    }
+
+    // ---- repr and str ----
+
+    pub fn repr(&mut self, value: &Value) -> PyResult<String> {
+        Ok(match value {
+            Value::None => "None".to_string(),
+            Value::Bool(true) => "True".to_string(),
+            Value::Bool(false) => "False".to_string(),
+            Value::Int(n) => n.to_string(),
+            Value::Float(x) => float_repr(*x),
+            Value::Str(s) => str_repr(s),
+            Value::List(list) => {
+                let items = list.borrow().clone();
+                match self.guarded(value, |interp| interp.join_reprs(&items))? {
+                    Some(body) => format!("[{body}]"),
+                    None => "[...]".to_string(),
+                }
+            }
+            Value::Tuple(items) => {
+                let body = self.join_reprs(items)?;
+                if items.len() == 1 {
+                    format!("({body},)")
+                } else {
+                    format!("({body})")
+                }
+            }
+            Value::Dict(dict) => {
+                let dict = dict.borrow().clone();
+                let body = self.guarded(value, |interp| {
+                    let mut parts = Vec::with_capacity(dict.map.len());
+                    for (key, value) in dict.map.values() {
+                        parts.push(format!("{}: {}", interp.repr(key)?, interp.repr(value)?));
+                    }
+                    Ok(parts.join(", "))
+                })?;
+                let Some(body) = body else {
+                    return Ok("{...}".to_string());
+                };
+                match &dict.kind {
+                    DictKind::Dict => format!("{{{body}}}"),
+                    DictKind::Counter if body.is_empty() => "Counter()".to_string(),
+                    DictKind::Counter => format!("Counter({{{body}}})"),
+                    DictKind::Ordered if body.is_empty() => "OrderedDict()".to_string(),
+                    DictKind::Ordered => format!("OrderedDict({{{body}}})"),
+                    DictKind::Default(factory) => {
+                        let factory = self.repr(factory)?;
+                        format!("defaultdict({factory}, {{{body}}})")
+                    }
+                }
+            }
+            Value::Set(set) => {
+                let set = set.borrow();
+                let items = set.items();
+                let frozen = set.frozen;
+                drop(set);
+                let body = self.join_reprs(&items)?;
+                match (frozen, items.is_empty()) {
+                    (false, true) => "set()".to_string(),
+                    (false, false) => format!("{{{body}}}"),
+                    (true, true) => "frozenset()".to_string(),
+                    (true, false) => format!("frozenset({{{body}}})"),
+                }
+            }
+            Value::Range(start, stop, step) => {
+                if *step == 1 {
+                    format!("range({start}, {stop})")
+                } else {
+                    format!("range({start}, {stop}, {step})")
+                }
+            }
+            Value::Slice(parts) => {
+                let body = self.join_reprs(&parts[..])?;
+                format!("slice({body})")
+            }
+            Value::Function(function) => {
+                format!("<function {} at {}>", function.qualname(), address(value))
+            }
+            Value::Builtin(name) => format!("<built-in function {}>", short_name(name)),
+            Value::Method(method) => {
+                let owner = match &method.0 {
+                    Value::Class(class) => class.name.to_string(),
+                    other => other.type_name().to_string(),
+                };
+                let name = match &method.1 {
+                    Value::Function(function) => function.qualname(),
+                    Value::Builtin(name) => format!("{owner}.{}", short_name(name)),
+                    _ => format!("{owner}.?"),
+                };
+                let object = self.repr(&method.0)?;
+                format!("<bound method {name} of {object}>")
+            }
+            Value::BuiltinMethod(method) => match &method.0 {
+                Value::Type(kind) => format!("<method '{}' of '{}' objects>", method.1, kind.name()),
+                object => format!(
+                    "<built-in method {} of {} object at {}>",
+                    method.1,
+                    object.type_name(),
+                    address(object)
+                ),
+            },
+            Value::Type(kind) => match kind.module() {
+                Some(module) => format!("<class '{module}.{}'>", kind.name()),
+                None => format!("<class '{}'>", kind.name()),
+            },
+            Value::Class(class) => match &*class.module {
+                "builtins" => format!("<class '{}'>", class.name),
+                module => format!("<class '{module}.{}'>", class.name),
+            },
+            Value::Instance(instance) => {
+                if let Some(result) = self.dunder(value, "__repr__", Vec::new())? {
+                    return match result {
+                        Value::Str(s) => Ok(s.to_string()),
+                        other => raise(
+                            "TypeError",
+                            format!("__repr__ returned non-string (type {})", other.type_name()),
+                        ),
+                    };
+                }
+                if instance.class.has_ancestor("BaseException") {
+                    let args = exception_args(instance);
+                    let body = self.join_reprs(&args)?;
+                    return Ok(format!("{}({body})", instance.class.name));
+                }
+                let class = &instance.class;
+                let name = match &*class.module {
+                    "builtins" => class.name.to_string(),
+                    module => format!("{module}.{}", class.name),
+                };
+                format!("<{name} object at {}>", address(value))
+            }
+            Value::Module(module) => match module.get("__file__") {
+                Some(Value::Str(file)) if !file.starts_with('<') => {
+                    format!("<module '{}' from '{file}'>", module.name)
+                }
+                _ => format!("<module '{}' (built-in)>", module.name),
+            },
+            Value::Iterator(_) => format!("<iterator object at {}>", address(value)),
+            Value::File(file) => {
+                let file = file.borrow();
+                format!(
+                    "<_io.TextIOWrapper name={} mode={} encoding='utf-8'>",
+                    str_repr(&file.name),
+                    str_repr(&file.mode)
+                )
+            }
+            Value::Pattern(pattern) => format!("re.compile({})", str_repr(&pattern.source)),
+            Value::Match(m) => {
+                let (start, end) = m.span(0).unwrap_or((-1, -1));
+                let text = m.group_str(0).unwrap_or_default();
+                format!(
+                    "<re.Match object; span=({start}, {end}), match={}>",
+                    str_repr(&text)
+                )
+            }
+            Value::Property(_) => format!("<property object at {}>", address(value)),
+            Value::StaticMethod(_) => format!("<staticmethod object at {}>", address(value)),
+            Value::ClassMethod(_) => format!("<classmethod object at {}>", address(value)),
+            Value::Super(sup) => {
+                let object = self.repr(&sup.1)?;
+                format!("<super: <class '{}'>, {object}>", sup.0.name)
+            }
+            Value::Ellipsis => "Ellipsis".to_string(),
+            Value::NotImplemented => "NotImplemented".to_string(),
+        })
+    }
+
+    /// Run `build` unless the container is already being shown further up,
+    /// in which case return `None` so the caller prints `...`.
+    fn guarded(
+        &mut self,
+        container: &Value,
+        build: impl FnOnce(&mut Interp) -> PyResult<String>,
+    ) -> PyResult<Option<String>> {
+        let id = container.identity().unwrap_or(0);
+        if self.repr_active.contains(&id) {
+            return Ok(None);
+        }
+        self.repr_active.push(id);
+        let result = build(self);
+        self.repr_active.pop();
+        result.map(Some)
+    }
+
+    fn join_reprs(&mut self, items: &[Value]) -> PyResult<String> {
+        let mut parts = Vec::with_capacity(items.len());
+        for item in items {
+            parts.push(self.repr(item)?);
+        }
+        Ok(parts.join(", "))
+    }
+
+    /// `str(value)`.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_str(&mut self, value: &Value) -> PyResult<String> {
+        match value {
+            Value::Str(s) => Ok(s.to_string()),
+            Value::Instance(instance) => {
+                if let Some(result) = self.dunder(value, "__str__", Vec::new())? {
+                    return match result {
+                        Value::Str(s) => Ok(s.to_string()),
+                        other => raise(
+                            "TypeError",
+                            format!("__str__ returned non-string (type {})", other.type_name()),
+                        ),
+                    };
+                }
+                if instance.class.has_ancestor("BaseException") {
+                    return self.exception_str(instance);
+                }
+                self.repr(value)
+            }
+            other => self.repr(other),
+        }
+    }
+
+    /// `str()` of an exception without its own `__str__`.
+    pub fn exception_str(&mut self, instance: &Instance) -> PyResult<String> {
+        let attrs = instance.attrs.borrow();
+        if let (Some(Value::Int(errno)), Some(strerror)) = (attrs.get("errno"), attrs.get("strerror")) {
+            let (errno, strerror) = (*errno, strerror.clone());
+            let filename = attrs.get("filename").cloned().unwrap_or(Value::None);
+            drop(attrs);
+            let strerror = self.to_str(&strerror)?;
+            return Ok(match filename {
+                Value::None => format!("[Errno {errno}] {strerror}"),
+                filename => format!("[Errno {errno}] {strerror}: {}", self.repr(&filename)?),
+            });
+        }
+        drop(attrs);
+        let args = exception_args(instance);
+        match args.len() {
+            0 => Ok(String::new()),
+            1 if instance.class.has_ancestor("KeyError") => self.repr(&args[0]),
+            1 => self.to_str(&args[0]),
+            _ => self.repr(&Value::tuple(args)),
+        }
+    }
+
+    // ---- Item access ----
+
+    pub fn get_item(&mut self, object: &Value, index: &Value) -> PyResult<Value> {
+        match object {
+            Value::List(list) => {
+                if let Value::Slice(slice) = index {
+                    let items = list.borrow();
+                    let picked = self.slice_pick(&items, slice)?;
+                    return Ok(Value::list(picked));
+                }
+                let items = list.borrow();
+                let i = self.index(index, items.len(), "list")?;
+                Ok(items[i].clone())
+            }
+            Value::Tuple(items) => {
+                if let Value::Slice(slice) = index {
+                    return Ok(Value::tuple(self.slice_pick(items, slice)?));
+                }
+                let i = self.index(index, items.len(), "tuple")?;
+                Ok(items[i].clone())
+            }
+            Value::Str(s) => {
+                if let Value::Slice(slice) = index {
+                    let chars: Vec<char> = s.chars().collect();
+                    let picked = self.slice_pick(&chars, slice)?;
+                    return Ok(Value::str(&picked.into_iter().collect::<String>()));
+                }
+                if s.is_ascii() {
+                    let i = self.index(index, s.len(), "string")?;
+                    return Ok(Value::str(&s[i..i + 1]));
+                }
+                let chars: Vec<char> = s.chars().collect();
+                let i = self.index(index, chars.len(), "string")?;
+                Ok(Value::str(&chars[i].to_string()))
+            }
+            Value::Range(start, stop, step) => {
+                let len = range_len(*start, *stop, *step);
+                if let Value::Slice(slice) = index {
+                    let (lower, _, by, count) = slice_bounds(slice, len as usize)?;
+                    let new_start = start + lower * step;
+                    let new_step = step * by;
+                    let new_stop = new_start + count as i64 * new_step;
+                    return Ok(Value::Range(new_start, new_stop, new_step));
+                }
+                let i = self.index(index, len as usize, "range object")?;
+                Ok(Value::Int(start + i as i64 * step))
+            }
+            Value::Dict(dict) => {
+                let key = self.key(index)?;
+                if let Some(value) = dict.borrow().get(&key) {
+                    return Ok(value);
+                }
+                let kind = dict.borrow().kind.clone();
+                match kind {
+                    DictKind::Counter => Ok(Value::Int(0)),
+                    DictKind::Default(factory) if !factory.is_none() => {
+                        let value = self.call(&factory, Args::default())?;
+                        dict.borrow_mut().insert(key, index.clone(), value.clone());
+                        Ok(value)
+                    }
+                    _ => Err(PyErr::with_arg("KeyError", index.clone())),
+                }
+            }
+            Value::Instance(_) => match self.dunder(object, "__getitem__", vec![index.clone()])? {
+                Some(value) => Ok(value),
+                None => raise(
+                    "TypeError",
+                    format!("'{}' object is not subscriptable", object.type_name()),
+                ),
+            },
+            Value::Match(_) => methods::call(self, object, "group", Args::new(vec![index.clone()])),
+            // Generic aliases such as `list[int]` are accepted and ignored
+            Value::Type(_) => Ok(object.clone()),
+            Value::Class(class) => match class.lookup("__class_getitem__") {
+                Some(method) => {
+                    let method = match method {
+                        Value::ClassMethod(inner) => (*inner).clone(),
+                        other => other,
+                    };
+                    self.call(&method, Args::new(vec![object.clone(), index.clone()]))
+                }
+                None => Ok(object.clone()),
+            },
+            other => raise(
+                "TypeError",
+                format!("'{}' object is not subscriptable", other.type_name()),
+            ),
+        }
+    }
+
+    /// A sequence index, counting from the end when negative.
+    fn index(&mut self, index: &Value, len: usize, what: &str) -> PyResult<usize> {
+        let i = match index {
+            Value::Int(n) => *n,
+            Value::Bool(b) => *b as i64,
+            Value::Instance(_) => match self.dunder(index, "__index__", Vec::new())? {
+                Some(Value::Int(n)) => n,
+                _ => return self.bad_index(index, what),
+            },
+            _ => return self.bad_index(index, what),
+        };
+        let resolved = if i < 0 { i + len as i64 } else { i };
+        if resolved < 0 || resolved >= len as i64 {
+            return raise("IndexError", format!("{what} index out of range"));
+        }
+        Ok(resolved as usize)
+    }
+
+    fn bad_index<T>(&self, index: &Value, what: &str) -> PyResult<T> {
+        let what = match what {
+            "string" => "string",
+            "range object" => "range",
+            other => other,
+        };
+        raise(
+            "TypeError",
+            format!(
+                "{what} indices must be integers or slices, not {}",
+                index.type_name()
+            ),
+        )
+    }
+
+    fn slice_pick<T: Clone>(&mut self, items: &[T], slice: &[Value; 3]) -> PyResult<Vec<T>> {
+        let (start, _, step, count) = slice_bounds(slice, items.len())?;
+        Ok((0..count)
+            .map(|k| items[(start + k as i64 * step) as usize].clone())
+            .collect())
+    }
+
+    pub fn set_item(&mut self, object: &Value, index: Value, value: Value) -> PyResult<()> {
+        match object {
+            Value::List(list) => {
+                if let Value::Slice(slice) = &index {
+                    let values = self.collect(&value)?;
+                    let len = list.borrow().len();
+                    let (start, stop, step, count) = slice_bounds(slice, len)?;
+                    let mut items = list.borrow_mut();
+                    if step == 1 {
+                        let stop = stop.max(start) as usize;
+                        items.splice(start as usize..stop, values);
+                    } else {
+                        if values.len() != count {
+                            return raise(
+                                "ValueError",
+                                format!(
+                                    "attempt to assign sequence of size {} to extended slice of size {count}",
+                                    values.len()
+                                ),
+                            );
+                        }
+                        for (k, value) in values.into_iter().enumerate() {
+                            items[(start + k as i64 * step) as usize] = value;
+                        }
+                    }
+                    return Ok(());
+                }
+                let len = list.borrow().len();
+                let i = self.index(&index, len, "list")?;
+                list.borrow_mut()[i] = value;
+                Ok(())
+            }
+            Value::Dict(dict) => {
+                let key = self.key(&index)?;
+                dict.borrow_mut().insert(key, index, value);
+                Ok(())
+            }
+            Value::Instance(_) => match self.dunder(object, "__setitem__", vec![index, value])? {
+                Some(_) => Ok(()),
+                None => raise(
+                    "TypeError",
+                    format!(
+                        "'{}' object does not support item assignment",
+                        object.type_name()
+                    ),
+                ),
+            },
+            other => raise(
+                "TypeError",
+                format!(
+                    "'{}' object does not support item assignment",
+                    other.type_name()
+                ),
+            ),
+        }
+    }
+
+    pub fn del_item(&mut self, object: &Value, index: &Value) -> PyResult<()> {
+        match object {
+            Value::List(list) => {
+                if let Value::Slice(slice) = index {
+                    let len = list.borrow().len();
+                    let (start, _, step, count) = slice_bounds(slice, len)?;
+                    let mut doomed: Vec<usize> = (0..count)
+                        .map(|k| (start + k as i64 * step) as usize)
+                        .collect();
+                    doomed.sort_unstable();
+                    let mut items = list.borrow_mut();
+                    for i in doomed.into_iter().rev() {
+                        items.remove(i);
+                    }
+                    return Ok(());
+                }
+                let len = list.borrow().len();
+                let i = self.index(index, len, "list")?;
+                list.borrow_mut().remove(i);
+                Ok(())
+            }
+            Value::Dict(dict) => {
+                let key = self.key(index)?;
+                match dict.borrow_mut().map.shift_remove(&key) {
+                    Some(_) => Ok(()),
+                    None => Err(PyErr::with_arg("KeyError", index.clone())),
+                }
+            }
+            Value::Instance(_) => match self.dunder(object, "__delitem__", vec![index.clone()])? {
+                Some(_) => Ok(()),
+                None => raise(
+                    "TypeError",
+                    format!(
+                        "'{}' object doesn't support item deletion",
+                        object.type_name()
+                    ),
+                ),
+            },
+            other => raise(
+                "TypeError",
+                format!(
+                    "'{}' object doesn't support item deletion",
+                    other.type_name()
+                ),
+            ),
+        }
+    }
+
+    // ---- Iteration ----
+
+    /// `iter(value)`.
+    pub fn iter(&mut self, value: &Value) -> PyResult<Value> {
+        let iter = match value {
+            Value::List(list) => Iter::List(list.clone(), 0),
+            Value::Tuple(items) => Iter::Tuple(items.clone(), 0),
+            Value::Str(s) => Iter::Str(s.clone(), 0),
+            Value::Range(start, stop, step) => Iter::Range {
+                next: *start,
+                stop: *stop,
+                step: *step,
+            },
+            Value::Dict(dict) => Iter::Values(dict.borrow().keys().into_iter()),
+            Value::Set(set) => Iter::Values(set.borrow().items().into_iter()),
+            Value::Iterator(_) => return Ok(value.clone()),
+            Value::File(file) => Iter::Lines(file.clone()),
+            Value::Instance(_) | Value::Class(_) => {
+                if let Some(iterator) = self.dunder(value, "__iter__", Vec::new())? {
+                    return Ok(iterator);
+                }
+                if let Value::Instance(instance) = value
+                    && instance.class.lookup("__getitem__").is_some()
+                {
+                    // The old sequence protocol: index from 0 until IndexError
+                    let mut items = Vec::new();
+                    for i in 0.. {
+                        match self.get_item(value, &Value::Int(i)) {
+                            Ok(item) => items.push(item),
+                            Err(e) if self.is_error(&e, "IndexError") => break,
+                            Err(e) => return Err(e),
+                        }
+                    }
+                    return Ok(Value::iterator(Iter::Values(items.into_iter())));
+                }
+                return raise(
+                    "TypeError",
+                    format!("'{}' object is not iterable", value.type_name()),
+                );
+            }
+            other => {
+                return raise(
+                    "TypeError",
+                    format!("'{}' object is not iterable", other.type_name()),
+                );
+            }
+        };
+        Ok(Value::iterator(iter))
+    }
+
+    /// Advance an iterator from `iter()`; `None` when it is exhausted.
+    pub fn next(&mut self, iterator: &Value) -> PyResult<Option<Value>> {
+        let iter = match iterator {
+            Value::Iterator(iter) => iter.clone(),
+            Value::Instance(_) => {
+                return match self.dunder(iterator, "__next__", Vec::new()) {
+                    Ok(Some(value)) => Ok(Some(value)),
+                    Ok(None) => raise(
+                        "TypeError",
+                        format!("'{}' object is not an iterator", iterator.type_name()),
+                    ),
+                    Err(e) if self.is_error(&e, "StopIteration") => Ok(None),
+                    Err(e) => Err(e),
+                };
+            }
+            other => {
+                return raise(
+                    "TypeError",
+                    format!("'{}' object is not an iterator", other.type_name()),
+                );
+            }
+        };
+        // Lazy adapters call back into the interpreter, so they must not hold the borrow
+        let adapter = match &*iter.borrow() {
+            Iter::Map(function, sources) => Some((1, function.clone(), sources.clone())),
+            Iter::Filter(function, source) => Some((2, function.clone(), vec![source.clone()])),
+            Iter::Zip(sources) => Some((3, Value::None, sources.clone())),
+            Iter::Enumerate(source, _) => Some((4, Value::None, vec![source.clone()])),
+            _ => None,
+        };
+        if let Some((kind, function, sources)) = adapter {
+            return match kind {
+                1 => {
+                    let mut args = Vec::with_capacity(sources.len());
+                    for source in &sources {
+                        match self.next(source)? {
+                            Some(value) => args.push(value),
+                            None => return Ok(None),
+                        }
+                    }
+                    self.call(&function, Args::new(args)).map(Some)
+                }
+                2 => loop {
+                    let Some(item) = self.next(&sources[0])? else {
+                        return Ok(None);
+                    };
+                    let keep = if function.is_none() {
+                        item.clone()
+                    } else {
+                        self.call(&function, Args::new(vec![item.clone()]))?
+                    };
+                    if self.truthy(&keep)? {
+                        return Ok(Some(item));
+                    }
+                },
+                3 => {
+                    if sources.is_empty() {
+                        return Ok(None);
+                    }
+                    let mut items = Vec::with_capacity(sources.len());
+                    for source in &sources {
+                        match self.next(source)? {
+                            Some(value) => items.push(value),
+                            None => return Ok(None),
+                        }
+                    }
+                    Ok(Some(Value::tuple(items)))
+                }
+                _ => {
+                    let Some(item) = self.next(&sources[0])? else {
+                        return Ok(None);
+                    };
+                    let mut state = iter.borrow_mut();
+                    let Iter::Enumerate(_, count) = &mut *state else {
+                        unreachable!()
+                    };
+                    let index = *count;
+                    *count += 1;
+                    Ok(Some(Value::tuple(vec![Value::Int(index), item])))
+                }
+            };
+        }
+        if let Iter::Lines(file) = &*iter.borrow() {
+            return Ok(methods::read_line(file)?.map(|line| Value::str(&line)));
+        }
+        let mut state = iter.borrow_mut();
+        Ok(match &mut *state {
+            Iter::List(list, i) => {
+                let item = list.borrow().get(*i).cloned();
+                *i += 1;
+                item
+            }
+            Iter::Tuple(items, i) => {
+                let item = items.get(*i).cloned();
+                *i += 1;
+                item
+            }
+            Iter::Values(values) => values.next(),
+            Iter::Range { next, stop, step } => {
+                let more = if *step > 0 { *next < *stop } else { *next > *stop };
+                if more {
+                    let value = *next;
+                    *next += *step;
+                    Some(Value::Int(value))
+                } else {
+                    None
+                }
+            }
+            Iter::Str(s, i) => match s[*i..].chars().next() {
+                Some(c) => {
+                    *i += c.len_utf8();
+                    Some(Value::str(c.encode_utf8(&mut [0; 4])))
+                }
+                None => None,
+            },
+            _ => unreachable!(),
+        })
+    }
+
+    /// Every item of an iterable.
+    pub fn collect(&mut self, value: &Value) -> PyResult<Vec<Value>> {
+        match value {
+            Value::List(list) => Ok(list.borrow().clone()),
+            Value::Tuple(items) => Ok(items.to_vec()),
+            Value::Dict(dict) => Ok(dict.borrow().keys()),
+            Value::Set(set) => Ok(set.borrow().items()),
+            _ => {
+                let iterator = self.iter(value)?;
+                let mut items = Vec::new();
+                while let Some(item) = self.next(&iterator)? {
+                    items.push(item);
+                }
+                Ok(items)
+            }
+        }
+    }
+}
+
+/// A numeric view of ints, bools and floats for mixed comparisons.
+#[derive(PartialEq, PartialOrd)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn eq(&self, other: &Number) -> bool {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a == b,
+            (a, b) => a.as_f64() == b.as_f64(),
+        }
+    }
+
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => Some(a.cmp(b)),
+            (a, b) => a.as_f64().partial_cmp(&b.as_f64()),
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            Number::Int(n) => *n as f64,
+            Number::Float(x) => *x,
+        }
+    }
+}
+
+fn number(value: &Value) -> Option<Number> {
+    match value {
+        Value::Int(n) => Some(Number::Int(*n)),
+        Value::Bool(b) => Some(Number::Int(*b as i64)),
+        Value::Float(x) => Some(Number::Float(*x)),
+        _ => None,
+    }
+}
+
+fn int_of(value: &Value) -> i64 {
+    match value {
+        Value::Int(n) => *n,
+        Value::Bool(b) => *b as i64,
+        _ => 0,
+    }
+}
+
+/// Arithmetic on ints, bools and floats; `None` for other operands.
+fn arithmetic(op: BinOp, left: &Value, right: &Value) -> PyResult<Option<Value>> {
+    let (Some(a), Some(b)) = (number(left), number(right)) else {
+        return Ok(None);
+    };
+    if let (Value::Bool(x), Value::Bool(y)) = (left, right) {
+        match op {
+            BinOp::BitAnd => return Ok(Some(Value::Bool(x & y))),
+            BinOp::BitOr => return Ok(Some(Value::Bool(x | y))),
+            BinOp::BitXor => return Ok(Some(Value::Bool(x ^ y))),
+            _ => {}
+        }
+    }
+    Ok(Some(match (a, b) {
+        (Number::Int(a), Number::Int(b)) => int_arithmetic(op, a, b)?,
+        (a, b) => {
+            if matches!(
+                op,
+                BinOp::LShift | BinOp::RShift | BinOp::BitOr | BinOp::BitXor | BinOp::BitAnd | BinOp::MatMul
+            ) {
+                return Ok(None);
+            }
+            Value::Float(float_arithmetic(op, a.as_f64(), b.as_f64())?)
+        }
+    }))
+}
+
+fn int_arithmetic(op: BinOp, a: i64, b: i64) -> PyResult<Value> {
+    let result = match op {
+        BinOp::Add => a.checked_add(b),
+        BinOp::Sub => a.checked_sub(b),
+        BinOp::Mul => a.checked_mul(b),
+        BinOp::Div => {
+            if b == 0 {
+                return raise("ZeroDivisionError", "division by zero");
+            }
+            return Ok(Value::Float(a as f64 / b as f64));
+        }
+        BinOp::FloorDiv => {
+            if b == 0 {
+                return raise("ZeroDivisionError", "integer division or modulo by zero");
+            }
+            a.checked_div(b).map(|q| {
+                if a % b != 0 && ((a < 0) != (b < 0)) {
+                    q - 1
+                } else {
+                    q
+                }
+            })
+        }
+        BinOp::Mod => {
+            if b == 0 {
+                return raise("ZeroDivisionError", "integer modulo by zero");
+            }
+            a.checked_rem(b).map(|r| if r != 0 && ((r < 0) != (b < 0)) { r + b } else { r })
+        }
+        BinOp::Pow => {
+            if b < 0 {
+                if a == 0 {
+                    return raise(
+                        "ZeroDivisionError",
+                        "0.0 cannot be raised to a negative power",
+                    );
+                }
+                return Ok(Value::Float((a as f64).powf(b as f64)));
+            }
+            match a {
+                0 | 1 => Some(if b == 0 { 1 } else { a }),
+                -1 => Some(if b % 2 == 0 { 1 } else { -1 }),
+                _ => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+            }
+        }
+        BinOp::LShift => {
+            if b < 0 {
+                return raise("ValueError", "negative shift count");
+            }
+            if a == 0 {
+                Some(0)
+            } else if b >= 63 {
+                None
+            } else {
+                let shifted = a << b;
+                (shifted >> b == a).then_some(shifted)
+            }
+        }
+        BinOp::RShift => {
+            if b < 0 {
+                return raise("ValueError", "negative shift count");
+            }
+            Some(a >> b.min(63))
+        }
+        BinOp::BitOr => Some(a | b),
+        BinOp::BitXor => Some(a ^ b),
+        BinOp::BitAnd => Some(a & b),
+        BinOp::MatMul => {
+            return raise(
+                "TypeError",
+                "unsupported operand type(s) for @: 'int' and 'int'",
+            );
+        }
+    };
+    match result {
+        Some(n) => Ok(Value::Int(n)),
+        None => int_too_large(),
+    }
+}
+
+fn float_arithmetic(op: BinOp, a: f64, b: f64) -> PyResult<f64> {
+    Ok(match op {
+        BinOp::Add => a + b,
+        BinOp::Sub => a - b,
+        BinOp::Mul => a * b,
+        BinOp::Div => {
+            if b == 0.0 {
+                return raise("ZeroDivisionError", "float division by zero");
+            }
+            a / b
+        }
+        BinOp::FloorDiv => {
+            if b == 0.0 {
+                return raise("ZeroDivisionError", "float floor division by zero");
+            }
+            (a / b).floor()
+        }
+        BinOp::Mod => {
+            if b == 0.0 {
+                return raise("ZeroDivisionError", "float modulo by zero");
+            }
+            let r = a % b;
+            if r != 0.0 && ((r < 0.0) != (b < 0.0)) { r + b } else { r }
+        }
+        BinOp::Pow => {
+            if a == 0.0 && b < 0.0 {
+                return raise(
+                    "ZeroDivisionError",
+                    "0.0 cannot be raised to a negative power",
+                );
+            }
+            if a < 0.0 && b.fract() != 0.0 {
+                return raise("ValueError", "negative number cannot be raised to a fractional power");
+            }
+            let result = a.powf(b);
+            if result.is_infinite() && a.is_finite() && b.is_finite() {
+                return raise("OverflowError", "(34, 'Numerical result out of range')");
+            }
+            result
+        }
+        _ => unreachable!(),
+    })
+}
+
+/// `sequence * n`.
+fn repeat(sequence: &Value, n: i64) -> PyResult<Value> {
+    let n = n.max(0) as usize;
+    let len = match sequence {
+        Value::Str(s) => s.len(),
+        Value::List(l) => l.borrow().len(),
+        Value::Tuple(t) => t.len(),
+        _ => 0,
+    };
+    if len.saturating_mul(n) > MAX_REPEAT {
+        return raise("MemoryError", "");
+    }
+    Ok(match sequence {
+        Value::Str(s) => Value::str(&s.repeat(n)),
+        Value::List(l) => {
+            let items = l.borrow();
+            Value::list(items.iter().cloned().cycle().take(items.len() * n).collect())
+        }
+        Value::Tuple(t) => Value::tuple(t.iter().cloned().cycle().take(t.len() * n).collect()),
+        _ => unreachable!(),
+    })
+}
+
+impl Value {
+    /// The items of a list value.
+    pub fn list_items(&self) -> Vec<Value> {
+        match self {
+            Value::List(list) => list.borrow().clone(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn set_op(op: BinOp, a: &Set, b: &Set) -> Set {
+    let mut result = Set::default();
+    match op {
+        BinOp::BitOr => {
+            result.map = a.map.clone();
+            for (key, value) in &b.map {
+                result.map.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        BinOp::BitAnd => {
+            for (key, value) in &a.map {
+                if b.map.contains_key(key) {
+                    result.map.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        BinOp::Sub => {
+            for (key, value) in &a.map {
+                if !b.map.contains_key(key) {
+                    result.map.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        _ => {
+            for (key, value) in &a.map {
+                if !b.map.contains_key(key) {
+                    result.map.insert(key.clone(), value.clone());
+                }
+            }
+            for (key, value) in &b.map {
+                if !a.map.contains_key(key) {
+                    result.map.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+    result
+}
+
+fn hash_key(key: &Key) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub fn range_len(start: i64, stop: i64, step: i64) -> i64 {
+    if step > 0 && start < stop {
+        (stop - start - 1) / step + 1
+    } else if step < 0 && start > stop {
+        (start - stop - 1) / (-step) + 1
+    } else {
+        0
+    }
+}
+
+/// Resolve a slice against a sequence length: start, stop, step and the
+/// number of items selected.
+pub fn slice_bounds(slice: &[Value; 3], len: usize) -> PyResult<(i64, i64, i64, usize)> {
+    let len = len as i64;
+    let bound = |value: &Value| -> PyResult<Option<i64>> {
+        match value {
+            Value::None => Ok(None),
+            Value::Int(n) => Ok(Some(*n)),
+            Value::Bool(b) => Ok(Some(*b as i64)),
+            _ => raise(
+                "TypeError",
+                "slice indices must be integers or None or have an __index__ method",
+            ),
+        }
+    };
+    let step = bound(&slice[2])?.unwrap_or(1);
+    if step == 0 {
+        return raise("ValueError", "slice step cannot be zero");
+    }
+    let clamp = |value: Option<i64>, default: i64| -> i64 {
+        match value {
+            None => default,
+            Some(n) => {
+                let n = if n < 0 { n + len } else { n };
+                if step > 0 {
+                    n.clamp(0, len)
+                } else {
+                    n.clamp(-1, len - 1)
+                }
+            }
+        }
+    };
+    let (start, stop) = if step > 0 {
+        (clamp(bound(&slice[0])?, 0), clamp(bound(&slice[1])?, len))
+    } else {
+        (clamp(bound(&slice[0])?, len - 1), clamp(bound(&slice[1])?, -1))
+    };
+    let count = range_len(start, stop, step) as usize;
+    Ok((start, stop, step, count))
+}
+
+/// The `args` tuple of an exception instance.
+pub fn exception_args(instance: &Instance) -> Vec<Value> {
+    match instance.attrs.borrow().get("args") {
+        Some(Value::Tuple(args)) => args.to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+/// `0x...` address of an object, as default `repr`s show it.
+pub fn address(value: &Value) -> String {
+    format!("{:#x}", value.identity().unwrap_or(0))
+}
+
+/// `json.dumps` is shown as `dumps`.
+fn short_name(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
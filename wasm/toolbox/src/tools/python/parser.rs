@@ -0,0 +1,1449 @@
+//! Recursive-descent parser from tokens to the syntax tree.
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use super::ast::*;
+use super::lexer::{self, SyntaxError, Tok, Token};
+use super::value::Value;
+
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+type PResult<T> = Result<T, SyntaxError>;
+
+/// Parse a module's source.
+pub fn parse_module(source: &str) -> PResult<Block> {
+    let tokens = lexer::tokenize(source)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        generators: Vec::new(),
+        line_offset: 0,
+    };
+    let mut body = Vec::new();
+    loop {
+        match parser.peek() {
+            Tok::Eof => break,
+            Tok::Newline => parser.pos += 1,
+            Tok::Indent => return parser.error("unexpected indent"),
+            _ => parser.statement(&mut body)?,
+        }
+    }
+    Ok(body)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// One entry per enclosing function: whether it has yielded.
+    generators: Vec<bool>,
+    /// Added to line numbers, for f-string fields parsed on their own.
+    line_offset: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].tok
+    }
+
+    fn peek_at(&self, offset: usize) -> &Tok {
+        let i = (self.pos + offset).min(self.tokens.len() - 1);
+        &self.tokens[i].tok
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].line + self.line_offset
+    }
+
+    fn advance(&mut self) -> Tok {
+        let tok = self.tokens[self.pos].tok.clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn error<T>(&self, msg: impl Into<String>) -> PResult<T> {
+        Err(SyntaxError::new(msg, self.line()))
+    }
+
+    fn unexpected<T>(&self) -> PResult<T> {
+        match self.peek() {
+            Tok::Eof => self.error("unexpected EOF while parsing"),
+            Tok::Indent => self.error("unexpected indent"),
+            Tok::Dedent => self.error("unindent does not match any outer indentation level"),
+            Tok::Newline => self.error("invalid syntax"),
+            tok => self.error(format!("invalid syntax near {}", describe(tok))),
+        }
+    }
+
+    fn is_op(&self, op: &str) -> bool {
+        matches!(self.peek(), Tok::Op(o) if *o == op)
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if self.is_op(op) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_op(&mut self, op: &str) -> PResult<()> {
+        if self.eat_op(op) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Tok::Eof | Tok::Newline => self.error(format!("expected '{op}'")),
+                tok => self.error(format!("expected '{op}', found {}", describe(tok))),
+            }
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Tok::Name(n) if &**n == keyword)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> PResult<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            self.error(format!("expected '{keyword}'"))
+        }
+    }
+
+    fn identifier(&mut self) -> PResult<Rc<str>> {
+        match self.peek().clone() {
+            Tok::Name(name) if !KEYWORDS.contains(&&*name) => {
+                self.advance();
+                Ok(name)
+            }
+            _ => self.unexpected(),
+        }
+    }
+
+    fn expect_newline(&mut self) -> PResult<()> {
+        match self.peek() {
+            Tok::Newline => {
+                self.advance();
+                Ok(())
+            }
+            Tok::Eof | Tok::Dedent => Ok(()),
+            _ => self.unexpected(),
+        }
+    }
+
+    // ---- Statements ----
+
+    fn statement(&mut self, out: &mut Block) -> PResult<()> {
+        let line = self.line();
+        let keyword = match self.peek() {
+            Tok::Name(name) => name.clone(),
+            Tok::Op("@") => {
+                out.push(self.decorated()?);
+                return Ok(());
+            }
+            _ => Rc::from(""),
+        };
+        let kind = match &*keyword {
+            "if" => {
+                self.advance();
+                self.if_statement()?
+            }
+            "while" => {
+                self.advance();
+                let test = self.named_expr()?;
+                let body = self.block()?;
+                let orelse = self.else_block()?;
+                StmtKind::While { test, body, orelse }
+            }
+            "for" => {
+                self.advance();
+                let target = self.target_list()?;
+                self.expect_keyword("in")?;
+                let iter = self.star_exprs()?;
+                let body = self.block()?;
+                let orelse = self.else_block()?;
+                StmtKind::For {
+                    target,
+                    iter,
+                    body,
+                    orelse,
+                }
+            }
+            "try" => {
+                self.advance();
+                self.try_statement()?
+            }
+            "with" => {
+                self.advance();
+                let mut items = Vec::new();
+                loop {
+                    let expr = self.expr()?;
+                    let target = if self.eat_keyword("as") {
+                        let target = self.target()?;
+                        Some(target)
+                    } else {
+                        None
+                    };
+                    items.push((expr, target));
+                    if !self.eat_op(",") {
+                        break;
+                    }
+                }
+                let body = self.block()?;
+                StmtKind::With { items, body }
+            }
+            "def" => {
+                self.advance();
+                StmtKind::FunctionDef(self.function(Vec::new())?)
+            }
+            "class" => {
+                self.advance();
+                self.class(Vec::new())?
+            }
+            "async" => return self.error("async functions are not supported"),
+            _ => {
+                self.simple_statements(out)?;
+                return Ok(());
+            }
+        };
+        out.push(Stmt { line, kind });
+        Ok(())
+    }
+
+    fn decorated(&mut self) -> PResult<Stmt> {
+        let line = self.line();
+        let mut decorators = Vec::new();
+        while self.eat_op("@") {
+            decorators.push(self.named_expr()?);
+            self.expect_newline()?;
+        }
+        let kind = if self.eat_keyword("def") {
+            StmtKind::FunctionDef(self.function(decorators)?)
+        } else if self.eat_keyword("class") {
+            self.class(decorators)?
+        } else {
+            return self.error("expected a function or class after a decorator");
+        };
+        Ok(Stmt { line, kind })
+    }
+
+    fn if_statement(&mut self) -> PResult<StmtKind> {
+        let test = self.named_expr()?;
+        let body = self.block()?;
+        let orelse = if self.is_keyword("elif") {
+            let line = self.line();
+            self.advance();
+            vec![Stmt {
+                line,
+                kind: self.if_statement()?,
+            }]
+        } else {
+            self.else_block()?
+        };
+        Ok(StmtKind::If { test, body, orelse })
+    }
+
+    fn else_block(&mut self) -> PResult<Block> {
+        if self.eat_keyword("else") {
+            self.block()
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn try_statement(&mut self) -> PResult<StmtKind> {
+        let body = self.block()?;
+        let mut handlers = Vec::new();
+        while self.is_keyword("except") {
+            self.advance();
+            let (kind, name) = if self.is_op(":") {
+                (None, None)
+            } else {
+                let kind = self.expr()?;
+                let name = if self.eat_keyword("as") {
+                    Some(self.identifier()?)
+                } else {
+                    None
+                };
+                (Some(kind), name)
+            };
+            let body = self.block()?;
+            handlers.push(Handler { kind, name, body });
+        }
+        let orelse = self.else_block()?;
+        let finally = if self.eat_keyword("finally") {
+            self.block()?
+        } else {
+            Vec::new()
+        };
+        if handlers.is_empty() && finally.is_empty() {
+            return self.error("expected 'except' or 'finally' block");
+        }
+        Ok(StmtKind::Try {
+            body,
+            handlers,
+            orelse,
+            finally,
+        })
+    }
+
+    fn function(&mut self, decorators: Vec<Expr>) -> PResult<Rc<FunctionDef>> {
+        let name = self.identifier()?;
+        self.expect_op("(")?;
+        let params = self.params(")", true)?;
+        self.expect_op(")")?;
+        if self.eat_op("->") {
+            self.expr()?;
+        }
+        self.generators.push(false);
+        let body = self.block();
+        let is_generator = self.generators.pop().unwrap();
+        let body = body?;
+        let mut locals = HashSet::new();
+        bound_names(&body, &mut locals);
+        Ok(Rc::new(FunctionDef {
+            name,
+            params,
+            body,
+            decorators,
+            is_generator,
+            locals,
+        }))
+    }
+
+    /// Parameters up to `end`; annotations are allowed in `def`, not `lambda`.
+    fn params(&mut self, end: &str, annotations: bool) -> PResult<Vec<Param>> {
+        let mut params: Vec<Param> = Vec::new();
+        let mut keyword_only = false;
+        while !self.is_op(end) {
+            if self.eat_op("/") {
+                // Positional-only marker: every parameter accepts keywords here
+            } else if self.eat_op("**") {
+                let name = self.identifier()?;
+                self.annotation(annotations)?;
+                params.push(Param {
+                    name,
+                    kind: ParamKind::VarKeywords,
+                    default: None,
+                });
+            } else if self.eat_op("*") {
+                keyword_only = true;
+                if !self.is_op(",") && !self.is_op(end) {
+                    let name = self.identifier()?;
+                    self.annotation(annotations)?;
+                    params.push(Param {
+                        name,
+                        kind: ParamKind::VarArgs,
+                        default: None,
+                    });
+                }
+            } else {
+                let name = self.identifier()?;
+                if params.iter().any(|p| p.name == name) {
+                    return self.error(format!(
+                        "duplicate argument '{name}' in function definition"
+                    ));
+                }
+                self.annotation(annotations)?;
+                let default = if self.eat_op("=") {
+                    Some(self.expr()?)
+                } else {
+                    None
+                };
+                if default.is_none()
+                    && !keyword_only
+                    && params
+                        .iter()
+                        .any(|p| p.kind == ParamKind::Positional && p.default.is_some())
+                {
+                    return self.error("non-default argument follows default argument");
+                }
+                let kind = if keyword_only {
+                    ParamKind::KeywordOnly
+                } else {
+                    ParamKind::Positional
+                };
+                params.push(Param {
+                    name,
+                    kind,
+                    default,
+                });
+            }
+            if !self.eat_op(",") {
+                break;
+            }
+        }
+        Ok(params)
+    }
+
+    fn annotation(&mut self, allowed: bool) -> PResult<()> {
+        if allowed && self.eat_op(":") {
+            self.expr()?;
+        }
+        Ok(())
+    }
+
+    fn class(&mut self, decorators: Vec<Expr>) -> PResult<StmtKind> {
+        let name = self.identifier()?;
+        let bases = if self.eat_op("(") {
+            let args = self.call_args()?;
+            self.expect_op(")")?;
+            args
+        } else {
+            Vec::new()
+        };
+        // A class body is not part of an enclosing generator
+        self.generators.push(false);
+        let body = self.block();
+        self.generators.pop();
+        Ok(StmtKind::ClassDef {
+            name,
+            bases,
+            body: body?,
+            decorators,
+        })
+    }
+
+    /// `:` followed by an indented block or simple statements on the same line.
+    fn block(&mut self) -> PResult<Block> {
+        self.expect_op(":")?;
+        let mut body = Vec::new();
+        if matches!(self.peek(), Tok::Newline) {
+            self.advance();
+            if !matches!(self.peek(), Tok::Indent) {
+                return self.error("expected an indented block");
+            }
+            self.advance();
+            loop {
+                match self.peek() {
+                    Tok::Dedent => {
+                        self.advance();
+                        break;
+                    }
+                    Tok::Eof => break,
+                    Tok::Newline => {
+                        self.advance();
+                    }
+                    _ => self.statement(&mut body)?,
+                }
+            }
+        } else {
+            self.simple_statements(&mut body)?;
+        }
+        Ok(body)
+    }
+
+    fn simple_statements(&mut self, out: &mut Block) -> PResult<()> {
+        loop {
+            let line = self.line();
+            let kind = self.simple_statement()?;
+            out.push(Stmt { line, kind });
+            if !self.eat_op(";") || matches!(self.peek(), Tok::Newline | Tok::Eof) {
+                break;
+            }
+        }
+        self.expect_newline()
+    }
+
+    fn simple_statement(&mut self) -> PResult<StmtKind> {
+        let keyword = match self.peek() {
+            Tok::Name(name) => name.clone(),
+            _ => Rc::from(""),
+        };
+        let kind = match &*keyword {
+            "pass" => {
+                self.advance();
+                StmtKind::Pass
+            }
+            "break" => {
+                self.advance();
+                StmtKind::Break
+            }
+            "continue" => {
+                self.advance();
+                StmtKind::Continue
+            }
+            "return" => {
+                self.advance();
+                if self.at_statement_end() {
+                    StmtKind::Return(None)
+                } else {
+                    StmtKind::Return(Some(self.star_exprs()?))
+                }
+            }
+            "raise" => {
+                self.advance();
+                if self.at_statement_end() {
+                    StmtKind::Raise {
+                        exc: None,
+                        cause: None,
+                    }
+                } else {
+                    let exc = Some(self.expr()?);
+                    let cause = if self.eat_keyword("from") {
+                        Some(self.expr()?)
+                    } else {
+                        None
+                    };
+                    StmtKind::Raise { exc, cause }
+                }
+            }
+            "global" | "nonlocal" => {
+                self.advance();
+                let mut names = vec![self.identifier()?];
+                while self.eat_op(",") {
+                    names.push(self.identifier()?);
+                }
+                if &*keyword == "global" {
+                    StmtKind::Global(names)
+                } else {
+                    StmtKind::Nonlocal(names)
+                }
+            }
+            "import" => {
+                self.advance();
+                let mut names = Vec::new();
+                loop {
+                    let module = self.dotted_name()?;
+                    let alias = if self.eat_keyword("as") {
+                        Some(self.identifier()?)
+                    } else {
+                        None
+                    };
+                    names.push((module, alias));
+                    if !self.eat_op(",") {
+                        break;
+                    }
+                }
+                StmtKind::Import(names)
+            }
+            "from" => {
+                self.advance();
+                self.import_from()?
+            }
+            "assert" => {
+                self.advance();
+                let test = self.expr()?;
+                let msg = if self.eat_op(",") {
+                    Some(self.expr()?)
+                } else {
+                    None
+                };
+                StmtKind::Assert { test, msg }
+            }
+            "del" => {
+                self.advance();
+                let mut targets = vec![self.target()?];
+                while self.eat_op(",") {
+                    if self.at_statement_end() {
+                        break;
+                    }
+                    targets.push(self.target()?);
+                }
+                StmtKind::Del(targets)
+            }
+            _ => self.expression_statement()?,
+        };
+        Ok(kind)
+    }
+
+    fn at_statement_end(&self) -> bool {
+        matches!(self.peek(), Tok::Newline | Tok::Eof | Tok::Dedent | Tok::Op(";"))
+    }
+
+    fn dotted_name(&mut self) -> PResult<String> {
+        let mut name = self.identifier()?.to_string();
+        while self.eat_op(".") {
+            name.push('.');
+            name.push_str(&self.identifier()?);
+        }
+        Ok(name)
+    }
+
+    fn import_from(&mut self) -> PResult<StmtKind> {
+        let mut level = 0;
+        loop {
+            if self.eat_op(".") {
+                level += 1;
+            } else if self.eat_op("...") {
+                level += 3;
+            } else {
+                break;
+            }
+        }
+        let module = if level > 0 && self.is_keyword("import") {
+            String::new()
+        } else {
+            self.dotted_name()?
+        };
+        self.expect_keyword("import")?;
+        let mut names = Vec::new();
+        if self.eat_op("*") {
+            return Ok(StmtKind::ImportFrom {
+                module,
+                level,
+                names,
+            });
+        }
+        let parenthesized = self.eat_op("(");
+        loop {
+            if parenthesized && self.is_op(")") {
+                break;
+            }
+            let name = self.identifier()?;
+            let alias = if self.eat_keyword("as") {
+                Some(self.identifier()?)
+            } else {
+                None
+            };
+            names.push((name, alias));
+            if !self.eat_op(",") {
+                break;
+            }
+        }
+        if parenthesized {
+            self.expect_op(")")?;
+        }
+        Ok(StmtKind::ImportFrom {
+            module,
+            level,
+            names,
+        })
+    }
+
+    fn expression_statement(&mut self) -> PResult<StmtKind> {
+        let first = self.yield_or_star_exprs()?;
+        if self.eat_op(":") {
+            check_target(&first, self.line())?;
+            self.expr()?;
+            let value = if self.eat_op("=") {
+                Some(self.yield_or_star_exprs()?)
+            } else {
+                None
+            };
+            return Ok(StmtKind::AnnAssign {
+                target: first,
+                value,
+            });
+        }
+        if let Tok::Op(op) = self.peek()
+            && let Some(bin) = augmented(op)
+        {
+            self.advance();
+            if !matches!(first, Expr::Name(_) | Expr::Attribute(..) | Expr::Subscript(..)) {
+                return self.error("illegal expression for augmented assignment");
+            }
+            let value = self.yield_or_star_exprs()?;
+            return Ok(StmtKind::AugAssign {
+                target: first,
+                op: bin,
+                value,
+            });
+        }
+        if !self.is_op("=") {
+            return Ok(StmtKind::Expr(first));
+        }
+        let mut targets = vec![first];
+        while self.eat_op("=") {
+            targets.push(self.yield_or_star_exprs()?);
+        }
+        let value = targets.pop().unwrap();
+        for target in &targets {
+            check_target(target, self.line())?;
+        }
+        Ok(StmtKind::Assign { targets, value })
+    }
+
+    // ---- Expressions ----
+
+    fn yield_or_star_exprs(&mut self) -> PResult<Expr> {
+        if self.is_keyword("yield") {
+            self.yield_expr()
+        } else {
+            self.star_exprs()
+        }
+    }
+
+    fn yield_expr(&mut self) -> PResult<Expr> {
+        self.expect_keyword("yield")?;
+        match self.generators.last_mut() {
+            Some(seen) => *seen = true,
+            None => return self.error("'yield' outside function"),
+        }
+        if self.eat_keyword("from") {
+            return Ok(Expr::YieldFrom(Box::new(self.expr()?)));
+        }
+        if self.at_statement_end() || self.is_op(")") || self.is_op("=") {
+            return Ok(Expr::Yield(None));
+        }
+        Ok(Expr::Yield(Some(Box::new(self.star_exprs()?))))
+    }
+
+    /// A comma-separated list that is a tuple unless it has one item and
+    /// no trailing comma, e.g. the value of `return a, b`.
+    fn star_exprs(&mut self) -> PResult<Expr> {
+        let first = self.star_or_expr()?;
+        if !self.is_op(",") {
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while self.eat_op(",") {
+            if self.at_expr_end() {
+                break;
+            }
+            items.push(self.star_or_expr()?);
+        }
+        Ok(Expr::Tuple(items))
+    }
+
+    fn at_expr_end(&self) -> bool {
+        self.at_statement_end()
+            || matches!(
+                self.peek(),
+                Tok::Op("=" | ")" | "]" | "}" | ":") | Tok::Op("+=" | "-=" | "*=" | "/=")
+            )
+    }
+
+    fn star_or_expr(&mut self) -> PResult<Expr> {
+        if self.eat_op("*") {
+            Ok(Expr::Starred(Box::new(self.bit_or()?)))
+        } else {
+            self.named_expr()
+        }
+    }
+
+    /// A `for` target: a comma-separated list that stops before `in`.
+    fn target_list(&mut self) -> PResult<Expr> {
+        let first = self.target()?;
+        if !self.is_op(",") {
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while self.eat_op(",") {
+            if self.is_keyword("in") || self.is_op("=") {
+                break;
+            }
+            items.push(self.target()?);
+        }
+        Ok(Expr::Tuple(items))
+    }
+
+    fn target(&mut self) -> PResult<Expr> {
+        let line = self.line();
+        let target = if self.eat_op("*") {
+            Expr::Starred(Box::new(self.bit_or()?))
+        } else {
+            self.bit_or()?
+        };
+        check_target(&target, line)?;
+        Ok(target)
+    }
+
+    fn named_expr(&mut self) -> PResult<Expr> {
+        if let Tok::Name(name) = self.peek().clone()
+            && matches!(self.peek_at(1), Tok::Op(":="))
+        {
+            self.advance();
+            self.advance();
+            let value = self.expr()?;
+            return Ok(Expr::Named(name, Box::new(value)));
+        }
+        self.expr()
+    }
+
+    /// A full expression: a lambda, or a conditional expression.
+    fn expr(&mut self) -> PResult<Expr> {
+        if self.is_keyword("lambda") {
+            return self.lambda();
+        }
+        let body = self.or_test()?;
+        if self.eat_keyword("if") {
+            let test = self.or_test()?;
+            self.expect_keyword("else")?;
+            let orelse = self.expr()?;
+            return Ok(Expr::IfExp(
+                Box::new(test),
+                Box::new(body),
+                Box::new(orelse),
+            ));
+        }
+        Ok(body)
+    }
+
+    fn lambda(&mut self) -> PResult<Expr> {
+        let line = self.line();
+        self.expect_keyword("lambda")?;
+        let params = self.params(":", false)?;
+        self.expect_op(":")?;
+        self.generators.push(false);
+        let body = self.expr();
+        let is_generator = self.generators.pop().unwrap();
+        Ok(Expr::Lambda(Rc::new(FunctionDef {
+            name: Rc::from("<lambda>"),
+            params,
+            body: vec![Stmt {
+                line,
+                kind: StmtKind::Return(Some(body?)),
+            }],
+            decorators: Vec::new(),
+            is_generator,
+            locals: HashSet::new(),
+        })))
+    }
+
+    fn or_test(&mut self) -> PResult<Expr> {
+        let mut left = self.and_test()?;
+        while self.eat_keyword("or") {
+            let right = self.and_test()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and_test(&mut self) -> PResult<Expr> {
+        let mut left = self.not_test()?;
+        while self.eat_keyword("and") {
+            let right = self.not_test()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn not_test(&mut self) -> PResult<Expr> {
+        if self.eat_keyword("not") {
+            let operand = self.not_test()?;
+            return Ok(Expr::Unary(UnaryOp::Not, Box::new(operand)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> PResult<Expr> {
+        let left = self.bit_or()?;
+        let mut ops = Vec::new();
+        loop {
+            let next_is = |parser: &Self, word: &str| {
+                matches!(parser.peek_at(1), Tok::Name(name) if &**name == word)
+            };
+            let (op, width) = match self.peek() {
+                Tok::Op("<") => (CmpOp::Lt, 1),
+                Tok::Op(">") => (CmpOp::Gt, 1),
+                Tok::Op("==") => (CmpOp::Eq, 1),
+                Tok::Op(">=") => (CmpOp::GtE, 1),
+                Tok::Op("<=") => (CmpOp::LtE, 1),
+                Tok::Op("!=") => (CmpOp::NotEq, 1),
+                Tok::Name(n) if &**n == "in" => (CmpOp::In, 1),
+                Tok::Name(n) if &**n == "not" && next_is(self, "in") => (CmpOp::NotIn, 2),
+                Tok::Name(n) if &**n == "is" && next_is(self, "not") => (CmpOp::IsNot, 2),
+                Tok::Name(n) if &**n == "is" => (CmpOp::Is, 1),
+                _ => break,
+            };
+            for _ in 0..width {
+                self.advance();
+            }
+            ops.push((op, self.bit_or()?));
+        }
+        if ops.is_empty() {
+            Ok(left)
+        } else {
+            Ok(Expr::Compare(Box::new(left), ops))
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[(&str, BinOp)],
+        next: fn(&mut Self) -> PResult<Expr>,
+    ) -> PResult<Expr> {
+        let mut left = next(self)?;
+        'outer: loop {
+            for &(symbol, op) in ops {
+                if self.eat_op(symbol) {
+                    let right = next(self)?;
+                    left = Expr::BinOp(Box::new(left), op, Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn bit_or(&mut self) -> PResult<Expr> {
+        self.binary(&[("|", BinOp::BitOr)], Self::bit_xor)
+    }
+
+    fn bit_xor(&mut self) -> PResult<Expr> {
+        self.binary(&[("^", BinOp::BitXor)], Self::bit_and)
+    }
+
+    fn bit_and(&mut self) -> PResult<Expr> {
+        self.binary(&[("&", BinOp::BitAnd)], Self::shift)
+    }
+
+    fn shift(&mut self) -> PResult<Expr> {
+        self.binary(&[("<<", BinOp::LShift), (">>", BinOp::RShift)], Self::arith)
+    }
+
+    fn arith(&mut self) -> PResult<Expr> {
+        self.binary(&[("+", BinOp::Add), ("-", BinOp::Sub)], Self::term)
+    }
+
+    fn term(&mut self) -> PResult<Expr> {
+        self.binary(
+            &[
+                ("*", BinOp::Mul),
+                ("/", BinOp::Div),
+                ("//", BinOp::FloorDiv),
+                ("%", BinOp::Mod),
+                ("@", BinOp::MatMul),
+            ],
+            Self::factor,
+        )
+    }
+
+    fn factor(&mut self) -> PResult<Expr> {
+        let op = match self.peek() {
+            Tok::Op("-") => UnaryOp::Neg,
+            Tok::Op("+") => UnaryOp::Pos,
+            Tok::Op("~") => UnaryOp::Invert,
+            _ => return self.power(),
+        };
+        self.advance();
+        let operand = self.factor()?;
+        // Fold negative literals, so `-1` is a constant
+        if op == UnaryOp::Neg {
+            match operand {
+                Expr::Const(Value::Int(n)) => return Ok(Expr::Const(Value::Int(-n))),
+                Expr::Const(Value::Float(f)) => return Ok(Expr::Const(Value::Float(-f))),
+                _ => {}
+            }
+        }
+        Ok(Expr::Unary(op, Box::new(operand)))
+    }
+
+    fn power(&mut self) -> PResult<Expr> {
+        if self.is_keyword("await") {
+            return self.error("'await' is not supported");
+        }
+        let base = self.primary()?;
+        if self.eat_op("**") {
+            let exponent = self.factor()?;
+            return Ok(Expr::BinOp(Box::new(base), BinOp::Pow, Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> PResult<Expr> {
+        let mut expr = self.atom()?;
+        loop {
+            if self.eat_op("(") {
+                let args = self.call_args()?;
+                self.expect_op(")")?;
+                expr = Expr::Call(Box::new(expr), args);
+            } else if self.eat_op("[") {
+                let index = self.subscript()?;
+                self.expect_op("]")?;
+                expr = Expr::Subscript(Box::new(expr), Box::new(index));
+            } else if self.eat_op(".") {
+                let name = self.identifier()?;
+                expr = Expr::Attribute(Box::new(expr), name);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn call_args(&mut self) -> PResult<Vec<Arg>> {
+        let mut args = Vec::new();
+        while !self.is_op(")") {
+            if self.eat_op("**") {
+                args.push(Arg::DoubleStarred(self.expr()?));
+            } else if self.eat_op("*") {
+                args.push(Arg::Starred(self.expr()?));
+            } else if let Tok::Name(name) = self.peek().clone()
+                && matches!(self.peek_at(1), Tok::Op("="))
+            {
+                self.advance();
+                self.advance();
+                args.push(Arg::Keyword(name, self.expr()?));
+            } else {
+                let value = self.named_expr()?;
+                if self.is_keyword("for") {
+                    let comp = self.comprehension(CompKind::Generator, value, None)?;
+                    args.push(Arg::Positional(comp));
+                } else {
+                    args.push(Arg::Positional(value));
+                }
+            }
+            if !self.eat_op(",") {
+                break;
+            }
+        }
+        Ok(args)
+    }
+
+    fn subscript(&mut self) -> PResult<Expr> {
+        let first = self.slice_item()?;
+        if !self.is_op(",") {
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while self.eat_op(",") {
+            if self.is_op("]") {
+                break;
+            }
+            items.push(self.slice_item()?);
+        }
+        Ok(Expr::Tuple(items))
+    }
+
+    fn slice_item(&mut self) -> PResult<Expr> {
+        let lower = if self.is_op(":") {
+            None
+        } else {
+            let value = self.named_expr()?;
+            if !self.is_op(":") {
+                return Ok(value);
+            }
+            Some(Box::new(value))
+        };
+        self.expect_op(":")?;
+        let bound = |parser: &mut Self| -> PResult<Option<Box<Expr>>> {
+            if parser.is_op(":") || parser.is_op("]") || parser.is_op(",") {
+                Ok(None)
+            } else {
+                Ok(Some(Box::new(parser.expr()?)))
+            }
+        };
+        let upper = bound(self)?;
+        let step = if self.eat_op(":") { bound(self)? } else { None };
+        Ok(Expr::Slice(lower, upper, step))
+    }
+
+    fn atom(&mut self) -> PResult<Expr> {
+        let line = self.line();
+        match self.peek().clone() {
+            Tok::Op("(") => {
+                self.advance();
+                if self.eat_op(")") {
+                    return Ok(Expr::Tuple(Vec::new()));
+                }
+                if self.is_keyword("yield") {
+                    let value = self.yield_expr()?;
+                    self.expect_op(")")?;
+                    return Ok(value);
+                }
+                let first = self.star_or_expr()?;
+                if self.is_keyword("for") {
+                    let comp = self.comprehension(CompKind::Generator, first, None)?;
+                    self.expect_op(")")?;
+                    return Ok(comp);
+                }
+                if self.eat_op(")") {
+                    return Ok(first);
+                }
+                let mut items = vec![first];
+                while self.eat_op(",") {
+                    if self.is_op(")") {
+                        break;
+                    }
+                    items.push(self.star_or_expr()?);
+                }
+                self.expect_op(")")?;
+                Ok(Expr::Tuple(items))
+            }
+            Tok::Op("[") => {
+                self.advance();
+                if self.eat_op("]") {
+                    return Ok(Expr::List(Vec::new()));
+                }
+                let first = self.star_or_expr()?;
+                if self.is_keyword("for") {
+                    let comp = self.comprehension(CompKind::List, first, None)?;
+                    self.expect_op("]")?;
+                    return Ok(comp);
+                }
+                let mut items = vec![first];
+                while self.eat_op(",") {
+                    if self.is_op("]") {
+                        break;
+                    }
+                    items.push(self.star_or_expr()?);
+                }
+                self.expect_op("]")?;
+                Ok(Expr::List(items))
+            }
+            Tok::Op("{") => {
+                self.advance();
+                self.braces()
+            }
+            Tok::Op("...") => {
+                self.advance();
+                Ok(Expr::Const(Value::Ellipsis))
+            }
+            Tok::Int(n) => {
+                self.advance();
+                Ok(Expr::Const(Value::Int(n)))
+            }
+            Tok::Float(f) => {
+                self.advance();
+                Ok(Expr::Const(Value::Float(f)))
+            }
+            Tok::Str(_) | Tok::FStr { .. } => self.strings(),
+            Tok::Name(name) => match &*name {
+                "None" => {
+                    self.advance();
+                    Ok(Expr::Const(Value::None))
+                }
+                "True" => {
+                    self.advance();
+                    Ok(Expr::Const(Value::Bool(true)))
+                }
+                "False" => {
+                    self.advance();
+                    Ok(Expr::Const(Value::Bool(false)))
+                }
+                "yield" => self.error("'yield' must be the whole expression here; add parentheses"),
+                _ if KEYWORDS.contains(&&*name) => {
+                    Err(SyntaxError::new("invalid syntax", line))
+                }
+                _ => {
+                    self.advance();
+                    Ok(Expr::Name(name))
+                }
+            },
+            _ => self.unexpected(),
+        }
+    }
+
+    /// After `{`: a dict, set, or a comprehension of either.
+    fn braces(&mut self) -> PResult<Expr> {
+        if self.eat_op("}") {
+            return Ok(Expr::Dict(Vec::new()));
+        }
+        if self.eat_op("**") {
+            let first = self.bit_or()?;
+            return self.dict_rest(None, first);
+        }
+        let first = self.star_or_expr()?;
+        if self.eat_op(":") {
+            let value = self.expr()?;
+            if self.is_keyword("for") {
+                let comp = self.comprehension(CompKind::Dict, first, Some(value))?;
+                self.expect_op("}")?;
+                return Ok(comp);
+            }
+            return self.dict_rest(Some(first), value);
+        }
+        if self.is_keyword("for") {
+            let comp = self.comprehension(CompKind::Set, first, None)?;
+            self.expect_op("}")?;
+            return Ok(comp);
+        }
+        let mut items = vec![first];
+        while self.eat_op(",") {
+            if self.is_op("}") {
+                break;
+            }
+            items.push(self.star_or_expr()?);
+        }
+        self.expect_op("}")?;
+        Ok(Expr::Set(items))
+    }
+
+    fn dict_rest(&mut self, key: Option<Expr>, value: Expr) -> PResult<Expr> {
+        let mut entries = vec![(key, value)];
+        while self.eat_op(",") {
+            if self.is_op("}") {
+                break;
+            }
+            if self.eat_op("**") {
+                entries.push((None, self.bit_or()?));
+            } else {
+                let key = self.expr()?;
+                self.expect_op(":")?;
+                entries.push((Some(key), self.expr()?));
+            }
+        }
+        self.expect_op("}")?;
+        Ok(Expr::Dict(entries))
+    }
+
+    fn comprehension(&mut self, kind: CompKind, elt: Expr, value: Option<Expr>) -> PResult<Expr> {
+        let mut generators = Vec::new();
+        while self.eat_keyword("for") {
+            let target = self.target_list()?;
+            self.expect_keyword("in")?;
+            let iter = self.or_test()?;
+            let mut ifs = Vec::new();
+            while self.eat_keyword("if") {
+                ifs.push(self.or_test_or_lambda()?);
+            }
+            generators.push(Generator { target, iter, ifs });
+        }
+        Ok(Expr::Comp(Box::new(Comprehension {
+            kind,
+            elt,
+            value,
+            generators,
+        })))
+    }
+
+    fn or_test_or_lambda(&mut self) -> PResult<Expr> {
+        if self.is_keyword("lambda") {
+            self.lambda()
+        } else {
+            self.or_test()
+        }
+    }
+
+    /// Adjacent string literals, concatenated; any f-string makes the whole
+    /// an f-string.
+    fn strings(&mut self) -> PResult<Expr> {
+        let mut parts: Vec<FPart> = Vec::new();
+        let mut formatted = false;
+        loop {
+            let line = self.line();
+            match self.peek().clone() {
+                Tok::Str(s) => {
+                    self.advance();
+                    push_literal(&mut parts, &s);
+                }
+                Tok::FStr { body, raw } => {
+                    self.advance();
+                    formatted = true;
+                    for part in parse_fstring(&body, raw, line)? {
+                        match part {
+                            FPart::Literal(s) => push_literal(&mut parts, &s),
+                            field => parts.push(field),
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+        if !formatted {
+            let text = match parts.pop() {
+                Some(FPart::Literal(s)) => s,
+                _ => String::new(),
+            };
+            return Ok(Expr::Const(Value::Str(text.into())));
+        }
+        Ok(Expr::FString(parts))
+    }
+}
+
+fn push_literal(parts: &mut Vec<FPart>, text: &str) {
+    if let Some(FPart::Literal(last)) = parts.last_mut() {
+        last.push_str(text);
+    } else {
+        parts.push(FPart::Literal(text.to_string()));
+    }
+}
+
+/// Split an f-string body into literal text and `{expr!c:spec}` fields.
+fn parse_fstring(body: &str, raw: bool, line: usize) -> PResult<Vec<FPart>> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut i = 0;
+    let flush = |literal: &mut String, parts: &mut Vec<FPart>| -> PResult<()> {
+        if !literal.is_empty() {
+            let text = if raw {
+                std::mem::take(literal)
+            } else {
+                let text = lexer::unescape(literal).map_err(|msg| SyntaxError::new(msg, line))?;
+                literal.clear();
+                text
+            };
+            parts.push(FPart::Literal(text));
+        }
+        Ok(())
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '{' && chars.get(i + 1) == Some(&'{') {
+            literal.push('{');
+            i += 2;
+            continue;
+        }
+        if c == '}' {
+            if chars.get(i + 1) == Some(&'}') {
+                literal.push('}');
+                i += 2;
+                continue;
+            }
+            return Err(SyntaxError::new("f-string: single '}' is not allowed", line));
+        }
+        if c == '\\' && !raw && i + 1 < chars.len() {
+            literal.push(c);
+            literal.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+        if c != '{' {
+            literal.push(c);
+            i += 1;
+            continue;
+        }
+        flush(&mut literal, &mut parts)?;
+        // Find the end of the expression: `!`, `:`, `=` or `}` at depth zero
+        let start = i + 1;
+        let mut j = start;
+        let mut depth = 0usize;
+        let mut quote: Option<char> = None;
+        while j < chars.len() {
+            let c = chars[j];
+            if let Some(q) = quote {
+                if c == q {
+                    quote = None;
+                }
+            } else {
+                match c {
+                    '\'' | '"' => quote = Some(c),
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' => depth = depth.saturating_sub(1),
+                    '}' if depth > 0 => depth -= 1,
+                    '}' => break,
+                    '!' if depth == 0 && chars.get(j + 1) != Some(&'=') => break,
+                    ':' if depth == 0 => break,
+                    '=' if depth == 0
+                        && chars.get(j + 1) != Some(&'=')
+                        && !matches!(chars.get(j.wrapping_sub(1)), Some('=' | '!' | '<' | '>')) =>
+                    {
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            j += 1;
+        }
+        let source: String = chars[start..j].iter().collect();
+        if source.trim().is_empty() {
+            return Err(SyntaxError::new("f-string: empty expression not allowed", line));
+        }
+        let expr = parse_expression(&source, line)?;
+        // `{expr=}` shows the expression text before its value
+        if chars.get(j) == Some(&'=') {
+            parts.push(FPart::Literal(format!("{source}=")));
+            j += 1;
+        }
+        let mut conversion = None;
+        if chars.get(j) == Some(&'!') {
+            conversion = chars.get(j + 1).copied();
+            if !matches!(conversion, Some('r' | 's' | 'a')) {
+                return Err(SyntaxError::new(
+                    "f-string: invalid conversion character: expected 's', 'r', or 'a'",
+                    line,
+                ));
+            }
+            j += 2;
+        }
+        let mut spec = Vec::new();
+        if chars.get(j) == Some(&':') {
+            let spec_start = j + 1;
+            let mut depth = 0usize;
+            j = spec_start;
+            while j < chars.len() {
+                match chars[j] {
+                    '{' => depth += 1,
+                    '}' if depth > 0 => depth -= 1,
+                    '}' => break,
+                    _ => {}
+                }
+                j += 1;
+            }
+            let spec_source: String = chars[spec_start..j].iter().collect();
+            spec = parse_fstring(&spec_source, raw, line)?;
+        }
+        if chars.get(j) != Some(&'}') {
+            return Err(SyntaxError::new("f-string: expecting '}'", line));
+        }
+        parts.push(FPart::Field {
+            expr,
+            conversion,
+            spec,
+        });
+        i = j + 1;
+    }
+    flush(&mut literal, &mut parts)?;
+    Ok(parts)
+}
+
+/// Parse a standalone expression, such as an f-string field.
+fn parse_expression(source: &str, line: usize) -> PResult<Expr> {
+    let tokens = lexer::tokenize(&format!("({})", source.trim()))?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        generators: vec![false],
+        line_offset: line - 1,
+    };
+    let expr = parser.star_exprs()?;
+    match parser.peek() {
+        Tok::Newline | Tok::Eof => Ok(expr),
+        _ => parser.unexpected(),
+    }
+}
+
+fn check_target(target: &Expr, line: usize) -> PResult<()> {
+    match target {
+        Expr::Name(_) | Expr::Attribute(..) | Expr::Subscript(..) => Ok(()),
+        Expr::Tuple(items) | Expr::List(items) => {
+            items.iter().try_for_each(|item| check_target(item, line))
+        }
+        Expr::Starred(inner) => check_target(inner, line),
+        Expr::Call(..) => Err(SyntaxError::new("cannot assign to function call", line)),
+        Expr::Const(_) => Err(SyntaxError::new("cannot assign to literal", line)),
+        _ => Err(SyntaxError::new("cannot assign to expression", line)),
+    }
+}
+
+fn augmented(op: &str) -> Option<BinOp> {
+    Some(match op {
+        "+=" => BinOp::Add,
+        "-=" => BinOp::Sub,
+        "*=" => BinOp::Mul,
+        "/=" => BinOp::Div,
+        "//=" => BinOp::FloorDiv,
+        "%=" => BinOp::Mod,
+        "**=" => BinOp::Pow,
+        "@=" => BinOp::MatMul,
+        "<<=" => BinOp::LShift,
+        ">>=" => BinOp::RShift,
+        "|=" => BinOp::BitOr,
+        "^=" => BinOp::BitXor,
+        "&=" => BinOp::BitAnd,
+        _ => return None,
+    })
+}
+
+fn describe(tok: &Tok) -> String {
+    match tok {
+        Tok::Name(name) => format!("'{name}'"),
+        Tok::Int(n) => format!("'{n}'"),
+        Tok::Float(f) => format!("'{f}'"),
+        Tok::Str(_) | Tok::FStr { .. } => "string".to_string(),
+        Tok::Op(op) => format!("'{op}'"),
+        Tok::Newline => "newline".to_string(),
+        Tok::Indent => "indent".to_string(),
+        Tok::Dedent => "dedent".to_string(),
+        Tok::Eof => "end of input".to_string(),
+    }
+}
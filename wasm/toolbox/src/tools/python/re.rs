@@ -0,0 +1,809 @@
+//! The `re` module: Python regular expressions on top of the `regex` crate.
+//!
+//! Patterns are translated to the crate's syntax; features it lacks
+//! (backreferences, look-around) raise `re.error`.
+
+use std::cell::{OnceCell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use regex::Regex;
+
+use super::builtins::{exact, index_value};
+use super::error::{PyResult, raise};
+use super::interp::Interp;
+use super::value::*;
+
+pub const IGNORECASE: i64 = 2;
+pub const LOCALE: i64 = 4;
+pub const MULTILINE: i64 = 8;
+pub const DOTALL: i64 = 16;
+pub const UNICODE: i64 = 32;
+pub const VERBOSE: i64 = 64;
+pub const ASCII: i64 = 256;
+
+pub const PATTERN_METHODS: &[&str] = &[
+    "findall", "finditer", "fullmatch", "match", "search", "split", "sub", "subn",
+];
+
+pub const MATCH_METHODS: &[&str] = &[
+    "end", "expand", "group", "groupdict", "groups", "span", "start",
+];
+
+/// A compiled pattern, as `re.compile` returns it.
+pub struct Pattern {
+    pub source: Rc<str>,
+    pub flags: i64,
+    regex: Regex,
+    translated: String,
+    /// `\A(?:...)`, for `match`.
+    anchored: OnceCell<Regex>,
+    /// `\A(?:...)\z`, for `fullmatch`.
+    full: OnceCell<Regex>,
+}
+
+/// The result of a successful match.
+pub struct Match {
+    pub text: Rc<str>,
+    pub pattern: Rc<Pattern>,
+    /// Byte offsets of every group; `None` if it didn't take part.
+    spans: Vec<Option<(usize, usize)>>,
+    pos: usize,
+    endpos: usize,
+}
+
+impl Pattern {
+    fn anchored(&self) -> &Regex {
+        self.anchored.get_or_init(|| {
+            Regex::new(&format!(r"\A(?:{})", self.translated)).expect("pattern compiled before")
+        })
+    }
+
+    fn full(&self) -> &Regex {
+        self.full.get_or_init(|| {
+            Regex::new(&format!(r"\A(?:{})\z", self.translated)).expect("pattern compiled before")
+        })
+    }
+
+    fn group_count(&self) -> usize {
+        self.regex.captures_len() - 1
+    }
+}
+
+impl Match {
+    /// Character offsets of group `i`.
+    pub fn span(&self, i: usize) -> Option<(i64, i64)> {
+        let (start, end) = self.spans.get(i).copied().flatten()?;
+        Some((char_index(&self.text, start), char_index(&self.text, end)))
+    }
+
+    pub fn group_str(&self, i: usize) -> Option<String> {
+        let (start, end) = self.spans.get(i).copied().flatten()?;
+        Some(self.text[start..end].to_string())
+    }
+
+    fn group_value(&self, i: usize) -> Value {
+        match self.group_str(i) {
+            Some(text) => Value::str(&text),
+            None => Value::None,
+        }
+    }
+}
+
+fn char_index(text: &str, byte: usize) -> i64 {
+    if text.is_ascii() {
+        byte as i64
+    } else {
+        text[..byte].chars().count() as i64
+    }
+}
+
+fn byte_offset(text: &str, chars: i64) -> usize {
+    let chars = chars.max(0) as usize;
+    if text.is_ascii() {
+        chars.min(text.len())
+    } else {
+        text.char_indices().nth(chars).map_or(text.len(), |(i, _)| i)
+    }
+}
+
+/// Compiled patterns by source and flags.
+type Cache = HashMap<(Rc<str>, i64), Rc<Pattern>>;
+
+thread_local! {
+    static CACHE: RefCell<Cache> = RefCell::new(HashMap::new());
+}
+
+/// Compile `source`, reusing an earlier compilation of the same pattern.
+pub fn compile(source: &Rc<str>, flags: i64) -> PyResult<Rc<Pattern>> {
+    let key = (source.clone(), flags);
+    if let Some(pattern) = CACHE.with(|cache| cache.borrow().get(&key).cloned()) {
+        return Ok(pattern);
+    }
+    let translated = match translate(source, flags) {
+        Ok(translated) => translated,
+        Err(msg) => return raise("re.error", msg),
+    };
+    let regex = match Regex::new(&translated) {
+        Ok(regex) => regex,
+        Err(e) => return raise("re.error", describe(&e)),
+    };
+    let pattern = Rc::new(Pattern {
+        source: source.clone(),
+        flags,
+        regex,
+        translated,
+        anchored: OnceCell::new(),
+        full: OnceCell::new(),
+    });
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= 512 {
+            cache.clear();
+        }
+        cache.insert(key, pattern.clone());
+    });
+    Ok(pattern)
+}
+
+/// The one-line summary of a `regex` syntax error.
+fn describe(err: &regex::Error) -> String {
+    let text = err.to_string();
+    text.lines()
+        .find_map(|line| line.strip_prefix("error: "))
+        .unwrap_or(&text)
+        .to_string()
+}
+
+/// Python regex syntax to the `regex` crate's.
+fn translate(source: &str, flags: i64) -> Result<String, String> {
+    let ascii = flags & ASCII != 0;
+    let mut out = String::with_capacity(source.len() + 8);
+    let inline: String = [
+        (IGNORECASE, 'i'),
+        (MULTILINE, 'm'),
+        (DOTALL, 's'),
+        (VERBOSE, 'x'),
+    ]
+    .iter()
+    .filter(|(flag, _)| flags & flag != 0)
+    .map(|(_, c)| *c)
+    .collect();
+    if !inline.is_empty() {
+        out.push_str(&format!("(?{inline})"));
+    }
+    let chars: Vec<char> = source.chars().collect();
+    let mut i = 0;
+    let mut in_class = false;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' => {
+                let Some(&next) = chars.get(i + 1) else {
+                    return Err(format!("bad escape (end of pattern) at position {i}"));
+                };
+                i += 2;
+                match next {
+                    'Z' => out.push_str(r"\z"),
+                    '<' | '>' => out.push(next),
+                    '1'..='9' if !in_class => {
+                        return Err(format!(
+                            "backreferences are not supported at position {}",
+                            i - 2
+                        ));
+                    }
+                    'w' | 'W' | 'd' | 'D' | 's' | 'S' if ascii => {
+                        let class = match next.to_ascii_lowercase() {
+                            'w' => "0-9A-Za-z_",
+                            'd' => "0-9",
+                            _ => r"\t\n\x0B\x0C\r ",
+                        };
+                        let negated = next.is_ascii_uppercase();
+                        match (in_class, negated) {
+                            (true, false) => out.push_str(class),
+                            (true, true) => out.push_str(&format!("\\{next}")),
+                            (false, false) => out.push_str(&format!("[{class}]")),
+                            (false, true) => out.push_str(&format!("[^{class}]")),
+                        }
+                    }
+                    'b' | 'B' if ascii && !in_class => out.push_str(&format!("(?-u:\\{next})")),
+                    'b' if in_class => out.push_str(r"\x08"),
+                    _ => {
+                        out.push('\\');
+                        out.push(next);
+                    }
+                }
+                continue;
+            }
+            '[' if in_class => out.push_str(r"\["),
+            '&' | '~' if in_class => {
+                out.push('\\');
+                out.push(c);
+            }
+            '[' => {
+                in_class = true;
+                out.push('[');
+                // A `]` first in the class is a literal
+                if chars.get(i + 1) == Some(&'^') {
+                    out.push('^');
+                    i += 1;
+                }
+                if chars.get(i + 1) == Some(&']') {
+                    out.push_str(r"\]");
+                    i += 1;
+                }
+            }
+            ']' if in_class => {
+                in_class = false;
+                out.push(']');
+            }
+            '(' if !in_class && chars.get(i + 1) == Some(&'?') => {
+                let rest: String = chars[i + 2..].iter().take(3).collect();
+                if rest.starts_with('=')
+                    || rest.starts_with('!')
+                    || rest.starts_with("<=")
+                    || rest.starts_with("<!")
+                {
+                    return Err(format!("look-around is not supported at position {i}"));
+                }
+                if rest.starts_with("P=") {
+                    return Err(format!(
+                        "backreferences are not supported at position {i}"
+                    ));
+                }
+                if rest.starts_with('#') {
+                    // A comment group
+                    while i < chars.len() && chars[i] != ')' {
+                        i += 1;
+                    }
+                    i += 1;
+                    continue;
+                }
+                if rest.starts_with('>') {
+                    return Err(format!("atomic groups are not supported at position {i}"));
+                }
+                // Inline flags: `a`, `L` and `u` have no equivalent
+                let flag_end = chars[i + 2..]
+                    .iter()
+                    .position(|c| !matches!(c, 'a' | 'i' | 'L' | 'm' | 's' | 'u' | 'x' | '-'));
+                if let Some(len) = flag_end.filter(|&len| len > 0) {
+                    let close = chars[i + 2 + len];
+                    if close == ')' || close == ':' {
+                        let kept: String = chars[i + 2..i + 2 + len]
+                            .iter()
+                            .filter(|c| !matches!(c, 'a' | 'L' | 'u'))
+                            .collect();
+                        if !kept.trim_end_matches('-').is_empty() {
+                            out.push_str(&format!("(?{kept}{close}"));
+                        } else if close == ':' {
+                            out.push_str("(?:");
+                        }
+                        i += 2 + len + 1;
+                        continue;
+                    }
+                }
+                out.push('(');
+            }
+            '{' if !in_class => match quantifier(&chars[i..]) {
+                Some((text, len)) => {
+                    out.push_str(&text);
+                    i += len;
+                    continue;
+                }
+                None => out.push_str(r"\{"),
+            },
+            '}' if !in_class => out.push_str(r"\}"),
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+    Ok(out)
+}
+
+/// A `{m,n}` quantifier at the start of `chars`: its translation and length.
+fn quantifier(chars: &[char]) -> Option<(String, usize)> {
+    let end = chars.iter().position(|&c| c == '}')?;
+    let body: String = chars[1..end].iter().collect();
+    let (min, max) = match body.split_once(',') {
+        Some((min, max)) => (min, Some(max)),
+        None => (body.as_str(), None),
+    };
+    let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if !digits(min) || !max.is_none_or(digits) || (min.is_empty() && max.is_none_or(str::is_empty)) {
+        return None;
+    }
+    let min = if min.is_empty() { "0" } else { min };
+    let text = match max {
+        Some(max) => format!("{{{min},{max}}}"),
+        None => format!("{{{min}}}"),
+    };
+    Some((text, end + 1))
+}
+
+// ---- Matching ----
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Search,
+    Match,
+    Full,
+}
+
+fn find(pattern: &Rc<Pattern>, text: &Rc<str>, pos: i64, endpos: Option<i64>, mode: Mode) -> Option<Match> {
+    let start = byte_offset(text, pos);
+    let end = endpos.map_or(text.len(), |e| byte_offset(text, e)).max(start);
+    let haystack = &text[..end];
+    let (captures, offset) = match mode {
+        Mode::Search => (pattern.regex.captures_at(haystack, start)?, 0),
+        Mode::Match => (pattern.anchored().captures(&haystack[start..])?, start),
+        Mode::Full => (pattern.full().captures(&haystack[start..])?, start),
+    };
+    let spans = (0..captures.len())
+        .map(|i| captures.get(i).map(|m| (m.start() + offset, m.end() + offset)))
+        .collect();
+    Some(Match {
+        text: text.clone(),
+        pattern: pattern.clone(),
+        spans,
+        pos: start,
+        endpos: end,
+    })
+}
+
+/// Every non-overlapping match in `text`.
+fn find_all(pattern: &Rc<Pattern>, text: &Rc<str>, pos: i64, endpos: Option<i64>) -> Vec<Match> {
+    let start = byte_offset(text, pos);
+    let end = endpos.map_or(text.len(), |e| byte_offset(text, e)).max(start);
+    let mut matches = Vec::new();
+    let mut at = start;
+    while at <= end {
+        let Some(captures) = pattern.regex.captures_at(&text[..end], at) else {
+            break;
+        };
+        let whole = captures.get(0).unwrap();
+        let spans: Vec<_> = (0..captures.len())
+            .map(|i| captures.get(i).map(|m| (m.start(), m.end())))
+            .collect();
+        at = if whole.end() == whole.start() {
+            // Step past an empty match
+            match text[whole.end()..].chars().next() {
+                Some(c) => whole.end() + c.len_utf8(),
+                None => end + 1,
+            }
+        } else {
+            whole.end()
+        };
+        matches.push(Match {
+            text: text.clone(),
+            pattern: pattern.clone(),
+            spans,
+            pos: start,
+            endpos: end,
+        });
+    }
+    matches
+}
+
+/// `findall`'s item for one match: the whole match, the only group, or
+/// a tuple of all groups.
+fn findall_item(m: &Match) -> Value {
+    let str_or_empty = |i: usize| Value::str(&m.group_str(i).unwrap_or_default());
+    match m.pattern.group_count() {
+        0 => str_or_empty(0),
+        1 => str_or_empty(1),
+        n => Value::tuple((1..=n).map(str_or_empty).collect()),
+    }
+}
+
+/// Expand a `sub` replacement template for match `m`.
+fn expand(template: &str, m: &Match) -> PyResult<String> {
+    let mut out = String::with_capacity(template.len());
+    let chars: Vec<char> = template.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c != '\\' || i + 1 == chars.len() {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        let next = chars[i + 1];
+        i += 2;
+        match next {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            'f' => out.push('\x0c'),
+            'v' => out.push('\x0b'),
+            'a' => out.push('\x07'),
+            '\\' => out.push('\\'),
+            '0'..='9' => {
+                let mut number = next.to_digit(10).unwrap() as usize;
+                if let Some(digit) = chars.get(i).and_then(|c| c.to_digit(10)) {
+                    number = number * 10 + digit as usize;
+                    i += 1;
+                }
+                if number >= m.spans.len() {
+                    return raise("re.error", format!("invalid group reference {number}"));
+                }
+                out.push_str(&m.group_str(number).unwrap_or_default());
+            }
+            'g' => {
+                let Some(close) = chars[i..].iter().position(|&c| c == '>') else {
+                    return raise("re.error", "missing >, unterminated name");
+                };
+                if chars.get(i) != Some(&'<') {
+                    return raise("re.error", "missing <");
+                }
+                let name: String = chars[i + 1..i + close].iter().collect();
+                i += close + 1;
+                let index = group_index(&m.pattern, &Value::str(&name))
+                    .ok_or_else(|| super::error::PyErr::new("IndexError", format!("unknown group name '{name}'")))?;
+                out.push_str(&m.group_str(index).unwrap_or_default());
+            }
+            c if c.is_ascii_alphabetic() => {
+                return raise("re.error", format!("bad escape \\{c} at position {}", i - 2));
+            }
+            c => {
+                out.push('\\');
+                out.push(c);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// The index of group `group`, given as a number or a name.
+fn group_index(pattern: &Pattern, group: &Value) -> Option<usize> {
+    match group {
+        Value::Int(n) if *n >= 0 && (*n as usize) <= pattern.group_count() => Some(*n as usize),
+        Value::Bool(b) => Some(*b as usize),
+        Value::Str(name) => match name.parse::<usize>() {
+            Ok(n) if n <= pattern.group_count() => Some(n),
+            _ => pattern
+                .regex
+                .capture_names()
+                .position(|n| n == Some(&**name)),
+        },
+        _ => None,
+    }
+}
+
+fn substitute(
+    interp: &mut Interp,
+    pattern: &Rc<Pattern>,
+    repl: &Value,
+    text: &Rc<str>,
+    count: i64,
+) -> PyResult<(String, i64)> {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut replaced = 0;
+    for m in find_all(pattern, text, 0, None) {
+        if count > 0 && replaced >= count {
+            break;
+        }
+        let (start, end) = m.spans[0].unwrap();
+        out.push_str(&text[last..start]);
+        last = end;
+        replaced += 1;
+        match repl {
+            Value::Str(template) => out.push_str(&expand(template, &m)?),
+            function => {
+                let result = interp.call(function, Args::new(vec![Value::Match(Rc::new(m))]))?;
+                match result {
+                    Value::Str(s) => out.push_str(&s),
+                    other => {
+                        return raise(
+                            "TypeError",
+                            format!("expected str instance, {} found", other.type_name()),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    out.push_str(&text[last..]);
+    Ok((out, replaced))
+}
+
+fn split(pattern: &Rc<Pattern>, text: &Rc<str>, maxsplit: i64) -> Value {
+    let mut parts = Vec::new();
+    let mut last = 0;
+    for (n, m) in find_all(pattern, text, 0, None).into_iter().enumerate() {
+        if maxsplit > 0 && n as i64 >= maxsplit {
+            break;
+        }
+        let (start, end) = m.spans[0].unwrap();
+        parts.push(Value::str(&text[last..start]));
+        for i in 1..m.spans.len() {
+            parts.push(m.group_value(i));
+        }
+        last = end;
+    }
+    parts.push(Value::str(&text[last..]));
+    Value::list(parts)
+}
+
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !c.is_alphanumeric() && c != '_' && c.is_ascii() {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// ---- Python interface ----
+
+fn text_arg(value: &Value, what: &str) -> PyResult<Rc<str>> {
+    match value {
+        Value::Str(s) => Ok(s.clone()),
+        other => raise(
+            "TypeError",
+            format!(
+                "expected string or bytes-like object, got '{}'{what}",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+fn pattern_arg(interp: &mut Interp, value: &Value, flags: Option<Value>) -> PyResult<Rc<Pattern>> {
+    let flags = match flags {
+        Some(flags) => index_value(interp, &flags)?,
+        None => 0,
+    };
+    match value {
+        Value::Pattern(pattern) => {
+            if flags != 0 {
+                return raise(
+                    "ValueError",
+                    "cannot process flags argument with a compiled pattern",
+                );
+            }
+            Ok(pattern.clone())
+        }
+        Value::Str(source) => compile(source, flags),
+        other => raise(
+            "TypeError",
+            format!(
+                "first argument must be string or compiled pattern, not {}",
+                other.type_name()
+            ),
+        ),
+    }
+}
+
+/// `re.<name>(...)`.
+pub fn call(interp: &mut Interp, name: &str, mut args: Args) -> PyResult<Value> {
+    match name {
+        "escape" => {
+            let [text] = exact::<1>("escape", args)?;
+            Ok(Value::str(&escape(&text_arg(&text, "")?)))
+        }
+        "purge" => {
+            CACHE.with(|cache| cache.borrow_mut().clear());
+            Ok(Value::None)
+        }
+        "compile" => {
+            let flags = args.arg(1, "flags");
+            let source = args.pos.first().cloned().unwrap_or(Value::None);
+            Ok(Value::Pattern(pattern_arg(interp, &source, flags)?))
+        }
+        _ => {
+            // The pattern and flags come first and last; what's between
+            // is the method's own arguments
+            let flags = args.take_kw("flags");
+            let arity = match name {
+                "sub" | "subn" => 4,
+                "split" => 3,
+                _ => 2,
+            };
+            let flags = match flags {
+                Some(flags) => Some(flags),
+                None if args.pos.len() > arity => args.pos.pop(),
+                None => None,
+            };
+            if args.pos.is_empty() {
+                return raise(
+                    "TypeError",
+                    format!("{name}() missing required argument 'pattern' (pos 1)"),
+                );
+            }
+            let source = args.pos.remove(0);
+            let pattern = pattern_arg(interp, &source, flags)?;
+            pattern_method(interp, &pattern, name, args)
+        }
+    }
+}
+
+fn pattern_method(interp: &mut Interp, pattern: &Rc<Pattern>, name: &str, mut args: Args) -> PyResult<Value> {
+    match name {
+        "search" | "match" | "fullmatch" | "findall" | "finditer" => {
+            let text = args.arg(0, "string").unwrap_or(Value::None);
+            let text = text_arg(&text, "")?;
+            let pos = match args.arg(1, "pos") {
+                Some(pos) => index_value(interp, &pos)?,
+                None => 0,
+            };
+            let endpos = match args.arg(2, "endpos") {
+                Some(endpos) => Some(index_value(interp, &endpos)?),
+                None => None,
+            };
+            let mode = match name {
+                "search" => Mode::Search,
+                "match" => Mode::Match,
+                "fullmatch" => Mode::Full,
+                "findall" => {
+                    let items = find_all(pattern, &text, pos, endpos)
+                        .iter()
+                        .map(findall_item)
+                        .collect();
+                    return Ok(Value::list(items));
+                }
+                _ => {
+                    let items = find_all(pattern, &text, pos, endpos)
+                        .into_iter()
+                        .map(|m| Value::Match(Rc::new(m)))
+                        .collect::<Vec<_>>();
+                    return Ok(Value::iterator(Iter::Values(items.into_iter())));
+                }
+            };
+            Ok(match find(pattern, &text, pos, endpos, mode) {
+                Some(m) => Value::Match(Rc::new(m)),
+                None => Value::None,
+            })
+        }
+        "sub" | "subn" => {
+            let repl = args.arg(0, "repl").unwrap_or(Value::None);
+            let text = args.arg(1, "string").unwrap_or(Value::None);
+            let text = text_arg(&text, "")?;
+            let count = match args.arg(2, "count") {
+                Some(count) => index_value(interp, &count)?,
+                None => 0,
+            };
+            let (result, replaced) = substitute(interp, pattern, &repl, &text, count)?;
+            Ok(if name == "sub" {
+                Value::str(&result)
+            } else {
+                Value::tuple(vec![Value::str(&result), Value::Int(replaced)])
+            })
+        }
+        "split" => {
+            let text = args.arg(0, "string").unwrap_or(Value::None);
+            let text = text_arg(&text, "")?;
+            let maxsplit = match args.arg(1, "maxsplit") {
+                Some(maxsplit) => index_value(interp, &maxsplit)?,
+                None => 0,
+            };
+            Ok(split(pattern, &text, maxsplit))
+        }
+        _ => raise(
+            "AttributeError",
+            format!("'re.Pattern' object has no attribute '{name}'"),
+        ),
+    }
+}
+
+/// Data attributes of patterns and matches.
+pub fn attribute(object: &Value, name: &str) -> Option<Value> {
+    match object {
+        Value::Pattern(pattern) => match name {
+            "pattern" => Some(Value::Str(pattern.source.clone())),
+            "flags" => Some(Value::Int(pattern.flags | UNICODE)),
+            "groups" => Some(Value::Int(pattern.group_count() as i64)),
+            "groupindex" => {
+                let mut dict = Dict::default();
+                for (i, name) in pattern.regex.capture_names().enumerate() {
+                    if let Some(name) = name {
+                        dict.set_str(name, Value::Int(i as i64));
+                    }
+                }
+                Some(Value::dict(dict))
+            }
+            _ => None,
+        },
+        Value::Match(m) => match name {
+            "string" => Some(Value::Str(m.text.clone())),
+            "re" => Some(Value::Pattern(m.pattern.clone())),
+            "pos" => Some(Value::Int(char_index(&m.text, m.pos))),
+            "endpos" => Some(Value::Int(char_index(&m.text, m.endpos))),
+            "lastindex" => Some(
+                (1..m.spans.len())
+                    .filter(|&i| m.spans[i].is_some())
+                    .max_by_key(|&i| m.spans[i].map(|(_, end)| end))
+                    .map_or(Value::None, |i| Value::Int(i as i64)),
+            ),
+            "lastgroup" => {
+                let last = (1..m.spans.len())
+                    .filter(|&i| m.spans[i].is_some())
+                    .max_by_key(|&i| m.spans[i].map(|(_, end)| end));
+                Some(
+                    last.and_then(|i| m.pattern.regex.capture_names().nth(i).flatten())
+                        .map_or(Value::None, Value::str),
+                )
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Methods of patterns and matches.
+pub fn method(interp: &mut Interp, receiver: &Value, name: &str, mut args: Args) -> PyResult<Value> {
+    let m = match receiver {
+        Value::Pattern(pattern) => return pattern_method(interp, pattern, name, args),
+        Value::Match(m) => m,
+        _ => unreachable!(),
+    };
+    let group = |group: &Value| -> PyResult<usize> {
+        match group_index(&m.pattern, group) {
+            Some(i) => Ok(i),
+            None => raise("IndexError", "no such group"),
+        }
+    };
+    match name {
+        "group" => {
+            if args.pos.is_empty() {
+                return Ok(m.group_value(0));
+            }
+            if args.pos.len() == 1 {
+                return Ok(m.group_value(group(&args.pos[0])?));
+            }
+            let mut values = Vec::new();
+            for arg in &args.pos {
+                values.push(m.group_value(group(arg)?));
+            }
+            Ok(Value::tuple(values))
+        }
+        "groups" => {
+            let default = args.arg(0, "default").unwrap_or(Value::None);
+            Ok(Value::tuple(
+                (1..m.spans.len())
+                    .map(|i| match m.group_str(i) {
+                        Some(text) => Value::str(&text),
+                        None => default.clone(),
+                    })
+                    .collect(),
+            ))
+        }
+        "groupdict" => {
+            let default = args.arg(0, "default").unwrap_or(Value::None);
+            let mut dict = Dict::default();
+            for (i, name) in m.pattern.regex.capture_names().enumerate() {
+                if let Some(name) = name {
+                    let value = match m.group_str(i) {
+                        Some(text) => Value::str(&text),
+                        None => default.clone(),
+                    };
+                    dict.set_str(name, value);
+                }
+            }
+            Ok(Value::dict(dict))
+        }
+        "start" | "end" | "span" => {
+            let index = match args.pos.first() {
+                Some(g) => group(g)?,
+                None => 0,
+            };
+            let (start, end) = m.span(index).unwrap_or((-1, -1));
+            Ok(match name {
+                "start" => Value::Int(start),
+                "end" => Value::Int(end),
+                _ => Value::tuple(vec![Value::Int(start), Value::Int(end)]),
+            })
+        }
+        "expand" => {
+            let [template] = exact::<1>("expand", args)?;
+            let template = text_arg(&template, "")?;
+            Ok(Value::str(&expand(&template, m)?))
+        }
+        _ => raise(
+            "AttributeError",
+            format!("'re.Match' object has no attribute '{name}'"),
+        ),
+    }
+}
@@ -0,0 +1,564 @@
+//! Runtime values and the objects behind them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufWriter;
+use std::rc::{Rc, Weak};
+
+use indexmap::IndexMap;
+
+use super::ast::FunctionDef;
+use super::re::{Match, Pattern};
+
+pub type Shared<T> = Rc<RefCell<T>>;
+
+pub fn shared<T>(value: T) -> Shared<T> {
+    Rc::new(RefCell::new(value))
+}
+
+#[derive(Clone)]
+pub enum Value {
+    None,
+    Bool(bool),
+    /// Integers are 64-bit; a result beyond that raises `NotImplementedError`.
+    Int(i64),
+    Float(f64),
+    Str(Rc<str>),
+    List(Shared<Vec<Value>>),
+    Tuple(Rc<[Value]>),
+    Dict(Shared<Dict>),
+    Set(Shared<Set>),
+    Range(i64, i64, i64),
+    Slice(Rc<[Value; 3]>),
+    Function(Rc<Function>),
+    /// A builtin function, by qualified name (`len`, `json.dumps`).
+    Builtin(&'static str),
+    /// A function bound to the object it was looked up on.
+    Method(Rc<(Value, Value)>),
+    /// A method of a builtin type bound to its receiver, e.g. `"a".upper`.
+    BuiltinMethod(Rc<(Value, Rc<str>)>),
+    Type(Type),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    Module(Rc<Module>),
+    Iterator(Shared<Iter>),
+    File(Shared<File>),
+    Pattern(Rc<Pattern>),
+    Match(Rc<Match>),
+    Property(Rc<Property>),
+    StaticMethod(Rc<Value>),
+    ClassMethod(Rc<Value>),
+    /// `super()`: the class to look past, and the object.
+    Super(Rc<(Rc<Class>, Value)>),
+    Ellipsis,
+    NotImplemented,
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::None => write!(f, "None"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Float(x) => write!(f, "{x}"),
+            Value::Str(s) => write!(f, "{s:?}"),
+            other => write!(f, "<{}>", other.type_name()),
+        }
+    }
+}
+
+impl Value {
+    pub fn str(s: &str) -> Value {
+        Value::Str(Rc::from(s))
+    }
+
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::List(shared(items))
+    }
+
+    pub fn tuple(items: Vec<Value>) -> Value {
+        Value::Tuple(Rc::from(items))
+    }
+
+    pub fn dict(dict: Dict) -> Value {
+        Value::Dict(shared(dict))
+    }
+
+    pub fn iterator(iter: Iter) -> Value {
+        Value::Iterator(shared(iter))
+    }
+
+    /// The builtin type of the value; user classes report `Object`.
+    pub fn type_of(&self) -> Type {
+        match self {
+            Value::None => Type::NoneType,
+            Value::Bool(_) => Type::Bool,
+            Value::Int(_) => Type::Int,
+            Value::Float(_) => Type::Float,
+            Value::Str(_) => Type::Str,
+            Value::List(_) => Type::List,
+            Value::Tuple(_) => Type::Tuple,
+            Value::Dict(d) => match d.borrow().kind {
+                DictKind::Dict => Type::Dict,
+                DictKind::Ordered => Type::OrderedDict,
+                DictKind::Counter => Type::Counter,
+                DictKind::Default(_) => Type::DefaultDict,
+            },
+            Value::Set(s) if s.borrow().frozen => Type::FrozenSet,
+            Value::Set(_) => Type::Set,
+            Value::Range(..) => Type::Range,
+            Value::Slice(_) => Type::Slice,
+            Value::Function(_) => Type::Function,
+            Value::Builtin(_) | Value::BuiltinMethod(_) => Type::BuiltinFunction,
+            Value::Method(_) => Type::Method,
+            Value::Type(_) | Value::Class(_) => Type::Type,
+            Value::Instance(_) => Type::Object,
+            Value::Module(_) => Type::Module,
+            Value::Iterator(_) => Type::Iterator,
+            Value::File(_) => Type::File,
+            Value::Pattern(_) => Type::Pattern,
+            Value::Match(_) => Type::Match,
+            Value::Property(_) => Type::Property,
+            Value::StaticMethod(_) => Type::StaticMethod,
+            Value::ClassMethod(_) => Type::ClassMethod,
+            Value::Super(_) => Type::Super,
+            Value::Ellipsis => Type::Ellipsis,
+            Value::NotImplemented => Type::NotImplemented,
+        }
+    }
+
+    /// The name `type(value).__name__` gives.
+    pub fn type_name(&self) -> Rc<str> {
+        match self {
+            Value::Instance(instance) => instance.class.name.clone(),
+            other => Rc::from(other.type_of().name()),
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self, Value::None)
+    }
+
+    /// Identity, as `is` compares.
+    pub fn is(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::None, Value::None)
+            | (Value::Ellipsis, Value::Ellipsis)
+            | (Value::NotImplemented, Value::NotImplemented) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            (Value::Str(a), Value::Str(b)) => Rc::ptr_eq(a, b) || (a.len() <= 1 && a == b),
+            (Value::Type(a), Value::Type(b)) => a == b,
+            (Value::Builtin(a), Value::Builtin(b)) => a == b,
+            _ => match (self.identity(), other.identity()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            },
+        }
+    }
+
+    /// Address of the object behind the value, for `is` and `id()`.
+    pub fn identity(&self) -> Option<usize> {
+        Some(match self {
+            Value::Str(s) => Rc::as_ptr(s) as *const u8 as usize,
+            Value::List(l) => Rc::as_ptr(l) as usize,
+            Value::Tuple(t) => Rc::as_ptr(t) as *const u8 as usize,
+            Value::Dict(d) => Rc::as_ptr(d) as usize,
+            Value::Set(s) => Rc::as_ptr(s) as usize,
+            Value::Slice(s) => Rc::as_ptr(s) as usize,
+            Value::Function(f) => Rc::as_ptr(f) as usize,
+            Value::Method(m) => Rc::as_ptr(m) as usize,
+            Value::BuiltinMethod(m) => Rc::as_ptr(m) as usize,
+            Value::Class(c) => Rc::as_ptr(c) as usize,
+            Value::Instance(i) => Rc::as_ptr(i) as usize,
+            Value::Module(m) => Rc::as_ptr(m) as usize,
+            Value::Iterator(i) => Rc::as_ptr(i) as usize,
+            Value::File(f) => Rc::as_ptr(f) as usize,
+            Value::Pattern(p) => Rc::as_ptr(p) as usize,
+            Value::Match(m) => Rc::as_ptr(m) as usize,
+            Value::Property(p) => Rc::as_ptr(p) as usize,
+            Value::StaticMethod(v) | Value::ClassMethod(v) => Rc::as_ptr(v) as usize,
+            Value::Super(s) => Rc::as_ptr(s) as usize,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Builtin types, as `type()` returns them and `isinstance` checks them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::enum_variant_names)]
+pub enum Type {
+    Object,
+    NoneType,
+    Bool,
+    Int,
+    Float,
+    Str,
+    List,
+    Tuple,
+    Dict,
+    Set,
+    FrozenSet,
+    Range,
+    Slice,
+    Type,
+    Function,
+    BuiltinFunction,
+    Method,
+    Module,
+    Iterator,
+    File,
+    Pattern,
+    Match,
+    Property,
+    StaticMethod,
+    ClassMethod,
+    Super,
+    Counter,
+    DefaultDict,
+    OrderedDict,
+    Ellipsis,
+    NotImplemented,
+}
+
+impl Type {
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Object => "object",
+            Type::NoneType => "NoneType",
+            Type::Bool => "bool",
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Str => "str",
+            Type::List => "list",
+            Type::Tuple => "tuple",
+            Type::Dict => "dict",
+            Type::Set => "set",
+            Type::FrozenSet => "frozenset",
+            Type::Range => "range",
+            Type::Slice => "slice",
+            Type::Type => "type",
+            Type::Function => "function",
+            Type::BuiltinFunction => "builtin_function_or_method",
+            Type::Method => "method",
+            Type::Module => "module",
+            Type::Iterator => "iterator",
+            Type::File => "TextIOWrapper",
+            Type::Pattern => "Pattern",
+            Type::Match => "Match",
+            Type::Property => "property",
+            Type::StaticMethod => "staticmethod",
+            Type::ClassMethod => "classmethod",
+            Type::Super => "super",
+            Type::Counter => "Counter",
+            Type::DefaultDict => "defaultdict",
+            Type::OrderedDict => "OrderedDict",
+            Type::Ellipsis => "ellipsis",
+            Type::NotImplemented => "NotImplementedType",
+        }
+    }
+
+    /// The module a type's `repr` names, for types outside `builtins`.
+    pub fn module(self) -> Option<&'static str> {
+        match self {
+            Type::Counter | Type::DefaultDict | Type::OrderedDict => Some("collections"),
+            Type::Pattern | Type::Match => Some("re"),
+            Type::File => Some("_io"),
+            _ => None,
+        }
+    }
+
+    /// Whether values of type `self` are instances of `other`.
+    pub fn is_subtype(self, other: Type) -> bool {
+        self == other
+            || other == Type::Object
+            || matches!(
+                (self, other),
+                (Type::Bool, Type::Int)
+                    | (Type::Counter | Type::DefaultDict | Type::OrderedDict, Type::Dict)
+            )
+    }
+}
+
+/// Hashable form of a value, used as the key of dicts and sets.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    None,
+    Int(i64),
+    /// Non-integral floats, by bit pattern.
+    Float(u64),
+    Str(Rc<str>),
+    Tuple(Rc<[Key]>),
+    Type(Type),
+    /// Objects hashed by identity.
+    Id(usize),
+    Ellipsis,
+}
+
+#[derive(Clone, Default)]
+pub struct Dict {
+    /// Entries by key, holding the original key value and the value.
+    pub map: IndexMap<Key, (Value, Value)>,
+    pub kind: DictKind,
+}
+
+impl Dict {
+    pub fn get(&self, key: &Key) -> Option<Value> {
+        self.map.get(key).map(|(_, v)| v.clone())
+    }
+
+    pub fn insert(&mut self, key: Key, key_value: Value, value: Value) {
+        match self.map.get_mut(&key) {
+            Some(entry) => entry.1 = value,
+            None => {
+                self.map.insert(key, (key_value, value));
+            }
+        }
+    }
+
+    /// Insert under a string key.
+    pub fn set_str(&mut self, key: &str, value: Value) {
+        let key: Rc<str> = Rc::from(key);
+        self.insert(Key::Str(key.clone()), Value::Str(key), value);
+    }
+
+    pub fn keys(&self) -> Vec<Value> {
+        self.map.values().map(|(k, _)| k.clone()).collect()
+    }
+
+    pub fn values(&self) -> Vec<Value> {
+        self.map.values().map(|(_, v)| v.clone()).collect()
+    }
+
+    pub fn items(&self) -> Vec<(Value, Value)> {
+        self.map.values().cloned().collect()
+    }
+}
+
+#[derive(Clone, Default)]
+pub enum DictKind {
+    #[default]
+    Dict,
+    Ordered,
+    Counter,
+    /// `defaultdict` and its default factory.
+    Default(Value),
+}
+
+#[derive(Clone, Default)]
+pub struct Set {
+    pub map: IndexMap<Key, Value>,
+    pub frozen: bool,
+}
+
+impl Set {
+    pub fn items(&self) -> Vec<Value> {
+        self.map.values().cloned().collect()
+    }
+}
+
+pub struct Function {
+    pub def: Rc<FunctionDef>,
+    /// Default values, one per parameter.
+    pub defaults: Vec<Option<Value>>,
+    /// The scope the function was defined in.
+    pub scope: Rc<Scope>,
+    /// File the function was defined in, for tracebacks.
+    pub file: Rc<str>,
+    /// The class whose body defined the function, for `super()`.
+    pub class: RefCell<Weak<Class>>,
+    /// Attributes set on the function object.
+    pub attrs: RefCell<HashMap<Rc<str>, Value>>,
+}
+
+impl Function {
+    /// `__qualname__`: prefixed with the class for methods.
+    pub fn qualname(&self) -> String {
+        match self.class.borrow().upgrade() {
+            Some(class) => format!("{}.{}", class.name, self.def.name),
+            None => self.def.name.to_string(),
+        }
+    }
+}
+
+pub struct Class {
+    pub name: Rc<str>,
+    pub bases: Vec<Rc<Class>>,
+    /// Ancestors in method resolution order, not including the class itself.
+    pub mro: Vec<Rc<Class>>,
+    pub attrs: RefCell<IndexMap<Rc<str>, Value>>,
+    pub module: Rc<str>,
+}
+
+impl Class {
+    /// Look an attribute up on the class and its ancestors.
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.attrs.borrow().get(name) {
+            return Some(value.clone());
+        }
+        self.mro
+            .iter()
+            .find_map(|class| class.attrs.borrow().get(name).cloned())
+    }
+
+    pub fn is_subclass(self: &Rc<Self>, other: &Rc<Class>) -> bool {
+        Rc::ptr_eq(self, other) || self.mro.iter().any(|c| Rc::ptr_eq(c, other))
+    }
+
+    pub fn has_ancestor(&self, name: &str) -> bool {
+        &*self.name == name || self.mro.iter().any(|c| &*c.name == name)
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub attrs: RefCell<IndexMap<Rc<str>, Value>>,
+}
+
+pub struct Module {
+    pub name: Rc<str>,
+    pub scope: Rc<Scope>,
+}
+
+impl Module {
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.scope.vars.borrow().get(name).cloned()
+    }
+
+    pub fn set(&self, name: &str, value: Value) {
+        self.scope.vars.borrow_mut().insert(Rc::from(name), value);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    Module,
+    Function,
+    Class,
+    /// The loop variables of a comprehension.
+    Comprehension,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Declared {
+    Global,
+    Nonlocal,
+}
+
+pub struct Scope {
+    pub vars: RefCell<IndexMap<Rc<str>, Value>>,
+    pub parent: Option<Rc<Scope>>,
+    pub kind: ScopeKind,
+    /// Names declared `global` or `nonlocal` in this scope.
+    pub declared: RefCell<HashMap<Rc<str>, Declared>>,
+    /// For a function call, the names its body binds.
+    pub function: Option<Rc<FunctionDef>>,
+}
+
+impl Scope {
+    pub fn new(kind: ScopeKind, parent: Option<Rc<Scope>>) -> Rc<Scope> {
+        Rc::new(Scope {
+            vars: RefCell::new(IndexMap::new()),
+            parent,
+            kind,
+            declared: RefCell::new(HashMap::new()),
+            function: None,
+        })
+    }
+
+    /// The local scope of a call to `def`.
+    pub fn call(def: Rc<FunctionDef>, parent: Rc<Scope>) -> Rc<Scope> {
+        Rc::new(Scope {
+            vars: RefCell::new(IndexMap::new()),
+            parent: Some(parent),
+            kind: ScopeKind::Function,
+            declared: RefCell::new(HashMap::new()),
+            function: Some(def),
+        })
+    }
+
+    /// The module scope at the root of the chain.
+    pub fn globals(self: &Rc<Self>) -> Rc<Scope> {
+        let mut scope = self.clone();
+        while let Some(parent) = scope.parent.clone() {
+            scope = parent;
+        }
+        scope
+    }
+}
+
+pub enum Iter {
+    List(Shared<Vec<Value>>, usize),
+    Tuple(Rc<[Value]>, usize),
+    Values(std::vec::IntoIter<Value>),
+    Range { next: i64, stop: i64, step: i64 },
+    Str(Rc<str>, usize),
+    Lines(Shared<File>),
+    /// `map(function, *iterators)`
+    Map(Value, Vec<Value>),
+    /// `filter(function, iterator)`; a `None` function keeps truthy items.
+    Filter(Value, Value),
+    Zip(Vec<Value>),
+    Enumerate(Value, i64),
+}
+
+pub struct File {
+    pub name: Rc<str>,
+    pub mode: Rc<str>,
+    pub handle: Handle,
+}
+
+pub enum Handle {
+    /// A file opened for reading is read whole when opened.
+    Read { text: String, pos: usize },
+    Write(BufWriter<std::fs::File>),
+    Stdin,
+    Stdout,
+    Stderr,
+    Closed,
+}
+
+pub struct Property {
+    pub fget: Value,
+    pub fset: Value,
+    pub fdel: Value,
+}
+
+/// A list of call arguments.
+#[derive(Default)]
+pub struct Args {
+    pub pos: Vec<Value>,
+    pub kw: Vec<(Rc<str>, Value)>,
+}
+
+impl Args {
+    pub fn new(pos: Vec<Value>) -> Args {
+        Args {
+            pos,
+            kw: Vec::new(),
+        }
+    }
+
+    /// Remove and return keyword argument `name`.
+    pub fn take_kw(&mut self, name: &str) -> Option<Value> {
+        let i = self.kw.iter().position(|(k, _)| &**k == name)?;
+        Some(self.kw.remove(i).1)
+    }
+
+    /// Positional argument `i`, or keyword argument `name` in its place.
+    pub fn arg(&mut self, i: usize, name: &str) -> Option<Value> {
+        match self.take_kw(name) {
+            Some(value) => Some(value),
+            None => self.pos.get(i).cloned(),
+        }
+    }
+}
@@ -6,6 +6,7 @@ pub fn run(args: &[String]) -> i32 {
     let mut force = false;
     let mut paths = Vec::new();
 
+    let (args, operands) = super::split_at_operands(args);
     for arg in args {
         match arg.as_str() {
             "-r" | "-R" => recursive = true,
@@ -29,6 +30,7 @@ pub fn run(args: &[String]) -> i32 {
             _ => paths.push(arg.clone()),
         }
     }
+    paths.extend_from_slice(operands);
 
     if paths.is_empty() {
         if !force {
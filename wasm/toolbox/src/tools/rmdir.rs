@@ -4,12 +4,14 @@ pub fn run(args: &[String]) -> i32 {
     let mut parents = false;
     let mut dirs = Vec::new();
 
+    let (args, operands) = super::split_at_operands(args);
     for arg in args {
         match arg.as_str() {
             "-p" | "--parents" => parents = true,
             _ => dirs.push(arg.as_str()),
         }
     }
+    dirs.extend(operands.iter().map(String::as_str));
 
     if dirs.is_empty() {
         eprintln!("rmdir: missing operand");
@@ -1,5 +1,5 @@
 use std::fs;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, BufWriter, Write};
 
 pub fn run(args: &[String]) -> i32 {
     let mut reverse = false;
@@ -84,9 +84,19 @@ pub fn run(args: &[String]) -> i32 {
         lines.dedup();
     }
 
-    for line in &lines {
-        println!("{}", line);
+    // Written in one go, so a reader that stops early (`sort | head -1`)
+    // still sees the output finished
+    let mut out = BufWriter::new(io::stdout().lock());
+    let written = lines
+        .iter()
+        .try_for_each(|line| writeln!(out, "{}", line))
+        .and_then(|()| out.flush());
+    match written {
+        Ok(()) => 0,
+        Err(e) if super::is_closed_pipe(&e) => 141,
+        Err(e) => {
+            eprintln!("sort: {}", e);
+            1
+        }
     }
-
-    0
 }
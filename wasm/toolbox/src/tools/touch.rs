@@ -6,11 +6,10 @@ pub fn run(args: &[String]) -> i32 {
         return 1;
     }
 
+    let (args, operands) = super::split_at_operands(args);
+    let files = args.iter().filter(|arg| !arg.starts_with('-'));
     let mut exit_code = 0;
-    for file in args {
-        if file.starts_with('-') {
-            continue;
-        }
+    for file in files.chain(operands) {
         if let Err(e) = fs::OpenOptions::new()
             .create(true)
             .truncate(false)
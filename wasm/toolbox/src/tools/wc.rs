@@ -43,6 +43,14 @@ pub fn run(args: &[String]) -> i32 {
         files.push("-".to_string());
     }
 
+    // Like GNU wc, a lone count of a single input is printed unpadded, so
+    // `$(wc -l < file)` is just the number
+    let columns = [count_lines, count_words, count_bytes || count_chars]
+        .iter()
+        .filter(|&&shown| shown)
+        .count();
+    let width = if files.len() == 1 && columns == 1 { 0 } else { 8 };
+
     for file in &files {
         let (lines, words, bytes) = if file == "-" {
             count_stdin()
@@ -69,6 +77,7 @@ pub fn run(args: &[String]) -> i32 {
             count_words,
             count_bytes,
             count_chars,
+            width,
         );
         if file != "-" {
             print!(" {}", file);
@@ -86,6 +95,7 @@ pub fn run(args: &[String]) -> i32 {
             count_words,
             count_bytes,
             count_chars,
+            width,
         );
         println!(" total");
     }
@@ -126,14 +136,15 @@ fn print_counts(
     count_words: bool,
     count_bytes: bool,
     count_chars: bool,
+    width: usize,
 ) {
     if show_all || count_lines {
-        print!("{:>8}", lines);
+        print!("{:>width$}", lines);
     }
     if show_all || count_words {
-        print!("{:>8}", words);
+        print!("{:>width$}", words);
     }
     if show_all || count_bytes || count_chars {
-        print!("{:>8}", bytes);
+        print!("{:>width$}", bytes);
     }
 }
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+
+use crate::fetch;
+use crate::tools::curl::{format_headers, remote_file_name};
+
+pub fn run(args: &[String]) -> i32 {
+    let mut quiet = false;
+    let mut server_response = false;
+    let mut output: Option<String> = None;
+    let mut method: Option<String> = None;
+    let mut body: Option<Vec<u8>> = None;
+    let mut headers: HashMap<String, String> = HashMap::new();
+    let mut urls = Vec::new();
+
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        // Accept both `--opt=value` and `--opt value`
+        let (name, inline) = match arg.split_once('=') {
+            Some((n, v)) if n.starts_with("--") => (n, Some(v.to_string())),
+            _ => (arg, None),
+        };
+        let mut value = || -> Option<String> {
+            if inline.is_some() {
+                return inline.clone();
+            }
+            i += 1;
+            args.get(i).cloned()
+        };
+
+        match name {
+            "-q" | "--quiet" => quiet = true,
+            "-S" | "--server-response" => server_response = true,
+            "-O" | "--output-document" => output = value(),
+            "--header" => {
+                if let Some(h) = value()
+                    && let Some((k, v)) = h.split_once(':')
+                {
+                    headers.insert(k.trim().to_string(), v.trim().to_string());
+                }
+            }
+            "-U" | "--user-agent" => {
+                if let Some(ua) = value() {
+                    headers.insert("User-Agent".to_string(), ua);
+                }
+            }
+            "--method" => method = value(),
+            "--post-data" | "--body-data" => {
+                body = value().map(|v| v.into_bytes());
+                if name == "--post-data" && method.is_none() {
+                    method = Some("POST".to_string());
+                }
+            }
+            "--post-file" | "--body-file" => {
+                let Some(file) = value() else { break };
+                match fs::read(&file) {
+                    Ok(bytes) => body = Some(bytes),
+                    Err(e) => {
+                        eprintln!("wget: {file}: {e}");
+                        return 1;
+                    }
+                }
+                if name == "--post-file" && method.is_none() {
+                    method = Some("POST".to_string());
+                }
+            }
+            "-T" | "--timeout" | "-t" | "--tries" | "--max-redirect" => {
+                value();
+            }
+            _ if name.starts_with('-') && name.len() > 1 => {
+                // Unsupported flag (e.g. --no-check-certificate) — ignore
+            }
+            _ => urls.push(arg.to_string()),
+        }
+        i += 1;
+    }
+
+    if urls.is_empty() {
+        eprintln!("wget: missing URL");
+        return 1;
+    }
+
+    if body.is_some()
+        && !headers
+            .keys()
+            .any(|k| k.eq_ignore_ascii_case("content-type"))
+    {
+        headers.insert(
+            "Content-Type".to_string(),
+            "application/x-www-form-urlencoded".to_string(),
+        );
+    }
+    let method = method.unwrap_or_else(|| "GET".to_string());
+
+    let mut exit_code = 0;
+    // With -O, all documents are concatenated into the one output
+    let mut combined: Option<Vec<u8>> = output.as_ref().map(|_| Vec::new());
+
+    for url in &urls {
+        let resp = match fetch::fetch_bytes(url, &method, &headers, body.as_deref()) {
+            Ok(resp) => resp,
+            Err(e) => {
+                eprintln!("wget: {url}: {e}");
+                exit_code = 4;
+                continue;
+            }
+        };
+
+        if server_response {
+            for line in format_headers(&resp).lines() {
+                if !line.is_empty() {
+                    eprintln!("  {line}");
+                }
+            }
+        }
+
+        if resp.status >= 400 {
+            if !quiet {
+                eprintln!("wget: {url}: server returned error: HTTP {}", resp.status);
+            }
+            exit_code = 8;
+            continue;
+        }
+
+        match combined.as_mut() {
+            Some(buf) => buf.extend_from_slice(&resp.body),
+            None => {
+                let file = remote_file_name(url).unwrap_or_else(|| "index.html".to_string());
+                if let Err(e) = fs::write(&file, &resp.body) {
+                    eprintln!("wget: {file}: {e}");
+                    exit_code = 3;
+                    continue;
+                }
+                if !quiet {
+                    eprintln!("Saving to: '{file}'");
+                }
+            }
+        }
+    }
+
+    if let (Some(target), Some(buf)) = (output, combined) {
+        let result = if target == "-" {
+            io::stdout().write_all(&buf)
+        } else {
+            if !quiet {
+                eprintln!("Saving to: '{target}'");
+            }
+            fs::write(&target, &buf)
+        };
+        if let Err(e) = result {
+            eprintln!("wget: {target}: {e}");
+            return 3;
+        }
+    }
+
+    exit_code
+}
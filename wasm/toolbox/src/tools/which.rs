@@ -15,7 +15,8 @@ pub fn run(args: &[String]) -> i32 {
     exit_code
 }
 
-fn is_known_command(cmd: &str) -> bool {
+/// Whether `cmd` is a command the toolbox provides.
+pub fn is_known_command(cmd: &str) -> bool {
     // List all commands available in the toolbox
     matches!(
         cmd,
@@ -53,6 +54,9 @@ fn is_known_command(cmd: &str) -> bool {
             | "zip"
             | "git"
             | "node"
+            | "curl"
+            | "wget"
+            | "hostcall"
             | "echo"
             | "printf"
             | "env"
@@ -86,6 +90,7 @@ fn is_known_command(cmd: &str) -> bool {
             | "file"
             | "date"
             | "expr"
+            | "yes"
             | "join"
             | "strings"
             | "true"
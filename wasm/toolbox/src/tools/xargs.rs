@@ -1,12 +1,12 @@
-use std::io::{self, BufRead};
+use std::io::{self, Read};
 
 pub fn run(args: &[String]) -> i32 {
-    // In WASM context, xargs just reads stdin lines and prints them as args
-    // Real execution would need to be handled by the sandbox runtime
     let mut delimiter = None;
+    let mut null_delimited = false;
     let mut max_args: Option<usize> = None;
-    let mut cmd_args = Vec::new();
+    let mut replace: Option<String> = None;
 
+    // Options come first; everything from the first operand on is the command
     let mut i = 0;
     while i < args.len() {
         match args[i].as_str() {
@@ -22,48 +22,85 @@ pub fn run(args: &[String]) -> i32 {
                     max_args = args[i].parse().ok();
                 }
             }
-            _ => cmd_args.push(args[i].clone()),
+            "-I" => {
+                i += 1;
+                if i < args.len() {
+                    replace = Some(args[i].clone());
+                }
+            }
+            "-0" | "--null" => null_delimited = true,
+            "-r" | "--no-run-if-empty" | "-t" => {}
+            _ => break,
         }
         i += 1;
     }
+    let cmd_args: Vec<String> = args[i..].to_vec();
 
-    let stdin = io::stdin();
-    let mut items = Vec::new();
+    let mut input = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut input) {
+        eprintln!("xargs: {e}");
+        return 1;
+    }
 
-    for line in stdin.lock().lines() {
-        let line = match line {
-            Ok(l) => l,
-            Err(_) => break,
-        };
+    let items: Vec<String> = if null_delimited {
+        input
+            .split('\0')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect()
+    } else if let Some(delim) = delimiter {
+        input
+            .split(delim)
+            .map(|s| s.trim_end_matches('\n'))
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect()
+    } else if replace.is_some() {
+        // -I takes whole lines
+        input
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect()
+    } else {
+        input.split_whitespace().map(|s| s.to_string()).collect()
+    };
 
-        if let Some(delim) = delimiter {
-            for part in line.split(delim) {
-                let trimmed = part.trim();
-                if !trimmed.is_empty() {
-                    items.push(trimmed.to_string());
-                }
-            }
-        } else {
-            for part in line.split_whitespace() {
-                items.push(part.to_string());
-            }
+    let (cmd, base_args) = match cmd_args.split_first() {
+        Some((cmd, rest)) => (cmd.clone(), rest.to_vec()),
+        None => ("echo".to_string(), Vec::new()),
+    };
+
+    let mut invocations: Vec<Vec<String>> = Vec::new();
+    if let Some(placeholder) = &replace {
+        for item in &items {
+            invocations.push(
+                base_args
+                    .iter()
+                    .map(|a| a.replace(placeholder.as_str(), item))
+                    .collect(),
+            );
+        }
+    } else if let Some(n) = max_args.filter(|n| *n > 0) {
+        for chunk in items.chunks(n) {
+            let mut argv = base_args.clone();
+            argv.extend_from_slice(chunk);
+            invocations.push(argv);
         }
+    } else if !items.is_empty() {
+        let mut argv = base_args.clone();
+        argv.extend(items);
+        invocations.push(argv);
     }
 
-    // In the sandbox, we just output what would be executed
-    if let Some(n) = max_args {
-        for chunk in items.chunks(n) {
-            if cmd_args.is_empty() {
-                println!("echo {}", chunk.join(" "));
-            } else {
-                println!("{} {}", cmd_args.join(" "), chunk.join(" "));
-            }
+    let mut exit_code = 0;
+    for argv in invocations {
+        if crate::dispatch(&cmd, &argv) != 0 {
+            // GNU xargs reports any failed invocation as 123
+            exit_code = 123;
         }
-    } else if cmd_args.is_empty() {
-        println!("echo {}", items.join(" "));
-    } else {
-        println!("{} {}", cmd_args.join(" "), items.join(" "));
     }
 
-    0
+    exit_code
 }
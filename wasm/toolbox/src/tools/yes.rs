@@ -0,0 +1,23 @@
+use std::io::{self, Write};
+
+pub fn run(args: &[String]) -> i32 {
+    let line = if args.is_empty() {
+        "y".to_string()
+    } else {
+        args.join(" ")
+    };
+
+    // Write in large chunks; the loop ends when the reader goes away
+    let mut chunk = String::new();
+    while chunk.len() < 8192 {
+        chunk.push_str(&line);
+        chunk.push('\n');
+    }
+
+    let mut out = io::stdout().lock();
+    loop {
+        if out.write_all(chunk.as_bytes()).is_err() {
+            return 141;
+        }
+    }
+}